@@ -0,0 +1,156 @@
+// autostart.rs — spawning exec / exec_once entries, and restarting hung ones
+//
+// `exec_once = setup.sh --timeout 5000` gives the entry a deadline. A timer
+// armed at spawn checks whether the process has exited by then; if not it
+// gets SIGTERM, then SIGKILL after `TERM_GRACE` if that didn't take, and is
+// spawned again. Meant for scripts that should finish — a long-running bar
+// or daemon must not be given a timeout. Entries without one are spawned and
+// forgotten, as before.
+
+use std::{
+    process::{Child, Command, ExitStatus},
+    time::Duration,
+};
+
+use smithay::reexports::calloop::{
+    timer::{TimeoutAction, Timer},
+    LoopHandle,
+};
+
+use crate::config::ExecEntry;
+use crate::state::KittyCompositor;
+
+/// Time a hung process gets between SIGTERM and SIGKILL.
+pub const TERM_GRACE: Duration = Duration::from_secs(1);
+/// Restarts of one entry before it is left dead.
+const MAX_RESTARTS: u32 = 3;
+
+/// `env` is the config's `env { }` block.
+pub fn spawn(entry: &ExecEntry, env: &[(String, String)], wayland_socket: &str) -> Option<Child> {
+    let bin = crate::util::expand_tilde(&entry.command);
+    tracing::info!("exec: {bin} {:?}", entry.args);
+    Command::new(&bin)
+        .args(&entry.args)
+        .env("WAYLAND_DISPLAY", wayland_socket)
+        .envs(env.iter().map(|(k, v)| (k, v)))
+        .spawn()
+        .map_err(|e| tracing::warn!("exec failed ({bin}): {e}"))
+        .ok()
+}
+
+/// Arm `entry`'s timeout for `child`; does nothing for entries without one.
+pub fn watch(
+    handle: &LoopHandle<'static, KittyCompositor>,
+    entry: ExecEntry,
+    child: Child,
+    restarts: u32,
+) {
+    let Some(ms) = entry.autostart_timeout_ms else {
+        return;
+    };
+    let mut hung = HungWatch::new(child);
+    handle
+        .insert_source(
+            Timer::from_duration(Duration::from_millis(ms)),
+            move |_, _, state| match hung.tick() {
+                Tick::Exited => TimeoutAction::Drop,
+                Tick::Again(d) => TimeoutAction::ToDuration(d),
+                Tick::Killed(_) => {
+                    tracing::warn!("Killed hung autostart: {}", entry.command);
+                    if restarts >= MAX_RESTARTS {
+                        tracing::warn!(
+                            "autostart {}: hung {} times, not restarting",
+                            entry.command,
+                            restarts + 1
+                        );
+                    } else if let Some(child) =
+                        spawn(&entry, &state.config.global_env, &state.wayland_socket)
+                    {
+                        watch(&state.handle, entry.clone(), child, restarts + 1);
+                    }
+                    TimeoutAction::Drop
+                }
+            },
+        )
+        .ok();
+}
+
+#[derive(Debug)]
+pub enum Tick {
+    /// Exited on its own before the deadline.
+    Exited,
+    /// SIGTERM sent; call `tick` again after this long.
+    Again(Duration),
+    /// Gone after being signalled, with its exit status if it was reaped.
+    Killed(Option<ExitStatus>),
+}
+
+/// The term-then-kill sequence for one process, advanced by `tick` each time
+/// its timer fires.
+pub struct HungWatch {
+    child: Child,
+    terminating: bool,
+}
+
+impl HungWatch {
+    pub fn new(child: Child) -> Self {
+        Self {
+            child,
+            terminating: false,
+        }
+    }
+
+    pub fn tick(&mut self) -> Tick {
+        let exited = !matches!(self.child.try_wait(), Ok(None));
+        if !self.terminating {
+            if exited {
+                return Tick::Exited;
+            }
+            self.terminating = true;
+            // SAFETY: plain kill(2) on our own child's pid.
+            unsafe { libc::kill(self.child.id() as libc::pid_t, libc::SIGTERM) };
+            return Tick::Again(TERM_GRACE);
+        }
+        if !exited {
+            let _ = self.child.kill();
+        }
+        Tick::Killed(self.child.wait().ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::process::ExitStatusExt;
+    use std::time::Instant;
+
+    #[test]
+    fn sigterm_ignored_gets_sigkill_after_grace() {
+        let timeout = Duration::from_millis(200);
+        let child = Command::new("sh")
+            .args(["-c", "trap '' TERM; exec sleep 30"])
+            .spawn()
+            .unwrap();
+        let started = Instant::now();
+        let mut hung = HungWatch::new(child);
+
+        std::thread::sleep(timeout);
+        let Tick::Again(grace) = hung.tick() else {
+            panic!("process should still be running at its deadline");
+        };
+        std::thread::sleep(grace);
+        let Tick::Killed(Some(status)) = hung.tick() else {
+            panic!("process ignoring SIGTERM should be killed");
+        };
+        assert_eq!(status.signal(), Some(libc::SIGKILL));
+        assert!(started.elapsed() >= timeout + TERM_GRACE);
+    }
+
+    #[test]
+    fn exited_before_deadline_is_left_alone() {
+        let child = Command::new("true").spawn().unwrap();
+        let mut hung = HungWatch::new(child);
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(matches!(hung.tick(), Tick::Exited));
+    }
+}
@@ -13,26 +13,35 @@ use smithay::{
         },
         drm::{
             compositor::DrmCompositor, exporter::gbm::GbmFramebufferExporter, DrmDevice,
-            DrmDeviceFd, DrmEvent, DrmNode,
+            DrmDeviceFd, DrmEvent, DrmEventMetadata, DrmEventTime, DrmNode, VrrSupport,
         },
         egl::{EGLContext, EGLDisplay},
         renderer::{damage::OutputDamageTracker, gles::GlesRenderer, Bind, ImportMemWl},
         session::Session,
     },
-    output::{Mode, Output, PhysicalProperties, Subpixel},
+    output::{Mode, Output, PhysicalProperties, Scale, Subpixel},
     reexports::{
         calloop::timer::{TimeoutAction, Timer},
-        drm::control::{connector, crtc, Device as DrmControlDevice, ModeTypeFlags},
+        drm::control::{
+            connector, crtc, plane, property, Device as DrmControlDevice, ModeFlags, ModeTypeFlags,
+            ResourceHandle,
+        },
         wayland_server::DisplayHandle,
     },
     utils::{DeviceFd, Size, Transform},
     wayland::output::OutputManagerState,
 };
 
-use crate::state::{BackendData, KittyCompositor, SurfaceData};
+use crate::config::{OutputConfig, OutputMode};
+use crate::cursor::HardwareCursor;
+use crate::output_management::{pick_mode, HeadInfo, ModeInfo};
+use crate::state::{
+    schedule_render, BackendData, FrameClock, GbmDrmCompositor, KittyCompositor, RecoveryTracker,
+    SurfaceData,
+};
 
 // Load the gl crate's function pointer table.
-// Called once per EGL context creation in add_gpu().
+// Called once per EGL context creation, see `load_gl`.
 use gl;
 
 pub fn add_gpu(
@@ -57,20 +66,12 @@ pub fn add_gpu(
         tracing::warn!("EGL bind_wl_display failed (hw-accel unavailable): {e}");
     }
 
-    // ── Load GL function pointers ─────────────────────────────────────────
-    // The `gl` crate requires explicit loading after the EGL context is
-    // current. GlesRenderer::new() makes it current, so we load here.
-    // Safe to call multiple times — just overwrites pointers with same values.
-    gl::load_with(|s| {
-        let sym = std::ffi::CString::new(s).unwrap();
-        unsafe { smithay::backend::egl::ffi::egl::GetProcAddress(sym.as_ptr()) as *const _ }
-    });
+    load_gl();
 
     // ── PixelUI init ──────────────────────────────────────────────────────
     // Must happen while this EGL context is current (i.e. right here).
-    // The guard prevents double-init on multi-GPU systems — cell_size()
-    // returns the default (8,16) until install_renderer() is called.
-    if crate::pixelui::overlay_element::cell_size() == (8, 16) {
+    // The guard prevents double-init on multi-GPU systems.
+    if !crate::pixelui::overlay_element::is_installed() {
         tracing::info!(
             "init_pixel_ui: starting, font path = {:?}",
             state.config.font.path
@@ -93,9 +94,16 @@ pub fn add_gpu(
 
     state
         .handle
-        .insert_source(drm_notifier, move |event, _, state| {
+        .insert_source(drm_notifier, move |event, metadata, state| {
             if let DrmEvent::VBlank(crtc) = event {
-                state.frame_finish(node, crtc);
+                let time = match metadata {
+                    Some(DrmEventMetadata {
+                        time: DrmEventTime::Monotonic(time),
+                        ..
+                    }) => Some(*time),
+                    _ => None,
+                };
+                state.frame_finish(node, crtc, time);
             }
         })
         .unwrap();
@@ -103,6 +111,7 @@ pub fn add_gpu(
     let mut backend = BackendData {
         surfaces: Default::default(),
         renderer,
+        context_lost: false,
         gbm,
         drm,
         drm_node: node,
@@ -117,46 +126,143 @@ pub fn add_gpu(
         .collect();
 
     for connector in connectors {
-        let mode = connector
-            .modes()
-            .iter()
-            .find(|m| m.mode_type().contains(ModeTypeFlags::PREFERRED))
-            .or_else(|| connector.modes().first())
-            .copied();
-        let Some(mode) = mode else { continue };
-        let crtc = res_handles
-            .crtcs()
-            .iter()
-            .copied()
-            .find(|&c| !backend.surfaces.contains_key(&c));
-        let Some(crtc) = crtc else { continue };
-        if let Err(e) = add_output(
-            state,
-            dh,
-            &mut backend,
-            node,
-            connector.handle(),
-            crtc,
-            mode,
-        ) {
+        if let Err(e) = setup_connector(state, dh, &mut backend, node, &connector) {
             tracing::warn!("Failed to set up connector: {e}");
         }
     }
 
     state.backends.insert(node, backend);
+    state.refresh_output_heads();
     Ok(())
 }
 
+// ── Load GL function pointers ─────────────────────────────────────────────
+// The `gl` crate requires explicit loading after the EGL context is
+// current. GlesRenderer::new() makes it current, so we load right after it.
+// Safe to call multiple times — just overwrites pointers with same values.
+fn load_gl() {
+    gl::load_with(|s| {
+        let sym = std::ffi::CString::new(s).unwrap();
+        unsafe { smithay::backend::egl::ffi::egl::GetProcAddress(sym.as_ptr()) as *const _ }
+    });
+}
+
+/// Replace `backend`'s renderer with one on a fresh EGL context, after the
+/// old one was lost. Everything created in the old context goes with it:
+/// imported client buffers are imported again on their next frame, the
+/// PixelUI objects need `reload_pixel_ui`.
+pub fn reset_renderer(backend: &mut BackendData) -> Result<(), Box<dyn std::error::Error>> {
+    let egl = unsafe { EGLDisplay::new(backend.gbm.clone())? };
+    let ctx = EGLContext::new(&egl)?;
+    backend.renderer = unsafe { GlesRenderer::new(ctx)? };
+    load_gl();
+    backend.context_lost = false;
+    Ok(())
+}
+
+/// Drive `connector` from the first free CRTC in its preferred mode, or the
+/// mode its `output` block asks for.
+fn setup_connector(
+    state: &mut KittyCompositor,
+    dh: &DisplayHandle,
+    backend: &mut BackendData,
+    node: DrmNode,
+    connector: &connector::Info,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mode = connector
+        .modes()
+        .iter()
+        .find(|m| m.mode_type().contains(ModeTypeFlags::PREFERRED))
+        .or_else(|| connector.modes().first())
+        .copied()
+        .ok_or("no modes")?;
+    let crtc = backend
+        .drm
+        .resource_handles()?
+        .crtcs()
+        .iter()
+        .copied()
+        .find(|&c| !backend.surfaces.contains_key(&c))
+        .ok_or("no free CRTC")?;
+    add_output(state, dh, backend, node, connector.handle(), crtc, mode)
+}
+
 // ── PixelUI / UiRenderer initialisation ──────────────────────────────────────
 
+/// Rebuild the glyph atlas and UiRenderer after a font change on reload.
+/// Uses the first GPU's EGL context; a no-op before any GPU was added.
+pub fn reload_pixel_ui(state: &KittyCompositor) {
+    let Some(backend) = state.backends.values().next() else {
+        return;
+    };
+    if let Err(e) = unsafe { backend.renderer.egl_context().make_current() } {
+        tracing::warn!("reload_pixel_ui: could not make EGL context current: {e}");
+        return;
+    }
+    init_pixel_ui(state);
+}
+
 fn init_pixel_ui(state: &KittyCompositor) {
     tracing::info!("init_pixel_ui entered");
-    use crate::font::GlyphAtlas;
     use crate::pixelui::overlay_element;
+
+    let Some(ui_renderer) = build_ui_renderer(&state.config.font, state.config.font.size) else {
+        return;
+    };
+    let (cw, ch) = (ui_renderer.cell_w, ui_renderer.cell_h);
+    overlay_element::install_renderer(ui_renderer);
+    tracing::info!("PixelUI installed — cell {cw}×{ch}px");
+
+    // Installing dropped the DPI-sized renderers; rebuild them on reload.
+    for output in state.space.outputs() {
+        scale_pixel_ui(&state.config, output);
+    }
+}
+
+/// Give `output` a renderer for `font_size_pt` at its physical DPI, shared
+/// with every output whose DPI rounds the same. Outputs that report no
+/// physical size keep the startup renderer (`font.size` pixels).
+/// An EGL context must be current.
+fn scale_pixel_ui(config: &crate::config::Config, output: &Output) {
+    use crate::pixelui::overlay_element;
+
+    let name = output.name();
+    let dpi = output.current_mode().and_then(|mode| {
+        let mm = output.physical_properties().size;
+        crate::font::output_dpi(mode.size.w as u32, mm.w as u32)
+    });
+    let Some(dpi) = dpi.filter(|_| overlay_element::is_installed()) else {
+        overlay_element::set_output_atlas(&name, None);
+        return;
+    };
+    let key = overlay_element::atlas_key(config.font_size_pt, dpi);
+    if !overlay_element::has_scaled(key) {
+        let size_px = crate::font::font_size_px(config.font_size_pt, key.1 as f32);
+        let Some(r) = build_ui_renderer(&config.font, size_px) else {
+            overlay_element::set_output_atlas(&name, None);
+            return;
+        };
+        tracing::info!(
+            "PixelUI: {} dpi atlas at {size_px:.1}px — cell {}×{}px",
+            key.1,
+            r.cell_w,
+            r.cell_h
+        );
+        overlay_element::install_scaled(key, r);
+    }
+    overlay_element::set_output_atlas(&name, Some(key));
+}
+
+/// Load the configured fonts at `size_px` and build a UiRenderer around
+/// them. Logs and returns `None` when a font or the GL setup fails.
+fn build_ui_renderer(
+    font_cfg: &crate::config::FontConfig,
+    size_px: f32,
+) -> Option<crate::pixelui::UiRenderer> {
+    use crate::font::{AtlasOptions, FontAxes, GlyphAtlas};
     use crate::pixelui::UiRenderer;
-    use crate::shaper::Shaper;
+    use crate::shaper::{Shaper, TextDirection};
 
-    let font_cfg = &state.config.font;
     tracing::info!("Reading font from {:?}", font_cfg.path);
 
     let regular_bytes: &'static [u8] = match std::fs::read(&font_cfg.path) {
@@ -166,7 +272,7 @@ fn init_pixel_ui(state: &KittyCompositor) {
                 "PixelUI: could not read font {:?}: {e} — TWM chrome disabled",
                 font_cfg.path
             );
-            return;
+            return None;
         }
     };
 
@@ -194,41 +300,57 @@ fn init_pixel_ui(state: &KittyCompositor) {
                 }
             });
 
-    let atlas = match GlyphAtlas::new(
-        regular_bytes,
-        bold_bytes,
-        italic_bytes,
-        font_cfg.size,
-        font_cfg.line_spacing.unwrap_or(1.1),
-        font_cfg.dpi.unwrap_or(96),
-    ) {
+    let opts = AtlasOptions {
+        size_px,
+        line_spacing: font_cfg.line_spacing.unwrap_or(1.1),
+        dpi: font_cfg.dpi.unwrap_or(96),
+        axes: FontAxes {
+            weight: font_cfg.weight.map(|w| w as f32),
+            width: font_cfg.width.map(|w| w as f32),
+        },
+        ..Default::default()
+    };
+    let atlas = match GlyphAtlas::new(regular_bytes, bold_bytes, italic_bytes, &opts) {
         Ok(a) => a,
         Err(e) => {
             tracing::warn!("PixelUI: GlyphAtlas::new failed: {e} — TWM chrome disabled");
-            return;
+            return None;
         }
     };
+    let fallback: Vec<&std::path::Path> =
+        font_cfg.fallback.iter().map(std::path::Path::new).collect();
+    let atlas = atlas.with_fallback_fonts(&fallback);
+    let emoji_path = font_cfg
+        .emoji_path
+        .clone()
+        .map(std::path::PathBuf::from)
+        .or_else(|| crate::util::find_font("coloremoji"));
+    let atlas = match emoji_path.map(|p| (std::fs::read(&p), p)) {
+        Some((Ok(data), _)) => atlas.with_color_emoji(&data),
+        Some((Err(e), p)) => {
+            tracing::warn!("PixelUI: could not read emoji font {p:?}: {e}");
+            atlas
+        }
+        None => atlas,
+    };
     tracing::info!("GlyphAtlas created ok");
-    let shaper = Shaper::new(regular_bytes);
+    let shaper = Shaper::new(regular_bytes).with_direction(if font_cfg.bidi_enabled {
+        TextDirection::Auto
+    } else {
+        TextDirection::Ltr
+    });
     tracing::info!("Shaper created ok");
 
     let current = unsafe { smithay::backend::egl::ffi::egl::GetCurrentContext() };
     tracing::info!("EGL current context before UiRenderer::new: {:?}", current);
 
-    let ui_renderer = match UiRenderer::new(atlas, shaper, 0, 0) {
-        Ok(r) => r,
+    match UiRenderer::new(atlas, shaper, 0, 0) {
+        Ok(r) => Some(r),
         Err(e) => {
             tracing::warn!("PixelUI: UiRenderer::new failed: {e} — TWM chrome disabled");
-            return;
+            None
         }
-    };
-
-    overlay_element::install_renderer(ui_renderer);
-    tracing::info!(
-        "PixelUI installed — cell {}×{}px",
-        overlay_element::cell_size().0,
-        overlay_element::cell_size().1,
-    );
+    }
 }
 
 // ── add_output ────────────────────────────────────────────────────────────────
@@ -242,60 +364,72 @@ pub fn add_output(
     crtc: crtc::Handle,
     drm_mode: smithay::reexports::drm::control::Mode,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    let info = backend.drm.get_connector(connector, false)?;
+    let connector_name = connector_name(&info);
+    let out_cfg = state
+        .config
+        .outputs
+        .get(&connector_name)
+        .cloned()
+        .unwrap_or_default();
+    let drm_mode = match out_cfg.mode {
+        Some(want) => find_mode(info.modes(), want).unwrap_or_else(|| {
+            tracing::warn!("{connector_name}: no mode {want:?}, using the preferred one");
+            drm_mode
+        }),
+        None => drm_mode,
+    };
+    let position = out_cfg.position.unwrap_or((0, 0));
+
     let wl_mode = Mode {
         size: (drm_mode.size().0 as i32, drm_mode.size().1 as i32).into(),
-        refresh: drm_mode.vrefresh() as i32 * 1000,
+        refresh: refresh_mhz(&drm_mode) as i32,
     };
 
     let connector_hz = drm_mode.vrefresh() as u64;
     let frame_duration = state.config.frame_duration_for(connector_hz);
 
-    let output = Output::new(
-        format!("{node}-{crtc:?}"),
-        PhysicalProperties {
-            size: (0, 0).into(),
-            subpixel: Subpixel::Unknown,
-            make: "KittyWM".into(),
-            model: "DRM".into(),
-        },
+    let output = new_output(
+        connector_name.clone(),
+        info.size().map_or((0, 0), |(w, h)| (w as i32, h as i32)),
     );
     let _global = output.create_global::<KittyCompositor>(dh);
     output.change_current_state(
         Some(wl_mode),
-        Some(Transform::Normal),
-        None,
-        Some((0, 0).into()),
+        Some(out_cfg.transform.unwrap_or(Transform::Normal)),
+        out_cfg.scale.map(Scale::Fractional),
+        Some(position.into()),
     );
     output.set_preferred(wl_mode);
-    state.space.map_output(&output, (0, 0));
+    state.space.map_output(&output, position);
+    if let Some(twm) = &mut state.twm {
+        twm.assign_output(&output.name());
+    }
+    state.update_fractional_scales();
 
     // Now we know the real pixel size — update the overlay viewport so NDC
     // projection is correct for this output.
     let ow = drm_mode.size().0 as u32;
     let oh = drm_mode.size().1 as u32;
-    crate::pixelui::overlay_element::set_viewport(ow, oh);
+    crate::pixelui::overlay_element::set_viewport(&output.name(), ow, oh);
+    match unsafe { backend.renderer.egl_context().make_current() } {
+        Ok(()) => scale_pixel_ui(&state.config, &output),
+        Err(e) => tracing::warn!("{connector_name}: could not make EGL context current: {e}"),
+    }
+    state
+        .embed_ipc
+        .broadcast_output_geometry(&output.name(), position.0, position.1, ow, oh);
 
-    let compositor = DrmCompositor::new(
-        &output,
-        backend.drm.create_surface(crtc, drm_mode, &[connector])?,
-        None,
-        GbmAllocator::new(
-            backend.gbm.clone(),
-            GbmBufferFlags::RENDERING | GbmBufferFlags::SCANOUT,
-        ),
-        GbmFramebufferExporter::new(backend.gbm.clone(), Some(node)),
-        [Fourcc::Argb8888, Fourcc::Xrgb8888].iter().copied(),
-        backend
-            .renderer
-            .egl_context()
-            .dmabuf_render_formats()
-            .clone(),
-        Size::<u32, smithay::utils::Buffer>::from((64, 64)),
-        None::<GbmDevice<DrmDeviceFd>>,
-    )?;
+    let mut compositor = create_compositor(backend, node, connector, crtc, drm_mode, &output)?;
+    let vrr = configure_vrr(
+        &mut compositor,
+        connector,
+        crtc,
+        state.config.vrr_for(&output.name()),
+    );
 
     tracing::info!(
-        "Output {node}-{crtc:?}: {}x{}@{}Hz → frame duration {:.2}ms (target_hz={:?})",
+        "Output {node}-{crtc:?} ({connector_name}): {}x{}@{}Hz → frame duration {:.2}ms (target_hz={:?})",
         drm_mode.size().0,
         drm_mode.size().1,
         connector_hz,
@@ -312,16 +446,784 @@ pub fn add_output(
             next_frame_time: Instant::now() + frame_duration,
             pending_frame: false,
             frame_duration,
+            connector,
+            drm_mode,
+            vrr,
+            recovery_pending: false,
+            recovery: RecoveryTracker::default(),
+            dpms: DpmsState::On,
+            direct_scanout_capable: false,
+            hardware_cursor: hardware_cursor_for(&backend.drm, crtc),
+            frame_clock: FrameClock::default(),
         },
     );
 
+    // With VRR the display waits for the next frame, so frames follow
+    // vblanks instead of a fixed-rate timer; see `schedule_render`.
+    if vrr {
+        schedule_render(&state.handle, node, crtc, Instant::now() + frame_duration);
+        return Ok(());
+    }
+
     state
         .handle
         .insert_source(Timer::from_duration(frame_duration), move |_, _, state| {
             state.render_surface(node, crtc);
+            // Follows mode changes made through output management.
+            let frame_duration = state
+                .backends
+                .get(&node)
+                .and_then(|b| b.surfaces.get(&crtc))
+                .map_or(frame_duration, |s| s.frame_duration);
             TimeoutAction::ToDuration(frame_duration)
         })
         .ok();
 
     Ok(())
 }
+
+/// `DP-1`, `HDMI-A-1`: the name `output` blocks and output management use.
+fn connector_name(info: &connector::Info) -> String {
+    output_name(info.interface(), info.interface_id())
+}
+
+/// The connector name for connector `id` of type `interface`. Names come
+/// from the connector alone, not its GPU: with two GPUs that both have a
+/// `DP-1`, both outputs are called `DP-1`, and every lookup by name (output
+/// blocks, IPC, output management) reaches whichever GPU `state.backends`
+/// yields first.
+pub fn output_name(interface: connector::Interface, id: u32) -> String {
+    format!("{}-{id}", interface.as_str())
+}
+
+/// The wl_output for the connector called `name`, `size` millimetres
+/// across. It carries the connector's name so monitor rules, workspace
+/// assignments and IPC requests, which all name outputs that way, find it.
+pub fn new_output(name: String, size: (i32, i32)) -> Output {
+    Output::new(
+        name,
+        PhysicalProperties {
+            size: size.into(),
+            subpixel: Subpixel::Unknown,
+            make: "KittyWM".into(),
+            model: "DRM".into(),
+        },
+    )
+}
+
+/// The connector mode `want` picks; see `output_management::pick_mode`.
+fn find_mode(
+    modes: &[smithay::reexports::drm::control::Mode],
+    want: OutputMode,
+) -> Option<smithay::reexports::drm::control::Mode> {
+    let infos: Vec<ModeInfo> = modes.iter().map(mode_info).collect();
+    modes.get(pick_mode(&infos, want)?).copied()
+}
+
+fn mode_info(m: &smithay::reexports::drm::control::Mode) -> ModeInfo {
+    ModeInfo {
+        width: m.size().0 as i32,
+        height: m.size().1 as i32,
+        refresh: refresh_mhz(m) as i32,
+        preferred: m.mode_type().contains(ModeTypeFlags::PREFERRED),
+    }
+}
+
+/// Refresh rate of `mode` in mHz, from its timings. `vrefresh` rounds to
+/// whole Hz, which makes a 59.94 Hz mode look like its 60 Hz sibling.
+pub fn refresh_mhz(mode: &smithay::reexports::drm::control::Mode) -> u32 {
+    let flags = mode.flags();
+    timings_mhz(
+        mode.clock(),
+        mode.hsync().2,
+        mode.vsync().2,
+        mode.vscan(),
+        flags.contains(ModeFlags::INTERLACE),
+        flags.contains(ModeFlags::DBLSCAN),
+    )
+    .unwrap_or(mode.vrefresh() * 1000)
+}
+
+/// The kernel's drm_mode_vrefresh in mHz: pixel clock in kHz over the
+/// htotal × vtotal frame. None for a mode without totals.
+fn timings_mhz(
+    clock_khz: u32,
+    htotal: u16,
+    vtotal: u16,
+    vscan: u16,
+    interlace: bool,
+    dblscan: bool,
+) -> Option<u32> {
+    let mut num = clock_khz as u64 * 1_000_000;
+    let mut den = htotal as u64 * vtotal as u64;
+    if den == 0 {
+        return None;
+    }
+    if interlace {
+        num *= 2;
+    }
+    if dblscan {
+        den *= 2;
+    }
+    if vscan > 1 {
+        den *= vscan as u64;
+    }
+    Some(((num + den / 2) / den) as u32)
+}
+
+/// A fresh DrmCompositor scanning `output` out of `crtc`. Also used to
+/// rebuild one after a GPU reset.
+pub fn create_compositor(
+    backend: &mut BackendData,
+    node: DrmNode,
+    connector: connector::Handle,
+    crtc: crtc::Handle,
+    drm_mode: smithay::reexports::drm::control::Mode,
+    output: &Output,
+) -> Result<GbmDrmCompositor, Box<dyn std::error::Error>> {
+    Ok(DrmCompositor::new(
+        output,
+        backend.drm.create_surface(crtc, drm_mode, &[connector])?,
+        None,
+        GbmAllocator::new(
+            backend.gbm.clone(),
+            GbmBufferFlags::RENDERING | GbmBufferFlags::SCANOUT,
+        ),
+        GbmFramebufferExporter::new(backend.gbm.clone(), Some(node)),
+        [Fourcc::Argb8888, Fourcc::Xrgb8888].iter().copied(),
+        backend
+            .renderer
+            .egl_context()
+            .dmabuf_render_formats()
+            .clone(),
+        backend.drm.cursor_size(),
+        // Lets DrmCompositor allocate cursor-plane buffers.
+        Some(backend.gbm.clone()),
+    )?)
+}
+
+// ── output management ─────────────────────────────────────────────────────────
+
+/// One head per connected connector on every GPU, by name. A head is
+/// enabled while its output is mapped in the space.
+pub fn output_heads(state: &KittyCompositor) -> Vec<HeadInfo> {
+    let mut heads = Vec::new();
+    for backend in state.backends.values() {
+        let Ok(res_handles) = backend.drm.resource_handles() else {
+            continue;
+        };
+        for &handle in res_handles.connectors() {
+            let Ok(info) = backend.drm.get_connector(handle, false) else {
+                continue;
+            };
+            if info.state() != connector::State::Connected {
+                continue;
+            }
+            let surface = backend.surfaces.values().find(|s| s.connector == handle);
+            let output = surface
+                .map(|s| &s.output)
+                .filter(|o| state.space.output_geometry(o).is_some());
+            let name = connector_name(&info);
+            heads.push(HeadInfo {
+                description: name.clone(),
+                name,
+                physical_size: info.size().map_or((0, 0), |(w, h)| (w as i32, h as i32)),
+                modes: info.modes().iter().map(mode_info).collect(),
+                enabled: output.is_some(),
+                current_mode: surface
+                    .and_then(|s| info.modes().iter().position(|m| *m == s.drm_mode)),
+                position: output.map_or((0, 0), |o| {
+                    let loc = o.current_location();
+                    (loc.x, loc.y)
+                }),
+                transform: output.map_or(Transform::Normal, |o| o.current_transform()),
+                scale: output.map_or(1.0, |o| o.current_scale().fractional_scale()),
+            });
+        }
+    }
+    heads.sort_by(|a, b| a.name.cmp(&b.name));
+    heads
+}
+
+/// The connector queries name lookups need; a trait so `connector_named`
+/// can be driven by a fake device in tests.
+pub trait ConnectorNames {
+    fn connector_handles(&self) -> Vec<connector::Handle>;
+    /// Type, per-type id and connection state of `handle`.
+    fn connector_identity(
+        &self,
+        handle: connector::Handle,
+    ) -> Option<(connector::Interface, u32, connector::State)>;
+}
+
+impl ConnectorNames for DrmDevice {
+    fn connector_handles(&self) -> Vec<connector::Handle> {
+        self.resource_handles()
+            .map(|r| r.connectors().to_vec())
+            .unwrap_or_default()
+    }
+
+    fn connector_identity(
+        &self,
+        handle: connector::Handle,
+    ) -> Option<(connector::Interface, u32, connector::State)> {
+        let info = self.get_connector(handle, false).ok()?;
+        Some((info.interface(), info.interface_id(), info.state()))
+    }
+}
+
+/// The connected connector on `dev` called `name`.
+fn connector_named(dev: &impl ConnectorNames, name: &str) -> Option<connector::Handle> {
+    dev.connector_handles().into_iter().find(|&handle| {
+        dev.connector_identity(handle)
+            .is_some_and(|(interface, id, state)| {
+                state == connector::State::Connected && output_name(interface, id) == name
+            })
+    })
+}
+
+/// GPU, handle and, once it has an output, CRTC of connected connector `name`.
+fn find_connector(
+    state: &KittyCompositor,
+    name: &str,
+) -> Option<(DrmNode, connector::Handle, Option<crtc::Handle>)> {
+    state.backends.iter().find_map(|(&node, backend)| {
+        let handle = connector_named(&backend.drm, name)?;
+        let crtc = backend
+            .surfaces
+            .iter()
+            .find(|(_, s)| s.connector == handle)
+            .map(|(&crtc, _)| crtc);
+        Some((node, handle, crtc))
+    })
+}
+
+/// Enable connector `name` with `cfg`: reconfigure its output in place, or
+/// set one up through `add_output` if it has none yet. Once that succeeds
+/// `cfg` is merged into the connector's entry in `config.outputs`, so a
+/// hotplug or GPU reset brings it back the same way.
+pub fn configure_output(
+    state: &mut KittyCompositor,
+    name: &str,
+    cfg: &OutputConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (node, handle, crtc) = find_connector(state, name).ok_or("not connected")?;
+    let previous = state.config.outputs.get(name).cloned();
+    let mut merged = previous.clone().unwrap_or_default();
+    merged.merge(cfg);
+    let result = match crtc {
+        Some(crtc) => reconfigure_output(state, node, crtc, cfg),
+        None => {
+            let mut backend = state.backends.remove(&node).ok_or("GPU gone")?;
+            // add_output takes its settings from the config.
+            state.config.outputs.insert(name.to_owned(), merged.clone());
+            let result = backend
+                .drm
+                .get_connector(handle, false)
+                .map_err(Into::into)
+                .and_then(|info| {
+                    let dh = state.display_handle.clone();
+                    setup_connector(state, &dh, &mut backend, node, &info)
+                });
+            state.backends.insert(node, backend);
+            result
+        }
+    };
+    match (&result, previous) {
+        (Ok(()), _) => state.config.outputs.insert(name.to_owned(), merged),
+        (Err(_), Some(previous)) => state.config.outputs.insert(name.to_owned(), previous),
+        (Err(_), None) => state.config.outputs.remove(name),
+    };
+    result
+}
+
+/// Apply `cfg` to the output on `crtc`; whatever it leaves unset stays as
+/// it is. Also maps the output again if it was disabled.
+fn reconfigure_output(
+    state: &mut KittyCompositor,
+    node: DrmNode,
+    crtc: crtc::Handle,
+    cfg: &OutputConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let backend = state.backends.get_mut(&node).ok_or("GPU gone")?;
+    let surface = backend.surfaces.get_mut(&crtc).ok_or("output gone")?;
+    let info = backend.drm.get_connector(surface.connector, false)?;
+    let drm_mode = match cfg.mode {
+        Some(want) => find_mode(info.modes(), want).ok_or_else(|| format!("no mode {want:?}"))?,
+        None => surface.drm_mode,
+    };
+    if drm_mode != surface.drm_mode {
+        surface
+            .compositor
+            .use_mode(drm_mode)
+            .map_err(|e| format!("use_mode: {e}"))?;
+        surface.drm_mode = drm_mode;
+        surface.frame_duration = state.config.frame_duration_for(drm_mode.vrefresh() as u64);
+    }
+
+    let output = surface.output.clone();
+    let location = output.current_location();
+    let position = cfg.position.unwrap_or((location.x, location.y));
+    output.change_current_state(
+        Some(Mode {
+            size: (drm_mode.size().0 as i32, drm_mode.size().1 as i32).into(),
+            refresh: refresh_mhz(&drm_mode) as i32,
+        }),
+        cfg.transform,
+        cfg.scale.map(Scale::Fractional),
+        Some(position.into()),
+    );
+    surface.damage_tracker = OutputDamageTracker::from_output(&output);
+
+    let (ow, oh) = (drm_mode.size().0 as u32, drm_mode.size().1 as u32);
+    crate::pixelui::overlay_element::set_viewport(&output.name(), ow, oh);
+    match unsafe { backend.renderer.egl_context().make_current() } {
+        Ok(()) => scale_pixel_ui(&state.config, &output),
+        Err(e) => tracing::warn!("{}: could not make EGL context current: {e}", output.name()),
+    }
+    state.space.map_output(&output, position);
+    state.update_fractional_scales();
+    state
+        .embed_ipc
+        .broadcast_output_geometry(&output.name(), position.0, position.1, ow, oh);
+    state.set_dpms(node, crtc, true);
+    Ok(())
+}
+
+/// Unmap connector `name`'s output and power it off. It keeps its CRTC, so
+/// enabling it again is a reconfigure.
+pub fn disable_output(
+    state: &mut KittyCompositor,
+    name: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (node, _, crtc) = find_connector(state, name).ok_or("not connected")?;
+    let Some(crtc) = crtc else {
+        return Ok(());
+    };
+    state.set_dpms(node, crtc, false);
+    let output = state.backends[&node].surfaces[&crtc].output.clone();
+    state.space.unmap_output(&output);
+    Ok(())
+}
+
+// ── cursor plane ──────────────────────────────────────────────────────────────
+
+/// Planes that can be attached to `crtc`.
+fn crtc_planes(drm: &DrmDevice, crtc: crtc::Handle) -> Vec<plane::Handle> {
+    let (Ok(resources), Ok(planes)) = (drm.resource_handles(), drm.plane_handles()) else {
+        return Vec::new();
+    };
+    planes
+        .into_iter()
+        .filter(|&p| {
+            drm.get_plane(p).is_ok_and(|info| {
+                resources
+                    .filter_crtcs(info.possible_crtcs())
+                    .contains(&crtc)
+            })
+        })
+        .collect()
+}
+
+/// The DRM property access cursor planes need; a trait so it can be driven
+/// by a fake device in tests.
+pub trait DrmProperties {
+    /// Handle and current value of `object`'s property called `name`.
+    fn find_property<H: ResourceHandle>(
+        &self,
+        object: H,
+        name: &str,
+    ) -> Option<(property::Handle, u64)>;
+}
+
+impl DrmProperties for DrmDevice {
+    fn find_property<H: ResourceHandle>(
+        &self,
+        object: H,
+        name: &str,
+    ) -> Option<(property::Handle, u64)> {
+        let props = self.get_properties(object).ok()?;
+        let (handles, values) = props.as_props_and_values();
+        handles.iter().zip(values).find_map(|(&prop, &value)| {
+            let info = self.get_property(prop).ok()?;
+            (info.name().to_str() == Ok(name)).then_some((prop, value))
+        })
+    }
+}
+
+/// Whether any of `planes` is a cursor plane (`type` = DRM_PLANE_TYPE_CURSOR).
+pub fn has_cursor_plane(
+    dev: &impl DrmProperties,
+    planes: impl IntoIterator<Item = plane::Handle>,
+) -> bool {
+    const DRM_PLANE_TYPE_CURSOR: u64 = 2;
+    planes.into_iter().any(|p| {
+        dev.find_property(p, "type")
+            .is_some_and(|(_, v)| v == DRM_PLANE_TYPE_CURSOR)
+    })
+}
+
+/// A HardwareCursor for `crtc`, or None to keep drawing the pointer in
+/// software.
+pub fn hardware_cursor_for(drm: &DrmDevice, crtc: crtc::Handle) -> Option<HardwareCursor> {
+    let capable = has_cursor_plane(drm, crtc_planes(drm, crtc));
+    if !capable {
+        tracing::info!("{crtc:?} has no cursor plane — software cursor");
+    }
+    capable.then(|| HardwareCursor::new(drm.cursor_size()))
+}
+
+// ── variable refresh rate ─────────────────────────────────────────────────────
+
+/// The VRR switch of an output's compositor; a trait so `configure_vrr` can
+/// be driven by a fake in tests.
+pub trait VrrControl {
+    /// Whether `connector` and the CRTC driving it can do VRR at all.
+    fn supports_vrr(&self, connector: connector::Handle) -> bool;
+    fn vrr_enabled(&self) -> bool;
+    /// Turn VRR on or off from the next commit.
+    fn set_vrr(&mut self, on: bool) -> Result<(), String>;
+}
+
+impl VrrControl for GbmDrmCompositor {
+    fn supports_vrr(&self, connector: connector::Handle) -> bool {
+        // RequiresModeset is fine: a new compositor's first commit is one.
+        matches!(
+            self.vrr_supported(connector),
+            Ok(VrrSupport::Supported | VrrSupport::RequiresModeset)
+        )
+    }
+
+    fn vrr_enabled(&self) -> bool {
+        GbmDrmCompositor::vrr_enabled(self)
+    }
+
+    fn set_vrr(&mut self, on: bool) -> Result<(), String> {
+        self.use_vrr(on).map_err(|e| e.to_string())
+    }
+}
+
+/// Have `compositor` drive `crtc` with VRR when `wanted`. Outputs that
+/// can't do VRR are left with it off. Returns whether VRR is on afterwards.
+pub fn configure_vrr(
+    compositor: &mut impl VrrControl,
+    connector: connector::Handle,
+    crtc: crtc::Handle,
+    wanted: bool,
+) -> bool {
+    if !compositor.supports_vrr(connector) {
+        tracing::info!("VRR {crtc:?}: not supported (wanted={wanted})");
+        return false;
+    }
+    if compositor.vrr_enabled() != wanted {
+        if let Err(e) = compositor.set_vrr(wanted) {
+            tracing::warn!("VRR {crtc:?}: setting enabled={wanted}: {e}");
+        }
+    }
+    let enabled = compositor.vrr_enabled();
+    tracing::info!("VRR {crtc:?}: capable, enabled={enabled}");
+    enabled
+}
+
+// ── DPMS ──────────────────────────────────────────────────────────────────────
+
+/// Power level of one output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DpmsState {
+    #[default]
+    On,
+    Off,
+}
+
+/// The power switch of an output's compositor; a trait so
+/// `set_output_power` can be driven by a fake in tests.
+pub trait OutputPower {
+    /// Commit the CRTC with `ACTIVE` = 0 and nothing on its planes.
+    fn power_off(&mut self) -> Result<(), String>;
+}
+
+impl OutputPower for GbmDrmCompositor {
+    fn power_off(&mut self) -> Result<(), String> {
+        self.clear().map_err(|e| e.to_string())
+    }
+}
+
+/// Power the CRTC `compositor` drives to `wanted`. Off takes effect at
+/// once; on takes effect with the next frame the compositor commits, which
+/// sets `ACTIVE` again, so the caller must render one. False if the driver
+/// refused.
+pub fn set_output_power(
+    compositor: &mut impl OutputPower,
+    crtc: crtc::Handle,
+    wanted: DpmsState,
+) -> bool {
+    if wanted == DpmsState::On {
+        return true;
+    }
+    match compositor.power_off() {
+        Ok(()) => true,
+        Err(e) => {
+            tracing::warn!("DPMS {crtc:?}: powering off: {e}");
+            false
+        }
+    }
+}
+
+// ── idle suspend ──────────────────────────────────────────────────────────────
+
+/// Handing a DRM device back and taking it again; a trait so idle suspend
+/// can be driven by a fake in tests.
+pub trait DrmActivation {
+    fn pause(&mut self);
+    fn activate(&mut self) -> Result<(), String>;
+}
+
+impl DrmActivation for DrmDevice {
+    fn pause(&mut self) {
+        DrmDevice::pause(self);
+    }
+
+    fn activate(&mut self) -> Result<(), String> {
+        DrmDevice::activate(self, false).map_err(|e| e.to_string())
+    }
+}
+
+/// Pause every device in `devices` unless `*suspended` already. True if
+/// this call suspended.
+pub fn suspend_devices<'a, D: DrmActivation + 'a>(
+    suspended: &mut bool,
+    devices: impl IntoIterator<Item = &'a mut D>,
+) -> bool {
+    if *suspended {
+        return false;
+    }
+    *suspended = true;
+    for dev in devices {
+        dev.pause();
+    }
+    true
+}
+
+/// Reactivate every device in `devices` if `*suspended`; called on each
+/// input event, so only the first one after a suspend does anything. True
+/// if this call resumed.
+pub fn resume_devices<'a, D: DrmActivation + 'a>(
+    suspended: &mut bool,
+    devices: impl IntoIterator<Item = &'a mut D>,
+) -> bool {
+    if !*suspended {
+        return false;
+    }
+    *suspended = false;
+    for dev in devices {
+        if let Err(e) = dev.activate() {
+            tracing::error!("Failed to activate DRM: {e}");
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use smithay::reexports::drm::control::from_u32;
+
+    /// Properties as (object id, name, value).
+    struct FakeDrm {
+        props: Vec<(u32, &'static str, u64)>,
+    }
+
+    impl DrmProperties for FakeDrm {
+        fn find_property<H: ResourceHandle>(
+            &self,
+            object: H,
+            name: &str,
+        ) -> Option<(property::Handle, u64)> {
+            let id: u32 = object.into();
+            self.props
+                .iter()
+                .position(|&(o, n, _)| o == id && n == name)
+                .map(|i| (from_u32(100 + i as u32).unwrap(), self.props[i].2))
+        }
+    }
+
+    /// A compositor's VRR switch; `sets` records every `set_vrr`.
+    #[derive(Default)]
+    struct FakeVrr {
+        capable: bool,
+        enabled: bool,
+        sets: Vec<bool>,
+    }
+
+    impl VrrControl for FakeVrr {
+        fn supports_vrr(&self, _: connector::Handle) -> bool {
+            self.capable
+        }
+
+        fn vrr_enabled(&self) -> bool {
+            self.enabled
+        }
+
+        fn set_vrr(&mut self, on: bool) -> Result<(), String> {
+            self.sets.push(on);
+            self.enabled = on;
+            Ok(())
+        }
+    }
+
+    fn handles() -> (connector::Handle, crtc::Handle) {
+        (from_u32(1).unwrap(), from_u32(2).unwrap())
+    }
+
+    #[test]
+    fn refresh_from_mode_timings() {
+        // CEA 1080p60 and its 1000/1001 sibling, which vrefresh() also
+        // calls 60.
+        let hd = |clock, interlace| timings_mhz(clock, 2200, 1125, 0, interlace, false);
+        assert_eq!(hd(148_500, false), Some(60_000));
+        assert_eq!(hd(148_352, false), Some(59_940));
+        // 1080i: two fields per frame.
+        assert_eq!(hd(74_250, true), Some(60_000));
+        assert_eq!(timings_mhz(0, 0, 0, 0, false, false), None);
+    }
+
+    /// Connectors as (handle, type, per-type id, state).
+    struct FakeConnectors(Vec<(u32, connector::Interface, u32, connector::State)>);
+
+    impl ConnectorNames for FakeConnectors {
+        fn connector_handles(&self) -> Vec<connector::Handle> {
+            self.0.iter().map(|c| from_u32(c.0).unwrap()).collect()
+        }
+
+        fn connector_identity(
+            &self,
+            handle: connector::Handle,
+        ) -> Option<(connector::Interface, u32, connector::State)> {
+            let id: u32 = handle.into();
+            let &(_, interface, n, state) = self.0.iter().find(|c| c.0 == id)?;
+            Some((interface, n, state))
+        }
+    }
+
+    #[test]
+    fn connectors_resolve_by_connector_name() {
+        use connector::{Interface, State};
+
+        let dev = FakeConnectors(vec![
+            (1, Interface::DisplayPort, 1, State::Disconnected),
+            (2, Interface::DisplayPort, 2, State::Connected),
+            (3, Interface::HDMIA, 1, State::Connected),
+        ]);
+        assert_eq!(connector_named(&dev, "DP-2"), from_u32(2));
+        assert_eq!(connector_named(&dev, "HDMI-A-1"), from_u32(3));
+        // Unplugged, or not there at all.
+        assert_eq!(connector_named(&dev, "DP-1"), None);
+        assert_eq!(connector_named(&dev, "eDP-1"), None);
+        // add_output names the output the same way.
+        let output = new_output(output_name(Interface::HDMIA, 1), (0, 0));
+        assert_eq!(connector_named(&dev, &output.name()), from_u32(3));
+    }
+
+    #[test]
+    fn vrr_enabled_when_configured_and_capable() {
+        let mut compositor = FakeVrr {
+            capable: true,
+            ..Default::default()
+        };
+        let (conn, crtc) = handles();
+        assert!(configure_vrr(&mut compositor, conn, crtc, true));
+        // Already on: nothing to change.
+        assert!(configure_vrr(&mut compositor, conn, crtc, true));
+        assert!(!configure_vrr(&mut compositor, conn, crtc, false));
+        assert_eq!(compositor.sets, [true, false]);
+    }
+
+    #[test]
+    fn vrr_left_off_without_capability() {
+        let mut compositor = FakeVrr::default();
+        let (conn, crtc) = handles();
+        assert!(!configure_vrr(&mut compositor, conn, crtc, true));
+        assert!(compositor.sets.is_empty());
+    }
+
+    #[test]
+    fn cursor_plane_detected_by_type() {
+        let dev = FakeDrm {
+            // Plane 10 is primary (1), plane 11 overlay (0), plane 12 cursor (2).
+            props: vec![(10, "type", 1), (11, "type", 0), (12, "type", 2)],
+        };
+        let plane = |id| from_u32::<plane::Handle>(id).unwrap();
+        assert!(has_cursor_plane(&dev, [plane(10), plane(12)]));
+        assert!(!has_cursor_plane(&dev, [plane(10), plane(11)]));
+        assert!(!has_cursor_plane(&dev, []));
+    }
+
+    /// A compositor whose power-offs succeed unless `refuse`.
+    #[derive(Default)]
+    struct FakePower {
+        refuse: bool,
+        offs: usize,
+    }
+
+    impl OutputPower for FakePower {
+        fn power_off(&mut self) -> Result<(), String> {
+            if self.refuse {
+                return Err("refused".into());
+            }
+            self.offs += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn dpms_powers_the_crtc_off_through_the_compositor() {
+        let (_, crtc) = handles();
+        let mut compositor = FakePower::default();
+        assert!(set_output_power(&mut compositor, crtc, DpmsState::Off));
+        // Back on with the next frame; nothing to commit now.
+        assert!(set_output_power(&mut compositor, crtc, DpmsState::On));
+        assert_eq!(compositor.offs, 1);
+
+        let mut stubborn = FakePower {
+            refuse: true,
+            ..Default::default()
+        };
+        assert!(!set_output_power(&mut stubborn, crtc, DpmsState::Off));
+    }
+
+    /// A DRM device counting its pauses and activations.
+    #[derive(Default)]
+    struct FakeSession {
+        pauses: usize,
+        activates: usize,
+    }
+
+    impl DrmActivation for FakeSession {
+        fn pause(&mut self) {
+            self.pauses += 1;
+        }
+
+        fn activate(&mut self) -> Result<(), String> {
+            self.activates += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn idle_suspend_pauses_then_first_key_activates() {
+        let mut devs = [FakeSession::default(), FakeSession::default()];
+        let mut suspended = false;
+
+        // The idle timer fires, then fires again while still idle.
+        assert!(suspend_devices(&mut suspended, &mut devs));
+        assert!(!suspend_devices(&mut suspended, &mut devs));
+        assert!(suspended);
+        assert!(devs.iter().all(|d| d.pauses == 1 && d.activates == 0));
+
+        // handle_input resumes on every event; only the first key counts.
+        assert!(resume_devices(&mut suspended, &mut devs));
+        assert!(!resume_devices(&mut suspended, &mut devs));
+        assert!(!suspended);
+        assert!(devs.iter().all(|d| d.pauses == 1 && d.activates == 1));
+    }
+}
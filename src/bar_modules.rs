@@ -0,0 +1,161 @@
+// bar_modules.rs — bar modules whose readings come from outside the compositor
+//
+// These are polled on calloop timers from main.rs and the results cached in
+// TwmState, so drawing the bar never touches the filesystem.
+
+use std::path::PathBuf;
+
+use crate::pixelui::icons::Icons;
+use crate::pixelui::ProgressBar;
+
+/// Bolt drawn after the battery icon while charging, if the icon map lacks
+/// a `charging` entry.
+const CHARGING_BOLT: char = '\u{F0E7}';
+
+/// Icon map names for the battery icon, by capacity quartile.
+const BATTERY_ICONS: [&str; 5] = [
+    "battery-empty",
+    "battery-quarter",
+    "battery-half",
+    "battery-three-quarters",
+    "battery",
+];
+
+/// "CPU 42%", the `cpu` module's text.
+pub fn cpu_label(percent: u8) -> String {
+    format!("CPU {percent:>2}%")
+}
+
+/// The `cpu` module's gauge, filled to the load.
+pub fn cpu_gauge(percent: u8) -> ProgressBar {
+    ProgressBar::new(percent as f32 / 100.0).label(cpu_label(percent))
+}
+
+/// Busy share of all CPUs between two /proc/stat samples.
+#[derive(Default)]
+pub struct CpuModule {
+    /// (busy, total) jiffies at the last sample.
+    jiffies: Option<(u64, u64)>,
+}
+
+impl CpuModule {
+    /// Percent busy since the previous call; `None` on the first call or
+    /// while /proc/stat is unreadable.
+    pub fn read(&mut self) -> Option<u8> {
+        let now = std::fs::read_to_string("/proc/stat")
+            .ok()
+            .and_then(|t| crate::pixelui::layers::parse_proc_stat(&t));
+        self.sample(now)
+    }
+
+    fn sample(&mut self, now: Option<(u64, u64)>) -> Option<u8> {
+        let (prev, now) = (self.jiffies, now?);
+        self.jiffies = Some(now);
+        let (busy, total) = prev?;
+        let busy = now.0.saturating_sub(busy) * 100;
+        busy.checked_div(now.1.saturating_sub(total))
+            .map(|pct| pct.min(100) as u8)
+    }
+}
+
+/// One reading of a power_supply battery.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatteryStatus {
+    /// Charge in percent, 0–100.
+    pub capacity: u8,
+    pub charging: bool,
+}
+
+impl BatteryStatus {
+    /// From the contents of the sysfs `capacity` and `status` files.
+    pub fn parse(capacity: &str, status: &str) -> Option<Self> {
+        let capacity: u8 = capacity.trim().parse().ok()?;
+        Some(Self {
+            capacity: capacity.min(100),
+            charging: status.trim() == "Charging",
+        })
+    }
+
+    /// Nerd Fonts battery glyph for the capacity quartile.
+    pub fn icon(&self) -> char {
+        let name = BATTERY_ICONS[self.capacity as usize * 4 / 100];
+        Icons::lookup(name).unwrap_or('\u{F240}')
+    }
+
+    /// Bar text: the icon, the percentage and a bolt while charging.
+    pub fn label(&self) -> String {
+        let mut label = format!("{} {}%", self.icon(), self.capacity);
+        if self.charging {
+            label.push(' ');
+            label.push(Icons::lookup("charging").unwrap_or(CHARGING_BOLT));
+        }
+        label
+    }
+
+    /// The `battery` module's gauge, filled to the charge.
+    pub fn gauge(&self) -> ProgressBar {
+        ProgressBar::new(self.capacity as f32 / 100.0).label(self.label())
+    }
+}
+
+/// Reads `capacity` and `status` of one battery under /sys/class/power_supply.
+pub struct BatteryModule {
+    dir: PathBuf,
+}
+
+impl Default for BatteryModule {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("/sys/class/power_supply/BAT0"),
+        }
+    }
+}
+
+impl BatteryModule {
+    /// `None` when there is no such battery or its capacity is unreadable.
+    pub fn read(&self) -> Option<BatteryStatus> {
+        let capacity = std::fs::read_to_string(self.dir.join("capacity")).ok()?;
+        let status = std::fs::read_to_string(self.dir.join("status")).unwrap_or_default();
+        BatteryStatus::parse(&capacity, &status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn battery_status_from_sysfs_files() {
+        let status = BatteryStatus::parse("98\n", "Charging\n").unwrap();
+        assert_eq!(
+            status,
+            BatteryStatus {
+                capacity: 98,
+                charging: true
+            }
+        );
+        assert_eq!(status.icon(), '\u{F241}');
+        assert!(status.label().starts_with("\u{F241} 98% "));
+
+        let low = BatteryStatus::parse("12", "Discharging").unwrap();
+        assert!(!low.charging);
+        assert_eq!(low.label(), "\u{F244} 12%");
+        assert_eq!(
+            BatteryStatus::parse("100", "Full").unwrap().icon(),
+            '\u{F240}'
+        );
+        assert_eq!(BatteryStatus::parse("", "Full"), None);
+    }
+
+    #[test]
+    fn cpu_load_between_samples() {
+        let mut cpu = CpuModule::default();
+        assert_eq!(cpu.sample(Some((100, 1000))), None);
+        assert_eq!(cpu.sample(Some((150, 1200))), Some(25));
+        // An unreadable sample is skipped, not counted as idle.
+        assert_eq!(cpu.sample(None), None);
+        assert_eq!(cpu.sample(Some((250, 1300))), Some(100));
+        // No time passed: no reading.
+        assert_eq!(cpu.sample(Some((250, 1300))), None);
+    }
+}
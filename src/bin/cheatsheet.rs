@@ -142,13 +142,29 @@ fn build_compositor_entries(config: &Config) -> Vec<Entry> {
             let mut parts: Vec<String> =
                 bind.mods.iter().map(|m| mod_label(m).to_string()).collect();
             parts.push(key_label(&bind.key));
-            let chord = parts.join(" + ");
+            let mut chord = parts.join(" + ");
+            if bind.on_release {
+                chord.push_str(" (release)");
+            }
 
             let (action, kind) = match &bind.action {
                 KeyAction::Quit => ("󰩈  Quit compositor".into(), EntryKind::Quit),
                 KeyAction::CloseWindow => ("󰅗  Close window".into(), EntryKind::Close),
                 KeyAction::ReloadConfig => ("󰑓  Reload config".into(), EntryKind::Close),
-                KeyAction::Spawn { command, args } => {
+                KeyAction::Overview => ("󰕰  Overview".into(), EntryKind::Close),
+                KeyAction::GridCols(cols) => (format!("󰕰  Grid, {cols} columns"), EntryKind::Close),
+                KeyAction::ToggleScratchpad => ("󰖲  Scratchpad".into(), EntryKind::Close),
+                KeyAction::SendToScratchpad => ("󰖲  Send to scratchpad".into(), EntryKind::Close),
+                KeyAction::FocusLast => ("󰁯  Focus last".into(), EntryKind::Close),
+                KeyAction::AltTabNext => ("󰓩  Alt-tab next".into(), EntryKind::Close),
+                KeyAction::AltTabPrev => ("󰓩  Alt-tab previous".into(), EntryKind::Close),
+                KeyAction::ToggleSticky => ("󰐃  Toggle sticky".into(), EntryKind::Close),
+                KeyAction::WorkspaceLeft => ("󰁍  Workspace left".into(), EntryKind::Close),
+                KeyAction::WorkspaceRight => ("󰁔  Workspace right".into(), EntryKind::Close),
+                KeyAction::WorkspaceUp => ("󰁝  Workspace up".into(), EntryKind::Close),
+                KeyAction::WorkspaceDown => ("󰁅  Workspace down".into(), EntryKind::Close),
+                KeyAction::UndoClose => ("󰕌  Undo close".into(), EntryKind::Close),
+                KeyAction::Spawn { command, args, .. } => {
                     let bin = command.rsplit('/').next().unwrap_or(command).to_string();
                     let icon = action_icon(&bin, args);
                     let first_arg = args
@@ -163,6 +179,18 @@ fn build_compositor_entries(config: &Config) -> Vec<Entry> {
                     };
                     (label, EntryKind::Spawn)
                 }
+                KeyAction::SendKey {
+                    target_app_id,
+                    key,
+                    mods,
+                } => {
+                    let mut combo: Vec<&str> = mods.iter().map(String::as_str).collect();
+                    combo.push(key);
+                    (
+                        format!("󰌌  {} → {target_app_id}", combo.join("+")),
+                        EntryKind::Spawn,
+                    )
+                }
             };
             Entry {
                 chord,
@@ -0,0 +1,145 @@
+// src/bin/ipc.rs — trixie-ipc, one IPC command from the shell
+//
+//   trixie-ipc '{"op":"list_windows"}'     raw JSON, sent as is
+//   trixie-ipc list_windows
+//   trixie-ipc get_config
+//   trixie-ipc list_marks
+//   trixie-ipc clipboard_history
+//   trixie-ipc screenshot OUTPUT PATH
+//   trixie-ipc record_output OUTPUT PATH
+//   trixie-ipc record_stop
+//   trixie-ipc focus APP_ID
+//   trixie-ipc dispatch ACTION [ARG]        e.g. `dispatch NextLayout`,
+//                                           `dispatch Workspace 3`
+//
+// PATH is made absolute here, since the compositor's working directory is
+// not the shell's. The reply line is printed to stdout. The exit status is 1 when the
+// compositor answers `"ok": false` or can't be reached, 2 on bad arguments.
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+};
+
+use serde_json::{json, Value};
+
+/// Must match `ipc_server::socket_path` in the compositor.
+fn socket_path() -> PathBuf {
+    let runtime = std::env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| "/tmp".into());
+    PathBuf::from(runtime).join("trixie.sock")
+}
+
+/// The JSON command line for `args`.
+fn build_command(args: &[String]) -> Result<String, String> {
+    let Some(op) = args.first() else {
+        return Err("missing command".into());
+    };
+    if op.trim_start().starts_with('{') {
+        return Ok(op.clone());
+    }
+    let cmd = match (op.as_str(), &args[1..]) {
+        (
+            "list_windows" | "list_marks" | "get_config" | "clipboard_history" | "record_stop",
+            [],
+        ) => {
+            json!({ "op": op })
+        }
+        ("screenshot" | "record_output", [output, path]) => {
+            let path = std::path::absolute(path).map_err(|e| format!("{path}: {e}"))?;
+            json!({ "op": op, "output": output, "path": path })
+        }
+        ("focus", [app_id]) => json!({ "op": "focus", "app_id": app_id }),
+        ("dispatch", [action]) => json!({ "op": "dispatch", "action": action }),
+        // `Workspace 3` → {"Workspace": 3}; the argument is JSON if it parses.
+        ("dispatch", [action, arg]) => {
+            let arg = serde_json::from_str(arg).unwrap_or_else(|_| Value::from(arg.as_str()));
+            json!({ "op": "dispatch", "action": { action.as_str(): arg } })
+        }
+        _ => return Err(format!("bad arguments for `{op}`")),
+    };
+    Ok(cmd.to_string())
+}
+
+fn send(cmd: &str) -> std::io::Result<String> {
+    let mut stream = UnixStream::connect(socket_path())?;
+    stream.write_all(cmd.as_bytes())?;
+    stream.write_all(b"\n")?;
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    Ok(reply)
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let cmd = match build_command(&args) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("trixie-ipc: {e}");
+            eprintln!(
+                "usage: trixie-ipc JSON | list_windows | list_marks | get_config | clipboard_history \
+                 | focus APP_ID | dispatch ACTION [ARG] | screenshot OUTPUT PATH \
+                 | record_output OUTPUT PATH | record_stop"
+            );
+            std::process::exit(2);
+        }
+    };
+    let reply = match send(&cmd) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("trixie-ipc: {}: {e}", socket_path().display());
+            std::process::exit(1);
+        }
+    };
+    print!("{reply}");
+    let ok = serde_json::from_str::<Value>(&reply)
+        .ok()
+        .and_then(|v| v["ok"].as_bool())
+        .unwrap_or(false);
+    if !ok {
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cmd(args: &[&str]) -> Result<Value, String> {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        build_command(&args).map(|c| serde_json::from_str(&c).unwrap())
+    }
+
+    #[test]
+    fn shorthand_commands() {
+        assert_eq!(cmd(&["list_windows"]), Ok(json!({ "op": "list_windows" })));
+        assert_eq!(
+            cmd(&["focus", "foot"]),
+            Ok(json!({ "op": "focus", "app_id": "foot" }))
+        );
+        assert_eq!(
+            cmd(&["dispatch", "NextLayout"]),
+            Ok(json!({ "op": "dispatch", "action": "NextLayout" }))
+        );
+        assert_eq!(
+            cmd(&["dispatch", "Workspace", "3"]),
+            Ok(json!({ "op": "dispatch", "action": { "Workspace": 3 } }))
+        );
+        assert_eq!(
+            cmd(&["dispatch", "OpenShell", "notes"]),
+            Ok(json!({ "op": "dispatch", "action": { "OpenShell": "notes" } }))
+        );
+        assert_eq!(
+            cmd(&["screenshot", "DP-1", "/tmp/shot.png"]),
+            Ok(json!({ "op": "screenshot", "output": "DP-1", "path": "/tmp/shot.png" }))
+        );
+        assert!(cmd(&["focus"]).is_err());
+        assert!(cmd(&[]).is_err());
+    }
+
+    #[test]
+    fn raw_json_passes_through() {
+        let raw = r#"{"op":"get_config"}"#.to_string();
+        assert_eq!(build_command(&[raw.clone()]), Ok(raw));
+    }
+}
@@ -0,0 +1,828 @@
+// src/bin/trixie-screenshot.rs — wlr-screencopy screenshot tool
+//
+//   trixie-screenshot [--region] [--delay N] [--output NAME]
+//                     [--format png|jpg|ppm] [PATH]
+//
+// Without --output every output is captured and stitched together at its
+// compositor position; with it only that output is. --region puts a
+// transparent overlay layer surface on the output (the first one unless
+// --output names another) and captures the rectangle dragged out with the
+// left button; Escape or any other button cancels.
+//
+// The file goes to PATH, or ~/Pictures/screenshot-<unix time>.<format>, and
+// its path is printed and handed to `wl-copy`.
+
+use std::{
+    io,
+    os::fd::{AsFd, OwnedFd},
+    path::{Path, PathBuf},
+    process::Command,
+    time::Duration,
+};
+
+use trixie::pixelui::{layout::Rect, Color, DrawCmd, ShmCanvas};
+
+use wayland_client::{
+    delegate_noop,
+    protocol::{
+        wl_buffer::WlBuffer,
+        wl_compositor::WlCompositor,
+        wl_keyboard::{self, WlKeyboard},
+        wl_output::{self, WlOutput},
+        wl_pointer::{self, WlPointer},
+        wl_registry::{self, WlRegistry},
+        wl_seat::{self, WlSeat},
+        wl_shm::{self, WlShm},
+        wl_shm_pool::WlShmPool,
+        wl_surface::WlSurface,
+    },
+    Connection, Dispatch, EventQueue, Proxy, QueueHandle, WEnum,
+};
+use wayland_protocols_wlr::{
+    layer_shell::v1::client::{
+        zwlr_layer_shell_v1::{self, ZwlrLayerShellV1},
+        zwlr_layer_surface_v1::{self, ZwlrLayerSurfaceV1},
+    },
+    screencopy::v1::client::{
+        zwlr_screencopy_frame_v1::{self, ZwlrScreencopyFrameV1},
+        zwlr_screencopy_manager_v1::ZwlrScreencopyManagerV1,
+    },
+};
+
+/// evdev KEY_ESC.
+const KEY_ESC: u32 = 1;
+/// evdev BTN_LEFT.
+const BTN_LEFT: u32 = 0x110;
+
+// ── options ───────────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Png,
+    Jpg,
+    Ppm,
+}
+
+impl Format {
+    fn from_name(s: &str) -> Option<Self> {
+        match s {
+            "png" => Some(Self::Png),
+            "jpg" | "jpeg" => Some(Self::Jpg),
+            "ppm" => Some(Self::Ppm),
+            _ => None,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpg => "jpg",
+            Self::Ppm => "ppm",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Options {
+    region: bool,
+    delay: u64,
+    output: Option<String>,
+    format: Format,
+    path: Option<PathBuf>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut opts = Options {
+        region: false,
+        delay: 0,
+        output: None,
+        format: Format::Png,
+        path: None,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| args.next().ok_or_else(|| format!("{flag} needs a value"));
+        match arg.as_str() {
+            "--region" => opts.region = true,
+            "--delay" => {
+                let v = value("--delay")?;
+                opts.delay = v.parse().map_err(|_| format!("bad --delay `{v}`"))?;
+            }
+            "--output" => opts.output = Some(value("--output")?),
+            "--format" => {
+                let v = value("--format")?;
+                opts.format =
+                    Format::from_name(&v).ok_or_else(|| format!("unknown --format `{v}`"))?;
+            }
+            other if other.starts_with("--") => return Err(format!("unknown option `{other}`")),
+            path => opts.path = Some(PathBuf::from(path)),
+        }
+    }
+    Ok(opts)
+}
+
+fn default_path(format: Format) -> PathBuf {
+    let home = PathBuf::from(std::env::var("HOME").unwrap_or_default());
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    home.join("Pictures")
+        .join(format!("screenshot-{secs}.{}", format.extension()))
+}
+
+// ── image output ──────────────────────────────────────────────────────────────
+
+/// Packed RGB8, `width * height * 3` bytes.
+struct RgbImage {
+    width: u32,
+    height: u32,
+    rgb: Vec<u8>,
+}
+
+fn encode_ppm(img: &RgbImage) -> Vec<u8> {
+    let mut out = format!("P6\n{} {}\n255\n", img.width, img.height).into_bytes();
+    out.extend_from_slice(&img.rgb);
+    out
+}
+
+fn write_image(path: &Path, format: Format, img: &RgbImage) -> Result<(), String> {
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).map_err(|e| format!("{}: {e}", dir.display()))?;
+    }
+    let image_format = match format {
+        Format::Ppm => {
+            return std::fs::write(path, encode_ppm(img))
+                .map_err(|e| format!("{}: {e}", path.display()))
+        }
+        Format::Png => image::ImageFormat::Png,
+        Format::Jpg => image::ImageFormat::Jpeg,
+    };
+    image::save_buffer_with_format(
+        path,
+        &img.rgb,
+        img.width,
+        img.height,
+        image::ExtendedColorType::Rgb8,
+        image_format,
+    )
+    .map_err(|e| format!("{}: {e}", path.display()))
+}
+
+/// Screencopy pixels to packed RGB. Only the 8-bit-per-channel formats every
+/// compositor offers are handled.
+fn to_rgb(
+    data: &[u8],
+    format: wl_shm::Format,
+    width: u32,
+    height: u32,
+    stride: u32,
+    y_invert: bool,
+) -> Option<Vec<u8>> {
+    // Byte offsets of R, G, B within one little-endian pixel.
+    let (r, g, b) = match format {
+        wl_shm::Format::Argb8888 | wl_shm::Format::Xrgb8888 => (2, 1, 0),
+        wl_shm::Format::Abgr8888 | wl_shm::Format::Xbgr8888 => (0, 1, 2),
+        _ => return None,
+    };
+    let mut out = Vec::with_capacity((width * height * 3) as usize);
+    for y in 0..height {
+        let row = if y_invert { height - 1 - y } else { y };
+        let start = (row * stride) as usize;
+        let line = data.get(start..start + width as usize * 4)?;
+        for px in line.chunks_exact(4) {
+            out.extend_from_slice(&[px[r], px[g], px[b]]);
+        }
+    }
+    Some(out)
+}
+
+/// Paste each capture at its position relative to the top-left-most one.
+fn stitch(parts: Vec<(i32, i32, RgbImage)>) -> Option<RgbImage> {
+    let x0 = parts.iter().map(|(x, _, _)| *x).min()?;
+    let y0 = parts.iter().map(|(_, y, _)| *y).min()?;
+    let x1 = parts.iter().map(|(x, _, i)| x + i.width as i32).max()?;
+    let y1 = parts.iter().map(|(_, y, i)| y + i.height as i32).max()?;
+    let (width, height) = ((x1 - x0) as u32, (y1 - y0) as u32);
+    let mut rgb = vec![0u8; (width * height * 3) as usize];
+    for (x, y, img) in &parts {
+        let (ox, oy) = ((x - x0) as usize, (y - y0) as usize);
+        let row_len = img.width as usize * 3;
+        for (row, line) in img.rgb.chunks_exact(row_len).enumerate() {
+            let start = ((oy + row) * width as usize + ox) * 3;
+            rgb[start..start + row_len].copy_from_slice(line);
+        }
+    }
+    Some(RgbImage { width, height, rgb })
+}
+
+// ── shm buffers ───────────────────────────────────────────────────────────────
+
+/// A wl_buffer backed by a mapped memfd.
+struct ShmBuffer {
+    buffer: WlBuffer,
+    _fd: OwnedFd,
+    ptr: *mut u8,
+    len: usize,
+}
+
+impl ShmBuffer {
+    fn new(
+        shm: &WlShm,
+        qh: &QueueHandle<App>,
+        width: u32,
+        height: u32,
+        stride: u32,
+        format: wl_shm::Format,
+    ) -> io::Result<Self> {
+        let len = (stride * height) as usize;
+        let fd = rustix::fs::memfd_create("trixie-screenshot", rustix::fs::MemfdFlags::CLOEXEC)?;
+        rustix::fs::ftruncate(&fd, len as u64)?;
+        // SAFETY: maps the memfd we just sized; unmapped in Drop.
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                std::os::fd::AsRawFd::as_raw_fd(&fd),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        let pool = shm.create_pool(fd.as_fd(), len as i32, qh, ());
+        let buffer = pool.create_buffer(
+            0,
+            width as i32,
+            height as i32,
+            stride as i32,
+            format,
+            qh,
+            (),
+        );
+        pool.destroy();
+        Ok(Self {
+            buffer,
+            _fd: fd,
+            ptr: ptr as *mut u8,
+            len,
+        })
+    }
+
+    fn data(&mut self) -> &mut [u8] {
+        // SAFETY: `ptr` maps `len` bytes for as long as `self` lives.
+        unsafe { std::slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
+
+impl Drop for ShmBuffer {
+    fn drop(&mut self) {
+        self.buffer.destroy();
+        // SAFETY: the mapping created in `new`.
+        unsafe { libc::munmap(self.ptr as *mut _, self.len) };
+    }
+}
+
+// ── wayland state ─────────────────────────────────────────────────────────────
+
+struct OutputInfo {
+    output: WlOutput,
+    name: String,
+    x: i32,
+    y: i32,
+}
+
+#[derive(Default)]
+struct Frame {
+    /// (format, width, height, stride) offered for wl_shm.
+    shm: Option<(wl_shm::Format, u32, u32, u32)>,
+    buffer: Option<ShmBuffer>,
+    y_invert: bool,
+    /// Some(true) once ready, Some(false) on failure.
+    done: Option<bool>,
+}
+
+#[derive(Default)]
+struct Selection {
+    layer: Option<(WlSurface, ZwlrLayerSurfaceV1)>,
+    buffer: Option<ShmBuffer>,
+    size: (u32, u32),
+    anchor: Option<(f64, f64)>,
+    cursor: (f64, f64),
+    /// Some(Some(rect)) when a region was dragged, Some(None) when cancelled.
+    result: Option<Option<(i32, i32, i32, i32)>>,
+}
+
+#[derive(Default)]
+struct App {
+    outputs: Vec<OutputInfo>,
+    compositor: Option<WlCompositor>,
+    shm: Option<WlShm>,
+    seat: Option<WlSeat>,
+    layer_shell: Option<ZwlrLayerShellV1>,
+    screencopy: Option<ZwlrScreencopyManagerV1>,
+    frame: Frame,
+    selection: Selection,
+}
+
+impl Dispatch<WlRegistry, ()> for App {
+    fn event(
+        state: &mut Self,
+        registry: &WlRegistry,
+        event: wl_registry::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        let wl_registry::Event::Global {
+            name,
+            interface,
+            version,
+        } = event
+        else {
+            return;
+        };
+        match interface.as_str() {
+            "wl_output" => state.outputs.push(OutputInfo {
+                output: registry.bind(name, version.min(4), qh, ()),
+                name: String::new(),
+                x: 0,
+                y: 0,
+            }),
+            "wl_compositor" => state.compositor = Some(registry.bind(name, version.min(4), qh, ())),
+            "wl_shm" => state.shm = Some(registry.bind(name, 1, qh, ())),
+            "wl_seat" => state.seat = Some(registry.bind(name, version.min(5), qh, ())),
+            "zwlr_layer_shell_v1" => {
+                state.layer_shell = Some(registry.bind(name, version.min(4), qh, ()))
+            }
+            "zwlr_screencopy_manager_v1" => {
+                state.screencopy = Some(registry.bind(name, version.min(3), qh, ()))
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<WlOutput, ()> for App {
+    fn event(
+        state: &mut Self,
+        output: &WlOutput,
+        event: wl_output::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let Some(info) = state.outputs.iter_mut().find(|o| &o.output == output) else {
+            return;
+        };
+        match event {
+            wl_output::Event::Geometry { x, y, .. } => (info.x, info.y) = (x, y),
+            wl_output::Event::Name { name } => info.name = name,
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<WlSeat, ()> for App {
+    fn event(
+        _: &mut Self,
+        seat: &WlSeat,
+        event: wl_seat::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        if let wl_seat::Event::Capabilities {
+            capabilities: WEnum::Value(caps),
+        } = event
+        {
+            if caps.contains(wl_seat::Capability::Pointer) {
+                seat.get_pointer(qh, ());
+            }
+            if caps.contains(wl_seat::Capability::Keyboard) {
+                seat.get_keyboard(qh, ());
+            }
+        }
+    }
+}
+
+impl Dispatch<WlPointer, ()> for App {
+    fn event(
+        state: &mut Self,
+        _: &WlPointer,
+        event: wl_pointer::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let sel = &mut state.selection;
+        match event {
+            wl_pointer::Event::Enter {
+                surface_x,
+                surface_y,
+                ..
+            }
+            | wl_pointer::Event::Motion {
+                surface_x,
+                surface_y,
+                ..
+            } => {
+                sel.cursor = (surface_x, surface_y);
+                if sel.anchor.is_some() {
+                    state.draw_selection();
+                }
+            }
+            wl_pointer::Event::Button {
+                button,
+                state: WEnum::Value(pressed),
+                ..
+            } => match (button, pressed) {
+                (BTN_LEFT, wl_pointer::ButtonState::Pressed) => {
+                    sel.anchor = Some(sel.cursor);
+                    state.draw_selection();
+                }
+                (BTN_LEFT, wl_pointer::ButtonState::Released) => {
+                    sel.result = Some(sel.anchor.take().and_then(|a| drag_rect(a, sel.cursor)));
+                }
+                (_, wl_pointer::ButtonState::Pressed) => sel.result = Some(None),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<WlKeyboard, ()> for App {
+    fn event(
+        state: &mut Self,
+        _: &WlKeyboard,
+        event: wl_keyboard::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wl_keyboard::Event::Key {
+            key: KEY_ESC,
+            state: WEnum::Value(wl_keyboard::KeyState::Pressed),
+            ..
+        } = event
+        {
+            state.selection.result = Some(None);
+        }
+    }
+}
+
+impl Dispatch<ZwlrLayerSurfaceV1, ()> for App {
+    fn event(
+        state: &mut Self,
+        layer: &ZwlrLayerSurfaceV1,
+        event: zwlr_layer_surface_v1::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_layer_surface_v1::Event::Configure {
+                serial,
+                width,
+                height,
+            } => {
+                layer.ack_configure(serial);
+                if state.selection.size != (width, height) {
+                    state.selection.size = (width, height);
+                    let shm = state.shm.as_ref().expect("wl_shm checked at startup");
+                    state.selection.buffer =
+                        ShmBuffer::new(shm, qh, width, height, width * 4, wl_shm::Format::Argb8888)
+                            .map_err(|e| eprintln!("trixie-screenshot: overlay buffer: {e}"))
+                            .ok();
+                }
+                state.draw_selection();
+            }
+            zwlr_layer_surface_v1::Event::Closed => state.selection.result = Some(None),
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwlrScreencopyFrameV1, ()> for App {
+    fn event(
+        state: &mut Self,
+        frame: &ZwlrScreencopyFrameV1,
+        event: zwlr_screencopy_frame_v1::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_screencopy_frame_v1::Event::Buffer {
+                format: WEnum::Value(format),
+                width,
+                height,
+                stride,
+            } => {
+                state.frame.shm = Some((format, width, height, stride));
+                // Before v3 there is no buffer_done: this is the only offer.
+                if frame.version() < 3 {
+                    state.start_copy(frame, qh);
+                }
+            }
+            zwlr_screencopy_frame_v1::Event::BufferDone => state.start_copy(frame, qh),
+            zwlr_screencopy_frame_v1::Event::Flags {
+                flags: WEnum::Value(flags),
+            } => {
+                state.frame.y_invert = flags.contains(zwlr_screencopy_frame_v1::Flags::YInvert);
+            }
+            zwlr_screencopy_frame_v1::Event::Ready { .. } => state.frame.done = Some(true),
+            zwlr_screencopy_frame_v1::Event::Failed => state.frame.done = Some(false),
+            _ => {}
+        }
+    }
+}
+
+delegate_noop!(App: WlCompositor);
+delegate_noop!(App: WlShmPool);
+delegate_noop!(App: ZwlrLayerShellV1);
+delegate_noop!(App: ZwlrScreencopyManagerV1);
+delegate_noop!(App: ignore WlShm);
+delegate_noop!(App: ignore WlBuffer);
+delegate_noop!(App: ignore WlSurface);
+
+impl App {
+    fn start_copy(&mut self, frame: &ZwlrScreencopyFrameV1, qh: &QueueHandle<Self>) {
+        let (Some((format, w, h, stride)), Some(shm)) = (self.frame.shm, &self.shm) else {
+            self.frame.done = Some(false);
+            return;
+        };
+        match ShmBuffer::new(shm, qh, w, h, stride, format) {
+            Ok(buffer) => {
+                frame.copy(&buffer.buffer);
+                self.frame.buffer = Some(buffer);
+            }
+            Err(e) => {
+                eprintln!("trixie-screenshot: capture buffer: {e}");
+                self.frame.done = Some(false);
+            }
+        }
+    }
+
+    /// Dim the output and outline the rectangle being dragged.
+    fn draw_selection(&mut self) {
+        let sel = &mut self.selection;
+        let (Some((surface, _)), Some(buffer)) = (&sel.layer, &mut sel.buffer) else {
+            return;
+        };
+        let (w, h) = sel.size;
+        let data = buffer.data();
+        data.fill(0);
+        let mut canvas = ShmCanvas::new(data, w as usize * 4, w, h);
+        canvas.draw(&DrawCmd::FillRect {
+            rect: Rect::new(0, 0, w, h),
+            color: Color::rgba(0, 0, 0, 96),
+        });
+        if let Some((x, y, rw, rh)) = sel.anchor.and_then(|a| drag_rect(a, sel.cursor)) {
+            canvas.draw(&DrawCmd::StrokeRect {
+                rect: Rect::new(x as u32, y as u32, rw as u32, rh as u32),
+                color: Color::WHITE,
+                thickness: 2,
+            });
+        }
+        surface.attach(Some(&buffer.buffer), 0, 0);
+        surface.damage_buffer(0, 0, w as i32, h as i32);
+        surface.commit();
+    }
+}
+
+/// The rectangle spanned by a drag from `a` to `b`, or None if it is empty.
+fn drag_rect(a: (f64, f64), b: (f64, f64)) -> Option<(i32, i32, i32, i32)> {
+    let (x0, y0) = (a.0.min(b.0).max(0.0) as i32, a.1.min(b.1).max(0.0) as i32);
+    let (x1, y1) = (a.0.max(b.0) as i32, a.1.max(b.1) as i32);
+    (x1 > x0 && y1 > y0).then_some((x0, y0, x1 - x0, y1 - y0))
+}
+
+// ── capture ───────────────────────────────────────────────────────────────────
+
+fn dispatch_until(
+    queue: &mut EventQueue<App>,
+    app: &mut App,
+    done: impl Fn(&App) -> bool,
+) -> Result<(), String> {
+    while !done(app) {
+        queue.blocking_dispatch(app).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Let the user drag out a rectangle on `output`; None if they cancelled.
+fn select_region(
+    queue: &mut EventQueue<App>,
+    app: &mut App,
+    output: &WlOutput,
+) -> Result<Option<(i32, i32, i32, i32)>, String> {
+    let qh = queue.handle();
+    let (Some(compositor), Some(layer_shell)) = (&app.compositor, &app.layer_shell) else {
+        return Err("--region needs wl_compositor and zwlr_layer_shell_v1".into());
+    };
+    let surface = compositor.create_surface(&qh, ());
+    let layer = layer_shell.get_layer_surface(
+        &surface,
+        Some(output),
+        zwlr_layer_shell_v1::Layer::Overlay,
+        "trixie-screenshot".into(),
+        &qh,
+        (),
+    );
+    layer.set_anchor(
+        zwlr_layer_surface_v1::Anchor::Top
+            | zwlr_layer_surface_v1::Anchor::Bottom
+            | zwlr_layer_surface_v1::Anchor::Left
+            | zwlr_layer_surface_v1::Anchor::Right,
+    );
+    layer.set_exclusive_zone(-1);
+    layer.set_keyboard_interactivity(zwlr_layer_surface_v1::KeyboardInteractivity::Exclusive);
+    surface.commit();
+    app.selection.layer = Some((surface, layer));
+
+    dispatch_until(queue, app, |a| a.selection.result.is_some())?;
+
+    if let Some((surface, layer)) = app.selection.layer.take() {
+        layer.destroy();
+        surface.destroy();
+    }
+    app.selection.buffer = None;
+    // Let the overlay disappear before it can end up in the capture.
+    queue.roundtrip(app).map_err(|e| e.to_string())?;
+    std::thread::sleep(Duration::from_millis(50));
+    Ok(app.selection.result.take().flatten())
+}
+
+fn capture(
+    queue: &mut EventQueue<App>,
+    app: &mut App,
+    output: &WlOutput,
+    region: Option<(i32, i32, i32, i32)>,
+) -> Result<RgbImage, String> {
+    let qh = queue.handle();
+    let manager = app
+        .screencopy
+        .as_ref()
+        .ok_or("compositor has no zwlr_screencopy_manager_v1")?;
+    app.frame = Frame::default();
+    let frame = match region {
+        Some((x, y, w, h)) => manager.capture_output_region(0, output, x, y, w, h, &qh, ()),
+        None => manager.capture_output(0, output, &qh, ()),
+    };
+    dispatch_until(queue, app, |a| a.frame.done.is_some())?;
+    frame.destroy();
+
+    if app.frame.done != Some(true) {
+        return Err("capture failed".into());
+    }
+    let (Some((format, width, height, stride)), Some(mut buffer)) =
+        (app.frame.shm, app.frame.buffer.take())
+    else {
+        return Err("compositor offered no wl_shm buffer".into());
+    };
+    let rgb = to_rgb(
+        buffer.data(),
+        format,
+        width,
+        height,
+        stride,
+        app.frame.y_invert,
+    )
+    .ok_or_else(|| format!("unsupported pixel format {format:?}"))?;
+    Ok(RgbImage { width, height, rgb })
+}
+
+fn run(opts: &Options) -> Result<PathBuf, String> {
+    if opts.delay > 0 {
+        std::thread::sleep(Duration::from_secs(opts.delay));
+    }
+
+    let conn = Connection::connect_to_env().map_err(|e| format!("wayland: {e}"))?;
+    let mut queue = conn.new_event_queue();
+    let qh = queue.handle();
+    conn.display().get_registry(&qh, ());
+    let mut app = App::default();
+    // Globals, then the events of the objects bound from them.
+    queue.roundtrip(&mut app).map_err(|e| e.to_string())?;
+    queue.roundtrip(&mut app).map_err(|e| e.to_string())?;
+    if app.shm.is_none() {
+        return Err("compositor has no wl_shm".into());
+    }
+
+    let targets: Vec<(WlOutput, i32, i32)> = app
+        .outputs
+        .iter()
+        .filter(|o| match &opts.output {
+            Some(name) => &o.name == name,
+            None => true,
+        })
+        .map(|o| (o.output.clone(), o.x, o.y))
+        .collect();
+    if targets.is_empty() {
+        return Err(match &opts.output {
+            Some(name) => format!("no output named {name:?}"),
+            None => "no outputs".into(),
+        });
+    }
+
+    let img = if opts.region {
+        let output = &targets[0].0;
+        let region = select_region(&mut queue, &mut app, output)?.ok_or("selection cancelled")?;
+        capture(&mut queue, &mut app, output, Some(region))?
+    } else {
+        let mut parts = Vec::new();
+        for (output, x, y) in &targets {
+            parts.push((*x, *y, capture(&mut queue, &mut app, output, None)?));
+        }
+        stitch(parts).ok_or("nothing captured")?
+    };
+
+    let path = opts
+        .path
+        .clone()
+        .unwrap_or_else(|| default_path(opts.format));
+    write_image(&path, opts.format, &img)?;
+    Ok(path)
+}
+
+fn copy_to_clipboard(path: &Path) {
+    let result = Command::new("wl-copy")
+        .args(["--type", "text/plain"])
+        .arg(path)
+        .status();
+    match result {
+        Ok(s) if s.success() => {}
+        Ok(s) => eprintln!("trixie-screenshot: wl-copy exited with {s}"),
+        Err(e) => eprintln!("trixie-screenshot: wl-copy: {e}"),
+    }
+}
+
+fn main() {
+    let opts = match parse_args(std::env::args().skip(1)) {
+        Ok(o) => o,
+        Err(e) => {
+            eprintln!("trixie-screenshot: {e}");
+            eprintln!(
+                "usage: trixie-screenshot [--region] [--delay N] [--output NAME] \
+                 [--format png|jpg|ppm] [PATH]"
+            );
+            std::process::exit(2);
+        }
+    };
+    match run(&opts) {
+        Ok(path) => {
+            println!("{}", path.display());
+            copy_to_clipboard(&path);
+        }
+        Err(e) => {
+            eprintln!("trixie-screenshot: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ppm_has_p6_header() {
+        let img = RgbImage {
+            width: 2,
+            height: 1,
+            rgb: vec![255, 0, 0, 0, 0, 255],
+        };
+        let path = std::env::temp_dir().join(format!("trixie-shot-{}.ppm", std::process::id()));
+        write_image(&path, Format::Ppm, &img).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert!(bytes.starts_with(b"P6\n2 1\n255\n"));
+        assert_eq!(&bytes[bytes.len() - 6..], &img.rgb[..]);
+    }
+
+    #[test]
+    fn xrgb_rows_flipped_to_rgb() {
+        // Two 1×1 rows, stride padded to 8 bytes; XRGB8888 is B, G, R, X.
+        let data = [1, 2, 3, 0, 9, 9, 9, 9, 4, 5, 6, 0, 9, 9, 9, 9];
+        let rgb = to_rgb(&data, wl_shm::Format::Xrgb8888, 1, 2, 8, true).unwrap();
+        assert_eq!(rgb, [6, 5, 4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn args() {
+        let opts = parse_args(
+            ["--region", "--delay", "3", "--format", "ppm", "/tmp/x.ppm"]
+                .into_iter()
+                .map(String::from),
+        )
+        .unwrap();
+        assert!(opts.region);
+        assert_eq!(opts.delay, 3);
+        assert_eq!(opts.format, Format::Ppm);
+        assert_eq!(opts.path.as_deref(), Some(Path::new("/tmp/x.ppm")));
+        assert!(parse_args(["--format", "gif"].into_iter().map(String::from)).is_err());
+    }
+}
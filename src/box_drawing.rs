@@ -1,4 +1,4 @@
-// box_drawing.rs — synthetic pixel rendering for box/block/braille characters
+// box_drawing.rs — synthetic pixel rendering for box/block/braille/legacy characters
 
 pub fn render_box_char(ch: char, cell_w: u32, cell_h: u32) -> Option<Vec<u8>> {
     let cp = ch as u32;
@@ -6,6 +6,7 @@ pub fn render_box_char(ch: char, cell_w: u32, cell_h: u32) -> Option<Vec<u8>> {
         0x2500..=0x257F => Some(draw_box(cp, cell_w, cell_h)),
         0x2580..=0x259F => Some(draw_block(cp, cell_w, cell_h)),
         0x2800..=0x28FF => Some(draw_braille(cp, cell_w, cell_h)),
+        0x1FB00..=0x1FBFF => draw_legacy_computing(cp, cell_w, cell_h),
         0xE0B0 => Some(draw_pl_right_solid(cell_w, cell_h)),
         0xE0B2 => Some(draw_pl_left_solid(cell_w, cell_h)),
         0xE0B1 => Some(draw_pl_right_hollow(cell_w, cell_h)),
@@ -634,6 +635,232 @@ fn circle_aa(p: &mut Vec<u8>, w: u32, h: u32, cx: f32, cy: f32, r: f32) {
     }
 }
 
+// ── legacy computing ──────────────────────────────────────────────────────────
+
+/// `None` for the code points not drawn here yet; they go to the font.
+fn draw_legacy_computing(cp: u32, w: u32, h: u32) -> Option<Vec<u8>> {
+    let mut p = buf(w, h);
+    let wi = w as i32;
+    let hi = h as i32;
+    match cp {
+        0x1FB00..=0x1FB3B => sextant(&mut p, w, h, sextant_bits(cp)),
+        0x1FB3C..=0x1FB67 => smooth_mosaic(&mut p, w, h, cp),
+        // Three-quarter blocks missing the left, upper, right or lower
+        // triangle, then that triangle alone.
+        0x1FB68..=0x1FB6B => triangles(&mut p, w, h, 0b1111 & !(1 << (cp - 0x1FB68))),
+        0x1FB6C..=0x1FB6F => triangles(&mut p, w, h, 1 << (cp - 0x1FB6C)),
+        // One-eighth bars at positions 2 to 7, vertical then horizontal.
+        0x1FB70..=0x1FB75 => {
+            let (x0, x1) = eighth(wi, (cp - 0x1FB70) as i32 + 2);
+            rect(&mut p, w, h, x0, 0, x1, hi);
+        }
+        0x1FB76..=0x1FB7B => {
+            let (y0, y1) = eighth(hi, (cp - 0x1FB76) as i32 + 2);
+            rect(&mut p, w, h, 0, y0, wi, y1);
+        }
+        // Pairs of edge eighths: left and lower, left and upper, right and
+        // upper, right and lower, then upper and lower.
+        0x1FB7C..=0x1FB80 => {
+            let (left, right) = (eighth(wi, 1), eighth(wi, 8));
+            let (upper, lower) = (eighth(hi, 1), eighth(hi, 8));
+            let (col, row) = match cp {
+                0x1FB7C => (Some(left), lower),
+                0x1FB7D => (Some(left), upper),
+                0x1FB7E => (Some(right), upper),
+                0x1FB7F => (Some(right), lower),
+                _ => (None, upper),
+            };
+            if let Some((x0, x1)) = col {
+                rect(&mut p, w, h, x0, 0, x1, hi);
+            }
+            rect(&mut p, w, h, 0, row.0, wi, row.1);
+            if cp == 0x1FB80 {
+                rect(&mut p, w, h, 0, lower.0, wi, lower.1);
+            }
+        }
+        0x1FB81 => {
+            for n in [1, 3, 5, 8] {
+                let (y0, y1) = eighth(hi, n);
+                rect(&mut p, w, h, 0, y0, wi, y1);
+            }
+        }
+        // Upper, then right, blocks of 2, 3, 5, 6 and 7 eighths.
+        0x1FB82..=0x1FB8B => {
+            let idx = (cp - 0x1FB82) as usize;
+            let n = [2, 3, 5, 6, 7][idx % 5];
+            if idx < 5 {
+                rect(&mut p, w, h, 0, 0, wi, hi * n / 8);
+            } else {
+                rect(&mut p, w, h, wi - wi * n / 8, 0, wi, hi);
+            }
+        }
+        // Medium shade over the left, right, upper or lower half.
+        0x1FB8C => medium_shade(&mut p, w, h, 0, 0, wi / 2, hi),
+        0x1FB8D => medium_shade(&mut p, w, h, wi / 2, 0, wi, hi),
+        0x1FB8E => medium_shade(&mut p, w, h, 0, 0, wi, hi / 2),
+        0x1FB8F => medium_shade(&mut p, w, h, 0, hi / 2, wi, hi),
+        0x1FB90 => inverse_shade(&mut p, w, h, 0, 0, wi, hi),
+        0x1FB91 => {
+            rect(&mut p, w, h, 0, 0, wi, hi / 2);
+            inverse_shade(&mut p, w, h, 0, hi / 2, wi, hi);
+        }
+        0x1FB92 => {
+            inverse_shade(&mut p, w, h, 0, 0, wi, hi / 2);
+            rect(&mut p, w, h, 0, hi / 2, wi, hi);
+        }
+        0x1FB94 => {
+            inverse_shade(&mut p, w, h, 0, 0, wi / 2, hi);
+            rect(&mut p, w, h, wi / 2, 0, wi, hi);
+        }
+        // Checker boards: four squares a side, then the inverse.
+        0x1FB95 | 0x1FB96 => {
+            let odd = (cp - 0x1FB95) as i32;
+            for row in 0..4 {
+                for col in 0..4 {
+                    if (row + col) % 2 == odd {
+                        let (x0, y0) = (wi * col / 4, hi * row / 4);
+                        rect(&mut p, w, h, x0, y0, wi * (col + 1) / 4, hi * (row + 1) / 4);
+                    }
+                }
+            }
+        }
+        // Heavy horizontal fill: the second and fourth quarters.
+        0x1FB97 => {
+            rect(&mut p, w, h, 0, hi / 4, wi, hi / 2);
+            rect(&mut p, w, h, 0, hi * 3 / 4, wi, hi);
+        }
+        // Hourglass and bow tie.
+        0x1FB9A => triangles(&mut p, w, h, 0b1010),
+        0x1FB9B => triangles(&mut p, w, h, 0b0101),
+        _ => return None,
+    }
+    Some(p)
+}
+
+/// Sextant pattern of U+1FB00.. as six bits, top-left first, row by row.
+/// The block skips the patterns already encoded elsewhere: empty, full,
+/// and the left and right halves (21 and 42).
+fn sextant_bits(cp: u32) -> u32 {
+    let mut bits = cp - 0x1FB00 + 1;
+    if bits >= 21 {
+        bits += 1;
+    }
+    if bits >= 42 {
+        bits += 1;
+    }
+    bits
+}
+
+/// Fill the parts of a 2×3 grid set in `bits` (see `sextant_bits`).
+fn sextant(p: &mut Vec<u8>, w: u32, h: u32, bits: u32) {
+    let (wi, hi) = (w as i32, h as i32);
+    for bit in 0..6 {
+        if bits & (1 << bit) != 0 {
+            let (col, row) = (bit % 2, bit / 2);
+            let (x0, x1) = (wi * col / 2, wi * (col + 1) / 2);
+            rect(p, w, h, x0, hi * row / 3, x1, hi * (row + 1) / 3);
+        }
+    }
+}
+
+/// Smooth mosaics, U+1FB3C..=U+1FB67: the cell cut by a line between two
+/// points of the sextant grid (x in halves, y in thirds), filled on the side
+/// of a lower corner. The second 22 are the first 22 inverted.
+fn smooth_mosaic(p: &mut Vec<u8>, w: u32, h: u32, cp: u32) {
+    /// (lower right corner?, from, to)
+    type Cut = (bool, (i32, i32), (i32, i32));
+    const CUTS: [Cut; 22] = [
+        (false, (0, 2), (1, 3)),
+        (false, (0, 2), (2, 3)),
+        (false, (0, 1), (1, 3)),
+        (false, (0, 1), (2, 3)),
+        (false, (0, 0), (1, 3)),
+        (true, (0, 1), (1, 0)),
+        (true, (0, 1), (2, 0)),
+        (true, (0, 2), (1, 0)),
+        (true, (0, 2), (2, 0)),
+        (true, (0, 3), (1, 0)),
+        (true, (0, 2), (2, 1)),
+        (true, (1, 3), (2, 2)),
+        (true, (0, 3), (2, 2)),
+        (true, (1, 3), (2, 1)),
+        (true, (0, 3), (2, 1)),
+        (true, (1, 3), (2, 0)),
+        (false, (1, 0), (2, 1)),
+        (false, (0, 0), (2, 1)),
+        (false, (1, 0), (2, 2)),
+        (false, (0, 0), (2, 2)),
+        (false, (1, 0), (2, 3)),
+        (false, (0, 1), (2, 2)),
+    ];
+    let idx = (cp - 0x1FB3C) as usize;
+    let (right, (ax, ay), (bx, by)) = CUTS[idx % 22];
+    let inverted = idx >= 22;
+    // Work in sixths of the cell so every grid point is an integer.
+    let side = |x: f32, y: f32| {
+        (bx - ax) as f32 * 3.0 * (y - ay as f32 * 2.0)
+            - (by - ay) as f32 * 2.0 * (x - ax as f32 * 3.0)
+    };
+    let corner = side(if right { 6.0 } else { 0.0 }, 6.0);
+    for y in 0..h as i32 {
+        for x in 0..w as i32 {
+            let px = (x as f32 + 0.5) * 6.0 / w as f32;
+            let py = (y as f32 + 0.5) * 6.0 / h as f32;
+            if (side(px, py) * corner > 0.0) != inverted {
+                set(p, w, h, x, y, 0xFF);
+            }
+        }
+    }
+}
+
+/// Fill the triangles set in `mask` of the four meeting at the cell centre:
+/// bit 0 left, 1 upper, 2 right, 3 lower.
+fn triangles(p: &mut Vec<u8>, w: u32, h: u32, mask: u32) {
+    for y in 0..h as i32 {
+        for x in 0..w as i32 {
+            let u = (x as f32 + 0.5) / w as f32;
+            let v = (y as f32 + 0.5) / h as f32;
+            let q = match (u < v, u < 1.0 - v) {
+                (true, true) => 0,   // left
+                (false, true) => 1,  // upper
+                (false, false) => 2, // right
+                (true, false) => 3,  // lower
+            };
+            if mask & (1 << q) != 0 {
+                set(p, w, h, x, y, 0xFF);
+            }
+        }
+    }
+}
+
+/// The `n`th of eight bands across `len`, counting from 1; never empty.
+fn eighth(len: i32, n: i32) -> (i32, i32) {
+    let start = len * (n - 1) / 8;
+    (start, (len * n / 8).max(start + 1))
+}
+
+/// Medium shade as `shade` draws it, within a rect.
+fn medium_shade(p: &mut Vec<u8>, w: u32, h: u32, x0: i32, y0: i32, x1: i32, y1: i32) {
+    for y in y0..y1 {
+        for x in x0..x1 {
+            if (x + y) % 2 == 0 {
+                set(p, w, h, x, y, 128);
+            }
+        }
+    }
+}
+
+/// Medium shade on the opposite checker parity to `shade`, within a rect.
+fn inverse_shade(p: &mut Vec<u8>, w: u32, h: u32, x0: i32, y0: i32, x1: i32, y1: i32) {
+    for y in y0..y1 {
+        for x in x0..x1 {
+            if (x + y) % 2 == 1 {
+                set(p, w, h, x, y, 128);
+            }
+        }
+    }
+}
+
 // ── powerline ─────────────────────────────────────────────────────────────────
 
 fn draw_pl_right_solid(w: u32, h: u32) -> Vec<u8> {
@@ -685,3 +912,117 @@ fn draw_pl_left_hollow(w: u32, h: u32) -> Vec<u8> {
     diag_seg(&mut p, w, h, wi - 1, hi - 1, 0, hi / 2, t);
     p
 }
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filled_columns(p: &[u8], w: u32, h: u32) -> u32 {
+        (0..w)
+            .filter(|&x| (0..h).any(|y| p[((y * w + x) * 4 + 3) as usize] > 0))
+            .count() as u32
+    }
+
+    #[test]
+    fn legacy_one_eighth_bars() {
+        let (w, h) = (24, 48);
+        // VERTICAL ONE EIGHTH BLOCK-2: the second column band, full height.
+        let p = render_box_char('\u{1FB70}', w, h).unwrap();
+        assert_eq!(filled_columns(&p, w, h), 3);
+        assert!((0..h).all(|y| alpha(&p, w, 3, y) == 0xFF));
+        assert_eq!(alpha(&p, w, 2, 0), 0);
+        assert_eq!(alpha(&p, w, 6, 0), 0);
+        // HORIZONTAL ONE EIGHTH BLOCK-7: rows 36..42 across the cell.
+        let p = render_box_char('\u{1FB7B}', w, h).unwrap();
+        assert_eq!(alpha(&p, w, 0, 36), 0xFF);
+        assert_eq!(alpha(&p, w, w - 1, 41), 0xFF);
+        assert_eq!(alpha(&p, w, 0, 35), 0);
+        assert_eq!(alpha(&p, w, 0, 42), 0);
+        // HORIZONTAL ONE EIGHTH BLOCK-1358.
+        let p = render_box_char('\u{1FB81}', w, h).unwrap();
+        let rows: Vec<u32> = (0..h).filter(|&y| alpha(&p, w, 0, y) > 0).collect();
+        assert_eq!(
+            rows,
+            [
+                0, 1, 2, 3, 4, 5, 12, 13, 14, 15, 16, 17, 24, 25, 26, 27, 28, 29, 42, 43, 44, 45,
+                46, 47
+            ]
+        );
+    }
+
+    fn alpha(p: &[u8], w: u32, x: u32, y: u32) -> u8 {
+        p[((y * w + x) * 4 + 3) as usize]
+    }
+
+    #[test]
+    fn legacy_sextants() {
+        let (w, h) = (12, 24);
+        // SEXTANT-1: the top-left sixth only.
+        let p = render_box_char('\u{1FB00}', w, h).unwrap();
+        assert_eq!(alpha(&p, w, 0, 0), 0xFF);
+        assert_eq!(alpha(&p, w, 5, 7), 0xFF);
+        assert_eq!(alpha(&p, w, 6, 0), 0);
+        assert_eq!(alpha(&p, w, 0, 8), 0);
+        // SEXTANT-23456: everything but the top-left sixth.
+        let p = render_box_char('\u{1FB3B}', w, h).unwrap();
+        assert_eq!(alpha(&p, w, 0, 0), 0);
+        assert_eq!(alpha(&p, w, 6, 0), 0xFF);
+        assert_eq!(alpha(&p, w, 0, 8), 0xFF);
+        assert_eq!(alpha(&p, w, w - 1, h - 1), 0xFF);
+        // SEXTANT-35, then SEXTANT-235 after the skipped left half.
+        assert_eq!(sextant_bits(0x1FB13), 0b010100);
+        assert_eq!(sextant_bits(0x1FB14), 0b010110);
+    }
+
+    #[test]
+    fn legacy_smooth_mosaic() {
+        let (w, h) = (12, 24);
+        // LOWER LEFT BLOCK DIAGONAL UPPER CENTRE TO LOWER RIGHT.
+        let p = render_box_char('\u{1FB50}', w, h).unwrap();
+        assert_eq!(alpha(&p, w, 0, 0), 0xFF);
+        assert_eq!(alpha(&p, w, 0, h - 1), 0xFF);
+        assert_eq!(alpha(&p, w, w - 1, 0), 0);
+        // Its UPPER RIGHT counterpart is the exact complement.
+        let q = render_box_char('\u{1FB66}', w, h).unwrap();
+        assert!(
+            (0..w * h).all(|i| (p[(i * 4 + 3) as usize] == 0) != (q[(i * 4 + 3) as usize] == 0))
+        );
+    }
+
+    #[test]
+    fn legacy_unassigned_falls_back_to_font() {
+        assert!(render_box_char('\u{1FB93}', 12, 24).is_none());
+        assert!(!crate::shaper::is_synthetic(0x1FB93));
+        assert!(crate::shaper::is_synthetic(0x1FB00));
+    }
+
+    #[test]
+    fn legacy_edge_and_fractional_blocks() {
+        let (w, h) = (16, 32);
+        // LEFT AND LOWER ONE EIGHTH BLOCK.
+        let p = render_box_char('\u{1FB7C}', w, h).unwrap();
+        assert_eq!(alpha(&p, w, 0, 0), 0xFF);
+        assert_eq!(alpha(&p, w, w - 1, h - 1), 0xFF);
+        assert_eq!(alpha(&p, w, w - 1, 0), 0);
+        assert_eq!(alpha(&p, w, w / 2, h / 2), 0);
+        // UPPER AND LOWER ONE EIGHTH BLOCK.
+        let p = render_box_char('\u{1FB80}', w, h).unwrap();
+        assert_eq!(alpha(&p, w, w / 2, 0), 0xFF);
+        assert_eq!(alpha(&p, w, w / 2, h - 1), 0xFF);
+        assert_eq!(alpha(&p, w, 0, h / 2), 0);
+        // UPPER THREE EIGHTHS and RIGHT FIVE EIGHTHS BLOCK.
+        let p = render_box_char('\u{1FB83}', w, h).unwrap();
+        assert_eq!(alpha(&p, w, 0, 11), 0xFF);
+        assert_eq!(alpha(&p, w, 0, 12), 0);
+        let p = render_box_char('\u{1FB89}', w, h).unwrap();
+        assert_eq!(filled_columns(&p, w, h), 10);
+        assert_eq!(alpha(&p, w, w - 1, 0), 0xFF);
+        // LOWER HALF MEDIUM SHADE: a checker below the middle only.
+        let p = render_box_char('\u{1FB8F}', w, h).unwrap();
+        assert!((0..h / 2).all(|y| (0..w).all(|x| alpha(&p, w, x, y) == 0)));
+        assert_eq!(alpha(&p, w, 0, h / 2), 128);
+        assert_eq!(alpha(&p, w, 1, h / 2), 0);
+    }
+}
@@ -0,0 +1,315 @@
+// clipboard.rs — clipboard history that outlives the copying client
+//
+// Every new clipboard selection is read once, in one mime type, into a ring
+// of the last `clipboard_history` entries. When the owning client goes away
+// and the selection is cleared, the compositor offers the newest entry
+// itself (see `SelectionHandler` in handlers.rs), so a copy from a closed
+// terminal can still be pasted. Contents over `clipboard_max_bytes` are kept
+// as a hash only: they show up in the history but can't be pasted back.
+//
+// The history is saved to $XDG_CACHE_HOME/trixie/clipboard.json on exit and
+// loaded again on startup, readable by the user only. Sources flagged by a
+// password manager are never recorded.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs::{DirBuilder, OpenOptions, Permissions},
+    hash::Hasher,
+    io::Write,
+    os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Mime types worth storing, most preferred first. Anything else falls back
+/// to the first type the source offers.
+const PREFERRED_MIME: &[&str] = &[
+    "text/plain;charset=utf-8",
+    "UTF8_STRING",
+    "text/plain",
+    "image/png",
+];
+
+/// Offered alongside secrets by KeePassXC and friends; such sources stay out
+/// of the history.
+const PASSWORD_HINT_MIME: &str = "x-kde-passwordManagerHint";
+
+/// The type to read out of a source offering `mime_types`, or None if it
+/// shouldn't be recorded at all.
+pub fn pick_mime(mime_types: &[String]) -> Option<&str> {
+    if mime_types.iter().any(|m| m == PASSWORD_HINT_MIME) {
+        return None;
+    }
+    PREFERRED_MIME
+        .iter()
+        .find_map(|p| mime_types.iter().find(|m| m == p))
+        .or_else(|| mime_types.first())
+        .map(String::as_str)
+}
+
+// ── entries ───────────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClipboardContent {
+    Text(String),
+    Bytes(Vec<u8>),
+    /// Over `clipboard_max_bytes`: only a hash of the data was kept.
+    Hash(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClipboardEntry {
+    pub mime_type: String,
+    /// Length of the original data in bytes.
+    pub size: usize,
+    #[serde(flatten)]
+    pub content: ClipboardContent,
+}
+
+impl ClipboardEntry {
+    /// The data to hand to a paste, or None for hashed entries.
+    pub fn data(&self) -> Option<&[u8]> {
+        match &self.content {
+            ClipboardContent::Text(t) => Some(t.as_bytes()),
+            ClipboardContent::Bytes(b) => Some(b),
+            ClipboardContent::Hash(_) => None,
+        }
+    }
+}
+
+/// Collects one selection as it is read from the source client. Data past
+/// `max_bytes` is hashed instead of buffered, so a huge copy never sits in
+/// memory.
+pub struct EntryBuilder {
+    mime_type: String,
+    max_bytes: usize,
+    buf: Vec<u8>,
+    size: usize,
+    hasher: DefaultHasher,
+}
+
+impl EntryBuilder {
+    pub fn new(mime_type: &str, max_bytes: usize) -> Self {
+        Self {
+            mime_type: mime_type.to_owned(),
+            max_bytes,
+            buf: Vec::new(),
+            size: 0,
+            hasher: DefaultHasher::new(),
+        }
+    }
+
+    pub fn feed(&mut self, chunk: &[u8]) {
+        self.size += chunk.len();
+        self.hasher.write(chunk);
+        if self.size <= self.max_bytes {
+            self.buf.extend_from_slice(chunk);
+        } else {
+            self.buf = Vec::new();
+        }
+    }
+
+    /// The finished entry; None for an empty selection.
+    pub fn finish(self) -> Option<ClipboardEntry> {
+        if self.size == 0 {
+            return None;
+        }
+        let content = if self.size > self.max_bytes {
+            ClipboardContent::Hash(format!("{:016x}", self.hasher.finish()))
+        } else if self.mime_type.starts_with("text/") || self.mime_type == "UTF8_STRING" {
+            match String::from_utf8(self.buf) {
+                Ok(s) => ClipboardContent::Text(s),
+                Err(e) => ClipboardContent::Bytes(e.into_bytes()),
+            }
+        } else {
+            ClipboardContent::Bytes(self.buf)
+        };
+        Some(ClipboardEntry {
+            mime_type: self.mime_type,
+            size: self.size,
+            content,
+        })
+    }
+}
+
+// ── history ───────────────────────────────────────────────────────────────────
+
+pub struct ClipboardManager {
+    /// Newest first.
+    entries: Vec<ClipboardEntry>,
+    capacity: usize,
+}
+
+impl ClipboardManager {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Vec::new(),
+            capacity,
+        }
+    }
+
+    /// Newest first; index 0 is the current selection.
+    pub fn history(&self) -> &[ClipboardEntry] {
+        &self.entries
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.entries.truncate(capacity);
+    }
+
+    /// Record a new selection. Copying something already in the history
+    /// moves it to the front instead of storing it twice.
+    pub fn push(&mut self, entry: ClipboardEntry) {
+        self.entries.retain(|e| e != &entry);
+        self.entries.insert(0, entry);
+        self.entries.truncate(self.capacity);
+    }
+
+    /// Move entry `idx` to the front and return it, to be offered as the
+    /// selection again.
+    pub fn set_active(&mut self, idx: usize) -> Option<&ClipboardEntry> {
+        if idx >= self.entries.len() {
+            return None;
+        }
+        let entry = self.entries.remove(idx);
+        self.entries.insert(0, entry);
+        self.entries.first()
+    }
+
+    // ── persistence ───────────────────────────────────────────────────────────
+
+    pub fn cache_path() -> PathBuf {
+        let base = std::env::var("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| {
+                PathBuf::from(std::env::var("HOME").unwrap_or_default()).join(".cache")
+            });
+        base.join("trixie").join("clipboard.json")
+    }
+
+    /// The history saved at `path`, cut to `capacity`; empty if there is none
+    /// or it can't be read.
+    pub fn load(path: &Path, capacity: usize) -> Self {
+        let mut manager = Self::new(capacity);
+        let Ok(text) = std::fs::read_to_string(path) else {
+            return manager;
+        };
+        match serde_json::from_str::<Vec<ClipboardEntry>>(&text) {
+            Ok(entries) => {
+                manager.entries = entries;
+                manager.entries.truncate(capacity);
+            }
+            Err(e) => tracing::warn!("{}: bad clipboard history: {e}", path.display()),
+        }
+        manager
+    }
+
+    /// Write the history to `path`, creating its directory 0700 and the
+    /// file 0600: it holds whatever was copied, passwords included.
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+        }
+        let json = serde_json::to_string(&self.entries).map_err(std::io::Error::other)?;
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)?;
+        // `mode` only applies to a new file; tighten one left by an older build.
+        file.set_permissions(Permissions::from_mode(0o600))?;
+        file.write_all(json.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> ClipboardEntry {
+        let mut b = EntryBuilder::new("text/plain;charset=utf-8", 16);
+        b.feed(s.as_bytes());
+        b.finish().unwrap()
+    }
+
+    #[test]
+    fn history_is_a_ring_without_duplicates() {
+        let mut clip = ClipboardManager::new(3);
+        for s in ["a", "b", "c", "d"] {
+            clip.push(text(s));
+        }
+        assert_eq!(clip.history(), [text("d"), text("c"), text("b")]);
+
+        clip.push(text("b"));
+        assert_eq!(clip.history()[0], text("b"));
+        assert_eq!(clip.history().len(), 3);
+
+        assert_eq!(clip.set_active(2), Some(&text("c")));
+        assert_eq!(clip.set_active(3), None);
+        clip.set_capacity(1);
+        assert_eq!(clip.history(), [text("c")]);
+    }
+
+    #[test]
+    fn large_entries_keep_only_a_hash() {
+        let mut b = EntryBuilder::new("image/png", 4);
+        b.feed(b"abc");
+        b.feed(b"de");
+        let entry = b.finish().unwrap();
+        assert_eq!(entry.size, 5);
+        assert!(matches!(entry.content, ClipboardContent::Hash(_)));
+        assert_eq!(entry.data(), None);
+
+        assert_eq!(EntryBuilder::new("text/plain", 4).finish(), None);
+    }
+
+    #[test]
+    fn entries_round_trip_through_json() {
+        let entry = text("hi");
+        let json = serde_json::to_string(&entry).unwrap();
+        assert_eq!(
+            json,
+            r#"{"mime_type":"text/plain;charset=utf-8","size":2,"text":"hi"}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<ClipboardEntry>(&json).unwrap(),
+            entry
+        );
+    }
+
+    #[test]
+    fn prefers_utf8_text() {
+        let offered = ["image/png".to_string(), "text/plain;charset=utf-8".into()];
+        assert_eq!(pick_mime(&offered), Some("text/plain;charset=utf-8"));
+        assert_eq!(
+            pick_mime(&["x-special/nautilus".into()]),
+            Some("x-special/nautilus")
+        );
+        assert_eq!(pick_mime(&[]), None);
+
+        let secret = ["text/plain".to_string(), "x-kde-passwordManagerHint".into()];
+        assert_eq!(pick_mime(&secret), None);
+    }
+
+    #[test]
+    fn saved_history_is_private() {
+        let dir = std::env::temp_dir().join(format!("trixie-clip-{}", std::process::id()));
+        let path = dir.join("trixie").join("clipboard.json");
+        let mut clip = ClipboardManager::new(2);
+        clip.push(text("hunter2"));
+        clip.save(&path).unwrap();
+
+        let mode = |p: &Path| std::fs::metadata(p).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&path), 0o600);
+        assert_eq!(mode(path.parent().unwrap()), 0o700);
+        assert_eq!(
+            ClipboardManager::load(&path, 2).history(),
+            [text("hunter2")]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -3,27 +3,109 @@
 // Config is loaded from ~/.config/trixie/*.conf (or $TRIXIE_CONFIG_DIR).
 // Uses Hyprland-style key = value / section { } syntax.
 
-use crate::shader_config::ShaderRegistry;
-use crate::util::{expand_tilde, hex4, resolve_path, shell_words, strip_comment};
+use crate::shader_config::{BlurConfig, ShaderRegistry};
+use crate::twm_drop_in::{Easing, Layout, WorkspaceRule};
+use crate::util::{expand_tilde, resolve_path, shell_words, strip_comment, try_hex4};
+use serde::{Serialize, Serializer};
+use smithay::backend::input::KeyState;
+use smithay::input::keyboard::ModifiersState;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use xkbcommon::xkb;
 
 // ── top-level ─────────────────────────────────────────────────────────────────
 
-#[derive(Debug, Clone)]
+/// Serialisable for the IPC `get_config` reply; `lock_pin` is left out.
+#[derive(Debug, Clone, Serialize)]
 pub struct Config {
     pub terminal: String,
     pub seat_name: String,
     pub background_color: [f32; 4],
     pub target_hz: Option<u64>,
     pub vsync: VsyncMode,
+    /// Variable refresh rate on outputs that support it; output blocks override.
+    pub vrr: bool,
+    /// Seconds without input before rendering is suspended. None = never.
+    pub idle_timeout: Option<u64>,
+    /// Let a lone window covering an output be scanned out without compositing.
+    pub direct_scanout: bool,
+    /// Clipboard entries kept in the history.
+    pub clipboard_history: usize,
+    /// Entries larger than this are stored as a hash only.
+    pub clipboard_max_bytes: usize,
+    /// Draw the glyph atlas in the bottom-right corner.
+    pub debug_atlas_overlay: bool,
+    pub debug_shader_timing: bool,
+    /// Offer zwp_input_method_v2 for an IME (fcitx5, ibus). Whoever binds it
+    /// receives what text-input clients send, password fields included, and
+    /// can commit text into the focused window, and any client may bind it.
+    /// So it is off unless asked for; checked per bind, so a reload applies
+    /// to the next IME to start.
+    pub input_method: bool,
+    /// Show the MPRIS now-playing title in the TWM bar.
+    pub mpris: bool,
+    /// Bus name suffix of the player to follow (`spotify`); None = first found.
+    pub mpris_player: Option<String>,
+    pub mpris_max_title_len: usize,
+    /// Named preset; the individual `anim_*` keys below override it.
+    pub animation_profile: Option<AnimationProfile>,
+    pub anim_duration_ms: Option<f64>,
+    pub anim_ease: Option<Easing>,
+    pub anim_enabled: Option<bool>,
+    /// Length of the workspace slide; None = `twm_drop_in::WORKSPACE_ANIM_MS`.
+    pub workspace_anim_duration_ms: Option<f64>,
+    /// bcrypt hash of the TWM lock PIN (`htpasswd -bnBC 10 "" PIN`). Without
+    /// one, `Lock` is refused. Costs above `LOCK_PIN_MAX_COST` are rejected:
+    /// the PIN is checked on the event loop, and each step doubles the stall
+    /// (about 60 ms at 10) every Enter on the curtain costs.
+    #[serde(skip)]
+    pub lock_pin: Option<String>,
+    /// XCursor theme drawn for named cursors, and its nominal size in pixels.
+    pub cursor_theme: String,
+    pub cursor_size: u32,
+    /// Grid step in pixels for dragged floating windows; 0 = off.
+    pub float_snap: u32,
+    /// Drop the TWM gaps on workspaces showing a single pane.
+    pub no_gap_when_single: bool,
+    /// Panes remembered for `focus_last` and alt-tab, newest first.
+    pub focus_history_len: usize,
+    /// Columns of the workspace grid walked by `workspace_left` etc.
+    pub workspace_grid_cols: usize,
+    /// Closed panes `undo_close` can bring back; 0 = off.
+    pub undo_depth: usize,
+    /// Chrome font size in points, scaled by each output's physical DPI.
+    /// Outputs that report no physical size use `font.size` pixels instead.
+    pub font_size_pt: f32,
+    /// Glob patterns for layer-shell namespaces allowed to map; empty = any.
+    pub layer_shell_namespaces: Vec<String>,
+    /// Built-in chrome layers drawn over the TWM (`clock`, `cpu`), in order.
+    pub chrome_layers: Vec<String>,
     pub vibrance: VibranceConfig,
+    pub gesture: GestureConfig,
+    pub bar: BarConfig,
     pub keyboard: KeyboardConfig,
     pub font: FontConfig,
     pub keybinds: Vec<Keybind>,
     pub window_rules: Vec<WindowRule>,
+    /// `output "DP-1" { }` and `monitor { name = DP-1 }` sections, keyed by
+    /// connector name.
+    pub outputs: HashMap<String, OutputConfig>,
+    /// One per `workspace_rule { }` or `workspace "N" { }` section; applied
+    /// after `workspace_layouts`.
+    pub workspace_rules: Vec<WorkspaceRule>,
     pub exec: Vec<ExecEntry>,
     pub exec_once: Vec<ExecEntry>,
+    /// `env { FOO = bar }` entries, set on every process the compositor
+    /// spawns; a bind's own `spawn env` pairs override them.
+    pub global_env: Vec<(String, String)>,
     pub shaders: ShaderRegistry,
+    /// Layout for workspaces without a `workspace_layout` entry.
+    pub default_layout: Layout,
+    /// Starting layout per workspace, keyed by 1-based workspace number.
+    pub workspace_layouts: HashMap<u8, Layout>,
+    /// User icon overrides from `icon_map = name = 0xF017`.
+    pub icon_map: Vec<(String, char)>,
 }
 
 impl Config {
@@ -39,18 +121,144 @@ impl Config {
         };
         std::time::Duration::from_micros(1_000_000 / hz)
     }
+
+    /// Effective animation settings: the profile (or the TWM defaults),
+    /// then any individually set `anim_*` key on top.
+    pub fn animation(&self) -> AnimationConfig {
+        let base = self
+            .animation_profile
+            .map(AnimationProfile::settings)
+            .unwrap_or_default();
+        AnimationConfig {
+            duration_ms: self.anim_duration_ms.unwrap_or(base.duration_ms),
+            ease: self.anim_ease.unwrap_or(base.ease),
+            enabled: self.anim_enabled.unwrap_or(base.enabled),
+        }
+    }
+
+    /// Clear colour for the output called `output_name`: its output block's
+    /// `background_color` if one sets it, else the global one.
+    pub fn background_for(&self, output_name: &str) -> [f32; 4] {
+        self.outputs
+            .get(output_name)
+            .and_then(|o| o.background_color)
+            .unwrap_or(self.background_color)
+    }
+
+    /// Whether VRR is wanted on `output_name`: its output block's `vrr` if
+    /// one sets it, else the global one.
+    pub fn vrr_for(&self, output_name: &str) -> bool {
+        self.outputs
+            .get(output_name)
+            .and_then(|o| o.vrr)
+            .unwrap_or(self.vrr)
+    }
+
+    /// Whether a layer surface with `namespace` may be mapped.
+    pub fn layer_namespace_allowed(&self, namespace: &str) -> bool {
+        self.layer_shell_namespaces.is_empty()
+            || self
+                .layer_shell_namespaces
+                .iter()
+                .filter_map(|p| glob::Pattern::new(p).ok())
+                .any(|p| p.matches(namespace))
+    }
+
+    /// Which expensive subsystems a reload from `old` to `new` must touch.
+    pub fn diff(old: &Config, new: &Config) -> ConfigDiff {
+        ConfigDiff {
+            keybinds_changed: old.keybinds != new.keybinds,
+            shaders_changed: old.shaders != new.shaders,
+            font_changed: old.font != new.font || old.font_size_pt != new.font_size_pt,
+            window_rules_changed: old.window_rules != new.window_rules,
+            keyboard_changed: old.keyboard != new.keyboard,
+            outputs_changed: old.outputs != new.outputs,
+        }
+    }
+}
+
+/// Result of `Config::diff`. Cheap fields (colours, layouts, …) are always
+/// copied on reload and are not tracked here.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConfigDiff {
+    pub keybinds_changed: bool,
+    pub shaders_changed: bool,
+    pub font_changed: bool,
+    pub window_rules_changed: bool,
+    pub keyboard_changed: bool,
+    /// Any `output` / `monitor` block was added, removed or edited.
+    pub outputs_changed: bool,
+}
+
+// ── animation ─────────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnimationConfig {
+    pub duration_ms: f64,
+    pub ease: Easing,
+    pub enabled: bool,
+}
+
+impl Default for AnimationConfig {
+    fn default() -> Self {
+        AnimationProfile::Fast.settings()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum AnimationProfile {
+    Fast,
+    Smooth,
+    Instant,
+    Disabled,
+}
+
+impl AnimationProfile {
+    pub fn from_name(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "fast" => Some(Self::Fast),
+            "smooth" => Some(Self::Smooth),
+            "instant" => Some(Self::Instant),
+            "disabled" | "off" | "none" => Some(Self::Disabled),
+            _ => None,
+        }
+    }
+
+    pub fn settings(self) -> AnimationConfig {
+        let (duration_ms, ease, enabled) = match self {
+            Self::Fast => (120.0, Easing::EaseOutCubic, true),
+            Self::Smooth => (200.0, Easing::EaseInOut, true),
+            Self::Instant => (0.0, Easing::Linear, true),
+            Self::Disabled => (0.0, Easing::Linear, false),
+        };
+        AnimationConfig {
+            duration_ms,
+            ease,
+            enabled,
+        }
+    }
 }
 
 // ── font ──────────────────────────────────────────────────────────────────────
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct FontConfig {
     pub path: String,
     pub bold_path: Option<String>,
     pub italic_path: Option<String>,
+    /// Color emoji font (CBDT or sbix bitmaps); searched for when unset.
+    pub emoji_path: Option<String>,
+    /// Faces tried in order for codepoints the regular face lacks.
+    pub fallback: Vec<String>,
     pub size: f32,
     pub line_spacing: Option<f32>,
     pub dpi: Option<u32>,
+    /// `wght` axis of a variable font (400 regular, 700 bold).
+    pub weight: Option<u32>,
+    /// `wdth` axis of a variable font, in percent of normal.
+    pub width: Option<u32>,
+    /// Lay Hebrew and Arabic runs out right-to-left in UI text.
+    pub bidi_enabled: bool,
 }
 
 impl Default for FontConfig {
@@ -59,16 +267,21 @@ impl Default for FontConfig {
             path: "/usr/share/fonts/TTF/JetBrainsMono-Regular.ttf".into(),
             bold_path: None,
             italic_path: None,
+            emoji_path: None,
+            fallback: vec![],
             size: 14.0,
             line_spacing: Some(1.1),
             dpi: Some(96),
+            weight: None,
+            width: None,
+            bidi_enabled: true,
         }
     }
 }
 
 // ── vibrance ──────────────────────────────────────────────────────────────────
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct VibranceConfig {
     pub enabled: bool,
     pub strength: f32,
@@ -85,9 +298,65 @@ impl Default for VibranceConfig {
     }
 }
 
+// ── touchpad gestures ─────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct GestureConfig {
+    /// Horizontal travel, in libinput's logical pixels, that switches workspace.
+    pub swipe_threshold: f64,
+    /// Finger count for the workspace swipe and the layout pinch.
+    pub fingers: u32,
+}
+
+impl Default for GestureConfig {
+    fn default() -> Self {
+        Self {
+            swipe_threshold: 100.0,
+            fingers: 3,
+        }
+    }
+}
+
+// ── bar ───────────────────────────────────────────────────────────────────────
+
+/// `bar { }` section: which modules the TWM bar draws where, in order. Names
+/// are those in `twm_drop_in::BAR_MODULE_NAMES`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BarConfig {
+    pub modules_left: Vec<String>,
+    pub modules_center: Vec<String>,
+    pub modules_right: Vec<String>,
+    /// Seconds between reads of the battery's sysfs files.
+    pub battery_poll_secs: u64,
+}
+
+impl BarConfig {
+    /// Left, centre and right module lists, as `TwmState::bar_modules`.
+    pub fn modules(&self) -> [Vec<String>; 3] {
+        [
+            self.modules_left.clone(),
+            self.modules_center.clone(),
+            self.modules_right.clone(),
+        ]
+    }
+}
+
+impl Default for BarConfig {
+    fn default() -> Self {
+        let [left, center, right] = crate::twm_drop_in::BAR_MODULES
+            .map(|side| side.iter().map(|m| m.to_string()).collect());
+        Self {
+            modules_left: left,
+            modules_center: center,
+            modules_right: right,
+            battery_poll_secs: 30,
+        }
+    }
+}
+
 // ── vsync mode ────────────────────────────────────────────────────────────────
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
 pub enum VsyncMode {
     #[default]
     On,
@@ -97,15 +366,17 @@ pub enum VsyncMode {
 
 // ── exec entry ────────────────────────────────────────────────────────────────
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ExecEntry {
     pub command: String,
     pub args: Vec<String>,
+    /// `exec_once` only: kill and respawn if still running after this long.
+    pub autostart_timeout_ms: Option<u64>,
 }
 
 // ── keyboard ──────────────────────────────────────────────────────────────────
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum Modifier {
     Super,
     Alt,
@@ -118,14 +389,19 @@ impl Default for Modifier {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct KeyboardConfig {
     pub layout: Option<String>,
     pub variant: Option<String>,
     pub options: Option<String>,
+    /// Complete `xkb_keymap { ... }` file; overrides layout/variant/options.
+    pub xkb_file: Option<PathBuf>,
     pub repeat_delay: u32,
     pub repeat_rate: u32,
     pub modifier: Modifier,
+    /// Physical modifier that acts as Super for keybinds, for keyboards
+    /// without a Super key (`modifier_as_super = alt`).
+    pub modifier_as_super: Option<Modifier>,
 }
 
 impl Default for KeyboardConfig {
@@ -134,45 +410,173 @@ impl Default for KeyboardConfig {
             layout: None,
             variant: None,
             options: None,
+            xkb_file: None,
             repeat_delay: 200,
             repeat_rate: 25,
             modifier: Modifier::default(),
+            modifier_as_super: None,
         }
     }
 }
 
 // ── keybind ───────────────────────────────────────────────────────────────────
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Keybind {
     pub mods: Vec<String>,
     pub key: String,
     pub action: KeyAction,
+    /// Fire when `key` is released (`bind_r`) rather than pressed.
+    pub on_release: bool,
+    /// File and line of the `bind` entry; empty for built-in defaults.
+    pub origin: (PathBuf, usize),
+}
+
+/// `origin` only says where a bind came from, so moving it within the file
+/// is not a keybind change.
+impl PartialEq for Keybind {
+    fn eq(&self, o: &Self) -> bool {
+        self.mods == o.mods
+            && self.key == o.key
+            && self.action == o.action
+            && self.on_release == o.on_release
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub enum KeyAction {
     Quit,
     CloseWindow,
     ReloadConfig,
-    Spawn { command: String, args: Vec<String> },
+    Spawn {
+        command: String,
+        args: Vec<String>,
+        /// `spawn env KEY=VALUE … cmd` overrides, in order.
+        env: Vec<(String, String)>,
+    },
+    /// Type `key` with `mods` held into the first window whose app_id is
+    /// `target_app_id`, without moving focus to it.
+    SendKey {
+        target_app_id: String,
+        key: String,
+        mods: Vec<String>,
+    },
+    /// Toggle the TWM expose overview.
+    Overview,
+    /// Put the active workspace in a grid layout this many columns wide.
+    GridCols(u16),
+    /// Show the newest scratchpad pane floating, or stash the shown one.
+    ToggleScratchpad,
+    /// Stash the focused pane on the scratchpad workspace.
+    SendToScratchpad,
+    /// Focus the previously focused pane.
+    FocusLast,
+    /// Open the alt-tab popup or step through it; releasing the bind's
+    /// modifiers focuses the selection.
+    AltTabNext,
+    AltTabPrev,
+    /// Show the focused pane on every workspace, or stop.
+    ToggleSticky,
+    /// Reopen the most recently closed shell pane.
+    UndoClose,
+    /// Switch to a neighbour on the workspace grid (`workspace_grid_cols`).
+    WorkspaceLeft,
+    WorkspaceRight,
+    WorkspaceUp,
+    WorkspaceDown,
+}
+
+// ── output ────────────────────────────────────────────────────────────────────
+
+/// Settings for one connector, from `output "DP-1" { }` or the older
+/// spelling `monitor { name = DP-1 }`. Anything left unset keeps the DRM
+/// default, or for `background_color` and `vrr` the global setting.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct OutputConfig {
+    pub mode: Option<OutputMode>,
+    pub position: Option<(i32, i32)>,
+    pub scale: Option<f64>,
+    #[serde(serialize_with = "serialize_transform")]
+    pub transform: Option<smithay::utils::Transform>,
+    pub background_color: Option<[f32; 4]>,
+    pub vrr: Option<bool>,
+}
+
+impl OutputConfig {
+    /// Take every setting `other` makes; the rest stay as they are.
+    pub fn merge(&mut self, other: &OutputConfig) {
+        self.mode = other.mode.or(self.mode);
+        self.position = other.position.or(self.position);
+        self.scale = other.scale.or(self.scale);
+        self.transform = other.transform.or(self.transform);
+        self.background_color = other.background_color.or(self.background_color);
+        self.vrr = other.vrr.or(self.vrr);
+    }
+}
+
+/// smithay's Transform has no serde support; write its variant name.
+fn serialize_transform<S: Serializer>(
+    t: &Option<smithay::utils::Transform>,
+    s: S,
+) -> Result<S::Ok, S::Error> {
+    t.map(|t| format!("{t:?}")).serialize(s)
+}
+
+/// `2560x1440@144`; without `@` any refresh rate of that size will do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct OutputMode {
+    pub width: u16,
+    pub height: u16,
+    /// mHz.
+    pub refresh: Option<u32>,
 }
 
 // ── window rule ───────────────────────────────────────────────────────────────
 
-#[derive(Debug, Clone)]
+/// `app_id` and `title` match as substrings, or as a regex when written
+/// `re:<pattern>`; the regex is compiled once by `parse_windowrule`.
+#[derive(Debug, Clone, Serialize)]
 pub struct WindowRule {
     pub app_id: Option<String>,
     pub title: Option<String>,
     pub floating: bool,
+    /// Draw the TWM pane without border or title.
+    pub no_decor: bool,
+    /// Put this mark on the window's pane when it opens.
+    pub mark: Option<String>,
     pub size: Option<[i32; 2]>,
     pub position: Option<[i32; 2]>,
+    #[serde(skip)]
+    pub app_id_re: Option<regex::Regex>,
+    #[serde(skip)]
+    pub title_re: Option<regex::Regex>,
 }
 
 impl WindowRule {
     pub fn matches(&self, app_id: &str, title: &str) -> bool {
-        self.app_id.as_deref().map_or(true, |p| app_id.contains(p))
-            && self.title.as_deref().map_or(true, |p| title.contains(p))
+        field_matches(self.app_id.as_deref(), self.app_id_re.as_ref(), app_id)
+            && field_matches(self.title.as_deref(), self.title_re.as_ref(), title)
+    }
+}
+
+/// The compiled regexes follow from `app_id` / `title`, so they're left out.
+impl PartialEq for WindowRule {
+    fn eq(&self, o: &Self) -> bool {
+        self.app_id == o.app_id
+            && self.title == o.title
+            && self.floating == o.floating
+            && self.no_decor == o.no_decor
+            && self.mark == o.mark
+            && self.size == o.size
+            && self.position == o.position
+    }
+}
+
+fn field_matches(pattern: Option<&str>, re: Option<&regex::Regex>, value: &str) -> bool {
+    match (pattern, re) {
+        (None, _) => true,
+        (Some(p), Some(re)) if p.starts_with("re:") => re.is_match(value),
+        (Some(p), _) => value.contains(p),
     }
 }
 
@@ -192,18 +596,55 @@ impl Default for Config {
             background_color: [0.05, 0.05, 0.05, 1.0],
             target_hz: None,
             vsync: VsyncMode::On,
+            vrr: false,
+            idle_timeout: None,
+            direct_scanout: true,
+            clipboard_history: 20,
+            clipboard_max_bytes: 1024 * 1024,
+            debug_atlas_overlay: false,
+            debug_shader_timing: false,
+            input_method: false,
+            mpris: false,
+            mpris_player: None,
+            mpris_max_title_len: 32,
+            animation_profile: None,
+            anim_duration_ms: None,
+            anim_ease: None,
+            anim_enabled: None,
+            workspace_anim_duration_ms: None,
+            lock_pin: None,
+            cursor_theme: "Adwaita".into(),
+            cursor_size: 24,
+            float_snap: 0,
+            no_gap_when_single: false,
+            focus_history_len: crate::twm_drop_in::FOCUS_HISTORY_LEN,
+            workspace_grid_cols: crate::twm_drop_in::WORKSPACE_GRID_COLS,
+            undo_depth: crate::twm_drop_in::UNDO_DEPTH,
+            font_size_pt: 13.0,
+            layer_shell_namespaces: vec![],
+            chrome_layers: vec![],
             vibrance: VibranceConfig::default(),
+            gesture: GestureConfig::default(),
+            bar: BarConfig::default(),
             keyboard: KeyboardConfig::default(),
             font: FontConfig::default(),
             keybinds: vec![Keybind {
                 mods: vec!["super".into(), "shift".into()],
                 key: "print".into(),
                 action: KeyAction::Quit,
+                on_release: false,
+                origin: (PathBuf::new(), 0),
             }],
             window_rules: vec![],
+            outputs: HashMap::new(),
+            workspace_rules: vec![],
             exec: vec![],
             exec_once: vec![],
             shaders: ShaderRegistry::default(),
+            default_layout: Layout::Bsp,
+            workspace_layouts: HashMap::new(),
+            icon_map: vec![],
+            global_env: vec![],
         }
     }
 }
@@ -262,7 +703,15 @@ impl Config {
             cfg.keybinds = Config::default().keybinds;
         }
 
-        cfg.shaders = ShaderRegistry::load(&Self::config_dir());
+        for warning in cfg.validate() {
+            tracing::warn!("{warning}");
+        }
+
+        // shaders.json has the user passes; the blur block came from the .conf.
+        cfg.shaders = ShaderRegistry {
+            blur: cfg.shaders.blur.take(),
+            ..ShaderRegistry::load(&Self::config_dir())
+        };
 
         tracing::info!(
             "Config loaded — terminal={:?} vsync={:?} target_hz={:?} font={:?}",
@@ -307,17 +756,44 @@ fn parse_into(
     has_keybinds: &mut bool,
 ) -> Result<(), String> {
     let mut section_stack: Vec<String> = Vec::new();
+    // Connector named by the innermost `output "…" {` header, or by the
+    // `name` line of a `monitor {` one.
+    let mut current_output: Option<String> = None;
 
-    for (raw_no, raw_line) in text.lines().enumerate() {
-        let lineno = raw_no + 1;
-        let line = strip_comment(raw_line).trim();
+    for (lineno, line) in logical_lines(text) {
+        let line = line.as_str();
 
         if line.is_empty() {
             continue;
         }
 
         if line.ends_with('{') {
-            let name = line.trim_end_matches('{').trim().to_lowercase();
+            let header = line.trim_end_matches('{').trim();
+            if let Some(output) = parse_output_header(header) {
+                current_output = Some(output.clone());
+                cfg.outputs.entry(output).or_default();
+                section_stack.push("output".into());
+                continue;
+            }
+            // `workspace "2" {` is a `workspace_rule` with its number up front.
+            if let Some(index) = parse_workspace_header(header) {
+                cfg.workspace_rules.push(WorkspaceRule {
+                    index,
+                    ..Default::default()
+                });
+                section_stack.push("workspace_rule".into());
+                continue;
+            }
+            let name = header.to_lowercase();
+            // `monitor { name = DP-1 … }` is an `output "DP-1" {` block.
+            if name == "monitor" {
+                current_output = None;
+                section_stack.push("output".into());
+                continue;
+            }
+            if name == "workspace_rule" {
+                cfg.workspace_rules.push(WorkspaceRule::default());
+            }
             section_stack.push(name);
             continue;
         }
@@ -338,6 +814,7 @@ fn parse_into(
         })?;
 
         let section = section_stack.last().map(String::as_str).unwrap_or("");
+        let parent = section_stack.iter().rev().nth(1).map(String::as_str);
 
         if key == "source" && section.is_empty() {
             let path = resolve_path(value, file);
@@ -366,8 +843,33 @@ fn parse_into(
             "" => apply_toplevel(key, value, file, lineno, cfg, has_keybinds),
             "general" => apply_general(key, value, file, lineno, cfg),
             "vibrance" => apply_vibrance(key, value, file, lineno, &mut cfg.vibrance),
+            "gesture" => apply_gesture(key, value, file, lineno, &mut cfg.gesture),
+            "bar" => apply_bar(key, value, file, lineno, &mut cfg.bar),
             "keyboard" => apply_keyboard(key, value, file, lineno, &mut cfg.keyboard),
             "font" => apply_font(key, value, file, lineno, &mut cfg.font),
+            "env" => cfg.global_env.push((key.to_string(), value.to_string())),
+            "workspace_rule" => {
+                if let Some(rule) = cfg.workspace_rules.last_mut() {
+                    apply_workspace_rule(key, value, file, lineno, rule);
+                }
+            }
+            "output" if key == "name" || key == "output" => {
+                let name = value.trim().trim_matches('"').to_string();
+                cfg.outputs.entry(name.clone()).or_default();
+                current_output = Some(name);
+            }
+            "output" => match current_output.as_ref().and_then(|n| cfg.outputs.get_mut(n)) {
+                Some(out) => apply_output(key, value, file, lineno, out),
+                None => tracing::warn!(
+                    "{}:{} — monitor.{key} before the monitor's name",
+                    file.display(),
+                    lineno
+                ),
+            },
+            "blur" if parent == Some("shaders") => {
+                let blur = cfg.shaders.blur.get_or_insert_with(BlurConfig::default);
+                apply_blur(key, value, file, lineno, blur);
+            }
             other => tracing::warn!("{}:{} — unknown section `{other}`", file.display(), lineno),
         }
     }
@@ -393,13 +895,23 @@ fn apply_toplevel(
     has_keybinds: &mut bool,
 ) {
     match key {
-        "bind" => match parse_bind(value) {
+        "bind" => match parse_bind(value, file, lineno) {
             Some(kb) => {
                 *has_keybinds = true;
                 cfg.keybinds.push(kb);
             }
             None => tracing::warn!("{}:{} — invalid bind `{value}`", file.display(), lineno),
         },
+        "bind_r" => match parse_bind(value, file, lineno) {
+            Some(kb) => {
+                *has_keybinds = true;
+                cfg.keybinds.push(Keybind {
+                    on_release: true,
+                    ..kb
+                });
+            }
+            None => tracing::warn!("{}:{} — invalid bind_r `{value}`", file.display(), lineno),
+        },
         "windowrule" => match parse_windowrule(value) {
             Some(r) => cfg.window_rules.push(r),
             None => tracing::warn!(
@@ -409,7 +921,7 @@ fn apply_toplevel(
             ),
         },
         "exec" => cfg.exec.push(parse_exec(value)),
-        "exec_once" => cfg.exec_once.push(parse_exec(value)),
+        "exec_once" => cfg.exec_once.push(parse_exec_once(value)),
         "terminal" => cfg.terminal = value.to_string(),
         "seat_name" => cfg.seat_name = value.to_string(),
         "background_color" => {
@@ -423,6 +935,22 @@ fn apply_toplevel(
             }
         }
         "vsync" => cfg.vsync = parse_vsync(value),
+        "default_layout" => match Layout::from_name(value) {
+            Some(l) => cfg.default_layout = l,
+            None => tracing::warn!("{}:{} — unknown layout `{value}`", file.display(), lineno),
+        },
+        "icon_map" => match parse_icon_map(value) {
+            Some(entry) => cfg.icon_map.push(entry),
+            None => tracing::warn!("{}:{} — invalid icon_map `{value}`", file.display(), lineno),
+        },
+        "workspace_layout" => match parse_workspace_layouts(value) {
+            Some(map) => cfg.workspace_layouts.extend(map),
+            None => tracing::warn!(
+                "{}:{} — invalid workspace_layout `{value}`",
+                file.display(),
+                lineno
+            ),
+        },
         _ => tracing::warn!(
             "{}:{} — unknown top-level key `{key}`",
             file.display(),
@@ -453,6 +981,170 @@ fn apply_general(key: &str, value: &str, file: &Path, lineno: usize, cfg: &mut C
             Err(_) => tracing::warn!("{}:{} — bad target_hz `{value}`", file.display(), lineno),
         },
         "vsync" => cfg.vsync = parse_vsync(value),
+        "vrr" => match parse_bool(value) {
+            Some(b) => cfg.vrr = b,
+            None => tracing::warn!("{}:{} — bad bool `{value}`", file.display(), lineno),
+        },
+        "direct_scanout" => match parse_bool(value) {
+            Some(b) => cfg.direct_scanout = b,
+            None => tracing::warn!("{}:{} — bad bool `{value}`", file.display(), lineno),
+        },
+        "debug_atlas_overlay" => match parse_bool(value) {
+            Some(b) => cfg.debug_atlas_overlay = b,
+            None => tracing::warn!("{}:{} — bad bool `{value}`", file.display(), lineno),
+        },
+        "debug_shader_timing" => match parse_bool(value) {
+            Some(b) => cfg.debug_shader_timing = b,
+            None => tracing::warn!("{}:{} — bad bool `{value}`", file.display(), lineno),
+        },
+        "input_method" => match parse_bool(value) {
+            Some(b) => cfg.input_method = b,
+            None => tracing::warn!("{}:{} — bad bool `{value}`", file.display(), lineno),
+        },
+        "mpris" => match parse_bool(value) {
+            Some(b) => cfg.mpris = b,
+            None => tracing::warn!("{}:{} — bad bool `{value}`", file.display(), lineno),
+        },
+        "mpris_player" => cfg.mpris_player = Some(value.trim().to_string()),
+        "mpris_max_title_len" => match value.trim().parse::<usize>() {
+            Ok(n) => cfg.mpris_max_title_len = n,
+            Err(_) => tracing::warn!(
+                "{}:{} — bad mpris_max_title_len `{value}`",
+                file.display(),
+                lineno
+            ),
+        },
+        "animation_profile" => match AnimationProfile::from_name(value) {
+            Some(p) => cfg.animation_profile = Some(p),
+            None => tracing::warn!(
+                "{}:{} — unknown animation_profile `{value}`",
+                file.display(),
+                lineno
+            ),
+        },
+        "anim_duration_ms" => match value.trim().parse::<f64>() {
+            Ok(n) if n >= 0.0 => cfg.anim_duration_ms = Some(n),
+            _ => tracing::warn!(
+                "{}:{} — bad anim_duration_ms `{value}`",
+                file.display(),
+                lineno
+            ),
+        },
+        "anim_ease" => match Easing::from_name(value) {
+            Some(e) => cfg.anim_ease = Some(e),
+            None => tracing::warn!(
+                "{}:{} — unknown anim_ease `{value}`",
+                file.display(),
+                lineno
+            ),
+        },
+        "anim_enabled" => match parse_bool(value) {
+            Some(b) => cfg.anim_enabled = Some(b),
+            None => tracing::warn!("{}:{} — bad bool `{value}`", file.display(), lineno),
+        },
+        "workspace_anim_duration_ms" => match value.trim().parse::<f64>() {
+            Ok(n) if n >= 0.0 => cfg.workspace_anim_duration_ms = Some(n),
+            _ => tracing::warn!(
+                "{}:{} — bad workspace_anim_duration_ms `{value}`",
+                file.display(),
+                lineno
+            ),
+        },
+        "lock_pin" => match parse_lock_pin(value) {
+            Ok(hash) => cfg.lock_pin = Some(hash),
+            Err(e) => tracing::warn!("{}:{} — bad lock_pin: {e}", file.display(), lineno),
+        },
+        "cursor_theme" => cfg.cursor_theme = value.trim().to_string(),
+        "cursor_size" => match value.trim().parse::<u32>() {
+            Ok(n) if n > 0 => cfg.cursor_size = n,
+            _ => tracing::warn!("{}:{} — bad cursor_size `{value}`", file.display(), lineno),
+        },
+        "float_snap" => match value.trim().parse::<u32>() {
+            Ok(n) => cfg.float_snap = n,
+            Err(_) => tracing::warn!("{}:{} — bad float_snap `{value}`", file.display(), lineno),
+        },
+        "focus_history_len" => match value.trim().parse::<usize>() {
+            Ok(n) if n > 0 => cfg.focus_history_len = n,
+            _ => tracing::warn!(
+                "{}:{} — bad focus_history_len `{value}`",
+                file.display(),
+                lineno
+            ),
+        },
+        "workspace_grid_cols" => match value.trim().parse::<usize>() {
+            Ok(n) if n > 0 => cfg.workspace_grid_cols = n,
+            _ => tracing::warn!(
+                "{}:{} — bad workspace_grid_cols `{value}`",
+                file.display(),
+                lineno
+            ),
+        },
+        "undo_depth" => match value.trim().parse::<usize>() {
+            Ok(n) => cfg.undo_depth = n,
+            Err(_) => tracing::warn!("{}:{} — bad undo_depth `{value}`", file.display(), lineno),
+        },
+        "font_size_pt" => match value.trim().parse::<f32>() {
+            Ok(n) if n > 0.0 => cfg.font_size_pt = n,
+            _ => tracing::warn!("{}:{} — bad font_size_pt `{value}`", file.display(), lineno),
+        },
+        "no_gap_when_single" => match parse_bool(value) {
+            Some(b) => cfg.no_gap_when_single = b,
+            None => tracing::warn!("{}:{} — bad bool `{value}`", file.display(), lineno),
+        },
+        "layer_shell_namespaces" => match parse_string_list(value) {
+            Some(list) => {
+                for p in list.iter().filter(|p| glob::Pattern::new(p).is_err()) {
+                    tracing::warn!("{}:{} — bad namespace glob `{p}`", file.display(), lineno);
+                }
+                cfg.layer_shell_namespaces = list;
+            }
+            None => tracing::warn!(
+                "{}:{} — layer_shell_namespaces expects [\"a\", \"b\"]",
+                file.display(),
+                lineno
+            ),
+        },
+        "chrome_layers" => match parse_string_list(value) {
+            Some(list) => {
+                for name in list
+                    .iter()
+                    .filter(|n| !matches!(n.as_str(), "clock" | "cpu"))
+                {
+                    tracing::warn!(
+                        "{}:{} — unknown chrome layer `{name}`",
+                        file.display(),
+                        lineno
+                    );
+                }
+                cfg.chrome_layers = list;
+            }
+            None => tracing::warn!(
+                "{}:{} — chrome_layers expects [\"clock\", \"cpu\"]",
+                file.display(),
+                lineno
+            ),
+        },
+        "idle_timeout" => match value.trim().parse::<u64>() {
+            Ok(0) => cfg.idle_timeout = None,
+            Ok(n) => cfg.idle_timeout = Some(n),
+            Err(_) => tracing::warn!("{}:{} — bad idle_timeout `{value}`", file.display(), lineno),
+        },
+        "clipboard_history" => match value.trim().parse::<usize>() {
+            Ok(n) => cfg.clipboard_history = n,
+            Err(_) => tracing::warn!(
+                "{}:{} — bad clipboard_history `{value}`",
+                file.display(),
+                lineno
+            ),
+        },
+        "clipboard_max_bytes" => match value.trim().parse::<usize>() {
+            Ok(n) => cfg.clipboard_max_bytes = n,
+            Err(_) => tracing::warn!(
+                "{}:{} — bad clipboard_max_bytes `{value}`",
+                file.display(),
+                lineno
+            ),
+        },
         _ => tracing::warn!("{}:{} — unknown general.{key}", file.display(), lineno),
     }
 }
@@ -482,11 +1174,157 @@ fn apply_vibrance(key: &str, value: &str, file: &Path, lineno: usize, v: &mut Vi
     }
 }
 
+fn apply_gesture(key: &str, value: &str, file: &Path, lineno: usize, g: &mut GestureConfig) {
+    match key {
+        "swipe_threshold" => match value.trim().parse::<f64>() {
+            Ok(t) if t > 0.0 => g.swipe_threshold = t,
+            _ => tracing::warn!(
+                "{}:{} — bad swipe_threshold `{value}`",
+                file.display(),
+                lineno
+            ),
+        },
+        "fingers" => match value.trim().parse::<u32>() {
+            Ok(n) if n >= 2 => g.fingers = n,
+            _ => tracing::warn!("{}:{} — bad fingers `{value}`", file.display(), lineno),
+        },
+        _ => tracing::warn!("{}:{} — unknown gesture.{key}", file.display(), lineno),
+    }
+}
+
+fn apply_bar(key: &str, value: &str, file: &Path, lineno: usize, b: &mut BarConfig) {
+    let side = match key {
+        "modules_left" => &mut b.modules_left,
+        "modules_center" => &mut b.modules_center,
+        "modules_right" => &mut b.modules_right,
+        "battery_poll_secs" => {
+            match value.trim().parse::<u64>() {
+                Ok(n) if n > 0 => b.battery_poll_secs = n,
+                _ => tracing::warn!(
+                    "{}:{} — bad battery_poll_secs `{value}`",
+                    file.display(),
+                    lineno
+                ),
+            }
+            return;
+        }
+        _ => {
+            tracing::warn!("{}:{} — unknown bar.{key}", file.display(), lineno);
+            return;
+        }
+    };
+    // `clock, cpu` or `["clock", "cpu"]`.
+    let list = parse_string_list(value).unwrap_or_else(|| {
+        value
+            .split(',')
+            .map(|m| m.trim().to_string())
+            .filter(|m| !m.is_empty())
+            .collect()
+    });
+    *side = list
+        .into_iter()
+        .filter(|m| {
+            let known = crate::twm_drop_in::BAR_MODULE_NAMES.contains(&m.as_str());
+            if !known {
+                tracing::warn!("{}:{} — unknown bar module `{m}`", file.display(), lineno);
+            }
+            known
+        })
+        .collect();
+}
+
+fn apply_blur(key: &str, value: &str, file: &Path, lineno: usize, b: &mut BlurConfig) {
+    match key {
+        "enabled" => match parse_bool(value) {
+            Some(v) => b.enabled = v,
+            None => tracing::warn!("{}:{} — bad bool `{value}`", file.display(), lineno),
+        },
+        "radius" => match value.trim().parse::<f32>() {
+            Ok(r) if r > 0.0 => b.radius = r,
+            _ => tracing::warn!("{}:{} — bad blur radius `{value}`", file.display(), lineno),
+        },
+        _ => tracing::warn!("{}:{} — unknown shaders.blur.{key}", file.display(), lineno),
+    }
+}
+
+fn apply_output(key: &str, value: &str, file: &Path, lineno: usize, o: &mut OutputConfig) {
+    match key {
+        "mode" => match parse_output_mode(value) {
+            Some(m) => o.mode = Some(m),
+            None => tracing::warn!("{}:{} — bad mode `{value}`", file.display(), lineno),
+        },
+        "position" | "pos" => {
+            let ns: Result<Vec<i32>, _> = value
+                .split([' ', ','])
+                .filter(|s| !s.is_empty())
+                .map(str::parse)
+                .collect();
+            match ns.as_deref() {
+                Ok(&[x, y]) => o.position = Some((x, y)),
+                _ => tracing::warn!("{}:{} — bad position `{value}`", file.display(), lineno),
+            }
+        }
+        "scale" => match value.trim().parse::<f64>() {
+            Ok(s) if s > 0.0 => o.scale = Some(s),
+            _ => tracing::warn!("{}:{} — bad scale `{value}`", file.display(), lineno),
+        },
+        "transform" => match parse_transform(value) {
+            Some(t) => o.transform = Some(t),
+            None => tracing::warn!("{}:{} — bad transform `{value}`", file.display(), lineno),
+        },
+        "background_color" => match parse_color_f32(value) {
+            Some(c) => o.background_color = Some(c),
+            None => tracing::warn!(
+                "{}:{} — bad background_color `{value}`",
+                file.display(),
+                lineno
+            ),
+        },
+        "vrr" => match parse_bool(value) {
+            Some(b) => o.vrr = Some(b),
+            None => tracing::warn!("{}:{} — bad bool `{value}`", file.display(), lineno),
+        },
+        _ => tracing::warn!("{}:{} — unknown output.{key}", file.display(), lineno),
+    }
+}
+
+fn apply_workspace_rule(key: &str, value: &str, file: &Path, lineno: usize, r: &mut WorkspaceRule) {
+    match key {
+        "workspace" => match value.trim().parse::<u8>() {
+            Ok(n) if n > 0 => r.index = n,
+            _ => tracing::warn!("{}:{} — bad workspace `{value}`", file.display(), lineno),
+        },
+        "name" => match value.trim().trim_matches('"') {
+            "" => tracing::warn!("{}:{} — empty workspace name", file.display(), lineno),
+            name => r.name = Some(name.to_string()),
+        },
+        "layout" => match Layout::from_name(value) {
+            Some(l) => r.layout = Some(l),
+            None => tracing::warn!("{}:{} — unknown layout `{value}`", file.display(), lineno),
+        },
+        "gap" | "gap_inner" | "gap_outer" => match value.trim().parse::<u16>() {
+            Ok(n) if key != "gap_outer" => r.gap_inner = Some(n),
+            Ok(n) => r.gap_outer = Some(n),
+            Err(_) => tracing::warn!("{}:{} — bad {key} `{value}`", file.display(), lineno),
+        },
+        "main_ratio" => match value.trim().parse::<f32>() {
+            Ok(n) if (0.1..=0.9).contains(&n) => r.main_ratio = Some(n),
+            _ => tracing::warn!("{}:{} — bad main_ratio `{value}`", file.display(), lineno),
+        },
+        _ => tracing::warn!(
+            "{}:{} — unknown workspace_rule.{key}",
+            file.display(),
+            lineno
+        ),
+    }
+}
+
 fn apply_keyboard(key: &str, value: &str, file: &Path, lineno: usize, k: &mut KeyboardConfig) {
     match key {
         "layout" => k.layout = Some(value.to_string()),
         "variant" => k.variant = Some(value.to_string()),
         "options" => k.options = Some(value.to_string()),
+        "xkb_file" => k.xkb_file = Some(resolve_path(value.trim(), file)),
         "repeat_delay" => match value.trim().parse::<u32>() {
             Ok(n) => k.repeat_delay = n,
             Err(_) => tracing::warn!("{}:{} — bad repeat_delay `{value}`", file.display(), lineno),
@@ -502,6 +1340,16 @@ fn apply_keyboard(key: &str, value: &str, file: &Path, lineno: usize, k: &mut Ke
                 _ => Modifier::Super,
             };
         }
+        "modifier_as_super" => match value.trim().to_lowercase().as_str() {
+            "alt" => k.modifier_as_super = Some(Modifier::Alt),
+            "ctrl" | "control" => k.modifier_as_super = Some(Modifier::Ctrl),
+            "none" | "super" => k.modifier_as_super = None,
+            _ => tracing::warn!(
+                "{}:{} — bad modifier_as_super `{value}`",
+                file.display(),
+                lineno
+            ),
+        },
         _ => tracing::warn!("{}:{} — unknown keyboard.{key}", file.display(), lineno),
     }
 }
@@ -511,6 +1359,15 @@ fn apply_font(key: &str, value: &str, file: &Path, lineno: usize, f: &mut FontCo
         "path" => f.path = expand_tilde(value),
         "bold_path" => f.bold_path = Some(expand_tilde(value)),
         "italic_path" => f.italic_path = Some(expand_tilde(value)),
+        "emoji_path" => f.emoji_path = Some(expand_tilde(value)),
+        "fallback" => match parse_string_list(value) {
+            Some(list) => f.fallback = list.iter().map(|p| expand_tilde(p)).collect(),
+            None => tracing::warn!(
+                "{}:{} — font.fallback expects [\"a.ttf\", \"b.ttf\"]",
+                file.display(),
+                lineno
+            ),
+        },
         "size" => match value.trim().parse::<f32>() {
             Ok(n) => f.size = n,
             Err(_) => tracing::warn!("{}:{} — bad font size `{value}`", file.display(), lineno),
@@ -523,13 +1380,25 @@ fn apply_font(key: &str, value: &str, file: &Path, lineno: usize, f: &mut FontCo
             Ok(n) => f.dpi = Some(n),
             Err(_) => tracing::warn!("{}:{} — bad dpi `{value}`", file.display(), lineno),
         },
+        "weight" => match value.trim().parse::<u32>() {
+            Ok(n) => f.weight = Some(n),
+            Err(_) => tracing::warn!("{}:{} — bad font weight `{value}`", file.display(), lineno),
+        },
+        "width" => match value.trim().parse::<u32>() {
+            Ok(n) => f.width = Some(n),
+            Err(_) => tracing::warn!("{}:{} — bad font width `{value}`", file.display(), lineno),
+        },
+        "bidi_enabled" => match parse_bool(value) {
+            Some(b) => f.bidi_enabled = b,
+            None => tracing::warn!("{}:{} — bad bool `{value}`", file.display(), lineno),
+        },
         _ => tracing::warn!("{}:{} — unknown font.{key}", file.display(), lineno),
     }
 }
 
 // ── bind parsing ──────────────────────────────────────────────────────────────
 
-fn parse_bind(value: &str) -> Option<Keybind> {
+fn parse_bind(value: &str, file: &Path, lineno: usize) -> Option<Keybind> {
     let parts: Vec<&str> = value.splitn(3, ',').map(str::trim).collect();
     if parts.len() < 3 {
         return None;
@@ -544,7 +1413,13 @@ fn parse_bind(value: &str) -> Option<Keybind> {
         return None;
     }
     let action = parse_key_action(parts[2].trim())?;
-    Some(Keybind { mods, key, action })
+    Some(Keybind {
+        mods,
+        key,
+        action,
+        on_release: false,
+        origin: (file.to_path_buf(), lineno),
+    })
 }
 
 fn parse_key_action(s: &str) -> Option<KeyAction> {
@@ -555,26 +1430,81 @@ fn parse_key_action(s: &str) -> Option<KeyAction> {
             return Some(KeyAction::Spawn {
                 command: std::env::var("SHELL").unwrap_or_else(|_| "kitty".into()),
                 args: vec![],
+                env: vec![],
             });
         }
-        let mut words = shell_words(cmd_str);
-        if words.is_empty() {
-            return None;
+        let mut words = shell_words(cmd_str).into_iter().peekable();
+        let mut env = Vec::new();
+        if words.next_if_eq("env").is_some() {
+            while let Some(pair) = words.next_if(|w| w.contains('=')) {
+                let (key, value) = pair.split_once('=')?;
+                if key.is_empty() {
+                    return None;
+                }
+                env.push((key.to_string(), value.to_string()));
+            }
         }
-        let command = words.remove(0);
+        let command = words.next()?;
         return Some(KeyAction::Spawn {
             command,
-            args: words,
+            args: words.collect(),
+            env,
         });
     }
+    if let Some(rest) = s.strip_prefix("grid_cols") {
+        let cols = rest
+            .trim()
+            .trim_start_matches('=')
+            .trim()
+            .parse::<u16>()
+            .ok()?;
+        return (cols > 0).then_some(KeyAction::GridCols(cols));
+    }
+    if let Some(rest) = s.strip_prefix("send_key") {
+        return parse_send_key(rest.trim().trim_start_matches('='));
+    }
     match s {
         "quit" => Some(KeyAction::Quit),
         "close_window" | "close" => Some(KeyAction::CloseWindow),
         "reload_config" | "reload" => Some(KeyAction::ReloadConfig),
+        "overview" | "expose" => Some(KeyAction::Overview),
+        "scratchpad" | "toggle_scratchpad" => Some(KeyAction::ToggleScratchpad),
+        "send_to_scratchpad" => Some(KeyAction::SendToScratchpad),
+        "focus_last" => Some(KeyAction::FocusLast),
+        "alt_tab" | "alt_tab_next" => Some(KeyAction::AltTabNext),
+        "alt_tab_prev" => Some(KeyAction::AltTabPrev),
+        "sticky" | "toggle_sticky" => Some(KeyAction::ToggleSticky),
+        "undo_close" => Some(KeyAction::UndoClose),
+        "workspace_left" => Some(KeyAction::WorkspaceLeft),
+        "workspace_right" => Some(KeyAction::WorkspaceRight),
+        "workspace_up" => Some(KeyAction::WorkspaceUp),
+        "workspace_down" => Some(KeyAction::WorkspaceDown),
         _ => None,
     }
 }
 
+/// `<app_id>, <key>[, <mods>]` — mods space-separated, or folded into the
+/// key as `ctrl+s`.
+fn parse_send_key(s: &str) -> Option<KeyAction> {
+    let mut parts = s.split(',').map(str::trim);
+    let target_app_id = parts.next().filter(|a| !a.is_empty())?.to_string();
+    let mut combo: Vec<String> = parts
+        .next()?
+        .split('+')
+        .map(|k| k.trim().to_lowercase())
+        .collect();
+    let key = combo.pop().filter(|k| !k.is_empty())?;
+    let mut mods = combo;
+    if let Some(rest) = parts.next() {
+        mods.extend(rest.split_whitespace().map(str::to_lowercase));
+    }
+    Some(KeyAction::SendKey {
+        target_app_id,
+        key,
+        mods,
+    })
+}
+
 // ── windowrule parsing ────────────────────────────────────────────────────────
 
 fn parse_windowrule(value: &str) -> Option<WindowRule> {
@@ -584,6 +1514,7 @@ fn parse_windowrule(value: &str) -> Option<WindowRule> {
     }
     let action = parts[0].to_lowercase();
     let floating = action == "float" || action == "floating";
+    let no_decor = action == "no_decor";
     let matcher = parts[1].to_string();
     let app_id = if matcher.is_empty() {
         None
@@ -591,10 +1522,23 @@ fn parse_windowrule(value: &str) -> Option<WindowRule> {
         Some(matcher)
     };
 
+    let mut title: Option<String> = None;
+    let mut mark: Option<String> = None;
     let mut size: Option<[i32; 2]> = None;
     let mut position: Option<[i32; 2]> = None;
     for extra in parts.iter().skip(2) {
-        if let Some(s) = extra.strip_prefix("size ") {
+        if let Some(t) = extra.strip_prefix("title ") {
+            title = Some(t.trim().to_string());
+        } else if let Some(m) = extra
+            .strip_prefix("mark")
+            .filter(|m| m.starts_with([' ', '=']))
+        {
+            // `mark term` or `mark = "term"`
+            let m = m.trim_start_matches([' ', '=']).trim().trim_matches('"');
+            if !m.is_empty() {
+                mark = Some(m.to_string());
+            }
+        } else if let Some(s) = extra.strip_prefix("size ") {
             let ns: Vec<i32> = s
                 .split_whitespace()
                 .filter_map(|n| n.parse().ok())
@@ -613,15 +1557,34 @@ fn parse_windowrule(value: &str) -> Option<WindowRule> {
         }
     }
     Some(WindowRule {
+        app_id_re: rule_regex(app_id.as_deref())?,
+        title_re: rule_regex(title.as_deref())?,
         app_id,
-        title: None,
+        title,
         floating,
+        no_decor,
+        mark,
         size,
         position,
     })
 }
 
-// ── exec parsing ──────────────────────────────────────────────────────────────
+/// Compile a `re:`-prefixed matcher. Ok(None) for plain substrings; None if
+/// the pattern doesn't compile, which drops the whole rule.
+fn rule_regex(matcher: Option<&str>) -> Option<Option<regex::Regex>> {
+    let Some(pattern) = matcher.and_then(|m| m.strip_prefix("re:")) else {
+        return Some(None);
+    };
+    match regex::Regex::new(pattern) {
+        Ok(re) => Some(Some(re)),
+        Err(e) => {
+            tracing::warn!("windowrule: bad regex `{pattern}`: {e}");
+            None
+        }
+    }
+}
+
+// ── exec parsing ──────────────────────────────────────────────────────────────
 
 fn parse_exec(value: &str) -> ExecEntry {
     let mut words = shell_words(value.trim());
@@ -629,15 +1592,32 @@ fn parse_exec(value: &str) -> ExecEntry {
         return ExecEntry {
             command: value.to_string(),
             args: vec![],
+            autostart_timeout_ms: None,
         };
     }
     let command = words.remove(0);
     ExecEntry {
         command,
         args: words,
+        autostart_timeout_ms: None,
     }
 }
 
+/// `parse_exec`, plus a trailing `--timeout <ms>` taken as the autostart
+/// timeout rather than passed to the program.
+fn parse_exec_once(value: &str) -> ExecEntry {
+    let mut entry = parse_exec(value);
+    if let [.., flag, ms] = entry.args.as_slice() {
+        if flag == "--timeout" {
+            if let Ok(ms) = ms.parse::<u64>() {
+                entry.autostart_timeout_ms = Some(ms);
+                entry.args.truncate(entry.args.len() - 2);
+            }
+        }
+    }
+    entry
+}
+
 // ── primitive parsers ─────────────────────────────────────────────────────────
 
 fn split_kv(line: &str) -> Option<(&str, &str)> {
@@ -645,6 +1625,109 @@ fn split_kv(line: &str) -> Option<(&str, &str)> {
         .map(|i| (line[..i].trim(), line[i + 1..].trim()))
 }
 
+/// The lines of `text` with their 1-based numbers and comments stripped.
+/// A one-line section, `header { a = 1; b = 2 }`, comes out as the lines it
+/// abbreviates, all with its line number.
+fn logical_lines(text: &str) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    for (i, raw) in text.lines().enumerate() {
+        let line = strip_comment(raw).trim();
+        match split_inline_section(line) {
+            Some((header, body)) => {
+                lines.push((i + 1, format!("{header} {{")));
+                lines.extend(body.into_iter().map(|kv| (i + 1, kv.to_string())));
+                lines.push((i + 1, "}".to_string()));
+            }
+            None => lines.push((i + 1, line.to_string())),
+        }
+    }
+    lines
+}
+
+/// `header { a = 1; b = "x; y" }` → (`header`, [`a = 1`, `b = "x; y"`]).
+/// None for anything else, including `key = {…}` values.
+fn split_inline_section(line: &str) -> Option<(&str, Vec<&str>)> {
+    let (header, rest) = line.split_once('{')?;
+    let body = rest.strip_suffix('}')?;
+    let header = header.trim();
+    if header.is_empty() || header.contains('=') {
+        return None;
+    }
+    let mut stmts = Vec::new();
+    let (mut start, mut quoted) = (0, false);
+    for (i, c) in body.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                stmts.push(&body[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    stmts.push(&body[start..]);
+    let stmts = stmts.into_iter().map(str::trim).filter(|s| !s.is_empty());
+    Some((header, stmts.collect()))
+}
+
+/// `workspace "2"` (or unquoted `workspace 2`) → 2. Numbers are 1-based.
+fn parse_workspace_header(header: &str) -> Option<u8> {
+    let rest = header.strip_prefix("workspace")?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    rest.trim()
+        .trim_matches('"')
+        .parse()
+        .ok()
+        .filter(|&n| n > 0)
+}
+
+/// `output "DP-1"` (or unquoted `output DP-1`) → `DP-1`.
+fn parse_output_header(header: &str) -> Option<String> {
+    let rest = header.strip_prefix("output")?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let name = rest.trim().trim_matches('"');
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// `WIDTHxHEIGHT` or `WIDTHxHEIGHT@HZ`, e.g. `2560x1440@143.97`.
+fn parse_output_mode(s: &str) -> Option<OutputMode> {
+    let (size, refresh) = match s.trim().split_once('@') {
+        Some((size, hz)) => (size, Some(hz.trim().parse::<f64>().ok()?)),
+        None => (s.trim(), None),
+    };
+    let (w, h) = size.trim().split_once('x')?;
+    let mode = OutputMode {
+        width: w.trim().parse().ok()?,
+        height: h.trim().parse().ok()?,
+        refresh: match refresh {
+            Some(hz) if hz > 0.0 => Some((hz * 1000.0).round() as u32),
+            Some(_) => return None,
+            None => None,
+        },
+    };
+    (mode.width > 0 && mode.height > 0).then_some(mode)
+}
+
+/// `0`/`90`/`180`/`270`, optionally prefixed `flipped-` (or `flipped` alone).
+fn parse_transform(s: &str) -> Option<smithay::utils::Transform> {
+    use smithay::utils::Transform;
+    Some(match s.trim().to_lowercase().as_str() {
+        "0" | "normal" => Transform::Normal,
+        "90" => Transform::_90,
+        "180" => Transform::_180,
+        "270" => Transform::_270,
+        "flipped" => Transform::Flipped,
+        "flipped-90" => Transform::Flipped90,
+        "flipped-180" => Transform::Flipped180,
+        "flipped-270" => Transform::Flipped270,
+        _ => return None,
+    })
+}
+
 fn parse_bool(s: &str) -> Option<bool> {
     match s.trim().to_lowercase().as_str() {
         "true" | "yes" | "1" | "on" => Some(true),
@@ -653,6 +1736,79 @@ fn parse_bool(s: &str) -> Option<bool> {
     }
 }
 
+/// Highest bcrypt cost `lock_pin` may use; see `Config::lock_pin`.
+pub const LOCK_PIN_MAX_COST: u32 = 10;
+
+/// A bcrypt hash as `htpasswd -bnB "" PIN` prints it, with or without the
+/// empty user name's leading `:`.
+fn parse_lock_pin(s: &str) -> Result<String, String> {
+    let hash = s.trim().trim_start_matches(':');
+    let parts = hash
+        .parse::<bcrypt::HashParts>()
+        .map_err(|e| e.to_string())?;
+    if parts.get_cost() > LOCK_PIN_MAX_COST {
+        return Err(format!(
+            "cost {} is over {LOCK_PIN_MAX_COST}",
+            parts.get_cost()
+        ));
+    }
+    Ok(hash.to_string())
+}
+
+/// `1:columns, 3:monocle, 5:rows` → {1: Columns, 3: Monocle, 5: Rows}.
+/// Workspace numbers are 1-based and must be in 1..=9.
+fn parse_workspace_layouts(s: &str) -> Option<Vec<(u8, Layout)>> {
+    s.split(',')
+        .filter(|e| !e.trim().is_empty())
+        .map(|entry| {
+            let (ws, layout) = entry.split_once(':')?;
+            let ws: u8 = ws.trim().parse().ok()?;
+            if !(1..=9).contains(&ws) {
+                return None;
+            }
+            Some((ws, Layout::from_name(layout)?))
+        })
+        .collect()
+}
+
+/// `clock = 0xF017` (also `U+F017` or the literal glyph) → ("clock", '\u{F017}').
+fn parse_icon_map(s: &str) -> Option<(String, char)> {
+    let (name, cp) = s.split_once('=')?;
+    let name = name.trim();
+    let cp = cp.trim();
+    if name.is_empty() {
+        return None;
+    }
+    let hex = cp
+        .strip_prefix("0x")
+        .or_else(|| cp.strip_prefix("0X"))
+        .or_else(|| cp.strip_prefix("U+"));
+    let ch = match hex {
+        Some(h) => char::from_u32(u32::from_str_radix(h, 16).ok()?)?,
+        None => {
+            let mut it = cp.chars();
+            let c = it.next()?;
+            if it.next().is_some() {
+                return None;
+            }
+            c
+        }
+    };
+    Some((name.to_owned(), ch))
+}
+
+/// `["waybar", "trixie-*"]` → its items. Quotes are optional.
+fn parse_string_list(s: &str) -> Option<Vec<String>> {
+    let inner = s.trim().strip_prefix('[')?.strip_suffix(']')?;
+    Some(
+        inner
+            .split(',')
+            .map(|item| item.trim().trim_matches('"').trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect(),
+    )
+}
+
 fn parse_vsync(s: &str) -> VsyncMode {
     match s.trim().to_lowercase().as_str() {
         "off" | "false" | "0" => VsyncMode::Off,
@@ -662,9 +1818,8 @@ fn parse_vsync(s: &str) -> VsyncMode {
 }
 
 fn parse_color_f32(s: &str) -> Option<[f32; 4]> {
-    let stripped = s.trim().trim_start_matches('#');
-    if stripped.len() >= 6 {
-        return Some(hex4(s));
+    if let Some(c) = try_hex4(s) {
+        return Some(c);
     }
     let nums: Vec<f32> = s
         .split_whitespace()
@@ -679,9 +1834,40 @@ fn parse_color_f32(s: &str) -> Option<[f32; 4]> {
 
 // ── modifier matching ─────────────────────────────────────────────────────────
 
-use smithay::input::keyboard::ModifiersState;
+/// Apply `modifier_as_super` for keybind matching: the chosen modifier
+/// reads as Super and no longer as itself. The keymap already turns the
+/// left-hand key into Super (`input::apply_keymap`); this lets the
+/// right-hand one, which clients still see as the real modifier, fire
+/// Super binds as well.
+pub fn effective_mods(mods: &ModifiersState, keyboard: &KeyboardConfig) -> ModifiersState {
+    let mut m = *mods;
+    match keyboard.modifier_as_super {
+        Some(Modifier::Alt) => {
+            m.logo |= m.alt;
+            m.alt = false;
+        }
+        Some(Modifier::Ctrl) => {
+            m.logo |= m.ctrl;
+            m.ctrl = false;
+        }
+        Some(Modifier::Super) | None => {}
+    }
+    m
+}
+
+/// Ctrl+Alt, for VT switching. Under `modifier_as_super` the remapped key
+/// arrives as Super, so Super stands in for the modifier it replaced.
+pub fn vt_switch_held(mods: &ModifiersState, keyboard: &KeyboardConfig) -> bool {
+    let (ctrl, alt) = match keyboard.modifier_as_super {
+        Some(Modifier::Alt) => (mods.ctrl, mods.alt || mods.logo),
+        Some(Modifier::Ctrl) => (mods.ctrl || mods.logo, mods.alt),
+        Some(Modifier::Super) | None => (mods.ctrl, mods.alt),
+    };
+    ctrl && alt
+}
 
 pub fn mods_match(mods: &ModifiersState, required: &[String], keyboard: &KeyboardConfig) -> bool {
+    let mods = &effective_mods(mods, keyboard);
     let wants_super = required.iter().any(|m| m == "super");
     let wants_shift = required.iter().any(|m| m == "shift");
     let wants_ctrl = required.iter().any(|m| m == "ctrl");
@@ -701,13 +1887,147 @@ pub fn normalise_key_name(name: &str) -> String {
     name.to_lowercase()
 }
 
+/// Whether bind key `key` names keysym `name`. Modifier keys can be bound
+/// without their side: `super` matches both `super_l` and `super_r`.
+pub fn key_matches(key: &str, name: &str) -> bool {
+    if key == name {
+        return true;
+    }
+    let base = match key {
+        "ctrl" => "control",
+        other => other,
+    };
+    matches!(base, "super" | "shift" | "control" | "alt")
+        && name
+            .strip_prefix(base)
+            .is_some_and(|side| side == "_l" || side == "_r")
+}
+
+/// Whether bind key `key` is itself a modifier, with or without its side.
+fn is_modifier_key(key: &str) -> bool {
+    let base = key
+        .strip_suffix("_l")
+        .or_else(|| key.strip_suffix("_r"))
+        .unwrap_or(key);
+    matches!(base, "super" | "shift" | "ctrl" | "control" | "alt")
+}
+
+/// Whether `kb` fires for keysym `name` going to `key_state`. A release bind
+/// on a modifier key skips the modifier check: by the time its key-up
+/// arrives it has already dropped out of `mods`. Every other release bind
+/// still needs its mods held, so `bind_r = super, space` ignores a bare
+/// Space.
+pub fn bind_matches(
+    kb: &Keybind,
+    mods: &ModifiersState,
+    name: &str,
+    key_state: KeyState,
+    keyboard: &KeyboardConfig,
+) -> bool {
+    kb.on_release == (key_state == KeyState::Released)
+        && key_matches(&kb.key, name)
+        && ((kb.on_release && is_modifier_key(&kb.key)) || mods_match(mods, &kb.mods, keyboard))
+}
+
+// ── validation ────────────────────────────────────────────────────────────────
+
+/// Something in a loaded config that parses but will not do what was meant.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigWarning {
+    /// `later` has the same mods, key and press/release as `first`, so it
+    /// never fires.
+    ConflictingBind {
+        key: String,
+        first: (PathBuf, usize),
+        later: (PathBuf, usize),
+    },
+    /// A bind key that names no keysym.
+    UnknownKeyName {
+        key: String,
+        origin: (PathBuf, usize),
+    },
+}
+
+impl std::fmt::Display for ConfigWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigWarning::ConflictingBind { key, first, later } => write!(
+                f,
+                "{}:{} — bind for `{key}` is shadowed by {}:{}",
+                later.0.display(),
+                later.1,
+                first.0.display(),
+                first.1
+            ),
+            ConfigWarning::UnknownKeyName { key, origin } => write!(
+                f,
+                "{}:{} — unknown key name `{key}`",
+                origin.0.display(),
+                origin.1
+            ),
+        }
+    }
+}
+
+impl Config {
+    /// Binds that can never fire and keys xkb does not know.
+    pub fn validate(&self) -> Vec<ConfigWarning> {
+        let mut warnings: Vec<ConfigWarning> = detect_keybind_conflicts(&self.keybinds)
+            .into_iter()
+            .map(|(a, b)| ConfigWarning::ConflictingBind {
+                key: self.keybinds[b].key.clone(),
+                first: self.keybinds[a].origin.clone(),
+                later: self.keybinds[b].origin.clone(),
+            })
+            .collect();
+        warnings.extend(
+            self.keybinds
+                .iter()
+                .filter(|kb| !is_known_key(&kb.key))
+                .map(|kb| ConfigWarning::UnknownKeyName {
+                    key: kb.key.clone(),
+                    origin: kb.origin.clone(),
+                }),
+        );
+        warnings
+    }
+}
+
+/// Index pairs `(first, later)` of binds where `later` is shadowed: the
+/// keybind lookup takes the first match, so `later` never fires. A bind
+/// shadowed several times is paired with the earliest one only.
+pub fn detect_keybind_conflicts(keybinds: &[Keybind]) -> Vec<(usize, usize)> {
+    let mut seen: HashMap<(Vec<&str>, &str, bool), usize> = HashMap::new();
+    let mut conflicts = Vec::new();
+    for (i, kb) in keybinds.iter().enumerate() {
+        let mut mods: Vec<&str> = kb.mods.iter().map(String::as_str).collect();
+        mods.sort_unstable();
+        mods.dedup();
+        match seen.entry((mods, kb.key.as_str(), kb.on_release)) {
+            Entry::Occupied(e) => conflicts.push((*e.get(), i)),
+            Entry::Vacant(e) => {
+                e.insert(i);
+            }
+        }
+    }
+    conflicts
+}
+
+/// Side-less modifiers are accepted as keys; see `key_matches`.
+fn is_known_key(key: &str) -> bool {
+    matches!(key, "super" | "shift" | "ctrl" | "control" | "alt")
+        || xkb::keysym_from_name(key, xkb::KEYSYM_CASE_INSENSITIVE) != xkb::Keysym::NoSymbol
+}
+
 // ── spawn helper ──────────────────────────────────────────────────────────────
 
-pub fn spawn_process(cmd: &str, args: &[String], wayland_socket: &str) {
+/// `env` is applied in order after WAYLAND_DISPLAY, so later pairs win.
+pub fn spawn_process(cmd: &str, args: &[String], env: &[(String, String)], wayland_socket: &str) {
     let bin = expand_tilde(cmd);
     if let Err(e) = std::process::Command::new(&bin)
         .args(args)
         .env("WAYLAND_DISPLAY", wayland_socket)
+        .envs(env.iter().map(|(k, v)| (k, v)))
         .spawn()
     {
         tracing::warn!("Spawn failed ({bin}): {e}");
@@ -743,6 +2063,72 @@ mod tests {
         assert_eq!(cfg.target_hz, Some(144));
     }
 
+    #[test]
+    fn focus_history_keys() {
+        let cfg = parse(
+            "general {\n  focus_history_len = 8\n}\n\
+             bind = alt, tab, alt_tab\nbind = alt shift, tab, alt_tab_prev",
+        );
+        assert_eq!(cfg.focus_history_len, 8);
+        assert_eq!(cfg.keybinds[0].action, KeyAction::AltTabNext);
+        assert_eq!(cfg.keybinds[1].action, KeyAction::AltTabPrev);
+        let cfg = parse("general {\n  focus_history_len = 0\n}");
+        assert_eq!(cfg.focus_history_len, crate::twm_drop_in::FOCUS_HISTORY_LEN);
+    }
+
+    #[test]
+    fn workspace_grid_keys() {
+        let cfg = parse(
+            "general {\n  workspace_grid_cols = 4\n}\n\
+             bind = super ctrl, left, workspace_left\nbind = super ctrl, down, workspace_down",
+        );
+        assert_eq!(cfg.workspace_grid_cols, 4);
+        assert_eq!(cfg.keybinds[0].action, KeyAction::WorkspaceLeft);
+        assert_eq!(cfg.keybinds[1].action, KeyAction::WorkspaceDown);
+        let cfg = parse("general {\n  workspace_grid_cols = 0\n}");
+        assert_eq!(
+            cfg.workspace_grid_cols,
+            crate::twm_drop_in::WORKSPACE_GRID_COLS
+        );
+    }
+
+    #[test]
+    fn undo_close_keys() {
+        let cfg = parse("general {\n  undo_depth = 3\n}\nbind = super shift, t, undo_close");
+        assert_eq!(cfg.undo_depth, 3);
+        assert_eq!(cfg.keybinds[0].action, KeyAction::UndoClose);
+        assert_eq!(parse("").undo_depth, crate::twm_drop_in::UNDO_DEPTH);
+    }
+
+    #[test]
+    fn bar_modules() {
+        let cfg = parse(
+            "bar {\n  modules_left = workspaces, title\n  battery_poll_secs = 10\n  modules_center =\n  modules_right = [\"cpu\", \"battery\", \"clock\", \"weather\"]\n}",
+        );
+        assert_eq!(cfg.bar.modules_left, ["workspaces", "title"]);
+        assert!(cfg.bar.modules_center.is_empty());
+        assert_eq!(cfg.bar.modules_right, ["cpu", "battery", "clock"]);
+        assert_eq!(cfg.bar.battery_poll_secs, 10);
+
+        let default = parse("").bar;
+        assert_eq!(default.modules_left, ["workspaces"]);
+        assert_eq!(default.modules_center, ["layout"]);
+        assert_eq!(default.modules_right, ["media", "clock"]);
+    }
+
+    #[test]
+    fn font_size_pt_key() {
+        assert_eq!(parse("").font_size_pt, 13.0);
+        assert_eq!(
+            parse("general {\n  font_size_pt = 10.5\n}").font_size_pt,
+            10.5
+        );
+        assert_eq!(
+            parse("general {\n  font_size_pt = -2\n}").font_size_pt,
+            13.0
+        );
+    }
+
     #[test]
     fn bind_quit() {
         let cfg = parse("bind = super shift, print, quit");
@@ -752,6 +2138,80 @@ mod tests {
         assert!(cfg.keybinds[0].mods.contains(&"shift".to_string()));
     }
 
+    #[test]
+    fn grid_layout_and_grid_cols_bind() {
+        let cfg = parse("default_layout = grid:3\nbind = super, g, grid_cols 4");
+        assert_eq!(cfg.default_layout, Layout::Grid { cols: 3 });
+        assert_eq!(cfg.keybinds[0].action, KeyAction::GridCols(4));
+        assert!(parse("bind = super, g, grid_cols 0").keybinds.is_empty());
+    }
+
+    #[test]
+    fn bind_r_fires_on_release() {
+        let cfg = parse("bind_r = super, super, overview");
+        let kb = &cfg.keybinds[0];
+        assert!(kb.on_release);
+        assert_eq!(kb.action, KeyAction::Overview);
+
+        let kbd = KeyboardConfig::default();
+        let held = ModifiersState {
+            logo: true,
+            ..Default::default()
+        };
+        assert!(!bind_matches(kb, &held, "super_l", KeyState::Pressed, &kbd));
+        // Super has already left the modifier state when its key-up arrives.
+        let released = ModifiersState::default();
+        assert!(bind_matches(
+            kb,
+            &released,
+            "super_l",
+            KeyState::Released,
+            &kbd
+        ));
+        assert!(bind_matches(
+            kb,
+            &released,
+            "super_r",
+            KeyState::Released,
+            &kbd
+        ));
+        assert!(!bind_matches(kb, &released, "s", KeyState::Released, &kbd));
+    }
+
+    #[test]
+    fn bind_r_on_a_plain_key_needs_its_mods() {
+        let cfg = parse("bind_r = super, space, overview\nbind_r = ctrl, x, close");
+        let kbd = KeyboardConfig::default();
+        let none = ModifiersState::default();
+        let (space, x) = (&cfg.keybinds[0], &cfg.keybinds[1]);
+        // A bare tap of the key is an ordinary keystroke.
+        assert!(!bind_matches(
+            space,
+            &none,
+            "space",
+            KeyState::Released,
+            &kbd
+        ));
+        assert!(!bind_matches(x, &none, "x", KeyState::Released, &kbd));
+
+        let logo = ModifiersState {
+            logo: true,
+            ..Default::default()
+        };
+        let ctrl = ModifiersState {
+            ctrl: true,
+            ..Default::default()
+        };
+        assert!(bind_matches(
+            space,
+            &logo,
+            "space",
+            KeyState::Released,
+            &kbd
+        ));
+        assert!(bind_matches(x, &ctrl, "x", KeyState::Released, &kbd));
+    }
+
     #[test]
     fn bind_spawn() {
         let cfg = parse("bind = super, return, spawn kitty --title term");
@@ -761,6 +2221,89 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn spawn_env_overrides_and_global_env() {
+        let cfg = parse(
+            "env {\n  MOZ_ENABLE_WAYLAND = 1\n  XCURSOR_SIZE = 32\n}\n\
+             bind = super, t, spawn env TERM=xterm-kitty LANG= kitty --single-instance\n\
+             bind = super, e, spawn env FOO=1\n\
+             bind = super, n, spawn envy",
+        );
+        assert_eq!(
+            cfg.global_env,
+            vec![
+                ("MOZ_ENABLE_WAYLAND".into(), "1".into()),
+                ("XCURSOR_SIZE".into(), "32".into()),
+            ]
+        );
+        assert_eq!(
+            cfg.keybinds[0].action,
+            KeyAction::Spawn {
+                command: "kitty".into(),
+                args: vec!["--single-instance".into()],
+                env: vec![
+                    ("TERM".into(), "xterm-kitty".into()),
+                    ("LANG".into(), String::new()),
+                ],
+            }
+        );
+        // `env` with nothing to run is dropped; a command merely starting
+        // with "env" is not an override.
+        assert_eq!(cfg.keybinds.len(), 2);
+        assert!(matches!(
+            &cfg.keybinds[1].action,
+            KeyAction::Spawn { command, env, .. } if command == "envy" && env.is_empty()
+        ));
+    }
+
+    #[test]
+    fn bind_send_key() {
+        let cfg = parse("bind = super, s, send_key foot, ctrl+c\nbind = super, x, send_key = code, s, ctrl shift");
+        assert_eq!(
+            cfg.keybinds[0].action,
+            KeyAction::SendKey {
+                target_app_id: "foot".into(),
+                key: "c".into(),
+                mods: vec!["ctrl".into()],
+            }
+        );
+        assert!(matches!(
+            &cfg.keybinds[1].action,
+            KeyAction::SendKey { target_app_id, key, mods }
+                if target_app_id == "code" && key == "s" && mods == &["ctrl", "shift"]
+        ));
+        assert_eq!(parse("bind = super, s, send_key foot").keybinds.len(), 0);
+    }
+
+    #[test]
+    fn identical_binds_conflict_once() {
+        let cfg = parse(
+            "bind = super shift, q, quit\nbind = super, return, spawn foot\n\
+             bind = shift super, q, close_window\nbind_r = super shift, q, overview",
+        );
+        assert_eq!(detect_keybind_conflicts(&cfg.keybinds), [(0, 2)]);
+        assert_eq!(
+            cfg.validate(),
+            [ConfigWarning::ConflictingBind {
+                key: "q".into(),
+                first: ("test.conf".into(), 1),
+                later: ("test.conf".into(), 3),
+            }]
+        );
+    }
+
+    #[test]
+    fn unknown_key_name_warns() {
+        let cfg = parse("bind = super, notakey, quit\nbind = super, super, overview");
+        assert_eq!(
+            cfg.validate(),
+            [ConfigWarning::UnknownKeyName {
+                key: "notakey".into(),
+                origin: ("test.conf".into(), 1),
+            }]
+        );
+    }
+
     #[test]
     fn bind_empty_key_rejected() {
         let cfg = parse("bind = super, , quit");
@@ -773,6 +2316,15 @@ mod tests {
         assert_eq!(cfg.keyboard.modifier, Modifier::Alt);
     }
 
+    #[test]
+    fn keyboard_xkb_file() {
+        let cfg = parse("keyboard {\n  xkb_file = /etc/trixie/custom.xkb\n}");
+        assert_eq!(
+            cfg.keyboard.xkb_file.as_deref(),
+            Some(Path::new("/etc/trixie/custom.xkb"))
+        );
+    }
+
     #[test]
     fn windowrule_float_size_pos() {
         let cfg = parse("windowrule = float, sysmenu, size 450 286, pos 100 200");
@@ -783,10 +2335,104 @@ mod tests {
         assert_eq!(r.position, Some([100, 200]));
     }
 
+    #[test]
+    fn windowrule_regex_exact_match() {
+        let cfg = parse("windowrule = float, re:^kitty$");
+        let r = &cfg.window_rules[0];
+        assert!(r.matches("kitty", ""));
+        assert!(!r.matches("kitty-scratch", ""));
+    }
+
+    #[test]
+    fn windowrule_regex_partial_match() {
+        let cfg = parse("windowrule = float, re:scratch\\d?, title re:^Pick");
+        let r = &cfg.window_rules[0];
+        assert!(r.matches("kitty-scratch2", "Pick a file"));
+        assert!(!r.matches("kitty-scratch2", "Save as"));
+        assert!(!r.matches("kitty", "Pick a file"));
+    }
+
+    #[test]
+    fn windowrule_regex_lookahead_rejected() {
+        // The regex crate has no look-around, so `(?!...)` negation doesn't
+        // compile and the rule is dropped instead of matching everything.
+        let cfg = parse("windowrule = float, re:^(?!kitty)\nwindowrule = float, mpv");
+        assert_eq!(cfg.window_rules.len(), 1);
+        assert_eq!(cfg.window_rules[0].app_id.as_deref(), Some("mpv"));
+    }
+
+    #[test]
+    fn windowrule_substring_fallback() {
+        let cfg = parse("windowrule = float, kitty");
+        let r = &cfg.window_rules[0];
+        assert!(r.app_id_re.is_none());
+        assert!(r.matches("kitty-scratch", ""));
+        assert!(!r.matches("foot", ""));
+    }
+
+    #[test]
+    fn windowrule_no_decor() {
+        let cfg = parse("windowrule = no_decor, mpv");
+        let r = &cfg.window_rules[0];
+        assert!(r.no_decor);
+        assert!(!r.floating);
+        assert_eq!(r.app_id.as_deref(), Some("mpv"));
+    }
+
+    #[test]
+    fn windowrule_mark() {
+        let cfg =
+            parse("windowrule = tile, foot, mark = \"term\"\nwindowrule = float, mpv, mark video");
+        assert_eq!(cfg.window_rules[0].mark.as_deref(), Some("term"));
+        assert!(!cfg.window_rules[0].floating);
+        assert_eq!(cfg.window_rules[1].mark.as_deref(), Some("video"));
+        assert_eq!(parse("windowrule = float, mpv").window_rules[0].mark, None);
+    }
+
+    #[test]
+    fn diff_only_keybinds() {
+        let old = parse("bind = super, q, close");
+        let new = parse("bind = super, q, close\nbind = super, r, reload");
+        assert_eq!(
+            Config::diff(&old, &new),
+            ConfigDiff {
+                keybinds_changed: true,
+                ..ConfigDiff::default()
+            }
+        );
+        assert_eq!(Config::diff(&new, &new), ConfigDiff::default());
+        let moved = parse("\nbind = super, q, close\nbind = super, r, reload");
+        assert_ne!(moved.keybinds[0].origin, new.keybinds[0].origin);
+        assert_eq!(Config::diff(&new, &moved), ConfigDiff::default());
+    }
+
+    #[test]
+    fn diff_tracks_output_blocks() {
+        let old = parse("output \"DP-1\" {\n  scale = 1.5\n}");
+        let new = parse("output \"DP-1\" {\n  scale = 2\n}");
+        assert_eq!(
+            Config::diff(&old, &new),
+            ConfigDiff {
+                outputs_changed: true,
+                ..ConfigDiff::default()
+            }
+        );
+        let renamed = parse("monitor {\n  name = DP-1\n  scale = 1.5\n}");
+        assert_eq!(Config::diff(&old, &renamed), ConfigDiff::default());
+    }
+
     #[test]
     fn exec_entry() {
         let cfg = parse("exec_once = /usr/bin/waybar");
         assert_eq!(cfg.exec_once[0].command, "/usr/bin/waybar");
+        assert_eq!(cfg.exec_once[0].autostart_timeout_ms, None);
+    }
+
+    #[test]
+    fn exec_once_timeout() {
+        let cfg = parse("exec_once = setup.sh -v --timeout 5000");
+        assert_eq!(cfg.exec_once[0].args, ["-v"]);
+        assert_eq!(cfg.exec_once[0].autostart_timeout_ms, Some(5000));
     }
 
     #[test]
@@ -798,12 +2444,135 @@ mod tests {
         assert!((cfg.vibrance.balance[1] - 0.9).abs() < 1e-6);
     }
 
+    #[test]
+    fn clipboard_keys() {
+        let cfg = parse("");
+        assert_eq!(
+            (cfg.clipboard_history, cfg.clipboard_max_bytes),
+            (20, 1048576)
+        );
+        let cfg = parse("general {\n  clipboard_history = 5\n  clipboard_max_bytes = 4096\n}");
+        assert_eq!((cfg.clipboard_history, cfg.clipboard_max_bytes), (5, 4096));
+    }
+
+    #[test]
+    fn gesture_section() {
+        assert_eq!(parse("").gesture, GestureConfig::default());
+        let cfg = parse("gesture {\n  swipe_threshold = 150.5\n  fingers = 4\n}");
+        assert_eq!(cfg.gesture.swipe_threshold, 150.5);
+        assert_eq!(cfg.gesture.fingers, 4);
+    }
+
     #[test]
     fn hex_color() {
         let cfg = parse("background_color = #0D0D14");
         assert!((cfg.background_color[0] - 0x0D as f32 / 255.0).abs() < 1e-4);
     }
 
+    #[test]
+    fn monitor_background_override() {
+        let cfg = parse(
+            "background_color = #0D0D14\nmonitor {\n  name = HDMI-A-1\n  background_color = #FFFFFF\n}\nmonitor {\n  name = eDP-1\n}",
+        );
+        assert_eq!(cfg.outputs.len(), 2);
+        assert_eq!(cfg.background_for("HDMI-A-1"), [1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(cfg.background_for("eDP-1"), cfg.background_color);
+        assert_eq!(cfg.background_for("DP-2"), cfg.background_color);
+    }
+
+    #[test]
+    fn monitor_vrr_override() {
+        let cfg = parse(
+            "general {\n  vrr = true\n}\nmonitor {\n  name = eDP-1\n  vrr = off\n}\nmonitor {\n  name = DP-1\n}",
+        );
+        assert!(cfg.vrr_for("DP-1"));
+        assert!(cfg.vrr_for("HDMI-A-1"));
+        assert!(!cfg.vrr_for("eDP-1"));
+    }
+
+    #[test]
+    fn monitor_is_an_output_block() {
+        let cfg = parse(
+            "monitor {\n  name = DP-1\n  scale = 2\n}\n\
+             output \"DP-1\" {\n  background_color = #FFFFFF\n}\n\
+             monitor {\n  vrr = on\n}",
+        );
+        let dp = &cfg.outputs["DP-1"];
+        assert_eq!(dp.scale, Some(2.0));
+        assert_eq!(dp.background_color, Some([1.0; 4]));
+        // A monitor block with no name has nothing to apply to.
+        assert_eq!(cfg.outputs.len(), 1);
+    }
+
+    #[test]
+    fn second_parse_wins() {
+        let mut cfg = Config::default();
+        let mut has_keybinds = false;
+        for terminal in ["foot", "kitty"] {
+            parse_into(
+                &format!("general {{\n  terminal = {terminal}\n}}"),
+                Path::new("test.conf"),
+                &mut vec![],
+                &mut cfg,
+                &mut has_keybinds,
+            )
+            .unwrap();
+        }
+        assert_eq!(cfg.terminal, "kitty");
+        // No bind lines, so load() would still fall back to the defaults.
+        assert!(!has_keybinds);
+    }
+
+    #[test]
+    fn output_section() {
+        let cfg = parse(
+            "output \"DP-1\" {\n  mode = 2560x1440@144\n  position = 1920 0\n  scale = 1.5\n  transform = 90\n}\n\
+             output \"eDP-1\" {\n  mode = 1920x1080\n}",
+        );
+        let dp = &cfg.outputs["DP-1"];
+        assert_eq!(
+            dp.mode,
+            Some(OutputMode {
+                width: 2560,
+                height: 1440,
+                refresh: Some(144_000)
+            })
+        );
+        assert_eq!(dp.position, Some((1920, 0)));
+        assert_eq!(dp.scale, Some(1.5));
+        assert_eq!(dp.transform, Some(smithay::utils::Transform::_90));
+        assert_eq!(cfg.outputs["eDP-1"].mode.unwrap().refresh, None);
+        let ntsc = parse_output_mode("1920x1080@59.94").unwrap();
+        assert_eq!(ntsc.refresh, Some(59_940));
+    }
+
+    #[test]
+    fn output_config_merge_keeps_what_is_unset() {
+        let cfg = parse("output \"DP-1\" {\n  mode = 2560x1440@144\n  scale = 1.5\n}");
+        let mut dp = cfg.outputs["DP-1"].clone();
+        dp.merge(&OutputConfig {
+            position: Some((1920, 0)),
+            scale: Some(2.0),
+            ..Default::default()
+        });
+        assert_eq!(dp.mode.unwrap().refresh, Some(144_000));
+        assert_eq!(dp.position, Some((1920, 0)));
+        assert_eq!(dp.scale, Some(2.0));
+    }
+
+    #[test]
+    fn output_mode_rejects_garbage() {
+        for bad in ["2560", "x1440", "2560x1440@", "2560x1440@fast", "0x0"] {
+            assert_eq!(parse_output_mode(bad), None, "{bad}");
+        }
+        let cfg = parse("output \"DP-1\" {\n  mode = 4k\n}");
+        assert_eq!(cfg.outputs["DP-1"].mode, None);
+        for bad in ["1920 x 0", "1920, 0, 5", "left 0"] {
+            let cfg = parse(&format!("output \"DP-1\" {{\n  position = {bad}\n}}"));
+            assert_eq!(cfg.outputs["DP-1"].position, None, "{bad}");
+        }
+    }
+
     #[test]
     fn inline_comment_stripped() {
         let cfg = parse("general {\n  vsync = off  # disable vsync\n}");
@@ -816,12 +2585,207 @@ mod tests {
         assert!((cfg.background_color[0] - 0x1E as f32 / 255.0).abs() < 1e-4);
     }
 
+    #[test]
+    fn shaders_blur_block() {
+        let cfg = parse("shaders {\n  blur {\n    enabled = true\n    radius = 6.5\n  }\n}");
+        let blur = cfg.shaders.blur.expect("blur block parsed");
+        assert!(blur.enabled);
+        assert_eq!(blur.taps(), 7);
+        assert!(parse("").shaders.blur.is_none());
+    }
+
     #[test]
     fn font_section() {
         let cfg =
             parse("font {\n  path = /usr/share/fonts/TTF/Hack.ttf\n  size = 16.0\n  dpi = 144\n}");
         assert_eq!(cfg.font.path, "/usr/share/fonts/TTF/Hack.ttf");
+        assert_eq!(cfg.font.emoji_path, None);
+        assert!(cfg.font.fallback.is_empty());
         assert!((cfg.font.size - 16.0).abs() < 1e-6);
         assert_eq!(cfg.font.dpi, Some(144));
+        assert_eq!((cfg.font.weight, cfg.font.width), (None, None));
+
+        let cfg = parse("font {\n  weight = 450\n  width = 100\n}");
+        assert_eq!((cfg.font.weight, cfg.font.width), (Some(450), Some(100)));
+
+        let cfg = parse("font {\n  fallback = [\"/a/Noto.ttf\", /b/Sym.otf]\n}");
+        assert_eq!(cfg.font.fallback, ["/a/Noto.ttf", "/b/Sym.otf"]);
+    }
+
+    #[test]
+    fn workspace_layouts() {
+        let cfg = parse("workspace_layout = 2:monocle");
+        let mut twm = crate::twm_drop_in::TwmState::new(80, 24);
+        twm.set_workspace_layouts(cfg.default_layout, &cfg.workspace_layouts);
+        assert_eq!(twm.workspaces[1].layout, Layout::Monocle);
+        assert_eq!(twm.workspaces[0].layout, Layout::Bsp);
+    }
+
+    #[test]
+    fn workspace_rule_block() {
+        let cfg = parse(
+            "workspace_rule {\n  workspace = 3\n  layout = monocle\n  gap_inner = 0\n}\n\
+             workspace_rule {\n  workspace = 4\n  gap_outer = 2\n  main_ratio = 0.7\n}",
+        );
+        let mut twm = crate::twm_drop_in::TwmState::new(80, 24);
+        twm.set_workspace_rules(&cfg.workspace_rules);
+        let ws3 = &twm.workspaces[2];
+        assert_eq!((ws3.gap_inner, ws3.layout), (0, Layout::Monocle));
+        let ws4 = &twm.workspaces[3];
+        assert_eq!((ws4.gap_inner, ws4.gap_outer, ws4.main_ratio), (1, 2, 0.7));
+        assert_eq!(twm.workspaces[0].gap_inner, 1);
+    }
+
+    #[test]
+    fn workspace_sections_name_workspaces() {
+        let cfg = parse(
+            "workspace \"2\" { name = \"web stuff\"; layout = monocle; gap = 4 }\n\
+             workspace 3 {\n  name = \"notes; misc\"  # trailing comment\n}",
+        );
+        let rule = &cfg.workspace_rules[0];
+        assert_eq!(rule.index, 2);
+        assert_eq!(rule.name.as_deref(), Some("web stuff"));
+        assert_eq!(
+            (rule.layout, rule.gap_inner),
+            (Some(Layout::Monocle), Some(4))
+        );
+        assert_eq!(cfg.workspace_rules[1].name.as_deref(), Some("notes; misc"));
+
+        // The names survive into the TWM and back out by name.
+        let mut twm = crate::twm_drop_in::TwmState::new(80, 24);
+        twm.set_workspace_rules(&cfg.workspace_rules);
+        let names: Vec<&str> = twm.workspaces.iter().map(|w| w.name.as_str()).collect();
+        assert_eq!(names[..4], ["1", "web stuff", "notes; misc", "4"]);
+        twm.dispatch(&crate::twm_drop_in::Action::WorkspaceByName(
+            "web stuff".into(),
+        ));
+        assert_eq!(twm.active_ws, 1);
+    }
+
+    #[test]
+    fn workspace_layouts_default_fallback() {
+        let cfg = parse("default_layout = rows\nworkspace_layout = 1:columns, 3:monocle");
+        assert_eq!(cfg.default_layout, Layout::Rows);
+        assert_eq!(cfg.workspace_layouts.get(&1), Some(&Layout::Columns));
+        assert_eq!(cfg.workspace_layouts.get(&3), Some(&Layout::Monocle));
+        assert_eq!(cfg.workspace_layouts.get(&2), None);
+        assert!(parse("workspace_layout = 12:bsp")
+            .workspace_layouts
+            .is_empty());
+    }
+
+    #[test]
+    fn animation_profile_disabled() {
+        let anim = parse("general {\n  animation_profile = disabled\n}").animation();
+        assert!(!anim.enabled);
+        assert_eq!(anim.duration_ms, 0.0);
+    }
+
+    #[test]
+    fn animation_keys_override_profile() {
+        let anim =
+            parse("general {\n  anim_ease = linear\n  animation_profile = smooth\n}").animation();
+        assert_eq!(anim.duration_ms, 200.0);
+        assert_eq!(anim.ease, Easing::Linear);
+        assert!(anim.enabled);
+        assert_eq!(Config::default().animation(), AnimationConfig::default());
+        assert_eq!(
+            parse("general {\n  workspace_anim_duration_ms = 120\n}").workspace_anim_duration_ms,
+            Some(120.0)
+        );
+    }
+
+    #[test]
+    fn input_method_off_by_default() {
+        assert!(!Config::default().input_method);
+        assert!(parse("general {\n  input_method = true\n}").input_method);
+    }
+
+    #[test]
+    fn mpris_keys() {
+        let cfg = parse(
+            "general {\n  mpris = true\n  mpris_player = spotify\n  mpris_max_title_len = 20\n}",
+        );
+        assert!(cfg.mpris);
+        assert_eq!(cfg.mpris_player.as_deref(), Some("spotify"));
+        assert_eq!(cfg.mpris_max_title_len, 20);
+    }
+
+    #[test]
+    fn idle_timeout() {
+        assert_eq!(Config::default().idle_timeout, None);
+        let cfg = parse("general {\n  idle_timeout = 300\n}");
+        assert_eq!(cfg.idle_timeout, Some(300));
+        let cfg = parse("general {\n  idle_timeout = 0\n}");
+        assert_eq!(cfg.idle_timeout, None);
+    }
+
+    #[test]
+    fn direct_scanout_defaults_on() {
+        assert!(Config::default().direct_scanout);
+        let cfg = parse("general {\n  direct_scanout = false\n}");
+        assert!(!cfg.direct_scanout);
+    }
+
+    #[test]
+    fn layer_shell_namespace_allowlist() {
+        assert!(Config::default().layer_namespace_allowed("anything"));
+        let cfg = parse("general {\n  layer_shell_namespaces = [\"waybar\"]\n}");
+        assert!(cfg.layer_namespace_allowed("waybar"));
+        assert!(!cfg.layer_namespace_allowed("fuzzel"));
+        let cfg = parse("general {\n  layer_shell_namespaces = [\"fuzzel\", \"trixie-*\"]\n}");
+        assert!(cfg.layer_namespace_allowed("trixie-bar"));
+        assert!(!cfg.layer_namespace_allowed("waybar"));
+    }
+
+    #[test]
+    fn icon_map_entries() {
+        let cfg = parse("icon_map = clock = 0xF017\nicon_map = bolt = U+F0E7\nicon_map = bad = zz");
+        assert_eq!(
+            cfg.icon_map,
+            vec![
+                ("clock".to_string(), '\u{F017}'),
+                ("bolt".to_string(), '\u{F0E7}'),
+            ]
+        );
+    }
+
+    #[test]
+    fn modifier_as_super_alt() {
+        let cfg =
+            parse("keyboard {\n  modifier_as_super = alt\n}\nbind = super, return, spawn kitty");
+        assert_eq!(cfg.keyboard.modifier_as_super, Some(Modifier::Alt));
+        let kb = &cfg.keybinds[0];
+        let alt = ModifiersState {
+            alt: true,
+            ..Default::default()
+        };
+        assert!(mods_match(&alt, &kb.mods, &cfg.keyboard));
+        assert_eq!(kb.key, normalise_key_name("Return"));
+
+        // Without the remap Alt is just Alt.
+        let plain = parse("bind = super, return, spawn kitty");
+        assert!(!mods_match(&alt, &plain.keybinds[0].mods, &plain.keyboard));
+
+        // Ctrl + the remapped left Alt (now Super) still switches VTs.
+        let ctrl_lalt = ModifiersState {
+            ctrl: true,
+            logo: true,
+            ..Default::default()
+        };
+        assert!(vt_switch_held(&ctrl_lalt, &cfg.keyboard));
+        assert!(!vt_switch_held(&ctrl_lalt, &plain.keyboard));
+    }
+
+    #[test]
+    fn lock_pin_cost_is_capped() {
+        let pin = |value: &str| parse(&format!("general {{\n  lock_pin = {value}\n}}")).lock_pin;
+        let hash = |cost| bcrypt::hash("1234", cost).unwrap();
+        // htpasswd prints the empty user name's `:` in front.
+        let fast = pin(&format!(":{}", hash(4))).unwrap();
+        assert!(bcrypt::verify("1234", &fast).unwrap());
+
+        assert_eq!(pin(&hash(LOCK_PIN_MAX_COST + 1)), None);
+        assert_eq!(pin("1234"), None);
     }
 }
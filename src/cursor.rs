@@ -0,0 +1,328 @@
+// cursor.rs — XCursor theme loading for the software-drawn pointer
+//
+// Themes are looked up the way libXcursor does it: `<dir>/<theme>/cursors/<name>`
+// in each icon directory, following `Inherits=` from the theme's index.theme.
+// Images are decoded once per cursor name and kept for the theme's lifetime.
+// render_surface hands the current frame to the output's HardwareCursor when
+// the CRTC has a cursor plane, and otherwise turns it into a DrawCmd::Image.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use smithay::{
+    backend::{allocator::Fourcc, renderer::element::memory::MemoryRenderBuffer},
+    utils::{Buffer, Size, Transform},
+};
+
+/// Inherits chains deeper than this are treated as cycles.
+const MAX_INHERIT_DEPTH: usize = 8;
+
+#[derive(Debug, Clone)]
+pub struct CursorFrame {
+    pub width: u32,
+    pub height: u32,
+    pub xhot: u32,
+    pub yhot: u32,
+    /// Milliseconds this frame stays up in an animated cursor.
+    pub delay: u32,
+    /// Straight (non-premultiplied) RGBA, `width * height * 4` bytes.
+    pub rgba: Arc<Vec<u8>>,
+}
+
+/// Every frame of one cursor at the nominal size closest to the requested one.
+#[derive(Debug, Clone)]
+pub struct CursorImages {
+    pub frames: Vec<CursorFrame>,
+}
+
+impl CursorImages {
+    /// The frame showing `millis` into the animation (the only frame for
+    /// static cursors).
+    pub fn frame_at(&self, millis: u64) -> Option<&CursorFrame> {
+        let total: u64 = self.frames.iter().map(|f| f.delay.max(1) as u64).sum();
+        if self.frames.len() < 2 || total == 0 {
+            return self.frames.first();
+        }
+        let mut t = millis % total;
+        for f in &self.frames {
+            let d = f.delay.max(1) as u64;
+            if t < d {
+                return Some(f);
+            }
+            t -= d;
+        }
+        self.frames.last()
+    }
+}
+
+pub struct CursorTheme {
+    name: String,
+    size: u32,
+    search_dirs: Vec<PathBuf>,
+    /// None caches a miss so a missing cursor isn't re-read every frame.
+    images: HashMap<String, Option<CursorImages>>,
+}
+
+impl CursorTheme {
+    /// `name` from the standard icon directories, starting with
+    /// `~/.local/share/icons`.
+    pub fn load(name: &str, size: u32) -> Self {
+        Self::load_from(default_search_dirs(), name, size)
+    }
+
+    pub fn load_from(search_dirs: Vec<PathBuf>, name: &str, size: u32) -> Self {
+        let mut theme = Self {
+            name: name.to_owned(),
+            size,
+            search_dirs,
+            images: HashMap::new(),
+        };
+        if theme.get("default").is_none() {
+            tracing::warn!("cursor theme {name:?}: no `default` cursor found");
+        }
+        theme
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Images for the cursor called `cursor` (`default`, `pointer`, `text`, …).
+    pub fn get(&mut self, cursor: &str) -> Option<&CursorImages> {
+        if !self.images.contains_key(cursor) {
+            let loaded = self
+                .find_file(&self.name, cursor, 0)
+                .and_then(|path| std::fs::read(path).ok())
+                .and_then(|bytes| decode(&bytes, self.size));
+            self.images.insert(cursor.to_owned(), loaded);
+        }
+        self.images.get(cursor)?.as_ref()
+    }
+
+    fn find_file(&self, theme: &str, cursor: &str, depth: usize) -> Option<PathBuf> {
+        if depth > MAX_INHERIT_DEPTH {
+            return None;
+        }
+        let mut inherits = Vec::new();
+        for dir in &self.search_dirs {
+            let theme_dir = dir.join(theme);
+            let path = theme_dir.join("cursors").join(cursor);
+            if path.is_file() {
+                return Some(path);
+            }
+            inherits.extend(read_inherits(&theme_dir.join("index.theme")));
+        }
+        inherits
+            .iter()
+            .filter(|parent| parent.as_str() != theme)
+            .find_map(|parent| self.find_file(parent, cursor, depth + 1))
+    }
+}
+
+fn default_search_dirs() -> Vec<PathBuf> {
+    let home = PathBuf::from(std::env::var("HOME").unwrap_or_default());
+    let data_home = std::env::var("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| home.join(".local/share"));
+    vec![
+        data_home.join("icons"),
+        home.join(".icons"),
+        PathBuf::from("/usr/share/icons"),
+        PathBuf::from("/usr/share/pixmaps"),
+    ]
+}
+
+/// `Inherits=a,b;c` from an index.theme, or nothing.
+fn read_inherits(index: &Path) -> Vec<String> {
+    let Ok(text) = std::fs::read_to_string(index) else {
+        return Vec::new();
+    };
+    text.lines()
+        .filter_map(|l| l.trim().strip_prefix("Inherits"))
+        .filter_map(|l| l.trim_start().strip_prefix('='))
+        .flat_map(|v| v.split([',', ';']))
+        .map(|s| s.trim().to_owned())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Decode an XCursor file, keeping the frames whose nominal size is nearest
+/// to `size`.
+fn decode(bytes: &[u8], size: u32) -> Option<CursorImages> {
+    let images = xcursor::parser::parse_xcursor(bytes)?;
+    let nominal = images
+        .iter()
+        .map(|i| i.size)
+        .min_by_key(|&s| s.abs_diff(size))?;
+    let frames: Vec<CursorFrame> = images
+        .into_iter()
+        .filter(|i| i.size == nominal)
+        .map(|i| CursorFrame {
+            width: i.width,
+            height: i.height,
+            xhot: i.xhot,
+            yhot: i.yhot,
+            delay: i.delay,
+            rgba: Arc::new(unpremultiply(i.pixels_rgba)),
+        })
+        .collect();
+    (!frames.is_empty()).then_some(CursorImages { frames })
+}
+
+// ── hardware cursor ───────────────────────────────────────────────────────────
+
+/// The pointer image for one output's DRM cursor plane. render_surface wraps
+/// it in a `Kind::Cursor` element; DrmCompositor puts that on the plane, so a
+/// moving pointer only repositions the plane instead of re-rendering the
+/// frame. If the plane can't take it this frame, DrmCompositor composites
+/// the element like any other.
+pub struct HardwareCursor {
+    plane_size: Size<u32, Buffer>,
+    /// The frame's pixels (by identity) and their upload.
+    uploaded: Option<(Arc<Vec<u8>>, MemoryRenderBuffer)>,
+}
+
+impl HardwareCursor {
+    pub fn new(plane_size: Size<u32, Buffer>) -> Self {
+        Self {
+            plane_size,
+            uploaded: None,
+        }
+    }
+
+    /// Whether `frame` fits the cursor plane at all.
+    pub fn fits(&self, frame: &CursorFrame) -> bool {
+        frame.width <= self.plane_size.w && frame.height <= self.plane_size.h
+    }
+
+    fn needs_upload(&self, frame: &CursorFrame) -> bool {
+        !matches!(&self.uploaded, Some((px, _)) if Arc::ptr_eq(px, &frame.rgba))
+    }
+
+    /// `frame` as a render buffer, re-uploaded only when the image changes.
+    pub fn buffer(&mut self, frame: &CursorFrame) -> &MemoryRenderBuffer {
+        if self.needs_upload(frame) {
+            let buffer = MemoryRenderBuffer::from_slice(
+                &premultiply(&frame.rgba),
+                Fourcc::Abgr8888,
+                (frame.width as i32, frame.height as i32),
+                1,
+                Transform::Normal,
+                None,
+            );
+            self.uploaded = Some((frame.rgba.clone(), buffer));
+        }
+        &self.uploaded.as_ref().unwrap().1
+    }
+}
+
+/// Straight RGBA back to premultiplied, as the GL renderer and the cursor
+/// plane expect it.
+fn premultiply(rgba: &[u8]) -> Vec<u8> {
+    let mut out = rgba.to_vec();
+    for px in out.chunks_exact_mut(4) {
+        let a = px[3] as u32;
+        for c in &mut px[..3] {
+            *c = ((*c as u32 * a + 127) / 255) as u8;
+        }
+    }
+    out
+}
+
+/// XCursor pixels are premultiplied; the UI pipeline blends straight alpha.
+fn unpremultiply(mut rgba: Vec<u8>) -> Vec<u8> {
+    for px in rgba.chunks_exact_mut(4) {
+        let a = px[3] as u32;
+        if a > 0 && a < 255 {
+            for c in &mut px[..3] {
+                *c = ((*c as u32 * 255 + a / 2) / a).min(255) as u8;
+            }
+        }
+    }
+    rgba
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IMAGE_TYPE: u32 = 0xfffd_0002;
+
+    /// A one-image XCursor file: `w × h` opaque red, hotspot (1, 2).
+    fn xcursor_file(nominal: u32, w: u32, h: u32) -> Vec<u8> {
+        let words: Vec<u32> = [
+            // file header: magic, header size, version, toc entries
+            vec![u32::from_le_bytes(*b"Xcur"), 16, 0x1_0000, 1],
+            // toc: type, subtype, position
+            vec![IMAGE_TYPE, nominal, 28],
+            // image chunk header
+            vec![36, IMAGE_TYPE, nominal, 1, w, h, 1, 2, 50],
+            vec![0xFFFF_0000; (w * h) as usize],
+        ]
+        .concat();
+        words.iter().flat_map(|w| w.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn loads_default_from_theme_dir() {
+        let root = std::env::temp_dir().join(format!("trixie-cursor-{}", std::process::id()));
+        let cursors = root.join("Test/cursors");
+        std::fs::create_dir_all(&cursors).unwrap();
+        std::fs::write(cursors.join("default"), xcursor_file(24, 4, 3)).unwrap();
+
+        let mut theme = CursorTheme::load_from(vec![root.clone()], "Test", 24);
+        let frame = theme
+            .get("default")
+            .and_then(|c| c.frame_at(0))
+            .cloned()
+            .expect("default cursor should load");
+        assert!(theme.get("pointer").is_none());
+        std::fs::remove_dir_all(&root).ok();
+
+        assert_eq!((frame.width, frame.height), (4, 3));
+        assert_eq!((frame.xhot, frame.yhot), (1, 2));
+        assert_eq!(frame.rgba.len(), 4 * 3 * 4);
+        assert!(frame.rgba.chunks_exact(4).all(|p| p == [255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn hardware_cursor_reuploads_only_new_images() {
+        let frame = |w, rgba: &Arc<Vec<u8>>| CursorFrame {
+            width: w,
+            height: 1,
+            xhot: 0,
+            yhot: 0,
+            delay: 0,
+            rgba: rgba.clone(),
+        };
+        let half_red = Arc::new(vec![255, 0, 0, 128]);
+        let mut hw = HardwareCursor::new((64, 64).into());
+        assert!(hw.fits(&frame(64, &half_red)));
+        assert!(!hw.fits(&frame(65, &half_red)));
+
+        assert!(hw.needs_upload(&frame(1, &half_red)));
+        hw.buffer(&frame(1, &half_red));
+        assert!(!hw.needs_upload(&frame(1, &half_red)));
+        // Same pixels, new allocation (another animation frame): upload.
+        let copy = Arc::new(half_red.to_vec());
+        assert!(hw.needs_upload(&frame(1, &copy)));
+
+        assert_eq!(premultiply(&half_red), [128, 0, 0, 128]);
+    }
+
+    #[test]
+    fn inherits_parsed() {
+        let path = std::env::temp_dir().join(format!("trixie-index-{}.theme", std::process::id()));
+        std::fs::write(&path, "[Icon Theme]\nName=X\nInherits = Adwaita,hicolor\n").unwrap();
+        let parents = read_inherits(&path);
+        std::fs::remove_file(&path).ok();
+        assert_eq!(parents, ["Adwaita", "hicolor"]);
+    }
+}
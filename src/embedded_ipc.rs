@@ -23,12 +23,27 @@
 //   List all currently embedded surfaces:
 //   { "cmd": "list" }
 //
+//   Keep TWM focus on the focused pane until unlocked (e.g. a fullscreen
+//   terminal app), and release it again:
+//   { "cmd": "focus_lock" }
+//   { "cmd": "focus_unlock" }
+//
+//   Keep the connection open for events about an output:
+//   { "cmd": "subscribe", "output": "DP-1" }
+//
 // ── Responses ─────────────────────────────────────────────────────────────────
 //
 //   { "ok": true, "windows": [ { "app_id": "firefox", "x":0,"y":0,"w":960,"h":1080,
 //                                 "mapped": true } ] }
 //   { "ok": false, "error": "app_id 'foo' not found" }
 //
+// ── Events (subscribed connections only) ──────────────────────────────────────
+//
+//   The output's mode or position changed; re-send `list` to pick up the
+//   pane's new geometry:
+//   { "event": "output_geometry", "output": "DP-1", "x": 0, "y": 0,
+//     "w": 2560, "h": 1440 }
+//
 // ── TWM side usage (twm.rs / trixterm) ───────────────────────────────────────
 //
 //   On tile create:   send Spawn with the tile's pixel rect
@@ -82,6 +97,24 @@ pub enum EmbedCommand {
         app_id: String,
     },
     List,
+    FocusLock,
+    FocusUnlock,
+    Subscribe {
+        output: String,
+    },
+}
+
+/// Pushed unprompted to connections that sent `subscribe`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EmbedEvent {
+    OutputGeometry {
+        output: String,
+        x: i32,
+        y: i32,
+        w: u32,
+        h: u32,
+    },
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -133,6 +166,8 @@ pub struct EmbedIpcServer {
     /// Snapshot of window state used to respond to List commands.
     /// Updated by the compositor after every structural change.
     pub windows: Vec<WindowStatus>,
+    /// Connections kept open by `subscribe`, with the output they follow.
+    subscribers: Vec<(String, UnixStream)>,
 }
 
 impl EmbedIpcServer {
@@ -147,6 +182,7 @@ impl EmbedIpcServer {
                     listener: Some(l),
                     pending: Vec::new(),
                     windows: Vec::new(),
+                    subscribers: Vec::new(),
                 }
             }
             Err(e) => {
@@ -155,6 +191,7 @@ impl EmbedIpcServer {
                     listener: None,
                     pending: Vec::new(),
                     windows: Vec::new(),
+                    subscribers: Vec::new(),
                 }
             }
         }
@@ -167,11 +204,14 @@ impl EmbedIpcServer {
         if let Some(ref listener) = self.listener {
             loop {
                 match listener.accept() {
-                    Ok((stream, _)) => {
-                        if let Some(cmd) = Self::handle_connection(stream, &self.windows) {
-                            self.pending.push(cmd);
+                    Ok((stream, _)) => match Self::handle_connection(stream, &self.windows) {
+                        Some((EmbedCommand::Subscribe { output }, stream)) => {
+                            stream.set_nonblocking(true).ok();
+                            self.subscribers.push((output, stream));
                         }
-                    }
+                        Some((cmd, _)) => self.pending.push(cmd),
+                        None => {}
+                    },
                     Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
                     Err(e) => {
                         tracing::warn!("Embed IPC accept error: {e}");
@@ -183,12 +223,43 @@ impl EmbedIpcServer {
         std::mem::take(&mut self.pending)
     }
 
+    /// Queue a command originating inside the compositor (e.g. TWM reflow)
+    /// so it is handled by the next `drain()` alongside socket traffic.
+    pub fn push(&mut self, cmd: EmbedCommand) {
+        self.pending.push(cmd);
+    }
+
     /// Update the window list snapshot (call after any add/remove/move).
     pub fn update_windows(&mut self, windows: Vec<WindowStatus>) {
         self.windows = windows;
     }
 
-    fn handle_connection(mut stream: UnixStream, windows: &[WindowStatus]) -> Option<EmbedCommand> {
+    /// Send an `output_geometry` event to every connection subscribed to
+    /// `output_name`. Subscribers whose socket has gone away, or is too
+    /// backed up to take the whole line, are dropped.
+    pub fn broadcast_output_geometry(&mut self, output_name: &str, x: i32, y: i32, w: u32, h: u32) {
+        let event = EmbedEvent::OutputGeometry {
+            output: output_name.to_owned(),
+            x,
+            y,
+            w,
+            h,
+        };
+        let Ok(mut json) = serde_json::to_string(&event) else {
+            return;
+        };
+        json.push('\n');
+        self.subscribers.retain_mut(|(output, stream)| {
+            output != output_name || stream.write_all(json.as_bytes()).is_ok()
+        });
+    }
+
+    /// Answer one connection. Returns the command to queue along with its
+    /// stream, which the caller keeps open for `subscribe`.
+    fn handle_connection(
+        mut stream: UnixStream,
+        windows: &[WindowStatus],
+    ) -> Option<(EmbedCommand, UnixStream)> {
         stream.set_nonblocking(false).ok();
         let mut reader = BufReader::new(stream.try_clone().ok()?);
         let mut line = String::new();
@@ -228,7 +299,7 @@ impl EmbedIpcServer {
             stream.write_all(j.as_bytes()).ok();
         }
 
-        Some(cmd)
+        Some((cmd, stream))
     }
 }
 
@@ -238,6 +309,7 @@ impl Default for EmbedIpcServer {
             listener: None,
             pending: Vec::new(),
             windows: Vec::new(),
+            subscribers: Vec::new(),
         }
     }
 }
@@ -302,3 +374,61 @@ pub fn move_embedded(
         h,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn geometry(output: &str, w: u32, h: u32) -> EmbedEvent {
+        EmbedEvent::OutputGeometry {
+            output: output.into(),
+            x: 0,
+            y: 0,
+            w,
+            h,
+        }
+    }
+
+    fn read_events(stream: &UnixStream, n: usize) -> Vec<EmbedEvent> {
+        let mut reader = BufReader::new(stream);
+        (0..n)
+            .filter_map(|_| {
+                let mut line = String::new();
+                reader.read_line(&mut line).ok()?;
+                serde_json::from_str(line.trim()).ok()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn output_geometry_reaches_subscribers_of_that_output() {
+        let mut server = EmbedIpcServer::default();
+        let (a_client, a_server) = UnixStream::pair().unwrap();
+        let (b_client, b_server) = UnixStream::pair().unwrap();
+        let (other_client, other_server) = UnixStream::pair().unwrap();
+        server.subscribers.push(("DP-1".into(), a_server));
+        server.subscribers.push(("DP-1".into(), b_server));
+        server.subscribers.push(("HDMI-A-1".into(), other_server));
+
+        server.broadcast_output_geometry("DP-1", 0, 0, 1920, 1080);
+        server.broadcast_output_geometry("DP-1", 0, 0, 2560, 1440);
+
+        let expected = [geometry("DP-1", 1920, 1080), geometry("DP-1", 2560, 1440)];
+        assert_eq!(read_events(&a_client, 2), expected);
+        assert_eq!(read_events(&b_client, 2), expected);
+        other_client.set_nonblocking(true).unwrap();
+        assert!(read_events(&other_client, 1).is_empty());
+
+        // A subscriber that hung up is forgotten on the next broadcast.
+        drop(a_client);
+        server.broadcast_output_geometry("DP-1", 0, 0, 2560, 1440);
+        assert_eq!(server.subscribers.len(), 2);
+    }
+
+    #[test]
+    fn subscribe_parses() {
+        let cmd: EmbedCommand =
+            serde_json::from_str(r#"{"cmd":"subscribe","output":"DP-1"}"#).unwrap();
+        assert!(matches!(cmd, EmbedCommand::Subscribe { output } if output == "DP-1"));
+    }
+}
@@ -20,6 +20,7 @@ use smithay::{
     wayland::{compositor::with_states, shell::xdg::ToplevelSurface},
 };
 
+use crate::embedded_ipc::EmbedCommand;
 use crate::shared_frame_shm::ShmWriter;
 
 // ── Placement ─────────────────────────────────────────────────────────────────
@@ -42,6 +43,55 @@ struct PendingPlacement {
     placement: EmbeddedPlacement,
 }
 
+// ── TWM-side geometry sync ────────────────────────────────────────────────────
+
+/// Geometry tracker for one embedded pane, owned by `TwmState`.
+///
+/// `set_geometry` queues an `EmbedCommand::Move` only when the pixel rect
+/// differs from the last one sent, so a settled layout never re-sends.
+/// The compositor drains the queue into `EmbedIpcServer::push` each tick.
+#[derive(Debug, Clone)]
+pub struct EmbeddedWindow {
+    pub app_id: String,
+    pub last_sent_rect: Option<EmbeddedPlacement>,
+    outbox: Vec<EmbedCommand>,
+}
+
+impl EmbeddedWindow {
+    pub fn new(app_id: &str) -> Self {
+        Self {
+            app_id: app_id.to_owned(),
+            last_sent_rect: None,
+            outbox: Vec::new(),
+        }
+    }
+
+    pub fn set_geometry(&mut self, x: i32, y: i32, w: u32, h: u32) {
+        let placement = EmbeddedPlacement {
+            x,
+            y,
+            w: w as i32,
+            h: h as i32,
+        };
+        if self.last_sent_rect == Some(placement) {
+            return;
+        }
+        self.last_sent_rect = Some(placement);
+        self.outbox.push(EmbedCommand::Move {
+            app_id: self.app_id.clone(),
+            x,
+            y,
+            w: placement.w,
+            h: placement.h,
+        });
+    }
+
+    /// Take every queued command, leaving the outbox empty.
+    pub fn drain(&mut self) -> Vec<EmbedCommand> {
+        std::mem::take(&mut self.outbox)
+    }
+}
+
 // ── Per-surface entry ─────────────────────────────────────────────────────────
 
 pub struct EmbeddedEntry {
@@ -195,9 +245,20 @@ impl EmbeddedManager {
             .collect()
     }
 
-    pub fn render_elements(&self) -> Vec<EmbeddedRenderElement> {
-        self.entries
-            .values()
+    /// Quads for the mapped windows. With `visible`, the TWM's on-screen
+    /// app_ids bottom to top, only those are drawn, topmost first; without
+    /// a TWM every mapped window is.
+    pub fn render_elements(&self, visible: Option<&[String]>) -> Vec<EmbeddedRenderElement> {
+        let entries: Vec<&EmbeddedEntry> = match visible {
+            Some(apps) => apps
+                .iter()
+                .rev()
+                .filter_map(|app_id| self.entries.get(app_id))
+                .collect(),
+            None => self.entries.values().collect(),
+        };
+        entries
+            .into_iter()
             .filter_map(|e| {
                 let tex = e.texture.as_ref()?;
                 if !e.mapped {
@@ -2,15 +2,30 @@
 //
 // Synthetic characters (box drawing, block elements, braille, Powerline) are
 // rendered pixel-perfectly via box_drawing::render_box_char() rather than going
-// through the font outline path. Everything else uses ab_glyph.
+// through the font outline path. Everything else uses ab_glyph: the requested
+// face, then regular, then each `with_fallback_fonts` face in order. Glyphs
+// none of those can outline come from the `with_color_emoji` face's PNG
+// strikes (CBDT/sbix), stored in the atlas in full colour.
+//
+// The atlas is a stack of fixed-size pages, each its own texture. Glyphs fill
+// the current page shelf by shelf; a full page opens the next one, up to
+// ATLAS_MAX_PAGES, and only then are glyphs compacted and evicted. A glyph's
+// UVs are relative to its page, so opening a page never moves anything.
 
-use ab_glyph::{Font, FontRef, GlyphId, PxScale, ScaleFont};
-use std::collections::HashMap;
+use ab_glyph::{Font, FontRef, GlyphId, PxScale, ScaleFont, VariableFont};
+use image::imageops::FilterType;
+use rustybuzz::ttf_parser::{self, RasterImageFormat};
+use serde::Serialize;
+use std::path::Path;
+use std::{cmp::Reverse, collections::HashMap};
 
 use crate::box_drawing::render_box_char;
 use crate::shaper::is_synthetic;
 
+/// Edge length of every atlas page.
 pub const ATLAS_SIZE: u32 = 2048;
+/// Pages opened before the atlas falls back to compaction and eviction.
+pub const ATLAS_MAX_PAGES: usize = 8;
 // 2px gap prevents LINEAR filter bleed between adjacent glyph bitmaps.
 const GAP: u32 = 2;
 
@@ -25,6 +40,57 @@ pub struct GlyphInfo {
     pub bearing_x: i32,
     pub bearing_y: i32,
     pub advance: i32,
+    /// The atlas texels carry the glyph's own colours (emoji), not white
+    /// coverage to be tinted with the text colour.
+    pub is_color: bool,
+    /// Index into `GlyphAtlas::pages` of the page the UVs refer to.
+    pub page: u32,
+}
+
+/// Snapshot of atlas utilisation (see `GlyphAtlas::get_atlas_stats`).
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct AtlasStats {
+    pub total_glyphs: usize,
+    pub bytes_used: usize,
+    pub bytes_total: usize,
+    pub fill_percentage: f32,
+}
+
+/// One `atlas_size`² RGBA texture of packed glyphs.
+pub struct AtlasPage {
+    pub pixels: Vec<u8>,
+    pub cursor_x: u32,
+    pub cursor_y: u32,
+    pub row_h: u32,
+    /// GL texture holding `pixels`; 0 until the renderer first uploads it.
+    pub tex: u32,
+    pub dirty: bool,
+    /// Rows `[min_y, max_y)` written since the last GPU upload.
+    pub dirty_region: Option<(u32, u32)>,
+}
+
+impl AtlasPage {
+    fn new(size: u32) -> Self {
+        Self {
+            pixels: vec![0u8; (size * size * 4) as usize],
+            cursor_x: 0,
+            cursor_y: 0,
+            row_h: 0,
+            tex: 0,
+            dirty: true,
+            dirty_region: None,
+        }
+    }
+
+    /// Extend the region `patch_atlas` re-uploads to cover rows
+    /// `[y_start, y_end)`.
+    pub fn mark_dirty_range(&mut self, y_start: u32, y_end: u32) {
+        self.dirty_region = Some(match self.dirty_region {
+            Some((lo, hi)) => (lo.min(y_start), hi.max(y_end)),
+            None => (y_start, y_end),
+        });
+        self.dirty = true;
+    }
 }
 
 // Cache key for char-based lookups (regular path + synthetic).
@@ -43,6 +109,395 @@ struct GlyphIdKey {
     italic: bool,
 }
 
+// Cache key for icons, which are rasterised at their own pixel size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct IconKey {
+    ch: char,
+    size: u32,
+}
+
+// Any glyph's key in `PageStore`, whichever lookup cached it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum CacheKey {
+    Char(GlyphKey),
+    Id(GlyphIdKey),
+    Icon(IconKey),
+}
+
+// What `blit_bitmap` is handed, which decides how it is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BitmapKind {
+    // Box drawing etc.: exactly one cell, alpha-only or RGBA coverage.
+    Synthetic,
+    // A font outline's alpha coverage.
+    Glyph,
+    // Color emoji RGBA, stored as it is.
+    Color,
+}
+
+impl BitmapKind {
+    fn label(self) -> &'static str {
+        match self {
+            BitmapKind::Synthetic => "synthetic",
+            BitmapKind::Glyph => "glyph",
+            BitmapKind::Color => "color",
+        }
+    }
+}
+
+// ── glyph store ───────────────────────────────────────────────────────────────
+
+/// The glyph caches and the pages their bitmaps are packed into: all of the
+/// atlas but its fonts.
+pub struct PageStore {
+    /// Every glyph looked up so far; `None` for one no face could draw.
+    glyphs: HashMap<CacheKey, Option<GlyphInfo>>,
+    /// Access tick of every entry in `glyphs`; `clock` is the latest tick
+    /// handed out.
+    last_used: HashMap<CacheKey, u64>,
+    clock: u64,
+    /// Never shrinks: compaction empties pages rather than dropping them,
+    /// so their textures stay valid.
+    pub pages: Vec<AtlasPage>,
+    /// The page new glyphs are packed into.
+    current: usize,
+    /// Edge length of every page.
+    pub atlas_size: u32,
+    /// Past this many pages, glyphs are evicted instead.
+    max_pages: usize,
+    /// Bumped by every compaction: UVs read before a bump are stale.
+    pub compactions: u64,
+}
+
+impl PageStore {
+    fn new(atlas_size: u32, max_pages: usize) -> Self {
+        Self {
+            glyphs: HashMap::new(),
+            last_used: HashMap::new(),
+            clock: 0,
+            pages: vec![AtlasPage::new(atlas_size)],
+            current: 0,
+            atlas_size,
+            max_pages: max_pages.max(1),
+            compactions: 0,
+        }
+    }
+
+    /// The cached entry for `key`, marked as just used; `None` on a miss.
+    fn get(&mut self, key: CacheKey) -> Option<Option<GlyphInfo>> {
+        let cached = *self.glyphs.get(&key)?;
+        self.touch(key);
+        Some(cached)
+    }
+
+    fn insert(&mut self, key: CacheKey, info: Option<GlyphInfo>) {
+        self.glyphs.insert(key, info);
+        self.touch(key);
+    }
+
+    // ── LRU eviction ──────────────────────────────────────────────────────────
+
+    fn touch(&mut self, key: CacheKey) {
+        self.clock += 1;
+        self.last_used.insert(key, self.clock);
+    }
+
+    fn forget(&mut self, key: CacheKey) {
+        self.glyphs.remove(&key);
+        self.last_used.remove(&key);
+    }
+
+    /// Drop the `n` least recently used glyphs and compact the rest. They
+    /// are rasterised again on their next lookup. UVs handed out before
+    /// this call are stale afterwards.
+    pub fn evict_lru(&mut self, n: usize) {
+        let mut by_age: Vec<(u64, CacheKey)> =
+            self.last_used.iter().map(|(&k, &t)| (t, k)).collect();
+        by_age.sort_unstable_by_key(|&(t, _)| t);
+        for (_, key) in by_age.into_iter().take(n) {
+            self.forget(key);
+        }
+        self.compact();
+    }
+
+    /// Re-pack every cached glyph from page 0 on, tallest first so the
+    /// shelves waste less height, and move their UVs with them. Every page
+    /// is flagged for `patch_atlas`.
+    fn compact(&mut self) {
+        self.compactions += 1;
+        let size = self.atlas_size;
+        let half = 0.5 / size as f32;
+        let mut glyphs: Vec<(CacheKey, GlyphInfo)> = self
+            .glyphs
+            .iter()
+            .filter_map(|(&k, g)| Some((k, (*g)?)))
+            .filter(|(_, g)| g.width > 0 && g.height > 0)
+            .collect();
+        glyphs.sort_by_key(|(_, g)| Reverse(g.height));
+
+        let mut packed: Vec<AtlasPage> = self.pages.iter().map(|_| AtlasPage::new(size)).collect();
+        let mut current = 0;
+        let row = size as usize * 4;
+        for (key, mut g) in glyphs {
+            let (w, h) = (g.width as u32, g.height as u32);
+            let fits = loop {
+                let page = &mut packed[current];
+                if page.cursor_x + w + GAP > size {
+                    page.cursor_y += page.row_h + GAP;
+                    page.cursor_x = 0;
+                    page.row_h = 0;
+                }
+                if page.cursor_y + h + GAP <= size {
+                    break true;
+                }
+                if current + 1 == packed.len() {
+                    break false;
+                }
+                current += 1;
+            };
+            if !fits {
+                // Shelf order changed and this one no longer fits.
+                self.forget(key);
+                continue;
+            }
+            let src = &self.pages[g.page as usize].pixels;
+            let dst = &mut packed[current];
+            let sx = ((g.uv_x - half) * size as f32).round() as usize;
+            let sy = ((g.uv_y - half) * size as f32).round() as usize;
+            for r in 0..h as usize {
+                let from = (sy + r) * row + sx * 4;
+                let to = (dst.cursor_y as usize + r) * row + dst.cursor_x as usize * 4;
+                dst.pixels[to..to + w as usize * 4]
+                    .copy_from_slice(&src[from..from + w as usize * 4]);
+            }
+            g.uv_x = dst.cursor_x as f32 / size as f32 + half;
+            g.uv_y = dst.cursor_y as f32 / size as f32 + half;
+            g.page = current as u32;
+            self.glyphs.insert(key, Some(g));
+            dst.cursor_x += w + GAP;
+            dst.row_h = dst.row_h.max(h);
+        }
+
+        // Keep the textures: the renderer patches them in place.
+        for (page, mut fresh) in self.pages.iter_mut().zip(packed) {
+            fresh.tex = page.tex;
+            fresh.mark_dirty_range(0, size);
+            *page = fresh;
+        }
+        self.current = current;
+    }
+
+    // ── packing ───────────────────────────────────────────────────────────────
+
+    /// Cached glyph count and an approximation of packed bytes: on every
+    /// page, each full shelf above the cursor plus the current shelf up to
+    /// `cursor_x`.
+    pub fn stats(&self) -> AtlasStats {
+        let bytes_used = self
+            .pages
+            .iter()
+            .map(|p| (p.cursor_y * self.atlas_size * 4 + p.cursor_x * p.row_h * 4) as usize)
+            .sum();
+        let bytes_total = self.pages.iter().map(|p| p.pixels.len()).sum();
+        AtlasStats {
+            total_glyphs: self.glyphs.len(),
+            bytes_used,
+            bytes_total,
+            fill_percentage: bytes_used as f32 / bytes_total.max(1) as f32 * 100.0,
+        }
+    }
+
+    /// Move packing on to the next page, allocating it on first use. False
+    /// once the last page allowed is the current one.
+    fn open_page(&mut self) -> bool {
+        if self.current + 1 >= self.max_pages {
+            return false;
+        }
+        self.current += 1;
+        if self.current == self.pages.len() {
+            self.pages.push(AtlasPage::new(self.atlas_size));
+            tracing::info!(
+                "Glyph atlas page {} of {} opened",
+                self.current + 1,
+                self.max_pages
+            );
+        }
+        true
+    }
+
+    /// Copy the top `rows` rows of a `w`×`h` bitmap into the current page,
+    /// opening, compacting and evicting as needed to make room. Only the
+    /// placement of the returned glyph is filled in; bearings and advance
+    /// are the caller's.
+    fn pack(
+        &mut self,
+        bitmap: &[u8],
+        w: u32,
+        h: u32,
+        rows: u32,
+        kind: BitmapKind,
+    ) -> Option<GlyphInfo> {
+        let label = kind.label();
+        // No page could ever hold it; don't evict everything finding out.
+        let size = self.atlas_size;
+        if w + GAP > size || rows + GAP > size {
+            tracing::warn!("{label} of {w}×{rows} is larger than an atlas page — dropped");
+            return None;
+        }
+
+        // Full: open a page, then compact, then evict the older half until
+        // it fits.
+        let mut compacted = false;
+        loop {
+            let page = &mut self.pages[self.current];
+            if page.cursor_x + w + GAP > size {
+                page.cursor_y += page.row_h + GAP;
+                page.cursor_x = 0;
+                page.row_h = 0;
+            }
+            if page.cursor_y + rows + GAP <= size {
+                break;
+            }
+            if self.open_page() {
+                continue;
+            }
+            if !compacted {
+                compacted = true;
+                self.compact();
+                continue;
+            }
+            let live = self.last_used.len();
+            if live == 0 {
+                tracing::warn!("Atlas full — {label} dropped");
+                return None;
+            }
+            tracing::debug!(
+                "Atlas full — evicting {} of {live} glyphs",
+                live.div_ceil(2)
+            );
+            self.evict_lru(live.div_ceil(2));
+        }
+
+        let aw = size as usize;
+        let is_color = kind == BitmapKind::Color;
+        let page = &mut self.pages[self.current];
+        for py in 0..rows {
+            for px in 0..w {
+                let src_idx = (py * w + px) as usize;
+                let base = ((page.cursor_y + py) as usize * aw + (page.cursor_x + px) as usize) * 4;
+                if is_color {
+                    // Color emoji — keep the RGBA as it is.
+                    page.pixels[base..base + 4]
+                        .copy_from_slice(&bitmap[src_idx * 4..src_idx * 4 + 4]);
+                    continue;
+                }
+                // bitmap may be alpha-only (font path) OR RGBA (box_drawing path).
+                // We always produce RGBA in the atlas.
+                let alpha = if bitmap.len() == (w * h) as usize {
+                    // alpha-only (font outline path)
+                    bitmap[src_idx]
+                } else {
+                    // RGBA (box_drawing path) — channel 3 is alpha
+                    bitmap[src_idx * 4 + 3]
+                };
+                page.pixels[base] = 0xFF;
+                page.pixels[base + 1] = 0xFF;
+                page.pixels[base + 2] = 0xFF;
+                page.pixels[base + 3] = alpha;
+            }
+        }
+
+        // UV coordinates: inset by 0.5 texel so NEAREST sampling always fetches
+        // the correct atlas pixel even when floating-point UV values land on a
+        // texel boundary. Without this inset, borderline UVs can round the wrong
+        // direction and fetch a neighbouring (possibly empty) texel.
+        let size = size as f32;
+        let half = 0.5 / size;
+        let info = GlyphInfo {
+            uv_x: page.cursor_x as f32 / size + half,
+            uv_y: page.cursor_y as f32 / size + half,
+            uv_w: w as f32 / size - 2.0 * half,
+            uv_h: rows as f32 / size - 2.0 * half,
+            width: w as i32,
+            height: rows as i32,
+            bearing_x: 0,
+            bearing_y: 0,
+            advance: 0,
+            is_color,
+            page: self.current as u32,
+        };
+
+        page.mark_dirty_range(page.cursor_y, page.cursor_y + rows);
+        page.cursor_x += w + GAP;
+        if rows > page.row_h {
+            page.row_h = rows;
+        }
+
+        let stats = self.stats();
+        if stats.fill_percentage > 80.0 {
+            tracing::debug!(
+                "Atlas {:.1}% full ({} glyphs, {}/{} bytes)",
+                stats.fill_percentage,
+                stats.total_glyphs,
+                stats.bytes_used,
+                stats.bytes_total
+            );
+        }
+        Some(info)
+    }
+}
+
+/// Size and packing settings for `GlyphAtlas::new`.
+#[derive(Debug, Clone, Copy)]
+pub struct AtlasOptions {
+    pub size_px: f32,
+    /// Cell height as a multiple of the font's line height.
+    pub line_spacing: f32,
+    pub dpi: u32,
+    pub axes: FontAxes,
+    /// Side of one atlas page, in pixels.
+    pub atlas_size: u32,
+    /// Pages opened before glyphs are compacted and evicted.
+    pub max_pages: usize,
+}
+
+impl Default for AtlasOptions {
+    fn default() -> Self {
+        Self {
+            size_px: 16.0,
+            line_spacing: 1.1,
+            dpi: 96,
+            axes: FontAxes::default(),
+            atlas_size: ATLAS_SIZE,
+            max_pages: ATLAS_MAX_PAGES,
+        }
+    }
+}
+
+/// Variation axis values for variable fonts (OpenType `fvar`); `None`
+/// leaves an axis at the font's default. Static fonts ignore them.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FontAxes {
+    /// `wght`: 100 thin … 400 regular … 700 bold … 900 black.
+    pub weight: Option<f32>,
+    /// `wdth`: percent of normal width, 100 = normal.
+    pub width: Option<f32>,
+}
+
+impl FontAxes {
+    /// Weight the bold face is set to when the regular face can vary it.
+    pub const BOLD_WEIGHT: f32 = 700.0;
+
+    fn apply(&self, font: &mut FontRef) {
+        if let Some(wght) = self.weight {
+            font.set_variation(b"wght", wght);
+        }
+        if let Some(wdth) = self.width {
+            font.set_variation(b"wdth", wdth);
+        }
+    }
+}
+
 // Owned font bytes + parsed ab_glyph handle together.
 // We use a raw pointer trick so FontRef's lifetime is tied to the Vec
 // inside the same struct, which is never moved after creation.
@@ -55,12 +510,13 @@ struct OwnedFont {
 }
 
 impl OwnedFont {
-    fn new(data: Vec<u8>, scale: PxScale) -> Result<Self, String> {
-        let font_ref: FontRef<'static> = unsafe {
+    fn new(data: Vec<u8>, scale: PxScale, axes: &FontAxes) -> Result<Self, String> {
+        let mut font_ref: FontRef<'static> = unsafe {
             let slice: &[u8] = &data;
             let extended: &'static [u8] = &*(slice as *const [u8]);
             FontRef::try_from_slice(extended).map_err(|e| format!("ab_glyph parse error: {e}"))?
         };
+        axes.apply(&mut font_ref);
         Ok(Self {
             _bytes: data,
             font: font_ref,
@@ -71,23 +527,44 @@ impl OwnedFont {
     fn scaled(&self) -> ab_glyph::PxScaleFont<&FontRef<'static>> {
         self.font.as_scaled(self.scale)
     }
+
+    fn has_axis(&self, tag: &[u8; 4]) -> bool {
+        self.font.variations().iter().any(|v| &v.tag == tag)
+    }
+}
+
+// Owned font bytes + the ttf_parser face reading its bitmap strikes, with the
+// same lifetime trick as OwnedFont.
+struct OwnedColorFont {
+    _bytes: Vec<u8>,
+    face: ttf_parser::Face<'static>,
+}
+
+impl OwnedColorFont {
+    fn new(data: Vec<u8>) -> Result<Self, String> {
+        let face = unsafe {
+            let slice: &[u8] = &data;
+            let extended: &'static [u8] = &*(slice as *const [u8]);
+            ttf_parser::Face::parse(extended, 0).map_err(|e| format!("ttf_parser error: {e}"))?
+        };
+        Ok(Self { _bytes: data, face })
+    }
 }
 
 pub struct GlyphAtlas {
     regular: OwnedFont,
     bold: Option<OwnedFont>,
     italic: Option<OwnedFont>,
+    /// Tried in order for codepoints the regular face lacks.
+    fallback_fonts: Vec<OwnedFont>,
+    /// Bitmap emoji, for codepoints no outline face can draw.
+    color_emoji_font: Option<OwnedColorFont>,
     pub size_px: f32,
-    cache: HashMap<GlyphKey, Option<GlyphInfo>>,
-    id_cache: HashMap<GlyphIdKey, Option<GlyphInfo>>,
-    pub pixels: Vec<u8>,
-    pub cursor_x: u32,
-    pub cursor_y: u32,
-    pub row_h: u32,
+    /// Cached glyphs and the pages they are packed into.
+    pub store: PageStore,
     pub cell_w: u32,
     pub cell_h: u32,
     pub ascender: i32,
-    pub dirty: bool,
 }
 
 impl GlyphAtlas {
@@ -95,10 +572,16 @@ impl GlyphAtlas {
         font_data: &[u8],
         bold_data: Option<&[u8]>,
         italic_data: Option<&[u8]>,
-        size_px: f32,
-        line_spacing: f32,
-        dpi: u32,
+        opts: &AtlasOptions,
     ) -> Result<Self, String> {
+        let AtlasOptions {
+            size_px,
+            line_spacing,
+            dpi,
+            ref axes,
+            atlas_size,
+            max_pages,
+        } = *opts;
         // ── PxScale vs FreeType pixel size ────────────────────────────────────
         //
         // ab_glyph PxScale(n) sets:  ascent + |descent| = n  (in pixels)
@@ -125,7 +608,7 @@ impl GlyphAtlas {
             let asc = tmp.ascent_unscaled();
             let dsc = tmp.descent_unscaled(); // negative
             let height_unscaled = asc - dsc; // positive total
-            let ratio = height_unscaled / upm;
+            let ratio = em_ratio(&tmp);
             tracing::info!(
                 "font em_scale: UPM={upm} ascent_u={asc} descent_u={dsc} \
                  height_u={height_unscaled} ratio={ratio:.4} \
@@ -136,19 +619,31 @@ impl GlyphAtlas {
         };
         let scale = PxScale::from(em_scale);
 
-        let regular = OwnedFont::new(font_data.to_vec(), scale)?;
-        let bold = bold_data
-            .map(|d| OwnedFont::new(d.to_vec(), scale))
-            .transpose()
-            .unwrap_or(None);
+        let regular = OwnedFont::new(font_data.to_vec(), scale, axes)?;
+        // A variable regular face draws bold itself at wght 700, matching it
+        // exactly, rather than loading a separate bold file.
+        let variable_bold = regular.has_axis(b"wght");
+        let bold = if variable_bold {
+            let bold_axes = FontAxes {
+                weight: Some(FontAxes::BOLD_WEIGHT),
+                ..*axes
+            };
+            OwnedFont::new(font_data.to_vec(), scale, &bold_axes).ok()
+        } else {
+            bold_data
+                .map(|d| OwnedFont::new(d.to_vec(), scale, axes))
+                .transpose()
+                .unwrap_or(None)
+        };
         let italic = italic_data
-            .map(|d| OwnedFont::new(d.to_vec(), scale))
+            .map(|d| OwnedFont::new(d.to_vec(), scale, axes))
             .transpose()
             .unwrap_or(None);
 
         tracing::info!(
-            "GlyphAtlas faces — regular: ok, bold: {}, italic: {}",
+            "GlyphAtlas faces — regular: ok, bold: {}{}, italic: {}",
             bold.is_some(),
+            if variable_bold { " (wght)" } else { "" },
             italic.is_some()
         );
 
@@ -216,17 +711,13 @@ impl GlyphAtlas {
             regular,
             bold,
             italic,
+            fallback_fonts: Vec::new(),
+            color_emoji_font: None,
             size_px,
-            cache: HashMap::new(),
-            id_cache: HashMap::new(),
-            pixels: vec![0u8; (ATLAS_SIZE * ATLAS_SIZE * 4) as usize],
-            cursor_x: 0,
-            cursor_y: 0,
-            row_h: 0,
+            store: PageStore::new(atlas_size, max_pages),
             cell_w,
             cell_h,
             ascender,
-            dirty: true,
         };
 
         // Pre-warm printable ASCII (all three variants).
@@ -246,6 +737,11 @@ impl GlyphAtlas {
                 atlas.glyph(ch, false, false);
             }
         }
+        for cp in (0x1FB00u32..=0x1FBFF).filter(|&cp| is_synthetic(cp)) {
+            if let Some(ch) = char::from_u32(cp) {
+                atlas.glyph(ch, false, false);
+            }
+        }
         for cp in [0xE0B0u32, 0xE0B1, 0xE0B2, 0xE0B3] {
             if let Some(ch) = char::from_u32(cp) {
                 atlas.glyph(ch, false, false);
@@ -255,15 +751,55 @@ impl GlyphAtlas {
         Ok(atlas)
     }
 
+    /// Add faces to try, in order, when the regular face has no glyph for a
+    /// codepoint. Files that can't be read or parsed are skipped with a
+    /// warning. Only glyphs rasterised afterwards use them, so call this
+    /// before drawing anything outside printable ASCII.
+    pub fn with_fallback_fonts(mut self, paths: &[&Path]) -> Self {
+        for path in paths {
+            let face = std::fs::read(path)
+                .map_err(|e| e.to_string())
+                .and_then(|data| {
+                    // Same em size as the regular face, whatever the file's
+                    // ascent/descent.
+                    let ratio = FontRef::try_from_slice(&data)
+                        .map(|f| em_ratio(&f))
+                        .map_err(|e| format!("ab_glyph parse error: {e}"))?;
+                    OwnedFont::new(
+                        data,
+                        PxScale::from(self.size_px * ratio),
+                        &FontAxes::default(),
+                    )
+                });
+            match face {
+                Ok(face) => self.fallback_fonts.push(face),
+                Err(e) => tracing::warn!("fallback font {}: {e}", path.display()),
+            }
+        }
+        tracing::info!("GlyphAtlas fallback faces: {}", self.fallback_fonts.len());
+        self
+    }
+
+    /// Use the font in `data` for color emoji: glyphs that no outline face
+    /// draws are taken from its PNG bitmap strikes (CBDT or sbix tables).
+    /// A font that doesn't parse is skipped with a warning.
+    pub fn with_color_emoji(mut self, data: &[u8]) -> Self {
+        match OwnedColorFont::new(data.to_vec()) {
+            Ok(font) => self.color_emoji_font = Some(font),
+            Err(e) => tracing::warn!("color emoji font: {e}"),
+        }
+        self
+    }
+
     // ── char-based lookup ──────────────────────────────────────────────────────
 
     pub fn glyph(&mut self, ch: char, bold: bool, italic: bool) -> Option<GlyphInfo> {
-        let key = GlyphKey { ch, bold, italic };
-        if let Some(&cached) = self.cache.get(&key) {
+        let key = CacheKey::Char(GlyphKey { ch, bold, italic });
+        if let Some(cached) = self.store.get(key) {
             return cached;
         }
         let info = self.rasterise_char(ch, bold, italic);
-        self.cache.insert(key, info);
+        self.store.insert(key, info);
         info
     }
 
@@ -282,13 +818,62 @@ impl GlyphAtlas {
                     0,                  // bearing_x: flush left
                     self.ascender,      // bearing_y: position at top of cell
                     self.cell_w as i32, // advance: exactly one cell
-                    "synthetic",
+                    BitmapKind::Synthetic,
                 );
             }
             // render_box_char returned None for an unrecognised synthetic codepoint —
             // fall through to the font path as a last resort.
         }
 
+        let (font_ptr, glyph_id) = self.pick_face(ch, bold, italic);
+        let outline = if glyph_id == GlyphId(0) {
+            None
+        } else {
+            self.rasterise_glyph_from_ptr(glyph_id, font_ptr)
+        };
+        // Nothing drawn — no face has it, or its face only has a bitmap.
+        if outline.is_none_or(|g| g.width == 0) {
+            if let Some(info) = self.rasterise_color(ch) {
+                return Some(info);
+            }
+        }
+        if glyph_id == GlyphId(0) {
+            return self.rasterise_glyph_from_ptr(glyph_id, font_ptr); // .notdef
+        }
+        outline
+    }
+
+    // `ch` from the color emoji face: the strike nearest cell_h, decoded and
+    // scaled to cell_h tall, sitting on the top of the cell.
+    fn rasterise_color(&mut self, ch: char) -> Option<GlyphInfo> {
+        let face = &self.color_emoji_font.as_ref()?.face;
+        let id = face.glyph_index(ch)?;
+        let strike = face.glyph_raster_image(id, self.cell_h.min(u16::MAX as u32) as u16)?;
+        if strike.format != RasterImageFormat::PNG {
+            return None;
+        }
+        let png = image::load_from_memory_with_format(strike.data, image::ImageFormat::Png)
+            .map_err(|e| tracing::debug!("emoji {ch:?}: {e}"))
+            .ok()?
+            .into_rgba8();
+        let h = self.cell_h;
+        let w = (png.width() as f32 * h as f32 / png.height().max(1) as f32).round() as u32;
+        let rgba = image::imageops::resize(&png, w.max(1), h, FilterType::Triangle);
+        let w = rgba.width();
+        self.blit_bitmap(
+            rgba.into_raw(),
+            w,
+            h,
+            0,
+            self.ascender,
+            w as i32,
+            BitmapKind::Color,
+        )
+    }
+
+    // The face that draws `ch` and its glyph id there. GlyphId(0) only if no
+    // face has it, in which case regular's .notdef is drawn.
+    fn pick_face(&self, ch: char, bold: bool, italic: bool) -> (*const OwnedFont, GlyphId) {
         // ── Font outline path ──────────────────────────────────────────────────
         // Pick font pointer — SAFETY: always points into self which outlives this call.
         let font_ptr: *const OwnedFont = if bold && self.bold.is_some() {
@@ -314,7 +899,16 @@ impl GlyphAtlas {
             glyph_id
         };
 
-        self.rasterise_glyph_from_ptr(final_id, final_ptr)
+        // Regular lacks it too: first fallback face that has it.
+        if final_id == GlyphId(0) {
+            for face in &self.fallback_fonts {
+                let id = face.font.glyph_id(ch);
+                if id != GlyphId(0) {
+                    return (face as *const _, id);
+                }
+            }
+        }
+        (final_ptr, final_id)
     }
 
     // Rasterise glyph_id using the font at `font_ptr` (raw ptr to avoid
@@ -324,8 +918,20 @@ impl GlyphAtlas {
         glyph_id: GlyphId,
         font_ptr: *const OwnedFont,
     ) -> Option<GlyphInfo> {
-        // SAFETY: font_ptr always points into self.{regular,bold,italic}.
-        let sf = unsafe { (*font_ptr).scaled() };
+        // SAFETY: font_ptr always points into self.{regular,bold,italic,fallback_fonts}.
+        let scale = unsafe { (*font_ptr).scale };
+        self.rasterise_at(glyph_id, font_ptr, scale)
+    }
+
+    // `rasterise_glyph_from_ptr` at `scale` rather than the face's own.
+    fn rasterise_at(
+        &mut self,
+        glyph_id: GlyphId,
+        font_ptr: *const OwnedFont,
+        scale: PxScale,
+    ) -> Option<GlyphInfo> {
+        // SAFETY: as for rasterise_glyph_from_ptr.
+        let sf = unsafe { (*font_ptr).font.as_scaled(scale) };
         let advance = sf.h_advance(glyph_id).ceil() as i32;
         let ascent_px = sf.ascent().round();
         let glyph = glyph_id.with_scale_and_position(sf.scale, ab_glyph::point(0.0, ascent_px));
@@ -363,20 +969,24 @@ impl GlyphAtlas {
                 bearing_x,
                 bearing_y,
                 advance,
+                is_color: false,
+                page: 0,
             }),
-            Some(buf) => self.blit_bitmap(buf, w, h, bearing_x, bearing_y, advance, "glyph"),
+            Some(buf) => {
+                self.blit_bitmap(buf, w, h, bearing_x, bearing_y, advance, BitmapKind::Glyph)
+            }
         }
     }
 
     // ── glyph-id-based lookup (shaped/ligature path) ───────────────────────────
 
     pub fn glyph_by_id(&mut self, id: u32, bold: bool, italic: bool) -> Option<GlyphInfo> {
-        let key = GlyphIdKey { id, bold, italic };
-        if let Some(&cached) = self.id_cache.get(&key) {
+        let key = CacheKey::Id(GlyphIdKey { id, bold, italic });
+        if let Some(cached) = self.store.get(key) {
             return cached;
         }
         let info = self.rasterise_by_id(id, bold, italic);
-        self.id_cache.insert(key, info);
+        self.store.insert(key, info);
         info
     }
 
@@ -392,8 +1002,47 @@ impl GlyphAtlas {
         self.rasterise_glyph_from_ptr(glyph_id, font_ptr)
     }
 
+    // ── icons ─────────────────────────────────────────────────────────────────
+
+    /// `ch` rasterised to fit a `size × size` pixel box, for icons drawn
+    /// larger or smaller than the text around them. None for size 0 or a
+    /// codepoint no face has.
+    pub fn icon(&mut self, ch: char, size: u32) -> Option<GlyphInfo> {
+        let key = CacheKey::Icon(IconKey { ch, size });
+        if let Some(cached) = self.store.get(key) {
+            return cached;
+        }
+        let info = self.rasterise_icon(ch, size);
+        self.store.insert(key, info);
+        info
+    }
+
+    fn rasterise_icon(&mut self, ch: char, size: u32) -> Option<GlyphInfo> {
+        let (font_ptr, glyph_id) = self.pick_face(ch, false, false);
+        if size == 0 || glyph_id == GlyphId(0) {
+            return None;
+        }
+        // One em per box edge, shrunk if the outline overshoots it.
+        let em = PxScale::from(size as f32);
+        // SAFETY: pick_face only returns pointers into self.
+        let font = unsafe { &(*font_ptr).font };
+        let bounds = font.outline_glyph(glyph_id.with_scale(em))?.px_bounds();
+        let overshoot = bounds.width().max(bounds.height()) / size as f32;
+        let scale = if overshoot > 1.0 {
+            PxScale::from(size as f32 / overshoot)
+        } else {
+            em
+        };
+        self.rasterise_at(glyph_id, font_ptr, scale)
+    }
+
     // ── atlas blitter ─────────────────────────────────────────────────────────
 
+    /// Cached glyph count and packed bytes (see `PageStore::stats`).
+    pub fn get_atlas_stats(&self) -> AtlasStats {
+        self.store.stats()
+    }
+
     fn blit_bitmap(
         &mut self,
         bitmap_buf: Vec<u8>,
@@ -402,69 +1051,377 @@ impl GlyphAtlas {
         bearing_x: i32,
         bearing_y: i32,
         advance: i32,
-        label: &str,
+        kind: BitmapKind,
     ) -> Option<GlyphInfo> {
         // For synthetic glyphs the bitmap is exactly cell_w × cell_h.
         // For font glyphs we cap height at cell_h*2 as before.
-        let hu = if label == "synthetic" {
+        let rows = if kind == BitmapKind::Synthetic {
             h
         } else {
             h.min(self.cell_h * 2)
         };
+        let info = self.store.pack(&bitmap_buf, w, h, rows, kind)?;
+        Some(GlyphInfo {
+            bearing_x,
+            bearing_y,
+            advance,
+            ..info
+        })
+    }
+}
+
+/// Ascent-to-descent height over units-per-em: the PxScale for a pixel size
+/// is `size_px * em_ratio`, so the em square, not the line, is `size_px`.
+fn em_ratio(font: &FontRef) -> f32 {
+    let upm = font.units_per_em().unwrap_or(1000.0);
+    (font.ascent_unscaled() - font.descent_unscaled()) / upm
+}
+
+/// Pixel size of a `size_pt` font on a `dpi` display (1pt = 1/72in).
+pub fn font_size_px(size_pt: f32, dpi: f32) -> f32 {
+    size_pt * dpi / 72.0
+}
+
+/// Horizontal DPI of an output `width_px` wide and `width_mm` millimetres
+/// across. `None` when the panel reports no physical size (projectors,
+/// virtual outputs) and the caller has to fall back to a fixed size.
+pub fn output_dpi(width_px: u32, width_mm: u32) -> Option<f32> {
+    (width_px > 0 && width_mm > 0).then(|| width_px as f32 * 25.4 / width_mm as f32)
+}
+
+// ── tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn font_size_follows_dpi() {
+        assert_eq!(font_size_px(12.0, 96.0), 16.0);
+        assert_eq!(font_size_px(12.0, 192.0), 32.0);
+        assert!((font_size_px(13.0, 96.0) - 17.333).abs() < 0.001);
+        // 24" 1080p is ~92 DPI; a 27" 4K panel is ~163.
+        assert_eq!(output_dpi(1920, 531).map(f32::round), Some(92.0));
+        assert_eq!(output_dpi(3840, 597).map(f32::round), Some(163.0));
+        assert_eq!(output_dpi(1920, 0), None);
+    }
+
+    #[test]
+    #[ignore = "needs an installed variable font with a wght axis"]
+    fn variable_font_weight_axis() {
+        let path = crate::util::find_font("variable").expect("no variable font installed");
+        let data = std::fs::read(path).unwrap();
+        let ink = |weight: f32| {
+            let axes = FontAxes {
+                weight: Some(weight),
+                width: None,
+            };
+            let font = OwnedFont::new(data.clone(), PxScale::from(48.0), &axes).unwrap();
+            assert!(font.has_axis(b"wght"), "the variable font has no wght axis");
+            let sf = font.scaled();
+            let glyph = sf.font.glyph_id('A').with_scale(sf.scale);
+            let mut covered = 0;
+            sf.font.outline_glyph(glyph).unwrap().draw(|_, _, c| {
+                if c > 0.5 {
+                    covered += 1;
+                }
+            });
+            covered
+        };
+        let (light, heavy) = (ink(300.0), ink(800.0));
+        assert!(
+            heavy > light,
+            "wght 800 drew {heavy} pixels, wght 300 {light}"
+        );
+    }
 
-        if self.cursor_x + w + GAP > ATLAS_SIZE {
-            self.cursor_y += self.row_h + GAP;
-            self.cursor_x = 0;
-            self.row_h = 0;
+    #[test]
+    #[ignore = "needs an installed monospace font"]
+    fn atlas_stats_after_prewarm() {
+        let path = crate::util::find_font("mono").expect("no monospace font installed");
+        let data = std::fs::read(path).unwrap();
+        let atlas = GlyphAtlas::new(&data, None, None, &AtlasOptions::default()).unwrap();
+        let stats = atlas.get_atlas_stats();
+        assert!(stats.fill_percentage > 0.0);
+        assert!(stats.total_glyphs >= 95);
+        assert_eq!(stats.bytes_total, (ATLAS_SIZE * ATLAS_SIZE * 4) as usize);
+    }
+
+    #[test]
+    #[ignore = "needs an installed monospace font"]
+    fn icons_are_rasterised_at_their_size() {
+        let path = crate::util::find_font("mono").expect("no monospace font installed");
+        let data = std::fs::read(path).unwrap();
+        let mut atlas = GlyphAtlas::new(&data, None, None, &AtlasOptions::default()).unwrap();
+        let text = atlas.glyph('@', false, false).unwrap();
+        let small = atlas.icon('@', 12).unwrap();
+        let large = atlas.icon('@', 48).unwrap();
+        for (g, size) in [(small, 12), (large, 48)] {
+            assert!(g.width as u32 <= size && g.height as u32 <= size);
         }
-        if self.cursor_y + hu + GAP > ATLAS_SIZE {
-            tracing::warn!("Atlas full — {label} dropped");
-            return None;
+        assert!(small.height < text.height && large.height > text.height);
+        assert!(atlas.icon('@', 0).is_none());
+    }
+
+    #[test]
+    #[ignore = "needs an installed color emoji font"]
+    fn color_emoji_keeps_its_colours() {
+        let mono = crate::util::find_font("mono").expect("no monospace font installed");
+        let emoji = crate::util::find_font("coloremoji").expect("no color emoji font installed");
+        let data = std::fs::read(mono).unwrap();
+        let mut atlas = GlyphAtlas::new(&data, None, None, &AtlasOptions::default())
+            .unwrap()
+            .with_color_emoji(&std::fs::read(emoji).unwrap());
+        assert!(!atlas.glyph('a', false, false).unwrap().is_color);
+
+        let g = atlas.glyph('\u{1F600}', false, false).unwrap();
+        assert!(g.is_color);
+        assert_eq!(g.height as u32, atlas.cell_h);
+        assert_eq!(g.bearing_y, atlas.ascender);
+        // Some texel isn't the white used for coverage glyphs.
+        let size = atlas.store.atlas_size as f32;
+        let (x0, y0) = ((g.uv_x * size) as usize, (g.uv_y * size) as usize);
+        let row = atlas.store.atlas_size as usize * 4;
+        let pixels = &atlas.store.pages[g.page as usize].pixels;
+        assert!((0..g.height as usize).any(|y| {
+            (0..g.width as usize).any(|x| {
+                let i = (y0 + y) * row + (x0 + x) * 4;
+                pixels[i + 3] > 0 && pixels[i..i + 3] != [0xFF; 3]
+            })
+        }));
+    }
+
+    #[test]
+    #[ignore = "needs DejaVu Sans and DejaVu Sans Mono installed"]
+    fn missing_glyph_comes_from_fallback() {
+        // DejaVu Sans has U+01C4 (Ǆ), DejaVu Sans Mono doesn't.
+        let mono = crate::util::find_font("dejavusansmono.ttf").expect("no DejaVu Sans Mono");
+        let sans = crate::util::find_font("dejavusans.ttf").expect("no DejaVu Sans");
+        let ch = '\u{01C4}';
+        let data = std::fs::read(mono).unwrap();
+        let atlas = GlyphAtlas::new(&data, None, None, &AtlasOptions::default()).unwrap();
+        assert_eq!(atlas.pick_face(ch, false, false).1, GlyphId(0));
+
+        let mut atlas = atlas.with_fallback_fonts(&[Path::new("/nonexistent.ttf"), sans.as_path()]);
+        assert_eq!(atlas.fallback_fonts.len(), 1);
+        let (face, id) = atlas.pick_face(ch, false, false);
+        assert_ne!(id, GlyphId(0));
+        assert!(std::ptr::eq(face, &atlas.fallback_fonts[0]));
+        // Bold falls through regular to the fallback as well.
+        assert_ne!(atlas.pick_face(ch, true, false).1, GlyphId(0));
+        let g = atlas.glyph(ch, false, false).unwrap();
+        assert!(g.width > 0 && g.height > 0);
+    }
+
+    /// Alpha summed over the texels `g` points at.
+    fn coverage(store: &PageStore, g: &GlyphInfo) -> u32 {
+        let size = store.atlas_size as f32;
+        let x = ((g.uv_x - 0.5 / size) * size).round() as usize;
+        let y = ((g.uv_y - 0.5 / size) * size).round() as usize;
+        let row = store.atlas_size as usize * 4;
+        let pixels = &store.pages[g.page as usize].pixels;
+        (0..g.height as usize)
+            .flat_map(|r| (0..g.width as usize).map(move |c| (y + r) * row + (x + c) * 4 + 3))
+            .map(|i| pixels[i] as u32)
+            .sum()
+    }
+
+    #[test]
+    #[ignore = "needs an installed monospace font"]
+    fn glyphs_past_the_first_page_render_from_theirs() {
+        let path = crate::util::find_font("mono").expect("no monospace font installed");
+        let data = std::fs::read(path).unwrap();
+        let reference = |size, pages| {
+            GlyphAtlas::new(
+                &data,
+                None,
+                None,
+                &AtlasOptions {
+                    atlas_size: size,
+                    max_pages: pages,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+        let mut single = reference(ATLAS_SIZE, 1);
+        let mut paged = reference(512, ATLAS_MAX_PAGES);
+
+        // Prewarming fills most of a 512² page; accented Latin overflows it.
+        let mut later_pages = 0;
+        for ch in (' '..='~').chain('\u{A1}'..='\u{24F}') {
+            let (Some(want), Some(got)) = (
+                single.glyph(ch, false, false),
+                paged.glyph(ch, false, false),
+            ) else {
+                continue;
+            };
+            assert_eq!((got.width, got.height), (want.width, want.height));
+            assert_eq!(
+                coverage(&paged.store, &got),
+                coverage(&single.store, &want),
+                "{ch:?}"
+            );
+            later_pages += (got.page > 0) as usize;
         }
+        assert!(paged.store.pages.len() > 1);
+        assert_eq!(single.store.pages.len(), 1);
+        assert!(later_pages > 0, "nothing landed past page 0");
+    }
 
-        let aw = ATLAS_SIZE as usize;
-        for py in 0..hu {
-            for px in 0..w {
-                let src_idx = (py * w + px) as usize;
-                // bitmap_buf may be alpha-only (font path) OR RGBA (box_drawing path).
-                // We always produce RGBA in the atlas.
-                let alpha = if bitmap_buf.len() == (w * h) as usize {
-                    // alpha-only (font outline path)
-                    bitmap_buf[src_idx]
-                } else {
-                    // RGBA (box_drawing path) — channel 3 is alpha
-                    bitmap_buf[src_idx * 4 + 3]
-                };
-                let base = ((self.cursor_y + py) as usize * aw + (self.cursor_x + px) as usize) * 4;
-                self.pixels[base] = 0xFF;
-                self.pixels[base + 1] = 0xFF;
-                self.pixels[base + 2] = 0xFF;
-                self.pixels[base + 3] = alpha;
-            }
+    #[test]
+    #[ignore = "needs an installed monospace font"]
+    fn tiny_atlas_evicts_and_rerasterises() {
+        let path = crate::util::find_font("mono").expect("no monospace font installed");
+        let data = std::fs::read(path).unwrap();
+        // Prewarming alone is far more than 64×64 holds.
+        let mut atlas = GlyphAtlas::new(
+            &data,
+            None,
+            None,
+            &AtlasOptions {
+                atlas_size: 64,
+                max_pages: 1,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let a_key = CacheKey::Char(GlyphKey {
+            ch: 'A',
+            bold: false,
+            italic: false,
+        });
+        assert!(
+            !atlas.store.glyphs.contains_key(&a_key),
+            "'A' should have been evicted"
+        );
+        let a = atlas
+            .glyph('A', false, false)
+            .expect("evicted glyph comes back");
+        assert!(a.width > 0 && coverage(&atlas.store, &a) > 0);
+    }
+
+    fn key(n: u8) -> CacheKey {
+        CacheKey::Char(GlyphKey {
+            ch: n as char,
+            bold: false,
+            italic: false,
+        })
+    }
+
+    /// Pack a `w`×`h` bitmap of alpha `n` and cache it as char `n`, as a
+    /// lookup would.
+    fn put(store: &mut PageStore, n: u8, w: u32, h: u32) -> Option<GlyphInfo> {
+        let info = store.pack(&vec![n; (w * h) as usize], w, h, h, BitmapKind::Glyph);
+        store.insert(key(n), info);
+        info
+    }
+
+    /// Look `n` up, as drawing it would.
+    fn placed(store: &mut PageStore, n: u8) -> Option<GlyphInfo> {
+        store.get(key(n)).flatten()
+    }
+
+    #[test]
+    fn dirty_region_tracks_rows() {
+        let mut store = PageStore::new(64, 1);
+        put(&mut store, 1, 8, 16).unwrap();
+        let first_y = store.pages[0].cursor_y;
+        // Force the next glyph onto a fresh row.
+        store.pages[0].cursor_x = store.atlas_size;
+        put(&mut store, 2, 8, 16).unwrap();
+        let second_y = store.pages[0].cursor_y;
+        assert!(second_y > first_y);
+        assert_eq!(store.pages[0].dirty_region, Some((first_y, second_y + 16)));
+
+        // Uploaded; the next glyph on the same row dirties only that row.
+        store.pages[0].dirty_region = None;
+        put(&mut store, 3, 8, 16).unwrap();
+        assert_eq!(store.pages[0].cursor_y, second_y);
+        assert_eq!(store.pages[0].dirty_region, Some((second_y, second_y + 16)));
+    }
+
+    #[test]
+    fn eviction_drops_the_least_recently_used() {
+        let mut store = PageStore::new(64, 1);
+        for n in 1..=4 {
+            put(&mut store, n, 10, 10).unwrap();
         }
+        // 1 is the oldest entry, but was just drawn again.
+        placed(&mut store, 1).unwrap();
+        let compactions = store.compactions;
+        store.evict_lru(3);
+        assert_eq!(store.compactions, compactions + 1);
+        assert_eq!(store.stats().total_glyphs, 1);
+        assert!(store.get(key(2)).is_none());
 
-        // UV coordinates: inset by 0.5 texel so NEAREST sampling always fetches
-        // the correct atlas pixel even when floating-point UV values land on a
-        // texel boundary. Without this inset, borderline UVs can round the wrong
-        // direction and fetch a neighbouring (possibly empty) texel.
-        let half = 0.5 / ATLAS_SIZE as f32;
-        let info = GlyphInfo {
-            uv_x: self.cursor_x as f32 / ATLAS_SIZE as f32 + half,
-            uv_y: self.cursor_y as f32 / ATLAS_SIZE as f32 + half,
-            uv_w: w as f32 / ATLAS_SIZE as f32 - 2.0 * half,
-            uv_h: hu as f32 / ATLAS_SIZE as f32 - 2.0 * half,
-            width: w as i32,
-            height: hu as i32,
-            bearing_x,
-            bearing_y,
-            advance,
-        };
+        // The survivor moved to the top left and kept its texels.
+        let moved = placed(&mut store, 1).unwrap();
+        assert_eq!((moved.uv_x, moved.uv_y), (0.5 / 64.0, 0.5 / 64.0));
+        assert_eq!(coverage(&store, &moved), 100);
+        assert_eq!(store.pages[0].dirty_region, Some((0, 64)));
+    }
 
-        self.cursor_x += w + GAP;
-        if hu > self.row_h {
-            self.row_h = hu;
+    #[test]
+    fn full_page_opens_the_next_then_evicts_at_the_cap() {
+        // 10×10 glyphs and their gaps: two shelves of two on a 32² page.
+        let mut store = PageStore::new(32, 2);
+        for n in 1..=4 {
+            assert_eq!(put(&mut store, n, 10, 10).unwrap().page, 0);
         }
-        self.dirty = true;
-        Some(info)
+        let first = placed(&mut store, 1).unwrap();
+        assert_eq!(store.pages.len(), 1);
+
+        let g = put(&mut store, 5, 10, 10).expect("packed on a new page");
+        assert_eq!(store.pages.len(), 2);
+        assert_eq!((g.page, g.uv_x, g.uv_y), (1, 0.5 / 32.0, 0.5 / 32.0));
+        // Opening a page moves nothing.
+        let kept = placed(&mut store, 1).unwrap();
+        assert_eq!(
+            (kept.page, kept.uv_x, kept.uv_y),
+            (0, first.uv_x, first.uv_y)
+        );
+        assert_eq!(store.compactions, 0);
+
+        for n in 6..=8 {
+            assert_eq!(put(&mut store, n, 10, 10).unwrap().page, 1);
+        }
+        placed(&mut store, 1).unwrap();
+
+        // No third page: a compaction that frees nothing, then the older
+        // half goes (compacting again) and the glyph fits.
+        let g = put(&mut store, 9, 10, 10).expect("packed after eviction");
+        assert_eq!(store.pages.len(), 2);
+        assert_eq!(store.compactions, 2);
+        assert_eq!(g.page, 1);
+        for n in 2..=5 {
+            assert!(store.get(key(n)).is_none(), "{n} should have been evicted");
+        }
+        for n in [1, 6, 7, 8, 9] {
+            let g = placed(&mut store, n).unwrap();
+            assert_eq!(coverage(&store, &g), n as u32 * 100, "{n}");
+        }
+    }
+
+    #[test]
+    fn oversized_bitmap_is_dropped_without_evicting() {
+        let mut store = PageStore::new(32, 1);
+        put(&mut store, 1, 8, 8).unwrap();
+        assert!(put(&mut store, 2, 40, 8).is_none());
+        assert!(placed(&mut store, 1).is_some());
+        assert_eq!(store.compactions, 0);
+    }
+
+    #[test]
+    fn stats_count_packed_shelves() {
+        let mut store = PageStore::new(64, 1);
+        assert_eq!(store.stats().bytes_used, 0);
+        put(&mut store, 1, 10, 10).unwrap();
+        put(&mut store, 2, 10, 6).unwrap();
+        let stats = store.stats();
+        assert_eq!(stats.total_glyphs, 2);
+        // One open shelf, 10 rows tall, two glyphs and their gaps wide.
+        assert_eq!(stats.bytes_used, 24 * 10 * 4);
+        assert_eq!(stats.bytes_total, 64 * 64 * 4);
     }
 }
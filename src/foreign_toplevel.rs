@@ -0,0 +1,343 @@
+// foreign_toplevel.rs — wlr-foreign-toplevel-management-v1 for taskbars
+//
+// Smithay only implements the read-only ext-foreign-toplevel-list, while
+// Waybar's taskbar and friends speak the wlr protocol, so this module follows
+// the shape of gamma_control.rs: a `ForeignToplevelManagerState` owning the
+// global, a `ForeignToplevelHandler` trait for activate / close, and a
+// `delegate_foreign_toplevel!` macro wiring up the dispatch.
+//
+// Every bound manager gets one handle per toplevel. A handle is announced
+// with `toplevel`, then `title`, `app_id` and `done`; later changes resend
+// only what changed followed by `done`, and `closed` ends it. Embedded panes
+// are not listed — they belong to the compositor, not to a taskbar.
+
+use smithay::reexports::{
+    wayland_protocols_wlr::foreign_toplevel::v1::server::{
+        zwlr_foreign_toplevel_handle_v1::{self, ZwlrForeignToplevelHandleV1},
+        zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1},
+    },
+    wayland_server::{
+        backend::ClientId, protocol::wl_surface::WlSurface, Client, DataInit, Dispatch,
+        DisplayHandle, GlobalDispatch, New, Resource, Weak,
+    },
+};
+
+// ── per-toplevel bookkeeping ──────────────────────────────────────────────────
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HandleEvent {
+    Title(String),
+    AppId(String),
+    Done,
+    Closed,
+}
+
+/// What the handles of one toplevel were last told.
+#[derive(Debug, Default, Clone)]
+struct ToplevelInfo {
+    title: String,
+    app_id: String,
+}
+
+impl ToplevelInfo {
+    /// Everything a freshly created handle needs to hear.
+    fn announce(&self) -> Vec<HandleEvent> {
+        vec![
+            HandleEvent::Title(self.title.clone()),
+            HandleEvent::AppId(self.app_id.clone()),
+            HandleEvent::Done,
+        ]
+    }
+
+    /// Record `title` and `app_id`; the events for whatever changed, or
+    /// nothing when both are as before.
+    fn update(&mut self, title: &str, app_id: &str) -> Vec<HandleEvent> {
+        let mut events = Vec::new();
+        if self.title != title {
+            self.title = title.to_owned();
+            events.push(HandleEvent::Title(self.title.clone()));
+        }
+        if self.app_id != app_id {
+            self.app_id = app_id.to_owned();
+            events.push(HandleEvent::AppId(self.app_id.clone()));
+        }
+        if !events.is_empty() {
+            events.push(HandleEvent::Done);
+        }
+        events
+    }
+}
+
+fn send(handle: &ZwlrForeignToplevelHandleV1, events: &[HandleEvent]) {
+    for event in events {
+        match event {
+            HandleEvent::Title(t) => handle.title(t.clone()),
+            HandleEvent::AppId(a) => handle.app_id(a.clone()),
+            HandleEvent::Done => handle.done(),
+            HandleEvent::Closed => handle.closed(),
+        }
+    }
+}
+
+struct ToplevelEntry {
+    surface: WlSurface,
+    info: ToplevelInfo,
+    handles: Vec<ZwlrForeignToplevelHandleV1>,
+}
+
+// ── handler ───────────────────────────────────────────────────────────────────
+
+pub trait ForeignToplevelHandler {
+    fn foreign_toplevel_state(&mut self) -> &mut ForeignToplevelManagerState;
+    /// A taskbar asked for the toplevel of `surface` to be focused.
+    fn activate(&mut self, surface: &WlSurface);
+    /// A taskbar asked for the toplevel of `surface` to be closed.
+    fn close(&mut self, surface: &WlSurface);
+}
+
+pub struct ForeignToplevelHandleData {
+    surface: Weak<WlSurface>,
+}
+
+pub struct ForeignToplevelManagerState {
+    dh: DisplayHandle,
+    managers: Vec<ZwlrForeignToplevelManagerV1>,
+    toplevels: Vec<ToplevelEntry>,
+}
+
+impl ForeignToplevelManagerState {
+    pub fn new<D>(dh: &DisplayHandle) -> Self
+    where
+        D: GlobalDispatch<ZwlrForeignToplevelManagerV1, ()>
+            + Dispatch<ZwlrForeignToplevelManagerV1, ()>
+            + Dispatch<ZwlrForeignToplevelHandleV1, ForeignToplevelHandleData>
+            + ForeignToplevelHandler
+            + 'static,
+    {
+        dh.create_global::<D, ZwlrForeignToplevelManagerV1, _>(3, ());
+        Self {
+            dh: dh.clone(),
+            managers: Vec::new(),
+            toplevels: Vec::new(),
+        }
+    }
+
+    /// Announce a new toplevel to every bound manager.
+    pub fn new_toplevel<D>(&mut self, surface: &WlSurface, title: &str, app_id: &str)
+    where
+        D: Dispatch<ZwlrForeignToplevelHandleV1, ForeignToplevelHandleData> + 'static,
+    {
+        if self.toplevels.iter().any(|t| &t.surface == surface) {
+            return;
+        }
+        let mut entry = ToplevelEntry {
+            surface: surface.clone(),
+            info: ToplevelInfo::default(),
+            handles: Vec::new(),
+        };
+        entry.info.update(title, app_id);
+        for manager in &self.managers {
+            create_handle::<D>(&self.dh, manager, &mut entry);
+        }
+        self.toplevels.push(entry);
+    }
+
+    /// Resend the title / app_id of `surface` if either changed. Surfaces that
+    /// aren't announced toplevels are ignored.
+    pub fn update(&mut self, surface: &WlSurface, title: &str, app_id: &str) {
+        let Some(entry) = self.toplevels.iter_mut().find(|t| &t.surface == surface) else {
+            return;
+        };
+        let events = entry.info.update(title, app_id);
+        if events.is_empty() {
+            return;
+        }
+        for handle in &entry.handles {
+            send(handle, &events);
+        }
+    }
+
+    /// Send `closed` for `surface` and forget it.
+    pub fn toplevel_closed(&mut self, surface: &WlSurface) {
+        let Some(i) = self.toplevels.iter().position(|t| &t.surface == surface) else {
+            return;
+        };
+        let entry = self.toplevels.remove(i);
+        for handle in &entry.handles {
+            send(handle, &[HandleEvent::Closed]);
+        }
+    }
+}
+
+fn create_handle<D>(
+    dh: &DisplayHandle,
+    manager: &ZwlrForeignToplevelManagerV1,
+    entry: &mut ToplevelEntry,
+) where
+    D: Dispatch<ZwlrForeignToplevelHandleV1, ForeignToplevelHandleData> + 'static,
+{
+    let Ok(client) = dh.get_client(manager.id()) else {
+        return;
+    };
+    let data = ForeignToplevelHandleData {
+        surface: entry.surface.downgrade(),
+    };
+    let Ok(handle) =
+        client.create_resource::<ZwlrForeignToplevelHandleV1, _, D>(dh, manager.version(), data)
+    else {
+        return;
+    };
+    manager.toplevel(&handle);
+    send(&handle, &entry.info.announce());
+    entry.handles.push(handle);
+}
+
+impl<D> GlobalDispatch<ZwlrForeignToplevelManagerV1, (), D> for ForeignToplevelManagerState
+where
+    D: GlobalDispatch<ZwlrForeignToplevelManagerV1, ()>
+        + Dispatch<ZwlrForeignToplevelManagerV1, ()>
+        + Dispatch<ZwlrForeignToplevelHandleV1, ForeignToplevelHandleData>
+        + ForeignToplevelHandler
+        + 'static,
+{
+    fn bind(
+        state: &mut D,
+        dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwlrForeignToplevelManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let manager = data_init.init(resource, ());
+        let ft = state.foreign_toplevel_state();
+        for entry in &mut ft.toplevels {
+            create_handle::<D>(dh, &manager, entry);
+        }
+        ft.managers.push(manager);
+    }
+}
+
+impl<D> Dispatch<ZwlrForeignToplevelManagerV1, (), D> for ForeignToplevelManagerState
+where
+    D: Dispatch<ZwlrForeignToplevelManagerV1, ()> + ForeignToplevelHandler + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        resource: &ZwlrForeignToplevelManagerV1,
+        request: zwlr_foreign_toplevel_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        if let zwlr_foreign_toplevel_manager_v1::Request::Stop = request {
+            state
+                .foreign_toplevel_state()
+                .managers
+                .retain(|m| m != resource);
+            resource.finished();
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        resource: &ZwlrForeignToplevelManagerV1,
+        _data: &(),
+    ) {
+        state
+            .foreign_toplevel_state()
+            .managers
+            .retain(|m| m != resource);
+    }
+}
+
+impl<D> Dispatch<ZwlrForeignToplevelHandleV1, ForeignToplevelHandleData, D>
+    for ForeignToplevelManagerState
+where
+    D: Dispatch<ZwlrForeignToplevelHandleV1, ForeignToplevelHandleData>
+        + ForeignToplevelHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _resource: &ZwlrForeignToplevelHandleV1,
+        request: zwlr_foreign_toplevel_handle_v1::Request,
+        data: &ForeignToplevelHandleData,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        let Ok(surface) = data.surface.upgrade() else {
+            return;
+        };
+        match request {
+            zwlr_foreign_toplevel_handle_v1::Request::Activate { .. } => state.activate(&surface),
+            zwlr_foreign_toplevel_handle_v1::Request::Close => state.close(&surface),
+            // Maximize / minimize / fullscreen / rectangle hints are not
+            // meaningful under the TWM layout.
+            _ => {}
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        resource: &ZwlrForeignToplevelHandleV1,
+        _data: &ForeignToplevelHandleData,
+    ) {
+        for entry in &mut state.foreign_toplevel_state().toplevels {
+            entry.handles.retain(|h| h != resource);
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! delegate_foreign_toplevel {
+    ($ty:ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::foreign_toplevel::v1::server::zwlr_foreign_toplevel_manager_v1::ZwlrForeignToplevelManagerV1: ()
+        ] => $crate::foreign_toplevel::ForeignToplevelManagerState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::foreign_toplevel::v1::server::zwlr_foreign_toplevel_manager_v1::ZwlrForeignToplevelManagerV1: ()
+        ] => $crate::foreign_toplevel::ForeignToplevelManagerState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::foreign_toplevel::v1::server::zwlr_foreign_toplevel_handle_v1::ZwlrForeignToplevelHandleV1: $crate::foreign_toplevel::ForeignToplevelHandleData
+        ] => $crate::foreign_toplevel::ForeignToplevelManagerState);
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use HandleEvent::*;
+
+    #[test]
+    fn two_toplevels_emit_their_own_events() {
+        let mut foot = ToplevelInfo::default();
+        let mut firefox = ToplevelInfo::default();
+        foot.update("~", "foot");
+        firefox.update("", "firefox");
+
+        assert_eq!(
+            foot.announce(),
+            vec![Title("~".into()), AppId("foot".into()), Done]
+        );
+        assert_eq!(
+            firefox.announce(),
+            vec![Title("".into()), AppId("firefox".into()), Done]
+        );
+
+        // A title change resends only the title; an unchanged commit is silent.
+        assert_eq!(
+            firefox.update("Mozilla Firefox", "firefox"),
+            vec![Title("Mozilla Firefox".into()), Done]
+        );
+        assert_eq!(firefox.update("Mozilla Firefox", "firefox"), vec![]);
+        assert_eq!(foot.update("~", "foot"), vec![]);
+        assert_eq!(
+            foot.update("vim", "footclient"),
+            vec![Title("vim".into()), AppId("footclient".into()), Done]
+        );
+    }
+}
@@ -0,0 +1,360 @@
+// gamma_control.rs — wlr-gamma-control-unstable-v1 for gammastep / redshift
+//
+// Smithay ships no implementation of this protocol, so this module follows
+// the shape of its built-in ones: a `GammaControlManagerState` owning the
+// global, a `GammaControlHandler` trait the compositor implements, and a
+// `delegate_gamma_control!` macro wiring up the dispatch.
+//
+// Wire format: `set_gamma` passes an fd holding `3 * gamma_size` native-endian
+// u16 values — the red ramp, then green, then blue. Any number of controls
+// may exist per output; the most recently written ramp is the one on the
+// CRTC. When its control goes away the output falls back to the ramp of the
+// next most recent writer, or to linear once there is none.
+//
+// The ramp is applied by the CRTC after scanout, so it composes with the
+// GLSL post-process passes (vibrance and the like) instead of fighting them:
+// those shape the framebuffer, the ramp then maps its values to the panel.
+
+use std::os::{fd::OwnedFd, unix::fs::FileExt};
+
+use smithay::{
+    output::Output,
+    reexports::{
+        wayland_protocols_wlr::gamma_control::v1::server::{
+            zwlr_gamma_control_manager_v1::{self, ZwlrGammaControlManagerV1},
+            zwlr_gamma_control_v1::{self, ZwlrGammaControlV1},
+        },
+        wayland_server::{
+            backend::ClientId, Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New,
+        },
+    },
+};
+
+// ── ramps ─────────────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GammaRamp {
+    pub red: Vec<u16>,
+    pub green: Vec<u16>,
+    pub blue: Vec<u16>,
+}
+
+impl GammaRamp {
+    /// Identity ramp — `(i << 8) | i` for a 256-entry table.
+    pub fn linear(size: usize) -> Self {
+        let max = size.saturating_sub(1).max(1) as u32;
+        let ramp: Vec<u16> = (0..size as u32)
+            .map(|i| (i * 0xFFFF / max) as u16)
+            .collect();
+        Self {
+            red: ramp.clone(),
+            green: ramp.clone(),
+            blue: ramp,
+        }
+    }
+
+    /// The `set_gamma` payload for this ramp.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [&self.red, &self.green, &self.blue]
+            .into_iter()
+            .flatten()
+            .flat_map(|v| v.to_ne_bytes())
+            .collect()
+    }
+
+    /// Split the raw `set_gamma` payload into its three channels.
+    /// None unless `bytes` holds exactly `3 * size` u16 values.
+    pub fn from_bytes(bytes: &[u8], size: usize) -> Option<Self> {
+        if size == 0 || bytes.len() != size * 3 * 2 {
+            return None;
+        }
+        let values: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|b| u16::from_ne_bytes([b[0], b[1]]))
+            .collect();
+        Some(Self {
+            red: values[..size].to_vec(),
+            green: values[size..size * 2].to_vec(),
+            blue: values[size * 2..].to_vec(),
+        })
+    }
+}
+
+/// Read the ramp from the start of `fd`, as wlroots does: the table is
+/// meant to be mmap-able, so it has to be complete by the time `set_gamma`
+/// arrives. The fd is made non-blocking first, so a pipe or socket the
+/// client never writes to fails here instead of stalling the event loop.
+fn read_ramp(fd: OwnedFd, size: usize) -> Option<GammaRamp> {
+    let flags = rustix::fs::fcntl_getfl(&fd).ok()?;
+    rustix::fs::fcntl_setfl(&fd, flags | rustix::fs::OFlags::NONBLOCK).ok()?;
+    let mut bytes = vec![0u8; size * 3 * 2];
+    std::fs::File::from(fd).read_exact_at(&mut bytes, 0).ok()?;
+    GammaRamp::from_bytes(&bytes, size)
+}
+
+// ── handler ───────────────────────────────────────────────────────────────────
+
+pub trait GammaControlHandler {
+    fn gamma_control_state(&mut self) -> &mut GammaControlManagerState;
+    /// Ramp length of the CRTC driving `output`, or None if it has none.
+    fn gamma_size(&mut self, output: &Output) -> Option<u32>;
+    /// Apply `ramp` to `output`; None restores the linear ramp.
+    /// Returns false if the hardware rejected it.
+    fn set_gamma(&mut self, output: &Output, ramp: Option<GammaRamp>) -> bool;
+}
+
+pub struct GammaControlData {
+    output: Option<Output>,
+    size: u32,
+}
+
+struct ControlEntry {
+    output: Output,
+    control: ZwlrGammaControlV1,
+    /// None until the client's first `set_gamma`.
+    ramp: Option<GammaRamp>,
+}
+
+pub struct GammaControlManagerState {
+    /// Oldest write first.
+    controls: Vec<ControlEntry>,
+}
+
+impl GammaControlManagerState {
+    pub fn new<D>(dh: &DisplayHandle) -> Self
+    where
+        D: GlobalDispatch<ZwlrGammaControlManagerV1, ()>
+            + Dispatch<ZwlrGammaControlManagerV1, ()>
+            + Dispatch<ZwlrGammaControlV1, GammaControlData>
+            + GammaControlHandler
+            + 'static,
+    {
+        dh.create_global::<D, ZwlrGammaControlManagerV1, _>(1, ());
+        Self {
+            controls: Vec::new(),
+        }
+    }
+
+    /// The ramp `output` should show: the latest one written by a live
+    /// control, or None for linear.
+    fn current(&self, output: &Output) -> Option<GammaRamp> {
+        self.controls
+            .iter()
+            .rev()
+            .filter(|e| &e.output == output)
+            .find_map(|e| e.ramp.clone())
+    }
+
+    /// Forget `control`. Returns its output if its ramp was the one shown.
+    fn remove(&mut self, control: &ZwlrGammaControlV1) -> Option<Output> {
+        let i = self.controls.iter().position(|e| &e.control == control)?;
+        let entry = self.controls.remove(i);
+        let shown = entry.ramp.is_some()
+            && !self.controls[i..]
+                .iter()
+                .any(|e| e.output == entry.output && e.ramp.is_some());
+        shown.then_some(entry.output)
+    }
+}
+
+/// Put `output` back on the ramp of its latest remaining writer.
+fn restore<D: GammaControlHandler>(state: &mut D, output: &Output) {
+    let ramp = state.gamma_control_state().current(output);
+    state.set_gamma(output, ramp);
+}
+
+impl<D> GlobalDispatch<ZwlrGammaControlManagerV1, (), D> for GammaControlManagerState
+where
+    D: GlobalDispatch<ZwlrGammaControlManagerV1, ()>
+        + Dispatch<ZwlrGammaControlManagerV1, ()>
+        + Dispatch<ZwlrGammaControlV1, GammaControlData>
+        + GammaControlHandler
+        + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwlrGammaControlManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<D> Dispatch<ZwlrGammaControlManagerV1, (), D> for GammaControlManagerState
+where
+    D: Dispatch<ZwlrGammaControlManagerV1, ()>
+        + Dispatch<ZwlrGammaControlV1, GammaControlData>
+        + GammaControlHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _resource: &ZwlrGammaControlManagerV1,
+        request: zwlr_gamma_control_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_gamma_control_manager_v1::Request::GetGammaControl { id, output } => {
+                let output = Output::from_resource(&output);
+                let size = output.as_ref().and_then(|o| state.gamma_size(o));
+                let control = data_init.init(
+                    id,
+                    GammaControlData {
+                        output: output.clone(),
+                        size: size.unwrap_or(0),
+                    },
+                );
+                match (output, size) {
+                    (Some(output), Some(size)) if size > 0 => {
+                        control.gamma_size(size);
+                        state.gamma_control_state().controls.push(ControlEntry {
+                            output,
+                            control,
+                            ramp: None,
+                        });
+                    }
+                    _ => control.failed(),
+                }
+            }
+            zwlr_gamma_control_manager_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+}
+
+impl<D> Dispatch<ZwlrGammaControlV1, GammaControlData, D> for GammaControlManagerState
+where
+    D: Dispatch<ZwlrGammaControlV1, GammaControlData> + GammaControlHandler + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        resource: &ZwlrGammaControlV1,
+        request: zwlr_gamma_control_v1::Request,
+        data: &GammaControlData,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_gamma_control_v1::Request::SetGamma { fd } => {
+                let Some(output) = &data.output else { return };
+                let controls = &mut state.gamma_control_state().controls;
+                let Some(i) = controls.iter().position(|e| &e.control == resource) else {
+                    return;
+                };
+                // The latest write wins, so this control moves to the end.
+                let mut entry = controls.remove(i);
+                let applied = match read_ramp(fd, data.size as usize) {
+                    Some(ramp) => {
+                        entry.ramp = Some(ramp.clone());
+                        state.gamma_control_state().controls.push(entry);
+                        state.set_gamma(output, Some(ramp))
+                    }
+                    None => {
+                        tracing::warn!("gamma control: malformed ramp from client");
+                        false
+                    }
+                };
+                if !applied {
+                    resource.failed();
+                    state.gamma_control_state().remove(resource);
+                    restore(state, output);
+                }
+            }
+            zwlr_gamma_control_v1::Request::Destroy => {}
+            _ => {}
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        resource: &ZwlrGammaControlV1,
+        data: &GammaControlData,
+    ) {
+        if state.gamma_control_state().remove(resource).is_some() {
+            if let Some(output) = &data.output {
+                restore(state, output);
+            }
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! delegate_gamma_control {
+    ($ty:ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::gamma_control::v1::server::zwlr_gamma_control_manager_v1::ZwlrGammaControlManagerV1: ()
+        ] => $crate::gamma_control::GammaControlManagerState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::gamma_control::v1::server::zwlr_gamma_control_manager_v1::ZwlrGammaControlManagerV1: ()
+        ] => $crate::gamma_control::GammaControlManagerState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::gamma_control::v1::server::zwlr_gamma_control_v1::ZwlrGammaControlV1: $crate::gamma_control::GammaControlData
+        ] => $crate::gamma_control::GammaControlManagerState);
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linear_ramp_is_identity() {
+        let ramp = GammaRamp::linear(256);
+        for i in 0..256u16 {
+            assert_eq!(ramp.red[i as usize], (i << 8) | i);
+        }
+        assert_eq!(ramp.red, ramp.blue);
+    }
+
+    #[test]
+    fn ramp_round_trips_through_bytes() {
+        let ramp = GammaRamp {
+            red: vec![0, 0x1234, 0xFFFF],
+            green: vec![0, 0x8000, 0xFFFF],
+            blue: vec![0, 0x00FF, 0x7FFF],
+        };
+        let bytes = ramp.to_bytes();
+        assert_eq!(&bytes[2..4], &0x1234u16.to_ne_bytes());
+        assert_eq!(GammaRamp::from_bytes(&bytes, 3), Some(ramp));
+        assert_eq!(GammaRamp::from_bytes(&bytes, 2), None);
+    }
+
+    #[test]
+    fn warm_ramp_splits_channels() {
+        let size = 256;
+        let warm = GammaRamp {
+            red: GammaRamp::linear(size).red,
+            green: (0..size as u32).map(|i| (i * 220) as u16).collect(),
+            blue: (0..size as u32).map(|i| (i * 160) as u16).collect(),
+        };
+        let bytes = warm.to_bytes();
+        assert_eq!(bytes.len(), size * 3 * 2);
+
+        assert_eq!(GammaRamp::from_bytes(&bytes, size), Some(warm));
+        assert_eq!(GammaRamp::from_bytes(&bytes[2..], size), None);
+    }
+
+    #[test]
+    fn ramp_reads_never_block() {
+        use std::io::Write;
+
+        // Written with write(2), so the offset is at the end: read from 0.
+        let ramp = GammaRamp::linear(4);
+        let memfd = rustix::fs::memfd_create("gamma", rustix::fs::MemfdFlags::CLOEXEC).unwrap();
+        let mut file = std::fs::File::from(memfd);
+        file.write_all(&ramp.to_bytes()).unwrap();
+        assert_eq!(read_ramp(file.into(), 4), Some(ramp));
+
+        // A socket nobody writes to fails at once instead of hanging.
+        let (_client, server) = std::os::unix::net::UnixStream::pair().unwrap();
+        assert_eq!(read_ramp(server.into(), 4), None);
+    }
+}
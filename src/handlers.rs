@@ -1,21 +1,39 @@
 // handlers.rs — Smithay protocol delegate implementations
 
-use std::{process::Command, sync::atomic::Ordering};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    io::{Read, Write},
+    os::{fd::OwnedFd, unix::net::UnixStream},
+    process::Command,
+    sync::atomic::Ordering,
+};
 
 use smithay::{
-    delegate_compositor, delegate_data_device, delegate_dmabuf, delegate_layer_shell,
-    delegate_output, delegate_primary_selection, delegate_seat, delegate_shm,
-    delegate_xdg_decoration, delegate_xdg_shell,
-    desktop::{layer_map_for_output, LayerSurface, PopupKind, Window},
-    input::{Seat, SeatHandler, SeatState},
+    delegate_compositor, delegate_cursor_shape, delegate_data_control, delegate_data_device,
+    delegate_dmabuf, delegate_fractional_scale, delegate_idle_inhibit,
+    delegate_input_method_manager, delegate_layer_shell, delegate_output,
+    delegate_pointer_constraints, delegate_primary_selection, delegate_relative_pointer,
+    delegate_seat, delegate_session_lock, delegate_shm, delegate_text_input_manager,
+    delegate_viewporter, delegate_xdg_decoration, delegate_xdg_shell,
+    desktop::{layer_map_for_output, LayerSurface, PopupKind, PopupManager, Window},
+    input::{pointer::PointerHandle, Seat, SeatHandler, SeatState},
+    output::Output,
     reexports::{
+        calloop::{generic::Generic, Interest, Mode, PostAction},
+        drm::control::Device as DrmControlDevice,
         wayland_protocols::xdg::decoration::zv1::server::zxdg_toplevel_decoration_v1,
+        wayland_protocols_wlr::data_control::v1::server::zwlr_data_control_manager_v1::ZwlrDataControlManagerV1,
         wayland_server::{
-            protocol::{wl_buffer::WlBuffer, wl_output, wl_seat, wl_surface::WlSurface},
-            Client, Resource,
+            protocol::{
+                wl_buffer::WlBuffer,
+                wl_output, wl_seat,
+                wl_surface::{self, WlSurface},
+            },
+            Client, DisplayHandle, GlobalDispatch, Resource,
         },
     },
-    utils::SERIAL_COUNTER as SCOUNTER,
+    utils::{Logical, Rectangle, SERIAL_COUNTER as SCOUNTER},
     wayland::{
         buffer::BufferHandler,
         compositor::{
@@ -23,18 +41,29 @@ use smithay::{
             CompositorState,
         },
         dmabuf::{DmabufGlobal, DmabufHandler, DmabufState, ImportNotifier},
+        fractional_scale::{with_fractional_scale, FractionalScaleHandler},
+        idle_inhibit::IdleInhibitHandler,
+        input_method::{InputMethodHandler, PopupSurface as ImePopupSurface},
         output::OutputHandler,
+        pointer_constraints::{
+            with_pointer_constraint, PointerConstraint, PointerConstraintsHandler,
+        },
         seat::WaylandFocus,
         selection::{
             data_device::{
-                set_data_device_focus, ClientDndGrabHandler, DataDeviceHandler, DataDeviceState,
-                ServerDndGrabHandler,
+                request_data_device_client_selection, set_data_device_focus,
+                set_data_device_selection, ClientDndGrabHandler, DataDeviceHandler,
+                DataDeviceState, ServerDndGrabHandler,
             },
             primary_selection::{
                 set_primary_focus, PrimarySelectionHandler, PrimarySelectionState,
             },
-            SelectionHandler,
+            wlr_data_control::{
+                DataControlHandler, DataControlManagerGlobalData, DataControlState,
+            },
+            SelectionHandler, SelectionSource, SelectionTarget,
         },
+        session_lock::{LockSurface, SessionLockHandler, SessionLockManagerState, SessionLocker},
         shell::{
             wlr_layer::{Layer, WlrLayerShellHandler, WlrLayerShellState},
             xdg::{
@@ -43,16 +72,27 @@ use smithay::{
             },
         },
         shm::{ShmHandler, ShmState},
+        tablet_manager::TabletSeatHandler,
     },
 };
 
 use ratatui::layout::Margin;
+use smithay::backend::input::TabletToolDescriptor;
 use smithay::backend::renderer::{utils::on_commit_buffer_handler, ImportDma};
 use smithay::input::pointer::CursorImageStatus;
 
 use crate::{
+    clipboard::{self, ClipboardEntry, EntryBuilder},
+    config::OutputConfig,
+    delegate_foreign_toplevel, delegate_gamma_control, delegate_output_management,
+    delegate_screencopy,
+    foreign_toplevel::{ForeignToplevelHandler, ForeignToplevelManagerState},
+    gamma_control::{GammaControlHandler, GammaControlManagerState, GammaRamp},
+    output_management::{OutputManagementHandler, OutputManagementState},
     render::{ensure_initial_configure, try_apply_pending_rule},
-    state::{ClientState, KittyCompositor},
+    screencopy::{ScreencopyFrame, ScreencopyHandler},
+    state::{preferred_scale, Capture, ClientState, KittyCompositor},
+    twm_drop_in::{Action as TwmAction, PaneContent},
 };
 
 // ── dmabuf ────────────────────────────────────────────────────────────────────
@@ -142,6 +182,7 @@ impl CompositorHandler for KittyCompositor {
                         }
                         let statuses = self.embedded.window_statuses();
                         self.embed_ipc.update_windows(statuses);
+                        self.foreign_toplevel_state.toplevel_closed(surface);
                     }
                 } else {
                     self.unclaimed_toplevels.remove(&obj_id);
@@ -173,17 +214,131 @@ impl CompositorHandler for KittyCompositor {
             }
         }
 
+        let (title, app_id) = toplevel_title_app_id(surface);
+        self.foreign_toplevel_state.update(surface, &title, &app_id);
+
+        self.commit_constraint(surface);
+
         self.popups.commit(surface);
         ensure_initial_configure(surface, &self.space, &mut self.popups);
         try_apply_pending_rule(self, surface);
     }
+
+    fn destroyed(&mut self, surface: &WlSurface) {
+        self.idle_inhibitors.surface_destroyed(surface);
+    }
 }
 delegate_compositor!(KittyCompositor);
 
 // ── selection / data device ───────────────────────────────────────────────────
 
 impl SelectionHandler for KittyCompositor {
-    type SelectionUserData = ();
+    /// Set when the compositor itself owns the clipboard: the history entry
+    /// being offered.
+    type SelectionUserData = ClipboardEntry;
+
+    fn new_selection(
+        &mut self,
+        ty: SelectionTarget,
+        source: Option<SelectionSource>,
+        seat: Seat<Self>,
+    ) {
+        if ty != SelectionTarget::Clipboard {
+            return;
+        }
+        match source {
+            Some(source) => self.read_clipboard(&seat, &source.mime_types()),
+            // The owner cleared the clipboard or exited; keep the newest
+            // entry pasteable.
+            None => {
+                if let Some(entry) = self.clipboard.history().first().cloned() {
+                    self.offer_clipboard(entry);
+                }
+            }
+        }
+    }
+
+    fn send_selection(
+        &mut self,
+        _ty: SelectionTarget,
+        _mime_type: String,
+        fd: OwnedFd,
+        _seat: Seat<Self>,
+        user_data: &ClipboardEntry,
+    ) {
+        let Some(data) = user_data.data().map(<[u8]>::to_vec) else {
+            return;
+        };
+        // The reader may be slow to drain the pipe; don't block the loop.
+        std::thread::spawn(move || {
+            if let Err(e) = std::fs::File::from(fd).write_all(&data) {
+                tracing::debug!("clipboard: paste write failed: {e}");
+            }
+        });
+    }
+}
+
+impl KittyCompositor {
+    /// Read the new client selection into the clipboard history.
+    fn read_clipboard(&mut self, seat: &Seat<Self>, mime_types: &[String]) {
+        let Some(mime) = clipboard::pick_mime(mime_types) else {
+            return;
+        };
+        let (reader, writer) = match UnixStream::pair() {
+            Ok(pair) => pair,
+            Err(e) => {
+                tracing::warn!("clipboard: socketpair: {e}");
+                return;
+            }
+        };
+        if let Err(e) =
+            request_data_device_client_selection(seat, mime.to_owned(), OwnedFd::from(writer))
+        {
+            tracing::warn!("clipboard: could not read selection: {e:?}");
+            return;
+        }
+        let _ = reader.set_nonblocking(true);
+        let mut builder = Some(EntryBuilder::new(mime, self.config.clipboard_max_bytes));
+        let source = Generic::new(reader, Interest::READ, Mode::Level);
+        let inserted = self.handle.insert_source(source, move |_, reader, state| {
+            let mut chunk = [0u8; 8192];
+            loop {
+                // SAFETY: the stream is only read, never dropped or replaced.
+                match unsafe { reader.get_mut() }.read(&mut chunk) {
+                    Ok(0) => break,
+                    Ok(n) => {
+                        if let Some(b) = &mut builder {
+                            b.feed(&chunk[..n]);
+                        }
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                        return Ok(PostAction::Continue)
+                    }
+                    Err(e) => {
+                        tracing::debug!("clipboard: read failed: {e}");
+                        return Ok(PostAction::Remove);
+                    }
+                }
+            }
+            if let Some(entry) = builder.take().and_then(EntryBuilder::finish) {
+                state.clipboard.push(entry);
+            }
+            Ok(PostAction::Remove)
+        });
+        if let Err(e) = inserted {
+            tracing::warn!("clipboard: {e}");
+        }
+    }
+
+    /// Make the compositor the clipboard owner, serving `entry`. Hashed
+    /// entries have nothing to paste and are skipped.
+    pub fn offer_clipboard(&mut self, entry: ClipboardEntry) {
+        if entry.data().is_none() {
+            return;
+        }
+        let mime_types = vec![entry.mime_type.clone()];
+        set_data_device_selection(&self.display_handle, &self.seat, mime_types, entry);
+    }
 }
 impl ClientDndGrabHandler for KittyCompositor {}
 impl ServerDndGrabHandler for KittyCompositor {}
@@ -202,6 +357,281 @@ impl PrimarySelectionHandler for KittyCompositor {
 }
 delegate_primary_selection!(KittyCompositor);
 
+// Clipboard managers (wl-paste --watch, clipman, cliphist). Selection changes
+// made through wl_data_device / primary selection are forwarded to every
+// bound data-control device by smithay's selection plumbing, and sources set
+// by a data-control client become the seat selection.
+impl DataControlHandler for KittyCompositor {
+    fn data_control_state(&self) -> &DataControlState {
+        &self.data_control_state
+    }
+}
+delegate_data_control!(KittyCompositor);
+
+/// The wlr-data-control global, offered to every client on purpose, as sway
+/// does: a clipboard manager is an ordinary client started from the user's
+/// session, and nothing marks it apart from the clients it watches. So any
+/// client that binds it can read and replace the clipboard.
+pub fn data_control_state<D>(
+    dh: &DisplayHandle,
+    primary: &PrimarySelectionState,
+) -> DataControlState
+where
+    D: GlobalDispatch<ZwlrDataControlManagerV1, DataControlManagerGlobalData> + 'static,
+{
+    DataControlState::new::<D, _>(dh, Some(primary), |_| true)
+}
+
+// ── gamma control ─────────────────────────────────────────────────────────────
+
+impl GammaControlHandler for KittyCompositor {
+    fn gamma_control_state(&mut self) -> &mut GammaControlManagerState {
+        &mut self.gamma_control_state
+    }
+
+    fn gamma_size(&mut self, output: &Output) -> Option<u32> {
+        let (backend, crtc) = self.crtc_for_output(output)?;
+        let len = backend.drm.get_crtc(crtc).ok()?.gamma_length();
+        // Legacy gamma tables are 256 entries on virtually every driver.
+        Some(if len == 0 { 256 } else { len })
+    }
+
+    fn set_gamma(&mut self, output: &Output, ramp: Option<GammaRamp>) -> bool {
+        let Some(size) = self.gamma_size(output) else {
+            return false;
+        };
+        let Some((backend, crtc)) = self.crtc_for_output(output) else {
+            return false;
+        };
+        let ramp = ramp.unwrap_or_else(|| GammaRamp::linear(size as usize));
+        match backend
+            .drm
+            .set_gamma(crtc, &ramp.red, &ramp.green, &ramp.blue)
+        {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::warn!("set_gamma on {}: {e}", output.name());
+                false
+            }
+        }
+    }
+}
+delegate_gamma_control!(KittyCompositor);
+
+// ── screencopy ────────────────────────────────────────────────────────────────
+
+impl ScreencopyHandler for KittyCompositor {
+    fn copy_frame(&mut self, frame: ScreencopyFrame) {
+        // Filled in by render_surface on the output's next frame, which an
+        // idle-suspended compositor would never draw.
+        self.captures.push(Capture::Screencopy(frame));
+        self.resume_render();
+    }
+}
+delegate_screencopy!(KittyCompositor);
+
+// ── foreign toplevel management ───────────────────────────────────────────────
+
+/// `title` and `app_id` of an xdg toplevel surface; empty for anything else.
+fn toplevel_title_app_id(surface: &WlSurface) -> (String, String) {
+    with_states(surface, |states| {
+        states
+            .data_map
+            .get::<XdgToplevelSurfaceData>()
+            .and_then(|d| d.lock().ok())
+            .map(|d| {
+                (
+                    d.title.clone().unwrap_or_default(),
+                    d.app_id.clone().unwrap_or_default(),
+                )
+            })
+            .unwrap_or_default()
+    })
+}
+
+/// `surface` as a key the TWM, which holds no Wayland objects, can keep.
+fn surface_key(surface: &WlSurface) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    surface.id().hash(&mut hasher);
+    hasher.finish()
+}
+
+impl KittyCompositor {
+    fn window_for_surface(&self, surface: &WlSurface) -> Option<Window> {
+        self.space
+            .elements()
+            .find(|w| w.wl_surface().as_deref() == Some(surface))
+            .cloned()
+    }
+
+    /// Give the TWM a pane for each window that was around before it:
+    /// mapped shell windows and claimed embedded ones. Windows it already
+    /// took in are left alone.
+    pub fn import_windows_into_twm(&mut self) {
+        let shells = self.space.elements().filter_map(|w| {
+            let surface = w.wl_surface()?;
+            let (_, app_id) = toplevel_title_app_id(&surface);
+            let content = PaneContent::Shell {
+                title: app_id.clone(),
+            };
+            Some((surface_key(&surface), app_id, content))
+        });
+        let embedded = self.embedded.entries.iter().map(|(app_id, e)| {
+            let content = PaneContent::Embedded {
+                app_id: app_id.clone(),
+            };
+            (surface_key(&e.surface), app_id.clone(), content)
+        });
+        // As in new_toplevel, windows without an app_id get no pane.
+        let windows: Vec<(u64, String, PaneContent)> = shells
+            .chain(embedded)
+            .filter(|(_, app_id, _)| !app_id.is_empty())
+            .collect();
+        let windows: Vec<(u64, &str, PaneContent)> = windows
+            .iter()
+            .map(|(key, app_id, content)| (*key, app_id.as_str(), content.clone()))
+            .collect();
+        if let Some(twm) = &mut self.twm {
+            twm.import_existing_windows(&windows);
+        }
+    }
+}
+
+impl ForeignToplevelHandler for KittyCompositor {
+    fn foreign_toplevel_state(&mut self) -> &mut ForeignToplevelManagerState {
+        &mut self.foreign_toplevel_state
+    }
+
+    fn activate(&mut self, surface: &WlSurface) {
+        let Some(window) = self.window_for_surface(surface) else {
+            return;
+        };
+        // A taskbar doesn't get to move focus behind a lock curtain.
+        if self.session_lock.is_locked() || self.twm.as_ref().is_some_and(|t| t.locked) {
+            return;
+        }
+        let (_, app_id) = toplevel_title_app_id(surface);
+        if self
+            .twm
+            .as_ref()
+            .is_some_and(|t| t.workspace_of_app(&app_id).is_some())
+        {
+            // The IPC path: a focus lock on another pane wins.
+            crate::input::run_twm_action(self, TwmAction::FocusApp(app_id));
+            return;
+        }
+        self.space.raise_element(&window, true);
+        if let Some(kbd) = self.seat.get_keyboard() {
+            kbd.set_focus(self, Some(surface.clone()), SCOUNTER.next_serial());
+        }
+        self.render_all();
+    }
+
+    fn close(&mut self, surface: &WlSurface) {
+        if let Some(toplevel) = self
+            .window_for_surface(surface)
+            .and_then(|w| w.toplevel().cloned())
+        {
+            toplevel.send_close();
+        }
+    }
+}
+delegate_foreign_toplevel!(KittyCompositor);
+
+// ── output management ─────────────────────────────────────────────────────────
+
+impl KittyCompositor {
+    /// Send output management clients the heads as they are now.
+    pub fn refresh_output_heads(&mut self) {
+        let heads = crate::backend::output_heads(self);
+        self.output_management_state.update::<Self>(heads);
+    }
+}
+
+impl OutputManagementHandler for KittyCompositor {
+    fn output_management_state(&mut self) -> &mut OutputManagementState {
+        &mut self.output_management_state
+    }
+
+    fn apply_output_configuration(&mut self, changes: &[(String, Option<OutputConfig>)]) -> bool {
+        fn apply(
+            state: &mut KittyCompositor,
+            name: &str,
+            cfg: Option<&OutputConfig>,
+        ) -> Result<(), Box<dyn std::error::Error>> {
+            match cfg {
+                Some(cfg) => crate::backend::configure_output(state, name, cfg),
+                None => crate::backend::disable_output(state, name),
+            }
+        }
+
+        // The configuration applies as a whole or not at all.
+        let before = crate::backend::output_heads(self);
+        let saved = self.config.outputs.clone();
+        let failed = changes.iter().position(|(name, cfg)| {
+            let result = apply(self, name, cfg.as_ref());
+            if let Err(e) = &result {
+                tracing::warn!("output management: {name}: {e}");
+            }
+            result.is_err()
+        });
+        if let Some(failed) = failed {
+            // Undo newest first; the failed head may be half applied too.
+            for (name, _) in changes[..=failed].iter().rev() {
+                let Some(head) = before.iter().find(|h| &h.name == name) else {
+                    continue;
+                };
+                if let Err(e) = apply(self, name, head.as_change().as_ref()) {
+                    tracing::warn!("output management: {name}: could not roll back: {e}");
+                }
+            }
+            self.config.outputs = saved;
+        }
+        self.refresh_output_heads();
+        self.render_all();
+        failed.is_none()
+    }
+}
+delegate_output_management!(KittyCompositor);
+
+// ── fractional scale ──────────────────────────────────────────────────────────
+
+impl FractionalScaleHandler for KittyCompositor {
+    fn new_fractional_scale(&mut self, surface: WlSurface) {
+        // Popups and subsurfaces take the scale of the window they belong to.
+        let mut root = surface.clone();
+        while let Some(p) = get_parent(&root) {
+            root = p;
+        }
+        let output = match self.window_for_surface(&root) {
+            Some(window) => self.get_output_for_window(&window),
+            None => self.space.outputs().next().cloned(),
+        };
+        let scale = preferred_scale(output.as_ref());
+        with_states(&surface, |states| {
+            with_fractional_scale(states, |fs| fs.set_preferred_scale(scale));
+        });
+    }
+}
+delegate_fractional_scale!(KittyCompositor);
+// wp_fractional_scale_v1 clients draw at the real scale and hand the
+// compositor a viewport of the logical size; smithay applies it when
+// rendering the surface tree.
+delegate_viewporter!(KittyCompositor);
+
+// ── idle inhibit ──────────────────────────────────────────────────────────────
+
+impl IdleInhibitHandler for KittyCompositor {
+    fn inhibit(&mut self, surface: WlSurface) {
+        self.idle_inhibitors.inhibit(surface);
+    }
+
+    fn uninhibit(&mut self, surface: WlSurface) {
+        self.idle_inhibitors.uninhibit(&surface);
+    }
+}
+delegate_idle_inhibit!(KittyCompositor);
+
 // ── output ────────────────────────────────────────────────────────────────────
 
 impl OutputHandler for KittyCompositor {}
@@ -218,6 +648,16 @@ impl SeatHandler for KittyCompositor {
         &mut self.seat_state
     }
     fn focus_changed(&mut self, seat: &Seat<Self>, target: Option<&WlSurface>) {
+        // While the session is locked, focus may only rest on a lock surface.
+        // Whoever moved it elsewhere is overruled on the next idle.
+        if target.is_some() && !self.session_lock.allows(target) {
+            self.handle.insert_idle(|state| {
+                let lock = state.session_lock.focus_surface();
+                if let Some(kbd) = state.seat.get_keyboard() {
+                    kbd.set_focus(state, lock, SCOUNTER.next_serial());
+                }
+            });
+        }
         let dh = &self.display_handle;
         let focus = target.and_then(|s| dh.get_client(s.id()).ok());
         set_data_device_focus(dh, seat, focus.clone());
@@ -229,6 +669,146 @@ impl SeatHandler for KittyCompositor {
 }
 delegate_seat!(KittyCompositor);
 
+// ── cursor shape ──────────────────────────────────────────────────────────────
+// wp-cursor-shape-v1 arrives as a Named status through cursor_image above
+// and is drawn from the XCursor theme like any other named cursor.
+
+impl TabletSeatHandler for KittyCompositor {
+    fn tablet_tool_image(&mut self, _tool: &TabletToolDescriptor, image: CursorImageStatus) {
+        self.cursor_status = image;
+    }
+}
+delegate_cursor_shape!(KittyCompositor);
+
+// ── input method ──────────────────────────────────────────────────────────────
+// zwp_input_method_v2 (fcitx5, ibus) and zwp_text_input_v3 are wired together
+// by smithay: text-input enter / leave follow keyboard focus, and commit and
+// preedit strings go straight from the input method to the focused client,
+// which draws its own preedit. What's left here is the candidate popup.
+
+impl InputMethodHandler for KittyCompositor {
+    fn new_popup(&mut self, surface: ImePopupSurface) {
+        if let Err(e) = self.popups.track_popup(PopupKind::from(surface)) {
+            tracing::warn!("input method popup: {e}");
+        }
+    }
+
+    fn popup_repositioned(&mut self, _: ImePopupSurface) {}
+
+    fn dismiss_popup(&mut self, surface: ImePopupSurface) {
+        if let Some(parent) = surface.get_parent().map(|p| p.surface.clone()) {
+            let _ = PopupManager::dismiss_popup(&parent, &PopupKind::from(surface));
+        }
+    }
+
+    /// Where the text-input client sits, for placing the candidate popup
+    /// next to its cursor rectangle.
+    fn parent_geometry(&self, parent: &WlSurface) -> Rectangle<i32, Logical> {
+        self.window_for_surface(parent)
+            .and_then(|w| self.space.element_geometry(&w))
+            .unwrap_or_default()
+    }
+}
+delegate_input_method_manager!(KittyCompositor);
+delegate_text_input_manager!(KittyCompositor);
+
+// ── pointer constraints ───────────────────────────────────────────────────────
+
+impl KittyCompositor {
+    /// A constraint is active exactly while its surface has pointer focus.
+    fn commit_constraint(&mut self, surface: &WlSurface) {
+        let pointer = self.pointer.clone();
+        let focused = pointer.current_focus().as_ref() == Some(surface);
+        with_pointer_constraint(surface, &pointer, |constraint| match constraint {
+            Some(c) if focused && !c.is_active() => c.activate(),
+            Some(c) if !focused && c.is_active() => c.deactivate(),
+            _ => {}
+        });
+    }
+}
+
+impl PointerConstraintsHandler for KittyCompositor {
+    fn new_constraint(&mut self, surface: &WlSurface, _pointer: &PointerHandle<Self>) {
+        self.commit_constraint(surface);
+    }
+
+    fn cursor_position_hint(
+        &mut self,
+        surface: &WlSurface,
+        pointer: &PointerHandle<Self>,
+        location: smithay::utils::Point<f64, smithay::utils::Logical>,
+    ) {
+        // Only a locked pointer may be warped, and only where its client asks.
+        let locked = with_pointer_constraint(surface, pointer, |constraint| {
+            constraint.is_some_and(|c| c.is_active() && matches!(&*c, PointerConstraint::Locked(_)))
+        });
+        let origin = self
+            .window_for_surface(surface)
+            .and_then(|w| self.space.element_location(&w));
+        if let (true, Some(origin)) = (locked, origin) {
+            pointer.set_location(origin.to_f64() + location);
+        }
+    }
+}
+delegate_pointer_constraints!(KittyCompositor);
+delegate_relative_pointer!(KittyCompositor);
+
+// ── session lock ──────────────────────────────────────────────────────────────
+
+impl SessionLockHandler for KittyCompositor {
+    fn lock_state(&mut self) -> &mut SessionLockManagerState {
+        &mut self.session_lock_state
+    }
+
+    fn lock(&mut self, confirmation: SessionLocker) {
+        tracing::info!("session locked");
+        // Nothing but lock surfaces is drawn from the next frame on; the
+        // client hears it's locked once every output has flipped one.
+        let outputs = self.space.outputs().map(|o| o.name()).collect();
+        self.session_lock.lock(Some(confirmation), outputs);
+        if let Some(kbd) = self.seat.get_keyboard() {
+            kbd.set_focus(self, None, SCOUNTER.next_serial());
+        }
+        self.render_all();
+    }
+
+    fn unlock(&mut self) {
+        tracing::info!("session unlocked");
+        self.session_lock.unlock();
+        let next = self
+            .space
+            .elements()
+            .next()
+            .and_then(|w| w.wl_surface().map(|s| s.into_owned()));
+        if let Some(kbd) = self.seat.get_keyboard() {
+            kbd.set_focus(self, next, SCOUNTER.next_serial());
+        }
+        self.render_all();
+    }
+
+    fn new_surface(&mut self, surface: LockSurface, output: wl_output::WlOutput) {
+        let Some(output) = Output::from_resource(&output) else {
+            return;
+        };
+        let size = self
+            .space
+            .output_geometry(&output)
+            .map(|g| g.size)
+            .unwrap_or_else(|| smithay::utils::Size::from((1920, 1080)));
+        surface.with_pending_state(|s| {
+            s.size = Some((size.w as u32, size.h as u32).into());
+        });
+        surface.send_configure();
+
+        let wl = surface.wl_surface().clone();
+        self.session_lock.add_surface(output, surface);
+        if let Some(kbd) = self.seat.get_keyboard() {
+            kbd.set_focus(self, Some(wl), SCOUNTER.next_serial());
+        }
+    }
+}
+delegate_session_lock!(KittyCompositor);
+
 // ── layer shell ───────────────────────────────────────────────────────────────
 
 impl WlrLayerShellHandler for KittyCompositor {
@@ -240,11 +820,19 @@ impl WlrLayerShellHandler for KittyCompositor {
         surface: smithay::wayland::shell::wlr_layer::LayerSurface,
         _output: Option<wl_output::WlOutput>,
         _layer: Layer,
-        _namespace: String,
+        namespace: String,
     ) {
+        if !self.config.layer_namespace_allowed(&namespace) {
+            tracing::warn!("layer surface namespace {namespace:?} not in layer_shell_namespaces");
+            surface.wl_surface().post_error(
+                wl_surface::Error::DefunctRoleObject,
+                format!("layer-shell namespace {namespace:?} is not allowed"),
+            );
+            return;
+        }
         if let Some(output) = self.space.outputs().next().cloned() {
             let mut map = layer_map_for_output(&output);
-            let _ = map.map_layer(&LayerSurface::new(surface, String::new()));
+            let _ = map.map_layer(&LayerSurface::new(surface, namespace));
         }
     }
     fn layer_destroyed(&mut self, _: smithay::wayland::shell::wlr_layer::LayerSurface) {}
@@ -299,6 +887,10 @@ impl XdgShellHandler for KittyCompositor {
         let obj_id = surface.wl_surface().id();
         self.unclaimed_toplevels.insert(obj_id, surface.clone());
 
+        let (title, _) = toplevel_title_app_id(surface.wl_surface());
+        self.foreign_toplevel_state
+            .new_toplevel::<Self>(surface.wl_surface(), &title, &app_id);
+
         let window = Window::new_wayland_window(surface);
 
         // Send an initial configure with the output size so the client knows
@@ -315,7 +907,8 @@ impl XdgShellHandler for KittyCompositor {
         let twm_size: Option<smithay::utils::Size<i32, smithay::utils::Logical>> =
             if !app_id.is_empty() {
                 self.twm.as_ref().and_then(|twm| {
-                    let (cw, ch) = crate::pixelui::overlay_element::cell_size();
+                    let output = self.space.outputs().next()?.name();
+                    let (cw, ch) = crate::pixelui::overlay_element::cell_size(&output);
                     if cw == 0 || ch == 0 {
                         return None;
                     }
@@ -379,6 +972,7 @@ impl XdgShellHandler for KittyCompositor {
         }
 
         // Normal toplevel destroyed — also clean up TWM pane.
+        self.foreign_toplevel_state.toplevel_closed(wl);
         let app_id = with_states(wl, |states| {
             states
                 .data_map
@@ -400,6 +994,7 @@ impl XdgShellHandler for KittyCompositor {
             match Command::new(&bin)
                 .args(&args)
                 .env("WAYLAND_DISPLAY", &self.wayland_socket)
+                .envs(self.config.global_env.iter().map(|(k, v)| (k, v)))
                 .spawn()
             {
                 Ok(_) => {}
@@ -470,3 +1065,245 @@ impl XdgDecorationHandler for KittyCompositor {
     }
 }
 delegate_xdg_decoration!(KittyCompositor);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use smithay::reexports::wayland_server::{
+        backend::{ClientData, ClientId, DisconnectReason},
+        Display,
+    };
+    use std::sync::Arc;
+    use wayland_client::{
+        event_created_child,
+        protocol::{wl_registry, wl_seat::WlSeat},
+        Connection, Dispatch, EventQueue, Proxy, QueueHandle,
+    };
+    use wayland_protocols_wlr::data_control::v1::client::{
+        zwlr_data_control_device_v1::{self, ZwlrDataControlDeviceV1},
+        zwlr_data_control_manager_v1::ZwlrDataControlManagerV1 as ClientManager,
+        zwlr_data_control_offer_v1::{self, ZwlrDataControlOfferV1},
+    };
+
+    /// The selection plumbing `KittyCompositor` wires up, without the DRM
+    /// session it can't be built without.
+    struct Server {
+        compositor_state: CompositorState,
+        seat_state: SeatState<Self>,
+        data_device_state: DataDeviceState,
+        primary_selection_state: PrimarySelectionState,
+        data_control_state: DataControlState,
+    }
+
+    impl CompositorHandler for Server {
+        fn compositor_state(&mut self) -> &mut CompositorState {
+            &mut self.compositor_state
+        }
+
+        fn client_compositor_state<'a>(&self, client: &'a Client) -> &'a CompositorClientState {
+            &client.get_data::<TestClient>().unwrap().0
+        }
+
+        fn commit(&mut self, _: &WlSurface) {}
+    }
+
+    impl SeatHandler for Server {
+        type KeyboardFocus = WlSurface;
+        type PointerFocus = WlSurface;
+        type TouchFocus = WlSurface;
+
+        fn seat_state(&mut self) -> &mut SeatState<Self> {
+            &mut self.seat_state
+        }
+    }
+
+    impl SelectionHandler for Server {
+        type SelectionUserData = ();
+    }
+    impl ClientDndGrabHandler for Server {}
+    impl ServerDndGrabHandler for Server {}
+
+    impl DataDeviceHandler for Server {
+        fn data_device_state(&self) -> &DataDeviceState {
+            &self.data_device_state
+        }
+    }
+
+    impl PrimarySelectionHandler for Server {
+        fn primary_selection_state(&self) -> &PrimarySelectionState {
+            &self.primary_selection_state
+        }
+    }
+
+    impl DataControlHandler for Server {
+        fn data_control_state(&self) -> &DataControlState {
+            &self.data_control_state
+        }
+    }
+
+    delegate_compositor!(Server);
+    delegate_seat!(Server);
+    delegate_data_device!(Server);
+    delegate_primary_selection!(Server);
+    delegate_data_control!(Server);
+
+    #[derive(Default)]
+    struct TestClient(CompositorClientState);
+
+    impl ClientData for TestClient {
+        fn initialized(&self, _: ClientId) {}
+        fn disconnected(&self, _: ClientId, _: DisconnectReason) {}
+    }
+
+    /// A clipboard manager: binds the seat and the data-control manager and
+    /// keeps the MIME types of the offer it is handed as the selection.
+    #[derive(Default)]
+    struct Watcher {
+        seat: Option<WlSeat>,
+        manager: Option<ClientManager>,
+        offered: Vec<String>,
+        selection: Option<Vec<String>>,
+    }
+
+    impl Dispatch<wl_registry::WlRegistry, ()> for Watcher {
+        fn event(
+            w: &mut Self,
+            registry: &wl_registry::WlRegistry,
+            event: wl_registry::Event,
+            _: &(),
+            _: &Connection,
+            qh: &QueueHandle<Self>,
+        ) {
+            if let wl_registry::Event::Global {
+                name, interface, ..
+            } = event
+            {
+                match interface.as_str() {
+                    "wl_seat" => w.seat = Some(registry.bind(name, 1, qh, ())),
+                    "zwlr_data_control_manager_v1" => {
+                        w.manager = Some(registry.bind(name, 2, qh, ()))
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    impl Dispatch<WlSeat, ()> for Watcher {
+        fn event(
+            _: &mut Self,
+            _: &WlSeat,
+            _: <WlSeat as Proxy>::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+        }
+    }
+
+    impl Dispatch<ClientManager, ()> for Watcher {
+        fn event(
+            _: &mut Self,
+            _: &ClientManager,
+            _: <ClientManager as Proxy>::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+        }
+    }
+
+    impl Dispatch<ZwlrDataControlDeviceV1, ()> for Watcher {
+        fn event(
+            w: &mut Self,
+            _: &ZwlrDataControlDeviceV1,
+            event: zwlr_data_control_device_v1::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+            match event {
+                zwlr_data_control_device_v1::Event::DataOffer { .. } => w.offered.clear(),
+                zwlr_data_control_device_v1::Event::Selection { id } => {
+                    w.selection = id.map(|_| std::mem::take(&mut w.offered));
+                }
+                _ => {}
+            }
+        }
+
+        event_created_child!(Watcher, ZwlrDataControlDeviceV1, [
+            zwlr_data_control_device_v1::EVT_DATA_OFFER_OPCODE => (ZwlrDataControlOfferV1, ()),
+        ]);
+    }
+
+    impl Dispatch<ZwlrDataControlOfferV1, ()> for Watcher {
+        fn event(
+            w: &mut Self,
+            _: &ZwlrDataControlOfferV1,
+            event: zwlr_data_control_offer_v1::Event,
+            _: &(),
+            _: &Connection,
+            _: &QueueHandle<Self>,
+        ) {
+            if let zwlr_data_control_offer_v1::Event::Offer { mime_type } = event {
+                w.offered.push(mime_type);
+            }
+        }
+    }
+
+    /// Let the server handle what the client sent, then the client what
+    /// the server sent back.
+    fn pump(
+        display: &mut Display<Server>,
+        server: &mut Server,
+        (conn, queue): (&Connection, &mut EventQueue<Watcher>),
+        watcher: &mut Watcher,
+    ) {
+        conn.flush().unwrap();
+        display.dispatch_clients(server).unwrap();
+        display.flush_clients().unwrap();
+        if let Some(guard) = conn.prepare_read() {
+            guard.read().unwrap();
+        }
+        queue.dispatch_pending(watcher).unwrap();
+    }
+
+    #[test]
+    fn data_control_device_gets_the_selection_mime_type() {
+        let mut display = Display::<Server>::new().unwrap();
+        let dh = display.handle();
+        let primary_selection_state = PrimarySelectionState::new::<Server>(&dh);
+        let mut server = Server {
+            compositor_state: CompositorState::new::<Server>(&dh),
+            seat_state: SeatState::new(),
+            data_device_state: DataDeviceState::new::<Server>(&dh),
+            data_control_state: data_control_state::<Server>(&dh, &primary_selection_state),
+            primary_selection_state,
+        };
+        let seat = server.seat_state.new_wl_seat(&dh, "seat0");
+
+        let (client_end, server_end) = UnixStream::pair().unwrap();
+        dh.clone()
+            .insert_client(server_end, Arc::new(TestClient::default()))
+            .unwrap();
+        let conn = Connection::from_socket(client_end).unwrap();
+        let mut queue = conn.new_event_queue();
+        let qh = queue.handle();
+        let mut watcher = Watcher::default();
+        conn.display().get_registry(&qh, ());
+        pump(&mut display, &mut server, (&conn, &mut queue), &mut watcher);
+
+        let (Some(manager), Some(wl_seat)) = (&watcher.manager, &watcher.seat) else {
+            panic!("no data-control manager or seat advertised");
+        };
+        manager.get_data_device(wl_seat, &qh, ());
+        pump(&mut display, &mut server, (&conn, &mut queue), &mut watcher);
+        assert_eq!(watcher.selection, None);
+
+        // What offer_clipboard does with a history entry.
+        let mime = "text/plain;charset=utf-8".to_string();
+        set_data_device_selection(&dh, &seat, vec![mime.clone()], ());
+        pump(&mut display, &mut server, (&conn, &mut queue), &mut watcher);
+        assert_eq!(watcher.selection, Some(vec![mime]));
+    }
+}
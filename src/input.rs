@@ -1,30 +1,42 @@
-// input.rs — keyboard, pointer, and axis event handling
+// input.rs — keyboard, pointer, axis and touchpad gesture handling
 
-use std::sync::atomic::Ordering;
+use std::{
+    path::Path,
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
 
 use smithay::{
     backend::{
         input::{
-            AbsolutePositionEvent, Axis, ButtonState, Event, InputEvent, KeyState,
+            AbsolutePositionEvent, Axis, ButtonState, Event, GestureBeginEvent,
+            GesturePinchUpdateEvent, GestureSwipeUpdateEvent, InputEvent, KeyState,
             KeyboardKeyEvent, PointerAxisEvent, PointerButtonEvent, PointerMotionAbsoluteEvent,
             PointerMotionEvent,
         },
         libinput::LibinputInputBackend,
         session::Session,
     },
+    desktop::{layer_map_for_output, utils::bbox_from_surface_tree},
     input::{
-        keyboard::FilterResult,
-        pointer::{AxisFrame, ButtonEvent, MotionEvent},
+        keyboard::{FilterResult, KeyboardTarget, ModifiersState, XkbConfig},
+        pointer::{AxisFrame, ButtonEvent, MotionEvent, PointerHandle, RelativeMotionEvent},
+        Seat, SeatHandler,
+    },
+    reexports::wayland_server::protocol::{wl_pointer, wl_surface::WlSurface},
+    utils::{Logical, Point, Rectangle, SERIAL_COUNTER as SCOUNTER},
+    wayland::{
+        compositor::{with_states, RectangleKind},
+        pointer_constraints::{with_pointer_constraint, PointerConstraint},
+        seat::WaylandFocus,
+        shell::{wlr_layer::KeyboardInteractivity, xdg::XdgToplevelSurfaceData},
     },
-    reexports::wayland_server::protocol::wl_pointer,
-    utils::SERIAL_COUNTER as SCOUNTER,
-    wayland::seat::WaylandFocus,
 };
 
 use xkbcommon::xkb;
 
 use crate::{
-    config::{self, KeyAction},
+    config::{self, GestureConfig, KeyAction, KeyboardConfig, Modifier},
     render::surface_under,
     state::{KittyCompositor, MouseMode},
     twm_drop_in::Action as TwmAction,
@@ -39,11 +51,246 @@ pub fn vt_from_keysym(keysym: xkb::Keysym) -> Option<u32> {
     (raw >= VT_FIRST && raw <= VT_LAST).then(|| raw - VT_FIRST + 1)
 }
 
+// ── keymap ────────────────────────────────────────────────────────────────────
+
+fn compile_keymap(source: &str) -> Option<xkb::Keymap> {
+    let ctx = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+    xkb::Keymap::new_from_string(
+        &ctx,
+        source.to_owned(),
+        xkb::KEYMAP_FORMAT_TEXT_V1,
+        xkb::KEYMAP_COMPILE_NO_FLAGS,
+    )
+}
+
+/// Read a complete `xkb_keymap { ... }` file. Returns the source only if it
+/// compiles, so a typo never leaves the seat without a keymap.
+pub fn load_xkb_file(path: &Path) -> Option<String> {
+    let source = match std::fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(e) => {
+            tracing::warn!("xkb_file {}: {e}", path.display());
+            return None;
+        }
+    };
+    if compile_keymap(&source).is_none() {
+        tracing::warn!("xkb_file {}: keymap does not compile", path.display());
+        return None;
+    }
+    Some(source)
+}
+
+/// Install the keymap described by `keyboard` on the seat: the `xkb_file`
+/// when set and valid, otherwise layout/variant/options, with
+/// `modifier_as_super` applied on top.
+pub fn apply_keymap(state: &mut KittyCompositor, keyboard: &KeyboardConfig) {
+    state.send_key_keymap = None;
+    let Some(kbd) = state.seat.get_keyboard() else {
+        return;
+    };
+    if keyboard.xkb_file.is_some() || keyboard.modifier_as_super.is_some() {
+        let Some(keymap) = config_keymap(keyboard) else {
+            tracing::warn!("keyboard: keymap does not compile, keeping the current one");
+            return;
+        };
+        let source = keymap.get_as_string(xkb::KEYMAP_FORMAT_TEXT_V1);
+        if let Err(e) = kbd.set_keymap_from_string(state, source) {
+            tracing::warn!("set_keymap_from_string: {e:?}");
+        }
+        return;
+    }
+    let xkb_config = XkbConfig {
+        layout: keyboard.layout.as_deref().unwrap_or(""),
+        variant: keyboard.variant.as_deref().unwrap_or(""),
+        options: keyboard.options.clone(),
+        ..XkbConfig::default()
+    };
+    if let Err(e) = kbd.set_xkb_config(state, xkb_config) {
+        tracing::warn!("set_xkb_config: {e:?}");
+    }
+}
+
+/// `modifier_as_super`: rebind the left-hand `modifier` key of a serialized
+/// keymap to `Super_L` on Mod4, as a setxkbmap option would. The right-hand
+/// key keeps its modifier, so Alt shortcuts inside apps still have a key.
+fn super_remap(source: &str, modifier: Modifier) -> Option<String> {
+    let key = match modifier {
+        Modifier::Alt => "LALT",
+        Modifier::Ctrl => "LCTL",
+        Modifier::Super => return Some(source.to_owned()),
+    };
+    // xkb_symbols is the last section; insert before its closing brace.
+    let body = source.trim_end().strip_suffix("};")?;
+    let end = body.rfind("};")?;
+    Some(format!(
+        "{}\treplace key <{key}> {{ type= \"ONE_LEVEL\", symbols[Group1]= [ Super_L ] }};\n\
+         \treplace modifier_map Mod4 {{ <{key}> }};\n{}",
+        &source[..end],
+        &source[end..],
+    ))
+}
+
+// ── key injection ─────────────────────────────────────────────────────────────
+
+fn keycode_for(keymap: &xkb::Keymap, name: &str) -> Option<xkb::Keycode> {
+    let sym = match name {
+        "ctrl" | "control" => xkb::Keysym::Control_L,
+        "shift" => xkb::Keysym::Shift_L,
+        "alt" => xkb::Keysym::Alt_L,
+        "super" | "logo" | "mod4" => xkb::Keysym::Super_L,
+        _ => xkb::keysym_from_name(name, xkb::KEYSYM_CASE_INSENSITIVE),
+    };
+    if sym == xkb::Keysym::NoSymbol {
+        return None;
+    }
+    let (min, max) = (keymap.min_keycode().raw(), keymap.max_keycode().raw());
+    (min..=max)
+        .map(xkb::Keycode::new)
+        .find(|&kc| keymap.key_get_syms_by_level(kc, 0, 0).contains(&sym))
+}
+
+/// Press every modifier, tap `key`, release the modifiers in reverse.
+fn key_sequence(
+    keymap: &xkb::Keymap,
+    key: &str,
+    mods: &[String],
+) -> Option<Vec<(xkb::Keycode, KeyState)>> {
+    let mod_codes = mods
+        .iter()
+        .map(|m| keycode_for(keymap, m))
+        .collect::<Option<Vec<_>>>()?;
+    let key_code = keycode_for(keymap, key)?;
+    let mut seq: Vec<_> = mod_codes.iter().map(|&c| (c, KeyState::Pressed)).collect();
+    seq.push((key_code, KeyState::Pressed));
+    seq.push((key_code, KeyState::Released));
+    seq.extend(mod_codes.iter().rev().map(|&c| (c, KeyState::Released)));
+    Some(seq)
+}
+
+/// The same keymap the seat was given by `apply_keymap`.
+fn config_keymap(keyboard: &KeyboardConfig) -> Option<xkb::Keymap> {
+    let keymap = base_keymap(keyboard)?;
+    match keyboard.modifier_as_super {
+        Some(m) => compile_keymap(&super_remap(
+            &keymap.get_as_string(xkb::KEYMAP_FORMAT_TEXT_V1),
+            m,
+        )?),
+        None => Some(keymap),
+    }
+}
+
+/// `keyboard`'s keymap before `modifier_as_super`.
+fn base_keymap(keyboard: &KeyboardConfig) -> Option<xkb::Keymap> {
+    if let Some(source) = keyboard.xkb_file.as_deref().and_then(load_xkb_file) {
+        return compile_keymap(&source);
+    }
+    let ctx = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+    xkb::Keymap::new_from_names(
+        &ctx,
+        "",
+        "",
+        keyboard.layout.as_deref().unwrap_or(""),
+        keyboard.variant.as_deref().unwrap_or(""),
+        keyboard.options.clone(),
+        xkb::KEYMAP_COMPILE_NO_FLAGS,
+    )
+}
+
+/// An exclusive-keyboard layer surface (a screen locker, usually) owns input;
+/// nothing may be typed behind its back.
+fn keyboard_locked(state: &KittyCompositor) -> bool {
+    state.space.outputs().any(|o| {
+        layer_map_for_output(o)
+            .layers()
+            .any(|l| l.cached_state().keyboard_interactivity == KeyboardInteractivity::Exclusive)
+    })
+}
+
+/// `KeyAction::SendKey`: type `mods`+`key` into the window whose app_id is
+/// `target_app_id`, wherever focus is.
+fn send_key(state: &mut KittyCompositor, target_app_id: &str, key: &str, mods: &[String]) {
+    if keyboard_locked(state) {
+        tracing::warn!("send_key to {target_app_id:?} rejected: keyboard is inhibited");
+        return;
+    }
+    let target = state.space.elements().find_map(|w| {
+        let surface = w.toplevel()?.wl_surface().clone();
+        let app_id = with_states(&surface, |states| {
+            states
+                .data_map
+                .get::<XdgToplevelSurfaceData>()
+                .and_then(|d| d.lock().ok())
+                .and_then(|l| l.app_id.clone())
+        });
+        (app_id.as_deref() == Some(target_app_id)).then_some(surface)
+    });
+    let Some(target) = target else {
+        tracing::warn!("send_key: no window with app_id {target_app_id:?}");
+        return;
+    };
+    if state.send_key_keymap.is_none() {
+        state.send_key_keymap = config_keymap(&state.config.keyboard);
+    }
+    let Some(seq) = state
+        .send_key_keymap
+        .as_ref()
+        .and_then(|km| key_sequence(km, key, mods))
+    else {
+        tracing::warn!("send_key: cannot map {mods:?}+{key:?} to keycodes");
+        return;
+    };
+    let seat = state.seat.clone();
+    let time = Duration::from(state.clock.now()).as_millis() as u32;
+    inject_keys(state, &seat, target, &seq, time);
+}
+
+/// Type `seq` into `target`, then hand focus back to whatever had it. The
+/// modifiers held on the seat (the bind's own Super, usually) are cleared
+/// for the sequence and restored after it, and `target` is told both
+/// times: otherwise `ctrl+c` would arrive as Super+Ctrl+C.
+fn inject_keys<D>(
+    data: &mut D,
+    seat: &Seat<D>,
+    target: WlSurface,
+    seq: &[(xkb::Keycode, KeyState)],
+    time: u32,
+) where
+    D: SeatHandler<KeyboardFocus = WlSurface> + 'static,
+{
+    let Some(kbd) = seat.get_keyboard() else {
+        return;
+    };
+    let previous = kbd.current_focus();
+    let held = kbd.modifier_state();
+    kbd.set_modifier_state(ModifiersState {
+        caps_lock: held.caps_lock,
+        num_lock: held.num_lock,
+        ..Default::default()
+    });
+    kbd.set_focus(data, Some(target.clone()), SCOUNTER.next_serial());
+    // set_focus only reports modifiers on enter, and the target may
+    // already have had focus.
+    target.modifiers(seat, data, kbd.modifier_state(), SCOUNTER.next_serial());
+    for &(keycode, key_state) in seq {
+        kbd.input::<(), _>(
+            data,
+            keycode,
+            key_state,
+            SCOUNTER.next_serial(),
+            time,
+            |_, _, _| FilterResult::Forward,
+        );
+    }
+    kbd.set_modifier_state(held);
+    target.modifiers(seat, data, kbd.modifier_state(), SCOUNTER.next_serial());
+    kbd.set_focus(data, previous, SCOUNTER.next_serial());
+}
+
 // ── TWM action dispatch ───────────────────────────────────────────────────────
 
 /// Central point for all TWM keybind effects.
 /// Dispatches to TwmState, then syncs focus + placement + triggers a redraw.
-fn run_twm_action(state: &mut KittyCompositor, action: TwmAction) {
+pub fn run_twm_action(state: &mut KittyCompositor, action: TwmAction) {
     if let Some(twm) = &mut state.twm {
         twm.dispatch(&action);
     }
@@ -54,16 +301,138 @@ fn run_twm_action(state: &mut KittyCompositor, action: TwmAction) {
 // ── main input handler ────────────────────────────────────────────────────────
 
 pub fn handle_input(state: &mut KittyCompositor, event: InputEvent<LibinputInputBackend>) {
+    state.last_activity = Instant::now();
+    if state.suspended {
+        state.resume_render();
+    }
+
     match event {
         InputEvent::Keyboard { event } => handle_keyboard(state, event),
         InputEvent::PointerMotionAbsolute { event } => handle_pointer_motion_abs(state, event),
         InputEvent::PointerMotion { event } => handle_pointer_motion(state, event),
         InputEvent::PointerButton { event } => handle_pointer_button(state, event),
         InputEvent::PointerAxis { event } => handle_pointer_axis(state, event),
+        InputEvent::GestureSwipeBegin { event } => state.gestures.begin(event.fingers()),
+        InputEvent::GestureSwipeUpdate { event } => {
+            let fingers = state.gestures.fingers();
+            let action = state
+                .gestures
+                .update(event.delta_x(), event.delta_y(), fingers);
+            run_gesture_action(state, action);
+        }
+        InputEvent::GesturePinchBegin { event } => state.gestures.begin(event.fingers()),
+        InputEvent::GesturePinchUpdate { event } => {
+            let fingers = state.gestures.fingers();
+            let action = state.gestures.pinch(event.scale(), fingers);
+            run_gesture_action(state, action);
+        }
         _ => {}
     }
 }
 
+// ── touchpad gestures ─────────────────────────────────────────────────────────
+
+/// Pinch scale away from 1.0 that counts as a pinch rather than jitter.
+const PINCH_THRESHOLD: f64 = 0.25;
+
+/// Turns libinput swipe / pinch updates into TWM actions. At most one action
+/// fires per gesture; `begin` arms the tracker again.
+#[derive(Debug, Clone)]
+pub struct GestureTracker {
+    config: GestureConfig,
+    fingers: u32,
+    dx: f64,
+    dy: f64,
+    fired: bool,
+}
+
+impl GestureTracker {
+    pub fn new(config: GestureConfig) -> Self {
+        Self {
+            config,
+            fingers: 0,
+            dx: 0.0,
+            dy: 0.0,
+            fired: false,
+        }
+    }
+
+    /// A swipe or pinch with `fingers` fingers started.
+    pub fn begin(&mut self, fingers: u32) {
+        *self = Self {
+            fingers,
+            ..Self::new(self.config)
+        };
+    }
+
+    /// Finger count of the gesture in progress.
+    pub fn fingers(&self) -> u32 {
+        self.fingers
+    }
+
+    /// Accumulate one swipe update. A mostly horizontal swipe of at least
+    /// `swipe_threshold` switches workspace: left for the next one, right
+    /// for the previous, like dragging the workspaces along.
+    pub fn update(&mut self, dx: f64, dy: f64, fingers: u32) -> Option<TwmAction> {
+        if self.fired || fingers != self.config.fingers {
+            return None;
+        }
+        self.dx += dx;
+        self.dy += dy;
+        if self.dx.abs() < self.config.swipe_threshold || self.dx.abs() < self.dy.abs() {
+            return None;
+        }
+        self.fired = true;
+        Some(if self.dx < 0.0 {
+            TwmAction::NextWorkspace
+        } else {
+            TwmAction::PrevWorkspace
+        })
+    }
+
+    /// One pinch update; `scale` is relative to the start of the gesture.
+    pub fn pinch(&mut self, scale: f64, fingers: u32) -> Option<TwmAction> {
+        if self.fired || fingers != self.config.fingers || (scale - 1.0).abs() < PINCH_THRESHOLD {
+            return None;
+        }
+        self.fired = true;
+        Some(TwmAction::NextLayout)
+    }
+}
+
+// ── release binds ─────────────────────────────────────────────────────────────
+
+/// Whether a key was tapped on its own, which is when a `bind_r` fires:
+/// releasing Super after Super+Return, or after a Super-drag, must not
+/// also open the overview.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TapTracker {
+    last_pressed: Option<xkb::Keycode>,
+}
+
+impl TapTracker {
+    pub fn press(&mut self, key: xkb::Keycode) {
+        self.last_pressed = Some(key);
+    }
+
+    /// Something other than a key press joined the chord (a click).
+    pub fn interrupt(&mut self) {
+        self.last_pressed = None;
+    }
+
+    /// `key` went up; whether nothing else was pressed since it went down.
+    pub fn release(&mut self, key: xkb::Keycode) -> bool {
+        self.last_pressed.take() == Some(key)
+    }
+}
+
+fn run_gesture_action(state: &mut KittyCompositor, action: Option<TwmAction>) {
+    // Session locked: gestures are compositor bindings, so they're refused.
+    if let Some(action) = action.filter(|_| state.session_lock.allows(None)) {
+        run_twm_action(state, action);
+    }
+}
+
 // ── keyboard ──────────────────────────────────────────────────────────────────
 
 fn handle_keyboard(
@@ -74,11 +443,18 @@ fn handle_keyboard(
     let time = event.time_msec();
     let keycode = event.key_code();
     let key_state = event.state();
+    let tapped = match key_state {
+        KeyState::Pressed => {
+            state.key_taps.press(keycode);
+            false
+        }
+        KeyState::Released => state.key_taps.release(keycode),
+    };
 
     // Auto-focus the first window if nothing is focused yet.
     {
         let kbd = state.seat.get_keyboard().unwrap();
-        if kbd.current_focus().is_none() {
+        if kbd.current_focus().is_none() && !state.session_lock.is_locked() {
             let surface = state
                 .space
                 .elements()
@@ -104,12 +480,36 @@ fn handle_keyboard(
         serial,
         time,
         |state, mods, keysym_handle| {
+            // ── Release binds (`bind_r`) ──────────────────────────────────────
+            // Only a key tapped on its own fires one. The key-up is still
+            // forwarded: the client saw the press, and swallowing the
+            // release would leave the key stuck down for it.
             if key_state != KeyState::Pressed {
+                if state.session_lock.is_locked() || state.twm.as_ref().is_some_and(|t| t.locked) {
+                    return FilterResult::Forward;
+                }
+                // The alt-tab popup stays up while a modifier is held.
+                let holding = mods.alt || mods.logo || mods.ctrl || mods.shift;
+                if !holding && state.twm.as_ref().is_some_and(|t| t.alt_tab_active()) {
+                    if let Some(twm) = &mut state.twm {
+                        twm.alt_tab_finish();
+                    }
+                    state.sync_twm_focus_to_wayland();
+                    state.render_all();
+                }
+                let name =
+                    config::normalise_key_name(&xkb::keysym_get_name(keysym_handle.modified_sym()));
+                let bind = state.config.keybinds.iter().find(|kb| {
+                    config::bind_matches(kb, mods, &name, key_state, &state.config.keyboard)
+                });
+                if let Some(action) = bind.filter(|_| tapped).map(|kb| kb.action.clone()) {
+                    run_key_action(state, action, &wayland_socket);
+                }
                 return FilterResult::Forward;
             }
 
             // ── VT switching ──────────────────────────────────────────────────
-            if mods.ctrl && mods.alt {
+            if config::vt_switch_held(mods, &state.config.keyboard) {
                 let base_sym = keysym_handle
                     .raw_syms()
                     .first()
@@ -130,6 +530,39 @@ fn handle_keyboard(
                 }
             }
 
+            // ── Session lock: keys go to the lock surface and nowhere else ────
+            if state.session_lock.is_locked() {
+                return FilterResult::Forward;
+            }
+
+            // ── TWM lock: every key feeds the PIN entry ───────────────────────
+            if state.twm.as_ref().is_some_and(|t| t.locked) {
+                let name =
+                    config::normalise_key_name(&xkb::keysym_get_name(keysym_handle.modified_sym()));
+                let ch = keysym_handle.modified_sym().key_char();
+                let mut submitted = None;
+                if let Some(twm) = &mut state.twm {
+                    match name.as_str() {
+                        "return" | "kp_enter" => {
+                            submitted = Some(std::mem::take(&mut twm.lock_entry));
+                        }
+                        "backspace" => {
+                            twm.lock_entry.pop();
+                        }
+                        "escape" => twm.lock_entry.clear(),
+                        _ => twm.lock_entry.extend(ch.filter(|c| !c.is_control())),
+                    }
+                }
+                if let Some(pin) = submitted {
+                    run_twm_action(state, TwmAction::Unlock(pin));
+                }
+                return FilterResult::Intercept(());
+            }
+
+            // Everything below matches compositor binds, so fold in
+            // `modifier_as_super` (VT switching above has its own check).
+            let mods = &config::effective_mods(mods, &state.config.keyboard);
+
             let pressed_sym = keysym_handle.modified_sym();
             let name = config::normalise_key_name(&xkb::keysym_get_name(pressed_sym));
 
@@ -163,6 +596,12 @@ fn handle_keyboard(
                 mods.alt,
             );
 
+            // Super+Ctrl+L locks; plain Super+L is already focus-right.
+            if mods.logo && mods.ctrl && !mods.alt && !mods.shift && name == "l" {
+                run_twm_action(state, TwmAction::Lock);
+                return FilterResult::Intercept(());
+            }
+
             // ── Hard-coded TWM keybinds (Super + hjkl / arrows / etc.) ────────
             //
             // These are intercepted before the config keybind table so they
@@ -187,6 +626,7 @@ fn handle_keyboard(
                         // Main ratio
                         "equal" => Some(TwmAction::GrowMain),
                         "minus" => Some(TwmAction::ShrinkMain),
+                        "z" => Some(TwmAction::UndoResize),
                         // Workspace by number (Super+1..9)
                         "1" => Some(TwmAction::Workspace(1)),
                         "2" => Some(TwmAction::Workspace(2)),
@@ -201,10 +641,21 @@ fn handle_keyboard(
                     }
                 } else {
                     // Super+Shift combos
+                    let gap = state
+                        .twm
+                        .as_ref()
+                        .map_or(0, |t| t.workspaces[t.active_ws].gap_inner);
                     match name.as_str() {
                         // Move pane within workspace
                         "h" | "left" => Some(TwmAction::MoveLeft),
                         "l" | "right" => Some(TwmAction::MoveRight),
+                        "z" => Some(TwmAction::RedoResize),
+                        "space" => Some(TwmAction::ToggleFocusLock),
+                        // Inner gap (Super+Shift+= / Super+Shift+-)
+                        "plus" | "equal" => Some(TwmAction::SetPaneGap(gap.saturating_add(1))),
+                        "underscore" | "minus" => {
+                            Some(TwmAction::SetPaneGap(gap.saturating_sub(1)))
+                        }
                         // Move to workspace (Super+Shift+1..9)
                         "1" => Some(TwmAction::MoveToWorkspace(1)),
                         "2" => Some(TwmAction::MoveToWorkspace(2)),
@@ -226,52 +677,11 @@ fn handle_keyboard(
             }
 
             // ── Config-file keybinds ──────────────────────────────────────────
-            for i in 0..state.config.keybinds.len() {
-                if !config::mods_match(mods, &state.config.keybinds[i].mods, &state.config.keyboard)
-                {
-                    continue;
-                }
-                if name != state.config.keybinds[i].key {
-                    continue;
-                }
-
-                let action = state.config.keybinds[i].action.clone();
-                match action {
-                    KeyAction::Quit => {
-                        state.running.store(false, Ordering::SeqCst);
-                    }
-                    KeyAction::CloseWindow => {
-                        // Close via TWM first; fall back to killing the focused
-                        // space window if TWM has no pane for it.
-                        let had_twm_pane =
-                            state.twm.as_ref().and_then(|t| t.focused_id()).is_some();
-                        if had_twm_pane {
-                            run_twm_action(state, TwmAction::Close);
-                        } else {
-                            let focus = state.seat.get_keyboard().and_then(|k| k.current_focus());
-                            let target = focus
-                                .and_then(|fs| {
-                                    state
-                                        .space
-                                        .elements()
-                                        .find(|w| w.wl_surface().as_deref() == Some(&fs))
-                                        .cloned()
-                                })
-                                .or_else(|| state.space.elements().next().cloned());
-                            if let Some(w) = target {
-                                if let Some(t) = w.toplevel() {
-                                    t.send_close();
-                                }
-                            }
-                        }
-                    }
-                    KeyAction::ReloadConfig => {
-                        crate::main_loop::reload_config(state);
-                    }
-                    KeyAction::Spawn { command, args } => {
-                        config::spawn_process(&command, &args, &wayland_socket);
-                    }
-                }
+            let bind = state.config.keybinds.iter().find(|kb| {
+                config::bind_matches(kb, mods, &name, key_state, &state.config.keyboard)
+            });
+            if let Some(action) = bind.map(|kb| kb.action.clone()) {
+                run_key_action(state, action, &wayland_socket);
                 return FilterResult::Intercept(());
             }
 
@@ -280,6 +690,69 @@ fn handle_keyboard(
     );
 }
 
+/// Run the action of a config-file keybind.
+fn run_key_action(state: &mut KittyCompositor, action: KeyAction, wayland_socket: &str) {
+    match action {
+        KeyAction::Quit => {
+            state.running.store(false, Ordering::SeqCst);
+        }
+        KeyAction::CloseWindow => {
+            // Close via TWM first; fall back to killing the focused
+            // space window if TWM has no pane for it.
+            let had_twm_pane = state.twm.as_ref().and_then(|t| t.focused_id()).is_some();
+            if had_twm_pane {
+                run_twm_action(state, TwmAction::Close);
+            } else {
+                let focus = state.seat.get_keyboard().and_then(|k| k.current_focus());
+                let target = focus
+                    .and_then(|fs| {
+                        state
+                            .space
+                            .elements()
+                            .find(|w| w.wl_surface().as_deref() == Some(&fs))
+                            .cloned()
+                    })
+                    .or_else(|| state.space.elements().next().cloned());
+                if let Some(w) = target {
+                    if let Some(t) = w.toplevel() {
+                        t.send_close();
+                    }
+                }
+            }
+        }
+        KeyAction::ReloadConfig => {
+            crate::main_loop::reload_config(state);
+        }
+        KeyAction::Spawn { command, args, env } => {
+            let env: Vec<_> = state.config.global_env.iter().cloned().chain(env).collect();
+            config::spawn_process(&command, &args, &env, wayland_socket);
+        }
+        KeyAction::SendKey {
+            target_app_id,
+            key,
+            mods,
+        } => {
+            // Deferred: the seat keyboard is mid-`input` here.
+            state
+                .handle
+                .insert_idle(move |state| send_key(state, &target_app_id, &key, &mods));
+        }
+        KeyAction::Overview => run_twm_action(state, TwmAction::Expose),
+        KeyAction::GridCols(cols) => run_twm_action(state, TwmAction::SetGridCols(cols)),
+        KeyAction::ToggleScratchpad => run_twm_action(state, TwmAction::ToggleScratchpad),
+        KeyAction::SendToScratchpad => run_twm_action(state, TwmAction::SendToScratchpad),
+        KeyAction::FocusLast => run_twm_action(state, TwmAction::FocusLast),
+        KeyAction::AltTabNext => run_twm_action(state, TwmAction::AltTabNext),
+        KeyAction::AltTabPrev => run_twm_action(state, TwmAction::AltTabPrev),
+        KeyAction::ToggleSticky => run_twm_action(state, TwmAction::ToggleSticky),
+        KeyAction::UndoClose => run_twm_action(state, TwmAction::UndoClose),
+        KeyAction::WorkspaceLeft => run_twm_action(state, TwmAction::WorkspaceLeft),
+        KeyAction::WorkspaceRight => run_twm_action(state, TwmAction::WorkspaceRight),
+        KeyAction::WorkspaceUp => run_twm_action(state, TwmAction::WorkspaceUp),
+        KeyAction::WorkspaceDown => run_twm_action(state, TwmAction::WorkspaceDown),
+    }
+}
+
 // ── pointer motion (absolute) ─────────────────────────────────────────────────
 
 fn handle_pointer_motion_abs(
@@ -295,10 +768,7 @@ fn handle_pointer_motion_abs(
     let pos = event.position_transformed(output_geo.size) + output_geo.loc.to_f64();
     let serial = SCOUNTER.next_serial();
 
-    let under = match state.mouse_mode {
-        MouseMode::Normal => surface_under(&state.space, pos),
-        MouseMode::Insert => None,
-    };
+    let under = pointer_target(state, pos);
 
     let ptr = state.pointer.clone();
     ptr.motion(
@@ -313,44 +783,228 @@ fn handle_pointer_motion_abs(
     ptr.frame(state);
 }
 
+/// What pointer motion and scrolling may reach.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PointerReach {
+    Nothing,
+    /// The lock surface of the output under the pointer.
+    LockSurface,
+    Windows,
+}
+
+/// Like clicks, nothing gets through the TWM lock curtain: the windows
+/// behind it are hidden. Insert mode keeps the pointer to itself, except
+/// on a lock screen.
+fn pointer_reach(twm_locked: bool, session_locked: bool, mode: MouseMode) -> PointerReach {
+    match (twm_locked, session_locked, mode) {
+        (true, _, _) => PointerReach::Nothing,
+        (false, true, _) => PointerReach::LockSurface,
+        (false, false, MouseMode::Normal) => PointerReach::Windows,
+        (false, false, MouseMode::Insert) => PointerReach::Nothing,
+    }
+}
+
+/// `pointer_reach` for the compositor as it is now.
+fn state_pointer_reach(state: &KittyCompositor) -> PointerReach {
+    pointer_reach(
+        state.twm.as_ref().is_some_and(|t| t.locked),
+        state.session_lock.is_locked(),
+        state.mouse_mode,
+    )
+}
+
+/// The surface the pointer is over, within what `pointer_reach` allows.
+fn pointer_target(
+    state: &KittyCompositor,
+    pos: Point<f64, Logical>,
+) -> Option<(WlSurface, Point<f64, Logical>)> {
+    match state_pointer_reach(state) {
+        PointerReach::Nothing => None,
+        PointerReach::LockSurface => {
+            let output = state.space.output_under(pos).next()?;
+            let origin = state.space.output_geometry(output)?.loc.to_f64();
+            let lock = state.session_lock.surface_for(output)?;
+            Some((lock.wl_surface().clone(), origin))
+        }
+        PointerReach::Windows => surface_under(&state.space, pos),
+    }
+}
+
 // ── pointer motion (relative) ─────────────────────────────────────────────────
 
-fn handle_pointer_motion(
-    state: &mut KittyCompositor,
-    event: <LibinputInputBackend as smithay::backend::input::InputBackend>::PointerMotionEvent,
+/// What an active pointer constraint on the focused surface does to motion.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MotionConstraint {
+    None,
+    /// zwp_locked_pointer_v1: the pointer stays put.
+    Locked,
+    /// zwp_confined_pointer_v1: the pointer stays inside this global rect.
+    Confined(Rectangle<f64, Logical>),
+}
+
+/// Where a relative motion by `delta` from `current` takes the pointer, or
+/// None when it must not move at all. Relative motion events are sent either
+/// way — a locked pointer is exactly what games read them for.
+pub fn constrained_motion(
+    current: Point<f64, Logical>,
+    delta: Point<f64, Logical>,
+    constraint: MotionConstraint,
+) -> Option<Point<f64, Logical>> {
+    let pos = current + delta;
+    match constraint {
+        MotionConstraint::None => Some(pos),
+        MotionConstraint::Locked => None,
+        MotionConstraint::Confined(r) => Some(Point::from((
+            pos.x.clamp(r.loc.x, r.loc.x + (r.size.w - 1.0).max(0.0)),
+            pos.y.clamp(r.loc.y, r.loc.y + (r.size.h - 1.0).max(0.0)),
+        ))),
+    }
+}
+
+/// Where the events of one pointer motion go: the seat's pointer in the
+/// compositor, a recording in tests.
+pub trait MotionSink {
+    /// `wl_pointer.motion` to `location`.
+    fn motion(&mut self, location: Point<f64, Logical>);
+    /// `zwp_relative_pointer_v1.relative_motion`.
+    fn relative_motion(&mut self, event: RelativeMotionEvent);
+    fn frame(&mut self);
+}
+
+/// Send one libinput relative motion to `sink`: absolute motion unless the
+/// pointer is locked, then the raw deltas as relative motion, then a frame
+/// for both. Relative motion goes out whether or not a constraint is
+/// active, so clients can read raw input without locking the pointer.
+pub fn send_motion(
+    sink: &mut impl MotionSink,
+    current: Point<f64, Logical>,
+    constraint: MotionConstraint,
+    delta: Point<f64, Logical>,
+    delta_unaccel: Point<f64, Logical>,
+    utime: u64,
 ) {
-    let mut pos = state.pointer.current_location() + event.delta();
-    if let Some(geo) = state
-        .space
-        .outputs()
-        .next()
-        .and_then(|o| state.space.output_geometry(o))
-    {
-        pos.x = pos
-            .x
-            .clamp(geo.loc.x as f64, (geo.loc.x + geo.size.w) as f64);
-        pos.y = pos
-            .y
-            .clamp(geo.loc.y as f64, (geo.loc.y + geo.size.h) as f64);
+    if let Some(location) = constrained_motion(current, delta, constraint) {
+        sink.motion(location);
     }
-    let serial = SCOUNTER.next_serial();
+    sink.relative_motion(RelativeMotionEvent {
+        delta,
+        delta_unaccel,
+        utime,
+    });
+    sink.frame();
+}
 
-    let under = match state.mouse_mode {
-        MouseMode::Normal => surface_under(&state.space, pos),
-        MouseMode::Insert => None,
+/// The active constraint on the surface under the pointer. A constraint
+/// only holds while the pointer is inside its region.
+fn active_constraint(
+    ptr: &PointerHandle<KittyCompositor>,
+    under: Option<&(WlSurface, Point<f64, Logical>)>,
+) -> MotionConstraint {
+    let Some((surface, origin)) = under else {
+        return MotionConstraint::None;
     };
+    let local = (ptr.current_location() - *origin).to_i32_round();
+    let mut out = MotionConstraint::None;
+    with_pointer_constraint(surface, ptr, |constraint| {
+        let Some(constraint) = constraint.filter(|c| c.is_active()) else {
+            return;
+        };
+        if let Some(region) = constraint.region() {
+            if !region.contains(local) {
+                return;
+            }
+        }
+        out = match &*constraint {
+            PointerConstraint::Locked(_) => MotionConstraint::Locked,
+            PointerConstraint::Confined(_) => {
+                let bounds = match constraint.region() {
+                    Some(region) => region
+                        .rects
+                        .iter()
+                        .filter(|(kind, _)| matches!(kind, RectangleKind::Add))
+                        .map(|(_, r)| *r)
+                        .reduce(|a, b| a.merge(b)),
+                    None => None,
+                }
+                .unwrap_or_else(|| bbox_from_surface_tree(surface, (0, 0)));
+                let mut bounds = bounds.to_f64();
+                bounds.loc += *origin;
+                MotionConstraint::Confined(bounds)
+            }
+        };
+    });
+    out
+}
 
+fn handle_pointer_motion(
+    state: &mut KittyCompositor,
+    event: <LibinputInputBackend as smithay::backend::input::InputBackend>::PointerMotionEvent,
+) {
     let ptr = state.pointer.clone();
-    ptr.motion(
+    let current = ptr.current_location();
+    let under = pointer_target(state, current);
+    let constraint = active_constraint(&ptr, under.as_ref());
+    let mut sink = SeatMotion {
         state,
+        ptr,
         under,
-        &MotionEvent {
-            location: pos,
-            serial,
-            time: event.time_msec(),
-        },
+        time: event.time_msec(),
+    };
+    send_motion(
+        &mut sink,
+        current,
+        constraint,
+        event.delta(),
+        event.delta_unaccel(),
+        event.time(),
     );
-    ptr.frame(state);
+}
+
+/// The seat's pointer as a `MotionSink`. `under` follows the absolute
+/// motion, so the relative event goes to the surface the pointer ends on.
+struct SeatMotion<'a> {
+    state: &'a mut KittyCompositor,
+    ptr: PointerHandle<KittyCompositor>,
+    under: Option<(WlSurface, Point<f64, Logical>)>,
+    time: u32,
+}
+
+impl MotionSink for SeatMotion<'_> {
+    fn motion(&mut self, mut pos: Point<f64, Logical>) {
+        if let Some(geo) = self
+            .state
+            .space
+            .outputs()
+            .next()
+            .and_then(|o| self.state.space.output_geometry(o))
+        {
+            pos.x = pos
+                .x
+                .clamp(geo.loc.x as f64, (geo.loc.x + geo.size.w) as f64);
+            pos.y = pos
+                .y
+                .clamp(geo.loc.y as f64, (geo.loc.y + geo.size.h) as f64);
+        }
+        self.under = pointer_target(self.state, pos);
+        self.ptr.motion(
+            self.state,
+            self.under.clone(),
+            &MotionEvent {
+                location: pos,
+                serial: SCOUNTER.next_serial(),
+                time: self.time,
+            },
+        );
+    }
+
+    fn relative_motion(&mut self, event: RelativeMotionEvent) {
+        self.ptr
+            .relative_motion(self.state, self.under.clone(), &event);
+    }
+
+    fn frame(&mut self) {
+        self.ptr.frame(self.state);
+    }
 }
 
 // ── pointer button ────────────────────────────────────────────────────────────
@@ -361,9 +1015,37 @@ fn handle_pointer_button(
 ) {
     let serial = SCOUNTER.next_serial();
     let btn_state = wl_pointer::ButtonState::from(event.state());
+    if btn_state == wl_pointer::ButtonState::Pressed {
+        state.key_taps.interrupt();
+    }
+
+    if state.twm.as_ref().is_some_and(|t| t.locked) {
+        return;
+    }
+    // Session locked: the click goes to the lock surface, never to chrome.
+    let locked = state.session_lock.is_locked();
+    let mode = if locked {
+        MouseMode::Normal
+    } else {
+        state.mouse_mode
+    };
 
-    match state.mouse_mode {
+    match mode {
         MouseMode::Normal => {
+            // Clicks on TWM chrome (bar tabs) are handled here, not forwarded.
+            if btn_state == wl_pointer::ButtonState::Pressed && !locked {
+                let pos = state.pointer.current_location();
+                let hit = state
+                    .twm
+                    .as_ref()
+                    .and_then(|t| t.click_at(pos.x.max(0.0) as u32, pos.y.max(0.0) as u32));
+                if let Some(id) = hit {
+                    state
+                        .handle
+                        .insert_idle(move |state| run_twm_action(state, TwmAction::UiClick(id)));
+                    return;
+                }
+            }
             let ptr = state.pointer.clone();
             ptr.button(
                 state,
@@ -412,7 +1094,7 @@ fn handle_pointer_axis(
     state: &mut KittyCompositor,
     event: <LibinputInputBackend as smithay::backend::input::InputBackend>::PointerAxisEvent,
 ) {
-    if state.mouse_mode == MouseMode::Insert {
+    if state_pointer_reach(state) == PointerReach::Nothing {
         return;
     }
 
@@ -435,3 +1117,470 @@ fn handle_pointer_axis(
     ptr.axis(state, frame);
     ptr.frame(state);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swipe_fires_at_threshold_once() {
+        let mut g = GestureTracker::new(GestureConfig::default());
+        g.begin(3);
+        assert_eq!(g.update(-60.0, 0.0, 3), None);
+        assert_eq!(g.update(-39.5, 0.0, 3), None);
+        assert_eq!(g.update(-0.5, 0.0, 3), Some(TwmAction::NextWorkspace));
+        assert_eq!(g.update(-200.0, 0.0, 3), None);
+
+        g.begin(3);
+        assert_eq!(g.update(100.0, 0.0, 3), Some(TwmAction::PrevWorkspace));
+
+        // Mostly vertical, or the wrong finger count: nothing.
+        g.begin(3);
+        assert_eq!(g.update(100.0, 150.0, 3), None);
+        g.begin(4);
+        assert_eq!(g.update(500.0, 0.0, 4), None);
+    }
+
+    #[test]
+    fn lock_curtain_keeps_the_pointer_off_windows() {
+        use MouseMode::{Insert, Normal};
+        assert_eq!(pointer_reach(false, false, Normal), PointerReach::Windows);
+        assert_eq!(pointer_reach(false, false, Insert), PointerReach::Nothing);
+        // Behind the curtain the windows are hidden: no focus, no scrolling.
+        assert_eq!(pointer_reach(true, false, Normal), PointerReach::Nothing);
+        assert_eq!(pointer_reach(true, true, Normal), PointerReach::Nothing);
+        assert_eq!(
+            pointer_reach(false, true, Insert),
+            PointerReach::LockSurface
+        );
+    }
+
+    #[test]
+    fn release_binds_need_a_lone_tap() {
+        let (sup, ret) = (xkb::Keycode::new(133), xkb::Keycode::new(36));
+        let mut taps = TapTracker::default();
+        taps.press(sup);
+        assert!(taps.release(sup));
+
+        // Super+Return: neither release is a tap of Super.
+        taps.press(sup);
+        taps.press(ret);
+        assert!(taps.release(ret));
+        assert!(!taps.release(sup));
+
+        // Super held through a click.
+        taps.press(sup);
+        taps.interrupt();
+        assert!(!taps.release(sup));
+    }
+
+    #[test]
+    fn pinch_cycles_layout() {
+        let mut g = GestureTracker::new(GestureConfig::default());
+        g.begin(3);
+        assert_eq!(g.pinch(1.1, 3), None);
+        assert_eq!(g.pinch(0.75, 3), Some(TwmAction::NextLayout));
+        assert_eq!(g.pinch(0.5, 3), None);
+    }
+
+    const KEYMAP: &str = r#"xkb_keymap {
+    xkb_keycodes "test" { <AC01> = 38; };
+    xkb_types "test" { };
+    xkb_compat "test" { };
+    xkb_symbols "test" { key <AC01> { [ a ] }; };
+};
+"#;
+
+    const CTRL_C_KEYMAP: &str = r#"xkb_keymap {
+    xkb_keycodes "test" { <LCTL> = 37; <AB03> = 54; };
+    xkb_types "test" { };
+    xkb_compat "test" { };
+    xkb_symbols "test" {
+        key <LCTL> { [ Control_L ] };
+        key <AB03> { [ c, C ] };
+    };
+};
+"#;
+
+    const ALT_KEYMAP: &str = r#"xkb_keymap {
+    xkb_keycodes "test" { <LALT> = 64; <RALT> = 108; };
+    xkb_types "test" {
+        type "ONE_LEVEL" { modifiers = none; level_name[Level1] = "Any"; };
+    };
+    xkb_compat "test" {
+        interpret Any+AnyOf(all) { action = SetMods(modifiers=modMapMods,clearLocks); };
+    };
+    xkb_symbols "test" {
+        key <LALT> { [ Alt_L ] };
+        key <RALT> { [ Alt_R ] };
+        modifier_map Mod1 { <LALT>, <RALT> };
+    };
+};
+"#;
+
+    #[test]
+    fn modifier_as_super_remaps_left_alt() {
+        let keymap = compile_keymap(ALT_KEYMAP).unwrap();
+        let source = keymap.get_as_string(xkb::KEYMAP_FORMAT_TEXT_V1);
+        let remapped = compile_keymap(&super_remap(&source, Modifier::Alt).unwrap()).unwrap();
+        let (lalt, ralt) = (xkb::Keycode::new(64), xkb::Keycode::new(108));
+        let held = |key| {
+            let mut st = xkb::State::new(&remapped);
+            st.update_key(key, xkb::KeyDirection::Down);
+            let active = |name| st.mod_name_is_active(name, xkb::STATE_MODS_EFFECTIVE);
+            (active(xkb::MOD_NAME_LOGO), active(xkb::MOD_NAME_ALT))
+        };
+        assert_eq!(
+            xkb::State::new(&remapped).key_get_one_sym(lalt),
+            xkb::Keysym::Super_L
+        );
+        assert_eq!(held(lalt), (true, false));
+        assert_eq!(held(ralt), (false, true));
+        assert_eq!(
+            super_remap(&source, Modifier::Super).as_deref(),
+            Some(&*source)
+        );
+    }
+
+    #[test]
+    fn send_key_ctrl_c_sequence() {
+        let keymap = compile_keymap(CTRL_C_KEYMAP).unwrap();
+        let (ctrl, c) = (xkb::Keycode::new(37), xkb::Keycode::new(54));
+        assert_eq!(
+            key_sequence(&keymap, "c", &["ctrl".into()]),
+            Some(vec![
+                (ctrl, KeyState::Pressed),
+                (c, KeyState::Pressed),
+                (c, KeyState::Released),
+                (ctrl, KeyState::Released),
+            ])
+        );
+        assert_eq!(key_sequence(&keymap, "c", &["hyper".into()]), None);
+        assert_eq!(key_sequence(&keymap, "q", &[]), None);
+    }
+
+    const SUPER_CTRL_C_KEYMAP: &str = r#"xkb_keymap {
+    xkb_keycodes "test" { <LCTL> = 37; <AB03> = 54; <LWIN> = 133; };
+    xkb_types "test" {
+        type "ONE_LEVEL" { modifiers = none; level_name[Level1] = "Any"; };
+    };
+    xkb_compat "test" {
+        interpret Any+AnyOf(all) { action = SetMods(modifiers=modMapMods,clearLocks); };
+    };
+    xkb_symbols "test" {
+        key <LCTL> { [ Control_L ] };
+        key <AB03> { [ c ] };
+        key <LWIN> { [ Super_L ] };
+        modifier_map Control { <LCTL> };
+        modifier_map Mod4 { <LWIN> };
+    };
+};
+"#;
+
+    mod seat {
+        use super::*;
+        use smithay::{
+            delegate_compositor, delegate_seat,
+            input::SeatState,
+            reexports::wayland_server::{
+                backend::{ClientData, ClientId, DisconnectReason},
+                Client, Display,
+            },
+            wayland::compositor::{CompositorClientState, CompositorHandler, CompositorState},
+        };
+        use std::{os::unix::net::UnixStream, sync::Arc};
+        use wayland_client::{
+            protocol::{wl_compositor, wl_keyboard, wl_registry, wl_seat, wl_surface},
+            Connection, Dispatch, EventQueue, QueueHandle, WEnum,
+        };
+
+        /// A seat and surfaces, without the DRM session `KittyCompositor`
+        /// can't be built without.
+        pub struct Server {
+            compositor_state: CompositorState,
+            seat_state: SeatState<Self>,
+            pub surfaces: Vec<WlSurface>,
+        }
+
+        impl CompositorHandler for Server {
+            fn compositor_state(&mut self) -> &mut CompositorState {
+                &mut self.compositor_state
+            }
+
+            fn client_compositor_state<'a>(&self, client: &'a Client) -> &'a CompositorClientState {
+                &client.get_data::<TestClient>().unwrap().0
+            }
+
+            fn new_surface(&mut self, surface: &WlSurface) {
+                self.surfaces.push(surface.clone());
+            }
+
+            fn commit(&mut self, _: &WlSurface) {}
+        }
+
+        impl SeatHandler for Server {
+            type KeyboardFocus = WlSurface;
+            type PointerFocus = WlSurface;
+            type TouchFocus = WlSurface;
+
+            fn seat_state(&mut self) -> &mut SeatState<Self> {
+                &mut self.seat_state
+            }
+        }
+
+        delegate_compositor!(Server);
+        delegate_seat!(Server);
+
+        #[derive(Default)]
+        struct TestClient(CompositorClientState);
+
+        impl ClientData for TestClient {
+            fn initialized(&self, _: ClientId) {}
+            fn disconnected(&self, _: ClientId, _: DisconnectReason) {}
+        }
+
+        /// The `send_key` target: keeps the depressed modifiers its
+        /// wl_keyboard was last told, and what they were when each key
+        /// went down.
+        #[derive(Default)]
+        pub struct Target {
+            compositor: Option<wl_compositor::WlCompositor>,
+            seat: Option<wl_seat::WlSeat>,
+            pub mods: u32,
+            pub pressed: Vec<(u32, u32)>,
+        }
+
+        impl Dispatch<wl_registry::WlRegistry, ()> for Target {
+            fn event(
+                t: &mut Self,
+                registry: &wl_registry::WlRegistry,
+                event: wl_registry::Event,
+                _: &(),
+                _: &Connection,
+                qh: &QueueHandle<Self>,
+            ) {
+                if let wl_registry::Event::Global {
+                    name, interface, ..
+                } = event
+                {
+                    match interface.as_str() {
+                        "wl_compositor" => t.compositor = Some(registry.bind(name, 4, qh, ())),
+                        "wl_seat" => t.seat = Some(registry.bind(name, 1, qh, ())),
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        impl Dispatch<wl_keyboard::WlKeyboard, ()> for Target {
+            fn event(
+                t: &mut Self,
+                _: &wl_keyboard::WlKeyboard,
+                event: wl_keyboard::Event,
+                _: &(),
+                _: &Connection,
+                _: &QueueHandle<Self>,
+            ) {
+                match event {
+                    wl_keyboard::Event::Modifiers { mods_depressed, .. } => t.mods = mods_depressed,
+                    wl_keyboard::Event::Key {
+                        key,
+                        state: WEnum::Value(wl_keyboard::KeyState::Pressed),
+                        ..
+                    } => t.pressed.push((key, t.mods)),
+                    _ => {}
+                }
+            }
+        }
+
+        wayland_client::delegate_noop!(Target: ignore wl_compositor::WlCompositor);
+        wayland_client::delegate_noop!(Target: ignore wl_seat::WlSeat);
+        wayland_client::delegate_noop!(Target: ignore wl_surface::WlSurface);
+
+        /// Let the server handle what the client sent, then the client what
+        /// the server sent back.
+        pub fn pump(
+            display: &mut Display<Server>,
+            server: &mut Server,
+            (conn, queue): (&Connection, &mut EventQueue<Target>),
+            target: &mut Target,
+        ) {
+            conn.flush().unwrap();
+            display.dispatch_clients(server).unwrap();
+            display.flush_clients().unwrap();
+            if let Some(guard) = conn.prepare_read() {
+                guard.read().unwrap();
+            }
+            queue.dispatch_pending(target).unwrap();
+        }
+
+        /// A server with `keymap` on its seat, and a client with a surface
+        /// and a keyboard.
+        #[allow(clippy::type_complexity)]
+        pub fn connect(
+            keymap: &str,
+        ) -> (
+            Display<Server>,
+            Server,
+            Seat<Server>,
+            Connection,
+            EventQueue<Target>,
+            Target,
+        ) {
+            let mut display = Display::<Server>::new().unwrap();
+            let dh = display.handle();
+            let mut server = Server {
+                compositor_state: CompositorState::new::<Server>(&dh),
+                seat_state: SeatState::new(),
+                surfaces: Vec::new(),
+            };
+            let mut seat = server.seat_state.new_wl_seat(&dh, "seat0");
+            let kbd = seat.add_keyboard(XkbConfig::default(), 200, 25).unwrap();
+            kbd.set_keymap_from_string(&mut server, keymap.to_owned())
+                .unwrap();
+
+            let (client_end, server_end) = UnixStream::pair().unwrap();
+            dh.clone()
+                .insert_client(server_end, Arc::new(TestClient::default()))
+                .unwrap();
+            let conn = Connection::from_socket(client_end).unwrap();
+            let mut queue = conn.new_event_queue();
+            let qh = queue.handle();
+            let mut target = Target::default();
+            conn.display().get_registry(&qh, ());
+            pump(&mut display, &mut server, (&conn, &mut queue), &mut target);
+
+            let (Some(compositor), Some(wl_seat)) = (&target.compositor, &target.seat) else {
+                panic!("no compositor or seat advertised");
+            };
+            compositor.create_surface(&qh, ());
+            wl_seat.get_keyboard(&qh, ());
+            pump(&mut display, &mut server, (&conn, &mut queue), &mut target);
+            (display, server, seat, conn, queue, target)
+        }
+    }
+
+    #[test]
+    fn send_key_hides_the_held_bind_modifier() {
+        const CTRL: u32 = 1 << 2;
+        const LOGO: u32 = 1 << 6;
+        let (mut display, mut server, seat, conn, mut queue, mut target) =
+            seat::connect(SUPER_CTRL_C_KEYMAP);
+        let surface = server.surfaces[0].clone();
+
+        // Super is still down from the bind that fired send_key.
+        let kbd = seat.get_keyboard().unwrap();
+        kbd.input::<(), _>(
+            &mut server,
+            xkb::Keycode::new(133),
+            KeyState::Pressed,
+            SCOUNTER.next_serial(),
+            0,
+            |_, _, _| FilterResult::Forward,
+        );
+        assert!(kbd.modifier_state().logo);
+
+        let keymap = compile_keymap(SUPER_CTRL_C_KEYMAP).unwrap();
+        let seq = key_sequence(&keymap, "c", &["ctrl".into()]).unwrap();
+        inject_keys(&mut server, &seat, surface, &seq, 0);
+        seat::pump(&mut display, &mut server, (&conn, &mut queue), &mut target);
+
+        // wl_keyboard keys are evdev codes: xkb's minus 8.
+        assert_eq!(target.pressed, vec![(29, 0), (46, CTRL)]);
+        assert_eq!(target.mods, LOGO);
+        assert!(kbd.modifier_state().logo);
+        assert_eq!(kbd.current_focus(), None);
+    }
+
+    #[test]
+    fn locked_pointer_only_moves_relatively() {
+        let at = Point::from((400.0, 300.0));
+        let delta = Point::from((25.0, -10.0));
+        assert_eq!(
+            constrained_motion(at, delta, MotionConstraint::None),
+            Some(Point::from((425.0, 290.0)))
+        );
+        // Locked: no absolute movement; handle_pointer_motion still sends
+        // the delta as relative motion.
+        assert_eq!(
+            constrained_motion(at, delta, MotionConstraint::Locked),
+            None
+        );
+
+        let window = Rectangle::from_loc_and_size((100.0, 100.0), (310.0, 200.0));
+        assert_eq!(
+            constrained_motion(at, delta, MotionConstraint::Confined(window)),
+            Some(Point::from((409.0, 290.0)))
+        );
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Sent {
+        Motion(Point<f64, Logical>),
+        Relative(Point<f64, Logical>, Point<f64, Logical>, u64),
+        Frame,
+    }
+
+    impl MotionSink for Vec<Sent> {
+        fn motion(&mut self, location: Point<f64, Logical>) {
+            self.push(Sent::Motion(location));
+        }
+        fn relative_motion(&mut self, e: RelativeMotionEvent) {
+            self.push(Sent::Relative(e.delta, e.delta_unaccel, e.utime));
+        }
+        fn frame(&mut self) {
+            self.push(Sent::Frame);
+        }
+    }
+
+    #[test]
+    fn motion_sends_absolute_and_relative() {
+        let at = Point::from((400.0, 300.0));
+        let delta = Point::from((6.0, 2.0));
+        let raw = Point::from((3.0, 1.0));
+        let sent = |constraint| {
+            let mut sent = Vec::new();
+            send_motion(&mut sent, at, constraint, delta, raw, 1_500);
+            sent
+        };
+        let relative = Sent::Relative(delta, raw, 1_500);
+
+        assert_eq!(
+            sent(MotionConstraint::None),
+            [
+                Sent::Motion(Point::from((406.0, 302.0))),
+                relative,
+                Sent::Frame
+            ]
+        );
+        // A locked pointer doesn't move, but still reports raw motion.
+        let relative = Sent::Relative(delta, raw, 1_500);
+        assert_eq!(sent(MotionConstraint::Locked), [relative, Sent::Frame]);
+        // A confined one stops at the edge; the raw delta is unclipped.
+        let region = Rectangle::from_loc_and_size((0.0, 0.0), (403.0, 301.0));
+        let relative = Sent::Relative(delta, raw, 1_500);
+        assert_eq!(
+            sent(MotionConstraint::Confined(region)),
+            [
+                Sent::Motion(Point::from((402.0, 300.0))),
+                relative,
+                Sent::Frame
+            ]
+        );
+    }
+
+    #[test]
+    fn xkb_file_keymap() {
+        let path = std::env::temp_dir().join(format!("trixie-{}.xkb", std::process::id()));
+        std::fs::write(&path, KEYMAP).unwrap();
+        let source = load_xkb_file(&path);
+        std::fs::remove_file(&path).ok();
+
+        let keymap = compile_keymap(&source.expect("keymap should load")).unwrap();
+        assert_eq!(
+            keymap.key_get_syms_by_level(xkb::Keycode::new(38), 0, 0),
+            &[xkb::Keysym::a]
+        );
+        assert!(load_xkb_file(Path::new("/nonexistent/trixie.xkb")).is_none());
+    }
+}
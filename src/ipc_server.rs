@@ -0,0 +1,598 @@
+// ipc_server.rs — general-purpose Unix socket IPC for scripts
+//
+// The compositor listens on $XDG_RUNTIME_DIR/trixie.sock. A client connects,
+// sends one newline-terminated JSON command and reads one JSON reply line;
+// `trixie-ipc` does exactly that from the shell.
+//
+// ── Command format ────────────────────────────────────────────────────────────
+//
+//   { "op": "list_windows" }
+//   { "op": "focus",    "app_id": "foot" }
+//   { "op": "dispatch", "action": "NextLayout" }      any TWM Action;
+//   { "op": "dispatch", "action": { "Workspace": 3 } } data variants as maps
+//   { "op": "get_config" }
+//   { "op": "list_marks" }
+//   { "op": "clipboard_history" }
+//   { "op": "clipboard_set", "index": 2 }            0 = newest
+//   { "op": "screenshot", "output": "DP-1", "path": "/tmp/shot.png" }
+//   { "op": "record_output", "output": "DP-1", "path": "/tmp/out.mp4" }
+//   { "op": "record_stop" }
+//   { "op": "mpris", "command": "next" }           play / pause / next / previous;
+//   { "op": "mpris", "command": "play", "player": "spotify" }   default mpris_player
+//
+// ── Response format ───────────────────────────────────────────────────────────
+//
+//   { "ok": true, "windows": [ { "app_id": "foot", "title": "~", "workspace": 1 }, … ] }
+//   { "ok": true, "marks": [ { "mark": "term", "pane_id": 3, "app_id": "foot" }, … ] }
+//   { "ok": true, "config": { … } }
+//   { "ok": true, "entries": [ { "mime_type": "text/plain", "size": 2, "text": "hi" }, … ] }
+//   { "ok": true }                                     (everything else)
+//   { "ok": false, "error": "no window with app_id 'foo'" }
+//
+// `workspace` is 1-based and null for windows without a TWM pane. Clipboard
+// entries carry `text`, `bytes` (an array of numbers) or, past
+// `clipboard_max_bytes`, only a `hash`.
+//
+// `screenshot` replies as soon as the capture is queued; the PNG is written
+// after the output's next frame. `record_output` pipes every frame to ffmpeg
+// until `record_stop`; only one output is recorded at a time.
+
+use std::{
+    cell::Cell,
+    io::{ErrorKind, Read, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+    rc::Rc,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use smithay::{
+    desktop::{Space, Window},
+    reexports::calloop::{
+        generic::Generic,
+        timer::{TimeoutAction, Timer},
+        Interest, LoopHandle, Mode, PostAction, RegistrationToken,
+    },
+    utils::SERIAL_COUNTER as SCOUNTER,
+    wayland::{compositor::with_states, shell::xdg::XdgToplevelSurfaceData},
+};
+
+use crate::clipboard::ClipboardEntry;
+use crate::config::Config;
+use crate::mpris::MprisCommand;
+use crate::recorder::Recorder;
+use crate::state::{Capture, KittyCompositor};
+use crate::twm_drop_in::{Action as TwmAction, PaneContent};
+
+/// A client gets this long to send its command before it is dropped, so a
+/// stalled script can't pile up connections.
+const READ_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Longest command accepted; a client sending more is dropped.
+const MAX_COMMAND_LEN: usize = 64 * 1024;
+
+// ── socket path ───────────────────────────────────────────────────────────────
+
+pub fn socket_path() -> PathBuf {
+    let runtime = std::env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| "/tmp".into());
+    PathBuf::from(runtime).join("trixie.sock")
+}
+
+// ── wire types ────────────────────────────────────────────────────────────────
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum IpcRequest {
+    ListWindows,
+    Focus { app_id: String },
+    Dispatch { action: TwmAction },
+    GetConfig,
+    ListMarks,
+    ClipboardHistory,
+    ClipboardSet { index: usize },
+    Screenshot { output: String, path: PathBuf },
+    RecordOutput { output: String, path: PathBuf },
+    RecordStop,
+    Mpris(MprisRequest),
+}
+
+/// A missing `player` falls back to `mpris_player`, then the first player.
+#[derive(Debug, Deserialize)]
+pub struct MprisRequest {
+    pub command: MprisCommand,
+    pub player: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WindowInfo {
+    pub app_id: Option<String>,
+    pub title: Option<String>,
+    pub workspace: Option<usize>,
+}
+
+/// A `Mark` and the TWM pane carrying it. `app_id` is the pane's title for
+/// panes not showing a client.
+#[derive(Debug, Serialize)]
+pub struct MarkInfo {
+    pub mark: String,
+    pub pane_id: u32,
+    pub app_id: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum IpcReply {
+    Windows {
+        ok: bool,
+        windows: Vec<WindowInfo>,
+    },
+    Marks {
+        ok: bool,
+        marks: Vec<MarkInfo>,
+    },
+    Config {
+        ok: bool,
+        config: Box<Config>,
+    },
+    Clipboard {
+        ok: bool,
+        entries: Vec<ClipboardEntry>,
+    },
+    Done {
+        ok: bool,
+    },
+    Err {
+        ok: bool,
+        error: String,
+    },
+}
+
+impl IpcReply {
+    fn done() -> Self {
+        Self::Done { ok: true }
+    }
+
+    fn err(msg: impl Into<String>) -> Self {
+        Self::Err {
+            ok: false,
+            error: msg.into(),
+        }
+    }
+}
+
+// ── server ────────────────────────────────────────────────────────────────────
+
+/// The listening socket, registered with the event loop. Dropping it removes
+/// the source and the socket file.
+pub struct IpcServer {
+    path: PathBuf,
+    token: RegistrationToken,
+    handle: LoopHandle<'static, KittyCompositor>,
+}
+
+impl IpcServer {
+    pub fn bind(handle: &LoopHandle<'static, KittyCompositor>) -> std::io::Result<Self> {
+        let path = socket_path();
+        // Remove stale socket from a previous run.
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path)?;
+        listener.set_nonblocking(true)?;
+
+        let token = handle
+            .insert_source(
+                Generic::new(listener, Interest::READ, Mode::Level),
+                |_, listener, state| {
+                    loop {
+                        match listener.accept() {
+                            Ok((stream, _)) => {
+                                if let Err(e) = accept(state, stream) {
+                                    tracing::debug!("IPC client: {e}");
+                                }
+                            }
+                            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                            Err(e) => {
+                                tracing::warn!("IPC accept error: {e}");
+                                break;
+                            }
+                        }
+                    }
+                    Ok(PostAction::Continue)
+                },
+            )
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
+        tracing::info!("IPC socket: {}", path.display());
+
+        Ok(Self {
+            path,
+            token,
+            handle: handle.clone(),
+        })
+    }
+}
+
+impl Drop for IpcServer {
+    fn drop(&mut self) {
+        self.handle.remove(self.token);
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Watch a new client until its command line is in, then run it and send
+/// the reply. The stream stays non-blocking, so a slow client never holds
+/// up the event loop.
+fn accept(state: &mut KittyCompositor, stream: UnixStream) -> std::io::Result<()> {
+    stream.set_nonblocking(true)?;
+    let handle = state.handle.clone();
+    let read = Rc::new(Cell::new(false));
+    let mut buf = Vec::new();
+    let token = handle
+        .insert_source(Generic::new(stream, Interest::READ, Mode::Level), {
+            let read = read.clone();
+            move |_, stream, state| {
+                let line = match read_command(stream, &mut buf) {
+                    Ok(Some(line)) => line,
+                    Ok(None) => return Ok(PostAction::Continue),
+                    Err(e) => {
+                        tracing::debug!("IPC client: {e}");
+                        read.set(true);
+                        return Ok(PostAction::Remove);
+                    }
+                };
+                read.set(true);
+                if let Some(reply) = run_command(state, &line) {
+                    match stream.try_clone() {
+                        Ok(stream) => send_reply(state, stream, reply),
+                        Err(e) => tracing::debug!("IPC client: {e}"),
+                    }
+                }
+                Ok(PostAction::Remove)
+            }
+        })
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+    handle
+        .insert_source(Timer::from_duration(READ_TIMEOUT), move |_, _, state| {
+            if !read.get() {
+                tracing::debug!("IPC client: no command within {READ_TIMEOUT:?}");
+                state.handle.remove(token);
+            }
+            TimeoutAction::Drop
+        })
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+    Ok(())
+}
+
+/// Add whatever has arrived on `stream` to `buf`. The command once its line
+/// is complete, or the client hung up after it; None while more is to come.
+fn read_command(mut stream: &UnixStream, buf: &mut Vec<u8>) -> std::io::Result<Option<String>> {
+    let mut chunk = [0u8; 4096];
+    loop {
+        if let Some(end) = buf.iter().position(|&b| b == b'\n') {
+            return Ok(Some(String::from_utf8_lossy(&buf[..end]).into_owned()));
+        }
+        if buf.len() > MAX_COMMAND_LEN {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                "command too long",
+            ));
+        }
+        match stream.read(&mut chunk) {
+            Ok(0) if buf.is_empty() => return Err(ErrorKind::UnexpectedEof.into()),
+            Ok(0) => return Ok(Some(String::from_utf8_lossy(buf).into_owned())),
+            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(None),
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Run command `line`; the reply to send, or None for a blank line.
+fn run_command(state: &mut KittyCompositor, line: &str) -> Option<Vec<u8>> {
+    let line = line.trim();
+    if line.is_empty() {
+        return None;
+    }
+    let reply = match serde_json::from_str::<IpcRequest>(line) {
+        Ok(req) => handle_request(state, req),
+        Err(e) => IpcReply::err(format!("parse error: {e}")),
+    };
+    let mut json = serde_json::to_string(&reply).unwrap_or_default();
+    json.push('\n');
+    Some(json.into_bytes())
+}
+
+/// Write `reply` to `stream` as fast as the client drains it, then close.
+fn send_reply(state: &KittyCompositor, stream: UnixStream, reply: Vec<u8>) {
+    let mut sent = 0;
+    let inserted = state.handle.insert_source(
+        Generic::new(stream, Interest::WRITE, Mode::Level),
+        move |_, stream, _| loop {
+            match (&**stream).write(&reply[sent..]) {
+                Ok(0) => return Ok(PostAction::Remove),
+                Ok(n) => {
+                    sent += n;
+                    if sent == reply.len() {
+                        return Ok(PostAction::Remove);
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(PostAction::Continue),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => {
+                    tracing::debug!("IPC client: {e}");
+                    return Ok(PostAction::Remove);
+                }
+            }
+        },
+    );
+    if let Err(e) = inserted {
+        tracing::warn!("IPC client: {e}");
+    }
+}
+
+// ── command dispatch ──────────────────────────────────────────────────────────
+
+fn handle_request(state: &mut KittyCompositor, req: IpcRequest) -> IpcReply {
+    match req {
+        IpcRequest::ListWindows => IpcReply::Windows {
+            ok: true,
+            windows: list_windows(state),
+        },
+        IpcRequest::Focus { app_id } => focus(state, &app_id),
+        IpcRequest::Dispatch { action } => {
+            if state.twm.is_none() {
+                return IpcReply::err("TWM is not running");
+            }
+            crate::input::run_twm_action(state, action);
+            IpcReply::done()
+        }
+        IpcRequest::GetConfig => IpcReply::Config {
+            ok: true,
+            config: Box::new(state.config.clone()),
+        },
+        IpcRequest::ListMarks => match &state.twm {
+            Some(twm) => IpcReply::Marks {
+                ok: true,
+                marks: twm
+                    .marks()
+                    .into_iter()
+                    .map(|(mark, pane_id, app_id)| MarkInfo {
+                        mark,
+                        pane_id,
+                        app_id,
+                    })
+                    .collect(),
+            },
+            None => IpcReply::err("TWM is not running"),
+        },
+        IpcRequest::ClipboardHistory => IpcReply::Clipboard {
+            ok: true,
+            entries: state.clipboard.history().to_vec(),
+        },
+        IpcRequest::ClipboardSet { index } => match state.clipboard.set_active(index) {
+            Some(entry) if entry.data().is_some() => {
+                let entry = entry.clone();
+                state.offer_clipboard(entry);
+                IpcReply::done()
+            }
+            Some(_) => IpcReply::err("entry was too large to keep"),
+            None => IpcReply::err(format!("no clipboard entry {index}")),
+        },
+        IpcRequest::Screenshot { output, path } => {
+            if find_output(&state.space, &output).is_none() {
+                return IpcReply::err(format!("no output '{output}'"));
+            }
+            state.captures.push(Capture::Png { output, path });
+            // Wakes an idle-suspended compositor; resuming queues the frame.
+            state.resume_render();
+            state.render_all();
+            IpcReply::done()
+        }
+        IpcRequest::RecordOutput { output, path } => {
+            let Some(o) = find_output(&state.space, &output) else {
+                return IpcReply::err(format!("no output '{output}'"));
+            };
+            let Some(mode) = o.current_mode() else {
+                return IpcReply::err(format!("output '{output}' has no mode"));
+            };
+            // Stop any running recording before ffmpeg opens the new file.
+            state.recorder = None;
+            match Recorder::start(&output, (mode.size.w, mode.size.h), &path) {
+                Ok(recorder) => {
+                    state.recorder = Some(recorder);
+                    IpcReply::done()
+                }
+                Err(e) => IpcReply::err(format!("could not start ffmpeg: {e}")),
+            }
+        }
+        IpcRequest::RecordStop => match state.recorder.take() {
+            Some(_) => IpcReply::done(),
+            None => IpcReply::err("not recording"),
+        },
+        IpcRequest::Mpris(MprisRequest { command, player }) => {
+            // Fire-and-forget: the D-Bus call runs on its own thread.
+            let player = player.or_else(|| state.config.mpris_player.clone());
+            crate::mpris::send(player, command);
+            IpcReply::done()
+        }
+    }
+}
+
+/// The mapped output with connector name `name`, e.g. `DP-1`.
+fn find_output(space: &Space<Window>, name: &str) -> Option<smithay::output::Output> {
+    space.outputs().find(|o| o.name() == name).cloned()
+}
+
+/// `app_id` and `title` of a toplevel, as last committed.
+fn toplevel_ids(window: &Window) -> (Option<String>, Option<String>) {
+    let Some(toplevel) = window.toplevel() else {
+        return (None, None);
+    };
+    with_states(toplevel.wl_surface(), |states| {
+        states
+            .data_map
+            .get::<XdgToplevelSurfaceData>()
+            .and_then(|d| d.lock().ok())
+            .map(|d| (d.app_id.clone(), d.title.clone()))
+            .unwrap_or_default()
+    })
+}
+
+fn list_windows(state: &KittyCompositor) -> Vec<WindowInfo> {
+    state
+        .space
+        .elements()
+        .map(|w| {
+            let (app_id, title) = toplevel_ids(w);
+            let workspace = app_id
+                .as_deref()
+                .zip(state.twm.as_ref())
+                .and_then(|(id, twm)| twm.workspace_of_app(id))
+                .map(|i| i + 1);
+            WindowInfo {
+                app_id,
+                title,
+                workspace,
+            }
+        })
+        .collect()
+}
+
+/// Give keyboard focus to the window with `app_id`. A window with a TWM
+/// pane is focused through the TWM, so its lock and focus lock apply; any
+/// other window is raised and focused directly.
+fn focus(state: &mut KittyCompositor, app_id: &str) -> IpcReply {
+    let window = state
+        .space
+        .elements()
+        .find(|w| toplevel_ids(w).0.as_deref() == Some(app_id))
+        .cloned();
+    let Some(window) = window else {
+        return IpcReply::err(format!("no window with app_id '{app_id}'"));
+    };
+    if state.session_lock.is_locked() || state.twm.as_ref().is_some_and(|t| t.locked) {
+        return IpcReply::err("session is locked");
+    }
+    if state
+        .twm
+        .as_ref()
+        .is_some_and(|t| t.workspace_of_app(app_id).is_some())
+    {
+        crate::input::run_twm_action(state, TwmAction::FocusApp(app_id.to_owned()));
+        let focused = state
+            .twm
+            .as_ref()
+            .and_then(|t| t.focused_content())
+            .is_some_and(|c| matches!(c, PaneContent::Embedded { app_id: a } if a == app_id));
+        return match focused {
+            true => IpcReply::done(),
+            false => IpcReply::err("focus is locked to another pane"),
+        };
+    }
+    state.space.raise_element(&window, true);
+    if let (Some(kbd), Some(toplevel)) = (state.seat.get_keyboard(), window.toplevel()) {
+        kbd.set_focus(
+            state,
+            Some(toplevel.wl_surface().clone()),
+            SCOUNTER.next_serial(),
+        );
+    }
+    state.render_all();
+    IpcReply::done()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_are_read_without_blocking() {
+        let (mut client, server) = UnixStream::pair().unwrap();
+        server.set_nonblocking(true).unwrap();
+        let mut buf = Vec::new();
+        assert_eq!(read_command(&server, &mut buf).unwrap(), None);
+        client.write_all(br#"{"op":"list_"#).unwrap();
+        assert_eq!(read_command(&server, &mut buf).unwrap(), None);
+        client.write_all(b"windows\"}\n").unwrap();
+        assert_eq!(
+            read_command(&server, &mut buf).unwrap().as_deref(),
+            Some(r#"{"op":"list_windows"}"#)
+        );
+
+        // A client may hang up instead of ending the line.
+        let (mut client, server) = UnixStream::pair().unwrap();
+        client.write_all(br#"{"op":"record_stop"}"#).unwrap();
+        drop(client);
+        let mut buf = Vec::new();
+        assert_eq!(
+            read_command(&server, &mut buf).unwrap().as_deref(),
+            Some(r#"{"op":"record_stop"}"#)
+        );
+        let (client, server) = UnixStream::pair().unwrap();
+        drop(client);
+        assert!(read_command(&server, &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn requests_parse() {
+        let req: IpcRequest = serde_json::from_str(r#"{"op":"list_windows"}"#).unwrap();
+        assert!(matches!(req, IpcRequest::ListWindows));
+        let req: IpcRequest = serde_json::from_str(r#"{"op":"focus","app_id":"foot"}"#).unwrap();
+        assert!(matches!(req, IpcRequest::Focus { app_id } if app_id == "foot"));
+        let req: IpcRequest =
+            serde_json::from_str(r#"{"op":"dispatch","action":"NextLayout"}"#).unwrap();
+        assert!(matches!(
+            req,
+            IpcRequest::Dispatch {
+                action: TwmAction::NextLayout
+            }
+        ));
+        let req: IpcRequest = serde_json::from_str(r#"{"op":"clipboard_set","index":2}"#).unwrap();
+        assert!(matches!(req, IpcRequest::ClipboardSet { index: 2 }));
+        let req: IpcRequest =
+            serde_json::from_str(r#"{"op":"screenshot","output":"DP-1","path":"/tmp/shot.png"}"#)
+                .unwrap();
+        assert!(matches!(
+            req,
+            IpcRequest::Screenshot { output, path }
+                if output == "DP-1" && path == std::path::Path::new("/tmp/shot.png")
+        ));
+        let req: IpcRequest = serde_json::from_str(r#"{"op":"list_marks"}"#).unwrap();
+        assert!(matches!(req, IpcRequest::ListMarks));
+        let req: IpcRequest = serde_json::from_str(r#"{"op":"record_stop"}"#).unwrap();
+        assert!(matches!(req, IpcRequest::RecordStop));
+        let req: IpcRequest =
+            serde_json::from_str(r#"{"op":"mpris","command":"previous","player":"mpv"}"#).unwrap();
+        assert!(matches!(
+            req,
+            IpcRequest::Mpris(MprisRequest {
+                command: MprisCommand::Previous,
+                player: Some(p),
+            }) if p == "mpv"
+        ));
+        let req: IpcRequest = serde_json::from_str(r#"{"op":"mpris","command":"pause"}"#).unwrap();
+        assert!(matches!(
+            req,
+            IpcRequest::Mpris(MprisRequest {
+                command: MprisCommand::Pause,
+                player: None,
+            })
+        ));
+        assert!(serde_json::from_str::<IpcRequest>(r#"{"op":"reboot"}"#).is_err());
+    }
+
+    #[test]
+    fn config_reply_omits_lock_pin() {
+        let config = Config {
+            lock_pin: Some("$2b$10$secret".into()),
+            ..Config::default()
+        };
+        let json = serde_json::to_string(&IpcReply::Config {
+            ok: true,
+            config: Box::new(config),
+        })
+        .unwrap();
+        assert!(json.starts_with(r#"{"ok":true,"config":{"#));
+        assert!(!json.contains("secret"));
+    }
+}
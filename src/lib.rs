@@ -1,8 +1,10 @@
+mod bar_modules;
 mod box_drawing;
 pub mod config;
 mod embedded_ipc;
 mod embedded_window;
 mod font;
+pub mod mpris;
 pub mod pixelui;
 pub mod shader_config;
 pub mod shader_ipc;
@@ -1,15 +1,27 @@
 // main.rs — entry point, event loop, exec, config reload
 
+mod autostart;
 mod backend;
+mod bar_modules;
 mod box_drawing;
+mod clipboard;
 mod config;
+mod cursor;
 mod embedded_ipc;
 mod embedded_window;
 mod font;
+mod foreign_toplevel;
+mod gamma_control;
 mod handlers;
 mod input;
+mod ipc_server;
+mod mpris;
+mod output_management;
 mod pixelui;
+mod recorder;
 mod render;
+mod screencopy;
+mod session_lock;
 mod shader_config;
 mod shader_ipc;
 mod shader_pass;
@@ -24,14 +36,17 @@ pub mod main_loop {
     pub use super::reload_config;
 }
 
-use config::{Config, ExecEntry, VsyncMode};
+use config::{Config, VsyncMode};
+use foreign_toplevel::ForeignToplevelManagerState;
+use gamma_control::GammaControlManagerState;
+use output_management::OutputManagementState;
+use screencopy::ScreencopyManagerState;
 use shader_pass::ShaderPass;
 use state::{ClientState, KittyCompositor, MouseMode};
 
 use notify::{EventKind, RecursiveMode, Watcher};
 use std::{
     collections::HashMap,
-    process::Command,
     sync::{
         atomic::{AtomicBool, Ordering},
         mpsc, Arc,
@@ -51,22 +66,37 @@ use smithay::{
     },
     input::{keyboard::XkbConfig, SeatState},
     reexports::{
-        calloop::{generic::Generic, EventLoop, Interest, Mode as CalloopMode, PostAction},
+        calloop::{
+            channel::{channel, Event as ChannelEvent},
+            generic::Generic,
+            signals::{Signal, Signals},
+            timer::{TimeoutAction, Timer},
+            EventLoop, Interest, Mode as CalloopMode, PostAction,
+        },
         input::Libinput,
         wayland_server::Display as WlDisplay,
     },
     utils::Clock,
     wayland::{
         compositor::CompositorState,
+        cursor_shape::CursorShapeManagerState,
         dmabuf::DmabufState,
+        fractional_scale::FractionalScaleManagerState,
+        idle_inhibit::IdleInhibitManagerState,
+        input_method::InputMethodManagerState,
         output::OutputManagerState,
+        pointer_constraints::PointerConstraintsState,
+        relative_pointer::RelativePointerManagerState,
         selection::{data_device::DataDeviceState, primary_selection::PrimarySelectionState},
+        session_lock::SessionLockManagerState,
         shell::{
             wlr_layer::WlrLayerShellState,
             xdg::{decoration::XdgDecorationState, XdgShellState},
         },
         shm::ShmState,
         socket::ListeningSocketSource,
+        text_input::TextInputManagerState,
+        viewporter::ViewporterState,
     },
 };
 
@@ -82,29 +112,132 @@ use crate::embedded_window::EmbeddedManager;
 pub fn reload_config(state: &mut KittyCompositor) {
     tracing::info!("Reloading config…");
     let new = Config::load();
+    let diff = Config::diff(&state.config, &new);
 
     state.config.terminal = new.terminal;
     state.config.background_color = new.background_color;
+    // Changed output blocks apply to the connected outputs straight away.
+    if diff.outputs_changed {
+        let changed_outputs: Vec<(String, config::OutputConfig)> = new
+            .outputs
+            .iter()
+            .filter(|(name, cfg)| state.config.outputs.get(*name) != Some(*cfg))
+            .map(|(name, cfg)| (name.clone(), cfg.clone()))
+            .collect();
+        state.config.outputs = new.outputs.clone();
+        for (name, cfg) in changed_outputs {
+            if let Err(e) = backend::configure_output(state, &name, &cfg) {
+                tracing::debug!("output {name}: not applied on reload: {e}");
+            }
+        }
+    }
     state.config.target_hz = new.target_hz;
     state.config.vsync = new.vsync;
-    state.config.font = new.font.clone();
-    state.config.keybinds = new.keybinds;
-    state.config.window_rules = new.window_rules;
+    state.config.vrr = new.vrr;
+    state.config.idle_timeout = new.idle_timeout;
+    state.config.direct_scanout = new.direct_scanout;
+    state.config.clipboard_history = new.clipboard_history;
+    state.config.clipboard_max_bytes = new.clipboard_max_bytes;
+    state.clipboard.set_capacity(new.clipboard_history);
+    state.config.gesture = new.gesture;
+    state.gestures = input::GestureTracker::new(new.gesture);
+    state.config.debug_atlas_overlay = new.debug_atlas_overlay;
+    state.config.debug_shader_timing = new.debug_shader_timing;
+    state.shader_pass.log_timing = new.debug_shader_timing;
+    if new.mpris != state.config.mpris || new.mpris_player != state.config.mpris_player {
+        state.mpris = new
+            .mpris
+            .then(|| mpris::MprisWatcher::spawn(new.mpris_player.clone()));
+    }
+    state.config.mpris = new.mpris;
+    state.config.input_method = new.input_method;
+    state
+        .input_method_enabled
+        .store(new.input_method, Ordering::Relaxed);
+    state.config.mpris_player = new.mpris_player.clone();
+    state.config.mpris_max_title_len = new.mpris_max_title_len;
+    state.config.animation_profile = new.animation_profile;
+    state.config.anim_duration_ms = new.anim_duration_ms;
+    state.config.anim_ease = new.anim_ease;
+    state.config.anim_enabled = new.anim_enabled;
+    state.config.workspace_anim_duration_ms = new.workspace_anim_duration_ms;
+    apply_animation(state);
+    if new.cursor_theme != state.config.cursor_theme || new.cursor_size != state.config.cursor_size
+    {
+        state.cursor_theme = cursor::CursorTheme::load(&new.cursor_theme, new.cursor_size);
+        pixelui::overlay_element::clear_images();
+    }
+    state.config.cursor_theme = new.cursor_theme.clone();
+    state.config.cursor_size = new.cursor_size;
+    state.config.lock_pin = new.lock_pin.clone();
+    state.config.float_snap = new.float_snap;
+    state.config.no_gap_when_single = new.no_gap_when_single;
+    state.config.focus_history_len = new.focus_history_len;
+    state.config.workspace_grid_cols = new.workspace_grid_cols;
+    state.config.undo_depth = new.undo_depth;
+    state.config.bar = new.bar.clone();
+    state.config.layer_shell_namespaces = new.layer_shell_namespaces.clone();
+    if let Some(twm) = &mut state.twm {
+        twm.lock_pin = new.lock_pin;
+        twm.float_snap_px = new.float_snap;
+        twm.set_no_gap_when_single(new.no_gap_when_single);
+        twm.focus_history_len = new.focus_history_len;
+        twm.workspace_grid_cols = new.workspace_grid_cols;
+        twm.undo_depth = new.undo_depth;
+        twm.bar_modules = new.bar.modules();
+    }
+    if diff.font_changed {
+        state.config.font = new.font.clone();
+        state.config.font_size_pt = new.font_size_pt;
+        backend::reload_pixel_ui(state);
+    }
+    if diff.keybinds_changed {
+        state.config.keybinds = new.keybinds;
+    }
+    if diff.window_rules_changed {
+        state.config.window_rules = new.window_rules;
+    }
+    state.config.default_layout = new.default_layout;
+    state.config.workspace_layouts = new.workspace_layouts;
+    state.config.workspace_rules = new.workspace_rules;
+    if let Some(twm) = &mut state.twm {
+        twm.set_workspace_layouts(state.config.default_layout, &state.config.workspace_layouts);
+        twm.set_workspace_rules(&state.config.workspace_rules);
+    }
+    for (name, ch) in &new.icon_map {
+        pixelui::Icons::register(name, *ch);
+    }
+    state.config.icon_map = new.icon_map;
+    state.config.global_env = new.global_env;
     state.config.exec = new.exec.clone();
     run_exec(state);
 
-    // Reload shader registry and recompile any changed sources.
-    state.config.shaders = new.shaders;
-    let changed = state.config.shaders.hot_reload();
-    state.shader_pass.sync_programs(&state.config.shaders);
-    for name in changed {
-        state
-            .shader_pass
-            .recompile_shader(&state.config.shaders, &name);
+    // Swap the shader registry and recompile programs whose source or
+    // uniforms differ; untouched programs are kept.
+    if diff.shaders_changed {
+        let changed: Vec<String> =
+            new.shaders
+                .entries
+                .iter()
+                .filter(|e| {
+                    state.config.shaders.entries.iter().any(|o| {
+                        o.name == e.name && (o.source != e.source || o.uniforms != e.uniforms)
+                    })
+                })
+                .map(|e| e.name.clone())
+                .collect();
+        state.config.shaders = new.shaders;
+        state.shader_pass.sync_programs(&state.config.shaders);
+        for name in changed {
+            state
+                .shader_pass
+                .recompile_shader(&state.config.shaders, &name);
+        }
     }
 
-    if new.keyboard.repeat_delay != state.config.keyboard.repeat_delay
-        || new.keyboard.repeat_rate != state.config.keyboard.repeat_rate
+    if diff.keyboard_changed
+        && (new.keyboard.repeat_delay != state.config.keyboard.repeat_delay
+            || new.keyboard.repeat_rate != state.config.keyboard.repeat_rate)
     {
         if let Some(kbd) = state.seat.get_keyboard() {
             kbd.change_repeat_info(
@@ -113,6 +246,11 @@ pub fn reload_config(state: &mut KittyCompositor) {
             );
         }
     }
+    // An xkb_file is always re-read: its contents may have changed on disk
+    // even when the keyboard section did not.
+    if diff.keyboard_changed || new.keyboard.xkb_file.is_some() {
+        input::apply_keymap(state, &new.keyboard);
+    }
     state.config.keyboard = new.keyboard;
 
     if new.seat_name != state.config.seat_name {
@@ -123,14 +261,33 @@ pub fn reload_config(state: &mut KittyCompositor) {
         );
     }
 
+    // Outputs may have been rescaled, or windows moved between them.
+    state.update_fractional_scales();
+    state.refresh_output_heads();
+
     tracing::info!("Config reloaded OK");
 }
 
+// ── animation ─────────────────────────────────────────────────────────────────
+
+fn apply_animation(state: &mut KittyCompositor) {
+    let anim = state.config.animation();
+    if let Some(twm) = &mut state.twm {
+        twm.anim_duration_ms = anim.duration_ms;
+        twm.anim_ease = anim.ease;
+        twm.anim_enabled = anim.enabled;
+        twm.workspace_anim_ms = state
+            .config
+            .workspace_anim_duration_ms
+            .unwrap_or(twm_drop_in::WORKSPACE_ANIM_MS);
+    }
+}
+
 // ── exec ──────────────────────────────────────────────────────────────────────
 
 fn run_exec(state: &KittyCompositor) {
     for entry in &state.config.exec {
-        spawn_exec_entry(entry, &state.wayland_socket);
+        autostart::spawn(entry, &state.config.global_env, &state.wayland_socket);
     }
 }
 
@@ -140,20 +297,55 @@ fn run_exec_once(state: &mut KittyCompositor) {
     }
     state.exec_once_done = true;
     let entries: Vec<_> = state.config.exec_once.clone();
-    for entry in &entries {
-        spawn_exec_entry(entry, &state.wayland_socket);
+    for entry in entries {
+        if let Some(child) =
+            autostart::spawn(&entry, &state.config.global_env, &state.wayland_socket)
+        {
+            autostart::watch(&state.handle, entry, child, 0);
+        }
     }
 }
 
-fn spawn_exec_entry(entry: &ExecEntry, wayland_socket: &str) {
-    let bin = util::expand_tilde(&entry.command);
-    tracing::info!("exec: {bin} {:?}", entry.args);
-    if let Err(e) = Command::new(&bin)
-        .args(&entry.args)
-        .env("WAYLAND_DISPLAY", wayland_socket)
-        .spawn()
-    {
-        tracing::warn!("exec failed ({bin}): {e}");
+// ── shader watcher ────────────────────────────────────────────────────────────
+
+/// Send the path of every `.glsl` finished being written or moved into
+/// `dir`. None, silently, when `dir` doesn't exist.
+fn watch_shaders(
+    dir: &std::path::Path,
+    tx: smithay::reexports::calloop::channel::Sender<std::path::PathBuf>,
+) -> Option<notify::RecommendedWatcher> {
+    use notify::event::{AccessKind, AccessMode, ModifyKind, RenameMode};
+
+    if !dir.is_dir() {
+        return None;
+    }
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        let Ok(ev) = res else { return };
+        let written = matches!(
+            ev.kind,
+            EventKind::Access(AccessKind::Close(AccessMode::Write))
+                | EventKind::Modify(ModifyKind::Name(RenameMode::To | RenameMode::Both))
+        );
+        // A rename carries [from, to]; the new name is last.
+        if let Some(path) = ev
+            .paths
+            .last()
+            .filter(|p| written && p.extension().is_some_and(|e| e == "glsl"))
+        {
+            let _ = tx.send(path.clone());
+        }
+    })
+    .map_err(|e| tracing::warn!("Could not create shader watcher: {e}"))
+    .ok()?;
+    match watcher.watch(dir, RecursiveMode::NonRecursive) {
+        Ok(()) => {
+            tracing::info!("Watching shaders dir for changes: {}", dir.display());
+            Some(watcher)
+        }
+        Err(e) => {
+            tracing::warn!("Could not watch shaders dir {}: {e}", dir.display());
+            None
+        }
     }
 }
 
@@ -199,6 +391,19 @@ fn main() {
     }
 
     let mut event_loop: EventLoop<'static, KittyCompositor> = EventLoop::try_new().unwrap();
+
+    // ── SIGHUP → config reload ────────────────────────────────────────────────
+    // Signals blocks SIGHUP on this thread; threads spawned later inherit the
+    // mask, so this has to come before any of them or they'd take the signal
+    // (and its default action, exit) instead.
+    event_loop
+        .handle()
+        .insert_source(Signals::new(&[Signal::SIGHUP]).unwrap(), |_, _, state| {
+            tracing::info!("SIGHUP — reloading config");
+            reload_config(state);
+        })
+        .unwrap();
+
     let display: WlDisplay<KittyCompositor> = WlDisplay::new().unwrap();
     let dh = display.handle();
 
@@ -250,7 +455,15 @@ fn main() {
                     if let Err(e) = b.drm.activate(false) {
                         tracing::error!("Failed to activate DRM: {e}");
                     }
+                    // No vblank arrives for a frame queued before the switch.
+                    for s in b.surfaces.values_mut() {
+                        s.pending_frame = false;
+                    }
                 }
+                // Outputs blanked by the idle timer come back with the session.
+                state.set_dpms_all(true);
+                state.suspended = false;
+                state.last_activity = std::time::Instant::now();
                 state.handle.insert_idle(|state| state.render_all());
             }
         })
@@ -295,6 +508,26 @@ fn main() {
 
     let start_time = std::time::Instant::now();
 
+    let primary_selection_state = PrimarySelectionState::new::<KittyCompositor>(&dh);
+    let data_control_state =
+        handlers::data_control_state::<KittyCompositor>(&dh, &primary_selection_state);
+    let gamma_control_state = GammaControlManagerState::new::<KittyCompositor>(&dh);
+    let foreign_toplevel_state = ForeignToplevelManagerState::new::<KittyCompositor>(&dh);
+    let output_management_state = OutputManagementState::new::<KittyCompositor>(&dh);
+    // Only an IME the user opted into may bind input-method-v2; see
+    // `Config::input_method`.
+    let input_method_enabled = Arc::new(AtomicBool::new(config.input_method));
+    let input_method_state = InputMethodManagerState::new::<KittyCompositor, _>(&dh, {
+        let enabled = Arc::clone(&input_method_enabled);
+        move |_| enabled.load(Ordering::Relaxed)
+    });
+    let cursor_theme = cursor::CursorTheme::load(&config.cursor_theme, config.cursor_size);
+    let gestures = input::GestureTracker::new(config.gesture);
+    let clipboard = clipboard::ClipboardManager::load(
+        &clipboard::ClipboardManager::cache_path(),
+        config.clipboard_history,
+    );
+
     let mut state = KittyCompositor {
         display_handle: dh.clone(),
         running: Arc::new(AtomicBool::new(true)),
@@ -308,7 +541,20 @@ fn main() {
         output_manager_state: OutputManagerState::new_with_xdg_output::<KittyCompositor>(&dh),
         seat_state,
         data_device_state: DataDeviceState::new::<KittyCompositor>(&dh),
-        primary_selection_state: PrimarySelectionState::new::<KittyCompositor>(&dh),
+        primary_selection_state,
+        data_control_state,
+        gamma_control_state,
+        foreign_toplevel_state,
+        output_management_state,
+        session_lock_state: SessionLockManagerState::new::<KittyCompositor, _>(&dh, |_| true),
+        session_lock: Default::default(),
+        fractional_scale_state: FractionalScaleManagerState::new::<KittyCompositor>(&dh),
+        viewporter_state: ViewporterState::new::<KittyCompositor>(&dh),
+        pointer_constraints_state: PointerConstraintsState::new::<KittyCompositor>(&dh),
+        relative_pointer_state: RelativePointerManagerState::new::<KittyCompositor>(&dh),
+        text_input_state: TextInputManagerState::new::<KittyCompositor>(&dh),
+        input_method_state,
+        input_method_enabled,
         xdg_shell_state: XdgShellState::new::<KittyCompositor>(&dh),
         layer_shell_state: WlrLayerShellState::new::<KittyCompositor>(&dh),
         xdg_decoration_state: XdgDecorationState::new::<KittyCompositor>(&dh),
@@ -317,7 +563,11 @@ fn main() {
         seat,
         pointer,
         cursor_status: CursorImageStatus::default_named(),
+        cursor_shape_state: CursorShapeManagerState::new::<KittyCompositor>(&dh),
         mouse_mode: MouseMode::Normal,
+        gestures,
+        key_taps: Default::default(),
+        send_key_keymap: None,
         session,
         backends: Default::default(),
         primary_gpu,
@@ -326,11 +576,56 @@ fn main() {
         exec_once_done: false,
         shader_pass: ShaderPass::new(start_time),
         start_time,
+        suspended: false,
+        last_activity: start_time,
+        idle_inhibit_state: IdleInhibitManagerState::new::<KittyCompositor>(&dh),
+        idle_inhibitors: Default::default(),
         embedded: EmbeddedManager::default(),
         embed_ipc: EmbedIpcServer::bind(),
         twm: Some(twm_drop_in::TwmState::new(80, 24)),
         unclaimed_toplevels: HashMap::new(),
+        mpris: None,
+        clipboard,
+        screencopy_state: ScreencopyManagerState::new::<KittyCompositor>(&dh),
+        captures: Vec::new(),
+        recorder: None,
+        cursor_theme,
+        startup_spinner: Some(pixelui::SpinnerState::new()),
+        ipc: None,
     };
+    if state.config.mpris {
+        state.mpris = Some(mpris::MprisWatcher::spawn(
+            state.config.mpris_player.clone(),
+        ));
+    }
+    for (name, ch) in &state.config.icon_map {
+        pixelui::Icons::register(name, *ch);
+    }
+    if let Some(twm) = &mut state.twm {
+        twm.set_workspace_layouts(state.config.default_layout, &state.config.workspace_layouts);
+        twm.set_workspace_rules(&state.config.workspace_rules);
+    }
+    apply_animation(&mut state);
+    if let Some(twm) = &mut state.twm {
+        twm.lock_pin = state.config.lock_pin.clone();
+        twm.float_snap_px = state.config.float_snap;
+        twm.set_no_gap_when_single(state.config.no_gap_when_single);
+        twm.focus_history_len = state.config.focus_history_len;
+        twm.workspace_grid_cols = state.config.workspace_grid_cols;
+        twm.undo_depth = state.config.undo_depth;
+        twm.bar_modules = state.config.bar.modules();
+        for name in &state.config.chrome_layers {
+            match name.as_str() {
+                "clock" => twm.register_layer(Box::new(pixelui::ClockLayer::new())),
+                "cpu" => twm.register_layer(Box::new(pixelui::CpuLayer::new())),
+                _ => {}
+            }
+        }
+    }
+    let keyboard = state.config.keyboard.clone();
+    if keyboard.xkb_file.is_some() || keyboard.modifier_as_super.is_some() {
+        input::apply_keymap(&mut state, &keyboard);
+    }
 
     // ── udev ──────────────────────────────────────────────────────────────────
     let udev_backend = UdevBackend::new(state.session.seat()).unwrap();
@@ -352,8 +647,45 @@ fn main() {
     }
 
     // Compile shaders now that the GL context exists.
+    state.shader_pass.log_timing = state.config.debug_shader_timing;
     state.shader_pass.sync_programs(&state.config.shaders);
 
+    // Recompile the shaders registered with a .glsl in the shaders directory
+    // as soon as it is written; the full config reload below then leaves
+    // that directory alone.
+    let shaders_dir = Config::config_dir().join("shaders");
+    let (shader_tx, shader_rx) = channel::<std::path::PathBuf>();
+    let shader_watcher = watch_shaders(&shaders_dir, shader_tx);
+    event_loop
+        .handle()
+        .insert_source(shader_rx, |ev, _, state| {
+            let ChannelEvent::Msg(path) = ev else { return };
+            let mut changed = Vec::new();
+            for entry in state.config.shaders.entries_at(&path) {
+                match entry.reload() {
+                    Ok(true) => changed.push(entry.name.clone()),
+                    Ok(false) => {}
+                    Err(e) => tracing::warn!("Shader '{}' reload error: {e}", entry.name),
+                }
+            }
+            for name in changed {
+                state
+                    .shader_pass
+                    .recompile_shader(&state.config.shaders, &name);
+            }
+        })
+        .unwrap();
+
+    // The last session's newest clipboard entry stays pasteable.
+    if let Some(entry) = state.clipboard.history().first().cloned() {
+        state.offer_clipboard(entry);
+    }
+
+    // IPC socket for scripts (`trixie-ipc`).
+    state.ipc = ipc_server::IpcServer::bind(&state.handle)
+        .map_err(|e| tracing::warn!("Could not bind IPC socket: {e}"))
+        .ok();
+
     // IPC socket for the ratatui shader manager.
     {
         use std::io::{BufRead, BufReader, Write};
@@ -392,6 +724,7 @@ fn main() {
                                                 cmd,
                                                 &mut state.config.shaders,
                                                 &mut recompile,
+                                                state.shader_pass.shader_timings(),
                                             );
                                             for name in recompile {
                                                 state
@@ -425,10 +758,11 @@ fn main() {
                     let _ = backend::add_gpu(state, &state.display_handle.clone(), node, &path);
                 }
             }
-            UdevEvent::Changed { .. } => {}
+            // A monitor was plugged or unplugged; tools see it as a head.
+            UdevEvent::Changed { .. } => state.refresh_output_heads(),
             UdevEvent::Removed { device_id } => {
                 if let Ok(node) = DrmNode::from_dev_id(device_id) {
-                    state.backends.remove(&node);
+                    state.remove_backend(node);
                 }
             }
         })
@@ -442,6 +776,72 @@ fn main() {
         )
         .unwrap();
 
+    // ── idle suspend ──────────────────────────────────────────────────────────
+    // Polled once a second so `idle_timeout` changes apply on config reload.
+    // An idle inhibitor (video playback) holds the timer off entirely.
+    event_loop
+        .handle()
+        .insert_source(
+            Timer::from_duration(Duration::from_secs(1)),
+            |_, _, state| {
+                if let Some(secs) = state.config.idle_timeout {
+                    if !state.suspended
+                        && !state.is_idle_inhibited()
+                        && state.last_activity.elapsed() >= Duration::from_secs(secs)
+                    {
+                        state.suspend_render();
+                    }
+                }
+                TimeoutAction::ToDuration(Duration::from_secs(1))
+            },
+        )
+        .unwrap();
+
+    // ── battery ───────────────────────────────────────────────────────────────
+    // Read right away, then every `bar.battery_poll_secs` (looked up on each
+    // tick so a reload changes the period).
+    let battery = bar_modules::BatteryModule::default();
+    event_loop
+        .handle()
+        .insert_source(Timer::immediate(), move |_, _, state| {
+            let shown = state
+                .config
+                .bar
+                .modules()
+                .iter()
+                .flatten()
+                .any(|m| m == "battery");
+            if let Some(twm) = &mut state.twm {
+                twm.battery = if shown { battery.read() } else { None };
+            }
+            TimeoutAction::ToDuration(Duration::from_secs(state.config.bar.battery_poll_secs))
+        })
+        .unwrap();
+
+    // ── cpu ───────────────────────────────────────────────────────────────────
+    // Sampled every second while the module is shown; the first sample only
+    // sets the baseline.
+    let mut cpu = bar_modules::CpuModule::default();
+    event_loop
+        .handle()
+        .insert_source(Timer::immediate(), move |_, _, state| {
+            let shown = state
+                .config
+                .bar
+                .modules()
+                .iter()
+                .flatten()
+                .any(|m| m == "cpu");
+            if !shown {
+                cpu = bar_modules::CpuModule::default();
+            }
+            if let Some(twm) = &mut state.twm {
+                twm.cpu = if shown { cpu.read() } else { None };
+            }
+            TimeoutAction::ToDuration(Duration::from_secs(1))
+        })
+        .unwrap();
+
     // ── launch terminal ───────────────────────────────────────────────────────
     let running = state.running.clone();
     let (bin, args) = state.config.terminal_cmd();
@@ -461,6 +861,8 @@ fn main() {
 
     let mut watcher = {
         let tx = reload_tx.clone();
+        let shaders_dir = shaders_dir.clone();
+        let shaders_watched = shader_watcher.is_some();
         notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
             let Ok(ev) = res else { return };
             let is_write = matches!(
@@ -468,10 +870,12 @@ fn main() {
                 EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
             );
             let affects_config = ev.paths.iter().any(|p| {
-                matches!(
-                    p.extension().and_then(|e| e.to_str()),
-                    Some("conf") | Some("glsl")
-                )
+                match p.extension().and_then(|e| e.to_str()) {
+                    Some("conf") => true,
+                    // The shader watcher has those directly in its directory.
+                    Some("glsl") => !(shaders_watched && p.parent() == Some(&*shaders_dir)),
+                    _ => false,
+                }
             });
             if is_write && affects_config {
                 let _ = tx.send(());
@@ -488,6 +892,9 @@ fn main() {
         }
     }
 
+    // Anything that mapped while the compositor was being set up.
+    state.import_windows_into_twm();
+
     // ── event loop ────────────────────────────────────────────────────────────
     let mut dh = state.display_handle.clone();
     while running.load(Ordering::SeqCst) {
@@ -507,4 +914,12 @@ fn main() {
             tracing::warn!("flush_clients: {e}");
         }
     }
+
+    let path = clipboard::ClipboardManager::cache_path();
+    if let Err(e) = state.clipboard.save(&path) {
+        tracing::warn!(
+            "Could not save clipboard history to {}: {e}",
+            path.display()
+        );
+    }
 }
@@ -0,0 +1,204 @@
+// mpris.rs — now-playing status for the TWM bar via MPRIS2 over D-Bus
+//
+// A background thread polls `org.mpris.MediaPlayer2.Player` on the session
+// bus every 2 seconds and stores the result in shared state; the render path
+// only ever reads that state, so a slow or hung player never stalls a frame.
+// Playback commands (play / pause / next / previous) are sent from a
+// short-lived thread for the same reason.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use serde::Deserialize;
+use zbus::{
+    blocking::{fdo::DBusProxy, Connection, Proxy},
+    zvariant::OwnedValue,
+};
+
+const BUS_PREFIX: &str = "org.mpris.MediaPlayer2.";
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+const PLAYER_IFACE: &str = "org.mpris.MediaPlayer2.Player";
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaStatus {
+    pub title: String,
+    pub playing: bool,
+}
+
+impl MediaStatus {
+    /// "▶ title" / "⏸ title", with the title cut to `max_title_len` chars.
+    pub fn bar_label(&self, max_title_len: usize) -> String {
+        let glyph = if self.playing { '▶' } else { '⏸' };
+        let n = self.title.chars().count();
+        if n <= max_title_len {
+            return format!("{glyph} {}", self.title);
+        }
+        let cut: String = self
+            .title
+            .chars()
+            .take(max_title_len.saturating_sub(1))
+            .collect();
+        format!("{glyph} {cut}…")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MprisCommand {
+    Play,
+    Pause,
+    Next,
+    Previous,
+}
+
+impl MprisCommand {
+    fn method(self) -> &'static str {
+        match self {
+            Self::Play => "Play",
+            Self::Pause => "Pause",
+            Self::Next => "Next",
+            Self::Previous => "Previous",
+        }
+    }
+}
+
+// ── watcher ───────────────────────────────────────────────────────────────────
+
+/// Owns the polling thread; dropping it stops the thread after its next tick.
+pub struct MprisWatcher {
+    status: Arc<Mutex<Option<MediaStatus>>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl MprisWatcher {
+    /// `player` restricts polling to one bus name suffix (e.g. "spotify");
+    /// None follows the first player on the bus.
+    pub fn spawn(player: Option<String>) -> Self {
+        let status = Arc::new(Mutex::new(None));
+        let stop = Arc::new(AtomicBool::new(false));
+        let (st, sp) = (status.clone(), stop.clone());
+        std::thread::Builder::new()
+            .name("mpris".into())
+            .spawn(move || {
+                let conn = match Connection::session() {
+                    Ok(c) => c,
+                    Err(e) => {
+                        tracing::warn!("mpris: no session bus: {e}");
+                        return;
+                    }
+                };
+                while !sp.load(Ordering::Relaxed) {
+                    let now = poll(&conn, player.as_deref()).unwrap_or_else(|e| {
+                        tracing::debug!("mpris: poll failed: {e}");
+                        None
+                    });
+                    if let Ok(mut s) = st.lock() {
+                        *s = now;
+                    }
+                    std::thread::sleep(POLL_INTERVAL);
+                }
+            })
+            .ok();
+        Self { status, stop }
+    }
+
+    pub fn status(&self) -> Option<MediaStatus> {
+        self.status.lock().ok()?.clone()
+    }
+}
+
+impl Drop for MprisWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+// ── D-Bus ─────────────────────────────────────────────────────────────────────
+
+fn find_player(conn: &Connection, player: Option<&str>) -> zbus::Result<Option<String>> {
+    let names = DBusProxy::new(conn)?.list_names()?;
+    Ok(names
+        .iter()
+        .map(|n| n.as_str())
+        .filter_map(|n| n.strip_prefix(BUS_PREFIX).map(|suffix| (n, suffix)))
+        .find(|(_, suffix)| {
+            player.is_none_or(|p| *suffix == p || suffix.starts_with(&format!("{p}.")))
+        })
+        .map(|(n, _)| n.to_owned()))
+}
+
+fn player_proxy<'a>(conn: &'a Connection, dest: &'a str) -> zbus::Result<Proxy<'a>> {
+    Proxy::new(conn, dest, OBJECT_PATH, PLAYER_IFACE)
+}
+
+fn poll(conn: &Connection, player: Option<&str>) -> zbus::Result<Option<MediaStatus>> {
+    let Some(dest) = find_player(conn, player)? else {
+        return Ok(None);
+    };
+    let proxy = player_proxy(conn, &dest)?;
+    let metadata: HashMap<String, OwnedValue> = proxy.get_property("Metadata")?;
+    let status: String = proxy.get_property("PlaybackStatus")?;
+    Ok(title_from_metadata(&metadata).map(|title| MediaStatus {
+        title,
+        playing: status == "Playing",
+    }))
+}
+
+fn title_from_metadata(metadata: &HashMap<String, OwnedValue>) -> Option<String> {
+    let v = metadata.get("xesam:title")?;
+    let title = <&str>::try_from(v).ok()?;
+    (!title.is_empty()).then(|| title.to_owned())
+}
+
+/// Send `cmd` to `player` (or the first player) without blocking the caller.
+pub fn send(player: Option<String>, cmd: MprisCommand) {
+    std::thread::spawn(move || {
+        let result = Connection::session().and_then(|conn| {
+            let Some(dest) = find_player(&conn, player.as_deref())? else {
+                return Ok(());
+            };
+            player_proxy(&conn, &dest)?.call_method(cmd.method(), &())?;
+            Ok(())
+        });
+        if let Err(e) = result {
+            tracing::warn!("mpris: {} failed: {e}", cmd.method());
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bar_label_glyph_and_truncation() {
+        let s = MediaStatus {
+            title: "Test Song".into(),
+            playing: true,
+        };
+        assert_eq!(s.bar_label(32), "▶ Test Song");
+        let paused = MediaStatus {
+            playing: false,
+            ..s
+        };
+        assert_eq!(paused.bar_label(5), "⏸ Test…");
+    }
+
+    #[test]
+    fn title_from_xesam_metadata() {
+        let mut md = HashMap::new();
+        assert_eq!(title_from_metadata(&md), None);
+        md.insert(
+            "xesam:title".to_owned(),
+            OwnedValue::try_from(zbus::zvariant::Value::from("Test Song")).unwrap(),
+        );
+        assert_eq!(title_from_metadata(&md).as_deref(), Some("Test Song"));
+    }
+}
@@ -0,0 +1,819 @@
+// output_management.rs — wlr-output-management-unstable-v1 for kanshi / wlr-randr
+//
+// Smithay ships no implementation of this protocol, so this module follows
+// the shape of gamma_control.rs: an `OutputManagementState` owning the
+// global, an `OutputManagementHandler` trait the compositor implements, and a
+// `delegate_output_management!` macro wiring up the dispatch.
+//
+// Every bound manager gets one head per connected connector, named after
+// the connector (`DP-1`), with one mode object per DRM mode. After any
+// change the heads resend what changed and the manager gets `done` with a
+// new serial. A configuration built against an older serial is cancelled;
+// otherwise it is checked against the heads and, for `apply`, handed to the
+// compositor.
+
+use std::{
+    cmp::Reverse,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use smithay::{
+    reexports::{
+        wayland_protocols_wlr::output_management::v1::server::{
+            zwlr_output_configuration_head_v1::{self, ZwlrOutputConfigurationHeadV1},
+            zwlr_output_configuration_v1::{self, ZwlrOutputConfigurationV1},
+            zwlr_output_head_v1::{self, ZwlrOutputHeadV1},
+            zwlr_output_manager_v1::{self, ZwlrOutputManagerV1},
+            zwlr_output_mode_v1::{self, ZwlrOutputModeV1},
+        },
+        wayland_server::{
+            backend::ClientId, Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New,
+            Resource,
+        },
+    },
+    utils::Transform,
+};
+
+use crate::config::{OutputConfig, OutputMode};
+
+// ── heads ─────────────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModeInfo {
+    pub width: i32,
+    pub height: i32,
+    /// mHz.
+    pub refresh: i32,
+    pub preferred: bool,
+}
+
+/// How far off the wanted refresh rate a mode may be: `@60` still picks a
+/// 59.94 Hz mode when there is no 60 Hz one.
+const REFRESH_SLACK_MHZ: i32 = 500;
+
+impl ModeInfo {
+    pub fn as_output_mode(&self) -> OutputMode {
+        OutputMode {
+            width: self.width as u16,
+            height: self.height as u16,
+            refresh: Some(self.refresh as u32),
+        }
+    }
+}
+
+/// Index of the mode `want` picks: same size, and the refresh rate closest
+/// to the one given. Ties go to the preferred mode, then the highest
+/// refresh rate. `backend::find_mode` picks DRM modes the same way.
+pub fn pick_mode(modes: &[ModeInfo], want: OutputMode) -> Option<usize> {
+    modes
+        .iter()
+        .enumerate()
+        .filter(|(_, m)| (m.width, m.height) == (want.width as i32, want.height as i32))
+        .map(|(i, m)| {
+            let off = want.refresh.map_or(0, |r| (m.refresh - r as i32).abs());
+            (i, m, off)
+        })
+        .filter(|&(_, _, off)| off <= REFRESH_SLACK_MHZ)
+        .max_by_key(|&(_, m, off)| (Reverse(off), m.preferred, m.refresh))
+        .map(|(i, _, _)| i)
+}
+
+/// One connector as tools see it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeadInfo {
+    /// Connector name, e.g. `DP-1`.
+    pub name: String,
+    pub description: String,
+    /// Millimetres; (0, 0) when unknown.
+    pub physical_size: (i32, i32),
+    pub modes: Vec<ModeInfo>,
+    pub enabled: bool,
+    /// Index into `modes`.
+    pub current_mode: Option<usize>,
+    pub position: (i32, i32),
+    pub transform: Transform,
+    pub scale: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HeadEvent {
+    Enabled(bool),
+    CurrentMode(usize),
+    Position(i32, i32),
+    Transform(Transform),
+    Scale(f64),
+}
+
+impl HeadInfo {
+    /// The state events a freshly created head needs after its modes.
+    fn announce(&self) -> Vec<HeadEvent> {
+        let mut events = vec![HeadEvent::Enabled(self.enabled)];
+        if self.enabled {
+            events.extend(self.current_mode.map(HeadEvent::CurrentMode));
+            events.push(HeadEvent::Position(self.position.0, self.position.1));
+            events.push(HeadEvent::Transform(self.transform));
+            events.push(HeadEvent::Scale(self.scale));
+        }
+        events
+    }
+
+    /// Same connector with the same modes, so its objects can be kept.
+    fn same_head(&self, other: &HeadInfo) -> bool {
+        self.name == other.name
+            && self.description == other.description
+            && self.physical_size == other.physical_size
+            && self.modes == other.modes
+    }
+
+    /// The change that puts the head back the way it is now: its current
+    /// settings, or None if it is disabled.
+    pub fn as_change(&self) -> Option<OutputConfig> {
+        self.enabled.then(|| OutputConfig {
+            mode: self
+                .current_mode
+                .and_then(|i| self.modes.get(i))
+                .map(ModeInfo::as_output_mode),
+            position: Some(self.position),
+            scale: Some(self.scale),
+            transform: Some(self.transform),
+            ..Default::default()
+        })
+    }
+
+    /// Record `new`; the events for whatever changed. Only `enabled` is
+    /// sent for a disabled head.
+    fn update(&mut self, new: &HeadInfo) -> Vec<HeadEvent> {
+        let old = std::mem::replace(self, new.clone());
+        if old.enabled != new.enabled {
+            return new.announce();
+        }
+        if !new.enabled {
+            return Vec::new();
+        }
+        let mut events = Vec::new();
+        if old.current_mode != new.current_mode {
+            events.extend(new.current_mode.map(HeadEvent::CurrentMode));
+        }
+        if old.position != new.position {
+            events.push(HeadEvent::Position(new.position.0, new.position.1));
+        }
+        if old.transform != new.transform {
+            events.push(HeadEvent::Transform(new.transform));
+        }
+        if old.scale != new.scale {
+            events.push(HeadEvent::Scale(new.scale));
+        }
+        events
+    }
+}
+
+/// `heads` after `changes`: each change enables a head with its settings or
+/// disables it (`None`). None if a head is unknown, a mode is not one the
+/// head offers or a scale is not positive. Heads left out are unchanged.
+pub fn configure_heads(
+    heads: &[HeadInfo],
+    changes: &[(String, Option<OutputConfig>)],
+) -> Option<Vec<HeadInfo>> {
+    let mut heads = heads.to_vec();
+    for (name, cfg) in changes {
+        let head = heads.iter_mut().find(|h| &h.name == name)?;
+        let Some(cfg) = cfg else {
+            head.enabled = false;
+            continue;
+        };
+        head.enabled = true;
+        if let Some(want) = cfg.mode {
+            head.current_mode = Some(pick_mode(&head.modes, want)?);
+        } else if head.current_mode.is_none() {
+            head.current_mode = head.modes.iter().position(|m| m.preferred).or(Some(0));
+        }
+        if let Some(position) = cfg.position {
+            head.position = position;
+        }
+        if let Some(transform) = cfg.transform {
+            head.transform = transform;
+        }
+        if let Some(scale) = cfg.scale {
+            if scale <= 0.0 {
+                return None;
+            }
+            head.scale = scale;
+        }
+    }
+    Some(heads)
+}
+
+fn send(head: &ZwlrOutputHeadV1, modes: &[ZwlrOutputModeV1], events: &[HeadEvent]) {
+    for event in events {
+        match event {
+            HeadEvent::Enabled(on) => head.enabled(*on as i32),
+            HeadEvent::CurrentMode(i) => {
+                if let Some(mode) = modes.get(*i) {
+                    head.current_mode(mode);
+                }
+            }
+            HeadEvent::Position(x, y) => head.position(*x, *y),
+            HeadEvent::Transform(t) => head.transform((*t).into()),
+            HeadEvent::Scale(s) => head.scale(*s),
+        }
+    }
+}
+
+/// The objects one manager holds for one head.
+struct HeadResources {
+    head: ZwlrOutputHeadV1,
+    modes: Vec<ZwlrOutputModeV1>,
+}
+
+impl HeadResources {
+    fn finish(&self) {
+        for mode in &self.modes {
+            mode.finished();
+        }
+        self.head.finished();
+    }
+}
+
+struct HeadEntry {
+    info: HeadInfo,
+    resources: Vec<HeadResources>,
+}
+
+// ── handler ───────────────────────────────────────────────────────────────────
+
+pub trait OutputManagementHandler {
+    fn output_management_state(&mut self) -> &mut OutputManagementState;
+    /// Apply `changes`, which `configure_heads` has already accepted.
+    /// Returns false if any of them failed.
+    fn apply_output_configuration(&mut self, changes: &[(String, Option<OutputConfig>)]) -> bool;
+}
+
+pub struct OutputHeadData {
+    name: String,
+}
+
+/// A mode object is the `index`th DRM mode of head `head`.
+pub struct OutputModeData {
+    head: String,
+    index: usize,
+}
+
+/// Heads named so far, each with its settings or None to disable it.
+type ConfiguredHeads = Vec<(String, Option<Arc<Mutex<OutputConfig>>>)>;
+
+pub struct OutputConfigurationData {
+    serial: u32,
+    heads: Mutex<ConfiguredHeads>,
+    used: AtomicBool,
+}
+
+pub struct OutputConfigurationHeadData {
+    config: Arc<Mutex<OutputConfig>>,
+}
+
+pub struct OutputManagementState {
+    dh: DisplayHandle,
+    serial: u32,
+    managers: Vec<ZwlrOutputManagerV1>,
+    heads: Vec<HeadEntry>,
+}
+
+impl OutputManagementState {
+    pub fn new<D>(dh: &DisplayHandle) -> Self
+    where
+        D: GlobalDispatch<ZwlrOutputManagerV1, ()>
+            + Dispatch<ZwlrOutputManagerV1, ()>
+            + Dispatch<ZwlrOutputHeadV1, OutputHeadData>
+            + Dispatch<ZwlrOutputModeV1, OutputModeData>
+            + Dispatch<ZwlrOutputConfigurationV1, OutputConfigurationData>
+            + Dispatch<ZwlrOutputConfigurationHeadV1, OutputConfigurationHeadData>
+            + OutputManagementHandler
+            + 'static,
+    {
+        dh.create_global::<D, ZwlrOutputManagerV1, _>(1, ());
+        Self {
+            dh: dh.clone(),
+            serial: 0,
+            managers: Vec::new(),
+            heads: Vec::new(),
+        }
+    }
+
+    pub fn heads(&self) -> Vec<HeadInfo> {
+        self.heads.iter().map(|h| h.info.clone()).collect()
+    }
+
+    /// Bring every manager in line with `heads` and send `done` if anything
+    /// changed. Called after outputs are added, removed or reconfigured.
+    pub fn update<D>(&mut self, heads: Vec<HeadInfo>)
+    where
+        D: Dispatch<ZwlrOutputHeadV1, OutputHeadData>
+            + Dispatch<ZwlrOutputModeV1, OutputModeData>
+            + 'static,
+    {
+        let mut changed = false;
+        let mut old = std::mem::take(&mut self.heads);
+        for info in heads {
+            match old.iter().position(|e| e.info.same_head(&info)) {
+                Some(i) => {
+                    let mut entry = old.remove(i);
+                    entry.resources.retain(|r| r.head.is_alive());
+                    let events = entry.info.update(&info);
+                    for r in &entry.resources {
+                        send(&r.head, &r.modes, &events);
+                    }
+                    changed |= !events.is_empty();
+                    self.heads.push(entry);
+                }
+                None => {
+                    let mut entry = HeadEntry {
+                        info,
+                        resources: Vec::new(),
+                    };
+                    for manager in &self.managers {
+                        create_head::<D>(&self.dh, manager, &mut entry);
+                    }
+                    changed = true;
+                    self.heads.push(entry);
+                }
+            }
+        }
+        for entry in old {
+            for r in &entry.resources {
+                r.finish();
+            }
+            changed = true;
+        }
+        if changed {
+            self.serial = self.serial.wrapping_add(1);
+            for manager in &self.managers {
+                manager.done(self.serial);
+            }
+        }
+    }
+}
+
+fn create_head<D>(dh: &DisplayHandle, manager: &ZwlrOutputManagerV1, entry: &mut HeadEntry)
+where
+    D: Dispatch<ZwlrOutputHeadV1, OutputHeadData>
+        + Dispatch<ZwlrOutputModeV1, OutputModeData>
+        + 'static,
+{
+    let Ok(client) = dh.get_client(manager.id()) else {
+        return;
+    };
+    let data = OutputHeadData {
+        name: entry.info.name.clone(),
+    };
+    let Ok(head) = client.create_resource::<ZwlrOutputHeadV1, _, D>(dh, manager.version(), data)
+    else {
+        return;
+    };
+    manager.head(&head);
+    head.name(entry.info.name.clone());
+    head.description(entry.info.description.clone());
+    if entry.info.physical_size != (0, 0) {
+        head.physical_size(entry.info.physical_size.0, entry.info.physical_size.1);
+    }
+    let mut modes = Vec::new();
+    for (index, m) in entry.info.modes.iter().enumerate() {
+        let data = OutputModeData {
+            head: entry.info.name.clone(),
+            index,
+        };
+        let Ok(mode) = client.create_resource::<ZwlrOutputModeV1, _, D>(dh, head.version(), data)
+        else {
+            return;
+        };
+        head.mode(&mode);
+        mode.size(m.width, m.height);
+        mode.refresh(m.refresh);
+        if m.preferred {
+            mode.preferred();
+        }
+        modes.push(mode);
+    }
+    send(&head, &modes, &entry.info.announce());
+    entry.resources.push(HeadResources { head, modes });
+}
+
+impl<D> GlobalDispatch<ZwlrOutputManagerV1, (), D> for OutputManagementState
+where
+    D: GlobalDispatch<ZwlrOutputManagerV1, ()>
+        + Dispatch<ZwlrOutputManagerV1, ()>
+        + Dispatch<ZwlrOutputHeadV1, OutputHeadData>
+        + Dispatch<ZwlrOutputModeV1, OutputModeData>
+        + Dispatch<ZwlrOutputConfigurationV1, OutputConfigurationData>
+        + Dispatch<ZwlrOutputConfigurationHeadV1, OutputConfigurationHeadData>
+        + OutputManagementHandler
+        + 'static,
+{
+    fn bind(
+        state: &mut D,
+        dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwlrOutputManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let manager = data_init.init(resource, ());
+        let om = state.output_management_state();
+        for entry in &mut om.heads {
+            create_head::<D>(dh, &manager, entry);
+        }
+        manager.done(om.serial);
+        om.managers.push(manager);
+    }
+}
+
+impl<D> Dispatch<ZwlrOutputManagerV1, (), D> for OutputManagementState
+where
+    D: Dispatch<ZwlrOutputManagerV1, ()>
+        + Dispatch<ZwlrOutputConfigurationV1, OutputConfigurationData>
+        + OutputManagementHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        resource: &ZwlrOutputManagerV1,
+        request: zwlr_output_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_output_manager_v1::Request::CreateConfiguration { id, serial } => {
+                data_init.init(
+                    id,
+                    OutputConfigurationData {
+                        serial,
+                        heads: Mutex::new(Vec::new()),
+                        used: AtomicBool::new(false),
+                    },
+                );
+            }
+            zwlr_output_manager_v1::Request::Stop => {
+                state
+                    .output_management_state()
+                    .managers
+                    .retain(|m| m != resource);
+                resource.finished();
+            }
+            _ => {}
+        }
+    }
+
+    fn destroyed(state: &mut D, _client: ClientId, resource: &ZwlrOutputManagerV1, _data: &()) {
+        state
+            .output_management_state()
+            .managers
+            .retain(|m| m != resource);
+    }
+}
+
+impl<D> Dispatch<ZwlrOutputHeadV1, OutputHeadData, D> for OutputManagementState
+where
+    D: Dispatch<ZwlrOutputHeadV1, OutputHeadData> + OutputManagementHandler + 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _resource: &ZwlrOutputHeadV1,
+        _request: zwlr_output_head_v1::Request,
+        _data: &OutputHeadData,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        // Only `release`, from version 3; the global is version 1.
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        resource: &ZwlrOutputHeadV1,
+        _data: &OutputHeadData,
+    ) {
+        for entry in &mut state.output_management_state().heads {
+            entry.resources.retain(|r| &r.head != resource);
+        }
+    }
+}
+
+impl<D> Dispatch<ZwlrOutputModeV1, OutputModeData, D> for OutputManagementState
+where
+    D: Dispatch<ZwlrOutputModeV1, OutputModeData> + 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _resource: &ZwlrOutputModeV1,
+        _request: zwlr_output_mode_v1::Request,
+        _data: &OutputModeData,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+    }
+}
+
+impl<D> Dispatch<ZwlrOutputConfigurationV1, OutputConfigurationData, D> for OutputManagementState
+where
+    D: Dispatch<ZwlrOutputConfigurationV1, OutputConfigurationData>
+        + Dispatch<ZwlrOutputConfigurationHeadV1, OutputConfigurationHeadData>
+        + OutputManagementHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        resource: &ZwlrOutputConfigurationV1,
+        request: zwlr_output_configuration_v1::Request,
+        data: &OutputConfigurationData,
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let head_name =
+            |head: &ZwlrOutputHeadV1| head.data::<OutputHeadData>().map(|d| d.name.clone());
+        let test_only = match request {
+            zwlr_output_configuration_v1::Request::EnableHead { id, head } => {
+                let config = Arc::new(Mutex::new(OutputConfig::default()));
+                data_init.init(
+                    id,
+                    OutputConfigurationHeadData {
+                        config: config.clone(),
+                    },
+                );
+                if let Some(name) = head_name(&head) {
+                    add_head(resource, data, name, Some(config));
+                }
+                return;
+            }
+            zwlr_output_configuration_v1::Request::DisableHead { head } => {
+                if let Some(name) = head_name(&head) {
+                    add_head(resource, data, name, None);
+                }
+                return;
+            }
+            zwlr_output_configuration_v1::Request::Apply => false,
+            zwlr_output_configuration_v1::Request::Test => true,
+            _ => return,
+        };
+
+        if data.used.swap(true, Ordering::SeqCst) {
+            resource.post_error(
+                zwlr_output_configuration_v1::Error::AlreadyUsed,
+                "configuration already applied or tested",
+            );
+            return;
+        }
+        if data.serial != state.output_management_state().serial {
+            resource.cancelled();
+            return;
+        }
+        let changes: Vec<(String, Option<OutputConfig>)> = data
+            .heads
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, cfg)| {
+                (
+                    name.clone(),
+                    cfg.as_ref().map(|c| c.lock().unwrap().clone()),
+                )
+            })
+            .collect();
+        let heads = state.output_management_state().heads();
+        let ok = configure_heads(&heads, &changes).is_some()
+            && (test_only || state.apply_output_configuration(&changes));
+        if ok {
+            resource.succeeded();
+        } else {
+            resource.failed();
+        }
+    }
+}
+
+/// Record `name` in the configuration, or post `already_configured_head`.
+fn add_head(
+    resource: &ZwlrOutputConfigurationV1,
+    data: &OutputConfigurationData,
+    name: String,
+    config: Option<Arc<Mutex<OutputConfig>>>,
+) {
+    let mut heads = data.heads.lock().unwrap();
+    if heads.iter().any(|(n, _)| n == &name) {
+        resource.post_error(
+            zwlr_output_configuration_v1::Error::AlreadyConfiguredHead,
+            format!("{name} is already in this configuration"),
+        );
+        return;
+    }
+    heads.push((name, config));
+}
+
+impl<D> Dispatch<ZwlrOutputConfigurationHeadV1, OutputConfigurationHeadData, D>
+    for OutputManagementState
+where
+    D: Dispatch<ZwlrOutputConfigurationHeadV1, OutputConfigurationHeadData>
+        + OutputManagementHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        resource: &ZwlrOutputConfigurationHeadV1,
+        request: zwlr_output_configuration_head_v1::Request,
+        data: &OutputConfigurationHeadData,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        use zwlr_output_configuration_head_v1::{Error, Request};
+
+        let mut cfg = data.config.lock().unwrap();
+        let already_set = match &request {
+            Request::SetMode { .. } | Request::SetCustomMode { .. } => cfg.mode.is_some(),
+            Request::SetPosition { .. } => cfg.position.is_some(),
+            Request::SetTransform { .. } => cfg.transform.is_some(),
+            Request::SetScale { .. } => cfg.scale.is_some(),
+            _ => false,
+        };
+        if already_set {
+            resource.post_error(Error::AlreadySet, "property already set");
+            return;
+        }
+        match request {
+            Request::SetMode { mode } => {
+                // The mode's exact refresh rate: a rounded one could pick a
+                // sibling mode of the same size.
+                cfg.mode = mode.data::<OutputModeData>().and_then(|d| {
+                    let heads = &state.output_management_state().heads;
+                    let head = heads.iter().find(|h| h.info.name == d.head)?;
+                    Some(head.info.modes.get(d.index)?.as_output_mode())
+                });
+            }
+            Request::SetCustomMode {
+                width,
+                height,
+                refresh,
+            } => {
+                if width <= 0 || height <= 0 || refresh < 0 {
+                    resource.post_error(Error::InvalidCustomMode, "invalid custom mode");
+                    return;
+                }
+                // Only the DRM modes can be used, so this has to name one.
+                cfg.mode = Some(OutputMode {
+                    width: width as u16,
+                    height: height as u16,
+                    refresh: (refresh > 0).then_some(refresh as u32),
+                });
+            }
+            Request::SetPosition { x, y } => cfg.position = Some((x, y)),
+            Request::SetTransform { transform } => match transform.into_result() {
+                Ok(t) => cfg.transform = Some(t.into()),
+                Err(_) => resource.post_error(Error::InvalidTransform, "invalid transform"),
+            },
+            Request::SetScale { scale } => {
+                if scale <= 0.0 {
+                    resource.post_error(Error::InvalidScale, "scale must be positive");
+                    return;
+                }
+                cfg.scale = Some(scale);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! delegate_output_management {
+    ($ty:ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_manager_v1::ZwlrOutputManagerV1: ()
+        ] => $crate::output_management::OutputManagementState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_manager_v1::ZwlrOutputManagerV1: ()
+        ] => $crate::output_management::OutputManagementState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_head_v1::ZwlrOutputHeadV1: $crate::output_management::OutputHeadData
+        ] => $crate::output_management::OutputManagementState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_mode_v1::ZwlrOutputModeV1: $crate::output_management::OutputModeData
+        ] => $crate::output_management::OutputManagementState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_configuration_v1::ZwlrOutputConfigurationV1: $crate::output_management::OutputConfigurationData
+        ] => $crate::output_management::OutputManagementState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_configuration_head_v1::ZwlrOutputConfigurationHeadV1: $crate::output_management::OutputConfigurationHeadData
+        ] => $crate::output_management::OutputManagementState);
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mock_head() -> HeadInfo {
+        let mode = |width, height, preferred| ModeInfo {
+            width,
+            height,
+            refresh: 60_000,
+            preferred,
+        };
+        HeadInfo {
+            name: "DP-1".into(),
+            description: "DP-1".into(),
+            physical_size: (600, 340),
+            modes: vec![mode(2560, 1440, true), mode(1920, 1080, false)],
+            enabled: true,
+            current_mode: Some(0),
+            position: (0, 0),
+            transform: Transform::Normal,
+            scale: 1.0,
+        }
+    }
+
+    #[test]
+    fn applied_configuration_changes_the_mode() {
+        let mut head = mock_head();
+        let cfg = OutputConfig {
+            mode: Some(OutputMode {
+                width: 1920,
+                height: 1080,
+                refresh: Some(60_000),
+            }),
+            position: Some((2560, 0)),
+            ..OutputConfig::default()
+        };
+        let new = configure_heads(&[head.clone()], &[("DP-1".into(), Some(cfg))]).unwrap();
+        assert_eq!(new[0].current_mode, Some(1));
+        assert_eq!(
+            head.update(&new[0]),
+            [HeadEvent::CurrentMode(1), HeadEvent::Position(2560, 0)]
+        );
+        assert_eq!(head.update(&new[0]), []);
+
+        let off = configure_heads(&new, &[("DP-1".into(), None)]).unwrap();
+        assert_eq!(head.update(&off[0]), [HeadEvent::Enabled(false)]);
+    }
+
+    #[test]
+    fn configuration_must_name_offered_modes() {
+        let heads = [mock_head()];
+        let mode = |width, height| OutputConfig {
+            mode: Some(OutputMode {
+                width,
+                height,
+                refresh: Some(60_000),
+            }),
+            ..OutputConfig::default()
+        };
+        assert!(configure_heads(&heads, &[("DP-1".into(), Some(mode(1280, 720)))]).is_none());
+        assert!(configure_heads(&heads, &[("HDMI-A-1".into(), None)]).is_none());
+        assert!(configure_heads(&heads, &[("DP-1".into(), Some(mode(2560, 1440)))]).is_some());
+    }
+
+    #[test]
+    fn fractional_refresh_rates_pick_their_own_mode() {
+        let mode = |refresh, preferred| ModeInfo {
+            width: 1920,
+            height: 1080,
+            refresh,
+            preferred,
+        };
+        let modes = [mode(60_000, true), mode(59_940, false), mode(50_000, false)];
+        assert_eq!(pick_mode(&modes, modes[1].as_output_mode()), Some(1));
+        assert_eq!(pick_mode(&modes, modes[0].as_output_mode()), Some(0));
+
+        // Config modes: `@60` also takes a 59.94 Hz mode if that is all
+        // there is, but not a 59 Hz one.
+        let want = |refresh| OutputMode {
+            width: 1920,
+            height: 1080,
+            refresh,
+        };
+        assert_eq!(pick_mode(&modes, want(None)), Some(0));
+        assert_eq!(pick_mode(&modes, want(Some(59_000))), None);
+        assert_eq!(pick_mode(&modes[1..], want(Some(60_000))), Some(0));
+    }
+
+    #[test]
+    fn heads_round_trip_through_as_change() {
+        let head = HeadInfo {
+            current_mode: Some(1),
+            position: (2560, 0),
+            scale: 1.5,
+            ..mock_head()
+        };
+        let other = configure_heads(&[head.clone()], &[("DP-1".into(), None)]).unwrap();
+        let back = configure_heads(&other, &[("DP-1".into(), head.as_change())]).unwrap();
+        assert_eq!(back, [head.clone()]);
+
+        let off = &other[0];
+        assert_eq!(off.as_change(), None);
+        let back = configure_heads(&[head], &[("DP-1".into(), off.as_change())]).unwrap();
+        assert_eq!(back, [off.clone()]);
+    }
+}
@@ -1,12 +1,19 @@
 // pixelui.rs — native pixel-space UI framework.
 
+use std::collections::HashMap;
 use std::ffi::CString;
+use std::sync::{Arc, Weak};
 
-pub use self::layout::{Constraint, Direction, Layout, Rect};
+pub use self::icons::Icons;
+pub use self::layers::{ClockLayer, CpuLayer};
+pub use self::layout::{Constraint, Direction, Layout, Rect, SidebarSide};
 pub use self::style::{Color, Style};
-pub use self::widgets::{Block, Input, InputState, List, ListState, Paragraph};
+pub use self::widgets::{
+    Block, ClickableWidget, Input, InputState, List, ListState, Paragraph, ProgressBar,
+    ProgressDirection, Spinner, SpinnerState,
+};
 
-use crate::font::GlyphAtlas;
+use crate::font::{AtlasPage, GlyphAtlas};
 use crate::shaper::Shaper;
 
 // ── Color ─────────────────────────────────────────────────────────────────────
@@ -19,6 +26,7 @@ pub mod style {
         pub const BLACK: Self = Self(0, 0, 0, 255);
         pub const WHITE: Self = Self(255, 255, 255, 255);
         pub const RESET: Self = Self(0, 0, 0, 0);
+        pub const RED: Self = Self(255, 0, 0, 255);
         pub const CYAN: Self = Self(0, 200, 255, 255);
         pub const GRAY: Self = Self(128, 128, 128, 255);
         pub const DARK_GRAY: Self = Self(64, 64, 64, 255);
@@ -114,6 +122,19 @@ pub mod layout {
                 h: self.h.saturating_sub(px2),
             }
         }
+        /// The overlap of two rects; empty (zero-sized) when they don't meet.
+        pub fn intersect(&self, o: &Rect) -> Self {
+            let x = self.x.max(o.x);
+            let y = self.y.max(o.y);
+            let x1 = (self.x + self.w).min(o.x + o.w);
+            let y1 = (self.y + self.h).min(o.y + o.h);
+            Self {
+                x,
+                y,
+                w: x1.saturating_sub(x),
+                h: y1.saturating_sub(y),
+            }
+        }
     }
 
     #[derive(Clone, Copy, Debug)]
@@ -130,9 +151,57 @@ pub mod layout {
         Percentage(u32),
     }
 
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum SidebarSide {
+        Left,
+        Right,
+        Top,
+        Bottom,
+    }
+
     pub struct Layout;
 
     impl Layout {
+        /// Split `area` into `(sidebar, content)` with a fixed-size sidebar on
+        /// `side`. The sidebar is clamped so content always keeps at least 1px.
+        pub fn sidebar(area: Rect, sidebar_w: u32, side: SidebarSide) -> (Rect, Rect) {
+            let total = match side {
+                SidebarSide::Left | SidebarSide::Right => area.w,
+                SidebarSide::Top | SidebarSide::Bottom => area.h,
+            };
+            let s = if sidebar_w >= total {
+                total.saturating_sub(1)
+            } else {
+                sidebar_w
+            };
+            let rest = total - s;
+            match side {
+                SidebarSide::Left => (
+                    Rect::new(area.x, area.y, s, area.h),
+                    Rect::new(area.x + s, area.y, rest, area.h),
+                ),
+                SidebarSide::Right => (
+                    Rect::new(area.x + rest, area.y, s, area.h),
+                    Rect::new(area.x, area.y, rest, area.h),
+                ),
+                SidebarSide::Top => (
+                    Rect::new(area.x, area.y, area.w, s),
+                    Rect::new(area.x, area.y + s, area.w, rest),
+                ),
+                SidebarSide::Bottom => (
+                    Rect::new(area.x, area.y + rest, area.w, s),
+                    Rect::new(area.x, area.y, area.w, rest),
+                ),
+            }
+        }
+
+        /// A `w × h` rect centred inside `area`, shrunk to fit if needed.
+        pub fn centered(area: Rect, w: u32, h: u32) -> Rect {
+            let w = w.min(area.w);
+            let h = h.min(area.h);
+            Rect::new(area.x + (area.w - w) / 2, area.y + (area.h - h) / 2, w, h)
+        }
+
         pub fn split(area: Rect, dir: Direction, constraints: &[Constraint]) -> Vec<Rect> {
             if constraints.is_empty() {
                 return vec![];
@@ -184,8 +253,7 @@ pub mod layout {
                     }
                 }
             }
-            if flex_count > 0 {
-                let share = remaining / flex_count;
+            if let Some(share) = remaining.checked_div(flex_count) {
                 for (i, c) in constraints.iter().enumerate() {
                     match c {
                         Constraint::Min(m) => sizes[i] = share.max(*m),
@@ -199,6 +267,58 @@ pub mod layout {
     }
 }
 
+// ── Icons ─────────────────────────────────────────────────────────────────────
+
+pub mod icons {
+    use std::collections::HashMap;
+    use std::sync::{Mutex, OnceLock};
+
+    /// Built-in semantic icon names → Nerd Fonts codepoints.
+    const DEFAULT_ICONS: &[(&str, char)] = &[
+        ("cpu", '\u{F2DB}'),
+        ("battery", '\u{F240}'),
+        ("battery-three-quarters", '\u{F241}'),
+        ("battery-half", '\u{F242}'),
+        ("battery-quarter", '\u{F243}'),
+        ("battery-empty", '\u{F244}'),
+        ("charging", '\u{F0E7}'),
+        ("wifi", '\u{F1EB}'),
+        ("clock", '\u{F017}'),
+        ("arrow-right", '\u{F061}'),
+        ("arrow-left", '\u{F060}'),
+        ("terminal", '\u{F120}'),
+        ("folder", '\u{F07B}'),
+        ("volume", '\u{F028}'),
+        ("globe", '\u{F0AC}'),
+    ];
+
+    fn map() -> &'static Mutex<HashMap<String, char>> {
+        static ICON_MAP: OnceLock<Mutex<HashMap<String, char>>> = OnceLock::new();
+        ICON_MAP.get_or_init(|| {
+            Mutex::new(
+                DEFAULT_ICONS
+                    .iter()
+                    .map(|&(n, c)| (n.to_owned(), c))
+                    .collect(),
+            )
+        })
+    }
+
+    pub struct Icons;
+
+    impl Icons {
+        /// Add or override an icon mapping (e.g. from config `icon_map`).
+        pub fn register(name: &str, codepoint: char) {
+            if let Ok(mut m) = map().lock() {
+                m.insert(name.to_owned(), codepoint);
+            }
+        }
+        pub fn lookup(name: &str) -> Option<char> {
+            map().lock().ok()?.get(name).copied()
+        }
+    }
+}
+
 // ── DrawCmd ───────────────────────────────────────────────────────────────────
 
 #[derive(Debug, Clone)]
@@ -212,6 +332,19 @@ pub enum DrawCmd {
         color: Color,
         thickness: u32,
     },
+    /// `FillRect` with its corners rounded off to `radius` pixels.
+    FillRoundedRect {
+        rect: Rect,
+        color: Color,
+        radius: u32,
+    },
+    /// `StrokeRect` with its corners rounded off to `radius` pixels.
+    StrokeRoundedRect {
+        rect: Rect,
+        color: Color,
+        radius: u32,
+        thickness: u32,
+    },
     Text {
         x: u32,
         y: u32,
@@ -231,6 +364,102 @@ pub enum DrawCmd {
         h: u32,
         color: Color,
     },
+    /// `dash_len` pixels on, `gap_len` off, from (x1, y1) to (x2, y2).
+    DashedLine {
+        x1: u32,
+        y1: u32,
+        x2: u32,
+        y2: u32,
+        dash_len: u32,
+        gap_len: u32,
+        color: Color,
+        thickness: u32,
+    },
+    /// The whole glyph atlas as a `size × size` square in the bottom-right
+    /// corner of the viewport (`debug_atlas_overlay = true`).
+    AtlasDebugOverlay {
+        size: u32,
+    },
+    /// Straight-alpha RGBA bitmap (`w * h * 4` bytes) with its top-left at
+    /// (x, y); may start off-screen, e.g. a cursor hotspot at the edge.
+    Image {
+        x: i32,
+        y: i32,
+        w: u32,
+        h: u32,
+        rgba: Arc<Vec<u8>>,
+    },
+    /// Named icon (see `Icons`), rasterised to fit a `size × size` box at
+    /// (x, y) and centred in it.
+    Icon {
+        x: u32,
+        y: u32,
+        name: String,
+        size: u32,
+        color: Color,
+    },
+    /// Restrict every later command to `rect` (None lifts it) until the next
+    /// Clip. Emitted by `DrawContext::push_clip`/`pop_clip`.
+    Clip {
+        rect: Option<Rect>,
+    },
+    /// Small inline picture (thumbnail, file icon) in its own texture.
+    /// Textures are cached per `data` allocation, so keep the Arc around
+    /// between frames instead of rebuilding it.
+    Bitmap {
+        x: u32,
+        y: u32,
+        data: Arc<[u8]>,
+        w: u32,
+        h: u32,
+        format: BitmapFormat,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitmapFormat {
+    /// Straight-alpha RGBA, 4 bytes per pixel.
+    Rgba8,
+    /// Opaque greyscale, 1 byte per pixel.
+    Gray8,
+    /// Straight-alpha BGRA (e.g. decoded ARGB32 little-endian), 4 bytes.
+    Bgra8,
+}
+
+impl BitmapFormat {
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            Self::Rgba8 | Self::Bgra8 => 4,
+            Self::Gray8 => 1,
+        }
+    }
+
+    /// Whether `data` holds at least `w × h` pixels.
+    pub fn fits(self, data: &[u8], w: u32, h: u32) -> bool {
+        data.len() >= w as usize * h as usize * self.bytes_per_pixel()
+    }
+
+    /// `data` as straight RGBA, borrowed when it already is. None if `data`
+    /// is shorter than `w × h` pixels.
+    pub fn to_rgba(self, data: &[u8], w: u32, h: u32) -> Option<std::borrow::Cow<'_, [u8]>> {
+        if !self.fits(data, w, h) {
+            return None;
+        }
+        let data = &data[..w as usize * h as usize * self.bytes_per_pixel()];
+        Some(match self {
+            Self::Rgba8 => data.into(),
+            Self::Bgra8 => data
+                .chunks_exact(4)
+                .flat_map(|p| [p[2], p[1], p[0], p[3]])
+                .collect::<Vec<u8>>()
+                .into(),
+            Self::Gray8 => data
+                .iter()
+                .flat_map(|&v| [v, v, v, 255])
+                .collect::<Vec<u8>>()
+                .into(),
+        })
+    }
 }
 
 // ── DrawContext ───────────────────────────────────────────────────────────────
@@ -240,9 +469,24 @@ pub struct DrawContext {
     pub area: Rect,
     pub cell_w: u32,
     pub cell_h: u32,
+    /// Click targets registered this frame, in draw order. Checked against
+    /// the pointer by the caller after flushing — see `hit_test`.
+    pub clickable_regions: Vec<(Rect, u64)>,
+    /// `push_clip` rects, each already cut down to the one below it.
+    clip_stack: Vec<Rect>,
 }
 
 impl DrawContext {
+    pub fn new(area: Rect, cell_w: u32, cell_h: u32) -> Self {
+        Self {
+            cmds: Vec::new(),
+            area,
+            cell_w,
+            cell_h,
+            clickable_regions: Vec::new(),
+            clip_stack: Vec::new(),
+        }
+    }
     pub fn area(&self) -> Rect {
         self.area
     }
@@ -252,14 +496,41 @@ impl DrawContext {
     pub fn render_widget<W: Widget>(&mut self, widget: W, rect: Rect) {
         widget.render(rect, self);
     }
+    pub fn render_boxed(&mut self, widget: Box<dyn WidgetDyn>, rect: Rect) {
+        widget.render_dyn(rect, self);
+    }
+
+    /// Keep everything drawn until the matching `pop_clip` inside `rect`
+    /// (and inside any clip already pushed).
+    pub fn push_clip(&mut self, rect: Rect) {
+        let rect = self.clip().map_or(rect, |c| c.intersect(&rect));
+        self.clip_stack.push(rect);
+        self.cmds.push(DrawCmd::Clip { rect: Some(rect) });
+    }
+    pub fn pop_clip(&mut self) {
+        if self.clip_stack.pop().is_some() {
+            self.cmds.push(DrawCmd::Clip { rect: self.clip() });
+        }
+    }
+    /// The clip in force, if any.
+    pub fn clip(&self) -> Option<Rect> {
+        self.clip_stack.last().copied()
+    }
+    /// `rect` cut to the clip. Commands that can't be cut exactly (strokes,
+    /// text) only use this to be dropped when nothing of them shows; the
+    /// renderer's scissor trims the rest.
+    fn clipped(&self, rect: Rect) -> Rect {
+        self.clip().map_or(rect, |c| c.intersect(&rect))
+    }
 
     pub fn fill_rect(&mut self, rect: Rect, color: Color) {
+        let rect = self.clipped(rect);
         if !rect.is_empty() {
             self.cmds.push(DrawCmd::FillRect { rect, color });
         }
     }
     pub fn stroke_rect(&mut self, rect: Rect, color: Color, thickness: u32) {
-        if !rect.is_empty() {
+        if !self.clipped(rect).is_empty() {
             self.cmds.push(DrawCmd::StrokeRect {
                 rect,
                 color,
@@ -267,7 +538,35 @@ impl DrawContext {
             });
         }
     }
+    pub fn fill_rounded_rect(&mut self, rect: Rect, color: Color, radius: u32) {
+        if !self.clipped(rect).is_empty() {
+            self.cmds.push(DrawCmd::FillRoundedRect {
+                rect,
+                color,
+                radius,
+            });
+        }
+    }
+    pub fn stroke_rounded_rect(&mut self, rect: Rect, color: Color, radius: u32, thickness: u32) {
+        if !self.clipped(rect).is_empty() {
+            self.cmds.push(DrawCmd::StrokeRoundedRect {
+                rect,
+                color,
+                radius,
+                thickness,
+            });
+        }
+    }
     pub fn text(&mut self, x: u32, y: u32, s: &str, style: Style, max_w: Option<u32>) {
+        let mut max_w = max_w;
+        if let Some(c) = self.clip() {
+            let line = Rect::new(x, y, max_w.unwrap_or(u32::MAX - x), self.cell_h);
+            if self.clipped(line).is_empty() {
+                return;
+            }
+            let room = (c.x + c.w).saturating_sub(x);
+            max_w = Some(max_w.map_or(room, |m| m.min(room)));
+        }
         if !s.is_empty() {
             self.cmds.push(DrawCmd::Text {
                 x,
@@ -279,11 +578,209 @@ impl DrawContext {
         }
     }
     pub fn hline(&mut self, x: u32, y: u32, w: u32, color: Color) {
-        self.cmds.push(DrawCmd::HLine { x, y, w, color });
+        let r = self.clipped(Rect::new(x, y, w, 1));
+        if !r.is_empty() {
+            self.cmds.push(DrawCmd::HLine {
+                x: r.x,
+                y: r.y,
+                w: r.w,
+                color,
+            });
+        }
     }
     pub fn vline(&mut self, x: u32, y: u32, h: u32, color: Color) {
-        self.cmds.push(DrawCmd::VLine { x, y, h, color });
+        let r = self.clipped(Rect::new(x, y, 1, h));
+        if !r.is_empty() {
+            self.cmds.push(DrawCmd::VLine {
+                x: r.x,
+                y: r.y,
+                h: r.h,
+                color,
+            });
+        }
+    }
+    pub fn dashed_hline(&mut self, x: u32, y: u32, w: u32, dash: u32, gap: u32, color: Color) {
+        if !self.clipped(Rect::new(x, y, w, 1)).is_empty() {
+            self.cmds.push(DrawCmd::DashedLine {
+                x1: x,
+                y1: y,
+                x2: x + w - 1,
+                y2: y,
+                dash_len: dash,
+                gap_len: gap,
+                color,
+                thickness: 1,
+            });
+        }
+    }
+    pub fn dashed_vline(&mut self, x: u32, y: u32, h: u32, dash: u32, gap: u32, color: Color) {
+        if !self.clipped(Rect::new(x, y, 1, h)).is_empty() {
+            self.cmds.push(DrawCmd::DashedLine {
+                x1: x,
+                y1: y,
+                x2: x,
+                y2: y + h - 1,
+                dash_len: dash,
+                gap_len: gap,
+                color,
+                thickness: 1,
+            });
+        }
+    }
+    pub fn icon(&mut self, x: u32, y: u32, name: &str, size: u32, color: Color) {
+        if self.clipped(Rect::new(x, y, size, size)).is_empty() {
+            return;
+        }
+        self.cmds.push(DrawCmd::Icon {
+            x,
+            y,
+            name: name.to_owned(),
+            size,
+            color,
+        });
+    }
+    pub fn register_click(&mut self, rect: Rect, id: u64) {
+        let rect = self.clipped(rect);
+        if !rect.is_empty() {
+            self.clickable_regions.push((rect, id));
+        }
+    }
+}
+
+/// Topmost region containing pixel `(x, y)` — later registrations win, the
+/// same way later draw commands paint over earlier ones.
+pub fn hit_test(regions: &[(Rect, u64)], x: u32, y: u32) -> Option<u64> {
+    regions
+        .iter()
+        .rev()
+        .find(|(r, _)| x >= r.x && x < r.x + r.w && y >= r.y && y < r.y + r.h)
+        .map(|&(_, id)| id)
+}
+
+/// Dash rects `[x, y, w, h]` for a `DrawCmd::DashedLine`; both endpoints are
+/// inclusive. Axis-aligned lines give one rect per dash, anything else
+/// steps Bresenham-style and gives one `thickness` square per lit pixel.
+pub fn dash_segments(
+    (x1, y1): (u32, u32),
+    (x2, y2): (u32, u32),
+    dash: u32,
+    gap: u32,
+    thickness: u32,
+) -> Vec<[f32; 4]> {
+    let t = thickness.max(1) as f32;
+    let period = dash + gap;
+    if dash == 0 {
+        return Vec::new();
+    }
+    if y1 == y2 || x1 == x2 {
+        let horiz = y1 == y2;
+        let (a, b) = if horiz { (x1, x2) } else { (y1, y2) };
+        let (lo, hi) = (a.min(b), a.max(b));
+        let mut out = Vec::new();
+        let mut p = lo;
+        while p <= hi {
+            let len = dash.min(hi - p + 1) as f32;
+            out.push(if horiz {
+                [p as f32, y1 as f32, len, t]
+            } else {
+                [x1 as f32, p as f32, t, len]
+            });
+            p += period;
+        }
+        return out;
+    }
+
+    let (mut x, mut y) = (x1 as i64, y1 as i64);
+    let (ex, ey) = (x2 as i64, y2 as i64);
+    let dx = (ex - x).abs();
+    let dy = -(ey - y).abs();
+    let sx = if x < ex { 1 } else { -1 };
+    let sy = if y < ey { 1 } else { -1 };
+    let mut err = dx + dy;
+    let mut out = Vec::new();
+    let mut step = 0u32;
+    loop {
+        if step % period < dash {
+            out.push([x as f32, y as f32, t, t]);
+        }
+        if x == ex && y == ey {
+            break;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+        step += 1;
+    }
+    out
+}
+
+/// Pieces of a rounded rect as `[x, y, w, h]` with a coverage in 0–1: the
+/// straight runs as whole rects at full coverage, then the corners one
+/// pixel at a time, 4×4 supersampled. `stroke` is the border thickness, or
+/// None for a fill. The radius is clamped to half the shorter side.
+pub fn rounded_rect_spans(rect: Rect, radius: u32, stroke: Option<u32>) -> Vec<([f32; 4], f32)> {
+    const SAMPLES: u32 = 4;
+    if rect.is_empty() {
+        return Vec::new();
+    }
+    let r = radius.min(rect.w / 2).min(rect.h / 2);
+    let (x, y, w, h) = (rect.x as f32, rect.y as f32, rect.w as f32, rect.h as f32);
+    let rf = r as f32;
+    let mut out: Vec<([f32; 4], f32)> = match stroke {
+        None => vec![
+            [x + rf, y, w - 2.0 * rf, rf],
+            [x, y + rf, w, h - 2.0 * rf],
+            [x + rf, y + h - rf, w - 2.0 * rf, rf],
+        ],
+        Some(t) => {
+            let t = t.max(1) as f32;
+            vec![
+                [x + rf, y, w - 2.0 * rf, t],
+                [x + rf, y + h - t, w - 2.0 * rf, t],
+                [x, y + rf, t, h - 2.0 * rf],
+                [x + w - t, y + rf, t, h - 2.0 * rf],
+            ]
+        }
+    }
+    .into_iter()
+    .filter(|r| r[2] > 0.0 && r[3] > 0.0)
+    .map(|r| (r, 1.0))
+    .collect();
+
+    // Coverage of the top-left corner square, arc centre at (r, r); the
+    // other three corners are its mirror images.
+    let inner = stroke.map_or(0.0, |t| rf - t.max(1) as f32);
+    for j in 0..r {
+        for i in 0..r {
+            let mut hits = 0;
+            for sy in 0..SAMPLES {
+                for sx in 0..SAMPLES {
+                    let px = i as f32 + (sx as f32 + 0.5) / SAMPLES as f32;
+                    let py = j as f32 + (sy as f32 + 0.5) / SAMPLES as f32;
+                    let d = (rf - px).hypot(rf - py);
+                    if d <= rf && d >= inner {
+                        hits += 1;
+                    }
+                }
+            }
+            if hits == 0 {
+                continue;
+            }
+            let cov = hits as f32 / (SAMPLES * SAMPLES) as f32;
+            let (left, right) = (x + i as f32, x + w - 1.0 - i as f32);
+            let (top, bottom) = (y + j as f32, y + h - 1.0 - j as f32);
+            for (cx, cy) in [(left, top), (right, top), (left, bottom), (right, bottom)] {
+                out.push(([cx, cy, 1.0, 1.0], cov));
+            }
+        }
     }
+    out
 }
 
 // ── Widget ────────────────────────────────────────────────────────────────────
@@ -292,6 +789,26 @@ pub trait Widget {
     fn render(self, area: Rect, ctx: &mut DrawContext);
 }
 
+/// Object-safe face of `Widget`, for heterogeneous `Vec<Box<dyn WidgetDyn>>`.
+/// Every `Widget` gets it for free.
+pub trait WidgetDyn {
+    fn render_dyn(self: Box<Self>, area: Rect, ctx: &mut DrawContext);
+}
+
+impl<W: Widget> WidgetDyn for W {
+    fn render_dyn(self: Box<Self>, area: Rect, ctx: &mut DrawContext) {
+        (*self).render(area, ctx);
+    }
+}
+
+/// A persistent overlay drawn over the TWM chrome every frame (clocks,
+/// system stats, notifications). Unlike a `WidgetDyn` it is kept between
+/// frames, so it can hold its own state. `area` is the whole viewport.
+pub trait ChromeLayer {
+    fn render(&mut self, ctx: &mut DrawContext, area: Rect, cell_w: u32, cell_h: u32);
+    fn name(&self) -> &str;
+}
+
 // ── Widgets ───────────────────────────────────────────────────────────────────
 
 pub mod widgets {
@@ -304,6 +821,7 @@ pub mod widgets {
         bg: Color,
         title_style: Style,
         padding: u32,
+        border_radius: u32,
     }
 
     impl Default for Block {
@@ -320,6 +838,7 @@ pub mod widgets {
                 bg: Color::RESET,
                 title_style: Style::default().fg(Color::WHITE).bold(),
                 padding: 1,
+                border_radius: 0,
             }
         }
         pub fn title(mut self, t: impl Into<String>) -> Self {
@@ -338,6 +857,11 @@ pub mod widgets {
             self.title_style = s;
             self
         }
+        /// Round the border's corners (and the background's) to `r` pixels.
+        pub fn border_radius(mut self, r: u32) -> Self {
+            self.border_radius = r;
+            self
+        }
         pub fn inner(&self, area: Rect) -> Rect {
             area.inner(1 + self.padding)
         }
@@ -348,10 +872,17 @@ pub mod widgets {
             if area.is_empty() {
                 return;
             }
-            if self.bg != Color::RESET {
-                ctx.fill_rect(area, self.bg);
+            if self.border_radius > 0 {
+                if self.bg != Color::RESET {
+                    ctx.fill_rounded_rect(area, self.bg, self.border_radius);
+                }
+                ctx.stroke_rounded_rect(area, self.border_color, self.border_radius, 1);
+            } else {
+                if self.bg != Color::RESET {
+                    ctx.fill_rect(area, self.bg);
+                }
+                ctx.stroke_rect(area, self.border_color, 1);
             }
-            ctx.stroke_rect(area, self.border_color, 1);
             if let Some(ref title) = self.title {
                 let tx = area.x + 2;
                 let ty = area.y;
@@ -708,108 +1239,483 @@ pub mod widgets {
             }
         }
     }
-}
 
-// ── GL renderer ───────────────────────────────────────────────────────────────
+    // ── Spinner ───────────────────────────────────────────────────────────────
 
-const UI_BG_VERT: &str = r#"
-#version 300 es
-precision mediump float;
-in vec2 a_pos;
-in vec4 i_rect;
-in vec4 i_color;
-uniform vec2 u_vp;
-out vec4 v_color;
-void main() {
-    vec2 px  = i_rect.xy + a_pos * i_rect.zw;
-    vec2 ndc = (px / u_vp) * 2.0 - 1.0;
-    gl_Position = vec4(ndc, 0.0, 1.0);
-    v_color = i_color;
-}
-"#;
+    const SPINNER_FRAMES: [char; 8] = ['⣾', '⣽', '⣻', '⢿', '⡿', '⣟', '⣯', '⣷'];
 
-const UI_BG_FRAG: &str = r#"
-#version 300 es
-precision mediump float;
-in vec4 v_color;
-out vec4 fragColor;
-void main() { fragColor = v_color; }
-"#;
+    #[derive(Debug, Clone, Copy)]
+    pub struct SpinnerState {
+        pub started: std::time::Instant,
+    }
 
-const UI_GLYPH_VERT: &str = r#"
-#version 300 es
-precision mediump float;
-in vec2 a_pos;
-in vec4 i_glyph;
-in vec4 i_uv;
-in vec4 i_fg;
-uniform vec2 u_vp;
-out vec2 v_uv;
-out vec4 v_fg;
-void main() {
-    vec2 px  = i_glyph.xy + a_pos * i_glyph.zw;
-    vec2 ndc = (px / u_vp) * 2.0 - 1.0;
-    gl_Position = vec4(ndc, 0.0, 1.0);
-    v_uv = mix(i_uv.xy, i_uv.zw, a_pos);
-    v_fg = i_fg;
-}
-"#;
+    impl SpinnerState {
+        pub fn new() -> Self {
+            Self {
+                started: std::time::Instant::now(),
+            }
+        }
+    }
 
-const UI_GLYPH_FRAG: &str = r#"
-#version 300 es
-precision mediump float;
-uniform sampler2D u_atlas;
-in vec2 v_uv;
-in vec4 v_fg;
-out vec4 fragColor;
-void main() {
-    float a = texture(u_atlas, v_uv).a;
-    if (a < 0.004) discard;
-    // ab_glyph produces linear (physical) coverage values in [0,1].
-    // The framebuffer is sRGB (gamma ~2.2). Rendering linear coverage
-    // directly into sRGB makes glyphs look thin and washed out because
-    // the display darkens mid-grey values.
-    //
-    // To match FreeType/kitty's perceived stroke weight we convert from
-    // linear coverage to an sRGB-encoded alpha:
-    //   a_srgb = pow(a_linear, 1.0/2.2)  [exact sRGB EOTF approximation]
-    //
-    // This brightens the coverage curve so that a physically 50% covered
-    // pixel appears as 50% grey on a calibrated display, matching what
-    // FreeType's hinted rasteriser produces after gamma correction.
-    a = pow(a, 0.454545);  // 1.0/2.2
-    fragColor = vec4(v_fg.rgb, v_fg.a * a);
-}
-"#;
+    impl Default for SpinnerState {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
 
-#[repr(C)]
-#[derive(Clone, Copy)]
-struct BgInst {
-    rect: [f32; 4],
-    color: [f32; 4],
-}
+    /// One braille glyph that completes a full turn every `period_ms`.
+    /// Stateless between frames — the frame is picked from the wall clock.
+    pub struct Spinner<'a> {
+        pub period_ms: u64,
+        state: &'a SpinnerState,
+        style: Style,
+    }
 
-#[repr(C)]
-#[derive(Clone, Copy)]
-struct GlyphInst {
-    glyph: [f32; 4],
-    uv: [f32; 4],
-    fg: [f32; 4],
-}
+    impl<'a> Spinner<'a> {
+        pub fn new(state: &'a SpinnerState, period_ms: u64) -> Self {
+            Self {
+                period_ms,
+                state,
+                style: Style::default().fg(Color::CYAN),
+            }
+        }
+        pub fn style(mut self, s: Style) -> Self {
+            self.style = s;
+            self
+        }
+        pub fn frame_at(&self, t: std::time::Instant) -> char {
+            let period = self.period_ms.max(1) as u128;
+            let elapsed = t.saturating_duration_since(self.state.started).as_millis() % period;
+            SPINNER_FRAMES[(elapsed * SPINNER_FRAMES.len() as u128 / period) as usize]
+        }
+    }
 
-#[rustfmt::skip]
-const QUAD: [f32; 12] = [0.,0., 1.,0., 1.,1., 0.,0., 1.,1., 0.,1.];
+    impl<'a> Widget for Spinner<'a> {
+        fn render(self, area: Rect, ctx: &mut DrawContext) {
+            let ch = self.frame_at(std::time::Instant::now());
+            ctx.text(area.x, area.y, &ch.to_string(), self.style, Some(area.w));
+        }
+    }
 
-pub struct UiRenderer {
-    bg_prog: u32,
-    bg_vao: u32,
-    bg_ivbo: u32,
-    bg_cap: usize,
+    // ── ProgressBar ───────────────────────────────────────────────────────────
+
+    /// Which edge a `ProgressBar` fills from.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub enum ProgressDirection {
+        #[default]
+        LeftToRight,
+        RightToLeft,
+        BottomToTop,
+        TopToBottom,
+    }
+
+    /// A gauge filled to `value` (0.0–1.0) with a centred label, "42%"
+    /// unless set. `style.bg` is the track and `style.fg` the label colour;
+    /// `filled_style.bg` is the filled part.
+    #[derive(Clone, Debug)]
+    pub struct ProgressBar {
+        value: f32,
+        style: Style,
+        filled_style: Style,
+        block: Option<Block>,
+        direction: ProgressDirection,
+        label: Option<String>,
+    }
+
+    impl ProgressBar {
+        pub fn new(value: f32) -> Self {
+            Self {
+                value: if value.is_nan() {
+                    0.0
+                } else {
+                    value.clamp(0.0, 1.0)
+                },
+                style: Style::default(),
+                filled_style: Style::default().fg(Color::BLACK).bg(Color::CYAN),
+                block: None,
+                direction: ProgressDirection::default(),
+                label: None,
+            }
+        }
+        pub fn style(mut self, s: Style) -> Self {
+            self.style = s;
+            self
+        }
+        pub fn filled_style(mut self, s: Style) -> Self {
+            self.filled_style = s;
+            self
+        }
+        pub fn block(mut self, b: Block) -> Self {
+            self.block = Some(b);
+            self
+        }
+        pub fn direction(mut self, d: ProgressDirection) -> Self {
+            self.direction = d;
+            self
+        }
+        /// Text drawn instead of the percentage.
+        pub fn label(mut self, l: impl Into<String>) -> Self {
+            self.label = Some(l.into());
+            self
+        }
+        /// The part of `inner` covered by the fill.
+        pub fn filled_rect(&self, inner: Rect) -> Rect {
+            let part = |len: u32| (len as f32 * self.value).round() as u32;
+            let (w, h) = (part(inner.w), part(inner.h));
+            match self.direction {
+                ProgressDirection::LeftToRight => Rect::new(inner.x, inner.y, w, inner.h),
+                ProgressDirection::RightToLeft => {
+                    Rect::new(inner.x + inner.w - w, inner.y, w, inner.h)
+                }
+                ProgressDirection::TopToBottom => Rect::new(inner.x, inner.y, inner.w, h),
+                ProgressDirection::BottomToTop => {
+                    Rect::new(inner.x, inner.y + inner.h - h, inner.w, h)
+                }
+            }
+        }
+    }
+
+    impl Widget for ProgressBar {
+        fn render(mut self, area: Rect, ctx: &mut DrawContext) {
+            let inner = if let Some(b) = self.block.take() {
+                let inner = b.inner(area);
+                b.render(area, ctx);
+                inner
+            } else {
+                area
+            };
+            if inner.is_empty() {
+                return;
+            }
+            if self.style.bg != Color::RESET {
+                ctx.fill_rect(inner, self.style.bg);
+            }
+            ctx.fill_rect(self.filled_rect(inner), self.filled_style.bg);
+
+            let label = self
+                .label
+                .unwrap_or_else(|| format!("{}%", (self.value * 100.0).round() as u32));
+            let w = label.chars().count() as u32 * ctx.cell_w;
+            let x = inner.x + inner.w.saturating_sub(w) / 2;
+            let y = inner.y + inner.h.saturating_sub(ctx.cell_h) / 2;
+            let style = Style {
+                bg: Color::RESET,
+                ..self.style
+            };
+            ctx.text(x, y, &label, style, Some(inner.x + inner.w - x));
+        }
+    }
+
+    // ── ClickableWidget ───────────────────────────────────────────────────────
+
+    /// Wraps any widget and registers its full area as a click target.
+    /// The caller maps `id` back to an action when `hit_test` matches.
+    pub struct ClickableWidget<W: Widget> {
+        inner: W,
+        id: u64,
+    }
+
+    impl<W: Widget> ClickableWidget<W> {
+        pub fn new(inner: W, id: u64) -> Self {
+            Self { inner, id }
+        }
+    }
+
+    impl<W: Widget> Widget for ClickableWidget<W> {
+        fn render(self, area: Rect, ctx: &mut DrawContext) {
+            ctx.register_click(area, self.id);
+            self.inner.render(area, ctx);
+        }
+    }
+}
+
+// ── Chrome layers ─────────────────────────────────────────────────────────────
+
+pub mod layers {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    /// How often `CpuLayer` re-reads /proc/stat.
+    const CPU_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+    /// Right-align `label` on text row `row` of `area`, one cell in from the edge.
+    fn top_right(ctx: &mut DrawContext, area: Rect, row: u32, label: &str, style: Style) {
+        let (cell_w, cell_h) = ctx.cell_size();
+        let w = label.chars().count() as u32 * cell_w;
+        let x = area.x + area.w.saturating_sub(w + cell_w);
+        ctx.text(x, area.y + row * cell_h, label, style, Some(w));
+    }
+
+    /// HH:MM (UTC, like the bar clock) in the top-right corner.
+    pub struct ClockLayer {
+        style: Style,
+    }
+
+    impl ClockLayer {
+        pub fn new() -> Self {
+            Self {
+                style: Style::default(),
+            }
+        }
+        pub fn style(mut self, s: Style) -> Self {
+            self.style = s;
+            self
+        }
+    }
+
+    impl Default for ClockLayer {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl ChromeLayer for ClockLayer {
+        fn render(&mut self, ctx: &mut DrawContext, area: Rect, _cell_w: u32, _cell_h: u32) {
+            let secs = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            let label = format!("{:02}:{:02}", (secs / 3600) % 24, (secs / 60) % 60);
+            top_right(ctx, area, 0, &label, self.style);
+        }
+        fn name(&self) -> &str {
+            "clock"
+        }
+    }
+
+    /// Total CPU usage as "CPU 42%", one row under the clock.
+    pub struct CpuLayer {
+        style: Style,
+        /// (busy, total) jiffies at the last sample.
+        last: Option<(u64, u64)>,
+        sampled_at: Option<Instant>,
+        percent: Option<u32>,
+    }
+
+    impl CpuLayer {
+        pub fn new() -> Self {
+            Self {
+                style: Style::default(),
+                last: None,
+                sampled_at: None,
+                percent: None,
+            }
+        }
+        pub fn style(mut self, s: Style) -> Self {
+            self.style = s;
+            self
+        }
+
+        fn sample(&mut self) {
+            let Some(now) = std::fs::read_to_string("/proc/stat")
+                .ok()
+                .and_then(|t| parse_proc_stat(&t))
+            else {
+                return;
+            };
+            if let Some((busy, total)) = self.last {
+                let dt = now.1.saturating_sub(total);
+                if let Some(percent) = (now.0.saturating_sub(busy) * 100).checked_div(dt) {
+                    self.percent = Some(percent as u32);
+                }
+            }
+            self.last = Some(now);
+        }
+    }
+
+    impl Default for CpuLayer {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl ChromeLayer for CpuLayer {
+        fn render(&mut self, ctx: &mut DrawContext, area: Rect, _cell_w: u32, _cell_h: u32) {
+            if self
+                .sampled_at
+                .is_none_or(|t| t.elapsed() >= CPU_SAMPLE_INTERVAL)
+            {
+                self.sample();
+                self.sampled_at = Some(Instant::now());
+            }
+            if let Some(p) = self.percent {
+                top_right(ctx, area, 1, &format!("CPU {p:>2}%"), self.style);
+            }
+        }
+        fn name(&self) -> &str {
+            "cpu"
+        }
+    }
+
+    /// (busy, total) jiffies from the aggregate `cpu` line of /proc/stat.
+    /// Idle time is `idle + iowait`.
+    pub fn parse_proc_stat(text: &str) -> Option<(u64, u64)> {
+        let line = text.lines().find(|l| l.starts_with("cpu "))?;
+        let fields: Vec<u64> = line
+            .split_whitespace()
+            .skip(1)
+            .filter_map(|f| f.parse().ok())
+            .collect();
+        if fields.len() < 4 {
+            return None;
+        }
+        let total: u64 = fields.iter().take(8).sum();
+        let idle = fields[3] + fields.get(4).copied().unwrap_or(0);
+        Some((total - idle, total))
+    }
+}
+
+// ── GL renderer ───────────────────────────────────────────────────────────────
+
+const UI_BG_VERT: &str = r#"
+#version 300 es
+precision mediump float;
+in vec2 a_pos;
+in vec4 i_rect;
+in vec4 i_color;
+uniform vec2 u_vp;
+out vec4 v_color;
+void main() {
+    vec2 px  = i_rect.xy + a_pos * i_rect.zw;
+    vec2 ndc = (px / u_vp) * 2.0 - 1.0;
+    gl_Position = vec4(ndc, 0.0, 1.0);
+    v_color = i_color;
+}
+"#;
+
+const UI_BG_FRAG: &str = r#"
+#version 300 es
+precision mediump float;
+in vec4 v_color;
+out vec4 fragColor;
+void main() { fragColor = v_color; }
+"#;
+
+const UI_GLYPH_VERT: &str = r#"
+#version 300 es
+precision mediump float;
+in vec2 a_pos;
+in vec4 i_glyph;
+in vec4 i_uv;
+in vec4 i_fg;
+in float i_color;
+uniform vec2 u_vp;
+out vec2 v_uv;
+out vec4 v_fg;
+out float v_color;
+void main() {
+    vec2 px  = i_glyph.xy + a_pos * i_glyph.zw;
+    vec2 ndc = (px / u_vp) * 2.0 - 1.0;
+    gl_Position = vec4(ndc, 0.0, 1.0);
+    v_uv = mix(i_uv.xy, i_uv.zw, a_pos);
+    v_fg = i_fg;
+    v_color = i_color;
+}
+"#;
+
+const UI_GLYPH_FRAG: &str = r#"
+#version 300 es
+precision mediump float;
+uniform sampler2D u_atlas;
+uniform int u_rgba;
+in vec2 v_uv;
+in vec4 v_fg;
+in float v_color;
+out vec4 fragColor;
+void main() {
+    if (u_rgba == 1) {
+        // DrawCmd::Image — the bound texture is the picture itself.
+        vec4 c = texture(u_atlas, v_uv);
+        if (c.a < 0.004) discard;
+        fragColor = c * v_fg;
+        return;
+    }
+    vec4 t = texture(u_atlas, v_uv);
+    if (t.a < 0.004) discard;
+    if (v_color > 0.5) {
+        // Color emoji — the atlas holds the colours; the text colour only
+        // contributes its opacity.
+        fragColor = vec4(t.rgb, t.a * v_fg.a);
+        return;
+    }
+    float a = t.a;
+    // ab_glyph produces linear (physical) coverage values in [0,1].
+    // The framebuffer is sRGB (gamma ~2.2). Rendering linear coverage
+    // directly into sRGB makes glyphs look thin and washed out because
+    // the display darkens mid-grey values.
+    //
+    // To match FreeType/kitty's perceived stroke weight we convert from
+    // linear coverage to an sRGB-encoded alpha:
+    //   a_srgb = pow(a_linear, 1.0/2.2)  [exact sRGB EOTF approximation]
+    //
+    // This brightens the coverage curve so that a physically 50% covered
+    // pixel appears as 50% grey on a calibrated display, matching what
+    // FreeType's hinted rasteriser produces after gamma correction.
+    a = pow(a, 0.454545);  // 1.0/2.2
+    fragColor = vec4(v_fg.rgb, v_fg.a * a);
+}
+"#;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct BgInst {
+    rect: [f32; 4],
+    color: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct GlyphInst {
+    glyph: [f32; 4],
+    uv: [f32; 4],
+    fg: [f32; 4],
+    /// 1.0 for glyphs whose atlas texels are full colour (`GlyphInfo::is_color`).
+    color: f32,
+}
+
+/// `rounded_rect_spans` as background instances, coverage folded into alpha.
+fn rounded_rect_insts(
+    rect: Rect,
+    color: Color,
+    radius: u32,
+    stroke: Option<u32>,
+) -> impl Iterator<Item = BgInst> {
+    let [r, g, b, a] = color.to_f32();
+    rounded_rect_spans(rect, radius, stroke)
+        .into_iter()
+        .map(move |(rect, cov)| BgInst {
+            rect,
+            color: [r, g, b, a * cov],
+        })
+}
+
+/// Instances drawn under one clip; see `UiRenderer::flush`.
+struct Batch {
+    clip: Option<Rect>,
+    bg: Vec<BgInst>,
+    /// Each with the atlas page it samples.
+    glyphs: Vec<(u32, GlyphInst)>,
+    images: Vec<(u32, GlyphInst)>,
+}
+
+#[rustfmt::skip]
+const QUAD: [f32; 12] = [0.,0., 1.,0., 1.,1., 0.,0., 1.,1., 0.,1.];
+
+pub struct UiRenderer {
+    bg_prog: u32,
+    bg_vao: u32,
+    bg_ivbo: u32,
+    bg_cap: usize,
     glyph_prog: u32,
     glyph_vao: u32,
     glyph_ivbo: u32,
     glyph_cap: usize,
-    atlas_tex: u32,
+    /// DrawCmd::Image textures keyed by the bitmap's Arc address. The Weak
+    /// keeps that allocation from being reused for another bitmap while
+    /// its entry exists.
+    image_tex: HashMap<usize, (Weak<Vec<u8>>, u32)>,
+    /// DrawCmd::Bitmap textures, least recently drawn first.
+    bitmap_tex: BitmapCache,
     pub atlas: GlyphAtlas,
     shaper: Shaper,
     pub cell_w: u32,
@@ -828,7 +1734,6 @@ impl UiRenderer {
         let glyph_prog = unsafe { compile_prog(UI_GLYPH_VERT, UI_GLYPH_FRAG)? };
         let (bg_vao, bg_ivbo) = unsafe { create_bg_vao(bg_prog, 1024) };
         let (glyph_vao, glyph_ivbo) = unsafe { create_glyph_vao(glyph_prog, 4096) };
-        let atlas_tex = unsafe { upload_atlas(&atlas) };
         Ok(Self {
             bg_prog,
             bg_vao,
@@ -838,7 +1743,8 @@ impl UiRenderer {
             glyph_vao,
             glyph_ivbo,
             glyph_cap: 4096,
-            atlas_tex,
+            image_tex: HashMap::new(),
+            bitmap_tex: BitmapCache::new(BITMAP_CACHE_LEN),
             atlas,
             shaper,
             cell_w,
@@ -867,11 +1773,146 @@ impl UiRenderer {
             return;
         }
 
+        // A glyph that only fits after a compaction moves the ones shaped
+        // before it, so their UVs are stale: shape the frame again. Once is
+        // enough unless the frame alone overflows the atlas.
+        let mut batches = Vec::new();
+        for _ in 0..2 {
+            let compactions = self.atlas.store.compactions;
+            batches = self.build_batches(cmds);
+            if self.atlas.store.compactions == compactions {
+                break;
+            }
+        }
+
+        // Pages opened this frame get their texture; the rest are patched.
+        let atlas_size = self.atlas.store.atlas_size;
+        for page in &mut self.atlas.store.pages {
+            if page.tex == 0 {
+                page.tex = unsafe { upload_atlas(page, atlas_size) };
+            } else if page.dirty {
+                unsafe { patch_atlas(page, atlas_size) };
+            }
+            page.dirty = false;
+            page.dirty_region = None;
+        }
+
+        let (vw, vh) = (self.vp_w as f32, self.vp_h as f32);
+
+        unsafe {
+            gl::Enable(gl::BLEND);
+            gl::BlendFuncSeparate(
+                gl::SRC_ALPHA,
+                gl::ONE_MINUS_SRC_ALPHA,
+                gl::ONE,
+                gl::ONE_MINUS_SRC_ALPHA,
+            );
+
+            // Whatever scissor the caller had is put back afterwards.
+            let clipped = batches.iter().any(|b| b.clip.is_some());
+            let outer = if clipped && gl::IsEnabled(gl::SCISSOR_TEST) == gl::TRUE {
+                let mut b = [0i32; 4];
+                gl::GetIntegerv(gl::SCISSOR_BOX, b.as_mut_ptr());
+                Some(b)
+            } else {
+                None
+            };
+
+            for batch in &batches {
+                if batch.bg.is_empty() && batch.glyphs.is_empty() && batch.images.is_empty() {
+                    continue;
+                }
+                if clipped {
+                    // The shaders map pixel y straight onto window y, so the
+                    // clip rect goes in unflipped.
+                    set_scissor(
+                        batch
+                            .clip
+                            .map(|c| [c.x as i32, c.y as i32, c.w as i32, c.h as i32])
+                            .or(outer),
+                    );
+                }
+
+                gl::UseProgram(self.bg_prog);
+                gl::BindVertexArray(self.bg_vao);
+                set_u2f(self.bg_prog, "u_vp", vw, vh);
+                gl::BindBuffer(gl::ARRAY_BUFFER, self.bg_ivbo);
+                upload_inst(&batch.bg, &mut self.bg_cap, std::mem::size_of::<BgInst>());
+                if !batch.bg.is_empty() {
+                    gl::DrawArraysInstanced(gl::TRIANGLES, 0, 6, batch.bg.len() as i32);
+                }
+
+                gl::UseProgram(self.glyph_prog);
+                gl::BindVertexArray(self.glyph_vao);
+                set_u2f(self.glyph_prog, "u_vp", vw, vh);
+                set_u1i(self.glyph_prog, "u_atlas", 0);
+                gl::BindBuffer(gl::ARRAY_BUFFER, self.glyph_ivbo);
+                gl::ActiveTexture(gl::TEXTURE0);
+                // One draw per atlas page the batch's glyphs sample.
+                for (i, page) in self.atlas.store.pages.iter().enumerate() {
+                    let insts: Vec<GlyphInst> = batch
+                        .glyphs
+                        .iter()
+                        .filter(|&&(p, _)| p as usize == i)
+                        .map(|&(_, inst)| inst)
+                        .collect();
+                    if insts.is_empty() {
+                        continue;
+                    }
+                    upload_inst(
+                        &insts,
+                        &mut self.glyph_cap,
+                        std::mem::size_of::<GlyphInst>(),
+                    );
+                    gl::BindTexture(gl::TEXTURE_2D, page.tex);
+                    gl::DrawArraysInstanced(gl::TRIANGLES, 0, 6, insts.len() as i32);
+                }
+
+                // Images last, one draw each: every one has its own texture.
+                if !batch.images.is_empty() {
+                    set_u1i(self.glyph_prog, "u_rgba", 1);
+                    for (tex, inst) in &batch.images {
+                        upload_inst(
+                            std::slice::from_ref(inst),
+                            &mut self.glyph_cap,
+                            std::mem::size_of::<GlyphInst>(),
+                        );
+                        gl::BindTexture(gl::TEXTURE_2D, *tex);
+                        gl::DrawArraysInstanced(gl::TRIANGLES, 0, 6, 1);
+                    }
+                    set_u1i(self.glyph_prog, "u_rgba", 0);
+                }
+            }
+            if clipped {
+                set_scissor(outer);
+            }
+
+            gl::BindVertexArray(0);
+            gl::UseProgram(0);
+        }
+    }
+
+    /// Instances for `cmds`, one batch per run under the same clip.
+    fn build_batches(&mut self, cmds: &[DrawCmd]) -> Vec<Batch> {
         let mut bg_cpu: Vec<BgInst> = Vec::new();
-        let mut glyph_cpu: Vec<GlyphInst> = Vec::new();
+        let mut glyph_cpu: Vec<(u32, GlyphInst)> = Vec::new();
+        let mut images: Vec<(u32, GlyphInst)> = Vec::new();
+        // Instances are drawn in one batch per run of commands under the same
+        // clip; `clip` is the one the commands collected so far are under.
+        let mut batches: Vec<Batch> = Vec::new();
+        let mut clip: Option<Rect> = None;
 
         for cmd in cmds {
             match cmd {
+                DrawCmd::Clip { rect } => {
+                    batches.push(Batch {
+                        clip,
+                        bg: std::mem::take(&mut bg_cpu),
+                        glyphs: std::mem::take(&mut glyph_cpu),
+                        images: std::mem::take(&mut images),
+                    });
+                    clip = *rect;
+                }
                 DrawCmd::FillRect { rect, color } => {
                     if *color == Color::RESET {
                         continue;
@@ -900,6 +1941,21 @@ impl UiRenderer {
                         });
                     }
                 }
+                DrawCmd::FillRoundedRect {
+                    rect,
+                    color,
+                    radius,
+                } => {
+                    bg_cpu.extend(rounded_rect_insts(*rect, *color, *radius, None));
+                }
+                DrawCmd::StrokeRoundedRect {
+                    rect,
+                    color,
+                    radius,
+                    thickness,
+                } => {
+                    bg_cpu.extend(rounded_rect_insts(*rect, *color, *radius, Some(*thickness)));
+                }
                 DrawCmd::HLine { x, y, w, color } => {
                     bg_cpu.push(BgInst {
                         rect: [*x as f32, *y as f32, *w as f32, 1.0],
@@ -912,6 +1968,23 @@ impl UiRenderer {
                         color: color.to_f32(),
                     });
                 }
+                DrawCmd::DashedLine {
+                    x1,
+                    y1,
+                    x2,
+                    y2,
+                    dash_len,
+                    gap_len,
+                    color,
+                    thickness,
+                } => {
+                    let c = color.to_f32();
+                    bg_cpu.extend(
+                        dash_segments((*x1, *y1), (*x2, *y2), *dash_len, *gap_len, *thickness)
+                            .into_iter()
+                            .map(|rect| BgInst { rect, color: c }),
+                    );
+                }
                 DrawCmd::Text {
                     x,
                     y,
@@ -929,57 +2002,152 @@ impl UiRenderer {
                     }
                     self.shape_text_into(*x, *y, text, style, *max_width, &mut glyph_cpu);
                 }
+                DrawCmd::AtlasDebugOverlay { size } => {
+                    let x = self.vp_w.saturating_sub(size + 8) as f32;
+                    let y = self.vp_h.saturating_sub(size + 8) as f32;
+                    let sz = *size as f32;
+                    bg_cpu.push(BgInst {
+                        rect: [x, y, sz, sz],
+                        color: [0.0, 0.0, 0.0, 0.85],
+                    });
+                    glyph_cpu.push((
+                        0,
+                        GlyphInst {
+                            glyph: [x, y, sz, sz],
+                            uv: [0.0, 0.0, 1.0, 1.0],
+                            fg: [1.0, 1.0, 1.0, 1.0],
+                            color: 0.0,
+                        },
+                    ));
+                }
+                DrawCmd::Icon {
+                    x,
+                    y,
+                    name,
+                    size,
+                    color,
+                } => {
+                    glyph_cpu.extend(self.icon_inst(*x, *y, name, *size, *color));
+                }
+                DrawCmd::Image { x, y, w, h, rgba } => {
+                    if *w == 0 || *h == 0 || rgba.len() < (*w * *h * 4) as usize {
+                        continue;
+                    }
+                    let tex = self.image_texture(rgba, *w, *h);
+                    images.push((
+                        tex,
+                        GlyphInst {
+                            glyph: [*x as f32, *y as f32, *w as f32, *h as f32],
+                            uv: [0.0, 0.0, 1.0, 1.0],
+                            fg: [1.0; 4],
+                            color: 0.0,
+                        },
+                    ));
+                }
+                DrawCmd::Bitmap {
+                    x,
+                    y,
+                    data,
+                    w,
+                    h,
+                    format,
+                } => {
+                    if *w == 0 || *h == 0 || !format.fits(data, *w, *h) {
+                        continue;
+                    }
+                    // Converted only on a miss; a cached bitmap costs nothing.
+                    let (tex, evicted) = self.bitmap_tex.get_or_upload(data, || {
+                        let rgba = format.to_rgba(data, *w, *h).expect("checked by fits");
+                        unsafe { upload_rgba(&rgba, *w, *h) }
+                    });
+                    if let Some(old) = evicted {
+                        unsafe { gl::DeleteTextures(1, &old) };
+                    }
+                    images.push((
+                        tex,
+                        GlyphInst {
+                            glyph: [*x as f32, *y as f32, *w as f32, *h as f32],
+                            uv: [0.0, 0.0, 1.0, 1.0],
+                            fg: [1.0; 4],
+                            color: 0.0,
+                        },
+                    ));
+                }
             }
         }
 
-        if self.atlas.dirty {
-            unsafe {
-                patch_atlas(self.atlas_tex, &self.atlas);
-            }
-            self.atlas.dirty = false;
-        }
-
-        let (vw, vh) = (self.vp_w as f32, self.vp_h as f32);
-
-        unsafe {
-            gl::Enable(gl::BLEND);
-            gl::BlendFuncSeparate(
-                gl::SRC_ALPHA,
-                gl::ONE_MINUS_SRC_ALPHA,
-                gl::ONE,
-                gl::ONE_MINUS_SRC_ALPHA,
-            );
+        batches.push(Batch {
+            clip,
+            bg: bg_cpu,
+            glyphs: glyph_cpu,
+            images,
+        });
+        batches
+    }
 
-            gl::UseProgram(self.bg_prog);
-            gl::BindVertexArray(self.bg_vao);
-            set_u2f(self.bg_prog, "u_vp", vw, vh);
-            gl::BindBuffer(gl::ARRAY_BUFFER, self.bg_ivbo);
-            upload_inst(&bg_cpu, &mut self.bg_cap, std::mem::size_of::<BgInst>());
-            if !bg_cpu.is_empty() {
-                gl::DrawArraysInstanced(gl::TRIANGLES, 0, 6, bg_cpu.len() as i32);
-            }
-
-            gl::UseProgram(self.glyph_prog);
-            gl::BindVertexArray(self.glyph_vao);
-            set_u2f(self.glyph_prog, "u_vp", vw, vh);
-            set_u1i(self.glyph_prog, "u_atlas", 0);
-            gl::BindBuffer(gl::ARRAY_BUFFER, self.glyph_ivbo);
-            upload_inst(
-                &glyph_cpu,
-                &mut self.glyph_cap,
-                std::mem::size_of::<GlyphInst>(),
-            );
-            gl::ActiveTexture(gl::TEXTURE0);
-            gl::BindTexture(gl::TEXTURE_2D, self.atlas_tex);
-            if !glyph_cpu.is_empty() {
-                gl::DrawArraysInstanced(gl::TRIANGLES, 0, 6, glyph_cpu.len() as i32);
-            }
+    /// Texture for `rgba`, uploaded on first use. Bitmaps are expected to be
+    /// long-lived (cursor frames), so the cache is only dropped when it grows
+    /// past a theme's worth of entries or on `clear_images`.
+    fn image_texture(&mut self, rgba: &Arc<Vec<u8>>, w: u32, h: u32) -> u32 {
+        let key = Arc::as_ptr(rgba) as usize;
+        if let Some((_, tex)) = self.image_tex.get(&key) {
+            return *tex;
+        }
+        if self.image_tex.len() >= 256 {
+            // Bitmaps nobody holds any more go first.
+            self.image_tex.retain(|_, (bitmap, tex)| {
+                let live = bitmap.strong_count() > 0;
+                if !live {
+                    unsafe { gl::DeleteTextures(1, tex) };
+                }
+                live
+            });
+        }
+        if self.image_tex.len() >= 256 {
+            self.clear_images();
+        }
+        let tex = unsafe { upload_rgba(rgba, w, h) };
+        self.image_tex.insert(key, (Arc::downgrade(rgba), tex));
+        tex
+    }
 
-            gl::BindVertexArray(0);
-            gl::UseProgram(0);
+    /// Forget every DrawCmd::Image texture, e.g. after a cursor theme change
+    /// freed the bitmaps they were keyed by.
+    pub fn clear_images(&mut self) {
+        for (_, (_, tex)) in self.image_tex.drain() {
+            unsafe { gl::DeleteTextures(1, &tex) };
         }
     }
 
+    fn icon_inst(
+        &mut self,
+        x: u32,
+        y: u32,
+        name: &str,
+        size: u32,
+        color: Color,
+    ) -> Option<(u32, GlyphInst)> {
+        let Some(ch) = Icons::lookup(name) else {
+            tracing::debug!("unknown icon `{name}`");
+            return None;
+        };
+        let uv = self.atlas.icon(ch, size)?;
+        if uv.width <= 0 || uv.height <= 0 {
+            return None;
+        }
+        let gx = x as f32 + (size as f32 - uv.width as f32) / 2.0;
+        let gy = y as f32 + (size as f32 - uv.height as f32) / 2.0;
+        Some((
+            uv.page,
+            GlyphInst {
+                glyph: [gx.round(), gy.round(), uv.width as f32, uv.height as f32],
+                uv: [uv.uv_x, uv.uv_y, uv.uv_x + uv.uv_w, uv.uv_y + uv.uv_h],
+                fg: color.to_f32(),
+                color: uv.is_color as u8 as f32,
+            },
+        ))
+    }
+
     fn shape_text_into(
         &mut self,
         x: u32,
@@ -987,9 +2155,9 @@ impl UiRenderer {
         text: &str,
         style: &Style,
         max_w: Option<u32>,
-        out: &mut Vec<GlyphInst>,
+        out: &mut Vec<(u32, GlyphInst)>,
     ) {
-        use crate::shaper::segment_str;
+        use crate::shaper::{segment_str, TextDirection};
 
         let cell_w = self.cell_w as f32;
         let fg = style.fg.to_f32();
@@ -1013,11 +2181,20 @@ impl UiRenderer {
                         if uv.width > 0 && uv.height > 0 {
                             // Synthetic glyphs have bearing_x=0, bearing_y=ascender,
                             // so they sit flush at (px, y) filling the full cell.
-                            out.push(GlyphInst {
-                                glyph: [px.round(), y as f32, uv.width as f32, uv.height as f32],
-                                uv: [uv.uv_x, uv.uv_y, uv.uv_x + uv.uv_w, uv.uv_y + uv.uv_h],
-                                fg,
-                            });
+                            out.push((
+                                uv.page,
+                                GlyphInst {
+                                    glyph: [
+                                        px.round(),
+                                        y as f32,
+                                        uv.width as f32,
+                                        uv.height as f32,
+                                    ],
+                                    uv: [uv.uv_x, uv.uv_y, uv.uv_x + uv.uv_w, uv.uv_y + uv.uv_h],
+                                    fg,
+                                    color: 0.0,
+                                },
+                            ));
                         }
                     }
                     px += cell_w;
@@ -1028,38 +2205,131 @@ impl UiRenderer {
                 // Advance using the font's own per-glyph advance, NOT cell_w.
                 // Using cell_w here causes wrong inter-glyph spacing for UI text
                 // where characters have varying widths (symbols, CJK, etc.).
-                let shaped = self.shaper.shape(&run.text);
-                for sg in &shaped {
+                let dir = self.shaper.run_direction(&run);
+                let rtl = dir == TextDirection::Rtl;
+                let mut shaped = self.shaper.shape_with_direction(&run.text, dir);
+                if rtl {
+                    // HarfBuzz hands RTL runs back in visual order; walk them
+                    // logically and let run_positions fill from the right.
+                    shaped.reverse();
+                }
+                // The shaper only knows the regular face: glyphs it has no id
+                // for are looked up by codepoint, which reaches the fallback
+                // and color emoji faces.
+                let glyphs: Vec<_> = shaped
+                    .iter()
+                    .map(|sg| {
+                        let ch = run.text.get(sg.cluster..).and_then(|t| t.chars().next());
+                        let uv = match ch {
+                            Some(ch) if sg.glyph_id == 0 => {
+                                self.atlas.glyph(ch, run.bold, run.italic)
+                            }
+                            _ => self.atlas.glyph_by_id(sg.glyph_id, run.bold, run.italic),
+                        };
+                        // Advance by the font's actual advance × cluster width.
+                        // For monospace fonts this equals cell_w. For UI symbols
+                        // and variable-width characters this is the correct value.
+                        // Glyph missing from atlas — fall back to cell_w.
+                        let adv = uv.map_or(cell_w, |uv| uv.advance as f32);
+                        (uv, adv * sg.cluster_width as f32)
+                    })
+                    .collect();
+                let advances: Vec<f32> = glyphs.iter().map(|&(_, adv)| adv).collect();
+                for (&(uv, _), gx) in glyphs.iter().zip(run_positions(px, &advances, rtl)) {
                     if let Some(max) = max_px {
-                        if px - x as f32 >= max {
+                        if gx - x as f32 >= max {
+                            // RTL runs reach the clip edge first, then come back.
+                            if rtl {
+                                continue;
+                            }
                             return;
                         }
                     }
-                    if let Some(uv) = self.atlas.glyph_by_id(sg.glyph_id, run.bold, run.italic) {
-                        if uv.width > 0 && uv.height > 0 {
-                            out.push(GlyphInst {
+                    let Some(uv) = uv else { continue };
+                    if uv.width > 0 && uv.height > 0 {
+                        out.push((
+                            uv.page,
+                            GlyphInst {
                                 glyph: [
-                                    (px + uv.bearing_x as f32).round(),
+                                    (gx + uv.bearing_x as f32).round(),
                                     (y as f32 + (self.ascender - uv.bearing_y) as f32).round(),
                                     uv.width as f32,
                                     uv.height as f32,
                                 ],
                                 uv: [uv.uv_x, uv.uv_y, uv.uv_x + uv.uv_w, uv.uv_y + uv.uv_h],
                                 fg,
-                            });
-                        }
-                        // Advance by the font's actual advance × cluster width.
-                        // For monospace fonts this equals cell_w. For UI symbols
-                        // and variable-width characters this is the correct value.
-                        px += uv.advance as f32 * sg.cluster_width as f32;
-                    } else {
-                        // Glyph missing from atlas — fall back to cell_w.
-                        px += cell_w * sg.cluster_width as f32;
+                                color: uv.is_color as u8 as f32,
+                            },
+                        ));
                     }
                 }
+                px += advances.iter().sum::<f32>();
+            }
+        }
+    }
+}
+
+/// Pen x for each glyph of a run starting at `px`, given its advances in
+/// logical order. LTR runs step right from `px`; RTL runs start at the run's
+/// right edge and step left, so both occupy `[px, px + sum(advances))`.
+fn run_positions(px: f32, advances: &[f32], rtl: bool) -> Vec<f32> {
+    let mut pen = if rtl {
+        px + advances.iter().sum::<f32>()
+    } else {
+        px
+    };
+    advances
+        .iter()
+        .map(|&adv| {
+            if rtl {
+                pen -= adv;
+                pen
+            } else {
+                pen += adv;
+                pen - adv
             }
+        })
+        .collect()
+}
+
+/// DrawCmd::Bitmap textures kept before the least recently drawn is freed.
+const BITMAP_CACHE_LEN: usize = 64;
+
+/// LRU map from a bitmap's `Arc` allocation to its GL texture. Each entry
+/// holds a clone of the Arc, so the address can't be reused by a different
+/// bitmap while its texture is cached.
+struct BitmapCache {
+    cap: usize,
+    /// Most recently used last.
+    entries: Vec<(Arc<[u8]>, u32)>,
+}
+
+impl BitmapCache {
+    fn new(cap: usize) -> Self {
+        Self {
+            cap,
+            entries: Vec::with_capacity(cap),
         }
     }
+
+    /// The texture for `data`, calling `upload` on a miss. Also returns the
+    /// texture evicted to make room, which the caller must delete.
+    fn get_or_upload(
+        &mut self,
+        data: &Arc<[u8]>,
+        upload: impl FnOnce() -> u32,
+    ) -> (u32, Option<u32>) {
+        if let Some(i) = self.entries.iter().position(|(d, _)| Arc::ptr_eq(d, data)) {
+            let hit = self.entries.remove(i);
+            let tex = hit.1;
+            self.entries.push(hit);
+            return (tex, None);
+        }
+        let evicted = (self.entries.len() >= self.cap).then(|| self.entries.remove(0).1);
+        let tex = upload();
+        self.entries.push((data.clone(), tex));
+        (tex, evicted)
+    }
 }
 
 // ── PixelUi (thin wrapper, kept for API compat) ───────────────────────────────
@@ -1091,14 +2361,275 @@ impl PixelUi {
     pub fn cell_size(&self) -> (u32, u32) {
         (self.renderer.cell_w, self.renderer.cell_h)
     }
+
+    /// Rasterise `cmds` on the CPU into an ARGB8888 `wl_shm` buffer — the
+    /// screencopy fallback when GL readback is unavailable. Glyphs come from
+    /// the same atlas as the GL path; no GL calls are made.
+    pub fn render_to_shm(
+        &mut self,
+        cmds: &[DrawCmd],
+        shm_data: &mut [u8],
+        stride: usize,
+        width: u32,
+        height: u32,
+    ) {
+        let mut canvas = ShmCanvas::new(shm_data, stride, width, height);
+        let r = &mut self.renderer;
+        let mut glyphs: Vec<(u32, GlyphInst)> = Vec::new();
+        for cmd in cmds {
+            match cmd {
+                DrawCmd::Text {
+                    x,
+                    y,
+                    text,
+                    style,
+                    max_width,
+                } => {
+                    if style.bg != Color::RESET {
+                        let est_w = (text.chars().count() as u32) * r.cell_w;
+                        let w = max_width.map(|m| m.min(est_w)).unwrap_or(est_w);
+                        canvas.fill([*x as f32, *y as f32, w as f32, r.cell_h as f32], style.bg);
+                    }
+                    // As in `flush`: a compaction part way through moves
+                    // the glyphs shaped before it.
+                    for _ in 0..2 {
+                        let compactions = r.atlas.store.compactions;
+                        glyphs.clear();
+                        r.shape_text_into(*x, *y, text, style, *max_width, &mut glyphs);
+                        if r.atlas.store.compactions == compactions {
+                            break;
+                        }
+                    }
+                    for (page, g) in &glyphs {
+                        canvas.blit_glyph(g, &r.atlas.store.pages[*page as usize].pixels);
+                    }
+                }
+                DrawCmd::Icon {
+                    x,
+                    y,
+                    name,
+                    size,
+                    color,
+                } => {
+                    if let Some((page, g)) = r.icon_inst(*x, *y, name, *size, *color) {
+                        canvas.blit_glyph(&g, &r.atlas.store.pages[page as usize].pixels);
+                    }
+                }
+                other => canvas.draw(other),
+            }
+        }
+    }
+}
+
+// ── Software rasteriser ───────────────────────────────────────────────────────
+
+/// A borrowed ARGB8888 (little-endian `0xAARRGGBB`) pixel buffer.
+/// Handles every DrawCmd that needs no glyphs; `PixelUi::render_to_shm`
+/// adds text and icons on top.
+pub struct ShmCanvas<'a> {
+    data: &'a mut [u8],
+    stride: usize,
+    width: u32,
+    height: u32,
+    /// Set by `DrawCmd::Clip`; pixels outside it are left alone.
+    clip: Option<Rect>,
+}
+
+impl<'a> ShmCanvas<'a> {
+    pub fn new(data: &'a mut [u8], stride: usize, width: u32, height: u32) -> Self {
+        Self {
+            data,
+            stride,
+            width,
+            height,
+            clip: None,
+        }
+    }
+
+    pub fn draw(&mut self, cmd: &DrawCmd) {
+        match cmd {
+            DrawCmd::Clip { rect } => self.clip = *rect,
+            DrawCmd::FillRect { rect, color } => {
+                self.fill(
+                    [rect.x as f32, rect.y as f32, rect.w as f32, rect.h as f32],
+                    *color,
+                );
+            }
+            DrawCmd::StrokeRect {
+                rect,
+                color,
+                thickness,
+            } => {
+                let t = *thickness as f32;
+                let (x, y, w, h) = (rect.x as f32, rect.y as f32, rect.w as f32, rect.h as f32);
+                for r in [
+                    [x, y, w, t],
+                    [x, y + h - t, w, t],
+                    [x, y, t, h],
+                    [x + w - t, y, t, h],
+                ] {
+                    self.fill(r, *color);
+                }
+            }
+            DrawCmd::FillRoundedRect {
+                rect,
+                color,
+                radius,
+            } => {
+                for (r, cov) in rounded_rect_spans(*rect, *radius, None) {
+                    self.fill_coverage(r, *color, (cov * 255.0).round() as u8);
+                }
+            }
+            DrawCmd::StrokeRoundedRect {
+                rect,
+                color,
+                radius,
+                thickness,
+            } => {
+                for (r, cov) in rounded_rect_spans(*rect, *radius, Some(*thickness)) {
+                    self.fill_coverage(r, *color, (cov * 255.0).round() as u8);
+                }
+            }
+            DrawCmd::HLine { x, y, w, color } => {
+                self.fill([*x as f32, *y as f32, *w as f32, 1.0], *color);
+            }
+            DrawCmd::VLine { x, y, h, color } => {
+                self.fill([*x as f32, *y as f32, 1.0, *h as f32], *color);
+            }
+            DrawCmd::DashedLine {
+                x1,
+                y1,
+                x2,
+                y2,
+                dash_len,
+                gap_len,
+                color,
+                thickness,
+            } => {
+                for r in dash_segments((*x1, *y1), (*x2, *y2), *dash_len, *gap_len, *thickness) {
+                    self.fill(r, *color);
+                }
+            }
+            DrawCmd::Image { x, y, w, h, rgba } => self.blit_rgba(*x, *y, *w, *h, rgba),
+            DrawCmd::Bitmap {
+                x,
+                y,
+                data,
+                w,
+                h,
+                format,
+            } => {
+                if let Some(rgba) = format.to_rgba(data, *w, *h) {
+                    self.blit_rgba(*x as i32, *y as i32, *w, *h, &rgba);
+                }
+            }
+            // Glyph-backed commands need the atlas; the debug overlay is
+            // deliberately left out of captures.
+            DrawCmd::Text { .. } | DrawCmd::Icon { .. } | DrawCmd::AtlasDebugOverlay { .. } => {}
+        }
+    }
+
+    /// Source-over blit of `w × h` straight RGBA pixels at `(x, y)`,
+    /// clipped to the buffer.
+    fn blit_rgba(&mut self, x: i32, y: i32, w: u32, h: u32, rgba: &[u8]) {
+        for (i, px) in rgba.chunks_exact(4).take((w * h) as usize).enumerate() {
+            let (px_x, px_y) = (x + (i as u32 % w) as i32, y + (i as u32 / w) as i32);
+            if px_x >= 0 && px_y >= 0 && (px_x as u32) < self.width && (px_y as u32) < self.height {
+                self.blend(
+                    px_x as u32,
+                    px_y as u32,
+                    Color(px[0], px[1], px[2], 255),
+                    px[3],
+                );
+            }
+        }
+    }
+
+    /// Source-over fill of `[x, y, w, h]`, clipped to the buffer.
+    pub fn fill(&mut self, rect: [f32; 4], color: Color) {
+        self.fill_coverage(rect, color, 255);
+    }
+
+    /// `fill` with `color`'s alpha scaled by `coverage`.
+    fn fill_coverage(&mut self, [x, y, w, h]: [f32; 4], color: Color, coverage: u8) {
+        if color == Color::RESET || coverage == 0 {
+            return;
+        }
+        let x0 = (x.max(0.0) as u32).min(self.width);
+        let y0 = (y.max(0.0) as u32).min(self.height);
+        let x1 = ((x + w).max(0.0) as u32).min(self.width);
+        let y1 = ((y + h).max(0.0) as u32).min(self.height);
+        for py in y0..y1 {
+            for px in x0..x1 {
+                self.blend(px, py, color, coverage);
+            }
+        }
+    }
+
+    /// Nearest-sample the atlas alpha under `g.uv` into `g.glyph`, tinted `g.fg`.
+    fn blit_glyph(&mut self, g: &GlyphInst, atlas: &[u8]) {
+        let size = ((atlas.len() / 4) as f64).sqrt() as u32;
+        let [gx, gy, gw, gh] = g.glyph;
+        let [u0, v0, u1, v1] = g.uv;
+        let fg = Color(
+            (g.fg[0] * 255.0) as u8,
+            (g.fg[1] * 255.0) as u8,
+            (g.fg[2] * 255.0) as u8,
+            (g.fg[3] * 255.0) as u8,
+        );
+        for dy in 0..gh as u32 {
+            let py = gy as i64 + dy as i64;
+            if py < 0 || py >= self.height as i64 {
+                continue;
+            }
+            let sv = v0 + (v1 - v0) * (dy as f32 + 0.5) / gh;
+            let ay = ((sv * size as f32) as u32).min(size - 1);
+            for dx in 0..gw as u32 {
+                let px = gx as i64 + dx as i64;
+                if px < 0 || px >= self.width as i64 {
+                    continue;
+                }
+                let su = u0 + (u1 - u0) * (dx as f32 + 0.5) / gw;
+                let ax = ((su * size as f32) as u32).min(size - 1);
+                let alpha = atlas
+                    .get(((ay * size + ax) * 4 + 3) as usize)
+                    .copied()
+                    .unwrap_or(0);
+                if alpha > 0 {
+                    self.blend(px as u32, py as u32, fg, alpha);
+                }
+            }
+        }
+    }
+
+    fn blend(&mut self, x: u32, y: u32, c: Color, coverage: u8) {
+        if let Some(r) = self.clip {
+            if x < r.x || y < r.y || x >= r.x + r.w || y >= r.y + r.h {
+                return;
+            }
+        }
+        let i = y as usize * self.stride + x as usize * 4;
+        let Some(px) = self.data.get_mut(i..i + 4) else {
+            return;
+        };
+        let a = c.3 as u32 * coverage as u32 / 255;
+        let mix = |src: u8, dst: u8| ((src as u32 * a + dst as u32 * (255 - a)) / 255) as u8;
+        // Little-endian ARGB8888: B, G, R, A in memory.
+        px[0] = mix(c.2, px[0]);
+        px[1] = mix(c.1, px[1]);
+        px[2] = mix(c.0, px[2]);
+        px[3] = (a + px[3] as u32 * (255 - a) / 255) as u8;
+    }
 }
 
 // ── Smithay render element ────────────────────────────────────────────────────
 
 pub mod overlay_element {
     static CHROME_ID: std::sync::OnceLock<Id> = std::sync::OnceLock::new();
+    static CURSOR_ID: std::sync::OnceLock<Id> = std::sync::OnceLock::new();
     use super::{DrawCmd, UiRenderer};
     use std::cell::RefCell;
+    use std::collections::HashMap;
 
     use smithay::backend::renderer::{
         element::{Element, Id, Kind, RenderElement, UnderlyingStorage},
@@ -1107,54 +2638,118 @@ pub mod overlay_element {
     };
     use smithay::utils::{Buffer, Physical, Rectangle, Scale};
 
-    // ── Thread-local UiRenderer ───────────────────────────────────────────────
+    // ── Thread-local UiRenderers ──────────────────────────────────────────────
     // GlesRenderer is strictly single-threaded (tied to one EGL context).
     // A thread_local is the correct way to make UiRenderer accessible from
     // inside RenderElement::draw() without lifetime gymnastics.
+    //
+    // RENDERER is built from `font.size` at startup. Outputs that report a
+    // physical size get a renderer sized for their DPI from SCALED instead;
+    // outputs whose DPI rounds the same share one.
+
+    /// `(font_size_pt bits, dpi rounded)` — see `atlas_key`.
+    pub type AtlasKey = (u32, u32);
+
+    pub fn atlas_key(font_size_pt: f32, dpi: f32) -> AtlasKey {
+        (font_size_pt.to_bits(), dpi.round() as u32)
+    }
+
+    /// Renderer key and physical viewport of one output.
+    #[derive(Clone, Copy, Default)]
+    struct OutputUi {
+        key: Option<AtlasKey>,
+        viewport: Option<(u32, u32)>,
+    }
 
     thread_local! {
         static RENDERER: RefCell<Option<UiRenderer>> = const { RefCell::new(None) };
+        static SCALED: RefCell<HashMap<AtlasKey, UiRenderer>> = RefCell::new(HashMap::new());
+        static OUTPUTS: RefCell<HashMap<String, OutputUi>> = RefCell::new(HashMap::new());
+    }
+
+    /// Run `f` on the renderer `output` draws with: its DPI-sized one if
+    /// installed, the startup one otherwise.
+    fn with_renderer<T>(output: &str, f: impl FnOnce(&mut UiRenderer) -> T) -> Option<T> {
+        let key = OUTPUTS.with(|o| o.borrow().get(output).and_then(|o| o.key));
+        SCALED.with(|scaled| {
+            let mut scaled = scaled.borrow_mut();
+            match key.and_then(|k| scaled.get_mut(&k)) {
+                Some(r) => Some(f(r)),
+                None => RENDERER.with(|cell| cell.borrow_mut().as_mut().map(f)),
+            }
+        })
     }
 
-    /// Call once after the GL context exists (inside init_pixel_ui).
+    /// Call once after the GL context exists (inside init_pixel_ui). Drops
+    /// every DPI-sized renderer: they were built from the old font.
     pub fn install_renderer(r: UiRenderer) {
         RENDERER.with(|cell| *cell.borrow_mut() = Some(r));
+        SCALED.with(|scaled| scaled.borrow_mut().clear());
     }
 
     pub fn is_installed() -> bool {
         RENDERER.with(|cell| cell.borrow().is_some())
     }
 
-    /// Read cell dimensions from the installed renderer.
-    pub fn cell_size() -> (u32, u32) {
-        RENDERER.with(|cell| {
-            cell.borrow()
-                .as_ref()
-                .map(|r| (r.cell_w, r.cell_h))
-                .unwrap_or((8, 16))
-        })
+    /// Whether a renderer for `key` exists for outputs to share.
+    pub fn has_scaled(key: AtlasKey) -> bool {
+        SCALED.with(|scaled| scaled.borrow().contains_key(&key))
+    }
+
+    /// Add the renderer outputs with atlas `key` draw with.
+    pub fn install_scaled(key: AtlasKey, r: UiRenderer) {
+        SCALED.with(|scaled| scaled.borrow_mut().insert(key, r));
+    }
+
+    /// Draw `output` with the renderer for `key`; `None` = the startup one.
+    pub fn set_output_atlas(output: &str, key: Option<AtlasKey>) {
+        OUTPUTS.with(|o| o.borrow_mut().entry(output.to_owned()).or_default().key = key);
+    }
+
+    /// Read cell dimensions of the renderer `output` draws with.
+    pub fn cell_size(output: &str) -> (u32, u32) {
+        with_renderer(output, |r| (r.cell_w, r.cell_h)).unwrap_or((8, 16))
+    }
+
+    /// Record `output`'s physical size (no GL calls). The renderer is resized
+    /// to it right before each flush, since outputs may share one.
+    pub fn set_viewport(output: &str, w: u32, h: u32) {
+        OUTPUTS.with(|o| {
+            o.borrow_mut()
+                .entry(output.to_owned())
+                .or_default()
+                .viewport = Some((w, h))
+        });
     }
 
-    /// Update viewport size (no GL calls, just stores two u32s).
-    pub fn set_viewport(w: u32, h: u32) {
+    /// Drop cached DrawCmd::Image textures (see `UiRenderer::clear_images`).
+    pub fn clear_images() {
         RENDERER.with(|cell| {
             if let Some(r) = cell.borrow_mut().as_mut() {
-                r.resize(w, h);
+                r.clear_images();
             }
         });
+        SCALED.with(|scaled| {
+            scaled
+                .borrow_mut()
+                .values_mut()
+                .for_each(UiRenderer::clear_images)
+        });
+    }
+
+    /// Utilisation of the startup renderer's glyph atlas.
+    pub fn atlas_stats() -> Option<crate::font::AtlasStats> {
+        RENDERER.with(|cell| cell.borrow().as_ref().map(|r| r.atlas.get_atlas_stats()))
     }
 
-    /// Read the physical pixel viewport dimensions previously set by set_viewport().
-    /// Returns (1920, 1080) as a safe fallback if the renderer is not yet installed.
+    /// Read the physical pixel viewport previously set by set_viewport().
+    /// Returns (1920, 1080) as a safe fallback for an output never set.
     /// Use this in render_surface() to get the same dimensions that u_vp is set to,
     /// so DrawCmd pixel coordinates and the NDC projection are always in sync.
-    pub fn get_viewport() -> (u32, u32) {
-        RENDERER.with(|cell| {
-            cell.borrow()
-                .as_ref()
-                .map(|r| (r.vp_w, r.vp_h))
-                .unwrap_or((1920, 1080))
-        })
+    pub fn get_viewport(output: &str) -> (u32, u32) {
+        OUTPUTS
+            .with(|o| o.borrow().get(output).and_then(|o| o.viewport))
+            .unwrap_or((1920, 1080))
     }
 
     // ── TwmChromeElement ──────────────────────────────────────────────────────
@@ -1166,20 +2761,31 @@ pub mod overlay_element {
         id: Id,
         commit: CommitCounter,
         cmds: Vec<DrawCmd>,
+        /// Output drawn on; picks the renderer (and so the glyph size).
+        output: String,
         w: u32,
         h: u32,
     }
 
     impl TwmChromeElement {
-        pub fn new(cmds: Vec<DrawCmd>, w: u32, h: u32) -> Self {
+        pub fn new(cmds: Vec<DrawCmd>, output: &str, w: u32, h: u32) -> Self {
             Self {
                 id: CHROME_ID.get_or_init(Id::new).clone(),
                 commit: CommitCounter::default(),
                 cmds,
+                output: output.to_owned(),
                 w,
                 h,
             }
         }
+
+        /// Same element with its own id, for the pointer drawn above windows.
+        pub fn cursor(cmds: Vec<DrawCmd>, output: &str, w: u32, h: u32) -> Self {
+            Self {
+                id: CURSOR_ID.get_or_init(Id::new).clone(),
+                ..Self::new(cmds, output, w, h)
+            }
+        }
     }
 
     impl Element for TwmChromeElement {
@@ -1222,13 +2828,11 @@ pub mod overlay_element {
             _damage: &[Rectangle<i32, Physical>],
             _opaque: &[Rectangle<i32, Physical>],
         ) -> Result<(), GlesError> {
-            RENDERER.with(|cell| {
-                let mut borrow = cell.borrow_mut();
-                if let Some(r) = borrow.as_mut() {
-                    // Reset GL viewport to the full output before our instanced draw.
-                    // Smithay may have scissored/viewported to a damage sub-rect.
-                    r.flush(&self.cmds);
-                }
+            with_renderer(&self.output, |r| {
+                // Reset GL viewport to the full output before our instanced draw.
+                // Smithay may have scissored/viewported to a damage sub-rect.
+                r.resize(self.w, self.h);
+                r.flush(&self.cmds);
             });
             Ok(())
         }
@@ -1338,12 +2942,13 @@ unsafe fn create_glyph_vao(prog: u32, cap: usize) -> (u32, u32) {
     inst_attr(prog, "i_glyph", 4, 0, s);
     inst_attr(prog, "i_uv", 4, 16, s);
     inst_attr(prog, "i_fg", 4, 32, s);
+    inst_attr(prog, "i_color", 1, 48, s);
     gl::BindVertexArray(0);
     (vao, ivbo)
 }
 
-unsafe fn upload_atlas(atlas: &GlyphAtlas) -> u32 {
-    let atlas_dim = ((atlas.pixels.len() / 4) as f64).sqrt() as i32;
+unsafe fn upload_atlas(page: &AtlasPage, size: u32) -> u32 {
+    let atlas_dim = size as i32;
     let mut tex = 0u32;
     gl::GenTextures(1, &mut tex);
     gl::BindTexture(gl::TEXTURE_2D, tex);
@@ -1364,25 +2969,55 @@ unsafe fn upload_atlas(atlas: &GlyphAtlas) -> u32 {
         0,
         gl::RGBA,
         gl::UNSIGNED_BYTE,
-        atlas.pixels.as_ptr() as *const _,
+        page.pixels.as_ptr() as *const _,
     );
     tex
 }
 
-unsafe fn patch_atlas(tex: u32, atlas: &GlyphAtlas) {
-    let atlas_dim = ((atlas.pixels.len() / 4) as f64).sqrt() as i32;
-    let rows = (atlas.cursor_y as i32 + atlas.row_h as i32 + 1).min(atlas_dim);
+unsafe fn upload_rgba(rgba: &[u8], w: u32, h: u32) -> u32 {
+    let mut tex = 0u32;
+    gl::GenTextures(1, &mut tex);
     gl::BindTexture(gl::TEXTURE_2D, tex);
-    gl::TexSubImage2D(
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+    gl::TexImage2D(
         gl::TEXTURE_2D,
         0,
+        gl::RGBA8 as i32,
+        w as i32,
+        h as i32,
+        0,
+        gl::RGBA,
+        gl::UNSIGNED_BYTE,
+        rgba.as_ptr() as *const _,
+    );
+    tex
+}
+
+/// Re-upload only the rows in `page.dirty_region`.
+unsafe fn patch_atlas(page: &AtlasPage, size: u32) {
+    let Some((y0, y1)) = page.dirty_region else {
+        return;
+    };
+    let atlas_dim = size;
+    let y1 = y1.min(atlas_dim);
+    if y1 <= y0 {
+        return;
+    }
+    let offset = (y0 * atlas_dim * 4) as usize;
+    gl::BindTexture(gl::TEXTURE_2D, page.tex);
+    gl::TexSubImage2D(
+        gl::TEXTURE_2D,
         0,
         0,
-        atlas_dim,
-        rows,
+        y0 as i32,
+        atlas_dim as i32,
+        (y1 - y0) as i32,
         gl::RGBA,
         gl::UNSIGNED_BYTE,
-        atlas.pixels.as_ptr() as *const _,
+        page.pixels[offset..].as_ptr() as *const _,
     );
 }
 
@@ -1424,3 +3059,378 @@ unsafe fn set_u1i(prog: u32, name: &str, v: i32) {
     let c = CString::new(name).unwrap();
     gl::Uniform1i(gl::GetUniformLocation(prog, c.as_ptr()), v);
 }
+
+/// Scissor to `[x, y, w, h]` in window pixels, or switch it off.
+unsafe fn set_scissor(rect: Option<[i32; 4]>) {
+    match rect {
+        Some([x, y, w, h]) => {
+            gl::Enable(gl::SCISSOR_TEST);
+            gl::Scissor(x, y, w, h);
+        }
+        None => gl::Disable(gl::SCISSOR_TEST),
+    }
+}
+
+// ── Tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::layout::*;
+    use super::{
+        dash_segments, hit_test, layers::parse_proc_stat, run_positions, BitmapCache, BitmapFormat,
+        Block, ClickableWidget, Color, DrawCmd, DrawContext, Icons, Paragraph, ProgressBar,
+        ProgressDirection, ShmCanvas, Spinner, SpinnerState, Style, Widget, WidgetDyn,
+    };
+
+    const AREA: Rect = Rect {
+        x: 10,
+        y: 20,
+        w: 800,
+        h: 600,
+    };
+
+    #[test]
+    fn sidebar_left() {
+        let (s, c) = Layout::sidebar(AREA, 250, SidebarSide::Left);
+        assert_eq!(s, Rect::new(10, 20, 250, 600));
+        assert_eq!(c, Rect::new(260, 20, 550, 600));
+    }
+
+    #[test]
+    fn sidebar_right() {
+        let (s, c) = Layout::sidebar(AREA, 250, SidebarSide::Right);
+        assert_eq!(s, Rect::new(560, 20, 250, 600));
+        assert_eq!(c, Rect::new(10, 20, 550, 600));
+    }
+
+    #[test]
+    fn sidebar_top() {
+        let (s, c) = Layout::sidebar(AREA, 100, SidebarSide::Top);
+        assert_eq!(s, Rect::new(10, 20, 800, 100));
+        assert_eq!(c, Rect::new(10, 120, 800, 500));
+    }
+
+    #[test]
+    fn sidebar_bottom() {
+        let (s, c) = Layout::sidebar(AREA, 100, SidebarSide::Bottom);
+        assert_eq!(s, Rect::new(10, 520, 800, 100));
+        assert_eq!(c, Rect::new(10, 20, 800, 500));
+    }
+
+    #[test]
+    fn sidebar_clamped() {
+        let (s, c) = Layout::sidebar(AREA, 900, SidebarSide::Left);
+        assert_eq!(s.w, 799);
+        assert_eq!(c, Rect::new(809, 20, 1, 600));
+        let (s, c) = Layout::sidebar(AREA, 600, SidebarSide::Bottom);
+        assert_eq!(s.h, 599);
+        assert_eq!(c.h, 1);
+    }
+
+    #[test]
+    fn centered_rect() {
+        assert_eq!(
+            Layout::centered(AREA, 200, 100),
+            Rect::new(310, 270, 200, 100)
+        );
+        assert_eq!(Layout::centered(AREA, 2000, 100).w, 800);
+    }
+
+    #[test]
+    fn clickable_registers_outer_rect() {
+        let mut ctx = DrawContext::new(AREA, 8, 16);
+        let r = Rect::new(40, 50, 120, 60);
+        ClickableWidget::new(Block::new().title("ws"), 7).render(r, &mut ctx);
+        assert_eq!(ctx.clickable_regions, vec![(r, 7)]);
+        assert!(!ctx.cmds.is_empty());
+        assert_eq!(hit_test(&ctx.clickable_regions, 40, 50), Some(7));
+        assert_eq!(hit_test(&ctx.clickable_regions, 160, 50), None);
+    }
+
+    #[test]
+    fn boxed_widgets_render_in_order() {
+        let mut ctx = DrawContext::new(AREA, 8, 16);
+        let widgets: Vec<(Box<dyn WidgetDyn>, Rect)> = vec![
+            (
+                Box::new(Paragraph::new("hello")),
+                Rect::new(10, 20, 200, 32),
+            ),
+            (Box::new(Block::new()), Rect::new(10, 60, 100, 50)),
+        ];
+        for (w, r) in widgets {
+            ctx.render_boxed(w, r);
+        }
+        assert!(matches!(
+            ctx.cmds.as_slice(),
+            [
+                DrawCmd::Text { x: 10, y: 20, text, .. },
+                DrawCmd::StrokeRect { rect, thickness: 1, .. },
+            ] if text == "hello" && *rect == Rect::new(10, 60, 100, 50)
+        ));
+    }
+
+    #[test]
+    fn icon_lookup_and_register() {
+        let mut ctx = DrawContext::new(AREA, 8, 16);
+        ctx.icon(0, 0, "clock", 16, super::Color::WHITE);
+        let DrawCmd::Icon { name, .. } = &ctx.cmds[0] else {
+            panic!("expected DrawCmd::Icon");
+        };
+        assert_eq!(Icons::lookup(name), Some('\u{F017}'));
+        assert_eq!(Icons::lookup("no-such-icon"), None);
+
+        Icons::register("test-custom", '\u{F0E7}');
+        assert_eq!(Icons::lookup("test-custom"), Some('\u{F0E7}'));
+    }
+
+    #[test]
+    fn dashed_hline_segments() {
+        let segs = dash_segments((0, 0), (99, 0), 10, 5, 1);
+        assert_eq!(segs.len(), 7);
+        for (i, r) in segs.iter().enumerate() {
+            assert_eq!(*r, [i as f32 * 15.0, 0.0, 10.0, 1.0]);
+        }
+    }
+
+    #[test]
+    fn shm_fill_rect_red() {
+        let (w, h) = (16u32, 8u32);
+        let mut shm = vec![0u8; (w * h * 4) as usize];
+        ShmCanvas::new(&mut shm, w as usize * 4, w, h).draw(&DrawCmd::FillRect {
+            rect: Rect::new(0, 0, w, h),
+            color: Color::RED,
+        });
+        assert!(shm
+            .chunks_exact(4)
+            .all(|p| u32::from_le_bytes([p[0], p[1], p[2], p[3]]) == 0xFFFF0000));
+    }
+
+    #[test]
+    fn rounded_rect_corners_are_transparent() {
+        let (w, h) = (16u32, 12u32);
+        let mut shm = vec![0u8; (w * h * 4) as usize];
+        ShmCanvas::new(&mut shm, w as usize * 4, w, h).draw(&DrawCmd::FillRoundedRect {
+            rect: Rect::new(0, 0, w, h),
+            color: Color::WHITE,
+            radius: 4,
+        });
+        let alpha = |x: u32, y: u32| shm[((y * w + x) * 4 + 3) as usize];
+        for (x, y) in [(0, 0), (w - 1, 0), (0, h - 1), (w - 1, h - 1)] {
+            assert_eq!(alpha(x, y), 0, "corner ({x}, {y})");
+        }
+        // Partly covered along the arc, solid along the edges and inside.
+        assert!((1..255).contains(&alpha(1, 1)));
+        assert_eq!(alpha(4, 0), 255);
+        assert_eq!(alpha(0, 4), 255);
+        assert_eq!(alpha(w / 2, h / 2), 255);
+
+        // A stroke leaves the middle alone.
+        let mut shm = vec![0u8; (w * h * 4) as usize];
+        ShmCanvas::new(&mut shm, w as usize * 4, w, h).draw(&DrawCmd::StrokeRoundedRect {
+            rect: Rect::new(0, 0, w, h),
+            color: Color::WHITE,
+            radius: 4,
+            thickness: 1,
+        });
+        let alpha = |x: u32, y: u32| shm[((y * w + x) * 4 + 3) as usize];
+        assert_eq!(alpha(0, 0), 0);
+        assert_eq!(alpha(w / 2, 0), 255);
+        assert_eq!(alpha(w / 2, h / 2), 0);
+    }
+
+    #[test]
+    fn clip_cuts_fills_to_the_pushed_rect() {
+        let mut ctx = DrawContext::new(AREA, 8, 16);
+        ctx.push_clip(Rect::new(20, 30, 10, 10));
+        ctx.fill_rect(Rect::new(0, 0, 100, 100), Color::RED);
+        ctx.text(200, 30, "hidden", Default::default(), None);
+        ctx.pop_clip();
+        ctx.fill_rect(Rect::new(0, 0, 100, 100), Color::RED);
+        assert!(matches!(
+            ctx.cmds.as_slice(),
+            [
+                DrawCmd::Clip { rect: Some(c) },
+                DrawCmd::FillRect { rect: clipped, .. },
+                DrawCmd::Clip { rect: None },
+                DrawCmd::FillRect { rect: full, .. },
+            ] if *c == Rect::new(20, 30, 10, 10)
+                && *clipped == Rect::new(20, 30, 10, 10)
+                && *full == Rect::new(0, 0, 100, 100)
+        ));
+
+        // Nested clips only ever shrink.
+        ctx.cmds.clear();
+        ctx.push_clip(Rect::new(0, 0, 10, 10));
+        ctx.push_clip(Rect::new(5, 5, 100, 100));
+        assert_eq!(ctx.clip(), Some(Rect::new(5, 5, 5, 5)));
+        ctx.pop_clip();
+        assert_eq!(ctx.clip(), Some(Rect::new(0, 0, 10, 10)));
+    }
+
+    #[test]
+    fn shm_canvas_honours_clip() {
+        let (w, h) = (8u32, 4u32);
+        let mut shm = vec![0u8; (w * h * 4) as usize];
+        let mut canvas = ShmCanvas::new(&mut shm, w as usize * 4, w, h);
+        canvas.draw(&DrawCmd::Clip {
+            rect: Some(Rect::new(2, 1, 2, 2)),
+        });
+        canvas.draw(&DrawCmd::FillRect {
+            rect: Rect::new(0, 0, w, h),
+            color: Color::WHITE,
+        });
+        let lit: Vec<(usize, usize)> = shm
+            .chunks_exact(4)
+            .enumerate()
+            .filter(|(_, p)| p[3] != 0)
+            .map(|(i, _)| (i % w as usize, i / w as usize))
+            .collect();
+        assert_eq!(lit, [(2, 1), (3, 1), (2, 2), (3, 2)]);
+    }
+
+    #[test]
+    fn progress_bar_fills_half_at_50_percent() {
+        let (w, h) = (20u32, 6u32);
+        let filled = |direction: ProgressDirection| {
+            let mut ctx = DrawContext::new(Rect::new(0, 0, w, h), 8, 16);
+            ProgressBar::new(0.5)
+                .style(Style::default().bg(Color::BLACK))
+                .filled_style(Style::default().bg(Color::RED))
+                .direction(direction)
+                .render(Rect::new(0, 0, w, h), &mut ctx);
+            assert!(matches!(
+                ctx.cmds.last(),
+                Some(DrawCmd::Text { text, .. }) if text == "50%"
+            ));
+            let mut shm = vec![0u8; (w * h * 4) as usize];
+            let mut canvas = ShmCanvas::new(&mut shm, w as usize * 4, w, h);
+            for cmd in &ctx.cmds {
+                canvas.draw(cmd);
+            }
+            // Little-endian ARGB: red is B=0, G=0, R=255.
+            shm.chunks_exact(4)
+                .enumerate()
+                .filter(|(_, p)| p[..3] == [0, 0, 255])
+                .map(|(i, _)| (i as u32 % w, i as u32 / w))
+                .collect::<Vec<_>>()
+        };
+        let left = filled(ProgressDirection::LeftToRight);
+        assert_eq!(left.len() as u32, w * h / 2);
+        assert!(left.iter().all(|&(x, _)| x < w / 2));
+        let right = filled(ProgressDirection::RightToLeft);
+        assert_eq!(right.len() as u32, w * h / 2);
+        assert!(right.iter().all(|&(x, _)| x >= w / 2));
+        let up = filled(ProgressDirection::BottomToTop);
+        assert_eq!(up.len() as u32, w * h / 2);
+        assert!(up.iter().all(|&(_, y)| y >= h / 2));
+    }
+
+    #[test]
+    fn block_border_radius_rounds_the_border() {
+        let mut ctx = DrawContext::new(AREA, 8, 16);
+        ctx.render_widget(Block::new().border_radius(6), Rect::new(10, 60, 100, 50));
+        assert!(matches!(
+            ctx.cmds.as_slice(),
+            [DrawCmd::StrokeRoundedRect {
+                radius: 6,
+                thickness: 1,
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn shm_hline_clipped_to_buffer() {
+        let (w, h) = (8u32, 4u32);
+        let mut shm = vec![0u8; (w * h * 4) as usize];
+        ShmCanvas::new(&mut shm, w as usize * 4, w, h).draw(&DrawCmd::HLine {
+            x: 4,
+            y: 1,
+            w: 100,
+            color: Color::WHITE,
+        });
+        let px = |x: usize, y: usize| &shm[(y * w as usize + x) * 4..][..4];
+        assert_eq!(px(3, 1), [0, 0, 0, 0]);
+        assert_eq!(px(7, 1), [255, 255, 255, 255]);
+        assert_eq!(px(7, 2), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn dashed_diagonal_steps_pixels() {
+        // 10 pixels on the diagonal, 2 on / 2 off → pixels 0,1,4,5,8,9.
+        let segs = dash_segments((0, 0), (9, 9), 2, 2, 1);
+        let xs: Vec<f32> = segs.iter().map(|r| r[0]).collect();
+        assert_eq!(xs, [0.0, 1.0, 4.0, 5.0, 8.0, 9.0]);
+        assert!(segs.iter().all(|r| r[0] == r[1]));
+    }
+
+    #[test]
+    fn spinner_full_rotation() {
+        let state = SpinnerState::new();
+        let spinner = Spinner::new(&state, 800);
+        let t = state.started + std::time::Duration::from_millis(250);
+        assert_eq!(spinner.frame_at(t), '⣻');
+        assert_eq!(
+            spinner.frame_at(t),
+            spinner.frame_at(t + std::time::Duration::from_millis(800))
+        );
+        assert_eq!(spinner.frame_at(state.started), '⣾');
+    }
+
+    #[test]
+    fn bitmap_cache_uploads_once_per_arc() {
+        let mut cache = BitmapCache::new(2);
+        let mut uploads = 0;
+        let mut upload = || {
+            uploads += 1;
+            uploads
+        };
+        let icon: std::sync::Arc<[u8]> = vec![0u8; 16].into();
+        assert_eq!(cache.get_or_upload(&icon, &mut upload), (1, None));
+        assert_eq!(cache.get_or_upload(&icon.clone(), &mut upload), (1, None));
+
+        // Equal bytes in another allocation are a different bitmap.
+        let copy: std::sync::Arc<[u8]> = vec![0u8; 16].into();
+        assert_eq!(cache.get_or_upload(&copy, &mut upload), (2, None));
+        // Touching `icon` again leaves `copy` least recent, so it goes first.
+        cache.get_or_upload(&icon, &mut upload);
+        let third: std::sync::Arc<[u8]> = vec![1u8; 4].into();
+        assert_eq!(cache.get_or_upload(&third, &mut upload), (3, Some(2)));
+        assert_eq!(uploads, 3);
+    }
+
+    #[test]
+    fn bitmap_formats_to_rgba() {
+        let bgra = BitmapFormat::Bgra8.to_rgba(&[1, 2, 3, 4], 1, 1).unwrap();
+        assert_eq!(&*bgra, &[3, 2, 1, 4]);
+        let gray = BitmapFormat::Gray8.to_rgba(&[7, 9], 2, 1).unwrap();
+        assert_eq!(&*gray, &[7, 7, 7, 255, 9, 9, 9, 255]);
+        assert!(BitmapFormat::Rgba8.to_rgba(&[0; 7], 2, 1).is_none());
+    }
+
+    #[test]
+    fn rtl_run_fills_from_right_edge() {
+        use crate::shaper::segment_str;
+        let runs = segment_str("hello عالم", false, false);
+        let mut px = 10.0;
+        let mut spans = Vec::new();
+        for run in &runs {
+            let advances = vec![8.0; run.text.chars().count()];
+            spans.push(run_positions(px, &advances, run.rtl));
+            px += advances.iter().sum::<f32>();
+        }
+        // "hello " steps right from x, "عالم" steps left from its right edge.
+        assert_eq!(spans[0], [10.0, 18.0, 26.0, 34.0, 42.0, 50.0]);
+        assert_eq!(spans[1], [82.0, 74.0, 66.0, 58.0]);
+        // No Arabic glyph starts inside the Latin run.
+        assert!(spans[1].iter().all(|&gx| gx >= spans[0][5] + 8.0));
+    }
+
+    #[test]
+    fn proc_stat_busy_and_total() {
+        let stat = "cpu  100 5 50 800 40 3 2 0 0 0\ncpu0 50 2 25 400 20 1 1 0 0 0\n";
+        // idle = idle + iowait; guest columns are already counted in user.
+        assert_eq!(parse_proc_stat(stat), Some((160, 1000)));
+        assert_eq!(parse_proc_stat("intr 1 2 3"), None);
+    }
+}
@@ -0,0 +1,124 @@
+// recorder.rs — screen recording through an ffmpeg child process
+//
+// Every frame rendered on the recorded output is read back as RGBA and piped
+// to `ffmpeg -f rawvideo` on its stdin. A writer thread owns the pipe so a
+// slow encoder never stalls the compositor: frames are queued on a small
+// bounded channel and dropped when it is full. Frames are timestamped on
+// arrival, so idle stretches (no new frames) come out at their real length.
+//
+// Dropping the Recorder closes the channel; the writer thread then closes
+// ffmpeg's stdin and waits for it to finish the file.
+
+use std::{
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+    sync::mpsc::{sync_channel, SyncSender, TrySendError},
+};
+
+/// Frames that may wait for the encoder before new ones are dropped.
+const QUEUE_FRAMES: usize = 4;
+
+/// ffmpeg arguments for a `width`×`height` RGBA stream on stdin to `out`.
+fn ffmpeg_args(width: i32, height: i32, out: &Path) -> Vec<String> {
+    let size = format!("{width}x{height}");
+    [
+        "-loglevel",
+        "error",
+        "-y",
+        "-use_wallclock_as_timestamps",
+        "1",
+        "-f",
+        "rawvideo",
+        "-pix_fmt",
+        "rgba",
+        "-video_size",
+        &size,
+        "-i",
+        "-",
+        "-fps_mode",
+        "vfr",
+        "-pix_fmt",
+        "yuv420p",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .chain(std::iter::once(out.to_string_lossy().into_owned()))
+    .collect()
+}
+
+pub struct Recorder {
+    output: String,
+    size: (i32, i32),
+    frames: SyncSender<Vec<u8>>,
+}
+
+impl Recorder {
+    /// Start ffmpeg writing `path` from frames of `output`, `size` pixels.
+    pub fn start(output: &str, size: (i32, i32), path: &Path) -> std::io::Result<Self> {
+        let mut child = Command::new("ffmpeg")
+            .args(ffmpeg_args(size.0, size.1, path))
+            .stdin(Stdio::piped())
+            .spawn()?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let (frames, rx) = sync_channel::<Vec<u8>>(QUEUE_FRAMES);
+        std::thread::Builder::new()
+            .name("recorder".into())
+            .spawn(move || {
+                for frame in rx {
+                    if let Err(e) = stdin.write_all(&frame) {
+                        tracing::warn!("recorder: ffmpeg stopped reading: {e}");
+                        break;
+                    }
+                }
+                drop(stdin);
+                match child.wait() {
+                    Ok(status) if status.success() => tracing::info!("recorder: finished"),
+                    Ok(status) => tracing::warn!("recorder: ffmpeg exited with {status}"),
+                    Err(e) => tracing::warn!("recorder: waiting for ffmpeg: {e}"),
+                }
+            })?;
+        tracing::info!("recorder: {output} → {}", path.display());
+        Ok(Self {
+            output: output.to_owned(),
+            size,
+            frames,
+        })
+    }
+
+    /// Name of the recorded output.
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+
+    /// Queue one RGBA frame. Frames of the wrong size (the mode changed) and
+    /// frames arriving while the encoder is behind are dropped.
+    pub fn push_frame(&self, rgba: Vec<u8>) {
+        if rgba.len() != (self.size.0 * self.size.1 * 4) as usize {
+            return;
+        }
+        match self.frames.try_send(rgba) {
+            Ok(()) | Err(TrySendError::Full(_)) => {}
+            Err(TrySendError::Disconnected(_)) => {
+                tracing::debug!("recorder: writer gone, frame dropped");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ffmpeg_reads_raw_rgba_from_stdin() {
+        let args = ffmpeg_args(1920, 1080, Path::new("/tmp/out.mp4"));
+        let i = args.iter().position(|a| a == "-i").unwrap();
+        assert_eq!(args[i + 1], "-");
+        assert!(args[..i]
+            .windows(2)
+            .any(|w| w == ["-video_size", "1920x1080"]));
+        assert!(args[..i].windows(2).any(|w| w == ["-pix_fmt", "rgba"]));
+        assert_eq!(args.last().unwrap(), "/tmp/out.mp4");
+    }
+}
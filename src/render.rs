@@ -21,15 +21,21 @@ pub fn apply_window_rules(state: &mut KittyCompositor, window: &Window, app_id:
         .cloned();
 
     let Some(rule) = rule else { return };
+    if let Some(twm) = &mut state.twm {
+        if rule.no_decor {
+            twm.set_no_decor(app_id);
+        }
+        if let Some(mark) = &rule.mark {
+            twm.mark_app(app_id, mark);
+        }
+    }
     if !rule.floating {
         return;
     }
 
     let output_geo = state
-        .space
-        .outputs()
-        .next()
-        .and_then(|o| state.space.output_geometry(o))
+        .get_output_for_window(window)
+        .and_then(|o| state.space.output_geometry(&o))
         .unwrap_or_default();
 
     let sz: smithay::utils::Size<i32, Logical> = rule
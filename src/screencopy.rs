@@ -0,0 +1,363 @@
+// screencopy.rs — wlr-screencopy-unstable-v1 for grim and trixie-screenshot
+//
+// Same shape as gamma_control.rs: a `ScreencopyManagerState` owning the
+// global, a `ScreencopyHandler` trait, and a `delegate_screencopy!` macro.
+//
+// Only wl_shm buffers are offered, as ARGB8888 (BGRA in memory). A frame is
+// answered with `buffer` + `buffer_done`; once the client calls `copy` the
+// request is handed to the compositor as a `ScreencopyFrame`, which the next
+// render of that output fills from an offscreen copy of the frame and
+// completes with `flags` + `ready` (or `failed`).
+
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use smithay::{
+    output::Output,
+    reexports::{
+        wayland_protocols_wlr::screencopy::v1::server::{
+            zwlr_screencopy_frame_v1::{self, ZwlrScreencopyFrameV1},
+            zwlr_screencopy_manager_v1::{self, ZwlrScreencopyManagerV1},
+        },
+        wayland_server::{
+            protocol::{wl_buffer::WlBuffer, wl_shm},
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+        },
+    },
+    utils::{Logical, Physical, Rectangle, Size},
+    wayland::shm::{with_buffer_contents, with_buffer_contents_mut},
+};
+
+const FORMAT: wl_shm::Format = wl_shm::Format::Argb8888;
+
+// ── pixels ────────────────────────────────────────────────────────────────────
+
+/// Swap the first and third byte of every pixel: BGRA ↔ RGBA.
+pub fn swap_red_blue(pixels: &mut [u8]) {
+    for px in pixels.chunks_exact_mut(4) {
+        px.swap(0, 2);
+    }
+}
+
+/// Copy `region` out of a tightly packed RGBA frame `width` pixels wide into
+/// `dst` as BGRA rows `dst_stride` bytes apart.
+fn copy_region(
+    rgba: &[u8],
+    width: i32,
+    region: Rectangle<i32, Physical>,
+    dst: &mut [u8],
+    dst_stride: usize,
+) -> Option<()> {
+    let row_bytes = region.size.w as usize * 4;
+    for y in 0..region.size.h as usize {
+        let src_start = ((region.loc.y as usize + y) * width as usize + region.loc.x as usize) * 4;
+        let src = rgba.get(src_start..src_start + row_bytes)?;
+        let dst_row = dst.get_mut(y * dst_stride..y * dst_stride + row_bytes)?;
+        dst_row.copy_from_slice(src);
+        swap_red_blue(dst_row);
+    }
+    Some(())
+}
+
+/// The buffer-pixel rectangle to capture: all of an output `mode_size`
+/// pixels large, or `logical` scaled by `scale` and cut to the output.
+fn capture_region(
+    mode_size: Size<i32, Physical>,
+    scale: f64,
+    logical: Option<Rectangle<i32, Logical>>,
+) -> Option<Rectangle<i32, Physical>> {
+    let full = Rectangle::from_size(mode_size);
+    let region = match logical {
+        Some(r) => r
+            .to_f64()
+            .to_physical(scale)
+            .to_i32_round()
+            .intersection(full)?,
+        None => full,
+    };
+    (!region.is_empty()).then_some(region)
+}
+
+// ── handler ───────────────────────────────────────────────────────────────────
+
+pub trait ScreencopyHandler {
+    /// A client wants `frame` filled in; do it on the next render of
+    /// `frame.output()`.
+    fn copy_frame(&mut self, frame: ScreencopyFrame);
+}
+
+pub struct ScreencopyFrameData {
+    output: Option<Output>,
+    region: Option<Rectangle<i32, Physical>>,
+    overlay_cursor: bool,
+    copied: AtomicBool,
+}
+
+/// A `copy` waiting for its output to be rendered.
+pub struct ScreencopyFrame {
+    frame: ZwlrScreencopyFrameV1,
+    buffer: WlBuffer,
+    output: Output,
+    region: Rectangle<i32, Physical>,
+    overlay_cursor: bool,
+    with_damage: bool,
+}
+
+impl ScreencopyFrame {
+    pub fn output(&self) -> &Output {
+        &self.output
+    }
+
+    /// Whether the client asked for the pointer to be included.
+    pub fn overlay_cursor(&self) -> bool {
+        self.overlay_cursor
+    }
+
+    /// Fill the client's buffer from `rgba`, the whole output `width` pixels
+    /// wide, and send `ready` stamped with `time` (CLOCK_MONOTONIC).
+    pub fn submit(self, rgba: &[u8], width: i32, time: Duration) {
+        let region = self.region;
+        let copied = with_buffer_contents_mut(&self.buffer, |ptr, len, data| {
+            // SAFETY: smithay maps the whole pool, `len` bytes from `ptr`.
+            let pool = unsafe { std::slice::from_raw_parts_mut(ptr, len) };
+            let dst = pool.get_mut(data.offset as usize..)?;
+            copy_region(rgba, width, region, dst, data.stride as usize)
+        });
+        if !matches!(copied, Ok(Some(()))) {
+            self.fail();
+            return;
+        }
+        self.frame.flags(zwlr_screencopy_frame_v1::Flags::empty());
+        if self.with_damage {
+            self.frame
+                .damage(0, 0, region.size.w as u32, region.size.h as u32);
+        }
+        let secs = time.as_secs();
+        self.frame
+            .ready((secs >> 32) as u32, secs as u32, time.subsec_nanos());
+    }
+
+    pub fn fail(self) {
+        self.frame.failed();
+    }
+}
+
+/// Whether `buffer` is the shm buffer advertised for `region`.
+fn buffer_matches(buffer: &WlBuffer, region: Rectangle<i32, Physical>) -> bool {
+    with_buffer_contents(buffer, |_, _, data| {
+        data.format == FORMAT
+            && data.width == region.size.w
+            && data.height == region.size.h
+            && data.stride >= region.size.w * 4
+    })
+    .unwrap_or(false)
+}
+
+pub struct ScreencopyManagerState;
+
+impl ScreencopyManagerState {
+    pub fn new<D>(dh: &DisplayHandle) -> Self
+    where
+        D: GlobalDispatch<ZwlrScreencopyManagerV1, ()>
+            + Dispatch<ZwlrScreencopyManagerV1, ()>
+            + Dispatch<ZwlrScreencopyFrameV1, ScreencopyFrameData>
+            + ScreencopyHandler
+            + 'static,
+    {
+        dh.create_global::<D, ZwlrScreencopyManagerV1, _>(3, ());
+        Self
+    }
+}
+
+impl<D> GlobalDispatch<ZwlrScreencopyManagerV1, (), D> for ScreencopyManagerState
+where
+    D: GlobalDispatch<ZwlrScreencopyManagerV1, ()>
+        + Dispatch<ZwlrScreencopyManagerV1, ()>
+        + Dispatch<ZwlrScreencopyFrameV1, ScreencopyFrameData>
+        + ScreencopyHandler
+        + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwlrScreencopyManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<D> Dispatch<ZwlrScreencopyManagerV1, (), D> for ScreencopyManagerState
+where
+    D: Dispatch<ZwlrScreencopyManagerV1, ()>
+        + Dispatch<ZwlrScreencopyFrameV1, ScreencopyFrameData>
+        + ScreencopyHandler
+        + 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _resource: &ZwlrScreencopyManagerV1,
+        request: zwlr_screencopy_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let (frame, overlay_cursor, output, logical) = match request {
+            zwlr_screencopy_manager_v1::Request::CaptureOutput {
+                frame,
+                overlay_cursor,
+                output,
+            } => (frame, overlay_cursor, output, None),
+            zwlr_screencopy_manager_v1::Request::CaptureOutputRegion {
+                frame,
+                overlay_cursor,
+                output,
+                x,
+                y,
+                width,
+                height,
+            } => (
+                frame,
+                overlay_cursor,
+                output,
+                Some(Rectangle::new((x, y).into(), (width, height).into())),
+            ),
+            _ => return,
+        };
+        let output = Output::from_resource(&output);
+        let region = output.as_ref().and_then(|o| {
+            let mode = o.current_mode()?;
+            capture_region(mode.size, o.current_scale().fractional_scale(), logical)
+        });
+        let frame = data_init.init(
+            frame,
+            ScreencopyFrameData {
+                output,
+                region,
+                overlay_cursor: overlay_cursor != 0,
+                copied: AtomicBool::new(false),
+            },
+        );
+        let Some(region) = region else {
+            frame.failed();
+            return;
+        };
+        let (w, h) = (region.size.w as u32, region.size.h as u32);
+        frame.buffer(FORMAT, w, h, w * 4);
+        if frame.version() >= 3 {
+            frame.buffer_done();
+        }
+    }
+}
+
+impl<D> Dispatch<ZwlrScreencopyFrameV1, ScreencopyFrameData, D> for ScreencopyManagerState
+where
+    D: Dispatch<ZwlrScreencopyFrameV1, ScreencopyFrameData> + ScreencopyHandler + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        resource: &ZwlrScreencopyFrameV1,
+        request: zwlr_screencopy_frame_v1::Request,
+        data: &ScreencopyFrameData,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        let (buffer, with_damage) = match request {
+            zwlr_screencopy_frame_v1::Request::Copy { buffer } => (buffer, false),
+            zwlr_screencopy_frame_v1::Request::CopyWithDamage { buffer } => (buffer, true),
+            _ => return,
+        };
+        if data.copied.swap(true, Ordering::Relaxed) {
+            resource.post_error(
+                zwlr_screencopy_frame_v1::Error::AlreadyUsed,
+                "frame already copied",
+            );
+            return;
+        }
+        let (Some(output), Some(region)) = (data.output.clone(), data.region) else {
+            resource.failed();
+            return;
+        };
+        if !buffer_matches(&buffer, region) {
+            resource.post_error(
+                zwlr_screencopy_frame_v1::Error::InvalidBuffer,
+                "buffer does not match the advertised shm format",
+            );
+            return;
+        }
+        state.copy_frame(ScreencopyFrame {
+            frame: resource.clone(),
+            buffer,
+            output,
+            region,
+            overlay_cursor: data.overlay_cursor,
+            with_damage,
+        });
+    }
+}
+
+#[macro_export]
+macro_rules! delegate_screencopy {
+    ($ty:ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::screencopy::v1::server::zwlr_screencopy_manager_v1::ZwlrScreencopyManagerV1: ()
+        ] => $crate::screencopy::ScreencopyManagerState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::screencopy::v1::server::zwlr_screencopy_manager_v1::ZwlrScreencopyManagerV1: ()
+        ] => $crate::screencopy::ScreencopyManagerState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::screencopy::v1::server::zwlr_screencopy_frame_v1::ZwlrScreencopyFrameV1: $crate::screencopy::ScreencopyFrameData
+        ] => $crate::screencopy::ScreencopyManagerState);
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bgra_to_rgba() {
+        let mut px = vec![0x10, 0x20, 0x30, 0xFF, 0xAA, 0xBB, 0xCC, 0x80];
+        swap_red_blue(&mut px);
+        assert_eq!(px, [0x30, 0x20, 0x10, 0xFF, 0xCC, 0xBB, 0xAA, 0x80]);
+    }
+
+    #[test]
+    fn region_copies_into_strided_bgra() {
+        // 3×2 RGBA frame; pixel (x, y) has red = 10 * y + x.
+        let rgba: Vec<u8> = (0..6u8)
+            .flat_map(|i| [10 * (i / 3) + i % 3, 0, 0, 255])
+            .collect();
+        let region = Rectangle::new((1, 0).into(), (2, 2).into());
+        let mut dst = vec![0u8; 2 * 12];
+        copy_region(&rgba, 3, region, &mut dst, 12).unwrap();
+        // Red lands in byte 2 of each BGRA pixel; padding stays zero.
+        assert_eq!(
+            [dst[2], dst[6], dst[14], dst[18], dst[8]],
+            [1, 2, 11, 12, 0]
+        );
+        assert!(copy_region(&rgba, 3, region, &mut dst[..8], 12).is_none());
+    }
+
+    #[test]
+    fn region_is_scaled_and_clipped() {
+        let mode = Size::from((1920, 1080));
+        assert_eq!(
+            capture_region(mode, 1.0, None),
+            Some(Rectangle::from_size(mode))
+        );
+        let r = Rectangle::new((800, 400).into(), (400, 400).into());
+        assert_eq!(
+            capture_region(mode, 2.0, Some(r)),
+            Some(Rectangle::new((1600, 800).into(), (320, 280).into()))
+        );
+        let off = Rectangle::new((5000, 0).into(), (10, 10).into());
+        assert_eq!(capture_region(mode, 1.0, Some(off)), None);
+    }
+}
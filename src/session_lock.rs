@@ -0,0 +1,150 @@
+// session_lock.rs — ext-session-lock-v1 state for swaylock / waylock
+//
+// Smithay implements the protocol itself (`SessionLockManagerState`); this is
+// the compositor's side of it. While locked, only lock surfaces are drawn and
+// only they receive input — keybinds, pointer clicks on chrome and stray
+// focus changes are all refused. VT switching is checked before the lock so
+// a crashed locker can't strand the user.
+//
+// The client is told the session is locked only once every output has
+// presented a frame drawn under the lock, as the protocol requires: until
+// then the desktop may still be on screen somewhere.
+
+use smithay::{
+    output::Output,
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    wayland::session_lock::{LockSurface, SessionLocker},
+};
+
+#[derive(Default)]
+pub struct SessionLock {
+    locked: bool,
+    surfaces: Vec<(Output, LockSurface)>,
+    /// Held until every output in `awaiting` has presented a locked frame.
+    locker: Option<SessionLocker>,
+    /// Outputs yet to present a locked frame, and whether one is queued.
+    awaiting: Vec<(String, bool)>,
+}
+
+impl SessionLock {
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Lock, confirming through `locker` once each of `outputs` has shown
+    /// a locked frame.
+    pub fn lock(&mut self, locker: Option<SessionLocker>, outputs: Vec<String>) {
+        self.locked = true;
+        self.locker = locker;
+        self.awaiting = outputs.into_iter().map(|o| (o, false)).collect();
+        self.confirm_if_shown();
+    }
+
+    /// Clear the flag and forget the lock surfaces; the client destroys them.
+    pub fn unlock(&mut self) {
+        self.locked = false;
+        self.surfaces.clear();
+        self.locker = None;
+        self.awaiting.clear();
+    }
+
+    /// Whether the client has been told the session is locked.
+    pub fn is_confirmed(&self) -> bool {
+        self.locked && self.awaiting.is_empty()
+    }
+
+    /// A frame drawn under the lock was queued on `output`.
+    pub fn frame_queued(&mut self, output: &str) {
+        if !self.locked {
+            return;
+        }
+        if let Some((_, queued)) = self.awaiting.iter_mut().find(|(o, _)| o == output) {
+            *queued = true;
+        }
+    }
+
+    /// `output` flipped. Outputs not in `live` have gone away and are no
+    /// longer waited for.
+    pub fn frame_presented(&mut self, output: &str, live: &[String]) {
+        self.awaiting
+            .retain(|(o, queued)| live.contains(o) && !(o == output && *queued));
+        self.confirm_if_shown();
+    }
+
+    fn confirm_if_shown(&mut self) {
+        if self.awaiting.is_empty() {
+            if let Some(locker) = self.locker.take() {
+                tracing::info!("session lock confirmed");
+                locker.lock();
+            }
+        }
+    }
+
+    pub fn add_surface(&mut self, output: Output, surface: LockSurface) {
+        self.surfaces.retain(|(o, _)| o != &output);
+        self.surfaces.push((output, surface));
+    }
+
+    pub fn surface_for(&self, output: &Output) -> Option<&LockSurface> {
+        self.surfaces
+            .iter()
+            .find(|(o, _)| o == output)
+            .map(|(_, s)| s)
+    }
+
+    /// The lock surface that should hold keyboard focus, if any.
+    pub fn focus_surface(&self) -> Option<WlSurface> {
+        self.surfaces.first().map(|(_, s)| s.wl_surface().clone())
+    }
+
+    /// Whether `target` may receive input: anything while unlocked, only a
+    /// lock surface while locked. `None` stands for the compositor itself —
+    /// keybinds and chrome clicks.
+    pub fn allows(&self, target: Option<&WlSurface>) -> bool {
+        !self.locked
+            || target.is_some_and(|t| self.surfaces.iter().any(|(_, s)| s.wl_surface() == t))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lock_blocks_input_until_unlocked() {
+        let mut lock = SessionLock::default();
+        assert!(lock.allows(None));
+
+        lock.lock(None, vec![]);
+        assert!(lock.is_locked());
+        assert!(!lock.allows(None));
+        assert_eq!(lock.focus_surface(), None);
+
+        lock.unlock();
+        assert!(!lock.is_locked());
+        assert!(lock.allows(None));
+    }
+
+    #[test]
+    fn lock_confirmed_once_every_output_shows_it() {
+        let mut lock = SessionLock::default();
+        let live = vec!["DP-1".to_string(), "HDMI-A-1".to_string()];
+        lock.lock(None, live.clone());
+        assert!(!lock.is_confirmed());
+
+        // A flip queued before the lock doesn't count.
+        lock.frame_presented("DP-1", &live);
+        assert!(!lock.is_confirmed());
+
+        lock.frame_queued("DP-1");
+        lock.frame_presented("DP-1", &live);
+        assert!(!lock.is_confirmed());
+
+        // HDMI-A-1 is unplugged before it gets there.
+        lock.frame_presented("DP-1", &live[..1]);
+        assert!(lock.is_confirmed());
+
+        lock.unlock();
+        assert!(!lock.is_confirmed());
+    }
+}
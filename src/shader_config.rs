@@ -41,7 +41,7 @@
 //       fragColor = texture(u_tex, v_uv);
 //   }
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     path::{Path, PathBuf},
@@ -75,7 +75,7 @@ fn bool_true() -> bool {
 // ── public types ──────────────────────────────────────────────────────────────
 
 /// A fully resolved, source-loaded shader entry.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct ShaderEntry {
     /// Identifier used by the ratatui UI and IPC socket.
     pub name: String,
@@ -84,11 +84,13 @@ pub struct ShaderEntry {
     /// Absolute path to the .glsl file on disk.
     pub path: PathBuf,
     /// Raw GLSL fragment source. Loaded at startup and on hot-reload.
+    #[serde(skip)]
     pub source: String,
     /// User-defined uniform overrides. Auto-injected names are rejected at
     /// load time to avoid silent conflicts.
     pub uniforms: HashMap<String, f32>,
     /// mtime at last successful load, used for stale-check without inotify.
+    #[serde(skip)]
     pub last_modified: Option<SystemTime>,
 }
 
@@ -123,13 +125,44 @@ impl ShaderEntry {
     }
 }
 
+/// Largest blur radius, in pixels; the blur shaders' `u_weights` array holds
+/// this many taps plus the centre one.
+pub const MAX_BLUR_RADIUS: u32 = 32;
+
+/// The built-in Gaussian blur, set from a `shaders { blur { … } }` block in
+/// the .conf files rather than shaders.json. Runs before every user pass.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BlurConfig {
+    pub enabled: bool,
+    /// Taps on each side of the centre pixel, per direction.
+    pub radius: f32,
+}
+
+impl Default for BlurConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            radius: 10.0,
+        }
+    }
+}
+
+impl BlurConfig {
+    /// Whole taps on each side, within `1..=MAX_BLUR_RADIUS`.
+    pub fn taps(&self) -> u32 {
+        (self.radius.round() as u32).clamp(1, MAX_BLUR_RADIUS)
+    }
+}
+
 // ── registry ──────────────────────────────────────────────────────────────────
 
 /// Ordered collection of all shaders defined in shaders.json.
 /// Shaders are applied in declaration order when chaining multiple passes.
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
 pub struct ShaderRegistry {
     pub entries: Vec<ShaderEntry>,
+    /// None when the config has no `shaders { blur { … } }` block.
+    pub blur: Option<BlurConfig>,
 }
 
 // Names the renderer injects automatically — block users from shadowing them.
@@ -214,7 +247,10 @@ impl ShaderRegistry {
             });
         }
 
-        Self { entries }
+        Self {
+            entries,
+            blur: None,
+        }
     }
 
     // ── queries ───────────────────────────────────────────────────────────────
@@ -224,15 +260,30 @@ impl ShaderRegistry {
         self.entries.iter().filter(|e| e.enabled)
     }
 
-    /// True if at least one shader is enabled.
+    /// The blur settings, if the built-in blur is switched on.
+    pub fn blur_enabled(&self) -> Option<&BlurConfig> {
+        self.blur.as_ref().filter(|b| b.enabled)
+    }
+
+    /// True if at least one shader, or the built-in blur, is enabled.
     pub fn any_active(&self) -> bool {
-        self.entries.iter().any(|e| e.enabled)
+        self.entries.iter().any(|e| e.enabled) || self.blur_enabled().is_some()
     }
 
     pub fn get_mut(&mut self, name: &str) -> Option<&mut ShaderEntry> {
         self.entries.iter_mut().find(|e| e.name == name)
     }
 
+    /// Entries whose source is the file at `path`, however either path is
+    /// spelled; a file may be shared by shaders with different uniforms.
+    pub fn entries_at(&mut self, path: &Path) -> impl Iterator<Item = &mut ShaderEntry> + '_ {
+        let path = path.to_path_buf();
+        let canonical = path.canonicalize().ok();
+        self.entries.iter_mut().filter(move |e| {
+            e.path == path || canonical.is_some() && e.path.canonicalize().ok() == canonical
+        })
+    }
+
     // ── mutations (used by IPC / ratatui app) ─────────────────────────────────
 
     /// Toggle a shader by name. Returns the new enabled state or None if the
@@ -295,3 +346,43 @@ pub fn validate_glsl_source(source: &str) -> Result<(), String> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, path: PathBuf) -> ShaderEntry {
+        ShaderEntry {
+            name: name.into(),
+            enabled: true,
+            path,
+            source: String::new(),
+            uniforms: HashMap::new(),
+            last_modified: None,
+        }
+    }
+
+    #[test]
+    fn entries_found_by_registered_path() {
+        let dir = std::env::temp_dir().join(format!("trixie-shaders-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("real")).unwrap();
+        std::fs::write(dir.join("real/crt.glsl"), "").unwrap();
+        std::os::unix::fs::symlink(dir.join("real"), dir.join("link")).unwrap();
+
+        // Named apart from its file, and registered through the symlink.
+        let mut shaders = ShaderRegistry {
+            entries: vec![
+                entry("retro", dir.join("link/crt.glsl")),
+                entry("other", dir.join("real/other.glsl")),
+            ],
+            blur: None,
+        };
+        let names = |s: &mut ShaderRegistry, p: &Path| -> Vec<String> {
+            s.entries_at(p).map(|e| e.name.clone()).collect()
+        };
+        assert_eq!(names(&mut shaders, &dir.join("real/crt.glsl")), ["retro"]);
+        assert_eq!(names(&mut shaders, &dir.join("link/crt.glsl")), ["retro"]);
+        assert!(names(&mut shaders, &dir.join("real/vibrance.glsl")).is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
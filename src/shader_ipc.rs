@@ -11,16 +11,23 @@
 //   { "cmd": "enable",  "name": "crt" }
 //   { "cmd": "disable", "name": "crt" }
 //   { "cmd": "reload" }
+//   { "cmd": "atlas_stats" }
+//   { "cmd": "get_shader_perf" }
 //
 // ── Response format ───────────────────────────────────────────────────────────
 //
 //   { "ok": true,  "shaders": [ { "name": "crt", "enabled": true }, … ] }
 //   { "ok": false, "error": "shader 'foo' not found" }
 //
-// The "shaders" field is always present in ok responses — the ratatui app can
-// use it to fully refresh its list view after every command.
+//   { "ok": true,  "atlas": { "total_glyphs": 412, "bytes_used": …, … } }
+//   { "ok": true,  "timings": { "crt": 183000, … } }   (GPU nanoseconds)
+//
+// The "shaders" field is present in every ok response except atlas_stats and
+// get_shader_perf — the ratatui app can use it to fully refresh its list view
+// after every command.
 
 use std::{
+    collections::HashMap,
     io::{BufRead, BufReader, Write},
     os::unix::net::{UnixListener, UnixStream},
     path::PathBuf,
@@ -31,6 +38,7 @@ use calloop::{
 };
 use serde::{Deserialize, Serialize};
 
+use crate::font::AtlasStats;
 use crate::shader_config::ShaderRegistry;
 
 // ── socket path ───────────────────────────────────────────────────────────────
@@ -50,6 +58,8 @@ pub enum IpcCommand {
     Enable { name: String },
     Disable { name: String },
     Reload,
+    AtlasStats,
+    GetShaderPerf,
 }
 
 #[derive(Debug, Serialize)]
@@ -66,6 +76,14 @@ pub enum IpcResponse {
         ok: bool,
         shaders: Vec<ShaderStatus>,
     },
+    Atlas {
+        ok: bool,
+        atlas: AtlasStats,
+    },
+    Perf {
+        ok: bool,
+        timings: HashMap<String, u64>,
+    },
     Err {
         ok: bool,
         error: String,
@@ -222,6 +240,7 @@ pub fn dispatch_command_with_registry(
     cmd: IpcCommand,
     registry: &mut ShaderRegistry,
     recompile: &mut Vec<String>,
+    timings: HashMap<String, u64>,
 ) -> IpcResponse {
     match cmd {
         IpcCommand::List => IpcResponse::ok(registry),
@@ -246,6 +265,13 @@ pub fn dispatch_command_with_registry(
             recompile.extend(changed);
             IpcResponse::ok(registry)
         }
+
+        IpcCommand::AtlasStats => match crate::pixelui::overlay_element::atlas_stats() {
+            Some(atlas) => IpcResponse::Atlas { ok: true, atlas },
+            None => IpcResponse::err("pixel UI renderer not installed"),
+        },
+
+        IpcCommand::GetShaderPerf => IpcResponse::Perf { ok: true, timings },
     }
 }
 
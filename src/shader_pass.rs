@@ -38,12 +38,15 @@
 //       }
 //       sd.compositor.queue_frame(())?;
 //   }
+//
+// The built-in blur (`shaders { blur { … } }`) is two more ping-pong passes,
+// horizontal then vertical, run ahead of the user's shaders.
 
 use std::{collections::HashMap, ffi::CString, time::Instant};
 
 use smithay::backend::renderer::gles::ffi;
 
-use crate::shader_config::ShaderRegistry;
+use crate::shader_config::{BlurConfig, ShaderRegistry, MAX_BLUR_RADIUS};
 
 // ── GLSL ──────────────────────────────────────────────────────────────────────
 
@@ -74,6 +77,49 @@ in  vec2 v_uv;
 out vec4 fragColor;
 "#;
 
+// Separable Gaussian blur: each pass reads u_radius taps either side along one
+// axis. u_weights[0] is the centre tap, u_weights[i] the pair at ±i. Its
+// length is MAX_BLUR_RADIUS + 1.
+const BLUR_HORIZ_SRC: &str = r#"
+uniform float u_weights[33];
+uniform int   u_radius;
+void main() {
+    vec2 dx = vec2(1.0 / u_resolution.x, 0.0);
+    vec4 sum = texture(u_tex, v_uv) * u_weights[0];
+    for (int i = 1; i <= u_radius; i++) {
+        vec2 off = dx * float(i);
+        sum += (texture(u_tex, v_uv + off) + texture(u_tex, v_uv - off)) * u_weights[i];
+    }
+    fragColor = sum;
+}
+"#;
+
+const BLUR_VERT_SRC: &str = r#"
+uniform float u_weights[33];
+uniform int   u_radius;
+void main() {
+    vec2 dy = vec2(0.0, 1.0 / u_resolution.y);
+    vec4 sum = texture(u_tex, v_uv) * u_weights[0];
+    for (int i = 1; i <= u_radius; i++) {
+        vec2 off = dy * float(i);
+        sum += (texture(u_tex, v_uv + off) + texture(u_tex, v_uv - off)) * u_weights[i];
+    }
+    fragColor = sum;
+}
+"#;
+
+/// One side of a normalised Gaussian kernel with `radius` taps per side:
+/// `[centre, ±1, …, ±radius]`, so the 2·radius + 1 taps sum to 1. Sigma is
+/// half the radius, which leaves the outermost taps near 2% of the centre.
+fn blur_weights(radius: u32) -> Vec<f32> {
+    let sigma = (radius as f32 / 2.0).max(0.5);
+    let raw: Vec<f32> = (0..=radius)
+        .map(|i| (-((i * i) as f32) / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let total = raw[0] + 2.0 * raw[1..].iter().sum::<f32>();
+    raw.iter().map(|w| w / total).collect()
+}
+
 // ── compiled GL program ───────────────────────────────────────────────────────
 
 struct GlProgram {
@@ -83,10 +129,20 @@ struct GlProgram {
     loc_resolution: ffi::types::GLint,
     loc_mouse: ffi::types::GLint,
     user_locs: HashMap<String, ffi::types::GLint>,
+    /// TIME_ELAPSED query wrapped around this program's draw; 0 when the
+    /// driver lacks GL_EXT_disjoint_timer_query.
+    timer_query: ffi::types::GLuint,
+    /// A query was issued and its result has not been read back yet.
+    query_pending: bool,
+    last_time_ns: u64,
 }
 
 impl GlProgram {
-    unsafe fn compile(frag_source: &str, user_uniform_names: &[&str]) -> Result<Self, String> {
+    unsafe fn compile(
+        frag_source: &str,
+        user_uniform_names: &[&str],
+        timed: bool,
+    ) -> Result<Self, String> {
         let gl = gl_fns();
 
         let vert = compile_shader(&gl, ffi::VERTEX_SHADER, VERT_SRC)?;
@@ -120,6 +176,11 @@ impl GlProgram {
             .map(|&n| (n.to_owned(), loc(n)))
             .collect();
 
+        let mut timer_query = 0;
+        if timed {
+            gl.GenQueries(1, &mut timer_query);
+        }
+
         Ok(Self {
             id: prog,
             loc_tex: loc("u_tex"),
@@ -127,11 +188,39 @@ impl GlProgram {
             loc_resolution: loc("u_resolution"),
             loc_mouse: loc("u_mouse"),
             user_locs,
+            timer_query,
+            query_pending: false,
+            last_time_ns: 0,
         })
     }
 
+    /// Read back the previous frame's query without stalling: if the GPU
+    /// has not finished it yet, keep the old value and try again next frame.
+    unsafe fn benchmark_pass(&mut self, gl: &ffi::Gles2) {
+        if !self.query_pending {
+            return;
+        }
+        let mut available = 0;
+        gl.GetQueryObjectuiv(
+            self.timer_query,
+            ffi::QUERY_RESULT_AVAILABLE,
+            &mut available,
+        );
+        if available == 0 {
+            return;
+        }
+        let mut ns = 0;
+        gl.GetQueryObjectuiv(self.timer_query, ffi::QUERY_RESULT, &mut ns);
+        self.last_time_ns = ns as u64;
+        self.query_pending = false;
+    }
+
     unsafe fn delete(&self) {
-        gl_fns().DeleteProgram(self.id);
+        let gl = gl_fns();
+        if self.timer_query != 0 {
+            gl.DeleteQueries(1, &self.timer_query);
+        }
+        gl.DeleteProgram(self.id);
     }
 }
 
@@ -286,30 +375,92 @@ impl SceneFbo {
     }
 }
 
+// ── built-in blur ─────────────────────────────────────────────────────────────
+
+struct BlurPrograms {
+    horiz: GlProgram,
+    vert: GlProgram,
+    radius: u32,
+    weights: Vec<f32>,
+}
+
+impl BlurPrograms {
+    unsafe fn compile(cfg: &BlurConfig) -> Result<Self, String> {
+        let uniforms = ["u_weights", "u_radius"];
+        let horiz = GlProgram::compile(BLUR_HORIZ_SRC, &uniforms, false)?;
+        let vert = match GlProgram::compile(BLUR_VERT_SRC, &uniforms, false) {
+            Ok(v) => v,
+            Err(e) => {
+                horiz.delete();
+                return Err(e);
+            }
+        };
+        let mut blur = Self {
+            horiz,
+            vert,
+            radius: 0,
+            weights: Vec::new(),
+        };
+        blur.set_radius(cfg.taps());
+        Ok(blur)
+    }
+
+    fn set_radius(&mut self, radius: u32) {
+        if radius != self.radius {
+            self.radius = radius;
+            self.weights = blur_weights(radius);
+        }
+    }
+
+    unsafe fn delete(&self) {
+        self.horiz.delete();
+        self.vert.delete();
+    }
+}
+
 // ── ShaderPass ────────────────────────────────────────────────────────────────
 
 pub struct ShaderPass {
     programs: HashMap<String, GlProgram>,
+    /// Compiled while `shaders { blur { enabled = true } }` is set.
+    blur: Option<BlurPrograms>,
     ping_pong: Option<FboPair>,
     scene: Option<SceneFbo>,
     /// FBO id that was bound before we replaced it, restored after render_frame.
     saved_drm_fbo: ffi::types::GLuint,
     size: (u32, u32),
     start: Instant,
+    /// GL_EXT_disjoint_timer_query support, probed on first compile.
+    timer_queries: Option<bool>,
+    /// Log a per-frame timing breakdown (`debug_shader_timing = true`).
+    pub log_timing: bool,
 }
 
 impl ShaderPass {
     pub fn new(start: Instant) -> Self {
         Self {
             programs: HashMap::new(),
+            blur: None,
             ping_pong: None,
             scene: None,
             saved_drm_fbo: 0,
             size: (0, 0),
             start,
+            timer_queries: None,
+            log_timing: false,
         }
     }
 
+    /// Last measured GPU time of each compiled shader, in nanoseconds.
+    /// Empty when timer queries are unavailable.
+    pub fn shader_timings(&self) -> HashMap<String, u64> {
+        self.programs
+            .iter()
+            .filter(|(_, p)| p.timer_query != 0)
+            .map(|(name, p)| (name.clone(), p.last_time_ns))
+            .collect()
+    }
+
     pub fn sync_programs(&mut self, registry: &ShaderRegistry) {
         self.programs
             .retain(|name, _| registry.entries.iter().any(|e| &e.name == name));
@@ -318,6 +469,25 @@ impl ShaderPass {
                 self.compile_shader_entry(entry);
             }
         }
+        self.sync_blur(registry.blur_enabled());
+    }
+
+    fn sync_blur(&mut self, cfg: Option<&BlurConfig>) {
+        match (cfg, self.blur.as_mut()) {
+            (Some(cfg), Some(blur)) => blur.set_radius(cfg.taps()),
+            (Some(cfg), None) => match unsafe { BlurPrograms::compile(cfg) } {
+                Ok(blur) => {
+                    tracing::info!("Compiled built-in blur (radius {})", blur.radius);
+                    self.blur = Some(blur);
+                }
+                Err(e) => tracing::error!("Built-in blur compile error:\n{e}"),
+            },
+            (None, _) => {
+                if let Some(old) = self.blur.take() {
+                    unsafe { old.delete() };
+                }
+            }
+        }
     }
 
     pub fn recompile_shader(&mut self, registry: &ShaderRegistry, name: &str) {
@@ -331,7 +501,10 @@ impl ShaderPass {
 
     fn compile_shader_entry(&mut self, entry: &crate::shader_config::ShaderEntry) {
         let user_names: Vec<&str> = entry.uniforms.keys().map(|s| s.as_str()).collect();
-        match unsafe { GlProgram::compile(&entry.source, &user_names) } {
+        let timed = *self
+            .timer_queries
+            .get_or_insert_with(|| unsafe { has_timer_queries(&gl_fns()) });
+        match unsafe { GlProgram::compile(&entry.source, &user_names, timed) } {
             Ok(prog) => {
                 tracing::info!("Compiled shader '{}'", entry.name);
                 self.programs.insert(entry.name.clone(), prog);
@@ -420,7 +593,7 @@ impl ShaderPass {
 
     pub fn end(&mut self, width: u32, height: u32, mouse: (f32, f32), registry: &ShaderRegistry) {
         let enabled: Vec<_> = registry.enabled().collect();
-        if enabled.is_empty() {
+        if enabled.is_empty() && registry.blur_enabled().is_none() {
             return;
         }
 
@@ -465,8 +638,34 @@ impl ShaderPass {
 
             let mut src_idx = 0usize;
 
+            // Built-in blur first, so user passes see the blurred scene.
+            if let Some(blur) = self
+                .blur
+                .as_ref()
+                .filter(|_| registry.blur_enabled().is_some())
+            {
+                for prog in [&blur.horiz, &blur.vert] {
+                    let dst_idx = 1 - src_idx;
+                    gl.BindFramebuffer(ffi::FRAMEBUFFER, pp.fbos[dst_idx]);
+                    gl.Viewport(0, 0, width as i32, height as i32);
+                    gl.UseProgram(prog.id);
+                    gl.ActiveTexture(ffi::TEXTURE0);
+                    gl.BindTexture(ffi::TEXTURE_2D, pp.textures[src_idx]);
+                    gl.Uniform1i(prog.loc_tex, 0);
+                    gl.Uniform2f(prog.loc_resolution, width as f32, height as f32);
+                    gl.Uniform1fv(
+                        prog.user_locs["u_weights"],
+                        blur.weights.len() as i32,
+                        blur.weights.as_ptr(),
+                    );
+                    gl.Uniform1i(prog.user_locs["u_radius"], blur.radius as i32);
+                    gl.DrawArrays(ffi::TRIANGLES, 0, 3);
+                    src_idx = dst_idx;
+                }
+            }
+
             for entry in &enabled {
-                let Some(prog) = self.programs.get(&entry.name) else {
+                let Some(prog) = self.programs.get_mut(&entry.name) else {
                     tracing::warn!(
                         "Shader '{}' enabled but not compiled — skipping",
                         entry.name
@@ -503,10 +702,39 @@ impl ShaderPass {
                     }
                 }
 
-                gl.DrawArrays(ffi::TRIANGLES, 0, 3);
+                if prog.timer_query != 0 {
+                    prog.benchmark_pass(&gl);
+                    gl.BeginQuery(TIME_ELAPSED_EXT, prog.timer_query);
+                    gl.DrawArrays(ffi::TRIANGLES, 0, 3);
+                    gl.EndQuery(TIME_ELAPSED_EXT);
+                    prog.query_pending = true;
+                } else {
+                    gl.DrawArrays(ffi::TRIANGLES, 0, 3);
+                }
                 src_idx = dst_idx;
             }
 
+            // A disjoint event (clock change, GPU reset) invalidates every
+            // query in flight — drop them rather than report garbage.
+            let mut disjoint = 0;
+            gl.GetIntegerv(GPU_DISJOINT_EXT, &mut disjoint);
+            if disjoint != 0 {
+                for prog in self.programs.values_mut() {
+                    prog.query_pending = false;
+                }
+            }
+
+            if self.log_timing && self.timer_queries == Some(true) {
+                let breakdown: Vec<String> = enabled
+                    .iter()
+                    .filter_map(|e| {
+                        let p = self.programs.get(&e.name)?;
+                        Some(format!("{}={:.3}ms", e.name, p.last_time_ns as f64 / 1e6))
+                    })
+                    .collect();
+                tracing::debug!("shader timing: {}", breakdown.join(" "));
+            }
+
             // ── blit final result into DRM FBO ────────────────────────────────
             // Write-only into the DRM buffer — we never read back from it.
             // Blit with Y-flip: the DRM buffer expects bottom-up, our FBOs
@@ -539,6 +767,9 @@ impl Drop for ShaderPass {
         for (_, prog) in self.programs.drain() {
             unsafe { prog.delete() };
         }
+        if let Some(blur) = self.blur.take() {
+            unsafe { blur.delete() };
+        }
         unsafe {
             if let Some(s) = self.scene.take() {
                 s.delete();
@@ -552,6 +783,25 @@ impl Drop for ShaderPass {
 
 // ── GL helpers ────────────────────────────────────────────────────────────────
 
+// GL_EXT_disjoint_timer_query enums — not part of the generated GLES bindings.
+const TIME_ELAPSED_EXT: ffi::types::GLenum = 0x88BF;
+const GPU_DISJOINT_EXT: ffi::types::GLenum = 0x8FBB;
+
+unsafe fn has_timer_queries(gl: &ffi::Gles2) -> bool {
+    let ptr = gl.GetString(ffi::EXTENSIONS);
+    if ptr.is_null() {
+        return false;
+    }
+    let exts = std::ffi::CStr::from_ptr(ptr as *const _).to_string_lossy();
+    let found = exts
+        .split_whitespace()
+        .any(|e| e == "GL_EXT_disjoint_timer_query");
+    if !found {
+        tracing::info!("GL_EXT_disjoint_timer_query unavailable — shader timing disabled");
+    }
+    found
+}
+
 unsafe fn gl_fns() -> ffi::Gles2 {
     ffi::Gles2::load_with(|s| {
         let c = CString::new(s).unwrap();
@@ -587,3 +837,25 @@ unsafe fn compile_shader(
     }
     Ok(shader)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blur_weights_sum_to_one() {
+        for radius in [1, 5, 10, MAX_BLUR_RADIUS] {
+            let w = blur_weights(radius);
+            assert_eq!(w.len(), radius as usize + 1);
+            let sum = w[0] + 2.0 * w[1..].iter().sum::<f32>();
+            assert!((sum - 1.0).abs() < 1e-5, "radius {radius}: {sum}");
+            assert!(w.windows(2).all(|p| p[0] > p[1]));
+        }
+    }
+
+    #[test]
+    fn blur_shaders_size_weights_for_max_radius() {
+        let decl = format!("u_weights[{}]", MAX_BLUR_RADIUS + 1);
+        assert!(BLUR_HORIZ_SRC.contains(&decl) && BLUR_VERT_SRC.contains(&decl));
+    }
+}
@@ -1,6 +1,6 @@
 // shaper.rs — HarfBuzz run shaping for ligature support via rustybuzz
 
-use rustybuzz::{Face, GlyphBuffer, UnicodeBuffer};
+use rustybuzz::{Direction, Face, GlyphBuffer, UnicodeBuffer};
 
 // ── output types ──────────────────────────────────────────────────────────────
 
@@ -11,12 +11,24 @@ pub struct ShapedGlyph {
     /// Number of input *characters* this glyph consumes.
     /// 1 for normal glyphs, >1 for ligatures (e.g. "=>" → 2).
     pub cluster_width: usize,
+    /// Byte offset in the shaped text of the cluster this glyph belongs to.
+    pub cluster: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TextDirection {
+    Ltr,
+    Rtl,
+    /// Pick per run: Hebrew and Arabic runs are right-to-left.
+    #[default]
+    Auto,
 }
 
 // ── shaper ────────────────────────────────────────────────────────────────────
 
 pub struct Shaper {
     face: Face<'static>,
+    direction: TextDirection,
 }
 
 impl Shaper {
@@ -24,7 +36,25 @@ impl Shaper {
     /// before passing it in. Font data lives for the whole process anyway.
     pub fn new(font_data: &'static [u8]) -> Self {
         let face = Face::from_slice(font_data, 0).expect("rustybuzz: failed to parse font face");
-        Self { face }
+        Self {
+            face,
+            direction: TextDirection::Auto,
+        }
+    }
+
+    /// Force every run to `dir`; `Ltr` turns bidi handling off.
+    pub fn with_direction(mut self, dir: TextDirection) -> Self {
+        self.direction = dir;
+        self
+    }
+
+    /// Direction `run` is laid out in — never `Auto`.
+    pub fn run_direction(&self, run: &Run) -> TextDirection {
+        match self.direction {
+            TextDirection::Auto if run.rtl => TextDirection::Rtl,
+            TextDirection::Auto => TextDirection::Ltr,
+            dir => dir,
+        }
     }
 
     /// Shape a single visual run (homogeneous style, no newlines).
@@ -34,6 +64,12 @@ impl Shaper {
     /// Only call this for non-synthetic runs. Synthetic characters (box drawing
     /// etc.) must be looked up by char via `GlyphAtlas::glyph()` directly.
     pub fn shape(&self, text: &str) -> Vec<ShapedGlyph> {
+        self.shape_with_direction(text, self.direction)
+    }
+
+    /// `shape` with an explicit direction. Glyphs come back in visual
+    /// order, so an `Rtl` run starts with its last character.
+    pub fn shape_with_direction(&self, text: &str, dir: TextDirection) -> Vec<ShapedGlyph> {
         if text.is_empty() {
             return vec![];
         }
@@ -41,23 +77,38 @@ impl Shaper {
         buf.push_str(text);
         // Let rustybuzz auto-detect script/language — do NOT pre-specify LATIN,
         // as that panics when the face reports a different script (e.g. Zzzz).
+        match dir {
+            TextDirection::Ltr => buf.set_direction(Direction::LeftToRight),
+            TextDirection::Rtl => buf.set_direction(Direction::RightToLeft),
+            TextDirection::Auto => {}
+        }
         let output: GlyphBuffer = rustybuzz::shape(&self.face, &[], buf);
-        let positions = output.glyph_positions();
         let infos = output.glyph_infos();
 
-        let _ = positions; // positions not used — we advance by cell_w
+        // Clusters only ascend for LTR output, so find each cluster's end
+        // by its next larger start rather than by the neighbouring glyph.
+        let mut starts: Vec<usize> = infos.iter().map(|g| g.cluster as usize).collect();
+        starts.sort_unstable();
+        starts.dedup();
 
         let mut result = Vec::with_capacity(infos.len());
-        for i in 0..infos.len() {
-            let cluster_byte = infos[i].cluster as usize;
-            let next_cluster_byte = infos
-                .get(i + 1)
-                .map(|g| g.cluster as usize)
+        for (i, info) in infos.iter().enumerate() {
+            let cluster_byte = info.cluster as usize;
+            let shares_cluster = infos.get(i + 1).is_some_and(|g| g.cluster == info.cluster);
+            let end = starts
+                .iter()
+                .copied()
+                .find(|&s| s > cluster_byte)
                 .unwrap_or(text.len());
-            let cluster_chars = text[cluster_byte..next_cluster_byte].chars().count();
+            let cluster_chars = if shares_cluster {
+                0
+            } else {
+                text[cluster_byte..end].chars().count()
+            };
             result.push(ShapedGlyph {
-                glyph_id: infos[i].glyph_id,
+                glyph_id: info.glyph_id,
                 cluster_width: cluster_chars.max(1),
+                cluster: cluster_byte,
             });
         }
         result
@@ -75,19 +126,37 @@ pub struct Run {
     /// If true, skip HarfBuzz and render each char independently
     /// (box drawing, block elements, braille, Powerline).
     pub synthetic: bool,
+    /// Hebrew or Arabic text, laid out right-to-left when bidi is on.
+    pub rtl: bool,
 }
 
 /// Split a string into shaped/synthetic runs.
 ///
 /// `cells` is a slice of `(char, bold, italic)` triples as produced by the
-/// terminal cell grid. Runs are split on changes of style OR synthetic status,
-/// so box-drawing characters never get mixed into a shaped run.
+/// terminal cell grid. Runs are split on changes of style, synthetic status
+/// or direction, so box-drawing characters never get mixed into a shaped run.
+/// Whitespace stays with the run before it, keeping multi-word RTL text whole,
+/// except where an RTL run gives way to left-to-right text: its trailing
+/// whitespace is split off as a run of its own, so it isn't drawn on the far
+/// (left) side of the RTL text.
 pub fn segment_row(cells: &[(char, bool, bool)]) -> Vec<Run> {
     let mut runs: Vec<Run> = Vec::new();
     for (col, &(ch, bold, italic)) in cells.iter().enumerate() {
         let synthetic = is_synthetic(ch as u32);
+        if !ch.is_whitespace() && !is_rtl(ch as u32) {
+            split_trailing_space(&mut runs);
+        }
         if let Some(last) = runs.last_mut() {
-            if last.synthetic == synthetic && last.bold == bold && last.italic == italic {
+            let rtl = if ch.is_whitespace() {
+                last.rtl
+            } else {
+                is_rtl(ch as u32)
+            };
+            if last.synthetic == synthetic
+                && last.bold == bold
+                && last.italic == italic
+                && last.rtl == rtl
+            {
                 last.text.push(ch);
                 continue;
             }
@@ -98,11 +167,34 @@ pub fn segment_row(cells: &[(char, bool, bool)]) -> Vec<Run> {
             bold,
             italic,
             synthetic,
+            rtl: is_rtl(ch as u32),
         });
     }
+    split_trailing_space(&mut runs);
     runs
 }
 
+/// Move the trailing whitespace of an RTL last run into a run of its own.
+fn split_trailing_space(runs: &mut Vec<Run>) {
+    let Some(last) = runs.last_mut().filter(|r| r.rtl) else {
+        return;
+    };
+    let kept = last.text.trim_end().len();
+    if kept == 0 || kept == last.text.len() {
+        return;
+    }
+    let space = last.text.split_off(kept);
+    let run = Run {
+        start_col: last.start_col + last.text.chars().count(),
+        text: space,
+        bold: last.bold,
+        italic: last.italic,
+        synthetic: last.synthetic,
+        rtl: false,
+    };
+    runs.push(run);
+}
+
 /// Segment a plain `&str` into runs, treating every character as having the
 /// same bold/italic style. This is the path used by `pixelui`'s `DrawCmd::Text`
 /// handler, which has no per-character style information.
@@ -111,6 +203,12 @@ pub fn segment_str(text: &str, bold: bool, italic: bool) -> Vec<Run> {
     segment_row(&cells)
 }
 
+/// Hebrew and Arabic — the scripts laid out right-to-left.
+#[inline]
+pub fn is_rtl(cp: u32) -> bool {
+    (0x0590..=0x06FF).contains(&cp)
+}
+
 /// Returns true for characters that must be rendered programmatically rather
 /// than through the font shaping pipeline.
 #[inline]
@@ -119,6 +217,54 @@ pub fn is_synthetic(cp: u32) -> bool {
         0x2500..=0x257F |  // Box Drawing
         0x2580..=0x259F |  // Block Elements
         0x2800..=0x28FF |  // Braille Patterns
+        0x1FB00..=0x1FB92 |  // Legacy Computing: sextants, mosaics, fills
+        0x1FB94..=0x1FB97 |  // … shaded halves, checker boards
+        0x1FB9A | 0x1FB9B |  // … hourglass, bow tie
         0xE0B0 | 0xE0B1 | 0xE0B2 | 0xE0B3  // Powerline arrows
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[ignore = "needs an installed monospace font"]
+    fn clusters_are_byte_offsets() {
+        let path = crate::util::find_font("mono").expect("no monospace font installed");
+        let shaper = Shaper::new(Box::leak(std::fs::read(path).unwrap().into_boxed_slice()));
+        let clusters: Vec<usize> = shaper.shape("é→x").iter().map(|g| g.cluster).collect();
+        assert_eq!(clusters, [0, 2, 5]);
+    }
+
+    #[test]
+    fn segment_splits_rtl_runs() {
+        let runs = segment_str("hello عالم", false, false);
+        let parts: Vec<(&str, bool)> = runs.iter().map(|r| (r.text.as_str(), r.rtl)).collect();
+        assert_eq!(parts, [("hello ", false), ("عالم", true)]);
+        assert_eq!(runs[1].start_col, 6);
+
+        // Spaces between RTL words keep the run whole.
+        assert_eq!(segment_str("עולם שלום", false, false).len(), 1);
+    }
+
+    #[test]
+    fn rtl_runs_leave_trailing_space_to_what_follows() {
+        let parts = |text| {
+            segment_str(text, false, false)
+                .into_iter()
+                .map(|r| (r.text, r.rtl, r.start_col))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            parts("שלום  hi"),
+            [("שלום".into(), true, 0), ("  hi".into(), false, 4)]
+        );
+        assert_eq!(
+            parts("שלום "),
+            [("שלום".into(), true, 0), (" ".into(), false, 4)]
+        );
+        // Another RTL word still joins across the space.
+        assert_eq!(parts("שלום עולם").len(), 1);
+    }
+}
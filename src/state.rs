@@ -1,5 +1,6 @@
 use std::{
     collections::HashMap,
+    path::PathBuf,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
@@ -7,27 +8,44 @@ use std::{
     time::{Duration, Instant},
 };
 
+use crate::backend::{resume_devices, set_output_power, suspend_devices, DpmsState};
 use crate::config::Config;
+use crate::cursor::{CursorFrame, HardwareCursor};
 use crate::embedded_ipc::{EmbedCommand, EmbedIpcServer};
 use crate::embedded_window::{EmbeddedManager, EmbeddedPlacement, EmbeddedRenderElement};
+use crate::foreign_toplevel::ForeignToplevelManagerState;
+use crate::gamma_control::GammaControlManagerState;
+use crate::output_management::OutputManagementState;
+use crate::recorder::Recorder;
+use crate::screencopy::{ScreencopyFrame, ScreencopyManagerState};
+use crate::session_lock::SessionLock;
 use crate::shader_pass::ShaderPass;
 use crate::twm_drop_in::TwmState;
 
 use smithay::{
     backend::renderer::element::AsRenderElements,
     backend::{
-        allocator::gbm::{GbmAllocator, GbmDevice},
+        allocator::{
+            gbm::{GbmAllocator, GbmDevice},
+            Fourcc,
+        },
         drm::{
-            compositor::DrmCompositor, exporter::gbm::GbmFramebufferExporter, DrmDevice,
-            DrmDeviceFd, DrmNode,
+            compositor::{DrmCompositor, FrameFlags, PrimaryPlaneElement},
+            exporter::gbm::GbmFramebufferExporter,
+            DrmDevice, DrmDeviceFd, DrmNode,
         },
         renderer::{
             damage::OutputDamageTracker,
-            element::{solid::SolidColorRenderElement, surface::WaylandSurfaceRenderElement},
+            element::{
+                memory::MemoryRenderBufferRenderElement,
+                solid::SolidColorRenderElement,
+                surface::{render_elements_from_surface_tree, WaylandSurfaceRenderElement},
+                Kind,
+            },
             gles::{GlesRenderer, GlesTexture},
-            ImportDma,
+            Bind, ExportMem, ImportDma, Offscreen,
         },
-        session::libseat::LibSeatSession,
+        session::{libseat::LibSeatSession, Session},
     },
     desktop::{PopupManager, Space, Window},
     input::{
@@ -36,8 +54,11 @@ use smithay::{
     },
     output::Output,
     reexports::{
-        calloop::LoopHandle,
-        drm::control::crtc,
+        calloop::{
+            timer::{TimeoutAction, Timer},
+            LoopHandle,
+        },
+        drm::control::{connector, crtc, Mode as DrmMode},
         input::Libinput,
         wayland_server::{
             backend::{ClientId, ObjectId},
@@ -45,21 +66,41 @@ use smithay::{
             DisplayHandle,
         },
     },
-    utils::{Clock, Monotonic, SERIAL_COUNTER as SCOUNTER},
+    utils::{
+        Clock, Logical, Monotonic, Physical, Point, Rectangle, Scale, Size, Transform,
+        SERIAL_COUNTER as SCOUNTER,
+    },
     wayland::{
-        compositor::CompositorState,
+        compositor::{with_states, CompositorState},
+        cursor_shape::CursorShapeManagerState,
         dmabuf::{DmabufGlobal, DmabufState},
-        selection::{data_device::DataDeviceState, primary_selection::PrimarySelectionState},
+        fractional_scale::{with_fractional_scale, FractionalScaleManagerState},
+        idle_inhibit::IdleInhibitManagerState,
+        input_method::InputMethodManagerState,
+        pointer_constraints::PointerConstraintsState,
+        relative_pointer::RelativePointerManagerState,
+        seat::WaylandFocus,
+        selection::{
+            data_device::DataDeviceState, primary_selection::PrimarySelectionState,
+            wlr_data_control::DataControlState,
+        },
+        session_lock::SessionLockManagerState,
         shell::{
             wlr_layer::WlrLayerShellState,
             xdg::{decoration::XdgDecorationState, ToplevelSurface, XdgShellState},
         },
         shm::ShmState,
+        text_input::TextInputManagerState,
+        viewporter::ViewporterState,
     },
 };
 
-use crate::pixelui::overlay_element::TwmChromeElement;
-use ratatui::layout::Margin;
+use crate::pixelui::{
+    overlay_element::TwmChromeElement, DrawCmd, DrawContext, Rect as PixRect, Spinner, SpinnerState,
+};
+
+/// One full turn of the startup spinner.
+const STARTUP_SPINNER_PERIOD_MS: u64 = 800;
 
 // ── type alias ────────────────────────────────────────────────────────────────
 
@@ -74,6 +115,80 @@ smithay::backend::renderer::element::render_elements! {
     Cursor   = SolidColorRenderElement,
     Embedded = EmbeddedRenderElement,
     Chrome   = TwmChromeElement,
+    HwCursor = MemoryRenderBufferRenderElement<GlesRenderer>,
+}
+
+// ── captures ──────────────────────────────────────────────────────────────────
+
+/// A copy of an output's next frame, waiting for that frame to be rendered.
+pub enum Capture {
+    /// A wlr-screencopy client's frame.
+    Screencopy(ScreencopyFrame),
+    /// An IPC `screenshot`, written to `path` as PNG.
+    Png { output: String, path: PathBuf },
+}
+
+impl Capture {
+    fn output_name(&self) -> String {
+        match self {
+            Self::Screencopy(frame) => frame.output().name(),
+            Self::Png { output, .. } => output.clone(),
+        }
+    }
+
+    fn overlay_cursor(&self) -> bool {
+        match self {
+            Self::Screencopy(frame) => frame.overlay_cursor(),
+            Self::Png { .. } => true,
+        }
+    }
+
+    /// Hand over `rgba` (`size`, tightly packed), or report failure when
+    /// the read-back didn't work.
+    fn complete(self, rgba: Option<&[u8]>, size: Size<i32, Physical>, time: Duration) {
+        match (self, rgba) {
+            (Self::Screencopy(frame), Some(rgba)) => frame.submit(rgba, size.w, time),
+            (Self::Screencopy(frame), None) => frame.fail(),
+            (Self::Png { path, .. }, Some(rgba)) => {
+                // Encoding a 4K PNG takes long enough to drop frames.
+                let rgba = rgba.to_vec();
+                std::thread::spawn(move || {
+                    let (w, h) = (size.w as u32, size.h as u32);
+                    match image::save_buffer(&path, &rgba, w, h, image::ColorType::Rgba8) {
+                        Ok(()) => tracing::info!("screenshot: {}", path.display()),
+                        Err(e) => tracing::warn!("screenshot {}: {e}", path.display()),
+                    }
+                });
+            }
+            (Self::Png { path, .. }, None) => {
+                tracing::warn!("screenshot {}: read-back failed", path.display());
+            }
+        }
+    }
+}
+
+/// `elements` rendered into an offscreen buffer and read back as RGBA,
+/// exactly as the output would show them.
+fn render_offscreen(
+    renderer: &mut GlesRenderer,
+    elements: &[TrixieRenderElement],
+    size: Size<i32, Physical>,
+    scale: Scale<f64>,
+    clear: [f32; 4],
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let buffer_size = size.to_logical(1).to_buffer(1, Transform::Normal);
+    let mut texture: GlesTexture = renderer.create_buffer(Fourcc::Abgr8888, buffer_size)?;
+    let mut target = renderer.bind(&mut texture)?;
+    OutputDamageTracker::new(size, scale, Transform::Normal).render_output(
+        renderer,
+        &mut target,
+        0,
+        elements,
+        clear,
+    )?;
+    let mapping =
+        renderer.copy_framebuffer(&target, Rectangle::from_size(buffer_size), Fourcc::Abgr8888)?;
+    Ok(renderer.map_texture(&mapping)?.to_vec())
 }
 
 // ── mouse mode ────────────────────────────────────────────────────────────────
@@ -101,6 +216,318 @@ pub struct SurfaceData {
     pub next_frame_time: Instant,
     pub pending_frame: bool,
     pub frame_duration: Duration,
+    /// Kept so the compositor can be rebuilt after a GPU reset.
+    pub connector: connector::Handle,
+    pub drm_mode: DrmMode,
+    /// `VRR_ENABLED` was set on the CRTC; re-applied after a GPU reset.
+    pub vrr: bool,
+    /// A reset was seen and a rebuild is queued; rendering is skipped.
+    pub recovery_pending: bool,
+    pub recovery: RecoveryTracker,
+    /// Blanked by the idle timer while `Off`.
+    pub dpms: DpmsState,
+    /// The last frame put a client buffer straight on the primary plane.
+    /// Only used to log when that starts and stops.
+    pub direct_scanout_capable: bool,
+    /// None when the CRTC has no cursor plane; the pointer is then drawn
+    /// with the chrome.
+    pub hardware_cursor: Option<HardwareCursor>,
+    pub frame_clock: FrameClock,
+}
+
+// ── frame pacing ──────────────────────────────────────────────────────────────
+
+/// Vblanks averaged for each refresh rate log line.
+const REFRESH_LOG_FRAMES: u32 = 60;
+
+/// Vblank timestamps of one CRTC.
+#[derive(Debug, Default)]
+pub struct FrameClock {
+    last: Option<Duration>,
+    /// Time between the last two vblanks.
+    interval: Option<Duration>,
+    window_start: Option<Duration>,
+    frames: u32,
+}
+
+impl FrameClock {
+    /// Record a vblank at monotonic `time`. Every `REFRESH_LOG_FRAMES`
+    /// vblanks, returns their average rate in Hz.
+    pub fn vblank(&mut self, time: Duration) -> Option<f64> {
+        if let Some(last) = self.last.filter(|&l| time > l) {
+            self.interval = Some(time - last);
+        }
+        self.last = Some(time);
+        let start = *self.window_start.get_or_insert(time);
+        if time == start {
+            return None;
+        }
+        self.frames += 1;
+        if self.frames < REFRESH_LOG_FRAMES {
+            return None;
+        }
+        let hz = self.frames as f64 / (time - start).as_secs_f64();
+        self.window_start = Some(time);
+        self.frames = 0;
+        Some(hz)
+    }
+
+    pub fn interval(&self) -> Option<Duration> {
+        self.interval
+    }
+}
+
+/// Frame duration from the measured vblank `interval`. Only an interval
+/// within a quarter of the mode's `nominal` period counts; anything else
+/// spans skipped frames. Never shorter than `floor`, the `target_hz` cap.
+pub fn frame_interval(interval: Option<Duration>, nominal: Duration, floor: Duration) -> Duration {
+    interval
+        .filter(|i| i.abs_diff(nominal) <= nominal / 4)
+        .map_or(floor, |i| i.max(floor))
+}
+
+/// Render `crtc` at `at`, straight away if that has passed. With VRR this
+/// replaces the per-output frame timer: the next frame starts from the
+/// vblank of the last one.
+pub fn schedule_render(
+    handle: &LoopHandle<'static, KittyCompositor>,
+    node: DrmNode,
+    crtc: crtc::Handle,
+    at: Instant,
+) {
+    let wait = at.saturating_duration_since(Instant::now());
+    if wait.is_zero() {
+        handle.insert_idle(move |state| state.render_surface(node, crtc));
+    } else {
+        handle
+            .insert_source(Timer::from_duration(wait), move |_, _, state| {
+                state.render_surface(node, crtc);
+                TimeoutAction::Drop
+            })
+            .ok();
+    }
+}
+
+// ── GPU reset recovery ────────────────────────────────────────────────────────
+
+/// Rebuilds allowed inside `RECOVERY_WINDOW` before the GPU is given up on.
+const MAX_RECOVERIES: usize = 3;
+const RECOVERY_WINDOW: Duration = Duration::from_secs(5);
+/// Delay before retrying a rebuild that failed outright.
+const RECOVERY_RETRY: Duration = Duration::from_millis(500);
+
+/// Recent rebuild attempts for one surface.
+#[derive(Debug, Default)]
+pub struct RecoveryTracker {
+    attempts: Vec<Instant>,
+}
+
+impl RecoveryTracker {
+    /// Record an attempt at `now`. False once this would be more than
+    /// `MAX_RECOVERIES` attempts inside `RECOVERY_WINDOW`.
+    pub fn attempt(&mut self, now: Instant) -> bool {
+        self.attempts
+            .retain(|&t| now.saturating_duration_since(t) < RECOVERY_WINDOW);
+        if self.attempts.len() >= MAX_RECOVERIES {
+            return false;
+        }
+        self.attempts.push(now);
+        true
+    }
+}
+
+/// Whether a render_frame error means the GL context is gone and the
+/// compositor has to be rebuilt. The error is a deep generic nest of DRM,
+/// GBM and GLES errors, so this goes by the variant names in its Debug
+/// output rather than matching every layer. DeviceInactive is not one: it
+/// only means the session is switched away, and render_surface doesn't get
+/// that far while it is.
+fn is_fatal_render_error(debug: &str) -> bool {
+    debug.contains("ContextLost")
+}
+
+/// Everything needed to put a surface back after its compositor was dropped.
+struct LostSurface {
+    output: Output,
+    connector: connector::Handle,
+    drm_mode: DrmMode,
+    vrr: bool,
+    frame_duration: Duration,
+    recovery: RecoveryTracker,
+}
+
+/// The device work of putting a surface back, so the recovery sequence can
+/// run without a GPU.
+trait SurfaceRebuild {
+    type Compositor;
+    type Error: std::fmt::Display;
+    /// Replace the renderer if its context was lost; a no-op once another
+    /// surface on the same GPU has.
+    fn reset_renderer(&mut self) -> Result<(), Self::Error>;
+    fn rebuild_compositor(&mut self) -> Result<Self::Compositor, Self::Error>;
+}
+
+/// A fresh context first: a compositor rebuilt on the dead one fails its
+/// next frame all over again.
+fn recover<R: SurfaceRebuild>(gpu: &mut R) -> Result<R::Compositor, R::Error> {
+    gpu.reset_renderer()?;
+    gpu.rebuild_compositor()
+}
+
+/// `SurfaceRebuild` for `crtc` on GPU `node`.
+struct DeviceRebuild<'a> {
+    state: &'a mut KittyCompositor,
+    node: DrmNode,
+    crtc: crtc::Handle,
+    lost: &'a LostSurface,
+}
+
+impl DeviceRebuild<'_> {
+    fn backend(&mut self) -> Result<&mut BackendData, Box<dyn std::error::Error>> {
+        let backend = self.state.backends.get_mut(&self.node);
+        Ok(backend.ok_or("GPU removed")?)
+    }
+}
+
+impl SurfaceRebuild for DeviceRebuild<'_> {
+    type Compositor = GbmDrmCompositor;
+    type Error = Box<dyn std::error::Error>;
+
+    fn reset_renderer(&mut self) -> Result<(), Self::Error> {
+        let node = self.node;
+        let backend = self.backend()?;
+        if !backend.context_lost {
+            return Ok(());
+        }
+        tracing::warn!("GPU {node}: context lost, creating a new renderer");
+        crate::backend::reset_renderer(backend)?;
+        // The chrome's textures and buffers died with the old context.
+        crate::backend::reload_pixel_ui(self.state);
+        Ok(())
+    }
+
+    fn rebuild_compositor(&mut self) -> Result<GbmDrmCompositor, Self::Error> {
+        let (node, crtc, lost) = (self.node, self.crtc, self.lost);
+        crate::backend::create_compositor(
+            self.backend()?,
+            node,
+            lost.connector,
+            crtc,
+            lost.drm_mode,
+            &lost.output,
+        )
+    }
+}
+
+impl KittyCompositor {
+    /// Drop the compositor on `crtc` and rebuild it. Runs from an idle
+    /// callback queued by render_surface.
+    fn recover_surface(&mut self, node: DrmNode, crtc: crtc::Handle) {
+        let Some(backend) = self.backends.get_mut(&node) else {
+            return;
+        };
+        let Some(surface) = backend.surfaces.remove(&crtc) else {
+            return;
+        };
+        let lost = LostSurface {
+            output: surface.output,
+            connector: surface.connector,
+            drm_mode: surface.drm_mode,
+            vrr: surface.vrr,
+            frame_duration: surface.frame_duration,
+            recovery: surface.recovery,
+        };
+        self.rebuild_surface(node, crtc, lost);
+    }
+
+    fn rebuild_surface(&mut self, node: DrmNode, crtc: crtc::Handle, mut lost: LostSurface) {
+        if !lost.recovery.attempt(Instant::now()) {
+            tracing::error!(
+                "GPU {node}: {MAX_RECOVERIES} resets within {RECOVERY_WINDOW:?}, giving up on it"
+            );
+            self.space.unmap_output(&lost.output);
+            self.remove_backend(node);
+            return;
+        }
+        let Some(backend) = self.backends.get_mut(&node) else {
+            return;
+        };
+
+        tracing::warn!("GPU {node}: rebuilding compositor for {crtc:?}");
+        backend.drm.pause();
+        if let Err(e) = backend.drm.activate(false) {
+            tracing::warn!("GPU {node}: re-activating DRM device: {e}");
+        }
+        // That paused every CRTC on the device, not just this one: no vblank
+        // arrives for the frames the others had queued.
+        for (&other, s) in backend.surfaces.iter_mut() {
+            s.pending_frame = false;
+            if s.vrr {
+                schedule_render(&self.handle, node, other, Instant::now());
+            }
+        }
+
+        let rebuilt = recover(&mut DeviceRebuild {
+            state: self,
+            node,
+            crtc,
+            lost: &lost,
+        });
+        let Some(backend) = self.backends.get_mut(&node) else {
+            return;
+        };
+        match rebuilt {
+            Ok(mut compositor) => {
+                let vrr = lost.vrr
+                    && crate::backend::configure_vrr(&mut compositor, lost.connector, crtc, true);
+                backend.surfaces.insert(
+                    crtc,
+                    SurfaceData {
+                        damage_tracker: OutputDamageTracker::from_output(&lost.output),
+                        output: lost.output,
+                        compositor,
+                        next_frame_time: Instant::now(),
+                        pending_frame: false,
+                        frame_duration: lost.frame_duration,
+                        connector: lost.connector,
+                        drm_mode: lost.drm_mode,
+                        vrr,
+                        recovery_pending: false,
+                        recovery: lost.recovery,
+                        dpms: DpmsState::On,
+                        direct_scanout_capable: false,
+                        hardware_cursor: crate::backend::hardware_cursor_for(&backend.drm, crtc),
+                        frame_clock: FrameClock::default(),
+                    },
+                );
+                if vrr {
+                    schedule_render(&self.handle, node, crtc, Instant::now());
+                }
+            }
+            Err(e) => {
+                tracing::warn!("GPU {node}: rebuilding {crtc:?} failed: {e}");
+                let mut lost = Some(lost);
+                self.handle
+                    .insert_source(Timer::from_duration(RECOVERY_RETRY), move |_, _, state| {
+                        if let Some(lost) = lost.take() {
+                            state.rebuild_surface(node, crtc, lost);
+                        }
+                        TimeoutAction::Drop
+                    })
+                    .ok();
+            }
+        }
+    }
+
+    /// Forget GPU `node` and unmap every output it was driving.
+    pub fn remove_backend(&mut self, node: DrmNode) {
+        if let Some(backend) = self.backends.remove(&node) {
+            for surface in backend.surfaces.values() {
+                self.space.unmap_output(&surface.output);
+            }
+        }
+        self.refresh_output_heads();
+    }
 }
 
 // ── per-GPU data ──────────────────────────────────────────────────────────────
@@ -108,6 +535,8 @@ pub struct SurfaceData {
 pub struct BackendData {
     pub surfaces: HashMap<crtc::Handle, SurfaceData>,
     pub renderer: GlesRenderer,
+    /// A frame hit a lost context; the next rebuild replaces `renderer`.
+    pub context_lost: bool,
     pub gbm: GbmDevice<DrmDeviceFd>,
     pub drm: DrmDevice,
     pub drm_node: DrmNode,
@@ -148,6 +577,21 @@ pub struct KittyCompositor {
     pub seat_state: SeatState<Self>,
     pub data_device_state: DataDeviceState,
     pub primary_selection_state: PrimarySelectionState,
+    pub data_control_state: DataControlState,
+    pub gamma_control_state: GammaControlManagerState,
+    pub foreign_toplevel_state: ForeignToplevelManagerState,
+    pub output_management_state: OutputManagementState,
+    pub session_lock_state: SessionLockManagerState,
+    pub session_lock: SessionLock,
+    pub fractional_scale_state: FractionalScaleManagerState,
+    /// wp_viewporter: fractionally scaled clients size their buffers with it.
+    pub viewporter_state: ViewporterState,
+    pub pointer_constraints_state: PointerConstraintsState,
+    pub relative_pointer_state: RelativePointerManagerState,
+    pub text_input_state: TextInputManagerState,
+    pub input_method_state: InputMethodManagerState,
+    /// `input_method`, shared with the input-method global's bind filter.
+    pub input_method_enabled: Arc<AtomicBool>,
     pub xdg_shell_state: XdgShellState,
     pub layer_shell_state: WlrLayerShellState,
     pub twm: Option<TwmState>,
@@ -158,7 +602,14 @@ pub struct KittyCompositor {
     pub seat: Seat<Self>,
     pub pointer: PointerHandle<Self>,
     pub cursor_status: CursorImageStatus,
+    pub cursor_shape_state: CursorShapeManagerState,
     pub mouse_mode: MouseMode,
+    pub gestures: crate::input::GestureTracker,
+    /// Which key, if any, is being tapped alone; gates `bind_r` binds.
+    pub key_taps: crate::input::TapTracker,
+    /// The seat's keymap as `send_key` resolves keycodes against it; built
+    /// on first use and dropped whenever the keymap is reapplied.
+    pub send_key_keymap: Option<xkbcommon::xkb::Keymap>,
 
     pub embedded: EmbeddedManager,
     pub embed_ipc: EmbedIpcServer,
@@ -171,10 +622,313 @@ pub struct KittyCompositor {
     pub exec_once_done: bool,
     pub shader_pass: ShaderPass,
     pub start_time: Instant,
+    /// Rendering paused after `idle_timeout`; cleared by the next input event
+    /// or capture request.
+    pub suspended: bool,
+    pub last_activity: Instant,
+    pub idle_inhibit_state: IdleInhibitManagerState,
+    pub idle_inhibitors: IdleInhibitors,
+    /// Now-playing poller for the bar; None unless `mpris = true`.
+    pub mpris: Option<crate::mpris::MprisWatcher>,
+    pub clipboard: crate::clipboard::ClipboardManager,
+    pub screencopy_state: ScreencopyManagerState,
+    /// Screenshots and screencopy frames waiting for their output's next frame.
+    pub captures: Vec<Capture>,
+    /// Set by IPC `record_output`; every frame of its output is fed to it.
+    pub recorder: Option<Recorder>,
+    pub cursor_theme: crate::cursor::CursorTheme,
+    /// Drawn in place of the TWM chrome until the first frame is presented.
+    pub startup_spinner: Option<SpinnerState>,
+    /// `$XDG_RUNTIME_DIR/trixie.sock`; None if it couldn't be bound.
+    pub ipc: Option<crate::ipc_server::IpcServer>,
+}
+
+// ── output lookup ─────────────────────────────────────────────────────────────
+
+impl KittyCompositor {
+    /// The GPU and CRTC currently scanning out `output`.
+    pub fn crtc_for_output(&self, output: &Output) -> Option<(&BackendData, crtc::Handle)> {
+        self.backends.values().find_map(|b| {
+            b.surfaces
+                .iter()
+                .find(|(_, s)| &s.output == output)
+                .map(|(&crtc, _)| (b, crtc))
+        })
+    }
+
+    /// The output `window` is mostly on: of the outputs containing its
+    /// centre, the one it overlaps most. Falls back to the first output when
+    /// the window is unmapped or its centre is off every output.
+    pub fn get_output_for_window(&self, window: &Window) -> Option<Output> {
+        output_at(&self.space, self.space.element_geometry(window))
+    }
+
+    /// Tell every mapped window the fractional scale of the output it is on.
+    /// Clients that never bound wp_fractional_scale_v1 are unaffected, and
+    /// an unchanged scale sends nothing.
+    pub fn update_fractional_scales(&self) {
+        for window in self.space.elements() {
+            let Some(surface) = window.wl_surface() else {
+                continue;
+            };
+            let scale = preferred_scale(self.get_output_for_window(window).as_ref());
+            with_states(&surface, |states| {
+                with_fractional_scale(states, |fs| fs.set_preferred_scale(scale));
+            });
+        }
+    }
+}
+
+/// The output of `space` a window at `geo` is on (see `pick_output`), or
+/// the first output if it is on none or not mapped.
+fn output_at(space: &Space<Window>, geo: Option<Rectangle<i32, Logical>>) -> Option<Output> {
+    geo.and_then(|geo| {
+        let outputs = space
+            .outputs()
+            .filter_map(|o| Some((o, space.output_geometry(o)?)));
+        pick_output(geo, outputs).cloned()
+    })
+    .or_else(|| space.outputs().next().cloned())
+}
+
+/// The scale a surface on `output` should render at; 1.0 when it is on none.
+/// wp_fractional_scale_v1 carries it as a numerator over 120.
+pub fn preferred_scale(output: Option<&Output>) -> f64 {
+    output.map_or(1.0, |o| o.current_scale().fractional_scale())
+}
+
+/// The window that may be scanned out directly on `output`: the only one
+/// touching it, and covering it exactly.
+fn scanout_candidate<T>(
+    output: Rectangle<i32, Logical>,
+    windows: impl Iterator<Item = (T, Rectangle<i32, Logical>)>,
+) -> Option<T> {
+    let mut on_output = windows.filter(|(_, geo)| geo.overlaps(output));
+    let (window, geo) = on_output.next()?;
+    (on_output.next().is_none() && geo == output).then_some(window)
+}
+
+/// Everything one frame may draw on an output, by layer.
+struct FrameLayers<E> {
+    cursor: Option<E>,
+    /// The session lock surface, if it has committed.
+    lock: Vec<E>,
+    chrome: Option<E>,
+    embedded: Vec<E>,
+    space: Vec<E>,
+}
+
+impl<E> FrameLayers<E> {
+    /// The list render_frame takes, front to back, so the pointer leads.
+    /// With a session lock only the lock surface is shown, and until it has
+    /// committed nothing but the clear colour. With the TWM locked only the
+    /// black chrome curtain is. With a `scanout` window covering the output
+    /// the chrome is hidden under it anyway, and drawing it would keep the
+    /// window off the primary plane.
+    fn into_elements(self, session_locked: bool, twm_locked: bool, scanout: bool) -> Vec<E> {
+        let mut all: Vec<E> = self.cursor.into_iter().collect();
+        if session_locked {
+            all.extend(self.lock);
+            return all;
+        }
+        if !scanout {
+            all.extend(self.chrome);
+        }
+        if !twm_locked {
+            all.extend(self.embedded);
+            all.extend(self.space);
+        }
+        all
+    }
+}
+
+/// With ALLOW_CURSOR_PLANE_SCANOUT a pointer-only change just moves the
+/// plane; the primary plane is left alone. A `scanout` window may also go
+/// straight on the primary plane.
+fn frame_flags(scanout: bool) -> FrameFlags {
+    let mut flags = FrameFlags::ALLOW_CURSOR_PLANE_SCANOUT;
+    if scanout {
+        flags |= FrameFlags::ALLOW_PRIMARY_PLANE_SCANOUT;
+    }
+    flags
+}
+
+/// The output whose geometry contains the centre of `window` and shares the
+/// largest area with it.
+fn pick_output<T>(
+    window: Rectangle<i32, Logical>,
+    outputs: impl Iterator<Item = (T, Rectangle<i32, Logical>)>,
+) -> Option<T> {
+    let center = Point::<i32, Logical>::from((
+        window.loc.x + window.size.w / 2,
+        window.loc.y + window.size.h / 2,
+    ));
+    outputs
+        .filter(|(_, geo)| geo.contains(center))
+        .max_by_key(|(_, geo)| {
+            geo.intersection(window)
+                .map_or(0, |r| r.size.w as i64 * r.size.h as i64)
+        })
+        .map(|(o, _)| o)
+}
+
+// ── pointer ───────────────────────────────────────────────────────────────────
+
+impl KittyCompositor {
+    /// The themed pointer frame on `output` and its top-left corner in
+    /// physical pixels, hotspot already applied. None when the pointer is
+    /// elsewhere, hidden, or a client supplied its own cursor surface.
+    fn cursor_image(&mut self, output: &Output) -> Option<(CursorFrame, i32, i32)> {
+        let CursorImageStatus::Named(icon) = self.cursor_status.clone() else {
+            return None;
+        };
+        let out_geo = self.space.output_geometry(output)?;
+        let pos = self.pointer.current_location();
+        if !out_geo.to_f64().contains(pos) {
+            return None;
+        }
+        // Themes name some cursors only by their legacy alias (left_ptr, …).
+        let name = std::iter::once(icon.name())
+            .chain(icon.alt_names().iter().copied())
+            .find(|n| self.cursor_theme.get(n).is_some())?;
+        let millis = self.start_time.elapsed().as_millis() as u64;
+        let frame = self.cursor_theme.get(name)?.frame_at(millis)?.clone();
+        let local =
+            (pos - out_geo.loc.to_f64()).to_physical(output.current_scale().fractional_scale());
+        let x = local.x.round() as i32 - frame.xhot as i32;
+        let y = local.y.round() as i32 - frame.yhot as i32;
+        Some((frame, x, y))
+    }
+}
+
+// ── idle suspend ──────────────────────────────────────────────────────────────
+
+/// Surfaces holding a wp_idle_inhibitor_v1, once per inhibitor. Generic so
+/// the bookkeeping can be tested without a display.
+#[derive(Debug)]
+pub struct IdleInhibitors<S = WlSurface>(Vec<S>);
+
+impl<S> Default for IdleInhibitors<S> {
+    fn default() -> Self {
+        Self(Vec::new())
+    }
+}
+
+impl<S: PartialEq> IdleInhibitors<S> {
+    pub fn inhibit(&mut self, surface: S) {
+        self.0.push(surface);
+    }
+
+    /// One inhibitor on `surface` was destroyed.
+    pub fn uninhibit(&mut self, surface: &S) {
+        if let Some(i) = self.0.iter().position(|s| s == surface) {
+            self.0.remove(i);
+        }
+    }
+
+    /// `surface` is gone, and every inhibitor on it with it.
+    pub fn surface_destroyed(&mut self, surface: &S) {
+        self.0.retain(|s| s != surface);
+    }
+
+    pub fn any(&self) -> bool {
+        !self.0.is_empty()
+    }
+}
+
+impl KittyCompositor {
+    /// True while any client holds an idle inhibitor (a playing video, say);
+    /// the idle timer leaves rendering alone until it is released.
+    pub fn is_idle_inhibited(&self) -> bool {
+        self.idle_inhibitors.any()
+    }
+
+    /// Stop scanning out: pause every DRM device and make `render_surface`
+    /// a no-op. The per-output frame timers keep ticking but do nothing.
+    pub fn suspend_render(&mut self) {
+        if self.suspended {
+            return;
+        }
+        tracing::info!("Idle — suspending render");
+        // Blank first: powering off is a commit, which needs the device.
+        self.set_dpms_all(false);
+        let devices = self.backends.values_mut().map(|b| &mut b.drm);
+        suspend_devices(&mut self.suspended, devices);
+    }
+
+    /// Undo `suspend_render`: reactivate DRM and queue a full redraw.
+    pub fn resume_render(&mut self) {
+        let devices = self.backends.values_mut().map(|b| &mut b.drm);
+        if !resume_devices(&mut self.suspended, devices) {
+            return;
+        }
+        tracing::info!("Activity — resuming render");
+        // No vblank arrives for a frame queued before the pause.
+        for b in self.backends.values_mut() {
+            for s in b.surfaces.values_mut() {
+                s.pending_frame = false;
+            }
+        }
+        self.set_dpms_all(true);
+        self.handle.insert_idle(|state| state.render_all());
+    }
+
+    /// Power the display on `crtc` on or off by switching its CRTC. An
+    /// output disabled through output management (unmapped from the space)
+    /// stays off.
+    pub fn set_dpms(&mut self, node: DrmNode, crtc: crtc::Handle, on: bool) {
+        let Some(b) = self.backends.get_mut(&node) else {
+            return;
+        };
+        let Some(s) = b.surfaces.get_mut(&crtc) else {
+            return;
+        };
+        if on && self.space.output_geometry(&s.output).is_none() {
+            return;
+        }
+        let wanted = if on { DpmsState::On } else { DpmsState::Off };
+        if s.dpms != wanted && set_output_power(&mut s.compositor, crtc, wanted) {
+            s.dpms = wanted;
+            // The next commit turns the CRTC back on; a VRR output has no
+            // vblank to start it.
+            if on {
+                s.pending_frame = false;
+                schedule_render(&self.handle, node, crtc, Instant::now());
+            }
+        }
+    }
+
+    pub fn set_dpms_all(&mut self, on: bool) {
+        let outputs: Vec<(DrmNode, crtc::Handle)> = self
+            .backends
+            .iter()
+            .flat_map(|(node, b)| b.surfaces.keys().map(move |crtc| (*node, *crtc)))
+            .collect();
+        for (node, crtc) in outputs {
+            self.set_dpms(node, crtc, on);
+        }
+    }
 }
 
 // ── render ────────────────────────────────────────────────────────────────────
 
+/// The chrome for one output: the TWM's, or while `spinner` is still set,
+/// just the spinner in the middle of the screen.
+fn chrome_cmds(
+    twm: &mut TwmState,
+    spinner: Option<&SpinnerState>,
+    (cw, ch): (u32, u32),
+    (w, h): (u32, u32),
+) -> Vec<DrawCmd> {
+    let Some(spin) = spinner else {
+        return twm.build_frame_cmds(cw, ch, w, h);
+    };
+    let mut ctx = DrawContext::new(PixRect::new(0, 0, w, h), cw, ch);
+    let at = PixRect::new(w.saturating_sub(cw) / 2, h.saturating_sub(ch) / 2, cw, ch);
+    ctx.render_widget(Spinner::new(spin, STARTUP_SPINNER_PERIOD_MS), at);
+    ctx.cmds
+}
+
 impl KittyCompositor {
     pub fn render_all(&mut self) {
         let nodes: Vec<DrmNode> = self.backends.keys().copied().collect();
@@ -187,6 +941,11 @@ impl KittyCompositor {
     }
 
     pub fn render_surface(&mut self, node: DrmNode, crtc: crtc::Handle) {
+        // Switched to another VT: the device is someone else's until
+        // ActivateSession.
+        if self.suspended || !self.session.is_active() {
+            return;
+        }
         let now = Instant::now();
 
         let backend = match self.backends.get_mut(&node) {
@@ -198,27 +957,38 @@ impl KittyCompositor {
             None => return,
         };
 
-        if surface.pending_frame || now < surface.next_frame_time {
+        // Powered off: any commit would turn the CRTC back on.
+        if surface.dpms == DpmsState::Off
+            || surface.recovery_pending
+            || surface.pending_frame
+            || now < surface.next_frame_time
+        {
             return;
         }
 
         let output = surface.output.clone();
+        // Disabled through output management: its CRTC is powered off and
+        // stays dark until the output is mapped again.
+        if self.space.output_geometry(&output).is_none() {
+            return;
+        }
+        let output_name = output.name();
 
         // Use physical pixel dimensions — must match what set_viewport() received
         // (drm_mode.size() in backend.rs). output.current_mode().size is in logical
         // pixels and differs from physical when output scale != 1, which would desync
         // DrawCmd pixel coordinates from the u_vp NDC projection in the shader.
-        let (output_w, output_h) = crate::pixelui::overlay_element::get_viewport();
+        let (output_w, output_h) = crate::pixelui::overlay_element::get_viewport(&output_name);
 
         let scale = smithay::utils::Scale::from(output.current_scale().fractional_scale());
         let clear: [f32; 4] = {
-            let c = self.config.background_color;
+            let c = self.config.background_for(&output_name);
             [c[0], c[1], c[2], 1.0]
         };
 
         // ── 1. TWM chrome ─────────────────────────────────────────────────────
         let chrome_el: Option<TwmChromeElement> = if let Some(twm) = &mut self.twm {
-            let (cw, ch) = crate::pixelui::overlay_element::cell_size();
+            let (cw, ch) = crate::pixelui::overlay_element::cell_size(&output_name);
             if crate::pixelui::overlay_element::is_installed() {
                 let cols = (output_w / cw).max(1) as u16;
                 let rows = (output_h / ch).max(1) as u16;
@@ -235,7 +1005,20 @@ impl KittyCompositor {
                 if twm.cols != cols || twm.rows != rows {
                     twm.resize(cols, rows);
                 }
-                let cmds = twm.build_frame_cmds(cw, ch, output_w, output_h);
+                twm.bar_media = self
+                    .mpris
+                    .as_ref()
+                    .and_then(|m| m.status())
+                    .map(|s| s.bar_label(self.config.mpris_max_title_len));
+                let mut cmds = chrome_cmds(
+                    twm,
+                    self.startup_spinner.as_ref(),
+                    (cw, ch),
+                    (output_w, output_h),
+                );
+                if self.config.debug_atlas_overlay {
+                    cmds.push(DrawCmd::AtlasDebugOverlay { size: 256 });
+                }
                 tracing::info!(
                     "chrome: cell={}x{} output={}x{} cols={} rows={} cmds={}",
                     cw,
@@ -246,7 +1029,12 @@ impl KittyCompositor {
                     rows,
                     cmds.len()
                 );
-                Some(TwmChromeElement::new(cmds, output_w, output_h))
+                Some(TwmChromeElement::new(
+                    cmds,
+                    &output_name,
+                    output_w,
+                    output_h,
+                ))
             } else {
                 tracing::warn!("chrome: cell_size is (8,16) default — renderer not installed");
                 None
@@ -256,38 +1044,18 @@ impl KittyCompositor {
             None
         };
 
-        // ── 2. Sync embedded placements from TWM layout ───────────────────────
-        let embedded_rects: Vec<(String, i32, i32, i32, i32)> = if let Some(twm) = &self.twm {
-            let (cw, ch) = crate::pixelui::overlay_element::cell_size();
-            twm.all_embedded_cell_rects()
-                .into_iter()
-                .map(|(app_id, r)| {
-                    let inner = r.inner(&Margin {
-                        horizontal: 1,
-                        vertical: 1,
-                    });
-                    let px = inner.x as i32 * cw as i32;
-                    let py = inner.y as i32 * ch as i32;
-                    let pw = inner.width as i32 * cw as i32;
-                    let ph = inner.height as i32 * ch as i32;
-                    (app_id, px, py, pw, ph)
-                })
-                .collect()
-        } else {
-            vec![]
-        };
-        for (app_id, px, py, pw, ph) in embedded_rects {
-            self.embedded.update_placement(
-                &app_id,
-                EmbeddedPlacement {
-                    x: px,
-                    y: py,
-                    w: pw,
-                    h: ph,
-                },
-            );
+        // ── 2. Queue settled embedded geometry from TWM layout ────────────────
+        // TwmState only emits a Move once a pane's animation has finished, so
+        // clients are not flooded with in-flight sizes. process_embed_ipc()
+        // applies these on the next tick.
+        if let Some(twm) = &mut self.twm {
+            for cmd in twm.take_embedded_moves() {
+                self.embed_ipc.push(cmd);
+            }
         }
 
+        let cursor = self.cursor_image(&output);
+
         // Re-borrow backend mutably after the self.twm / self.embedded work.
         let backend = match self.backends.get_mut(&node) {
             Some(b) => b,
@@ -298,23 +1066,72 @@ impl KittyCompositor {
             None => return,
         };
 
+        // The pointer goes on the cursor plane when the CRTC has one and the
+        // image fits; otherwise it is drawn with the chrome.
+        let cursor_el = cursor.and_then(|(frame, x, y)| match &mut surface.hardware_cursor {
+            Some(hw) if hw.fits(&frame) => MemoryRenderBufferRenderElement::from_buffer(
+                &mut backend.renderer,
+                (x as f64, y as f64),
+                hw.buffer(&frame),
+                None,
+                None,
+                None,
+                Kind::Cursor,
+            )
+            .inspect_err(|e| tracing::warn!("cursor upload failed: {e}"))
+            .ok()
+            .map(TrixieRenderElement::HwCursor),
+            _ => {
+                let cmd = DrawCmd::Image {
+                    x,
+                    y,
+                    w: frame.width,
+                    h: frame.height,
+                    rgba: frame.rgba,
+                };
+                let el = TwmChromeElement::cursor(vec![cmd], &output_name, output_w, output_h);
+                Some(TrixieRenderElement::Chrome(el))
+            }
+        });
+
         // ── 3. Embedded quads ─────────────────────────────────────────────────
+        // Covered tabs and other workspaces' windows stay hidden.
+        let visible = self.twm.as_ref().map(|t| t.visible_embedded_apps());
         let embedded_elements: Vec<TrixieRenderElement> = self
             .embedded
-            .render_elements()
+            .render_elements(visible.as_deref())
             .into_iter()
             .map(TrixieRenderElement::Embedded)
             .collect();
 
         // ── 4. Space elements ─────────────────────────────────────────────────
+        // A lone window covering the whole output is offered for direct
+        // scanout. The chrome is left out under it; render_frame still
+        // composites if the pointer has no cursor plane to go on.
+        let locked = self.session_lock.is_locked() || self.twm.as_ref().is_some_and(|t| t.locked);
+        let scanout = self
+            .space
+            .output_geometry(&output)
+            .filter(|_| self.config.direct_scanout && !locked && embedded_elements.is_empty())
+            .and_then(|geo| {
+                let windows = self
+                    .space
+                    .elements()
+                    .filter_map(|w| Some((w, self.space.element_geometry(w)?)));
+                scanout_candidate(geo, windows)
+            });
         let space_elements: Vec<TrixieRenderElement> = self
             .space
             .elements()
             .flat_map(|w| {
-                let loc = self.space.element_location(w).unwrap_or_default();
+                let loc = self
+                    .space
+                    .element_location(w)
+                    .unwrap_or_default()
+                    .to_physical_precise_round(scale);
                 w.render_elements::<WaylandSurfaceRenderElement<GlesRenderer>>(
                     &mut backend.renderer,
-                    loc.to_physical_precise_round(scale),
+                    loc,
                     scale,
                     1.0,
                 )
@@ -322,50 +1139,156 @@ impl KittyCompositor {
             .map(TrixieRenderElement::Space)
             .collect();
 
-        // ── 5. Assemble — chrome first (bottom), windows on top ───────────────
-        let mut all: Vec<TrixieRenderElement> = Vec::new();
-        if let Some(el) = chrome_el {
-            all.push(TrixieRenderElement::Chrome(el));
+        // ── 4b. Session lock surface ──────────────────────────────────────────
+        let lock_elements: Vec<TrixieRenderElement> = match self.session_lock.surface_for(&output) {
+            Some(lock) => render_elements_from_surface_tree(
+                &mut backend.renderer,
+                lock.wl_surface(),
+                (0, 0),
+                scale,
+                1.0,
+                Kind::Unspecified,
+            ),
+            None => Vec::new(),
+        };
+
+        // ── 5. Assemble — front to back: pointer, chrome, windows ─────────────
+        let cursor_len = usize::from(cursor_el.is_some());
+        let all = FrameLayers {
+            cursor: cursor_el,
+            lock: lock_elements,
+            chrome: chrome_el.map(TrixieRenderElement::Chrome),
+            embedded: embedded_elements,
+            space: space_elements,
         }
-        all.extend(embedded_elements);
-        all.extend(space_elements);
+        .into_elements(
+            self.session_lock.is_locked(),
+            self.twm.as_ref().is_some_and(|t| t.locked),
+            scanout.is_some(),
+        );
 
         tracing::info!("render_surface: {} total elements", all.len(),);
 
         // ── 6. Render frame ───────────────────────────────────────────────────
+        let flags = frame_flags(scanout.is_some());
         let render_result = surface.compositor.render_frame::<_, TrixieRenderElement>(
             &mut backend.renderer,
             &all,
             clear,
-            smithay::backend::drm::compositor::FrameFlags::empty(),
+            flags,
         );
 
         match render_result {
             Ok(frame) => {
+                let direct = matches!(frame.primary_element, PrimaryPlaneElement::Element(_));
+                if direct != surface.direct_scanout_capable {
+                    if direct {
+                        tracing::info!("{}: direct scanout active", output.name());
+                    } else {
+                        tracing::info!("{}: direct scanout off, compositing", output.name());
+                    }
+                    surface.direct_scanout_capable = direct;
+                }
                 if !frame.is_empty {
                     match surface.compositor.queue_frame(()) {
-                        Ok(()) => surface.pending_frame = true,
+                        Ok(()) => {
+                            surface.pending_frame = true;
+                            // Something is on screen: the chrome takes over.
+                            self.startup_spinner = None;
+                            self.session_lock.frame_queued(&output.name());
+                        }
                         Err(e) => tracing::warn!("queue_frame({node},{crtc:?}): {e}"),
                     }
                 } else {
                     tracing::info!("render_surface: frame was empty (no damage)");
                 }
             }
-            Err(e) => tracing::warn!("render_frame({node},{crtc:?}): {e}"),
+            Err(e) => {
+                tracing::warn!("render_frame({node},{crtc:?}): {e}");
+                if is_fatal_render_error(&format!("{e:?}")) {
+                    backend.context_lost = true;
+                    surface.recovery_pending = true;
+                    self.handle
+                        .insert_idle(move |state| state.recover_surface(node, crtc));
+                }
+            }
         }
 
         surface.next_frame_time = now + surface.frame_duration;
+        // Without a flip in flight no vblank will start the next frame.
+        if surface.vrr && !surface.pending_frame {
+            schedule_render(&self.handle, node, crtc, surface.next_frame_time);
+        }
+
+        // ── 7. Captures and recording ─────────────────────────────────────────
+        // The same elements are rendered once more offscreen and read back:
+        // at most twice a frame, with and without the pointer.
+        let name = output.name();
+        let recorder = self.recorder.as_ref().filter(|r| r.output() == name);
+        if recorder.is_none() && !self.captures.iter().any(|c| c.output_name() == name) {
+            return;
+        }
+        let Some(size) = output.current_mode().map(|m| m.size) else {
+            return;
+        };
+        let (captures, rest) = std::mem::take(&mut self.captures)
+            .into_iter()
+            .partition::<Vec<_>, _>(|c| c.output_name() == name);
+        self.captures = rest;
+
+        let mut pixels: [Option<Option<Vec<u8>>>; 2] = [None, None];
+        let mut read_back = |with_cursor: bool| -> Option<Vec<u8>> {
+            pixels[usize::from(with_cursor)]
+                .get_or_insert_with(|| {
+                    let skip = if with_cursor { 0 } else { cursor_len };
+                    render_offscreen(&mut backend.renderer, &all[skip..], size, scale, clear)
+                        .inspect_err(|e| tracing::warn!("capture of {name} failed: {e}"))
+                        .ok()
+                })
+                .clone()
+        };
+        let time = Duration::from(self.clock.now());
+        for capture in captures {
+            let rgba = read_back(capture.overlay_cursor());
+            capture.complete(rgba.as_deref(), size, time);
+        }
+        if let (Some(recorder), Some(rgba)) = (recorder, read_back(true)) {
+            recorder.push_frame(rgba);
+        }
     }
 
-    pub fn frame_finish(&mut self, node: DrmNode, crtc: crtc::Handle) {
-        if let Some(b) = self.backends.get_mut(&node) {
-            if let Some(s) = b.surfaces.get_mut(&crtc) {
-                s.pending_frame = false;
-                if let Err(e) = s.compositor.frame_submitted() {
-                    tracing::warn!("frame_submitted({node},{crtc:?}): {e}");
-                }
+    /// A flip on `crtc` completed at monotonic `time`, if the driver said.
+    pub fn frame_finish(&mut self, node: DrmNode, crtc: crtc::Handle, time: Option<Duration>) {
+        let Some(s) = self
+            .backends
+            .get_mut(&node)
+            .and_then(|b| b.surfaces.get_mut(&crtc))
+        else {
+            return;
+        };
+        s.pending_frame = false;
+        if let Err(e) = s.compositor.frame_submitted() {
+            tracing::warn!("frame_submitted({node},{crtc:?}): {e}");
+        }
+        if let Some(time) = time {
+            if let Some(hz) = s.frame_clock.vblank(time) {
+                tracing::debug!("{}: {hz:.1} Hz", s.output.name());
             }
+            if !s.vrr {
+                let hz = s.drm_mode.vrefresh().max(1) as u64;
+                s.frame_duration = frame_interval(
+                    s.frame_clock.interval(),
+                    Duration::from_secs(1) / hz as u32,
+                    self.config.frame_duration_for(hz),
+                );
+            }
+        }
+        if s.vrr {
+            schedule_render(&self.handle, node, crtc, s.next_frame_time);
         }
+        let name = s.output.name();
+        let live: Vec<String> = self.space.outputs().map(|o| o.name()).collect();
+        self.session_lock.frame_presented(&name, &live);
     }
 
     // ── TWM helpers ───────────────────────────────────────────────────────────
@@ -432,6 +1355,7 @@ impl KittyCompositor {
                     .env("MOZ_ENABLE_WAYLAND", "1")
                     .env("GDK_BACKEND", "wayland")
                     .env("QT_QPA_PLATFORM", "wayland")
+                    .envs(self.config.global_env.iter().map(|(k, v)| (k, v)))
                     .spawn()
                 {
                     tracing::warn!("Failed to spawn '{}': {e}", app_id);
@@ -470,7 +1394,283 @@ impl KittyCompositor {
                 }
             }
 
-            EmbedCommand::List => {}
+            EmbedCommand::FocusLock => {
+                if let Some(twm) = &mut self.twm {
+                    twm.set_focus_lock(true);
+                }
+            }
+
+            EmbedCommand::FocusUnlock => {
+                if let Some(twm) = &mut self.twm {
+                    twm.set_focus_lock(false);
+                }
+            }
+
+            // Answered by the server itself, never queued.
+            EmbedCommand::List | EmbedCommand::Subscribe { .. } => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fatal_render_errors() {
+        assert!(is_fatal_render_error("RenderFrame(Rendering(ContextLost))"));
+        // A VT switch, not a reset.
+        assert!(!is_fatal_render_error(
+            "PrepareFrame(DrmError(DeviceInactive))"
+        ));
+        assert!(!is_fatal_render_error("PrepareFrame(NoFramebuffer)"));
+    }
+
+    #[test]
+    fn chrome_comes_back_once_the_spinner_is_cleared() {
+        let mut twm = TwmState::new(80, 24);
+        twm.anim_enabled = false;
+        let mut spinner = Some(SpinnerState::new());
+
+        let cmds = chrome_cmds(&mut twm, spinner.as_ref(), (8, 16), (640, 384));
+        assert!(matches!(cmds.as_slice(), [DrawCmd::Text { .. }]));
+
+        // What render_surface does once the first frame is queued.
+        spinner = None;
+        let cmds = chrome_cmds(&mut twm, spinner.as_ref(), (8, 16), (640, 384));
+        assert!(cmds.len() > 1);
+        assert!(cmds
+            .iter()
+            .any(|c| matches!(c, DrawCmd::Text { text, .. } if text.contains('1'))));
+    }
+
+    #[test]
+    fn recovery_gives_up_after_three_resets() {
+        let t0 = Instant::now();
+        let mut tracker = RecoveryTracker::default();
+        // One reset, one rebuild, then frames render again.
+        assert!(tracker.attempt(t0));
+        // A reset now and then is always retried.
+        assert!(tracker.attempt(t0 + Duration::from_secs(6)));
+        assert!(tracker.attempt(t0 + Duration::from_secs(12)));
+        // A GPU that keeps dying is not.
+        assert!(tracker.attempt(t0 + Duration::from_secs(13)));
+        assert!(tracker.attempt(t0 + Duration::from_secs(14)));
+        assert!(!tracker.attempt(t0 + Duration::from_secs(15)));
+    }
+
+    /// A GPU whose context stays lost until the renderer is replaced.
+    #[derive(Default)]
+    struct FlakyGpu {
+        context_lost: bool,
+        resets: u32,
+        frames: u32,
+    }
+
+    impl FlakyGpu {
+        fn render_frame(&mut self) -> Result<(), String> {
+            if self.context_lost {
+                return Err("RenderFrame(Rendering(ContextLost))".into());
+            }
+            self.frames += 1;
+            Ok(())
+        }
+    }
+
+    impl SurfaceRebuild for FlakyGpu {
+        type Compositor = ();
+        type Error = String;
+
+        fn reset_renderer(&mut self) -> Result<(), String> {
+            if self.context_lost {
+                self.context_lost = false;
+                self.resets += 1;
+            }
+            Ok(())
+        }
+
+        fn rebuild_compositor(&mut self) -> Result<(), String> {
+            if self.context_lost {
+                return Err("compositor on a dead context".into());
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn frame_after_a_lost_context_renders() {
+        let mut gpu = FlakyGpu {
+            context_lost: true,
+            ..Default::default()
+        };
+        let e = gpu.render_frame().unwrap_err();
+        assert!(is_fatal_render_error(&format!("{e:?}")));
+
+        recover(&mut gpu).unwrap();
+        gpu.render_frame()
+            .expect("the frame after the rebuild renders");
+        assert_eq!((gpu.resets, gpu.frames), (1, 1));
+
+        // Another surface on the same GPU only gets its compositor back.
+        recover(&mut gpu).unwrap();
+        assert_eq!(gpu.resets, 1);
+    }
+
+    #[test]
+    fn frame_interval_follows_vblanks() {
+        let nominal = Duration::from_micros(16_667);
+        let mut clock = FrameClock::default();
+        // 59.94 Hz: the mode's vrefresh() rounds this to 60.
+        let period = Duration::from_nanos(16_683_350);
+        let mut hz = None;
+        for i in 0..=REFRESH_LOG_FRAMES {
+            hz = clock.vblank(period * i);
         }
+        assert!((hz.unwrap() - 59.94).abs() < 0.01);
+        assert_eq!(frame_interval(clock.interval(), nominal, nominal), period);
+
+        // A gap of several frames is idle time, not the refresh rate.
+        clock.vblank(period * 70);
+        assert_eq!(frame_interval(clock.interval(), nominal, nominal), nominal);
+        // target_hz = 30 still caps it.
+        let floor = Duration::from_micros(33_333);
+        assert_eq!(frame_interval(Some(period), nominal, floor), floor);
+    }
+
+    #[test]
+    fn pick_output_by_window_centre() {
+        let left = Rectangle::<i32, Logical>::from_loc_and_size((0, 0), (1920, 1080));
+        let right = Rectangle::<i32, Logical>::from_loc_and_size((1920, 0), (2560, 1440));
+        let outputs = || [("left", left), ("right", right)].into_iter();
+
+        let window = Rectangle::from_loc_and_size((2900, 420), (600, 600));
+        assert_eq!(pick_output(window, outputs()), Some("right"));
+        // Straddling the seam: the centre decides, even though most of the
+        // window hangs below the shorter left output.
+        let window = Rectangle::from_loc_and_size((1500, 600), (800, 800));
+        assert_eq!(pick_output(window, outputs()), Some("left"));
+        let window = Rectangle::from_loc_and_size((-900, 0), (400, 400));
+        assert_eq!(pick_output(window, outputs()), None);
+    }
+
+    #[test]
+    fn windows_get_the_scale_of_the_output_they_are_on() {
+        use smithay::output::{Mode, PhysicalProperties, Scale, Subpixel};
+
+        let output = |name: &str, scale: f64| {
+            let output = Output::new(
+                name.into(),
+                PhysicalProperties {
+                    size: (0, 0).into(),
+                    subpixel: Subpixel::Unknown,
+                    make: "test".into(),
+                    model: "test".into(),
+                },
+            );
+            let mode = Mode {
+                size: (2880, 1620).into(),
+                refresh: 60_000,
+            };
+            output.change_current_state(Some(mode), None, Some(Scale::Fractional(scale)), None);
+            output
+        };
+        let mut space = Space::<Window>::default();
+        let (left, right) = (output("DP-1", 1.0), output("DP-2", 1.5));
+        space.map_output(&left, (0, 0));
+        // 2880x1620 at 1.5 is 1920x1080 logical.
+        space.map_output(&right, (2880, 0));
+
+        let on_right = Some(Rectangle::from_loc_and_size((3000, 100), (800, 600)));
+        let scale_at = |space: &Space<Window>, geo| preferred_scale(output_at(space, geo).as_ref());
+        assert_eq!(scale_at(&space, on_right), 1.5);
+        // On the wire: 180/120.
+        assert_eq!((scale_at(&space, on_right) * 120.0).round(), 180.0);
+        // Off every output, or not mapped: the first output's.
+        assert_eq!(scale_at(&space, None), 1.0);
+
+        // Rescaled by output management or a config reload; what
+        // update_fractional_scales then sends follows it.
+        right.change_current_state(None, None, Some(Scale::Fractional(2.0)), None);
+        space.map_output(&right, (2880, 0));
+        assert_eq!(scale_at(&space, on_right), 2.0);
+        assert_eq!(preferred_scale(None), 1.0);
+    }
+
+    #[test]
+    fn fullscreen_window_is_scanned_out() {
+        let output = Rectangle::<i32, Logical>::from_loc_and_size((0, 0), (1920, 1080));
+        let full = Rectangle::from_loc_and_size((0, 0), (1920, 1080));
+        let half = Rectangle::from_loc_and_size((0, 0), (960, 1080));
+        let other = Rectangle::from_loc_and_size((1920, 0), (2560, 1440));
+
+        assert_eq!(
+            scanout_candidate(output, [("mpv", full)].into_iter()),
+            Some("mpv")
+        );
+        // A window on another output doesn't get in the way.
+        assert_eq!(
+            scanout_candidate(output, [("mpv", full), ("foot", other)].into_iter()),
+            Some("mpv")
+        );
+        assert_eq!(
+            scanout_candidate(output, [("foot", half)].into_iter()),
+            None
+        );
+        assert_eq!(
+            scanout_candidate(output, [("mpv", full), ("foot", half)].into_iter()),
+            None
+        );
+    }
+
+    #[test]
+    fn scanout_frame_leaves_the_chrome_out() {
+        let output = Rectangle::<i32, Logical>::from_loc_and_size((0, 0), (1920, 1080));
+        let full = Rectangle::from_loc_and_size((0, 0), (1920, 1080));
+        let layers = || FrameLayers {
+            cursor: Some("cursor"),
+            lock: Vec::new(),
+            chrome: Some("chrome"),
+            embedded: Vec::new(),
+            space: vec!["mpv"],
+        };
+
+        let scanout = scanout_candidate(output, [("mpv", full)].into_iter()).is_some();
+        assert!(frame_flags(scanout).contains(FrameFlags::ALLOW_PRIMARY_PLANE_SCANOUT));
+        // Nothing but the pointer, which has its own plane, is over the window.
+        assert_eq!(
+            layers().into_elements(false, false, scanout),
+            ["cursor", "mpv"]
+        );
+
+        // Tiled: the chrome is drawn over the windows.
+        assert!(!frame_flags(false).contains(FrameFlags::ALLOW_PRIMARY_PLANE_SCANOUT));
+        assert_eq!(
+            layers().into_elements(false, false, false),
+            ["cursor", "chrome", "mpv"]
+        );
+        assert_eq!(
+            layers().into_elements(false, true, false),
+            ["cursor", "chrome"]
+        );
+        assert_eq!(layers().into_elements(true, false, false), ["cursor"]);
+    }
+
+    #[test]
+    fn idle_inhibitor_lifecycle() {
+        let mut inhibitors = IdleInhibitors::default();
+        assert!(!inhibitors.any());
+
+        inhibitors.inhibit("mpv");
+        assert!(inhibitors.any());
+        inhibitors.uninhibit(&"mpv");
+        assert!(!inhibitors.any());
+
+        // Two inhibitors on one surface: both must go, or the surface.
+        inhibitors.inhibit("firefox");
+        inhibitors.inhibit("firefox");
+        inhibitors.uninhibit(&"firefox");
+        assert!(inhibitors.any());
+        inhibitors.surface_destroyed(&"firefox");
+        assert!(!inhibitors.any());
     }
 }
@@ -19,12 +19,13 @@
 // This file has NO dependency on smithay, gl, EGL, DRM, or any compositor
 // internals. It is pure CPU logic.
 
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::time::Instant;
 
 use ratatui::{
     buffer::Buffer,
-    layout::Rect,
+    layout::{Margin, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
     widgets::{Block, Borders, Paragraph, Widget},
@@ -36,15 +37,20 @@ use ratatui::{
 pub use crate::pixelui::{
     layout::Rect as PixRect,
     style::{Color as PixColor, Style as PixStyle},
-    DrawCmd,
+    ChromeLayer, DrawCmd, DrawContext, WidgetDyn,
 };
 
+use crate::bar_modules::{cpu_gauge, cpu_label, BatteryStatus};
+use crate::embedded_ipc::EmbedCommand;
+use crate::embedded_window::EmbeddedWindow;
+use crate::pixelui::ProgressBar;
+
 // ─────────────────────────────────────────────────────────────────────────────
 // SECTION 1 — Cell buffer (ratatui → pixel DrawCmds)
 // ─────────────────────────────────────────────────────────────────────────────
 
 /// One rendered terminal cell.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct TwmCell {
     pub ch: char,
     pub fg: [u8; 3],
@@ -88,15 +94,22 @@ impl CellBuffer {
             .resize(cols as usize * rows as usize, TwmCell::default());
     }
 
-    fn blit(&mut self, buf: &Buffer) {
-        let area = buf.area;
-        let cols = area.width.min(self.cols);
-        let rows = area.height.min(self.rows);
-        for row in 0..rows {
-            for col in 0..cols {
-                let c = buf.get(area.x + col, area.y + row);
-                let idx = row as usize * self.cols as usize + col as usize;
-                self.cells[idx] = cell_convert(c);
+    /// Walks ratatui's flat `content` vector one row slice at a time rather
+    /// than going through `Buffer::get` (index math + bounds assert) for
+    /// every cell. No per-cell allocation.
+    fn import(&mut self, buf: &Buffer) {
+        let src_w = buf.area.width as usize;
+        let dst_w = self.cols as usize;
+        let cols = src_w.min(dst_w);
+        let rows = buf.area.height.min(self.rows) as usize;
+        for (src_row, dst_row) in buf
+            .content
+            .chunks_exact(src_w.max(1))
+            .zip(self.cells.chunks_exact_mut(dst_w.max(1)))
+            .take(rows)
+        {
+            for (d, c) in dst_row[..cols].iter_mut().zip(&src_row[..cols]) {
+                *d = cell_convert(c);
             }
         }
     }
@@ -225,7 +238,7 @@ fn ansi256(i: u8) -> [u8; 3] {
 // SECTION 2 — Animation helpers
 // ─────────────────────────────────────────────────────────────────────────────
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
 pub enum Easing {
     EaseOutCubic,
     EaseInOut,
@@ -233,6 +246,15 @@ pub enum Easing {
 }
 
 impl Easing {
+    pub fn from_name(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().replace(['_', '-'], "").as_str() {
+            "easeoutcubic" | "outcubic" => Some(Self::EaseOutCubic),
+            "easeinout" | "inout" => Some(Self::EaseInOut),
+            "linear" => Some(Self::Linear),
+            _ => None,
+        }
+    }
+
     pub fn apply(self, t: f64) -> f64 {
         match self {
             Self::EaseOutCubic => 1.0 - (1.0 - t).powi(3),
@@ -280,6 +302,12 @@ impl From<RF> for Rect {
     }
 }
 impl RF {
+    fn shifted_x(self, dx: f64) -> Self {
+        Self {
+            x: self.x + dx,
+            ..self
+        }
+    }
     fn lerp(self, o: Self, t: f64) -> Self {
         Self {
             x: self.x + (o.x - self.x) * t,
@@ -301,12 +329,65 @@ impl AnimRect {
             ease: Easing::EaseOutCubic,
         }
     }
+    fn current_rf(&self) -> RF {
+        if self.dur_ms <= 0.0 {
+            return self.dst;
+        }
+        let t = (self.start.elapsed().as_secs_f64() * 1000.0 / self.dur_ms).min(1.0);
+        self.src.lerp(self.dst, self.ease.apply(t))
+    }
     fn current(&self) -> Rect {
+        self.current_rf().into()
+    }
+    /// Current rect clipped to `area` — unlike `current()`, a rect that has
+    /// moved past the left/top edge shrinks instead of pinning at 0.
+    fn current_in(&self, area: Rect) -> Rect {
+        let r = self.current_rf();
+        let x0 = r.x.max(area.x as f64);
+        let y0 = r.y.max(area.y as f64);
+        let x1 = (r.x + r.w).min((area.x + area.width) as f64);
+        let y1 = (r.y + r.h).min((area.y + area.height) as f64);
+        if x1 <= x0 || y1 <= y0 {
+            return Rect::default();
+        }
+        Rect::new(
+            x0.round() as u16,
+            y0.round() as u16,
+            (x1 - x0).round() as u16,
+            (y1 - y0).round() as u16,
+        )
+    }
+    fn is_done(&self) -> bool {
+        self.dur_ms <= 0.0 || self.start.elapsed().as_secs_f64() * 1000.0 >= self.dur_ms
+    }
+}
+
+/// A single animated value — `AnimRect` for one number (a workspace's gap).
+#[derive(Clone, Copy)]
+struct AnimF32 {
+    src: f32,
+    dst: f32,
+    start: Instant,
+    dur_ms: f64,
+    ease: Easing,
+}
+
+impl AnimF32 {
+    fn still(v: f32) -> Self {
+        Self {
+            src: v,
+            dst: v,
+            start: Instant::now(),
+            dur_ms: 0.0,
+            ease: Easing::EaseOutCubic,
+        }
+    }
+    fn current(&self) -> f32 {
         if self.dur_ms <= 0.0 {
-            return self.dst.into();
+            return self.dst;
         }
         let t = (self.start.elapsed().as_secs_f64() * 1000.0 / self.dur_ms).min(1.0);
-        self.src.lerp(self.dst, self.ease.apply(t)).into()
+        self.src + (self.dst - self.src) * self.ease.apply(t) as f32
     }
     fn is_done(&self) -> bool {
         self.dur_ms <= 0.0 || self.start.elapsed().as_secs_f64() * 1000.0 >= self.dur_ms
@@ -324,7 +405,7 @@ fn new_id() -> PaneId {
     NEXT_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum PaneContent {
     /// A Wayland toplevel tracked by app_id.
     Shell { title: String },
@@ -347,11 +428,27 @@ impl PaneContent {
     }
 }
 
+/// Orientation of a manual split. `Horizontal` places the new pane beside
+/// its sibling, `Vertical` stacks it below.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitDir {
+    Horizontal,
+    Vertical,
+}
+
 pub struct Pane {
     pub id: PaneId,
     pub content: PaneContent,
     anim: AnimRect,
     pub fullscreen: bool,
+    /// BSP hint: how this pane divides space with the pane before it.
+    /// `None` keeps the default alternating split.
+    pub split: Option<SplitDir>,
+    /// Names set by `Mark`; each is on at most one pane at a time.
+    pub marks: HashSet<String>,
+    /// Shown on every workspace, over the tiled panes, at the rect it had
+    /// when it was made sticky (`ToggleSticky`).
+    pub sticky: bool,
 }
 
 impl Pane {
@@ -361,6 +458,9 @@ impl Pane {
             content,
             anim: AnimRect::still(Rect::default()),
             fullscreen: false,
+            split: None,
+            marks: HashSet::new(),
+            sticky: false,
         }
     }
     fn title_label(&self) -> String {
@@ -372,12 +472,20 @@ impl Pane {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum Layout {
     Bsp,
     Columns,
     Rows,
     Monocle,
+    /// Every pane over the same rect under a one-row strip of tabs; only the
+    /// focused pane is drawn.
+    Tabbed,
+    Spiral,
+    /// Fixed column count; only set explicitly, never reached by `next`.
+    Grid {
+        cols: u16,
+    },
 }
 
 impl Layout {
@@ -386,7 +494,28 @@ impl Layout {
             Self::Bsp => Self::Columns,
             Self::Columns => Self::Rows,
             Self::Rows => Self::Monocle,
-            Self::Monocle => Self::Bsp,
+            Self::Monocle => Self::Tabbed,
+            Self::Tabbed => Self::Spiral,
+            Self::Spiral | Self::Grid { .. } => Self::Bsp,
+        }
+    }
+    /// Parse a config-file layout name (`bsp`, `columns`, `rows`, `monocle`,
+    /// `tabbed`, `spiral`, `grid` or `grid:N` for N columns).
+    pub fn from_name(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "bsp" => Some(Self::Bsp),
+            "columns" | "cols" => Some(Self::Columns),
+            "rows" => Some(Self::Rows),
+            "monocle" => Some(Self::Monocle),
+            "tabbed" | "tabs" => Some(Self::Tabbed),
+            "spiral" | "fibonacci" => Some(Self::Spiral),
+            "grid" => Some(Self::Grid {
+                cols: DEFAULT_GRID_COLS,
+            }),
+            s => match s.strip_prefix("grid:")?.trim().parse::<u16>() {
+                Ok(cols) if cols > 0 => Some(Self::Grid { cols }),
+                _ => None,
+            },
         }
     }
     pub fn label(&self) -> &'static str {
@@ -395,26 +524,79 @@ impl Layout {
             Self::Columns => "Columns",
             Self::Rows => "Rows",
             Self::Monocle => "Monocle",
+            Self::Tabbed => "Tabbed",
+            Self::Spiral => "Spiral",
+            Self::Grid { .. } => "Grid",
         }
     }
 }
 
+/// Columns of a `grid` layout that doesn't give a count.
+const DEFAULT_GRID_COLS: u16 = 2;
+
 pub struct Workspace {
+    /// Label on the bar tab; `"1"`–`"9"` unless a config rule names it.
+    pub name: String,
     pub panes: Vec<PaneId>,
     pub focused: Option<PaneId>,
     pub layout: Layout,
     pub main_ratio: f32,
-    pub gap: u16,
+    /// Column count `SetGridCols` last chose for this workspace.
+    pub grid_cols: u16,
+    /// Cells between neighbouring panes.
+    pub gap_inner: u16,
+    /// Cells between the panes and the edge of the content area.
+    pub gap_outer: u16,
+    /// `gap_inner` as laid out; trails it while a `SetPaneGap` animates.
+    gap_anim: AnimF32,
+}
+
+/// Per-workspace defaults from a `workspace_rule { }` config block.
+/// Unset fields leave the workspace as it is.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct WorkspaceRule {
+    /// 1-based workspace number.
+    pub index: u8,
+    pub name: Option<String>,
+    pub layout: Option<Layout>,
+    pub gap_inner: Option<u16>,
+    pub gap_outer: Option<u16>,
+    pub main_ratio: Option<f32>,
 }
 
 impl Workspace {
-    fn new(gap: u16) -> Self {
+    fn new(name: String, gap_inner: u16) -> Self {
         Self {
+            name,
             panes: vec![],
             focused: None,
             layout: Layout::Bsp,
             main_ratio: 0.5,
-            gap,
+            grid_cols: DEFAULT_GRID_COLS,
+            gap_inner,
+            gap_outer: 0,
+            gap_anim: AnimF32::still(gap_inner as f32),
+        }
+    }
+    fn apply_rule(&mut self, rule: &WorkspaceRule) {
+        if let Some(name) = &rule.name {
+            self.name = name.clone();
+        }
+        if let Some(l) = rule.layout {
+            self.layout = l;
+            if let Layout::Grid { cols } = l {
+                self.grid_cols = cols;
+            }
+        }
+        if let Some(g) = rule.gap_inner {
+            self.gap_inner = g;
+            self.gap_anim = AnimF32::still(g as f32);
+        }
+        if let Some(g) = rule.gap_outer {
+            self.gap_outer = g;
+        }
+        if let Some(r) = rule.main_ratio {
+            self.main_ratio = r.clamp(0.1, 0.9);
         }
     }
     fn focus_idx(&self) -> Option<usize> {
@@ -436,7 +618,8 @@ impl Workspace {
 // ─────────────────────────────────────────────────────────────────────────────
 
 /// Keybind actions — map your existing input.rs actions to these.
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Deserialisable for IPC: `"NextLayout"`, `{"Workspace": 3}`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
 pub enum Action {
     FocusLeft,
     FocusRight,
@@ -446,15 +629,109 @@ pub enum Action {
     MoveRight,
     Close,
     Workspace(u8),
+    WorkspaceByName(String), // first workspace with this name
     MoveToWorkspace(u8),
+    Mark(String),         // on the focused pane; taken off any other pane
+    FocusMark(String),    // switch to the marked pane's workspace and focus it
+    FocusApp(String),     // switch to the pane showing this app_id and focus it
+    MoveMark(String, u8), // move the marked pane to a 1-based workspace
+    FocusLast,            // the pane focused before the current one
+    AltTabNext,           // open the alt-tab popup, or step to the older pane
+    AltTabPrev,           // open the alt-tab popup, or step to the newer pane
+    ToggleSticky,         // show the focused pane on every workspace, or stop
+    UndoClose,            // reopen the most recently closed shell pane
     NextLayout,
     GrowMain,
     ShrinkMain,
     NextWorkspace,
     PrevWorkspace,
+    WorkspaceLeft, // neighbours on the workspace grid; rows and columns wrap
+    WorkspaceRight,
+    WorkspaceUp,
+    WorkspaceDown,
     ToggleBar,
     Fullscreen,
     OpenShell(String), // spawn a new placeholder pane with this title
+    UiClick(u64),      // pointer click on a registered chrome region
+    SplitHorizontal,
+    SplitVertical,
+    Minimize,
+    Unminimize(PaneId),
+    UnminimizeAll,
+    Expose,
+    Lock,
+    Unlock(String), // PIN as typed; checked against `lock_pin`
+    UndoResize,
+    RedoResize,
+    FocusOutput(String), // output name; switches to the workspace shown there
+    ToggleFocusLock,
+    SetPaneGap(u16),  // active workspace's gap_inner, animated
+    SetGridCols(u16), // switches the active workspace to a grid this wide
+    SendToScratchpad,
+    ToggleScratchpad,
+}
+
+/// Resize steps kept for `UndoResize`; the oldest is dropped past this.
+const RESIZE_HISTORY_LEN: usize = 20;
+
+/// One `main_ratio` change, replayable in either direction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResizeEvent {
+    /// Workspace index — the ratio belongs to the workspace, not a pane.
+    pub ws: usize,
+    pub old_ratio: f32,
+    pub new_ratio: f32,
+}
+
+/// Default `focus_history_len`.
+pub const FOCUS_HISTORY_LEN: usize = 32;
+
+/// Default `workspace_grid_cols`.
+pub const WORKSPACE_GRID_COLS: usize = 3;
+
+/// Default `undo_depth`.
+pub const UNDO_DEPTH: usize = 10;
+
+/// Default bar modules: left, centre, right.
+pub const BAR_MODULES: [&[&str]; 3] = [&["workspaces"], &["layout"], &["media", "clock"]];
+
+/// Module names `render_bar_module` draws.
+pub const BAR_MODULE_NAMES: &[&str] = &[
+    "workspaces",
+    "layout",
+    "title",
+    "media",
+    "clock",
+    "cpu",
+    "battery",
+];
+
+/// The alt-tab popup while it is open: the focus history as it was when the
+/// popup opened, and the entry that will be focused when it closes.
+struct AltTab {
+    panes: Vec<PaneId>,
+    selected: usize,
+    /// Row of the selection highlight; slides between entries.
+    highlight: AnimF32,
+}
+
+/// Click ids at or above this restore the minimized pane `id - base`.
+const MINIMIZED_CLICK_BASE: u64 = 1 << 32;
+/// Click ids at or above this pick pane `id - base` in expose mode.
+const EXPOSE_CLICK_BASE: u64 = 2 << 32;
+/// Click ids at or above this focus the tab of pane `id - base`.
+const TAB_CLICK_BASE: u64 = 3 << 32;
+
+/// Default length of the workspace slide.
+pub const WORKSPACE_ANIM_MS: f64 = 200.0;
+
+/// A workspace slide in progress. `anim` moves the outgoing workspace's
+/// content rect off screen; the panes of both workspaces follow it.
+#[derive(Clone, Copy)]
+struct WorkspaceTransition {
+    from_ws: usize,
+    to_ws: usize,
+    anim: AnimRect,
 }
 
 pub struct TwmState {
@@ -467,11 +744,21 @@ pub struct TwmState {
     pub anim_duration_ms: f64,
     pub anim_ease: Easing,
     pub anim_enabled: bool,
+    /// Length of the workspace slide; off with the other animations.
+    pub workspace_anim_ms: f64,
     // bar
     pub bar_visible: bool,
     pub bar_height: u16, // in cells, typically 1
     bar_at_bottom: bool,
     bar_clock: String,
+    /// Now-playing text drawn left of the clock (MPRIS), set by the compositor.
+    pub bar_media: Option<String>,
+    /// Bar module names — left, centre, right — drawn by `render_bar_module`.
+    pub bar_modules: [Vec<String>; 3],
+    /// Last `cpu` reading in percent, polled by the compositor.
+    pub cpu: Option<u8>,
+    /// Last battery reading, polled by the compositor (`bar.battery_poll_secs`).
+    pub battery: Option<BatteryStatus>,
     // colours (set from your existing theme)
     pub active_border: [u8; 3],
     pub inactive_border: [u8; 3],
@@ -480,6 +767,67 @@ pub struct TwmState {
     pub pane_bg: [u8; 3],
     pub bar_bg: [u8; 3],
     pub bar_fg: [u8; 3],
+    // embedded geometry sync — pixel size of one cell, updated every frame
+    pub embedded_windows: HashMap<String, EmbeddedWindow>,
+    cell_w: u32,
+    cell_h: u32,
+    // panes drawn without border or title (windowrule = no_decor)
+    pub no_decor_panes: HashSet<PaneId>,
+    // hidden panes — kept in ws.panes but skipped by layout and render
+    pub minimized: HashSet<PaneId>,
+    // expose overview: every pane of every workspace tiled in a grid
+    pub expose_active: bool,
+    // lock curtain: chrome is blacked out and every action but Unlock is dropped
+    pub locked: bool,
+    /// bcrypt hash of the unlock PIN (`lock_pin`). None refuses `Lock`; a
+    /// reload that drops it while locked lets the next Unlock through.
+    pub lock_pin: Option<String>,
+    /// PIN typed so far while locked, fed by the keyboard handler.
+    pub lock_entry: String,
+    /// Grid step for floating window moves (`float_snap`); 0 = no snapping.
+    pub float_snap_px: u32,
+    /// Lay a workspace's only visible pane out with no inner or outer gap
+    /// (`no_gap_when_single`).
+    pub no_gap_when_single: bool,
+    /// While set, focus can't be moved off this pane (`ToggleFocusLock`).
+    pub focus_locked: Option<PaneId>,
+    /// Panes most recently focused first; the front is the focused pane.
+    pub focus_history: VecDeque<PaneId>,
+    /// Entries kept in `focus_history` (`focus_history_len`).
+    pub focus_history_len: usize,
+    alt_tab: Option<AltTab>,
+    /// Surface key → pane, for windows taken in by `import_existing_windows`.
+    imported: HashMap<u64, PaneId>,
+    /// app_id → window-rule mark for apps whose pane isn't open yet.
+    pending_marks: HashMap<String, String>,
+    /// Closed shell panes and where they were, newest first, for
+    /// `UndoClose`. Embedded panes aren't kept: their client is gone.
+    closed_panes: VecDeque<(PaneContent, Rect)>,
+    /// Entries kept in `closed_panes` (`undo_depth`).
+    pub undo_depth: usize,
+    /// Workspace index → name of the output it is shown on.
+    pub workspace_to_output: HashMap<usize, String>,
+    /// Workspace holding scratchpad panes; left out of the bar, of
+    /// Next/PrevWorkspace and of workspace numbers. The tenth, after the
+    /// nine Super+1..9 reach.
+    pub scratchpad_ws: usize,
+    /// Width of the grid the other workspaces are laid out on, in order, for
+    /// WorkspaceLeft/Right/Up/Down and the bar (`workspace_grid_cols`).
+    pub workspace_grid_cols: usize,
+    /// Pane `ToggleScratchpad` pulled out; it floats centered over the
+    /// workspace it was shown on until toggled back.
+    scratch_shown: Option<PaneId>,
+    // main_ratio changes, newest last; redo is cleared by any new resize
+    pub resize_history: VecDeque<ResizeEvent>,
+    resize_redo: Vec<ResizeEvent>,
+    // workspace slide in progress, if any
+    transition: Option<WorkspaceTransition>,
+    // chrome click targets in pixels — ids 1..=9 are bar workspace tabs
+    click_regions: Vec<(PixRect, u64)>,
+    // pixelui widgets drawn over the chrome on the next frame only
+    overlay_widgets: Vec<(PixRect, Box<dyn WidgetDyn>)>,
+    // persistent overlays drawn over the chrome every frame, in registration order
+    chrome_layers: Vec<Box<dyn ChromeLayer>>,
     // internal
     buf: Buffer,
     cells: CellBuffer,
@@ -490,7 +838,9 @@ impl TwmState {
     /// Create with sensible defaults matching Catppuccin Mocha.
     /// Call resize() when you know the real cell dimensions.
     pub fn new(cols: u16, rows: u16) -> Self {
-        let mut workspaces: Vec<Workspace> = (0..9).map(|_| Workspace::new(1)).collect();
+        // Nine numbered workspaces, then the scratchpad.
+        let mut workspaces: Vec<Workspace> =
+            (1..=10).map(|n| Workspace::new(n.to_string(), 1)).collect();
 
         // Seed workspace 0 with one empty shell pane so there's always
         // something to draw before any client connects.
@@ -503,6 +853,7 @@ impl TwmState {
         workspaces[0].panes.push(root_id);
         workspaces[0].focused = Some(root_id);
 
+        let scratchpad_ws = workspaces.len() - 1;
         let buf = Buffer::empty(Rect::new(0, 0, cols, rows));
         let cells = CellBuffer::new(cols, rows);
 
@@ -515,10 +866,15 @@ impl TwmState {
             anim_duration_ms: 120.0,
             anim_ease: Easing::EaseOutCubic,
             anim_enabled: true,
+            workspace_anim_ms: WORKSPACE_ANIM_MS,
             bar_visible: true,
             bar_height: 1,
             bar_at_bottom: true,
             bar_clock: String::new(),
+            bar_media: None,
+            bar_modules: BAR_MODULES.map(|side| side.iter().map(|m| m.to_string()).collect()),
+            cpu: None,
+            battery: None,
             active_border: [180, 190, 254],
             inactive_border: [69, 71, 90],
             active_title: [180, 190, 254],
@@ -526,6 +882,35 @@ impl TwmState {
             pane_bg: [17, 17, 27],
             bar_bg: [24, 24, 37],
             bar_fg: [166, 173, 200],
+            embedded_windows: HashMap::new(),
+            cell_w: 8,
+            cell_h: 16,
+            no_decor_panes: HashSet::new(),
+            minimized: HashSet::new(),
+            expose_active: false,
+            locked: false,
+            lock_pin: None,
+            lock_entry: String::new(),
+            float_snap_px: 0,
+            no_gap_when_single: false,
+            focus_locked: None,
+            focus_history: VecDeque::new(),
+            focus_history_len: FOCUS_HISTORY_LEN,
+            alt_tab: None,
+            imported: HashMap::new(),
+            pending_marks: HashMap::new(),
+            closed_panes: VecDeque::new(),
+            undo_depth: UNDO_DEPTH,
+            workspace_to_output: HashMap::new(),
+            scratchpad_ws,
+            workspace_grid_cols: WORKSPACE_GRID_COLS,
+            scratch_shown: None,
+            resize_history: VecDeque::with_capacity(RESIZE_HISTORY_LEN),
+            resize_redo: Vec::new(),
+            transition: None,
+            click_regions: Vec::new(),
+            overlay_widgets: Vec::new(),
+            chrome_layers: Vec::new(),
             buf,
             cells,
             dirty: true,
@@ -543,8 +928,55 @@ impl TwmState {
         self.reflow();
     }
 
+    /// Set each workspace's starting layout: `per_ws` is keyed by 1-based
+    /// workspace number, anything missing falls back to `default`.
+    pub fn set_workspace_layouts(&mut self, default: Layout, per_ws: &HashMap<u8, Layout>) {
+        for (i, ws) in self.workspaces.iter_mut().enumerate() {
+            ws.layout = per_ws.get(&(i as u8 + 1)).copied().unwrap_or(default);
+        }
+        self.dirty = true;
+        self.reflow();
+    }
+
+    /// Apply `workspace_rule` blocks on top of the current workspace state;
+    /// rules for workspace numbers past the last workspace are ignored.
+    /// Names only come from rules, so a workspace whose rule lost its name
+    /// on reload goes back to its number.
+    pub fn set_workspace_rules(&mut self, rules: &[WorkspaceRule]) {
+        for (i, ws) in self.workspaces.iter_mut().enumerate() {
+            ws.name = (i + 1).to_string();
+        }
+        for rule in rules {
+            if let Some(ws) = self
+                .workspaces
+                .get_mut((rule.index as usize).wrapping_sub(1))
+            {
+                ws.apply_rule(rule);
+            }
+        }
+        self.dirty = true;
+        self.reflow();
+    }
+
+    /// Copy a rendered ratatui buffer into the cell grid via the row-slice
+    /// fast path (see `CellBuffer::import`).
+    pub fn import_ratatui_buffer(&mut self, buf: &Buffer) {
+        self.cells.import(buf);
+    }
+
+    pub fn set_no_gap_when_single(&mut self, on: bool) {
+        self.no_gap_when_single = on;
+        self.dirty = true;
+    }
+
     pub fn animating(&self) -> bool {
-        self.panes.values().any(|p| !p.anim.is_done())
+        self.panes.values().any(|p| !p.anim.is_done()) || self.gaps_animating()
+    }
+
+    /// True while any workspace's gap is still moving towards a `SetPaneGap`
+    /// value.
+    pub fn gaps_animating(&self) -> bool {
+        self.workspaces.iter().any(|w| !w.gap_anim.is_done())
     }
 
     // ── Pane management ───────────────────────────────────────────────────────
@@ -559,7 +991,69 @@ impl TwmState {
         let ws = &mut self.workspaces[self.active_ws];
         ws.panes.push(id);
         ws.focused = Some(id);
+        self.apply_pending_mark(id);
+        self.dirty = true;
+    }
+
+    /// Adopt windows that were mapped before this state existed, each given
+    /// as (surface key, app_id, content); the key tells windows apart, so two
+    /// terminals get a pane each and importing a window again does nothing.
+    /// Each one fills the first workspace-1 pane that is empty or already
+    /// labelled with its app_id (slots left by a restored layout) and not
+    /// taken by another import, else gets a new pane appended there; order
+    /// follows `windows`.
+    pub fn import_existing_windows(&mut self, windows: &[(u64, &str, PaneContent)]) {
+        for (key, app_id, content) in windows {
+            if self
+                .imported
+                .get(key)
+                .is_some_and(|id| self.panes.contains_key(id))
+            {
+                continue;
+            }
+            let taken: HashSet<PaneId> = self.imported.values().copied().collect();
+            let slot = self.workspaces[0].panes.iter().copied().find(|id| {
+                !taken.contains(id)
+                    && self.panes.get(id).is_some_and(|p| {
+                        p.content == PaneContent::Empty || p.content.label() == *app_id
+                    })
+            });
+            let id = match slot {
+                Some(id) => {
+                    if let Some(p) = self.panes.get_mut(&id) {
+                        p.content = content.clone();
+                    }
+                    id
+                }
+                None => {
+                    let p = Pane::new(content.clone());
+                    let id = p.id;
+                    self.panes.insert(id, p);
+                    self.workspaces[0].panes.push(id);
+                    id
+                }
+            };
+            self.imported.insert(*key, id);
+            self.apply_pending_mark(id);
+            self.workspaces[0].focused = Some(id);
+        }
+        self.dirty = true;
+        self.reflow();
+    }
+
+    /// Insert an empty placeholder right after `id` in its workspace and
+    /// focus it, so the next spawned app fills the new half.
+    pub fn split_pane(&mut self, id: PaneId, dir: SplitDir) -> Option<PaneId> {
+        let ws = self.workspaces.iter_mut().find(|w| w.panes.contains(&id))?;
+        let idx = ws.panes.iter().position(|&p| p == id)?;
+        let mut p = Pane::new(PaneContent::Empty);
+        p.split = Some(dir);
+        let new_id = p.id;
+        ws.panes.insert(idx + 1, new_id);
+        ws.focused = Some(new_id);
+        self.panes.insert(new_id, p);
         self.dirty = true;
+        Some(new_id)
     }
 
     /// Call when an embedded app_id arrives (from new_toplevel for embedded clients).
@@ -572,6 +1066,7 @@ impl TwmState {
                     p.content = PaneContent::Embedded {
                         app_id: app_id.to_owned(),
                     };
+                    self.apply_pending_mark(fid);
                     self.dirty = true;
                     return fid;
                 }
@@ -585,6 +1080,7 @@ impl TwmState {
         let ws = &mut self.workspaces[self.active_ws];
         ws.panes.push(id);
         ws.focused = Some(id);
+        self.apply_pending_mark(id);
         self.dirty = true;
         id
     }
@@ -601,8 +1097,71 @@ impl TwmState {
         }
     }
 
+    /// Drop the border and title of the pane showing `app_id`.
+    /// Returns false when no such pane exists.
+    pub fn set_no_decor(&mut self, app_id: &str) -> bool {
+        let id = self
+            .panes
+            .iter()
+            .find(|(_, p)| p.content.label() == app_id)
+            .map(|(&id, _)| id);
+        let Some(id) = id else { return false };
+        if self.no_decor_panes.insert(id) {
+            self.dirty = true;
+        }
+        true
+    }
+
+    /// Hide `id` without closing it. Focus moves to the last visible pane
+    /// of its workspace.
+    pub fn minimize_pane(&mut self, id: PaneId) {
+        if !self.panes.contains_key(&id) || !self.minimized.insert(id) {
+            return;
+        }
+        if let Some(p) = self.panes.get_mut(&id) {
+            p.fullscreen = false;
+            p.anim = AnimRect::still(Rect::new(0, 0, 0, 0));
+        }
+        for ws in &mut self.workspaces {
+            if ws.focused == Some(id) {
+                ws.focused = ws
+                    .panes
+                    .iter()
+                    .rev()
+                    .copied()
+                    .find(|p| !self.minimized.contains(p));
+            }
+        }
+        self.dirty = true;
+    }
+
+    /// Restore a minimized pane and focus it.
+    pub fn unminimize_pane(&mut self, id: PaneId) {
+        if !self.minimized.remove(&id) {
+            return;
+        }
+        for ws in &mut self.workspaces {
+            if ws.panes.contains(&id) {
+                ws.focused = Some(id);
+            }
+        }
+        self.dirty = true;
+    }
+
     pub fn close_pane(&mut self, id: PaneId) {
-        self.panes.remove(&id);
+        self.no_decor_panes.remove(&id);
+        self.minimized.remove(&id);
+        self.focus_history.retain(|&p| p != id);
+        match self.panes.remove(&id).map(|p| (p.content, p.anim.dst)) {
+            Some((PaneContent::Embedded { app_id }, _)) => {
+                self.embedded_windows.remove(&app_id);
+            }
+            Some((content @ PaneContent::Shell { .. }, dst)) => {
+                self.closed_panes.push_front((content, Rect::from(dst)));
+                self.closed_panes.truncate(self.undo_depth);
+            }
+            _ => {}
+        }
         for ws in &mut self.workspaces {
             ws.panes.retain(|&p| p != id);
             if ws.focused == Some(id) {
@@ -618,6 +1177,74 @@ impl TwmState {
         }
     }
 
+    /// Reopen the most recently closed shell pane on the active workspace,
+    /// starting from where it was when closed.
+    fn undo_close(&mut self) {
+        let Some((content, rect)) = self.closed_panes.pop_front() else {
+            return;
+        };
+        let mut p = Pane::new(content);
+        p.anim = AnimRect::still(rect);
+        let id = p.id;
+        self.panes.insert(id, p);
+        let ws = &mut self.workspaces[self.active_ws];
+        ws.panes.push(id);
+        ws.focused = Some(id);
+        self.dirty = true;
+    }
+
+    // ── Floating ──────────────────────────────────────────────────────────────
+
+    /// Where a floating window of `size` dragged to `pos` should land.
+    /// The position is floored to the `float_snap_px` grid, then pulled onto
+    /// any `output` or `others` edge (all `(x, y, w, h)`) closer than one
+    /// grid step. Returns `pos` unchanged when snapping is off.
+    pub fn float_snap_to_grid(
+        &self,
+        pos: (i32, i32),
+        size: (i32, i32),
+        output: (i32, i32, i32, i32),
+        others: &[(i32, i32, i32, i32)],
+    ) -> (i32, i32) {
+        let snap = self.float_snap_px as i32;
+        if snap <= 0 {
+            return pos;
+        }
+        let rects = || std::iter::once(output).chain(others.iter().copied());
+        let x = snap_axis(pos.0, size.0, snap, rects().map(|r| (r.0, r.2)));
+        let y = snap_axis(pos.1, size.1, snap, rects().map(|r| (r.1, r.3)));
+        (x, y)
+    }
+
+    // ── Outputs ───────────────────────────────────────────────────────────────
+
+    pub fn assign_workspace_to_output(&mut self, ws: usize, output: &str) {
+        if ws < self.workspaces.len() {
+            self.workspace_to_output.insert(ws, output.to_owned());
+        }
+    }
+
+    /// Give a newly connected output the lowest workspace nobody has yet.
+    /// Returns the workspace it ended up with (its old one if it had any).
+    pub fn assign_output(&mut self, output: &str) -> Option<usize> {
+        if let Some(ws) = self.workspace_for_output(output) {
+            return Some(ws);
+        }
+        let ws = (0..self.workspaces.len())
+            .find(|&i| i != self.scratchpad_ws && !self.workspace_to_output.contains_key(&i))?;
+        self.assign_workspace_to_output(ws, output);
+        Some(ws)
+    }
+
+    /// Lowest workspace index assigned to `output`.
+    pub fn workspace_for_output(&self, output: &str) -> Option<usize> {
+        self.workspace_to_output
+            .iter()
+            .filter(|(_, o)| o.as_str() == output)
+            .map(|(&ws, _)| ws)
+            .min()
+    }
+
     // ── Accessors ─────────────────────────────────────────────────────────────
 
     pub fn focused_id(&self) -> Option<PaneId> {
@@ -630,10 +1257,229 @@ impl TwmState {
             .map(|p| &p.content)
     }
 
-    /// Cell rect for a given embedded app_id on the active workspace.
-    pub fn embedded_cell_rect(&self, app_id: &str) -> Option<Rect> {
+    fn pane_for_app(&self, app_id: &str) -> Option<PaneId> {
+        self.panes
+            .iter()
+            .find(|(_, p)| matches!(&p.content, PaneContent::Embedded { app_id: a } if a == app_id))
+            .map(|(&id, _)| id)
+    }
+
+    /// Index of the workspace holding the pane that shows `app_id`.
+    pub fn workspace_of_app(&self, app_id: &str) -> Option<usize> {
+        let id = self.pane_for_app(app_id)?;
+        self.workspaces.iter().position(|w| w.panes.contains(&id))
+    }
+
+    /// Switch to the workspace showing `app_id` and focus its pane.
+    /// False when no pane shows it.
+    pub fn focus_app(&mut self, app_id: &str) -> bool {
+        self.pane_for_app(app_id)
+            .is_some_and(|id| self.focus_pane(id))
+    }
+
+    /// Switch to the workspace holding `id` and focus it, restoring it if it
+    /// was minimized. False when no workspace holds it.
+    fn focus_pane(&mut self, id: PaneId) -> bool {
+        let Some(ws) = self.workspaces.iter().position(|w| w.panes.contains(&id)) else {
+            return false;
+        };
+        self.unminimize_pane(id);
+        self.animate_workspace_switch(ws);
+        self.workspaces[ws].focused = Some(id);
+        self.dirty = true;
+        true
+    }
+
+    /// Move `id` to workspace `idx` (0-based), where it takes focus. The
+    /// workspace it left focuses its last pane. The scratchpad is only
+    /// reached through `send_to_scratchpad`.
+    fn move_pane_to_workspace(&mut self, id: PaneId, idx: usize) {
+        let Some(from) = self.workspaces.iter().position(|w| w.panes.contains(&id)) else {
+            return;
+        };
+        if from == idx || idx >= self.workspaces.len() || idx == self.scratchpad_ws {
+            return;
+        }
+        let ws = &mut self.workspaces[from];
+        ws.panes.retain(|&p| p != id);
+        if ws.focused == Some(id) {
+            ws.focused = ws.panes.last().copied();
+        }
+        self.workspaces[idx].panes.push(id);
+        self.workspaces[idx].focused = Some(id);
+        if self.scratch_shown == Some(id) {
+            self.scratch_shown = None;
+        }
+        self.dirty = true;
+    }
+
+    // ── Focus history ─────────────────────────────────────────────────────────
+
+    /// Move the focused pane to the front of `focus_history` if it isn't
+    /// there already. Runs after every action and every frame, so focus
+    /// changes made outside `dispatch` are picked up too.
+    fn note_focus(&mut self) {
+        let Some(id) = self.focused_id() else {
+            return;
+        };
+        if self.focus_history.front() == Some(&id) {
+            return;
+        }
+        self.focus_history.retain(|&p| p != id);
+        self.focus_history.push_front(id);
+        self.focus_history.truncate(self.focus_history_len.max(1));
+    }
+
+    /// Open the alt-tab popup on the previous pane, or move its selection
+    /// `delta` entries through the history, wrapping.
+    fn alt_tab_step(&mut self, delta: isize) {
+        self.note_focus();
+        if self.alt_tab.is_none() {
+            let panes: Vec<PaneId> = self
+                .focus_history
+                .iter()
+                .copied()
+                .filter(|id| self.panes.contains_key(id))
+                .collect();
+            if panes.len() < 2 {
+                return;
+            }
+            self.alt_tab = Some(AltTab {
+                panes,
+                selected: 0,
+                highlight: AnimF32::still(0.0),
+            });
+        }
+        let dur = self.anim_dur();
+        let ease = self.anim_ease;
+        let Some(tab) = &mut self.alt_tab else {
+            return;
+        };
+        let n = tab.panes.len() as isize;
+        tab.selected = (tab.selected as isize + delta).rem_euclid(n) as usize;
+        tab.highlight = AnimF32 {
+            src: tab.highlight.current(),
+            dst: tab.selected as f32,
+            start: Instant::now(),
+            dur_ms: dur,
+            ease,
+        };
+        self.dirty = true;
+    }
+
+    pub fn alt_tab_active(&self) -> bool {
+        self.alt_tab.is_some()
+    }
+
+    /// Close the alt-tab popup and focus its selection. Call when the
+    /// modifier held for `AltTabNext` / `AltTabPrev` is released.
+    pub fn alt_tab_finish(&mut self) {
+        let Some(tab) = self.alt_tab.take() else {
+            return;
+        };
+        if let Some(&id) = tab.panes.get(tab.selected) {
+            self.focus_pane(id);
+        }
+        self.note_focus();
+        self.dirty = true;
+    }
+
+    // ── Marks ─────────────────────────────────────────────────────────────────
+
+    /// Put `mark` on `id`. A mark names one pane, so any other pane
+    /// carrying it loses it.
+    pub fn set_mark(&mut self, id: PaneId, mark: &str) {
+        if !self.panes.contains_key(&id) {
+            return;
+        }
+        for p in self.panes.values_mut() {
+            p.marks.remove(mark);
+        }
+        if let Some(p) = self.panes.get_mut(&id) {
+            p.marks.insert(mark.to_owned());
+        }
+    }
+
+    /// Mark the pane showing `app_id` (`mark` in a window rule).
+    /// Returns false when no such pane exists yet; the mark then goes to
+    /// the next pane opened for `app_id`.
+    pub fn mark_app(&mut self, app_id: &str, mark: &str) -> bool {
+        let id = self
+            .panes
+            .iter()
+            .find(|(_, p)| p.content.label() == app_id)
+            .map(|(&id, _)| id);
+        let Some(id) = id else {
+            self.pending_marks
+                .insert(app_id.to_owned(), mark.to_owned());
+            return false;
+        };
+        self.set_mark(id, mark);
+        true
+    }
+
+    /// Give the newly opened pane `id` the mark `mark_app` left for it.
+    fn apply_pending_mark(&mut self, id: PaneId) {
+        let Some(label) = self.panes.get(&id).map(|p| p.content.label().to_owned()) else {
+            return;
+        };
+        if let Some(mark) = self.pending_marks.remove(&label) {
+            self.set_mark(id, &mark);
+        }
+    }
+
+    pub fn pane_for_mark(&self, mark: &str) -> Option<PaneId> {
+        self.panes
+            .iter()
+            .find(|(_, p)| p.marks.contains(mark))
+            .map(|(&id, _)| id)
+    }
+
+    /// Every mark with its pane and that pane's app_id (or title), sorted
+    /// by mark.
+    pub fn marks(&self) -> Vec<(String, PaneId, String)> {
+        let mut marks: Vec<_> = self
+            .panes
+            .values()
+            .flat_map(|p| {
+                p.marks
+                    .iter()
+                    .map(|m| (m.clone(), p.id, p.content.label().to_owned()))
+            })
+            .collect();
+        marks.sort();
+        marks
+    }
+
+    fn is_sticky(&self, id: PaneId) -> bool {
+        self.panes.get(&id).is_some_and(|p| p.sticky)
+    }
+
+    /// Sticky panes of every workspace, minimized ones left out.
+    fn sticky_panes(&self) -> Vec<PaneId> {
+        self.workspaces
+            .iter()
+            .flat_map(|w| w.panes.iter().copied())
+            .filter(|&id| self.is_sticky(id) && !self.minimized.contains(&id))
+            .collect()
+    }
+
+    /// The active workspace's own panes, then the sticky panes shown over
+    /// them — every pane on screen outside expose mode.
+    fn shown_panes(&self) -> Vec<PaneId> {
         let ws = &self.workspaces[self.active_ws];
-        ws.panes.iter().find_map(|&id| {
+        ws.panes
+            .iter()
+            .copied()
+            .filter(|&id| !self.is_sticky(id))
+            .chain(self.sticky_panes())
+            .collect()
+    }
+
+    /// Cell rect for a given embedded app_id on the active workspace,
+    /// sticky panes included.
+    pub fn embedded_cell_rect(&self, app_id: &str) -> Option<Rect> {
+        self.shown_panes().into_iter().find_map(|id| {
             let p = self.panes.get(&id)?;
             if let PaneContent::Embedded { app_id: aid } = &p.content {
                 if aid == app_id {
@@ -644,12 +1490,12 @@ impl TwmState {
         })
     }
 
-    /// All embedded panes on the active workspace: (app_id, cell_rect).
+    /// All embedded panes on the active workspace, sticky panes included:
+    /// (app_id, cell_rect).
     pub fn all_embedded_cell_rects(&self) -> Vec<(String, Rect)> {
-        let ws = &self.workspaces[self.active_ws];
-        ws.panes
-            .iter()
-            .filter_map(|&id| {
+        self.shown_panes()
+            .into_iter()
+            .filter_map(|id| {
                 let p = self.panes.get(&id)?;
                 if let PaneContent::Embedded { app_id } = &p.content {
                     Some((app_id.clone(), p.anim.current()))
@@ -660,9 +1506,40 @@ impl TwmState {
             .collect()
     }
 
+    /// app_ids of the embedded panes on screen, bottom to top: shown, not
+    /// minimized and not covered by another tab.
+    pub fn visible_embedded_apps(&self) -> Vec<String> {
+        self.shown_panes()
+            .into_iter()
+            .filter(|&id| !self.minimized.contains(&id) && !self.is_covered_tab(id))
+            .filter_map(|id| match &self.panes.get(&id)?.content {
+                PaneContent::Embedded { app_id } => Some(app_id.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Geometry updates queued for embedded clients since the last call.
+    /// Feed these into `EmbedIpcServer::push`.
+    pub fn take_embedded_moves(&mut self) -> Vec<EmbedCommand> {
+        self.embedded_windows
+            .values_mut()
+            .flat_map(|w| w.drain())
+            .collect()
+    }
+
     // ── Action dispatch ───────────────────────────────────────────────────────
 
     pub fn dispatch(&mut self, action: &Action) {
+        if self.locked {
+            if let Action::Unlock(pin) = action {
+                self.unlock(pin);
+            }
+            return;
+        }
+        if self.focus_lock_blocks(action) {
+            return;
+        }
         match action {
             Action::FocusLeft => self.focus_dir(-1, 0),
             Action::FocusRight => self.focus_dir(1, 0),
@@ -685,50 +1562,104 @@ impl TwmState {
                 self.dirty = true;
             }
             Action::Close => self.close_focused(),
+            Action::UndoClose => self.undo_close(),
             Action::Workspace(n) => {
                 let idx = n.saturating_sub(1) as usize;
-                if idx < self.workspaces.len() {
-                    self.active_ws = idx;
-                    self.dirty = true;
+                if idx < self.workspaces.len() && idx != self.scratchpad_ws {
+                    self.animate_workspace_switch(idx);
+                }
+            }
+            Action::WorkspaceByName(name) => {
+                let found = (0..self.workspaces.len())
+                    .find(|&i| i != self.scratchpad_ws && &self.workspaces[i].name == name);
+                match found {
+                    Some(idx) => self.animate_workspace_switch(idx),
+                    None => tracing::debug!("WorkspaceByName: no workspace {name:?}"),
                 }
             }
             Action::MoveToWorkspace(n) => {
-                let idx = n.saturating_sub(1) as usize;
-                if idx != self.active_ws && idx < self.workspaces.len() {
-                    if let Some(id) = self.focused_id() {
-                        self.workspaces[self.active_ws].panes.retain(|&p| p != id);
-                        self.workspaces[self.active_ws].focused =
-                            self.workspaces[self.active_ws].panes.last().copied();
-                        self.workspaces[idx].panes.push(id);
-                        self.workspaces[idx].focused = Some(id);
-                        self.dirty = true;
-                    }
+                if let Some(id) = self.focused_id() {
+                    self.move_pane_to_workspace(id, n.saturating_sub(1) as usize);
+                }
+            }
+            Action::Mark(mark) => {
+                if let Some(id) = self.focused_id() {
+                    self.set_mark(id, mark);
+                }
+            }
+            Action::FocusMark(mark) => match self.pane_for_mark(mark) {
+                Some(id) => {
+                    self.focus_pane(id);
+                }
+                None => tracing::debug!("FocusMark: no pane marked {mark:?}"),
+            },
+            Action::FocusApp(app_id) => {
+                if !self.focus_app(app_id) {
+                    tracing::debug!("FocusApp: no pane shows {app_id:?}");
                 }
             }
+            Action::MoveMark(mark, n) => match self.pane_for_mark(mark) {
+                Some(id) => self.move_pane_to_workspace(id, n.saturating_sub(1) as usize),
+                None => tracing::debug!("MoveMark: no pane marked {mark:?}"),
+            },
             Action::NextLayout => {
                 let ws = &mut self.workspaces[self.active_ws];
                 ws.layout = ws.layout.next();
                 self.dirty = true;
             }
             Action::GrowMain => {
-                self.workspaces[self.active_ws].main_ratio =
-                    (self.workspaces[self.active_ws].main_ratio + 0.05).min(0.9);
-                self.dirty = true;
+                let old = self.workspaces[self.active_ws].main_ratio;
+                self.resize_main((old + 0.05).min(0.9));
             }
             Action::ShrinkMain => {
-                self.workspaces[self.active_ws].main_ratio =
-                    (self.workspaces[self.active_ws].main_ratio - 0.05).max(0.1);
-                self.dirty = true;
+                let old = self.workspaces[self.active_ws].main_ratio;
+                self.resize_main((old - 0.05).max(0.1));
+            }
+            Action::FocusOutput(name) => match self.workspace_for_output(name) {
+                Some(ws) if ws == self.scratchpad_ws => {
+                    tracing::debug!("FocusOutput: {name:?} shows the scratchpad")
+                }
+                Some(ws) if ws != self.active_ws => {
+                    self.active_ws = ws;
+                    self.transition = None;
+                    self.dirty = true;
+                }
+                Some(_) => {}
+                None => tracing::debug!("FocusOutput: no workspace on output {name:?}"),
+            },
+            Action::UndoResize => {
+                if let Some(ev) = self.resize_history.pop_back() {
+                    self.set_main_ratio(ev.ws, ev.old_ratio);
+                    self.resize_redo.push(ev);
+                }
+            }
+            Action::RedoResize => {
+                if let Some(ev) = self.resize_redo.pop() {
+                    self.set_main_ratio(ev.ws, ev.new_ratio);
+                    self.resize_history.push_back(ev);
+                }
             }
             Action::NextWorkspace => {
-                self.active_ws = (self.active_ws + 1) % self.workspaces.len();
-                self.dirty = true;
+                let next = self.step_workspace(1);
+                self.animate_workspace_switch(next);
             }
             Action::PrevWorkspace => {
-                let n = self.workspaces.len();
-                self.active_ws = (self.active_ws + n - 1) % n;
-                self.dirty = true;
+                let prev = self.step_workspace(-1);
+                self.animate_workspace_switch(prev);
+            }
+            Action::WorkspaceLeft => self.step_workspace_grid(-1, 0),
+            Action::WorkspaceRight => self.step_workspace_grid(1, 0),
+            Action::WorkspaceUp => self.step_workspace_grid(0, -1),
+            Action::WorkspaceDown => self.step_workspace_grid(0, 1),
+            Action::SendToScratchpad => {
+                if let Some(id) = self.focused_id() {
+                    self.send_to_scratchpad(id);
+                }
             }
+            Action::ToggleScratchpad => match self.scratch_shown.take() {
+                Some(id) if self.panes.contains_key(&id) => self.send_to_scratchpad(id),
+                _ => self.show_scratchpad(),
+            },
             Action::ToggleBar => {
                 self.bar_visible = !self.bar_visible;
                 self.dirty = true;
@@ -742,7 +1673,409 @@ impl TwmState {
                 self.dirty = true;
             }
             Action::OpenShell(title) => self.open_shell_pane(title),
+            Action::SplitHorizontal => {
+                if let Some(id) = self.focused_id() {
+                    self.split_pane(id, SplitDir::Horizontal);
+                }
+            }
+            Action::SplitVertical => {
+                if let Some(id) = self.focused_id() {
+                    self.split_pane(id, SplitDir::Vertical);
+                }
+            }
+            Action::UiClick(id) => {
+                if (1..=self.workspaces.len() as u64).contains(id) {
+                    self.dispatch(&Action::Workspace(*id as u8));
+                } else if *id >= TAB_CLICK_BASE {
+                    self.focus_tab((*id - TAB_CLICK_BASE) as PaneId);
+                } else if *id >= EXPOSE_CLICK_BASE {
+                    self.expose_pick((*id - EXPOSE_CLICK_BASE) as PaneId);
+                } else if *id >= MINIMIZED_CLICK_BASE {
+                    self.unminimize_pane((*id - MINIMIZED_CLICK_BASE) as PaneId);
+                }
+            }
+            Action::Minimize => {
+                if let Some(id) = self.focused_id() {
+                    self.minimize_pane(id);
+                }
+            }
+            Action::Unminimize(id) => self.unminimize_pane(*id),
+            Action::UnminimizeAll => {
+                let ids: Vec<PaneId> = self.minimized.iter().copied().collect();
+                for id in ids {
+                    self.unminimize_pane(id);
+                }
+            }
+            Action::Lock if self.lock_pin.is_none() => {
+                tracing::warn!("Lock: no lock_pin configured, refusing to lock");
+            }
+            Action::Lock => {
+                self.locked = true;
+                self.lock_entry.clear();
+                self.dirty = true;
+            }
+            Action::Unlock(_) => {}
+            Action::Expose => {
+                self.expose_active = !self.expose_active;
+                self.transition = None;
+                self.dirty = true;
+            }
+            Action::ToggleFocusLock => {
+                let lock = self.focus_locked.is_none();
+                self.set_focus_lock(lock);
+            }
+            Action::SetGridCols(cols) => {
+                let ws = &mut self.workspaces[self.active_ws];
+                ws.grid_cols = (*cols).max(1);
+                ws.layout = Layout::Grid { cols: ws.grid_cols };
+                self.dirty = true;
+            }
+            Action::SetPaneGap(v) => {
+                let dur = self.anim_dur();
+                let ease = self.anim_ease;
+                let ws = &mut self.workspaces[self.active_ws];
+                if ws.gap_inner != *v {
+                    ws.gap_inner = *v;
+                    ws.gap_anim = AnimF32 {
+                        src: ws.gap_anim.current(),
+                        dst: *v as f32,
+                        start: Instant::now(),
+                        dur_ms: dur,
+                        ease,
+                    };
+                    self.dirty = true;
+                }
+            }
+            Action::FocusLast => {
+                self.note_focus();
+                if let Some(&id) = self.focus_history.get(1) {
+                    self.focus_pane(id);
+                }
+            }
+            Action::ToggleSticky => {
+                if let Some(p) = self.focused_id().and_then(|id| self.panes.get_mut(&id)) {
+                    p.sticky = !p.sticky;
+                    self.dirty = true;
+                }
+            }
+            Action::AltTabNext => self.alt_tab_step(1),
+            Action::AltTabPrev => self.alt_tab_step(-1),
+        }
+        self.note_focus();
+    }
+
+    /// The workspace `delta` steps from the active one, wrapping and
+    /// skipping the scratchpad.
+    fn step_workspace(&self, delta: isize) -> usize {
+        let n = self.workspaces.len() as isize;
+        let mut i = self.active_ws as isize;
+        for _ in 0..n {
+            i = (i + delta).rem_euclid(n);
+            if i as usize != self.scratchpad_ws {
+                break;
+            }
+        }
+        i as usize
+    }
+
+    /// Workspaces on the grid, in grid order: all but the scratchpad.
+    fn grid_workspaces(&self) -> Vec<usize> {
+        (0..self.workspaces.len())
+            .filter(|&i| i != self.scratchpad_ws)
+            .collect()
+    }
+
+    /// Switch to the active workspace's neighbour on the workspace grid.
+    fn step_workspace_grid(&mut self, dx: isize, dy: isize) {
+        let grid = self.grid_workspaces();
+        let Some(pos) = grid.iter().position(|&i| i == self.active_ws) else {
+            return;
+        };
+        let to = grid_step(pos, grid.len(), self.workspace_grid_cols, dx, dy);
+        self.animate_workspace_switch(grid[to]);
+    }
+
+    /// Move pane `id` from wherever it is onto the scratchpad, without
+    /// switching there.
+    fn send_to_scratchpad(&mut self, id: PaneId) {
+        let Some(from) = self.workspaces.iter().position(|w| w.panes.contains(&id)) else {
+            return;
+        };
+        if from == self.scratchpad_ws {
+            return;
+        }
+        let ws = &mut self.workspaces[from];
+        ws.panes.retain(|&p| p != id);
+        if ws.focused == Some(id) {
+            ws.focused = ws.panes.last().copied();
+        }
+        let pad = &mut self.workspaces[self.scratchpad_ws];
+        pad.panes.push(id);
+        pad.focused = Some(id);
+        if self.scratch_shown == Some(id) {
+            self.scratch_shown = None;
+        }
+        self.dirty = true;
+    }
+
+    /// Bring the most recently stashed scratchpad pane onto the active
+    /// workspace, floating and focused.
+    fn show_scratchpad(&mut self) {
+        if self.active_ws == self.scratchpad_ws {
+            return;
+        }
+        let Some(id) = self.workspaces[self.scratchpad_ws].panes.pop() else {
+            return;
+        };
+        let pad = &mut self.workspaces[self.scratchpad_ws];
+        pad.focused = pad.panes.last().copied();
+        let ws = &mut self.workspaces[self.active_ws];
+        ws.panes.push(id);
+        ws.focused = Some(id);
+        self.scratch_shown = Some(id);
+        self.dirty = true;
+    }
+
+    /// Where a shown scratchpad pane floats: centered, 60% of the content
+    /// area each way.
+    fn scratch_rect(&self) -> Rect {
+        let area = self.content_rect();
+        let (w, h) = (area.width * 3 / 5, area.height * 3 / 5);
+        Rect::new(
+            area.x + (area.width - w) / 2,
+            area.y + (area.height - h) / 2,
+            w,
+            h,
+        )
+    }
+
+    /// Pin focus to the focused pane, or release it.
+    pub fn set_focus_lock(&mut self, lock: bool) {
+        self.focus_locked = if lock { self.focused_id() } else { None };
+    }
+
+    /// Whether `action` would move focus off the pane focus is locked to.
+    /// A lock whose pane has closed is dropped here.
+    fn focus_lock_blocks(&mut self, action: &Action) -> bool {
+        let Some(id) = self.focus_locked else {
+            return false;
+        };
+        if !self.panes.contains_key(&id) {
+            self.focus_locked = None;
+            return false;
+        }
+        matches!(
+            action,
+            Action::FocusLeft
+                | Action::FocusRight
+                | Action::FocusUp
+                | Action::FocusDown
+                | Action::Workspace(_)
+                | Action::WorkspaceByName(_)
+                | Action::FocusMark(_)
+                | Action::FocusApp(_)
+                | Action::FocusLast
+                | Action::AltTabNext
+                | Action::AltTabPrev
+                | Action::ToggleScratchpad
+                | Action::NextWorkspace
+                | Action::PrevWorkspace
+                | Action::WorkspaceLeft
+                | Action::WorkspaceRight
+                | Action::WorkspaceUp
+                | Action::WorkspaceDown
+                | Action::FocusOutput(_)
+        )
+    }
+
+    /// Set the active workspace's `main_ratio`, recording it for undo.
+    fn resize_main(&mut self, ratio: f32) {
+        let ws = self.active_ws;
+        let old = self.workspaces[ws].main_ratio;
+        if ratio == old {
+            return;
+        }
+        if self.resize_history.len() == RESIZE_HISTORY_LEN {
+            self.resize_history.pop_front();
+        }
+        self.resize_history.push_back(ResizeEvent {
+            ws,
+            old_ratio: old,
+            new_ratio: ratio,
+        });
+        self.resize_redo.clear();
+        self.set_main_ratio(ws, ratio);
+    }
+
+    fn set_main_ratio(&mut self, ws: usize, ratio: f32) {
+        if let Some(w) = self.workspaces.get_mut(ws) {
+            w.main_ratio = ratio;
+            self.dirty = true;
+        }
+    }
+
+    /// Switch to workspace index `to`, sliding the old panes out and the new
+    /// ones in from the opposite edge. Higher indices slide left, lower ones
+    /// slide right. With animations disabled this is a plain switch, and a
+    /// switch made while the last one is still sliding lands at once.
+    pub fn animate_workspace_switch(&mut self, to: usize) {
+        if to == self.active_ws || to >= self.workspaces.len() {
+            return;
+        }
+        if self.transition.take().is_some_and(|t| !t.anim.is_done()) {
+            self.snap_workspace_switch(to);
+            return;
+        }
+        let dur = if self.anim_enabled {
+            self.workspace_anim_ms
+        } else {
+            0.0
+        };
+        let ease = Easing::EaseInOut;
+        let shift = if to > self.active_ws {
+            -(self.cols as f64)
+        } else {
+            self.cols as f64
+        };
+
+        // Sticky panes are on both workspaces and stay where they are.
+        if dur > 0.0 {
+            for &id in &self.workspaces[self.active_ws].panes {
+                if let Some(p) = self.panes.get_mut(&id).filter(|p| !p.sticky) {
+                    let cur = p.anim.current_rf();
+                    p.anim = AnimRect {
+                        src: cur,
+                        dst: cur.shifted_x(shift),
+                        start: Instant::now(),
+                        dur_ms: dur,
+                        ease,
+                    };
+                }
+            }
+        }
+
+        let from_ws = self.active_ws;
+        self.active_ws = to;
+        if dur <= 0.0 {
+            self.reflow();
+            return;
+        }
+        let area = RF::from(self.content_rect());
+        self.transition = Some(WorkspaceTransition {
+            from_ws,
+            to_ws: to,
+            anim: AnimRect {
+                src: area,
+                dst: area.shifted_x(shift),
+                start: Instant::now(),
+                dur_ms: dur,
+                ease,
+            },
+        });
+
+        let (ids, rects) = self.layout_rects();
+        for (id, rect) in ids.iter().zip(rects) {
+            if let Some(p) = self.panes.get_mut(id).filter(|p| !p.sticky) {
+                let dst = RF::from(rect);
+                p.anim = AnimRect {
+                    src: dst.shifted_x(-shift),
+                    dst,
+                    start: Instant::now(),
+                    dur_ms: dur,
+                    ease,
+                };
+            }
+        }
+        self.dirty = false;
+        self.sync_embedded_geometry();
+    }
+
+    /// Cut straight to workspace `to`: nothing slides out and its panes are
+    /// put at their final rects.
+    fn snap_workspace_switch(&mut self, to: usize) {
+        self.active_ws = to;
+        let (ids, rects) = self.layout_rects();
+        for (id, rect) in ids.iter().zip(rects) {
+            if let Some(p) = self.panes.get_mut(id) {
+                p.anim = AnimRect::still(rect);
+            }
+        }
+        self.dirty = false;
+        self.sync_embedded_geometry();
+    }
+
+    /// Leave expose mode on the workspace holding `id`, focused on it.
+    fn expose_pick(&mut self, id: PaneId) {
+        let Some(ws) = self.workspaces.iter().position(|w| w.panes.contains(&id)) else {
+            return;
+        };
+        self.expose_active = false;
+        self.active_ws = ws;
+        self.workspaces[ws].focused = Some(id);
+        self.dirty = true;
+    }
+
+    /// Id of the chrome click region under pixel `(x, y)`, if any.
+    /// Dispatch it as `Action::UiClick(id)`.
+    pub fn click_at(&self, x: u32, y: u32) -> Option<u64> {
+        crate::pixelui::hit_test(&self.click_regions, x, y)
+    }
+
+    fn unlock(&mut self, pin: &str) {
+        let ok = match &self.lock_pin {
+            None => true,
+            Some(_) if pin.is_empty() => false,
+            Some(hash) => bcrypt::verify(pin, hash).unwrap_or_else(|e| {
+                tracing::warn!("lock_pin is not a valid bcrypt hash: {e}");
+                false
+            }),
+        };
+        if ok {
+            self.locked = false;
+            self.dirty = true;
+        } else {
+            tracing::info!("unlock: wrong PIN");
         }
+        self.lock_entry.clear();
+    }
+
+    /// Draw `widget` over the chrome at pixel rect `rect` on the next frame.
+    /// Widgets are consumed when rendered, so re-queue them every frame;
+    /// any click regions they register are hit-tested like the bar's.
+    pub fn queue_widget(&mut self, rect: PixRect, widget: Box<dyn WidgetDyn>) {
+        self.overlay_widgets.push((rect, widget));
+    }
+
+    /// Draw `layer` over the chrome on every frame from now on. Layers are
+    /// drawn in registration order, under any queued widgets.
+    pub fn register_layer(&mut self, layer: Box<dyn ChromeLayer>) {
+        tracing::debug!("twm: registered chrome layer {:?}", layer.name());
+        self.chrome_layers.push(layer);
+    }
+
+    fn render_chrome_layers(&mut self, vp_w: u32, vp_h: u32) -> Vec<DrawCmd> {
+        if self.chrome_layers.is_empty() {
+            return Vec::new();
+        }
+        let area = PixRect::new(0, 0, vp_w, vp_h);
+        let mut ctx = DrawContext::new(area, self.cell_w, self.cell_h);
+        for layer in &mut self.chrome_layers {
+            layer.render(&mut ctx, area, self.cell_w, self.cell_h);
+        }
+        self.click_regions.extend(ctx.clickable_regions);
+        ctx.cmds
+    }
+
+    fn render_overlay_widgets(&mut self, vp_w: u32, vp_h: u32) -> Vec<DrawCmd> {
+        if self.overlay_widgets.is_empty() {
+            return Vec::new();
+        }
+        let mut ctx = DrawContext::new(PixRect::new(0, 0, vp_w, vp_h), self.cell_w, self.cell_h);
+        for (rect, widget) in self.overlay_widgets.drain(..) {
+            ctx.render_boxed(widget, rect);
+        }
+        self.click_regions.extend(ctx.clickable_regions);
+        ctx.cmds
     }
 
     // ── Main entry point called from your render path ─────────────────────────
@@ -776,6 +2109,8 @@ impl TwmState {
         // the em_scale fix grows cell_h by ~32%), and the ratatui cell grid must
         // always exactly tile the viewport or content gets cut off / mispositioned.
         if cell_w > 0 && cell_h > 0 {
+            self.cell_w = cell_w;
+            self.cell_h = cell_h;
             let cols = (vp_w / cell_w).max(1) as u16;
             let rows = (vp_h / cell_h).max(1) as u16;
             if cols != self.cols || rows != self.rows {
@@ -785,7 +2120,16 @@ impl TwmState {
 
         if self.dirty {
             self.reflow();
+        } else {
+            self.sync_embedded_geometry();
         }
+        self.note_focus();
+
+        // Dropped once it has run, or if something else changed workspace.
+        let active_ws = self.active_ws;
+        self.transition = self
+            .transition
+            .filter(|t| t.to_ws == active_ws && !t.anim.is_done());
 
         let area = Rect::new(0, 0, self.cols, self.rows);
 
@@ -794,17 +2138,152 @@ impl TwmState {
 
         // Render into a *local* buffer; self.buf is not borrowed during render.
         let mut buf = Buffer::empty(area);
-        TwmRenderer { snap: &snap }.render_all(&mut buf, area);
+        let mut renderer = TwmRenderer {
+            snap: &snap,
+            bar_clicks: Vec::new(),
+        };
+        renderer.render_all(&mut buf, area);
+        let bar_clicks = renderer.bar_clicks;
 
         // Copy result into self.buf (needed by cells.blit below).
         self.buf = buf;
 
-        self.cells.blit(&self.buf);
-        self.cells.to_draw_cmds(cell_w, cell_h, vp_w, vp_h)
+        self.cells.import(&self.buf);
+        self.click_regions = bar_clicks
+            .into_iter()
+            .map(|(r, id)| (self.cells_to_px(r), id))
+            .collect();
+        self.queue_bar_gauges(&snap);
+        if self.expose_active {
+            self.collect_expose_regions();
+        } else {
+            self.collect_tab_regions();
+        }
+        if self.locked {
+            self.click_regions.clear();
+            self.overlay_widgets.clear();
+            return vec![DrawCmd::FillRect {
+                rect: PixRect::new(0, 0, vp_w, vp_h),
+                color: PixColor::BLACK,
+            }];
+        }
+        let mut cmds = self.cells.to_draw_cmds(cell_w, cell_h, vp_w, vp_h);
+        cmds.extend(self.bar_separator_cmd(&snap));
+        cmds.extend(self.render_chrome_layers(vp_w, vp_h));
+        cmds.extend(self.render_overlay_widgets(vp_w, vp_h));
+        cmds
     }
 
     // ── Internal ──────────────────────────────────────────────────────────────
 
+    /// Pixel rect covering the cells of `r`.
+    fn cells_to_px(&self, r: Rect) -> PixRect {
+        PixRect::new(
+            r.x as u32 * self.cell_w,
+            r.y as u32 * self.cell_h,
+            r.width as u32 * self.cell_w,
+            r.height as u32 * self.cell_h,
+        )
+    }
+
+    /// Queue the `cpu` and `battery` modules' gauges over their bar text,
+    /// leaving the padding cell on either side.
+    fn queue_bar_gauges(&mut self, snap: &TwmSnapshot) {
+        if !self.bar_visible {
+            return;
+        }
+        let [r, g, b] = self.bar_bg;
+        let track = PixStyle::default().bg(PixColor::rgb(r, g, b));
+        let [r, g, b] = self.bar_fg;
+        let track = track.fg(PixColor::rgb(r, g, b));
+        let [r, g, b] = self.active_border;
+        let filled = PixStyle::default().bg(PixColor::rgb(r, g, b));
+        let gauges: Vec<(Rect, ProgressBar)> = bar_slots(snap)
+            .into_iter()
+            .flatten()
+            .filter_map(|(name, slot)| {
+                let gauge = match name {
+                    "cpu" => cpu_gauge(snap.bar_cpu?),
+                    "battery" => snap.bar_battery?.gauge(),
+                    _ => return None,
+                };
+                Some((slot, gauge))
+            })
+            .collect();
+        for (slot, gauge) in gauges {
+            if slot.width <= 2 {
+                continue;
+            }
+            let rect = PixRect::new(
+                (slot.x + 1) as u32 * self.cell_w,
+                slot.y as u32 * self.cell_h,
+                (slot.width - 2) as u32 * self.cell_w,
+                self.cell_h,
+            );
+            let gauge = gauge.style(track).filled_style(filled);
+            self.queue_widget(rect, Box::new(gauge));
+        }
+    }
+
+    /// Dashed rule between the left and centre bar modules, or None when the
+    /// two touch or either side is empty.
+    fn bar_separator_cmd(&self, snap: &TwmSnapshot) -> Option<DrawCmd> {
+        if !self.bar_visible {
+            return None;
+        }
+        let bar = self.bar_rect();
+        let [left, centre, _] = bar_slots(snap);
+        let tabs_end = left.iter().map(|(_, r)| r.x + r.width).max()? as u32 * self.cell_w;
+        let label_x = centre.iter().map(|(_, r)| r.x).min()? as u32 * self.cell_w;
+        if label_x <= tabs_end + self.cell_w {
+            return None;
+        }
+        let x = (tabs_end + label_x) / 2;
+        let [r, g, b] = self.inactive_title;
+        Some(DrawCmd::DashedLine {
+            x1: x,
+            y1: bar.y as u32 * self.cell_h + 2,
+            x2: x,
+            y2: (bar.y + bar.height) as u32 * self.cell_h - 3,
+            dash_len: 3,
+            gap_len: 2,
+            color: PixColor::rgb(r, g, b),
+            thickness: 1,
+        })
+    }
+
+    fn collect_expose_regions(&mut self) {
+        let (ids, rects) = self.layout_rects();
+        for (id, r) in ids.into_iter().zip(rects) {
+            let r = self.cells_to_px(r);
+            self.click_regions.push((r, EXPOSE_CLICK_BASE + id as u64));
+        }
+    }
+
+    fn collect_tab_regions(&mut self) {
+        let Some((strip, tabs)) = self.tab_strip() else {
+            return;
+        };
+        let cells = tab_cells(strip, tabs.len());
+        for (id, r) in tabs.into_iter().zip(cells) {
+            let r = self.cells_to_px(r);
+            self.click_regions.push((r, TAB_CLICK_BASE + id as u64));
+        }
+    }
+
+    /// Bar labels of the active workspace's minimized panes, in `ws.panes` order.
+    fn minimized_tabs(&self) -> Vec<(PaneId, String)> {
+        self.workspaces[self.active_ws]
+            .panes
+            .iter()
+            .filter(|id| self.minimized.contains(id))
+            .filter_map(|&id| {
+                let p = self.panes.get(&id)?;
+                Some((id, format!(" ↓{} ", truncate(p.content.label(), 16))))
+            })
+            .collect()
+    }
+
     fn focus_dir(&mut self, dx: i32, dy: i32) {
         let fid = match self.focused_id() {
             Some(id) => id,
@@ -814,13 +2293,22 @@ impl TwmState {
             Some(p) => p.anim.current(),
             None => return,
         };
+        // Tabs share one rect, so left/right step through them instead.
+        if dx != 0 && self.is_tabbed() {
+            let tabs = self.tiled_panes();
+            if let Some(i) = tabs.iter().position(|&id| id == fid) {
+                let next = (i as i32 + dx).rem_euclid(tabs.len() as i32) as usize;
+                self.focus_tab(tabs[next]);
+                return;
+            }
+        }
         let cx = cur.x as i32 + cur.width as i32 / 2;
         let cy = cur.y as i32 + cur.height as i32 / 2;
         let ws = &self.workspaces[self.active_ws];
         let best = ws
             .panes
             .iter()
-            .filter(|&&id| id != fid)
+            .filter(|&&id| id != fid && !self.minimized.contains(&id))
             .filter_map(|&id| self.panes.get(&id).map(|p| (id, p.anim.current())))
             .filter(|(_, r)| {
                 let rx = r.x as i32 + r.width as i32 / 2;
@@ -842,6 +2330,83 @@ impl TwmState {
         }
     }
 
+    /// Whether the active workspace shows its tiled panes as tabs.
+    fn is_tabbed(&self) -> bool {
+        !self.expose_active && self.workspaces[self.active_ws].layout == Layout::Tabbed
+    }
+
+    fn focus_tab(&mut self, id: PaneId) {
+        if self.workspaces[self.active_ws].panes.contains(&id) {
+            self.workspaces[self.active_ws].focused = Some(id);
+            self.dirty = true;
+        }
+    }
+
+    /// Whether `id` is a tab hidden behind the showing one.
+    fn is_covered_tab(&self, id: PaneId) -> bool {
+        self.is_tabbed() && Some(id) != self.visible_tab() && self.tiled_panes().contains(&id)
+    }
+
+    /// The tiled pane whose tab is showing: the focused one, or the first tab
+    /// while focus is on a floating or sticky pane.
+    fn visible_tab(&self) -> Option<PaneId> {
+        let tabs = self.tiled_panes();
+        self.focused_id()
+            .filter(|id| tabs.contains(id))
+            .or_else(|| tabs.first().copied())
+    }
+
+    /// The tab strip of a tabbed workspace and the panes it lists, in
+    /// `ws.panes` order.
+    fn tab_strip(&self) -> Option<(Rect, Vec<PaneId>)> {
+        if !self.is_tabbed() {
+            return None;
+        }
+        let tabs = self.tiled_panes();
+        if tabs.is_empty() {
+            return None;
+        }
+        let area = self.tiled_area(tabs.len());
+        Some((
+            Rect {
+                height: area.height.min(1),
+                ..area
+            },
+            tabs,
+        ))
+    }
+
+    /// Active-workspace panes laid out by the workspace layout: not
+    /// minimized, sticky, or the shown scratchpad pane.
+    fn tiled_panes(&self) -> Vec<PaneId> {
+        let ws = &self.workspaces[self.active_ws];
+        let floating = self.scratch_shown.filter(|id| ws.panes.contains(id));
+        ws.panes
+            .iter()
+            .copied()
+            .filter(|&id| {
+                !self.minimized.contains(&id) && Some(id) != floating && !self.is_sticky(id)
+            })
+            .collect()
+    }
+
+    /// The content rect inside the active workspace's outer gap, for `tiled`
+    /// panes.
+    fn tiled_area(&self, tiled: usize) -> Rect {
+        // A lone pane has nothing to be separated from; the workspace's gaps
+        // are left as they are and apply again once a second pane shows up.
+        let single = self.no_gap_when_single && tiled == 1;
+        let gap_outer = if single {
+            0
+        } else {
+            self.workspaces[self.active_ws].gap_outer
+        };
+        self.content_rect().inner(&Margin {
+            horizontal: gap_outer,
+            vertical: gap_outer,
+        })
+    }
+
     fn anim_dur(&self) -> f64 {
         if self.anim_enabled {
             self.anim_duration_ms
@@ -850,24 +2415,91 @@ impl TwmState {
         }
     }
 
-    fn reflow(&mut self) {
+    /// Target rects for the active workspace's panes, in `ws.panes` order.
+    fn layout_rects(&self) -> (Vec<PaneId>, Vec<Rect>) {
+        if self.expose_active {
+            let ids: Vec<PaneId> = self
+                .workspaces
+                .iter()
+                .flat_map(|w| w.panes.iter().copied())
+                .filter(|id| !self.minimized.contains(id))
+                .collect();
+            let cols = (ids.len() as f64).sqrt().ceil() as u16;
+            let rects = grid_split(self.content_rect(), ids.len(), cols, 1);
+            return (ids, rects);
+        }
+
         let ws = &self.workspaces[self.active_ws];
-        let ids: Vec<PaneId> = ws.panes.clone();
+        let floating = self.scratch_shown.filter(|id| ws.panes.contains(id));
+        // Sticky panes keep the rect they were made sticky at, on top.
+        let sticky = self.sticky_panes();
+        let sticky_rects = sticky
+            .iter()
+            .map(|id| Rect::from(self.panes[id].anim.dst))
+            .collect::<Vec<_>>();
+        let mut ids = self.tiled_panes();
         if ids.is_empty() {
-            self.dirty = false;
-            return;
+            let mut rects = vec![];
+            if let Some(id) = floating {
+                ids.push(id);
+                rects.push(self.scratch_rect());
+            }
+            ids.extend(sticky);
+            rects.extend(sticky_rects);
+            return (ids, rects);
         }
 
-        let content_area = self.content_rect();
-        let gap = ws.gap;
-        let rects = match ws.layout {
-            Layout::Bsp => bsp_split(content_area, ids.len(), gap),
+        let single = self.no_gap_when_single && ids.len() == 1;
+        let content_area = self.tiled_area(ids.len());
+        let gap = if single {
+            0
+        } else {
+            ws.gap_anim.current().round() as u16
+        };
+        let mut rects = match ws.layout {
+            Layout::Bsp => {
+                let hints: Vec<Option<SplitDir>> = ids
+                    .iter()
+                    .map(|id| self.panes.get(id).and_then(|p| p.split))
+                    .collect();
+                bsp_split(content_area, &hints, gap)
+            }
             Layout::Columns => col_split(content_area, ids.len(), ws.main_ratio, gap),
             Layout::Rows => row_split(content_area, ids.len(), ws.main_ratio, gap),
             Layout::Monocle => vec![content_area; ids.len()],
+            Layout::Tabbed => {
+                let body = Rect {
+                    y: content_area.y + content_area.height.min(1),
+                    height: content_area.height.saturating_sub(1),
+                    ..content_area
+                };
+                vec![body; ids.len()]
+            }
+            Layout::Spiral => spiral_split(content_area, ids.len(), gap),
+            Layout::Grid { cols } => grid_split(content_area, ids.len(), cols, gap),
         };
+        // Last, so it is drawn over the tiled panes.
+        if let Some(id) = floating {
+            ids.push(id);
+            rects.push(self.scratch_rect());
+        }
+        ids.extend(sticky);
+        rects.extend(sticky_rects);
+        (ids, rects)
+    }
 
-        let dur = self.anim_dur();
+    fn reflow(&mut self) {
+        let (ids, rects) = self.layout_rects();
+        if ids.is_empty() {
+            self.dirty = false;
+            return;
+        }
+
+        // While the gap animates the rects are recomputed every frame from
+        // the in-between gap, so panes jump straight to them instead of
+        // starting a fresh slide per frame.
+        let gaps_animating = self.gaps_animating();
+        let dur = if gaps_animating { 0.0 } else { self.anim_dur() };
         let ease = self.anim_ease;
         for (i, &id) in ids.iter().enumerate() {
             let dst = rects[i];
@@ -884,7 +2516,48 @@ impl TwmState {
                 }
             }
         }
-        self.dirty = false;
+        // Stay dirty until the gap settles so the final frame is laid out too.
+        self.dirty = gaps_animating;
+        self.sync_embedded_geometry();
+    }
+
+    /// Send the settled rect of every embedded pane on the active workspace.
+    /// Panes still mid-animation, and everything while a gap change animates,
+    /// are skipped so the client only ever sees the final geometry, never the
+    /// in-flight frames.
+    fn sync_embedded_geometry(&mut self) {
+        if self.gaps_animating() {
+            return;
+        }
+        let (cw, ch) = (self.cell_w, self.cell_h);
+        for id in self.shown_panes() {
+            if self.minimized.contains(&id) {
+                continue;
+            }
+            let Some(p) = self.panes.get(&id) else {
+                continue;
+            };
+            let PaneContent::Embedded { app_id } = &p.content else {
+                continue;
+            };
+            if !p.anim.is_done() {
+                continue;
+            }
+            let inset = u16::from(!self.no_decor_panes.contains(&id));
+            let inner = Rect::from(p.anim.dst).inner(&Margin {
+                horizontal: inset,
+                vertical: inset,
+            });
+            self.embedded_windows
+                .entry(app_id.clone())
+                .or_insert_with(|| EmbeddedWindow::new(app_id))
+                .set_geometry(
+                    inner.x as i32 * cw as i32,
+                    inner.y as i32 * ch as i32,
+                    inner.width as u32 * cw,
+                    inner.height as u32 * ch,
+                );
+        }
     }
 
     fn content_rect(&self) -> Rect {
@@ -928,6 +2601,9 @@ struct PaneSnap {
     content: PaneContent,
     fullscreen: bool,
     focused: bool,
+    no_decor: bool,
+    // workspace name shown in the title while in expose mode
+    expose_ws: Option<String>,
 }
 
 struct TwmSnapshot {
@@ -939,6 +2615,12 @@ struct TwmSnapshot {
     bar_rect: Rect,
     bar_at_bottom: bool,
     bar_clock: String,
+    bar_media: Option<String>,
+    bar_modules: [Vec<String>; 3],
+    // focused pane's label, for the `title` module
+    bar_title: Option<String>,
+    bar_cpu: Option<u8>,
+    bar_battery: Option<BatteryStatus>,
     // colours
     active_border: [u8; 3],
     inactive_border: [u8; 3],
@@ -947,36 +2629,87 @@ struct TwmSnapshot {
     pane_bg: [u8; 3],
     bar_bg: [u8; 3],
     bar_fg: [u8; 3],
-    // workspace tab info: (index, occupied, active)
-    ws_tabs: Vec<(usize, bool, bool)>,
+    // workspace tab info: (index, name, occupied, active), in grid order
+    ws_tabs: Vec<(usize, String, bool, bool)>,
+    ws_grid_cols: usize,
+    minimized_tabs: Vec<(PaneId, String)>,
+    // tabbed layout: the strip and each tab's (label, showing)
+    tab_strip: Option<(Rect, Vec<(String, bool)>)>,
     content_area: Rect,
+    // alt-tab popup: pane labels and the (animated) highlighted row
+    alt_tab: Option<(Vec<String>, f32)>,
 }
 
 impl TwmSnapshot {
     fn from_state(s: &TwmState) -> Self {
         let focused_id = s.focused_id();
         let ws = &s.workspaces[s.active_ws];
+        let content_area = s.content_rect();
 
-        let panes = ws
-            .panes
-            .iter()
-            .filter_map(|&id| {
+        // Outgoing panes from a workspace slide are drawn underneath.
+        let leaving = s.transition.iter().flat_map(|t| {
+            s.workspaces[t.from_ws].panes.iter().filter_map(|&id| {
+                if s.is_sticky(id) || s.minimized.contains(&id) {
+                    return None;
+                }
                 let p = s.panes.get(&id)?;
                 Some(PaneSnap {
                     id,
-                    rect: p.anim.current(),
+                    rect: p.anim.current_in(content_area),
                     content: p.content.clone(),
-                    fullscreen: p.fullscreen,
-                    focused: Some(id) == focused_id,
+                    fullscreen: false,
+                    focused: false,
+                    no_decor: s.no_decor_panes.contains(&id),
+                    expose_ws: None,
                 })
             })
-            .collect();
+        });
+        let panes = if s.expose_active {
+            // Every workspace's panes, each titled with its workspace.
+            let ids = s.layout_rects().0;
+            ids.into_iter()
+                .filter_map(|id| {
+                    let p = s.panes.get(&id)?;
+                    let wi = s.workspaces.iter().position(|w| w.panes.contains(&id))?;
+                    Some(PaneSnap {
+                        id,
+                        rect: p.anim.current_in(content_area),
+                        content: p.content.clone(),
+                        fullscreen: false,
+                        focused: Some(id) == focused_id,
+                        no_decor: false,
+                        expose_ws: Some(s.workspaces[wi].name.clone()),
+                    })
+                })
+                .collect()
+        } else {
+            // Sticky panes, this workspace's and others', come last so they
+            // are drawn on top.
+            leaving
+                .chain(s.shown_panes().into_iter().filter_map(|id| {
+                    if s.minimized.contains(&id) || s.is_covered_tab(id) {
+                        return None;
+                    }
+                    let p = s.panes.get(&id)?;
+                    Some(PaneSnap {
+                        id,
+                        rect: p.anim.current_in(content_area),
+                        content: p.content.clone(),
+                        fullscreen: p.fullscreen,
+                        focused: Some(id) == focused_id,
+                        no_decor: s.no_decor_panes.contains(&id),
+                        expose_ws: None,
+                    })
+                }))
+                .collect()
+        };
 
         let ws_tabs = s
             .workspaces
             .iter()
             .enumerate()
-            .map(|(i, w)| (i, !w.panes.is_empty(), i == s.active_ws))
+            .filter(|&(i, _)| i != s.scratchpad_ws)
+            .map(|(i, w)| (i, w.name.clone(), !w.panes.is_empty(), i == s.active_ws))
             .collect();
 
         Self {
@@ -988,6 +2721,13 @@ impl TwmSnapshot {
             bar_rect: s.bar_rect(),
             bar_at_bottom: s.bar_at_bottom,
             bar_clock: s.bar_clock.clone(),
+            bar_media: s.bar_media.clone(),
+            bar_modules: s.bar_modules.clone(),
+            bar_title: focused_id
+                .and_then(|id| s.panes.get(&id))
+                .map(|p| p.content.label().to_owned()),
+            bar_cpu: s.cpu,
+            bar_battery: s.battery,
             active_border: s.active_border,
             inactive_border: s.inactive_border,
             active_title: s.active_title,
@@ -996,17 +2736,40 @@ impl TwmSnapshot {
             bar_bg: s.bar_bg,
             bar_fg: s.bar_fg,
             ws_tabs,
-            content_area: s.content_rect(),
+            ws_grid_cols: s.workspace_grid_cols,
+            minimized_tabs: s.minimized_tabs(),
+            tab_strip: s.tab_strip().map(|(strip, tabs)| {
+                let visible = s.visible_tab();
+                let tabs = tabs
+                    .into_iter()
+                    .map(|id| {
+                        let label = s.panes.get(&id).map_or("", |p| p.content.label());
+                        (label.to_owned(), Some(id) == visible)
+                    })
+                    .collect();
+                (strip, tabs)
+            }),
+            content_area,
+            alt_tab: s.alt_tab.as_ref().map(|tab| {
+                let labels = tab
+                    .panes
+                    .iter()
+                    .map(|id| s.panes.get(id).map_or("", |p| p.content.label()).to_owned())
+                    .collect();
+                (labels, tab.highlight.current())
+            }),
         }
     }
 }
 
 struct TwmRenderer<'a> {
     snap: &'a TwmSnapshot,
+    /// Cells of each bar tab as drawn, with its click id.
+    bar_clicks: Vec<(Rect, u64)>,
 }
 
 impl<'a> TwmRenderer<'a> {
-    fn render_all(&self, buf: &mut Buffer, area: Rect) {
+    fn render_all(&mut self, buf: &mut Buffer, area: Rect) {
         let s = self.snap;
         let ab = ratatui_rgb(s.active_border);
         let ib = ratatui_rgb(s.inactive_border);
@@ -1061,16 +2824,25 @@ impl<'a> TwmRenderer<'a> {
                     PaneContent::Shell { title } => format!(" {title} [{}] ◆ ", pane.id),
                     PaneContent::Empty => format!(" empty [{}] ", pane.id),
                 };
+                let raw = match &pane.expose_ws {
+                    Some(name) => format!(" {name}:{raw}"),
+                    None => raw,
+                };
                 truncate(&raw, r.width.saturating_sub(4) as usize)
             };
 
-            let block = Block::default()
-                .borders(Borders::ALL)
-                .border_style(border_style)
-                .title(Span::styled(title_text, title_style))
-                .style(Style::default().bg(bg));
-            let inner = block.inner(r);
-            block.render(r, buf);
+            let inner = if pane.no_decor {
+                r
+            } else {
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(border_style)
+                    .title(Span::styled(title_text, title_style))
+                    .style(Style::default().bg(bg));
+                let inner = block.inner(r);
+                block.render(r, buf);
+                inner
+            };
 
             if inner.width > 0 && inner.height > 0 {
                 match &pane.content {
@@ -1093,12 +2865,86 @@ impl<'a> TwmRenderer<'a> {
             }
         }
 
+        if let Some((strip, tabs)) = &s.tab_strip {
+            self.render_tab_strip(buf, *strip, tabs);
+        }
         if s.bar_visible {
             self.render_bar(buf);
         }
+        if let Some((labels, highlight)) = &s.alt_tab {
+            self.render_alt_tab(buf, labels, *highlight);
+        }
+    }
+
+    /// One tab per pane across `strip`, the showing tab in the active border
+    /// colour.
+    fn render_tab_strip(&self, buf: &mut Buffer, strip: Rect, tabs: &[(String, bool)]) {
+        let s = self.snap;
+        let accent = ratatui_rgb(s.active_border);
+        for (r, (label, showing)) in tab_cells(strip, tabs.len()).into_iter().zip(tabs) {
+            let style = if *showing {
+                Style::default()
+                    .fg(Color::Black)
+                    .bg(accent)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+                    .fg(ratatui_rgb(s.inactive_title))
+                    .bg(ratatui_rgb(s.bar_bg))
+            };
+            let text = truncate(&format!(" {label}"), r.width as usize);
+            let text = format!("{text:<width$}", width = r.width as usize);
+            buf.set_string(r.x, r.y, text, style);
+        }
+    }
+
+    /// The alt-tab popup, centred on the content area: one row per pane,
+    /// newest first, with the highlight on row `highlight` (rounded while it
+    /// slides).
+    fn render_alt_tab(&self, buf: &mut Buffer, labels: &[String], highlight: f32) {
+        let s = self.snap;
+        let area = s.content_area;
+        let widest = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        let w = (widest as u16 + 6).max(20).min(area.width);
+        let h = (labels.len() as u16 + 2).min(area.height);
+        if w < 3 || h < 3 {
+            return;
+        }
+        let r = Rect::new(
+            area.x + (area.width - w) / 2,
+            area.y + (area.height - h) / 2,
+            w,
+            h,
+        );
+        let accent = ratatui_rgb(s.active_border);
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(accent))
+            .title(Span::styled(" windows ", Style::default().fg(accent)))
+            .style(Style::default().bg(ratatui_rgb(s.bar_bg)));
+        let inner = block.inner(r);
+        block.render(r, buf);
+
+        let row = highlight.round().max(0.0) as u16;
+        for (i, label) in labels.iter().enumerate().take(inner.height as usize) {
+            let y = inner.y + i as u16;
+            let style = if i as u16 == row {
+                Style::default()
+                    .fg(Color::Black)
+                    .bg(accent)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+                    .fg(ratatui_rgb(s.bar_fg))
+                    .bg(ratatui_rgb(s.bar_bg))
+            };
+            let text = truncate(&format!(" {label}"), inner.width as usize);
+            let text = format!("{text:<width$}", width = inner.width as usize);
+            buf.set_string(inner.x, y, text, style);
+        }
     }
 
-    fn render_bar(&self, buf: &mut Buffer) {
+    fn render_bar(&mut self, buf: &mut Buffer) {
         let s = self.snap;
         let area = s.bar_rect;
         if area.width == 0 || area.height == 0 {
@@ -1107,81 +2953,233 @@ impl<'a> TwmRenderer<'a> {
 
         let bar_bg = ratatui_rgb(s.bar_bg);
         let bar_fg = ratatui_rgb(s.bar_fg);
-        let accent = ratatui_rgb(s.active_border);
-        let dim = ratatui_rgb(s.inactive_title);
+        for y in area.y..area.y + area.height {
+            for x in area.x..area.x + area.width {
+                buf.get_mut(x, y)
+                    .set_char(' ')
+                    .set_style(Style::default().bg(bar_bg).fg(bar_fg));
+            }
+        }
 
-        for x in area.x..area.x + area.width {
-            buf.get_mut(x, area.y)
-                .set_char(' ')
-                .set_style(Style::default().bg(bar_bg).fg(bar_fg));
+        for (name, slot) in bar_slots(s).into_iter().flatten() {
+            render_bar_module(name, s, buf, slot, &mut self.bar_clicks);
         }
+    }
+}
 
-        // Left: workspace tabs
-        let mut x = area.x + 1;
-        for &(i, occupied, active) in &s.ws_tabs {
-            if x >= area.x + area.width {
-                break;
+/// Text of a one-line bar module, padded as drawn; None when it has
+/// nothing to show.
+fn bar_module_text(name: &str, snap: &TwmSnapshot) -> Option<String> {
+    match name {
+        "layout" => Some(format!(" [{}] ", snap.layout_label)),
+        "clock" => Some(format!(" {} ", snap.bar_clock)),
+        "media" => snap.bar_media.as_ref().map(|m| format!(" {m} │")),
+        "title" => snap
+            .bar_title
+            .as_ref()
+            .map(|t| format!(" {} ", truncate(t, 48))),
+        "cpu" => snap.bar_cpu.map(|c| format!(" {} ", cpu_label(c))),
+        "battery" => snap.bar_battery.map(|b| format!(" {} ", b.label())),
+        _ => None,
+    }
+}
+
+/// Bar cells the workspace tabs are laid out in, as the workspace grid.
+fn bar_ws_tabs(snap: &TwmSnapshot) -> (Vec<String>, TabLayout) {
+    let labels: Vec<String> = snap
+        .ws_tabs
+        .iter()
+        .map(|(_, name, ..)| format!(" {name} "))
+        .collect();
+    let widths: Vec<u16> = labels.iter().map(|l| l.chars().count() as u16).collect();
+    let tabs = ws_tab_layout(&widths, snap.ws_grid_cols, snap.bar_rect.height);
+    (labels, tabs)
+}
+
+/// Cells a bar module wants; 0 = nothing to draw.
+fn bar_module_width(name: &str, snap: &TwmSnapshot) -> u16 {
+    match name {
+        "workspaces" => {
+            let minimized: u16 = snap
+                .minimized_tabs
+                .iter()
+                .map(|(_, l)| l.chars().count() as u16 + 1)
+                .sum();
+            bar_ws_tabs(snap).1.end + minimized
+        }
+        _ => bar_module_text(name, snap).map_or(0, |t| t.chars().count() as u16),
+    }
+}
+
+/// Where each bar module goes — left, centre, right — in bar cells. Left
+/// modules are packed from one cell in and clipped at the bar's end; the
+/// centre ones are centred as a group. Right modules are packed from the
+/// right edge: the first whenever it fits, the rest only while the right
+/// group stays in the right half of the bar.
+fn bar_slots<'a>(snap: &'a TwmSnapshot) -> [Vec<(&'a str, Rect)>; 3] {
+    let area = snap.bar_rect;
+    let end = area.x + area.width;
+    let [left, centre, right] = &snap.bar_modules;
+    let sized = |names: &'a [String]| -> Vec<(&'a str, u16)> {
+        names
+            .iter()
+            .map(|n| (n.as_str(), bar_module_width(n, snap)))
+            .filter(|&(_, w)| w > 0)
+            .collect()
+    };
+    // Only workspace tabs use more than the bar's first line.
+    let lines = |name: &str| if name == "workspaces" { area.height } else { 1 };
+
+    let mut left_slots = Vec::new();
+    let mut x = area.x + 1;
+    for (name, w) in sized(left) {
+        if x >= end {
+            break;
+        }
+        left_slots.push((name, Rect::new(x, area.y, w.min(end - x), lines(name))));
+        x += w;
+    }
+
+    let mut centre_slots = Vec::new();
+    let centre = sized(centre);
+    let total: u16 = centre.iter().map(|&(_, w)| w).sum();
+    if total > 0 && total < area.width {
+        let mut x = area.x + (area.width / 2).saturating_sub(total / 2);
+        for (name, w) in centre {
+            centre_slots.push((name, Rect::new(x, area.y, w.min(end - x), lines(name))));
+            x += w;
+        }
+    }
+
+    let mut right_slots = Vec::new();
+    let mut x = end;
+    for (name, w) in sized(right).into_iter().rev() {
+        let used = end - x + w;
+        let fits = if right_slots.is_empty() {
+            used <= area.width
+        } else {
+            used <= area.width / 2
+        };
+        if !fits {
+            break;
+        }
+        x -= w;
+        right_slots.push((name, Rect::new(x, area.y, w, lines(name))));
+    }
+
+    [left_slots, centre_slots, right_slots]
+}
+
+/// Draw bar module `name` into `area`, the slot `bar_slots` gave it, and
+/// push the cells of each clickable tab onto `clicks`. Ids 1..=9 are
+/// workspace tabs.
+fn render_bar_module(
+    name: &str,
+    snap: &TwmSnapshot,
+    buf: &mut Buffer,
+    area: Rect,
+    clicks: &mut Vec<(Rect, u64)>,
+) {
+    let bar_bg = ratatui_rgb(snap.bar_bg);
+    let bar_fg = ratatui_rgb(snap.bar_fg);
+    let accent = ratatui_rgb(snap.active_border);
+    let dim = ratatui_rgb(snap.inactive_title);
+    let end = area.x + area.width;
+
+    if name == "workspaces" {
+        let (labels, tabs) = bar_ws_tabs(snap);
+        let tab_h = if tabs.stacked { 1 } else { area.height };
+        for ((label, (i, _, occupied, active)), &(dx, line, sep)) in
+            labels.iter().zip(&snap.ws_tabs).zip(&tabs.cells)
+        {
+            let (mut x, y) = (area.x + dx, area.y + line);
+            let w = label.chars().count() as u16;
+            if x + w <= end {
+                clicks.push((Rect::new(x, y, w, tab_h), *i as u64 + 1));
             }
-            let label = format!(" {} ", i + 1);
-            let style = if active {
+            let style = if *active {
                 Style::default()
                     .fg(Color::Black)
                     .bg(accent)
                     .add_modifier(Modifier::BOLD)
-            } else if occupied {
+            } else if *occupied {
                 Style::default().fg(accent).bg(bar_bg)
             } else {
                 Style::default().fg(dim).bg(bar_bg)
             };
             for ch in label.chars() {
-                if x >= area.x + area.width {
+                if x >= end {
                     break;
                 }
+                buf.get_mut(x, y).set_char(ch).set_style(style);
+                x += 1;
+            }
+            if x < end {
+                buf.get_mut(x, y)
+                    .set_char(sep)
+                    .set_style(Style::default().fg(dim).bg(bar_bg));
+            }
+        }
+
+        // Then: minimized panes, click to restore
+        let mut x = (area.x + tabs.end).min(end);
+        for (id, label) in &snap.minimized_tabs {
+            let w = label.chars().count() as u16;
+            if x + w > end {
+                break;
+            }
+            clicks.push((
+                Rect::new(x, area.y, w, area.height),
+                MINIMIZED_CLICK_BASE + *id as u64,
+            ));
+            let style = Style::default()
+                .fg(dim)
+                .bg(bar_bg)
+                .add_modifier(Modifier::ITALIC);
+            for ch in label.chars() {
                 buf.get_mut(x, area.y).set_char(ch).set_style(style);
                 x += 1;
             }
-            if x < area.x + area.width {
+            if x < end {
                 buf.get_mut(x, area.y)
                     .set_char('│')
                     .set_style(Style::default().fg(dim).bg(bar_bg));
                 x += 1;
             }
         }
+        return;
+    }
 
-        // Centre: layout label
-        let layout_label = format!(" [{}] ", s.layout_label);
-        let ll = layout_label.len() as u16;
-        if ll < area.width {
-            let cx = area.x + (area.width / 2).saturating_sub(ll / 2);
-            for (i, ch) in layout_label.chars().enumerate() {
-                let bx = cx + i as u16;
-                if bx >= area.x + area.width {
-                    break;
-                }
-                buf.get_mut(bx, area.y)
-                    .set_char(ch)
-                    .set_style(Style::default().fg(accent).bg(bar_bg));
-            }
-        }
-
-        // Right: clock
-        let right = format!(" {} ", s.bar_clock);
-        let rl = right.len() as u16;
-        if rl <= area.width {
-            let rx = area.x + area.width.saturating_sub(rl);
-            for (i, ch) in right.chars().enumerate() {
-                let bx = rx + i as u16;
-                if bx >= area.x + area.width {
-                    break;
-                }
-                buf.get_mut(bx, area.y)
-                    .set_char(ch)
-                    .set_style(Style::default().fg(bar_fg).bg(bar_bg));
-            }
-        }
+    let Some(text) = bar_module_text(name, snap) else {
+        return;
+    };
+    let style = match name {
+        "layout" | "media" => Style::default().fg(accent).bg(bar_bg),
+        _ => Style::default().fg(bar_fg).bg(bar_bg),
+    };
+    for (x, ch) in (area.x..end).zip(text.chars()) {
+        buf.get_mut(x, area.y).set_char(ch).set_style(style);
     }
 }
 
+/// `strip` split into `n` tabs of equal width, the last taking what is left.
+fn tab_cells(strip: Rect, n: usize) -> Vec<Rect> {
+    let Some(w) = strip.width.checked_div(n as u16) else {
+        return vec![];
+    };
+    (0..n as u16)
+        .map(|i| {
+            let x = strip.x + i * w;
+            let width = if i + 1 == n as u16 {
+                strip.x + strip.width - x
+            } else {
+                w
+            };
+            Rect { x, width, ..strip }
+        })
+        .collect()
+}
+
 // ─────────────────────────────────────────────────────────────────────────────
 // SECTION 5 — Public entry point
 // ─────────────────────────────────────────────────────────────────────────────
@@ -1193,9 +3191,9 @@ impl<'a> TwmRenderer<'a> {
 /// Example in render_surface():
 ///
 ///   if let Some(twm) = &mut self.twm {
-///       let (cw, ch) = overlay_element::cell_size();
+///       let (cw, ch) = overlay_element::cell_size(&output.name());
 ///       let cmds = twm.build_frame_cmds(cw, ch, output_w, output_h);
-///       // TwmChromeElement::new(cmds, output_w, output_h) as before
+///       // TwmChromeElement::new(cmds, &output.name(), output_w, output_h) as before
 ///   }
 ///
 pub fn build_frame_cmds(
@@ -1212,23 +3210,30 @@ pub fn build_frame_cmds(
 // SECTION 6 — Layout algorithms
 // ─────────────────────────────────────────────────────────────────────────────
 
-fn bsp_split(area: Rect, n: usize, gap: u16) -> Vec<Rect> {
-    bsp_inner(area, n, gap, area.width >= area.height)
+fn bsp_split(area: Rect, hints: &[Option<SplitDir>], gap: u16) -> Vec<Rect> {
+    bsp_inner(area, hints, gap, area.width >= area.height)
 }
-fn bsp_inner(area: Rect, n: usize, gap: u16, vert: bool) -> Vec<Rect> {
+fn bsp_inner(area: Rect, hints: &[Option<SplitDir>], gap: u16, vert: bool) -> Vec<Rect> {
+    let n = hints.len();
     if n == 0 {
         return vec![];
     }
     if n == 1 {
         return vec![area];
     }
+    // A manual split on the next pane overrides the alternating default.
+    let vert = match hints[1] {
+        Some(SplitDir::Horizontal) => true,
+        Some(SplitDir::Vertical) => false,
+        None => vert,
+    };
     if vert {
         let lw = area.width.saturating_sub(gap) / 2;
         let rw = area.width.saturating_sub(lw + gap);
         let left = Rect::new(area.x, area.y, lw, area.height);
         let right = Rect::new(area.x + lw + gap, area.y, rw, area.height);
         let mut out = vec![left];
-        out.extend(bsp_inner(right, n - 1, gap, false));
+        out.extend(bsp_inner(right, &hints[1..], gap, false));
         out
     } else {
         let th = area.height.saturating_sub(gap) / 2;
@@ -1236,11 +3241,45 @@ fn bsp_inner(area: Rect, n: usize, gap: u16, vert: bool) -> Vec<Rect> {
         let top = Rect::new(area.x, area.y, area.width, th);
         let bot = Rect::new(area.x, area.y + th + gap, area.width, bh);
         let mut out = vec![top];
-        out.extend(bsp_inner(bot, n - 1, gap, true));
+        out.extend(bsp_inner(bot, &hints[1..], gap, true));
         out
     }
 }
 
+/// `ceil(sqrt(n))` columns, filled row by row — the expose grid.
+fn grid_split(area: Rect, n: usize, cols: u16, gap: u16) -> Vec<Rect> {
+    if n == 0 {
+        return vec![];
+    }
+    let n = n as u16;
+    let cols = cols.clamp(1, n);
+    let rows = n.div_ceil(cols);
+    let ch = area.height.saturating_sub(gap * (rows - 1)) / rows;
+    let mut out = Vec::with_capacity(n as usize);
+    for row in 0..rows {
+        // The last row may be short; its cells widen to span the full width.
+        let in_row = cols.min(n - row * cols);
+        let cw = area.width.saturating_sub(gap * (in_row - 1)) / in_row;
+        let y = area.y + row * (ch + gap);
+        let h = if row + 1 == rows {
+            area.bottom().saturating_sub(y)
+        } else {
+            ch
+        };
+        for col in 0..in_row {
+            let x = area.x + col * (cw + gap);
+            // Rounding leftovers go to the last cell of the row.
+            let w = if col + 1 == in_row {
+                area.right().saturating_sub(x)
+            } else {
+                cw
+            };
+            out.push(Rect::new(x, y, w, h));
+        }
+    }
+    out
+}
+
 fn col_split(area: Rect, n: usize, ratio: f32, gap: u16) -> Vec<Rect> {
     if n == 1 {
         return vec![area];
@@ -1287,6 +3326,50 @@ fn row_split(area: Rect, n: usize, ratio: f32, gap: u16) -> Vec<Rect> {
     out
 }
 
+/// Fibonacci spiral: each pane takes half of what is left, going left, top,
+/// right, bottom and round again, so the remainder winds inwards.
+fn spiral_split(area: Rect, n: usize, gap: u16) -> Vec<Rect> {
+    let mut out = Vec::with_capacity(n);
+    let mut rest = area;
+    for i in 0..n {
+        if i + 1 == n {
+            out.push(rest);
+            break;
+        }
+        let Rect {
+            x,
+            y,
+            width: w,
+            height: h,
+        } = rest;
+        let half_w = w.saturating_sub(gap) / 2;
+        let half_h = h.saturating_sub(gap) / 2;
+        let rest_w = w.saturating_sub(half_w + gap);
+        let rest_h = h.saturating_sub(half_h + gap);
+        let (pane, next) = match i % 4 {
+            0 => (
+                Rect::new(x, y, half_w, h),
+                Rect::new(x + half_w + gap, y, rest_w, h),
+            ),
+            1 => (
+                Rect::new(x, y, w, half_h),
+                Rect::new(x, y + half_h + gap, w, rest_h),
+            ),
+            2 => (
+                Rect::new(x + rest_w + gap, y, half_w, h),
+                Rect::new(x, y, rest_w, h),
+            ),
+            _ => (
+                Rect::new(x, y + rest_h + gap, w, half_h),
+                Rect::new(x, y, w, rest_h),
+            ),
+        };
+        out.push(pane);
+        rest = next;
+    }
+    out
+}
+
 // ─────────────────────────────────────────────────────────────────────────────
 // SECTION 7 — Tiny helpers
 // ─────────────────────────────────────────────────────────────────────────────
@@ -1308,6 +3391,18 @@ fn ratatui_rgb(c: [u8; 3]) -> Color {
     Color::Rgb(c[0], c[1], c[2])
 }
 
+/// Floor `v` to the `snap` grid, then move it onto the nearest edge of any
+/// `(origin, len)` span less than `snap` away — flush inside or outside.
+fn snap_axis(v: i32, len: i32, snap: i32, spans: impl Iterator<Item = (i32, i32)>) -> i32 {
+    let v = v.div_euclid(snap) * snap;
+    spans
+        .flat_map(|(o, olen)| [o, o + olen - len, o + olen, o - len])
+        .map(|c| (c, (c - v).abs()))
+        .filter(|&(_, d)| d < snap)
+        .min_by_key(|&(_, d)| d)
+        .map_or(v, |(c, _)| c)
+}
+
 fn ws_swap(ws: &mut Workspace, forward: bool) {
     let n = ws.panes.len();
     if let Some(cur) = ws.focus_idx() {
@@ -1319,3 +3414,1105 @@ fn ws_swap(ws: &mut Workspace, forward: bool) {
         ws.panes.swap(cur, tgt);
     }
 }
+
+/// Step from cell `pos` of a grid of `n` cells laid out `cols` to a row, `dx`
+/// along the row or `dy` along the column, wrapping at either end. The last
+/// row may be short, so rows and columns wrap at their own length.
+fn grid_step(pos: usize, n: usize, cols: usize, dx: isize, dy: isize) -> usize {
+    if n == 0 {
+        return pos;
+    }
+    let cols = cols.clamp(1, n);
+    let (row, col) = (pos / cols, pos % cols);
+    if dx != 0 {
+        let row_len = cols.min(n - row * cols) as isize;
+        row * cols + (col as isize + dx).rem_euclid(row_len) as usize
+    } else {
+        let col_len = (n - col).div_ceil(cols) as isize;
+        (row as isize + dy).rem_euclid(col_len) as usize * cols + col
+    }
+}
+
+/// Bar placement of the workspace tabs, `cols` to a row like the workspace
+/// grid. Rows are stacked when the bar has a line for each of them, else
+/// they sit side by side.
+struct TabLayout {
+    /// Per tab: x and line offset from the first tab, and the separator
+    /// drawn after it — `┃` between side-by-side rows, `│` otherwise.
+    cells: Vec<(u16, u16, char)>,
+    /// x offset just past the widest row.
+    end: u16,
+    stacked: bool,
+}
+
+fn ws_tab_layout(widths: &[u16], cols: usize, lines: u16) -> TabLayout {
+    let cols = cols.max(1);
+    let stacked = lines > 1 && widths.len().div_ceil(cols) <= lines as usize;
+    let (mut x, mut line, mut end) = (0u16, 0u16, 0u16);
+    let mut cells = Vec::with_capacity(widths.len());
+    for (i, &w) in widths.iter().enumerate() {
+        if stacked && i > 0 && i % cols == 0 {
+            x = 0;
+            line += 1;
+        }
+        let row_end = (i + 1) % cols == 0 && i + 1 < widths.len();
+        cells.push((x, line, if row_end && !stacked { '┃' } else { '│' }));
+        x += w + 1;
+        end = end.max(x);
+    }
+    TabLayout {
+        cells,
+        end,
+        stacked,
+    }
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Tests
+// ─────────────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embedded_window::EmbeddedPlacement;
+
+    fn still_state() -> TwmState {
+        let mut s = TwmState::new(80, 24);
+        s.anim_enabled = false;
+        s.reflow();
+        s
+    }
+
+    #[test]
+    fn lock_ignores_actions_until_unlocked() {
+        let mut s = still_state();
+        s.open_shell_pane("a");
+        s.open_shell_pane("b");
+        // Directional focus needs the panes laid out by a frame.
+        s.build_frame_cmds(8, 16, 640, 384);
+        s.dispatch(&Action::FocusLeft);
+        let before = s.focused_id();
+
+        s.lock_pin = Some(bcrypt::hash("1234", 4).unwrap());
+        s.dispatch(&Action::Lock);
+        s.dispatch(&Action::FocusRight);
+        assert_eq!(s.focused_id(), before);
+        let cmds = s.build_frame_cmds(8, 16, 640, 384);
+        assert!(matches!(
+            cmds.as_slice(),
+            [DrawCmd::FillRect { color, .. }] if *color == PixColor::BLACK
+        ));
+
+        s.dispatch(&Action::Unlock("0000".into()));
+        assert!(s.locked);
+        s.dispatch(&Action::Unlock(String::new()));
+        assert!(s.locked);
+        s.dispatch(&Action::Unlock("1234".into()));
+        assert!(!s.locked);
+        s.dispatch(&Action::FocusRight);
+        assert_ne!(s.focused_id(), before);
+    }
+
+    #[test]
+    fn lock_refused_without_a_pin() {
+        let mut s = still_state();
+        s.dispatch(&Action::Lock);
+        assert!(!s.locked);
+    }
+
+    #[test]
+    fn registered_layer_draws_every_frame() {
+        struct Marker;
+        impl ChromeLayer for Marker {
+            fn render(&mut self, ctx: &mut DrawContext, area: PixRect, _: u32, _: u32) {
+                ctx.fill_rect(PixRect::new(area.w - 4, 0, 4, 4), PixColor::RED);
+            }
+            fn name(&self) -> &str {
+                "marker"
+            }
+        }
+        let is_marker = |c: &DrawCmd| {
+            matches!(c, DrawCmd::FillRect { rect, color }
+                if *rect == PixRect::new(636, 0, 4, 4) && *color == PixColor::RED)
+        };
+
+        let mut s = still_state();
+        s.register_layer(Box::new(Marker));
+        for _ in 0..2 {
+            let cmds = s.build_frame_cmds(8, 16, 640, 384);
+            assert_eq!(cmds.iter().filter(|c| is_marker(c)).count(), 1);
+        }
+    }
+
+    #[test]
+    fn import_existing_windows_in_order() {
+        let mut s = still_state();
+        let foot = PaneContent::Shell {
+            title: "foot".into(),
+        };
+        // Two terminals look alike; only their surface keys differ.
+        let windows = [
+            (11, "foot", foot.clone()),
+            (
+                12,
+                "firefox",
+                PaneContent::Embedded {
+                    app_id: "firefox".into(),
+                },
+            ),
+            (13, "foot", foot),
+        ];
+        s.import_existing_windows(&windows);
+        s.import_existing_windows(&windows);
+
+        // After the seeded pane.
+        let ws = &s.workspaces[0];
+        assert_eq!(ws.panes.len(), 4);
+        for (id, (_, _, content)) in ws.panes[1..].iter().zip(&windows) {
+            assert_eq!(&s.panes[id].content, content);
+        }
+        assert_eq!(ws.focused, ws.panes.last().copied());
+    }
+
+    #[test]
+    fn reflow_sends_settled_embedded_geometry() {
+        let mut s = still_state();
+        let id = s.assign_embedded("firefox");
+        s.open_shell_pane("shell");
+        s.reflow();
+
+        let dst = Rect::from(s.panes[&id].anim.dst).inner(&Margin {
+            horizontal: 1,
+            vertical: 1,
+        });
+        assert_eq!(
+            s.embedded_windows["firefox"].last_sent_rect,
+            Some(EmbeddedPlacement {
+                x: dst.x as i32 * 8,
+                y: dst.y as i32 * 16,
+                w: dst.width as i32 * 8,
+                h: dst.height as i32 * 16,
+            })
+        );
+        assert_eq!(s.take_embedded_moves().len(), 1);
+
+        // Unchanged layout must not re-send.
+        s.reflow();
+        assert!(s.take_embedded_moves().is_empty());
+    }
+
+    #[test]
+    fn reflow_skips_in_flight_embedded_geometry() {
+        let mut s = TwmState::new(80, 24);
+        s.anim_duration_ms = 60_000.0;
+        s.assign_embedded("firefox");
+        s.open_shell_pane("shell");
+        s.reflow();
+        assert!(!s.embedded_windows.contains_key("firefox"));
+        assert!(s.take_embedded_moves().is_empty());
+    }
+
+    #[test]
+    fn bar_workspace_tab_click() {
+        let mut s = still_state();
+        s.build_frame_cmds(8, 16, 640, 384);
+        // Bar is the bottom row; tab 2 (" 2 ") starts at column 5.
+        let y = 23 * 16 + 4;
+        assert_eq!(s.click_at(5 * 8 + 2, y), Some(2));
+        assert_eq!(s.click_at(4 * 8 + 2, y), None); // separator
+        assert_eq!(s.click_at(5 * 8 + 2, 0), None);
+
+        s.dispatch(&Action::UiClick(2));
+        assert_eq!(s.active_ws, 1);
+    }
+
+    #[test]
+    fn no_decor_pane_has_no_border() {
+        let mut s = still_state();
+        let id = s.assign_embedded("mpv");
+        s.build_frame_cmds(8, 16, 640, 384);
+        assert_eq!(s.buf.get(0, 0).symbol(), "┌");
+
+        assert!(s.set_no_decor("mpv"));
+        assert!(!s.set_no_decor("missing"));
+        s.build_frame_cmds(8, 16, 640, 384);
+        let r = Rect::from(s.panes[&id].anim.dst);
+        for x in r.x..r.x + r.width {
+            assert_eq!(s.buf.get(x, r.y).symbol(), " ");
+        }
+        for y in r.y..r.y + r.height {
+            assert_eq!(s.buf.get(r.x, y).symbol(), " ");
+        }
+
+        s.reflow();
+        let sent = s.embedded_windows["mpv"].last_sent_rect.unwrap();
+        assert_eq!((sent.x, sent.y), (0, 0));
+        assert_eq!(sent.w, r.width as i32 * 8);
+    }
+
+    #[test]
+    fn minimize_excludes_pane_from_layout() {
+        let mut s = still_state();
+        s.open_shell_pane("second");
+        s.open_shell_pane("third");
+        s.reflow();
+        assert_eq!(s.layout_rects().0.len(), 3);
+
+        let id = s.focused_id().unwrap();
+        s.dispatch(&Action::Minimize);
+        s.build_frame_cmds(8, 16, 640, 384);
+        assert_eq!(s.workspaces[0].panes.len(), 3);
+        assert_eq!(s.layout_rects().0.len(), 2);
+        assert_ne!(s.focused_id(), Some(id));
+        assert!(!s.layout_rects().0.contains(&id));
+
+        // The minimized tab follows the nine workspace tabs in the bar.
+        let (r, click_id) = *s.click_regions.last().unwrap();
+        assert_eq!(click_id, MINIMIZED_CLICK_BASE + id as u64);
+        s.dispatch(&Action::UiClick(s.click_at(r.x + 1, r.y + 1).unwrap()));
+        assert!(s.minimized.is_empty());
+        assert_eq!(s.focused_id(), Some(id));
+        assert_eq!(s.layout_rects().0.len(), 3);
+    }
+
+    #[test]
+    fn expose_grid_of_nine() {
+        let mut s = still_state();
+        for i in 0..8 {
+            if i % 3 == 0 {
+                s.dispatch(&Action::Workspace(i / 3 + 2));
+            }
+            s.open_shell_pane(&format!("p{i}"));
+        }
+        s.dispatch(&Action::Expose);
+        s.build_frame_cmds(8, 16, 640, 384);
+
+        let (ids, rects) = s.layout_rects();
+        assert_eq!(ids.len(), 9);
+        let xs: HashSet<u16> = rects.iter().map(|r| r.x).collect();
+        let ys: HashSet<u16> = rects.iter().map(|r| r.y).collect();
+        assert_eq!((xs.len(), ys.len()), (3, 3));
+        for (i, a) in rects.iter().enumerate() {
+            assert!(a.width > 0 && a.height > 0);
+            for b in &rects[i + 1..] {
+                assert!(a.intersection(*b).area() == 0, "{a:?} overlaps {b:?}");
+            }
+        }
+        for (id, r) in ids.iter().zip(&rects) {
+            assert_eq!(Rect::from(s.panes[id].anim.dst), *r);
+        }
+
+        // Picking the root pane returns to workspace 1 with it focused.
+        let root = s.workspaces[0].panes[0];
+        s.dispatch(&Action::UiClick(EXPOSE_CLICK_BASE + root as u64));
+        assert!(!s.expose_active);
+        assert_eq!(s.active_ws, 0);
+        assert_eq!(s.focused_id(), Some(root));
+    }
+
+    #[test]
+    fn bar_dashed_separator() {
+        let mut s = TwmState::new(160, 24);
+        s.anim_enabled = false;
+        let cmds = s.build_frame_cmds(8, 16, 1280, 384);
+        let Some(DrawCmd::DashedLine { x1, x2, y1, .. }) = cmds.last() else {
+            panic!("no separator");
+        };
+        assert_eq!(x1, x2);
+        assert_eq!(*y1, 23 * 16 + 2);
+        // Between the last tab's '│' (cell 32) and the layout label (cell 77).
+        assert!(*x1 > 33 * 8 && *x1 < 77 * 8);
+
+        // A 72-column bar has no room for it.
+        let mut s = TwmState::new(72, 24);
+        s.anim_enabled = false;
+        let cmds = s.build_frame_cmds(8, 16, 576, 384);
+        assert!(!matches!(cmds.last(), Some(DrawCmd::DashedLine { .. })));
+    }
+
+    #[test]
+    fn named_workspace_tabs() {
+        let mut s = still_state();
+        s.set_workspace_rules(&[WorkspaceRule {
+            index: 2,
+            name: Some("web stuff".into()),
+            ..Default::default()
+        }]);
+        s.build_frame_cmds(8, 16, 640, 384);
+        let row: String = (0..80).map(|x| s.buf.get(x, 23).symbol()).collect();
+        assert!(row.starts_with("  1 │ web stuff │ 3 "));
+        // Click regions follow the wider tab.
+        let y = 23 * 16 + 4;
+        assert_eq!(s.click_at(15 * 8 + 2, y), Some(2));
+        assert_eq!(s.click_at(17 * 8 + 2, y), Some(3));
+
+        s.dispatch(&Action::WorkspaceByName("web stuff".into()));
+        assert_eq!(s.active_ws, 1);
+        s.dispatch(&Action::WorkspaceByName("missing".into()));
+        assert_eq!(s.active_ws, 1);
+        s.dispatch(&Action::WorkspaceByName("1".into()));
+        assert_eq!(s.active_ws, 0);
+
+        // Reloaded without the rule: the number comes back.
+        s.set_workspace_rules(&[]);
+        assert_eq!(s.workspaces[1].name, "2");
+    }
+
+    #[test]
+    fn bar_shows_media_left_of_clock() {
+        let mut s = TwmState::new(160, 24);
+        s.anim_enabled = false;
+        s.bar_media = Some("▶ Test Song".into());
+        s.build_frame_cmds(8, 16, 1280, 384);
+        let row: String = (0..160).map(|x| s.buf.get(x, 23).symbol()).collect();
+        let at = row.find("▶ Test Song").expect("media label in bar");
+        assert!(at > row.find('[').unwrap());
+    }
+
+    #[test]
+    fn battery_gauge_covers_its_bar_slot() {
+        let mut s = still_state();
+        s.bar_modules = [vec![], vec![], vec!["battery".into()]];
+        s.battery = BatteryStatus::parse("40", "Discharging");
+        let cmds = s.build_frame_cmds(8, 16, 640, 384);
+        let label = s.battery.unwrap().label();
+        let row: String = (0..80).map(|x| s.buf.get(x, 23).symbol()).collect();
+        assert!(row.ends_with(&format!(" {label} ")), "{row:?}");
+
+        // Track over the slot minus its padding, then 40% of it filled.
+        let w = label.chars().count() as u32 * 8;
+        let x = (80 - label.chars().count() as u32 - 1) * 8;
+        let fills: Vec<PixRect> = cmds
+            .iter()
+            .filter_map(|c| match c {
+                DrawCmd::FillRect { rect, .. } if rect.y == 23 * 16 && rect.x >= x => Some(*rect),
+                _ => None,
+            })
+            .collect();
+        assert!(fills.ends_with(&[
+            PixRect::new(x, 23 * 16, w, 16),
+            PixRect::new(x, 23 * 16, (w as f32 * 0.4).round() as u32, 16),
+        ]));
+        assert!(matches!(cmds.last(), Some(DrawCmd::Text { text, .. }) if *text == label));
+    }
+
+    #[test]
+    fn bar_modules_follow_config() {
+        let mut s = TwmState::new(80, 24);
+        s.anim_enabled = false;
+        s.bar_modules = [vec!["layout".into()], vec![], vec!["workspaces".into()]];
+        let cmds = s.build_frame_cmds(8, 16, 640, 384);
+        let row: String = (0..80).map(|x| s.buf.get(x, 23).symbol()).collect();
+        let layout = format!(" [{}] ", s.workspaces[0].layout.label());
+        assert!(row[1..].starts_with(&layout), "{row:?}");
+        assert!(row.ends_with(" 9 │"), "{row:?}");
+        // Tabs moved to the right: so did their click regions, and with
+        // nothing in the centre there is no separator.
+        let y = 23 * 16 + 4;
+        assert_eq!(s.click_at(2 * 8 + 2, y), None);
+        assert_eq!(s.click_at(77 * 8 + 2, y), Some(9));
+        assert!(!matches!(cmds.last(), Some(DrawCmd::DashedLine { .. })));
+    }
+
+    #[test]
+    fn split_pane_in_bsp() {
+        let mut s = still_state();
+        s.open_shell_pane("second");
+        s.reflow();
+        let first = s.workspaces[0].panes[0];
+
+        let new_id = s.split_pane(first, SplitDir::Vertical).unwrap();
+        s.reflow();
+
+        let ws = &s.workspaces[0];
+        assert_eq!(ws.panes.len(), 3);
+        assert_eq!(ws.panes[1], new_id);
+        assert_eq!(ws.focused, Some(new_id));
+        assert!(matches!(s.panes[&new_id].content, PaneContent::Empty));
+        for id in &ws.panes {
+            assert!(Rect::from(s.panes[id].anim.dst).area() > 0);
+        }
+        // Vertical split stacks the new pane under the original.
+        let a = Rect::from(s.panes[&first].anim.dst);
+        let b = Rect::from(s.panes[&new_id].anim.dst);
+        assert_eq!(a.x, b.x);
+        assert!(b.y > a.y);
+    }
+
+    #[test]
+    fn workspace_slide_directions() {
+        let mut s = still_state();
+        let two = s.workspaces[0].panes[0];
+        s.dispatch(&Action::MoveToWorkspace(2));
+        s.open_shell_pane("one");
+        let one = s.workspaces[0].panes[0];
+        s.reflow();
+        s.anim_enabled = true;
+
+        // Next: old panes exit left, new panes enter from the right.
+        s.dispatch(&Action::NextWorkspace);
+        assert_eq!(s.active_ws, 1);
+        let exit = s.panes[&one].anim;
+        assert!(exit.dst.x < exit.src.x);
+        let entry = s.panes[&two].anim;
+        assert!(entry.src.x > entry.dst.x);
+
+        // Prev: the reverse, once the first slide has run.
+        s.transition = None;
+        s.dispatch(&Action::PrevWorkspace);
+        assert_eq!(s.active_ws, 0);
+        let exit = s.panes[&two].anim;
+        assert!(exit.dst.x > exit.src.x);
+        let entry = s.panes[&one].anim;
+        assert!(entry.src.x < entry.dst.x);
+    }
+
+    #[test]
+    fn second_workspace_switch_cuts_the_first_short() {
+        let mut s = still_state();
+        let one = s.workspaces[0].panes[0];
+        s.dispatch(&Action::Workspace(2));
+        s.open_shell_pane("two");
+        let two = s.workspaces[1].panes[0];
+        s.dispatch(&Action::Workspace(3));
+        s.open_shell_pane("three");
+        let three = s.workspaces[2].panes[0];
+        s.dispatch(&Action::Workspace(1));
+        frame(&mut s);
+        s.anim_enabled = true;
+        s.workspace_anim_ms = 60_000.0;
+
+        s.dispatch(&Action::Workspace(2));
+        assert!(s.transition.is_some());
+        s.dispatch(&Action::Workspace(3));
+        assert!(s.transition.is_none());
+        frame(&mut s);
+
+        // Only workspace 3 is drawn, already at its final rect.
+        let (_, rects) = s.layout_rects();
+        let snap = TwmSnapshot::from_state(&s);
+        let drawn: Vec<_> = snap.panes.iter().map(|p| (p.id, p.rect)).collect();
+        assert_eq!(drawn, [(three, rects[0])]);
+        assert!(s.panes[&three].anim.is_done());
+        assert!(!drawn.iter().any(|&(id, _)| id == one || id == two));
+    }
+
+    #[test]
+    fn import_matches_blit() {
+        let area = Rect::new(0, 0, 12, 4);
+        let mut buf = Buffer::empty(area);
+        buf.set_string(0, 0, "hello │ █", Style::default().fg(Color::Red));
+        buf.set_string(
+            2,
+            2,
+            "bold",
+            Style::default()
+                .bg(Color::Rgb(1, 2, 3))
+                .add_modifier(Modifier::BOLD | Modifier::REVERSED),
+        );
+        buf.set_string(8, 3, "dim", Style::default().add_modifier(Modifier::DIM));
+
+        // Reference: the old per-cell `Buffer::get` path.
+        let blit = |cols: u16, rows: u16| {
+            let mut cb = CellBuffer::new(cols, rows);
+            for y in 0..area.height.min(rows) {
+                for x in 0..area.width.min(cols) {
+                    cb.cells[y as usize * cols as usize + x as usize] = cell_convert(buf.get(x, y));
+                }
+            }
+            cb.cells
+        };
+
+        let mut b = CellBuffer::new(12, 4);
+        b.import(&buf);
+        assert_eq!(b.cells, blit(12, 4));
+
+        // Destination larger than the source: extra cells stay untouched.
+        let mut b = CellBuffer::new(16, 5);
+        b.import(&buf);
+        assert_eq!(b.cells, blit(16, 5));
+    }
+
+    #[test]
+    fn float_snap_grid_and_edges() {
+        let mut s = still_state();
+        let output = (0, 0, 1920, 1080);
+        assert_eq!(
+            s.float_snap_to_grid((13, 13), (300, 200), output, &[]),
+            (13, 13)
+        );
+
+        s.float_snap_px = 8;
+        assert_eq!(
+            s.float_snap_to_grid((13, 13), (300, 200), output, &[]),
+            (8, 8)
+        );
+        // Grid x 1616 leaves the right edge 4px short of the output: flush it.
+        assert_eq!(
+            s.float_snap_to_grid((1619, 500), (300, 200), output, &[]),
+            (1620, 496)
+        );
+        // Left edge lands just past another window's right edge at 410.
+        let other = (110, 40, 300, 200);
+        assert_eq!(
+            s.float_snap_to_grid((415, 43), (300, 200), output, &[other]),
+            (410, 40)
+        );
+    }
+
+    #[test]
+    fn undo_redo_main_ratio() {
+        let mut s = still_state();
+        s.dispatch(&Action::GrowMain);
+        let after_one = s.workspaces[0].main_ratio;
+        s.dispatch(&Action::GrowMain);
+        s.dispatch(&Action::GrowMain);
+        let after_three = s.workspaces[0].main_ratio;
+
+        s.dispatch(&Action::UndoResize);
+        s.dispatch(&Action::UndoResize);
+        assert_eq!(s.workspaces[0].main_ratio, after_one);
+        s.dispatch(&Action::RedoResize);
+        s.dispatch(&Action::RedoResize);
+        assert_eq!(s.workspaces[0].main_ratio, after_three);
+
+        // A fresh resize forgets what could have been redone.
+        s.dispatch(&Action::UndoResize);
+        s.dispatch(&Action::ShrinkMain);
+        s.dispatch(&Action::RedoResize);
+        assert_eq!(s.resize_history.len(), 3);
+        assert!(s.workspaces[0].main_ratio < after_three);
+    }
+
+    #[test]
+    fn focus_output_switches_workspace() {
+        let mut s = still_state();
+        s.assign_workspace_to_output(0, "DP-1");
+        s.assign_workspace_to_output(5, "HDMI-A-1");
+        s.dispatch(&Action::FocusOutput("HDMI-A-1".into()));
+        assert_eq!(s.active_ws, 5);
+        s.dispatch(&Action::FocusOutput("eDP-1".into()));
+        assert_eq!(s.active_ws, 5);
+
+        assert_eq!(s.assign_output("eDP-1"), Some(1));
+        assert_eq!(s.assign_output("DP-1"), Some(0));
+    }
+
+    #[test]
+    fn focus_lock_pins_focus_until_toggled() {
+        let mut s = still_state();
+        s.open_shell_pane("a");
+        s.open_shell_pane("b");
+        s.dispatch(&Action::FocusLeft);
+        let a = s.focused_id();
+
+        s.dispatch(&Action::ToggleFocusLock);
+        assert_eq!(s.focus_locked, a);
+        s.dispatch(&Action::FocusRight);
+        s.dispatch(&Action::Workspace(2));
+        assert_eq!(s.focused_id(), a);
+        assert_eq!(s.active_ws, 0);
+
+        s.dispatch(&Action::ToggleFocusLock);
+        assert_eq!(s.focus_locked, None);
+        s.dispatch(&Action::FocusRight);
+        assert_ne!(s.focused_id(), a);
+    }
+
+    #[test]
+    fn pane_gap_animates_between_values() {
+        let mut s = still_state();
+        s.anim_enabled = true;
+        s.anim_duration_ms = 400.0;
+        s.anim_ease = Easing::Linear;
+        s.open_shell_pane("a");
+        s.open_shell_pane("b");
+        s.reflow();
+        let from = s.workspaces[0].gap_inner as f32;
+
+        s.dispatch(&Action::SetPaneGap(9));
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        let anim = s.workspaces[0].gap_anim;
+        let cur = anim.current();
+        assert_eq!((anim.src, anim.dst), (from, 9.0));
+        assert!(anim.src < cur && cur < anim.dst, "{cur}");
+        assert!(s.gaps_animating() && s.animating());
+        assert_eq!(s.workspaces[0].gap_inner, 9);
+    }
+
+    #[test]
+    fn single_pane_drops_gaps() {
+        let mut s = still_state();
+        s.set_no_gap_when_single(true);
+        s.workspaces[0].gap_outer = 2;
+        s.reflow();
+        let a = s.workspaces[0].panes[0]; // the seeded pane
+        assert_eq!(s.panes[&a].anim.current(), s.content_rect());
+
+        s.open_shell_pane("b");
+        s.reflow();
+        let b = s.workspaces[0].panes[1];
+        let (ra, rb) = (s.panes[&a].anim.current(), s.panes[&b].anim.current());
+        assert_ne!(ra, s.content_rect());
+        assert!(rb.x > ra.x + ra.width, "{ra:?} {rb:?}");
+    }
+
+    #[test]
+    fn spiral_tiles_without_overlap() {
+        let area = Rect::new(2, 1, 120, 40);
+        let gap = 1;
+        for n in 1..=8 {
+            let rects = spiral_split(area, n, gap);
+            assert_eq!(rects.len(), n);
+            for (i, a) in rects.iter().enumerate() {
+                assert!(a.area() > 0, "n={n}: empty rect {i}");
+                assert_eq!(a.intersection(area), *a, "n={n}: {a:?} outside");
+                for b in &rects[i + 1..] {
+                    assert!(!a.intersects(*b), "n={n}: {a:?} overlaps {b:?}");
+                }
+            }
+            // Every cell no pane covers is gap: it borders a pane.
+            let covered = |x: u16, y: u16| {
+                rects
+                    .iter()
+                    .any(|r| x >= r.x && x < r.right() && y >= r.y && y < r.bottom())
+            };
+            for y in area.top()..area.bottom() {
+                for x in area.left()..area.right() {
+                    if covered(x, y) {
+                        continue;
+                    }
+                    let borders_pane = covered(x.saturating_sub(gap), y)
+                        || covered(x + gap, y)
+                        || covered(x, y.saturating_sub(gap))
+                        || covered(x, y + gap);
+                    assert!(borders_pane, "n={n}: hole at ({x}, {y})");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn grid_four_in_two_cols_equal() {
+        let rects = grid_split(Rect::new(0, 0, 81, 25), 4, 2, 1);
+        assert_eq!(
+            rects,
+            [
+                Rect::new(0, 0, 40, 12),
+                Rect::new(41, 0, 40, 12),
+                Rect::new(0, 13, 40, 12),
+                Rect::new(41, 13, 40, 12),
+            ]
+        );
+    }
+
+    #[test]
+    fn grid_last_row_expands() {
+        let area = Rect::new(0, 0, 92, 25);
+        let rects = grid_split(area, 5, 3, 1);
+        assert_eq!(rects.len(), 5);
+        assert!(rects[..3].iter().all(|r| r.width == 30 && r.y == 0));
+        // Two cells share the bottom row's full width.
+        assert_eq!((rects[3].x, rects[3].width), (0, 45));
+        assert_eq!((rects[4].x, rects[4].right()), (46, area.right()));
+        assert!(rects[3..].iter().all(|r| r.bottom() == area.bottom()));
+    }
+
+    #[test]
+    fn set_grid_cols_switches_layout() {
+        let mut s = still_state();
+        s.dispatch(&Action::SetGridCols(3));
+        assert_eq!(s.workspaces[0].layout, Layout::Grid { cols: 3 });
+        assert_eq!(s.workspaces[0].grid_cols, 3);
+        s.dispatch(&Action::NextLayout);
+        assert_eq!(s.workspaces[0].layout, Layout::Bsp);
+    }
+
+    #[test]
+    fn tabbed_panes_share_one_rect_under_the_strip() {
+        let mut s = still_state();
+        let ids: Vec<PaneId> = ["a", "b", "c"]
+            .into_iter()
+            .map(|app| {
+                let id = s.assign_embedded(app);
+                frame(&mut s);
+                id
+            })
+            .collect();
+        s.workspaces[0].layout = Layout::from_name("tabbed").unwrap();
+        s.dirty = true;
+        frame(&mut s);
+
+        let (laid_out, rects) = s.layout_rects();
+        assert_eq!(laid_out, ids);
+        assert!(rects.iter().all(|r| *r == rects[0]));
+        let (strip, tabs) = s.tab_strip().unwrap();
+        assert_eq!(tabs, ids);
+        assert_eq!((strip.height, strip.y + 1), (1, rects[0].y));
+        // Covered tabs keep advertising their geometry.
+        for app in ["a", "b", "c"] {
+            assert_eq!(s.embedded_cell_rect(app), Some(rects[0]));
+        }
+
+        // Only the showing tab is drawn; left and right step through the
+        // tabs and wrap.
+        let drawn = |s: &TwmState| -> Vec<PaneId> {
+            TwmSnapshot::from_state(s)
+                .panes
+                .iter()
+                .map(|p| p.id)
+                .collect()
+        };
+        assert_eq!(drawn(&s), [ids[2]]);
+        assert_eq!(s.visible_embedded_apps(), ["c"]);
+        s.dispatch(&Action::FocusRight);
+        assert_eq!(s.focused_id(), Some(ids[0]));
+        assert_eq!(drawn(&s), [ids[0]]);
+        s.dispatch(&Action::FocusLeft);
+        assert_eq!(s.focused_id(), Some(ids[2]));
+
+        // Clicking a tab shows its pane.
+        frame(&mut s);
+        let x = (strip.x + strip.width / 3) as u32 * 8 + 2;
+        let click = s.click_at(x, strip.y as u32 * 16 + 2).unwrap();
+        s.dispatch(&Action::UiClick(click));
+        assert_eq!(s.focused_id(), Some(ids[1]));
+    }
+
+    #[test]
+    fn scratchpad_round_trip() {
+        let mut s = still_state();
+        s.open_shell_pane("a");
+        let a = s.focused_id().unwrap();
+        s.dispatch(&Action::SendToScratchpad);
+        assert!(!s.workspaces[s.active_ws].panes.contains(&a));
+        assert_eq!(s.workspaces[s.scratchpad_ws].panes, [a]);
+
+        s.dispatch(&Action::ToggleScratchpad);
+        assert!(s.workspaces[s.active_ws].panes.contains(&a));
+        assert_eq!(s.focused_id(), Some(a));
+        // Floats over the tiled panes instead of taking a tile.
+        let (ids, rects) = s.layout_rects();
+        assert_eq!(ids.last(), Some(&a));
+        assert_eq!(*rects.last().unwrap(), s.scratch_rect());
+
+        s.dispatch(&Action::ToggleScratchpad);
+        assert!(!s.workspaces[s.active_ws].panes.contains(&a));
+    }
+
+    #[test]
+    fn focus_app_switches_workspace() {
+        let mut s = still_state();
+        let foot = s.assign_embedded("foot");
+        s.dispatch(&Action::MoveToWorkspace(3));
+        s.assign_embedded("mpv");
+        assert_eq!(s.workspace_of_app("foot"), Some(2));
+        assert!(s.focus_app("foot"));
+        assert_eq!((s.active_ws, s.focused_id()), (2, Some(foot)));
+        assert!(!s.focus_app("nope"));
+
+        // As an action it respects both locks.
+        s.dispatch(&Action::Workspace(1));
+        s.dispatch(&Action::ToggleFocusLock);
+        s.dispatch(&Action::FocusApp("foot".into()));
+        assert_eq!(s.active_ws, 0);
+        s.dispatch(&Action::ToggleFocusLock);
+        s.locked = true;
+        s.dispatch(&Action::FocusApp("foot".into()));
+        assert_eq!(s.active_ws, 0);
+        s.locked = false;
+        s.dispatch(&Action::FocusApp("foot".into()));
+        assert_eq!((s.active_ws, s.focused_id()), (2, Some(foot)));
+    }
+
+    #[test]
+    fn marks_follow_their_pane() {
+        let mut s = still_state();
+        let foot = s.assign_embedded("foot");
+        s.dispatch(&Action::Mark("term".into()));
+        s.dispatch(&Action::MoveMark("term".into(), 4));
+        assert_eq!(s.workspace_of_app("foot"), Some(3));
+        assert_eq!(s.active_ws, 0);
+
+        s.dispatch(&Action::Workspace(2));
+        s.dispatch(&Action::FocusMark("term".into()));
+        assert_eq!((s.active_ws, s.focused_id()), (3, Some(foot)));
+        s.dispatch(&Action::FocusMark("missing".into()));
+        assert_eq!(s.active_ws, 3);
+    }
+
+    /// Focus changes made outside `dispatch` reach the history on the next
+    /// frame.
+    fn frame(s: &mut TwmState) {
+        s.build_frame_cmds(8, 16, 640, 384);
+    }
+
+    #[test]
+    fn focus_last_returns_to_previous_pane() {
+        let mut s = still_state();
+        let foot = s.assign_embedded("foot");
+        frame(&mut s);
+        let mpv = s.assign_embedded("mpv");
+        frame(&mut s);
+        s.dispatch(&Action::MoveToWorkspace(2));
+        s.dispatch(&Action::Workspace(1));
+        assert_eq!(s.focused_id(), Some(foot));
+        assert_eq!(s.focus_history, [foot, mpv]);
+
+        s.dispatch(&Action::FocusLast);
+        assert_eq!((s.active_ws, s.focused_id()), (1, Some(mpv)));
+        s.dispatch(&Action::FocusLast);
+        assert_eq!((s.active_ws, s.focused_id()), (0, Some(foot)));
+
+        s.close_pane(mpv);
+        assert_eq!(s.focus_history, [foot]);
+        s.dispatch(&Action::FocusLast);
+        assert_eq!(s.focused_id(), Some(foot));
+    }
+
+    #[test]
+    fn focus_history_overflow_drops_oldest() {
+        let mut s = still_state();
+        s.focus_history_len = 3;
+        let ids: Vec<PaneId> = ["a", "b", "c", "d", "e"]
+            .into_iter()
+            .map(|app| {
+                let id = s.assign_embedded(app);
+                frame(&mut s);
+                id
+            })
+            .collect();
+        assert_eq!(s.focus_history, [ids[4], ids[3], ids[2]]);
+
+        // Refocusing moves an entry to the front instead of repeating it.
+        s.dispatch(&Action::FocusLast);
+        assert_eq!(s.focus_history, [ids[3], ids[4], ids[2]]);
+    }
+
+    #[test]
+    fn alt_tab_cycles_history_and_focuses_on_finish() {
+        let mut s = still_state();
+        let a = s.assign_embedded("a");
+        frame(&mut s);
+        let b = s.assign_embedded("b");
+        frame(&mut s);
+        let c = s.assign_embedded("c");
+        frame(&mut s);
+        assert_eq!(s.focus_history, [c, b, a]);
+
+        s.dispatch(&Action::AltTabNext);
+        s.dispatch(&Action::AltTabNext);
+        assert!(s.alt_tab_active());
+        // The popup doesn't move focus until it closes.
+        assert_eq!(s.focused_id(), Some(c));
+        frame(&mut s);
+        let popup: String = (0..80).map(|x| s.buf.get(x, 11).symbol()).collect();
+        assert!(popup.contains(" b "));
+
+        s.dispatch(&Action::AltTabPrev);
+        s.alt_tab_finish();
+        assert!(!s.alt_tab_active());
+        assert_eq!(s.focused_id(), Some(b));
+        assert_eq!(s.focus_history, [b, c, a]);
+    }
+
+    #[test]
+    fn sticky_pane_shows_on_every_workspace() {
+        let mut s = still_state();
+        s.assign_embedded("foot");
+        let mpv = s.assign_embedded("mpv");
+        frame(&mut s);
+        let rect = s.embedded_cell_rect("mpv").unwrap();
+
+        s.dispatch(&Action::ToggleSticky);
+        frame(&mut s);
+        assert_eq!(s.embedded_cell_rect("mpv"), Some(rect));
+        s.dispatch(&Action::Workspace(2));
+        assert_eq!(s.embedded_cell_rect("mpv"), Some(rect));
+        assert_eq!(s.embedded_cell_rect("foot"), None);
+        assert_eq!(s.all_embedded_cell_rects(), [("mpv".to_string(), rect)]);
+        let snap = TwmSnapshot::from_state(&s);
+        assert_eq!(snap.panes.last().map(|p| (p.id, p.rect)), Some((mpv, rect)));
+
+        s.dispatch(&Action::Workspace(1));
+        s.dispatch(&Action::ToggleSticky);
+        s.dispatch(&Action::Workspace(2));
+        assert_eq!(s.embedded_cell_rect("mpv"), None);
+    }
+
+    #[test]
+    fn super_nine_is_workspace_nine_not_the_scratchpad() {
+        let mut s = still_state();
+        s.open_shell_pane("a");
+        let a = s.focused_id().unwrap();
+        // What input.rs binds to Super+Shift+9 and Super+9.
+        s.dispatch(&Action::MoveToWorkspace(9));
+        assert!(s.workspaces[8].panes.contains(&a));
+        assert!(s.workspaces[s.scratchpad_ws].panes.is_empty());
+        s.dispatch(&Action::Workspace(9));
+        assert_eq!(s.active_ws, 8);
+        assert_ne!(s.active_ws, s.scratchpad_ws);
+        assert_eq!(s.focused_id(), Some(a));
+
+        // Nor can a number past nine reach it.
+        let pad = s.scratchpad_ws as u8 + 1;
+        s.dispatch(&Action::MoveToWorkspace(pad));
+        s.dispatch(&Action::Workspace(pad));
+        assert_eq!(s.active_ws, 8);
+        assert!(s.workspaces[s.scratchpad_ws].panes.is_empty());
+    }
+
+    #[test]
+    fn scratchpad_is_not_reachable_by_name_or_output() {
+        let mut s = still_state();
+        let pad = s.scratchpad_ws;
+        let name = s.workspaces[pad].name.clone();
+        s.dispatch(&Action::WorkspaceByName(name));
+        assert_eq!(s.active_ws, 0);
+
+        // Not even once a rule gives it a name of its own.
+        s.set_workspace_rules(&[WorkspaceRule {
+            index: pad as u8 + 1,
+            name: Some("notes".into()),
+            ..Default::default()
+        }]);
+        s.dispatch(&Action::WorkspaceByName("notes".into()));
+        assert_eq!(s.active_ws, 0);
+
+        s.assign_workspace_to_output(pad, "DP-2");
+        s.dispatch(&Action::FocusOutput("DP-2".into()));
+        assert_eq!(s.active_ws, 0);
+    }
+
+    #[test]
+    fn workspace_grid_wraps_in_all_directions() {
+        // Nine workspaces (the tenth is the scratchpad), three to a row:
+        //   1 2 3
+        //   4 5 6
+        //   7 8 9
+        let mut s = still_state();
+        let mut go = |a: Action| {
+            s.dispatch(&a);
+            s.active_ws + 1
+        };
+        assert_eq!(go(Action::WorkspaceLeft), 3);
+        assert_eq!(go(Action::WorkspaceRight), 1);
+        assert_eq!(go(Action::WorkspaceUp), 7);
+        assert_eq!(go(Action::WorkspaceDown), 1);
+        assert_eq!(go(Action::WorkspaceRight), 2);
+        assert_eq!(go(Action::WorkspaceDown), 5);
+        assert_eq!(go(Action::WorkspaceDown), 8);
+        assert_eq!(go(Action::WorkspaceRight), 9);
+        assert_eq!(go(Action::WorkspaceRight), 7);
+        assert_eq!(go(Action::WorkspaceLeft), 9);
+        assert_eq!(go(Action::WorkspaceDown), 3);
+        assert_eq!(go(Action::WorkspaceUp), 9);
+        assert_eq!(go(Action::WorkspaceUp), 6);
+    }
+
+    #[test]
+    fn grid_step_on_ragged_grids() {
+        // 0 1 2 3
+        // 4
+        assert_eq!(grid_step(4, 5, 4, 1, 0), 4);
+        assert_eq!(grid_step(4, 5, 4, 0, 1), 0);
+        assert_eq!(grid_step(3, 5, 4, 0, -1), 3);
+        assert_eq!(grid_step(0, 5, 4, -1, 0), 3);
+        // Wider than the grid: a single row.
+        assert_eq!(grid_step(0, 3, 9, -1, 0), 2);
+        assert_eq!(grid_step(1, 3, 9, 0, 1), 1);
+        assert_eq!(grid_step(0, 0, 3, 1, 0), 0);
+    }
+
+    #[test]
+    fn bar_tabs_follow_workspace_grid() {
+        let mut s = still_state();
+        frame(&mut s);
+        let row: String = (0..80).map(|x| s.buf.get(x, 23).symbol()).collect();
+        assert!(row.starts_with("  1 │ 2 │ 3 ┃ 4 │ 5 │ 6 ┃ 7 │ 8 │ 9 │"));
+
+        // A bar with a line per grid row stacks them.
+        s.bar_height = 3;
+        frame(&mut s);
+        let line = |s: &TwmState, y| {
+            (0..14)
+                .map(|x| s.buf.get(x, y).symbol())
+                .collect::<String>()
+        };
+        assert_eq!(line(&s, 21), "  1 │ 2 │ 3 │ ");
+        assert_eq!(line(&s, 22), "  4 │ 5 │ 6 │ ");
+        assert_eq!(line(&s, 23), "  7 │ 8 │ 9 │ ");
+        assert_eq!(s.click_at(2 * 8 + 2, 22 * 16 + 4), Some(4));
+        assert_eq!(s.click_at(6 * 8 + 2, 23 * 16 + 4), Some(8));
+        assert_eq!(s.click_at(10 * 8 + 2, 23 * 16 + 4), Some(9));
+        assert_eq!(s.click_at(14 * 8 + 2, 23 * 16 + 4), None);
+    }
+
+    #[test]
+    fn undo_close_reopens_closed_shell_panes() {
+        let mut s = still_state();
+        for t in ["a", "b", "c", "d"] {
+            s.open_shell_pane(t);
+        }
+        let before = s.workspaces[0].panes.len();
+        for _ in 0..3 {
+            s.dispatch(&Action::Close);
+        }
+        assert_eq!(s.workspaces[0].panes.len(), before - 3);
+
+        s.dispatch(&Action::UndoClose);
+        s.dispatch(&Action::UndoClose);
+        assert_eq!(s.workspaces[0].panes.len(), before - 1);
+        let label = |s: &TwmState| s.panes[&s.focused_id().unwrap()].content.label().to_owned();
+        assert_eq!(label(&s), "c");
+
+        // Embedded panes can't come back. mpv takes over c's pane.
+        s.assign_embedded("mpv");
+        s.dispatch(&Action::Close);
+        s.dispatch(&Action::UndoClose);
+        assert_eq!(label(&s), "d");
+        s.dispatch(&Action::UndoClose);
+        assert_eq!(s.workspaces[0].panes.len(), before - 1);
+    }
+
+    #[test]
+    fn second_mark_with_same_name_replaces_first() {
+        let mut s = still_state();
+        let foot = s.assign_embedded("foot");
+        s.dispatch(&Action::Mark("term".into()));
+        let mpv = s.assign_embedded("mpv");
+        s.dispatch(&Action::Mark("term".into()));
+        assert_eq!(s.pane_for_mark("term"), Some(mpv));
+        assert!(s.panes[&foot].marks.is_empty());
+        assert_eq!(s.marks(), [("term".to_string(), mpv, "mpv".to_string())]);
+
+        assert!(s.mark_app("foot", "term"));
+        assert!(!s.mark_app("nope", "term"));
+        assert_eq!(s.pane_for_mark("term"), Some(foot));
+    }
+
+    #[test]
+    fn rule_mark_waits_for_the_pane() {
+        let mut s = still_state();
+        // Rules run on the window's first commit, which can beat its pane.
+        assert!(!s.mark_app("foot", "term"));
+        assert_eq!(s.pane_for_mark("term"), None);
+        s.open_shell_pane("mpv");
+        assert_eq!(s.pane_for_mark("term"), None);
+        s.open_shell_pane("foot");
+        let foot = s.workspaces[s.active_ws].focused;
+        assert_eq!(s.pane_for_mark("term"), foot);
+
+        // Used up: a second foot pane stays unmarked.
+        s.open_shell_pane("foot");
+        assert_eq!(s.pane_for_mark("term"), foot);
+    }
+
+    #[test]
+    fn action_from_ipc_json() {
+        let a: Action = serde_json::from_str("\"NextLayout\"").unwrap();
+        assert_eq!(a, Action::NextLayout);
+        let a: Action = serde_json::from_str(r#"{"Workspace": 3}"#).unwrap();
+        assert_eq!(a, Action::Workspace(3));
+    }
+}
@@ -152,21 +152,34 @@ pub fn strip_comment(line: &str) -> &str {
 
 // ── Hex colour helpers ────────────────────────────────────────────────────────
 
-/// Parse `#RRGGBB` or `#RRGGBBAA` → `[f32; 4]`.
-/// Falls back to magenta on malformed input (with a tracing warning).
-pub fn hex4(s: &str) -> [f32; 4] {
-    let s = s.trim().trim_start_matches('#');
-    let p = |i: usize| u8::from_str_radix(&s[i..i + 2], 16).unwrap_or(0xFF) as f32 / 255.0;
+/// Parse `#RGB`, `#RRGGBB` or `#RRGGBBAA` → `[f32; 4]`, any case, `#`
+/// optional. Alpha is 1.0 unless given. None unless every digit is hex.
+pub fn try_hex4(s: &str) -> Option<[f32; 4]> {
+    let s = s.trim();
+    let s = s.strip_prefix('#').unwrap_or(s);
+    if !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let p = |i: usize| u8::from_str_radix(&s[i..i + 2], 16).unwrap_or(0) as f32 / 255.0;
+    // `#RGB` doubles each nibble: `F` → `FF`.
+    let n = |i: usize| u8::from_str_radix(&s[i..i + 1], 16).unwrap_or(0) as f32 * 17.0 / 255.0;
     match s.len() {
-        6 => [p(0), p(2), p(4), 1.0],
-        8 => [p(0), p(2), p(4), p(6)],
-        _ => {
-            tracing::warn!("Bad hex colour '#{s}' — using magenta");
-            [1.0, 0.0, 1.0, 1.0]
-        }
+        3 => Some([n(0), n(1), n(2), 1.0]),
+        6 => Some([p(0), p(2), p(4), 1.0]),
+        8 => Some([p(0), p(2), p(4), p(6)]),
+        _ => None,
     }
 }
 
+/// `try_hex4`, falling back to magenta on malformed input (with a tracing
+/// warning).
+pub fn hex4(s: &str) -> [f32; 4] {
+    try_hex4(s).unwrap_or_else(|| {
+        tracing::warn!("Bad hex colour '{}' — using magenta", s.trim());
+        [1.0, 0.0, 1.0, 1.0]
+    })
+}
+
 /// Parse `#RRGGBB` → `[u8; 3]`.
 /// Falls back to magenta on malformed input (with a tracing warning).
 pub fn hex3(s: &str) -> [u8; 3] {
@@ -313,6 +326,34 @@ mod tests {
         assert_eq!(c[3], 1.0);
     }
 
+    #[test]
+    fn hex4_rgb_expands_nibbles() {
+        assert_eq!(hex4("#F80"), hex4("#FF8800"));
+        assert_eq!(hex4("#fff"), [1.0, 1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn hex4_rrggbbaa_alpha() {
+        let c = hex4("#0D0D1480");
+        assert!((c[2] - 0x14 as f32 / 255.0).abs() < 1e-4);
+        assert!((c[3] - 0x80 as f32 / 255.0).abs() < 1e-4);
+        assert_eq!(hex4("#0D0D14FF")[3], 1.0);
+    }
+
+    #[test]
+    fn hex4_case_and_prefix() {
+        assert_eq!(hex4("#b4befe"), hex4("#B4BEFE"));
+        assert_eq!(hex4("B4BEFE"), hex4("#B4BEFE"));
+    }
+
+    #[test]
+    fn hex4_rejects_malformed() {
+        assert_eq!(try_hex4("#12345"), None);
+        assert_eq!(try_hex4("#GG0000"), None);
+        assert_eq!(try_hex4("#ÿÿÿ"), None);
+        assert_eq!(hex4("#GG0000"), [1.0, 0.0, 1.0, 1.0]);
+    }
+
     #[test]
     fn hex3_parse() {
         assert_eq!(hex3("#B4BEFE"), [0xB4, 0xBE, 0xFE]);